@@ -0,0 +1,168 @@
+//! Idle and user-activity detection
+//!
+//! The window reports native input (mouse, scroll, keys) with
+//! [`record_input`] and polls [`due`] periodically. Callbacks registered with
+//! `rasen.activity.onIdle(ms, cb)` fire once the user has been inactive for
+//! `ms`; `onActive(cb)` callbacks fire on the first input after an idle
+//! period: [`IDLE_AFTER`] without input, or the shortest `onIdle` timeout if
+//! that's shorter, whether or not any `onIdle` watcher is registered.
+//!
+//! Callbacks stay on the JS side in a table keyed by watcher ID; the native
+//! side only tracks timing and tells JS which IDs to call.
+
+use rquickjs::{Ctx, Function, Object};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::timers;
+
+/// Global holding the callbacks by watcher ID
+const CALLBACKS: &str = "__activityCallbacks";
+
+/// How long without input is an idle period for `onActive`, unless an
+/// `onIdle` timeout is shorter
+const IDLE_AFTER: Duration = Duration::from_secs(60);
+
+static STATE: Mutex<Option<ActivityState>> = Mutex::new(None);
+
+struct ActivityState {
+    last_input: Instant,
+    next_id: u64,
+    idle: Vec<IdleWatcher>,
+    active: Vec<u64>,
+}
+
+struct IdleWatcher {
+    id: u64,
+    timeout: Duration,
+    /// Fired since the last input; re-armed by the next input
+    fired: bool,
+}
+
+fn with_state<R>(f: impl FnOnce(&mut ActivityState) -> R) -> R {
+    let mut state = STATE.lock().unwrap();
+    let state = state.get_or_insert_with(|| ActivityState {
+        last_input: Instant::now(),
+        next_id: 1,
+        idle: Vec::new(),
+        active: Vec::new(),
+    });
+    f(state)
+}
+
+/// Note user input; returns the `onActive` watchers to call if the user was idle
+pub fn record_input() -> Vec<u64> {
+    with_state(|state| {
+        let idle_after = state.idle.iter().map(|w| w.timeout).fold(IDLE_AFTER, Duration::min);
+        let was_idle = state.last_input.elapsed() >= idle_after;
+        state.last_input = Instant::now();
+        for watcher in &mut state.idle {
+            watcher.fired = false;
+        }
+        if was_idle {
+            state.active.clone()
+        } else {
+            Vec::new()
+        }
+    })
+}
+
+/// `onIdle` watchers whose timeout has elapsed since the last input
+pub fn due() -> Vec<u64> {
+    with_state(|state| {
+        let inactive = state.last_input.elapsed();
+        state
+            .idle
+            .iter_mut()
+            .filter(|w| !w.fired && inactive >= w.timeout)
+            .map(|w| {
+                w.fired = true;
+                w.id
+            })
+            .collect()
+    })
+}
+
+/// Call the JS callbacks of the given watchers
+pub fn fire(ctx: &Ctx, ids: &[u64]) {
+    let Ok(callbacks) = ctx.globals().get::<_, Object>(CALLBACKS) else {
+        return;
+    };
+    for id in ids {
+        if let Ok(callback) = callbacks.get::<_, Function>(id.to_string()) {
+            if let Err(e) = callback.call::<_, ()>(()) {
+                eprintln!("Error in activity callback {}: {:?}", id, e);
+            }
+        }
+    }
+}
+
+/// Store a callback and return an unsubscribe function for it
+fn subscribe<'js>(ctx: &Ctx<'js>, id: u64, callback: Function<'js>) -> rquickjs::Result<Function<'js>> {
+    let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+    callbacks.set(id.to_string(), callback)?;
+
+    Function::new(ctx.clone(), move |ctx: Ctx<'js>| -> rquickjs::Result<()> {
+        with_state(|state| {
+            state.idle.retain(|w| w.id != id);
+            state.active.retain(|a| *a != id);
+        });
+        let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+        callbacks.remove(id.to_string())
+    })
+}
+
+/// Install `rasen.activity`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    ctx.globals().set(CALLBACKS, Object::new(ctx.clone())?)?;
+
+    let activity = Object::new(ctx.clone())?;
+    activity.set(
+        "onIdle",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, ms: f64, callback: Function<'js>| {
+            let id = with_state(|state| {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.idle.push(IdleWatcher {
+                    id,
+                    timeout: timers::delay_of(Some(ms)),
+                    fired: false,
+                });
+                id
+            });
+            subscribe(&ctx, id, callback)
+        })?,
+    )?;
+    activity.set(
+        "onActive",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, callback: Function<'js>| {
+            let id = with_state(|state| {
+                let id = state.next_id;
+                state.next_id += 1;
+                state.active.push(id);
+                id
+            });
+            subscribe(&ctx, id, callback)
+        })?,
+    )?;
+    rasen.set("activity", activity)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_active_after_idle_without_watchers() {
+        let id = with_state(|state| {
+            state.active.push(state.next_id);
+            state.next_id += 1;
+            state.next_id - 1
+        });
+        assert!(record_input().is_empty());
+
+        with_state(|state| state.last_input -= IDLE_AFTER);
+        assert_eq!(record_input(), vec![id]);
+        assert!(record_input().is_empty());
+    }
+}
@@ -0,0 +1,431 @@
+//! Transitions and built-in animations
+//!
+//! Re-renders replace the element tree wholesale, so transitions are driven
+//! natively: the [`TransitionDriver`] remembers the last resolved values per
+//! element id and, when they change, interpolates from the value on screen to
+//! the new one over the element's `duration-*` with its `ease-*` curve.
+//!
+//! GPUI applies `hover:`, `focus:` and `active:` styles at paint time, past
+//! the driver, so transitioning elements with such variants track their
+//! [`Interactions`] themselves and re-render as they change; their variants
+//! then transition like any other change. So do elements with `scale-*`,
+//! which can only be drawn at render time (see `elements::transform`).
+
+use gpui::*;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Tailwind's default transition duration
+pub const DEFAULT_DURATION: Duration = Duration::from_millis(150);
+
+/// Which properties a `transition-*` class animates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransitionProperty {
+    /// `transition-none`
+    None,
+    /// `transition`: colors, opacity and transforms
+    Default,
+    /// `transition-colors`
+    Colors,
+    /// `transition-opacity`
+    Opacity,
+    /// `transition-transform`
+    Transform,
+    /// `transition-all`
+    All,
+}
+
+impl TransitionProperty {
+    fn colors(self) -> bool {
+        matches!(self, Self::Default | Self::Colors | Self::All)
+    }
+
+    fn opacity(self) -> bool {
+        matches!(self, Self::Default | Self::Opacity | Self::All)
+    }
+
+    fn transform(self) -> bool {
+        matches!(self, Self::Default | Self::Transform | Self::All)
+    }
+}
+
+/// Timing function from `ease-*`, as Tailwind's cubic-bezier curves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    In,
+    Out,
+    InOut,
+}
+
+impl Easing {
+    /// Map linear progress (0..=1) onto the curve
+    pub fn apply(self, t: f32) -> f32 {
+        let (x1, y1, x2, y2) = match self {
+            Easing::Linear => return t,
+            Easing::In => (0.4, 0.0, 1.0, 1.0),
+            Easing::Out => (0.0, 0.0, 0.2, 1.0),
+            Easing::InOut => (0.4, 0.0, 0.2, 1.0),
+        };
+        let bezier = |p1: f32, p2: f32, s: f32| {
+            3.0 * p1 * s * (1.0 - s).powi(2) + 3.0 * p2 * s * s * (1.0 - s) + s.powi(3)
+        };
+
+        // x(s) is monotonic, so bisect for the curve parameter at time t
+        let (mut lo, mut hi) = (0.0, 1.0);
+        for _ in 0..20 {
+            let mid = (lo + hi) / 2.0;
+            if bezier(x1, x2, mid) < t {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        bezier(y1, y2, (lo + hi) / 2.0)
+    }
+}
+
+/// Built-in `animate-*` keyframes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Animate {
+    None,
+    Spin,
+    Ping,
+    Pulse,
+    Bounce,
+}
+
+/// Resolved transition settings for one element
+#[derive(Debug, Clone, Copy)]
+pub struct Transition {
+    pub property: TransitionProperty,
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+/// The animatable values of an element
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AnimatedValues {
+    pub background: Option<Hsla>,
+    pub text_color: Option<Hsla>,
+    pub border_color: Option<Hsla>,
+    pub opacity: Option<f32>,
+    pub translate_x: Option<Pixels>,
+    pub translate_y: Option<Pixels>,
+    pub scale: Option<f32>,
+}
+
+struct TransitionState {
+    transition: Transition,
+    from: AnimatedValues,
+    to: AnimatedValues,
+    start: Instant,
+}
+
+impl TransitionState {
+    fn progress(&self, now: Instant) -> f32 {
+        if self.transition.duration.is_zero() {
+            return 1.0;
+        }
+        let elapsed = now.saturating_duration_since(self.start).as_secs_f32();
+        (elapsed / self.transition.duration.as_secs_f32()).min(1.0)
+    }
+
+    fn current(&self, now: Instant) -> AnimatedValues {
+        let progress = self.progress(now);
+        if progress >= 1.0 {
+            return self.to;
+        }
+        let t = self.transition.easing.apply(progress);
+        let property = self.transition.property;
+
+        let color = |animate: bool, from: Option<Hsla>, to: Option<Hsla>| {
+            if animate { lerp_color(from, to, t) } else { to }
+        };
+        AnimatedValues {
+            background: color(property.colors(), self.from.background, self.to.background),
+            text_color: color(property.colors(), self.from.text_color, self.to.text_color),
+            border_color: color(property.colors(), self.from.border_color, self.to.border_color),
+            opacity: factor(property.opacity(), self.from.opacity, self.to.opacity, t),
+            translate_x: offset(property.transform(), self.from.translate_x, self.to.translate_x, t),
+            translate_y: offset(property.transform(), self.from.translate_y, self.to.translate_y, t),
+            scale: factor(property.transform(), self.from.scale, self.to.scale, t),
+        }
+    }
+}
+
+/// Interpolate an opacity or scale; a missing one is 1
+fn factor(animate: bool, from: Option<f32>, to: Option<f32>, t: f32) -> Option<f32> {
+    if !animate || from == to {
+        return to;
+    }
+    let (from, to) = (from.unwrap_or(1.0), to.unwrap_or(1.0));
+    Some(from + (to - from) * t)
+}
+
+/// Interpolate a translation; a missing one is no offset
+fn offset(animate: bool, from: Option<Pixels>, to: Option<Pixels>, t: f32) -> Option<Pixels> {
+    if !animate || from == to {
+        return to;
+    }
+    let (from, to) = (from.unwrap_or(px(0.0)), to.unwrap_or(px(0.0)));
+    Some(from + (to - from) * t)
+}
+
+/// Interpolate in RGB space; a missing color fades from/to transparent
+fn lerp_color(from: Option<Hsla>, to: Option<Hsla>, t: f32) -> Option<Hsla> {
+    let (from, to) = match (from, to) {
+        (Some(from), Some(to)) => (from, to),
+        (None, Some(to)) => (to.opacity(0.0), to),
+        (Some(from), None) => (from, from.opacity(0.0)),
+        (None, None) => return None,
+    };
+    let (a, b) = (Rgba::from(from), Rgba::from(to));
+    let mix = |x: f32, y: f32| x + (y - x) * t;
+    Some(
+        Rgba {
+            r: mix(a.r, b.r),
+            g: mix(a.g, b.g),
+            b: mix(a.b, b.b),
+            a: mix(a.a, b.a),
+        }
+        .into(),
+    )
+}
+
+/// Elements under the pointer and pressed, by element id, for the state
+/// variants of transitioning and scaled elements, and the natural sizes of
+/// scaled elements
+///
+/// Cheap to clone: clones share the same sets, so listeners can update them.
+#[derive(Clone, Default)]
+pub struct Interactions {
+    hovered: Rc<RefCell<HashSet<String>>>,
+    pressed: Rc<RefCell<HashSet<String>>>,
+    scaled: Rc<RefCell<HashMap<String, ScaledBox>>>,
+}
+
+/// A scaled element's size when unscaled, and the scale it's drawn at
+#[derive(Clone, Copy, Default)]
+struct ScaledBox {
+    size: Option<Size<Pixels>>,
+    scale: f32,
+}
+
+impl Interactions {
+    pub fn hovered(&self, id: &str) -> bool {
+        self.hovered.borrow().contains(id)
+    }
+
+    pub fn pressed(&self, id: &str) -> bool {
+        self.pressed.borrow().contains(id)
+    }
+
+    /// Record whether `id` is hovered; returns whether that changed
+    pub fn set_hovered(&self, id: &str, hovered: bool) -> bool {
+        set(&self.hovered, id, hovered)
+    }
+
+    /// Record whether `id` is pressed; returns whether that changed
+    pub fn set_pressed(&self, id: &str, pressed: bool) -> bool {
+        set(&self.pressed, id, pressed)
+    }
+
+    /// Record the scale `id` is drawn at this frame; returns its natural
+    /// size, or `None` until that's measured, in which case it's drawn unscaled
+    pub fn scale(&self, id: &str, scale: f32) -> Option<Size<Pixels>> {
+        let mut scaled = self.scaled.borrow_mut();
+        let scaled = scaled.entry(id.to_string()).or_default();
+        scaled.scale = if scaled.size.is_some() { scale } else { 1.0 };
+        scaled.size
+    }
+
+    /// Record the size `id` was laid out at, drawn at the scale recorded;
+    /// returns whether its natural size changed
+    pub fn measured(&self, id: &str, size: Size<Pixels>) -> bool {
+        let mut scaled = self.scaled.borrow_mut();
+        let Some(scaled) = scaled.get_mut(id).filter(|scaled| scaled.scale > 0.0) else {
+            return false;
+        };
+        let natural = size.map(|length| length / scaled.scale);
+        // Layout rounds to pixels: smaller changes would re-render forever
+        let changed = scaled.size.is_none_or(|known| {
+            (known.width - natural.width).abs() > px(0.5) || (known.height - natural.height).abs() > px(0.5)
+        });
+        if changed {
+            scaled.size = Some(natural);
+        }
+        changed
+    }
+
+    fn retain(&self, ids: &HashSet<String>) {
+        self.hovered.borrow_mut().retain(|id| ids.contains(id));
+        self.pressed.borrow_mut().retain(|id| ids.contains(id));
+        self.scaled.borrow_mut().retain(|id, _| ids.contains(id));
+    }
+}
+
+fn set(ids: &RefCell<HashSet<String>>, id: &str, on: bool) -> bool {
+    let mut ids = ids.borrow_mut();
+    if on {
+        ids.insert(id.to_string())
+    } else {
+        ids.remove(id)
+    }
+}
+
+/// Tracks in-flight transitions across renders
+///
+/// Call [`resolve`](Self::resolve) for each transitioning element while
+/// rendering and [`finish_frame`](Self::finish_frame) afterwards; it drops
+/// state for elements that are gone and reports whether another frame is needed.
+#[derive(Default)]
+pub struct TransitionDriver {
+    states: RefCell<HashMap<String, TransitionState>>,
+    seen: RefCell<HashSet<String>>,
+    animating: Cell<bool>,
+    interactions: Interactions,
+}
+
+impl TransitionDriver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hover and press states of transitioning and scaled elements
+    pub fn interactions(&self) -> Interactions {
+        self.interactions.clone()
+    }
+
+    /// Keep the interactions of a scaled element drawn this frame
+    pub fn keep(&self, id: &str) {
+        self.seen.borrow_mut().insert(id.to_string());
+    }
+
+    /// Values to draw for an element this frame
+    pub fn resolve(&self, id: &str, transition: Transition, target: AnimatedValues) -> AnimatedValues {
+        let now = Instant::now();
+        self.seen.borrow_mut().insert(id.to_string());
+
+        let mut states = self.states.borrow_mut();
+        let Some(state) = states.get_mut(id) else {
+            // First sighting: nothing to animate from
+            states.insert(id.to_string(), TransitionState {
+                transition,
+                from: target,
+                to: target,
+                start: now,
+            });
+            return target;
+        };
+
+        if state.to != target {
+            state.from = state.current(now);
+            state.to = target;
+            state.start = now;
+        }
+        state.transition = transition;
+
+        if state.progress(now) < 1.0 {
+            self.animating.set(true);
+        }
+        state.current(now)
+    }
+
+    /// End a render pass; returns true while any transition is still running
+    pub fn finish_frame(&self) -> bool {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.states.borrow_mut().retain(|id, _| seen.contains(id));
+        self.interactions.retain(&seen);
+        self.animating.replace(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing_endpoints() {
+        for easing in [Easing::Linear, Easing::In, Easing::Out, Easing::InOut] {
+            assert!(easing.apply(0.0).abs() < 1e-3);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-3);
+        }
+        assert!(Easing::In.apply(0.5) < 0.5);
+        assert!(Easing::Out.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn test_driver_starts_transition_on_change() {
+        let driver = TransitionDriver::new();
+        let transition = Transition {
+            property: TransitionProperty::Opacity,
+            duration: Duration::from_secs(60),
+            easing: Easing::Linear,
+        };
+        let visible = AnimatedValues { opacity: Some(1.0), ..Default::default() };
+        let hidden = AnimatedValues { opacity: Some(0.0), ..Default::default() };
+
+        assert_eq!(driver.resolve("a", transition, visible), visible);
+        assert!(!driver.finish_frame());
+
+        let current = driver.resolve("a", transition, hidden);
+        assert!(current.opacity.unwrap() > 0.9);
+        assert!(driver.finish_frame());
+
+        // Elements that weren't rendered are forgotten
+        assert!(!driver.finish_frame());
+        assert_eq!(driver.resolve("a", transition, visible), visible);
+    }
+
+    #[test]
+    fn test_translate_transitions() {
+        let driver = TransitionDriver::new();
+        let transition = Transition {
+            property: TransitionProperty::Transform,
+            duration: Duration::from_secs(60),
+            easing: Easing::Linear,
+        };
+        let rest = AnimatedValues::default();
+        let moved = AnimatedValues { translate_x: Some(px(8.0)), opacity: Some(0.5), ..Default::default() };
+
+        driver.resolve("a", transition, rest);
+        let current = driver.resolve("a", transition, moved);
+        assert!(current.translate_x.unwrap() < px(1.0));
+        // Only transforms transition
+        assert_eq!(current.opacity, Some(0.5));
+    }
+
+    #[test]
+    fn test_interactions_of_gone_elements_are_dropped() {
+        let driver = TransitionDriver::new();
+        let interactions = driver.interactions();
+        assert!(interactions.set_hovered("a", true));
+        assert!(!interactions.set_hovered("a", true));
+        assert!(interactions.set_pressed("b", true));
+
+        let transition = Transition {
+            property: TransitionProperty::Default,
+            duration: DEFAULT_DURATION,
+            easing: Easing::InOut,
+        };
+        driver.resolve("a", transition, AnimatedValues::default());
+        driver.finish_frame();
+        assert!(interactions.hovered("a"));
+        assert!(!interactions.pressed("b"));
+    }
+
+    #[test]
+    fn test_scaled_elements_keep_their_natural_size() {
+        let interactions = Interactions::default();
+        // Unmeasured elements are drawn unscaled
+        assert_eq!(interactions.scale("a", 0.5), None);
+        assert!(interactions.measured("a", size(px(100.0), px(40.0))));
+        assert_eq!(interactions.scale("a", 0.5), Some(size(px(100.0), px(40.0))));
+        // Measured at half size while drawn at half scale: unchanged
+        assert!(!interactions.measured("a", size(px(50.0), px(20.0))));
+        assert!(!interactions.measured("b", size(px(50.0), px(20.0))));
+    }
+}
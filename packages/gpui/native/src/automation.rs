@@ -0,0 +1,288 @@
+//! Scriptable automation over stdio
+//!
+//! With `rasen-gpui run --automation`, the runtime reads JSON-RPC 2.0 requests
+//! from stdin, one per line, and writes one response line per request to
+//! stdout. External test harnesses use it to inspect and drive the app:
+//!
+//! - `dumpTree` returns the current element tree
+//! - `click { testId }` invokes the click handler of the element with that `testId`
+//! - `setRef { name, value }` assigns a ref exposed with `rasen.automation.expose`
+//! - `logs { since }` returns `console` messages after sequence number `since`
+//!   (default 0), oldest first, as `{ seq, level, message }`
+//! - `handlerStats` returns how often each live handler ran and how long it
+//!   took, the most time taken first, as `{ id, name, calls, totalMs, longestMs }`
+//! - `screenshot { path }` writes a PNG of the window to `path`. GPUI can't
+//!   read frames back, so the window's area of the screen is captured with
+//!   the platform's screenshot tool (see [`screenshot`])
+
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use gpui::{Bounds, Pixels};
+use rquickjs::{Ctx, Function, Object};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use crate::elements::Element;
+use crate::event_manager::HandlerStats;
+
+/// JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const SERVER_ERROR: i64 = -32000;
+
+/// Global holding the refs registered with `rasen.automation.expose`
+const EXPOSED_REFS: &str = "__automationRefs";
+
+/// A JSON-RPC request read from stdin
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC error response
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// Read requests from stdin on a background thread
+///
+/// Malformed lines are answered with a parse error right away; everything
+/// else is handed to the app through the returned channel.
+pub fn listen() -> UnboundedReceiver<Request> {
+    let (tx, rx) = mpsc::unbounded();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Request>(&line) {
+                Ok(request) => {
+                    if tx.unbounded_send(request).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => respond(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+            }
+        }
+    });
+    rx
+}
+
+/// Write a response line to stdout
+pub fn respond(id: Value, result: Result<Value, RpcError>) {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    };
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", response);
+    let _ = stdout.flush();
+}
+
+/// Capture the screen area `bounds` (in logical pixels, `scale` device
+/// pixels each) as a PNG at `path`
+///
+/// Windows in front of the app show in the capture. Uses `screencapture` on
+/// macOS (the terminal or app needs the Screen Recording permission),
+/// PowerShell on Windows, and `grim` on Wayland or ImageMagick's `import`
+/// on X11.
+pub fn screenshot(bounds: Bounds<Pixels>, scale: f32, path: &Path) -> Result<(), RpcError> {
+    let (x, y, width, height) = region(bounds, 1.0);
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("screencapture");
+        command.arg("-x").arg(format!("-R{},{},{},{}", x, y, width, height)).arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        // CopyFromScreen works in device pixels
+        let (x, y, width, height) = region(bounds, scale);
+        let script = format!(
+            "Add-Type -AssemblyName System.Drawing; \
+             $bitmap = New-Object System.Drawing.Bitmap {}, {}; \
+             [System.Drawing.Graphics]::FromImage($bitmap).CopyFromScreen({}, {}, 0, 0, $bitmap.Size); \
+             $bitmap.Save('{}', [System.Drawing.Imaging.ImageFormat]::Png)",
+            width,
+            height,
+            x,
+            y,
+            path.display().to_string().replace('\'', "''"),
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &script]);
+        command
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let mut command = Command::new("grim");
+        command.arg("-g").arg(format!("{},{} {}x{}", x, y, width, height)).arg(path);
+        command
+    } else {
+        let (x, y, width, height) = region(bounds, scale);
+        let mut command = Command::new("import");
+        command.args(["-window", "root", "-crop"]).arg(format!("{}x{}+{}+{}", width, height, x, y)).arg(path);
+        command
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| RpcError::new(SERVER_ERROR, format!("Can't run {} for the screenshot: {}", program, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(RpcError::new(
+            SERVER_ERROR,
+            format!("{} failed to take the screenshot: {}", program, stderr.trim()),
+        ));
+    }
+    Ok(())
+}
+
+/// Whole pixels covering `bounds` at `scale`, as `(x, y, width, height)`
+fn region(bounds: Bounds<Pixels>, scale: f32) -> (i32, i32, u32, u32) {
+    let x = (f32::from(bounds.origin.x) * scale).floor();
+    let y = (f32::from(bounds.origin.y) * scale).floor();
+    let right = (f32::from(bounds.origin.x + bounds.size.width) * scale).ceil();
+    let bottom = (f32::from(bounds.origin.y + bounds.size.height) * scale).ceil();
+    (x as i32, y as i32, (right - x).max(1.0) as u32, (bottom - y).max(1.0) as u32)
+}
+
+/// Serialize the element tree for `dumpTree`
+///
+/// Built bottom-up from a pre-order listing rather than recursively, like
+/// `ElementDescriptor::into_element`.
+pub fn dump(root: &Element) -> Value {
+    let mut nodes: Vec<(Option<usize>, &Element)> = Vec::new();
+    let mut stack = vec![(None, root)];
+    while let Some((parent, element)) = stack.pop() {
+        let index = nodes.len();
+        if let Element::Div(div) = element {
+            stack.extend(div.children.iter().rev().map(|child| (Some(index), child)));
+        }
+        nodes.push((parent, element));
+    }
+    
+    let mut children: Vec<Vec<Value>> = nodes.iter().map(|_| Vec::new()).collect();
+    let mut dumped = Value::Null;
+    for (index, (parent, element)) in nodes.into_iter().enumerate().rev() {
+        let value = match element {
+            Element::Div(div) => {
+                let mut handlers = Vec::new();
+                if div.handlers.on_click.is_some() {
+                    handlers.push("click");
+                }
+                if div.handlers.on_mouse_enter.is_some() {
+                    handlers.push("mouseenter");
+                }
+                if div.handlers.on_mouse_leave.is_some() {
+                    handlers.push("mouseleave");
+                }
+                if div.handlers.on_mouse_down.is_some() {
+                    handlers.push("mousedown");
+                }
+                if div.handlers.on_mouse_move.is_some() {
+                    handlers.push("mousemove");
+                }
+                if div.handlers.on_mouse_up.is_some() {
+                    handlers.push("mouseup");
+                }
+                let mut div_children = std::mem::take(&mut children[index]);
+                div_children.reverse();
+                json!({
+                    "type": "div",
+                    "id": div.id,
+                    "testId": div.test_id,
+                    "handlers": handlers,
+                    "children": div_children,
+                })
+            }
+            Element::Text(text) => json!({
+                "type": "text",
+                "id": text.id,
+                "testId": text.test_id,
+                "text": text.text,
+            }),
+        };
+        match parent {
+            Some(parent) => children[parent].push(value),
+            None => dumped = value,
+        }
+    }
+    dumped
+}
+
+/// Find the first element (pre-order) with the given `testId`
+pub fn find<'a>(root: &'a Element, test_id: &str) -> Option<&'a Element> {
+    let mut stack = vec![root];
+    while let Some(element) = stack.pop() {
+        let (id, children) = match element {
+            Element::Div(div) => (&div.test_id, div.children.as_slice()),
+            Element::Text(text) => (&text.test_id, &[][..]),
+        };
+        if id.as_deref() == Some(test_id) {
+            return Some(element);
+        }
+        stack.extend(children.iter().rev());
+    }
+    None
+}
+
+/// Serialize handler call statistics for `handlerStats`
+pub fn handler_stats(stats: &[HandlerStats]) -> Value {
+    let millis = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+    stats
+        .iter()
+        .map(|stats| {
+            json!({
+                "id": stats.id,
+                "name": stats.name,
+                "calls": stats.calls,
+                "totalMs": millis(stats.total),
+                "longestMs": millis(stats.longest),
+            })
+        })
+        .collect()
+}
+
+/// Assign a JSON value to an exposed ref, going through its `value` setter
+pub fn set_ref(ctx: &Ctx, name: &str, value: &Value) -> Result<(), RpcError> {
+    let server_error = |e: rquickjs::Error| RpcError::new(SERVER_ERROR, e.to_string());
+
+    let refs: Object = ctx.globals().get(EXPOSED_REFS).map_err(server_error)?;
+    let target: Option<Object> = refs.get(name).map_err(server_error)?;
+    let target = target.ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("No exposed ref named '{}'", name)))?;
+
+    let value = ctx.json_parse(value.to_string()).map_err(server_error)?;
+    target.set("value", value).map_err(server_error)
+}
+
+/// Install `rasen.automation`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    ctx.globals().set(EXPOSED_REFS, Object::new(ctx.clone())?)?;
+
+    let automation = Object::new(ctx.clone())?;
+    automation.set(
+        "expose",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, name: String, target: Object<'js>| {
+            let refs: Object = ctx.globals().get(EXPOSED_REFS)?;
+            refs.set(name, target)
+        })?,
+    )?;
+    rasen.set("automation", automation)?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Built-in `path` and `os` modules
+//!
+//! Registered in `__modules` (also as `node:path` and `node:os`) before any
+//! script runs, so `import { join } from 'path'` works in the entry script
+//! and in bundled modules alike. They cover the part of Node's modules that
+//! apps need to build file paths for `rasen.fs` portably, using the
+//! platform's separator.
+
+use rquickjs::function::{Opt, Rest};
+use rquickjs::{Ctx, Function, Object};
+use std::env;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR};
+
+fn is_separator(c: char) -> bool {
+    c == '/' || (cfg!(windows) && c == '\\')
+}
+
+/// `path` with `.` removed and `..` folded into its parent, without touching
+/// the file system; `.` if nothing is left
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut parts: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match parts.last() {
+                Some(Component::Normal(_)) => {
+                    parts.pop();
+                }
+                // `..` at the root is the root
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => parts.push(component),
+            },
+            other => parts.push(other),
+        }
+    }
+    if parts.is_empty() {
+        return PathBuf::from(".");
+    }
+    parts.into_iter().collect()
+}
+
+/// Join segments with the platform separator and normalize the result
+pub fn join(parts: &[String]) -> String {
+    let joined: Vec<&str> = parts.iter().map(String::as_str).filter(|part| !part.is_empty()).collect();
+    normalize(Path::new(&joined.join(MAIN_SEPARATOR_STR))).to_string_lossy().into_owned()
+}
+
+/// Absolute path of the segments, resolved right to left against `base`:
+/// an absolute segment discards the ones before it
+pub fn resolve(parts: &[String], base: &Path) -> String {
+    let path = parts.iter().filter(|part| !part.is_empty()).fold(base.to_path_buf(), |path, part| path.join(part));
+    normalize(&path).to_string_lossy().into_owned()
+}
+
+/// Last segment of `path`, with `ext` removed if it ends with it
+pub fn basename<'a>(path: &'a str, ext: Option<&str>) -> &'a str {
+    let name = path.trim_end_matches(is_separator).rsplit(is_separator).next().unwrap_or_default();
+    match ext.and_then(|ext| name.strip_suffix(ext)) {
+        Some(stem) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+/// Extension of the last segment, from its last `.`; empty for names
+/// without one and for dotfiles like `.env`
+pub fn extname(path: &str) -> &str {
+    let name = basename(path, None);
+    match name.rfind('.') {
+        Some(0) | None => "",
+        _ if name == ".." => "",
+        Some(index) => &name[index..],
+    }
+}
+
+/// The user's home directory
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+/// Platform name as Node reports it, so code written for Node keeps working
+pub fn platform() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "darwin"
+    } else if cfg!(target_os = "windows") {
+        "win32"
+    } else {
+        env::consts::OS
+    }
+}
+
+/// Register `path` and `os` in `__modules`
+pub fn install<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+    let modules: Object = ctx.globals().get("__modules")?;
+
+    let path = Object::new(ctx.clone())?;
+    path.set("sep", MAIN_SEPARATOR_STR)?;
+    path.set("join", Function::new(ctx.clone(), |parts: Rest<String>| join(&parts.0))?)?;
+    path.set(
+        "resolve",
+        Function::new(ctx.clone(), |parts: Rest<String>| resolve(&parts.0, &env::current_dir().unwrap_or_default()))?,
+    )?;
+    path.set(
+        "basename",
+        Function::new(ctx.clone(), |path: String, ext: Opt<String>| basename(&path, ext.0.as_deref()).to_string())?,
+    )?;
+    path.set("extname", Function::new(ctx.clone(), |path: String| extname(&path).to_string())?)?;
+    modules.set("path", path.clone())?;
+    modules.set("node:path", path)?;
+
+    let os = Object::new(ctx.clone())?;
+    os.set("platform", Function::new(ctx.clone(), platform)?)?;
+    os.set(
+        "homedir",
+        Function::new(ctx.clone(), || home_dir().unwrap_or_default().to_string_lossy().into_owned())?,
+    )?;
+    os.set("tmpdir", Function::new(ctx.clone(), || env::temp_dir().to_string_lossy().into_owned())?)?;
+    modules.set("os", os.clone())?;
+    modules.set("node:os", os)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|part| part.to_string()).collect()
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_join_and_resolve() {
+        assert_eq!(join(&parts(&["a", "b/../c", "./d.txt"])), "a/c/d.txt");
+        assert_eq!(join(&parts(&["/a", "", "/b"])), "/a/b");
+        assert_eq!(join(&parts(&["..", "a"])), "../a");
+        assert_eq!(join(&parts(&["/", ".."])), "/");
+        assert_eq!(join(&[]), ".");
+        let base = Path::new("/home/user");
+        assert_eq!(resolve(&parts(&["docs", "a.txt"]), base), "/home/user/docs/a.txt");
+        assert_eq!(resolve(&parts(&["docs", "/etc", "../tmp"]), base), "/tmp");
+        assert_eq!(resolve(&[], base), "/home/user");
+    }
+
+    #[test]
+    fn test_basename_and_extname() {
+        assert_eq!(basename("/a/b/file.tar.gz", None), "file.tar.gz");
+        assert_eq!(basename("/a/b/", None), "b");
+        assert_eq!(basename("file.txt", Some(".txt")), "file");
+        assert_eq!(basename(".txt", Some(".txt")), ".txt");
+        assert_eq!(extname("/a/file.tar.gz"), ".gz");
+        assert_eq!(extname("file."), ".");
+        assert_eq!(extname("/a/.env"), "");
+        assert_eq!(extname("/a/b"), "");
+        assert_eq!(extname(".."), "");
+    }
+}
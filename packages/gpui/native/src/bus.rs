@@ -0,0 +1,200 @@
+//! App-wide message bus
+//!
+//! `rasen.bus.emit(topic, payload)` serializes the payload to JSON and queues
+//! it for every JS runtime in the process with a listener for the topic,
+//! including the sender's own. Each runtime registers an endpoint when it is
+//! created and drains its inbox when woken, so delivery is always
+//! asynchronous, like `postMessage`. Messages are numbered from a single
+//! process-wide counter, so every runtime sees them in the same order.
+//!
+//! Like `activity`, the callbacks stay on the JS side in a table keyed by
+//! listener ID; the native side tracks topics and queues messages.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rquickjs::{Ctx, Function, Object, Value};
+use std::sync::Mutex;
+
+/// Global holding the listeners by ID
+const LISTENERS: &str = "__busListeners";
+
+/// Identifies a JS runtime's endpoint on the bus
+pub type RuntimeId = u64;
+
+static STATE: Mutex<BusState> = Mutex::new(BusState {
+    next_seq: 1,
+    next_id: 1,
+    endpoints: Vec::new(),
+});
+
+struct BusState {
+    next_seq: u64,
+    /// Shared by endpoints and listeners
+    next_id: u64,
+    endpoints: Vec<Endpoint>,
+}
+
+struct Endpoint {
+    id: RuntimeId,
+    /// Listener IDs and their topics
+    listeners: Vec<(u64, String)>,
+    inbox: Vec<Message>,
+    wake: UnboundedSender<()>,
+}
+
+/// A message waiting for delivery
+#[derive(Debug)]
+pub struct Message {
+    pub seq: u64,
+    pub topic: String,
+    /// JSON, or `None` for an `undefined` payload
+    pub payload: Option<String>,
+}
+
+/// Add an endpoint for a new runtime; the receiver wakes when messages arrive
+pub fn register() -> (RuntimeId, UnboundedReceiver<()>) {
+    let (wake, wakeups) = unbounded();
+    let mut state = STATE.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.endpoints.push(Endpoint { id, listeners: Vec::new(), inbox: Vec::new(), wake });
+    (id, wakeups)
+}
+
+/// Remove a runtime's endpoint, dropping undelivered messages
+pub fn unregister(id: RuntimeId) {
+    STATE.lock().unwrap().endpoints.retain(|endpoint| endpoint.id != id);
+}
+
+/// Queue a message for every endpoint listening to `topic`; returns its sequence number
+pub fn emit(topic: &str, payload: Option<String>) -> u64 {
+    let mut state = STATE.lock().unwrap();
+    let seq = state.next_seq;
+    state.next_seq += 1;
+    for endpoint in &mut state.endpoints {
+        if endpoint.listeners.iter().any(|(_, t)| t == topic) {
+            endpoint.inbox.push(Message { seq, topic: topic.to_string(), payload: payload.clone() });
+            let _ = endpoint.wake.unbounded_send(());
+        }
+    }
+    seq
+}
+
+/// Call the runtime's listeners for its queued messages; returns whether any ran
+pub fn deliver(ctx: &Ctx, id: RuntimeId) -> bool {
+    let (messages, listeners) = {
+        let mut state = STATE.lock().unwrap();
+        let Some(endpoint) = state.endpoints.iter_mut().find(|endpoint| endpoint.id == id) else {
+            return false;
+        };
+        (std::mem::take(&mut endpoint.inbox), endpoint.listeners.clone())
+    };
+    let Ok(callbacks) = ctx.globals().get::<_, Object>(LISTENERS) else {
+        return false;
+    };
+
+    let mut delivered = false;
+    for message in messages {
+        let payload = match &message.payload {
+            Some(json) => ctx.json_parse(json.as_str()).unwrap_or_else(|_| Value::new_undefined(ctx.clone())),
+            None => Value::new_undefined(ctx.clone()),
+        };
+        for (listener, _) in listeners.iter().filter(|(_, topic)| *topic == message.topic) {
+            // Unsubscribed while earlier messages were being delivered
+            let Ok(callback) = callbacks.get::<_, Function>(listener.to_string()) else {
+                continue;
+            };
+            let meta = Object::new(ctx.clone()).and_then(|meta| {
+                meta.set("topic", message.topic.as_str())?;
+                meta.set("seq", message.seq)?;
+                Ok(meta)
+            });
+            if let Err(e) = meta.and_then(|meta| callback.call::<_, ()>((payload.clone(), meta))) {
+                eprintln!("Error in bus listener for '{}': {:?}", message.topic, e);
+            }
+            delivered = true;
+        }
+    }
+    delivered
+}
+
+/// Install `rasen.bus` for the runtime with endpoint `id`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, id: RuntimeId) -> rquickjs::Result<()> {
+    ctx.globals().set(LISTENERS, Object::new(ctx.clone())?)?;
+
+    let bus = Object::new(ctx.clone())?;
+    bus.set(
+        "emit",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, topic: String, payload: Value<'js>| -> rquickjs::Result<u64> {
+            let payload = ctx.json_stringify(payload)?.map(|json| json.to_string()).transpose()?;
+            Ok(emit(&topic, payload))
+        })?,
+    )?;
+    bus.set(
+        "on",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, topic: String, callback: Function<'js>| {
+            let listener = {
+                let mut state = STATE.lock().unwrap();
+                let listener = state.next_id;
+                state.next_id += 1;
+                if let Some(endpoint) = state.endpoints.iter_mut().find(|endpoint| endpoint.id == id) {
+                    endpoint.listeners.push((listener, topic));
+                }
+                listener
+            };
+            let callbacks: Object = ctx.globals().get(LISTENERS)?;
+            callbacks.set(listener.to_string(), callback)?;
+
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>| -> rquickjs::Result<()> {
+                if let Some(endpoint) = STATE.lock().unwrap().endpoints.iter_mut().find(|endpoint| endpoint.id == id) {
+                    endpoint.listeners.retain(|(l, _)| *l != listener);
+                }
+                let callbacks: Object = ctx.globals().get(LISTENERS)?;
+                callbacks.remove(listener.to_string())
+            })
+        })?,
+    )?;
+    rasen.set("bus", bus)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rquickjs::{Context, Runtime};
+
+    #[test]
+    fn test_subscribe_and_unsubscribe() {
+        let (id, mut wakeups) = register();
+        let (other, _) = register();
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            let rasen = Object::new(ctx.clone()).unwrap();
+            install(&ctx, &rasen, id).unwrap();
+            ctx.globals().set("rasen", rasen).unwrap();
+            ctx.eval::<(), _>(
+                "var received = [];
+                 var off = rasen.bus.on('test-subscribe', (payload, meta) => received.push([payload, meta.topic]));",
+            )
+            .unwrap();
+
+            // Delivered on the next wakeup, not during `emit`
+            ctx.eval::<(), _>("rasen.bus.emit('test-subscribe', { n: 1 }); rasen.bus.emit('test-other', 2);").unwrap();
+            assert_eq!(ctx.eval::<usize, _>("received.length").unwrap(), 0);
+            assert!(wakeups.try_next().is_ok());
+            assert!(deliver(&ctx, id));
+            assert_eq!(
+                ctx.eval::<String, _>("JSON.stringify(received)").unwrap(),
+                r#"[[{"n":1},"test-subscribe"]]"#
+            );
+            // Runtimes without a listener for the topic get nothing
+            assert!(!deliver(&ctx, other));
+
+            ctx.eval::<(), _>("off(); rasen.bus.emit('test-subscribe', 3);").unwrap();
+            assert!(!deliver(&ctx, id));
+            assert_eq!(ctx.eval::<usize, _>("received.length").unwrap(), 1);
+        });
+        unregister(id);
+        unregister(other);
+    }
+}
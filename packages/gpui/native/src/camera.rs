@@ -0,0 +1,266 @@
+//! Webcam capture for the `camera` element and `rasen.camera`
+//!
+//! Each device is read by a capture thread through nokhwa, started when a
+//! `camera` element first shows it or `snapshot` asks for it. The thread
+//! keeps the latest frame as a BGRA image GPUI can draw, and stops once
+//! nothing has used the device for [`LINGER`], so switching views doesn't
+//! reopen it. `camera` elements draw the latest frame on every animation
+//! frame while they're on screen.
+//!
+//! `rasen.camera.snapshot(deviceId?)` resolves with the PNG bytes of the
+//! next frame, and `rasen.camera.list()` with the devices found. A device
+//! that fails to open is retried after [`RETRY`].
+
+use gpui::*;
+use image::RgbaImage;
+use nokhwa::pixel_format::RgbAFormat;
+use nokhwa::utils::{ApiBackend, CameraIndex, RequestedFormat, RequestedFormatType};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Function, Object};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::files::{Files, FsError};
+use crate::{native_api, values};
+
+/// How long a device stays open after it was last shown or snapshotted
+const LINGER: Duration = Duration::from_secs(2);
+
+/// How long a device that failed to open is left alone
+const RETRY: Duration = Duration::from_secs(5);
+
+/// How long `snapshot` waits for a frame
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the platform has a capture backend
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "windows", target_os = "linux"));
+
+/// Open devices by device ID, `""` for the default one
+static STREAMS: Mutex<BTreeMap<String, Arc<Stream>>> = Mutex::new(BTreeMap::new());
+
+/// A device being read by its capture thread
+struct Stream {
+    state: Mutex<StreamState>,
+    /// Notified on every new frame and when capture stops
+    frames: Condvar,
+}
+
+struct StreamState {
+    /// Latest frame, in BGRA
+    frame: Option<Arc<RenderImage>>,
+    /// Frame drawn last, freed from the sprite atlas once superseded
+    shown: Option<Arc<RenderImage>>,
+    used: Instant,
+    /// Why capture stopped, and when
+    failed: Option<(String, Instant)>,
+}
+
+/// Props of a `camera` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraView {
+    /// `None` for the default device
+    pub device: Option<String>,
+    pub fit: ObjectFit,
+}
+
+/// A capture device, as `list()` reports it
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    /// What `deviceId` takes
+    pub device_id: String,
+    pub label: String,
+}
+
+/// The stream of `device`, started if it isn't running
+fn stream(device: Option<&str>) -> Arc<Stream> {
+    let key = device.unwrap_or_default().to_string();
+    let mut streams = STREAMS.lock().unwrap();
+    if let Some(stream) = streams.get(&key) {
+        let mut state = stream.state.lock().unwrap();
+        let retry = state.failed.as_ref().is_some_and(|(_, at)| at.elapsed() >= RETRY);
+        if !retry {
+            state.used = Instant::now();
+            return stream.clone();
+        }
+    }
+    let stream = Arc::new(Stream {
+        state: Mutex::new(StreamState { frame: None, shown: None, used: Instant::now(), failed: None }),
+        frames: Condvar::new(),
+    });
+    streams.insert(key.clone(), stream.clone());
+    let capturing = stream.clone();
+    thread::spawn(move || capture(key, capturing));
+    stream
+}
+
+/// Device index for a device ID: an index, or a platform ID from `list()`
+fn index(device: &str) -> CameraIndex {
+    match device.parse() {
+        Ok(index) => CameraIndex::Index(index),
+        Err(_) => CameraIndex::String(device.to_string()),
+    }
+}
+
+/// Read frames from device `key` until it's no longer used
+fn capture(key: String, stream: Arc<Stream>) {
+    // Asks for camera access the first time
+    #[cfg(target_os = "macos")]
+    nokhwa::nokhwa_initialize(|_| {});
+    let device = if key.is_empty() { CameraIndex::Index(0) } else { index(&key) };
+    let captured = (|| -> Result<(), nokhwa::NokhwaError> {
+        let format = RequestedFormat::new::<RgbAFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
+        let mut camera = nokhwa::Camera::new(device, format)?;
+        camera.open_stream()?;
+        // Opening can take longer than `LINGER`: read at least one frame
+        loop {
+            let decoded = camera.frame()?.decode_image::<RgbAFormat>()?;
+            let (width, height) = decoded.dimensions();
+            let Some(mut pixels) = RgbaImage::from_raw(width, height, decoded.into_raw()) else {
+                continue;
+            };
+            for pixel in pixels.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+            let frame = Arc::new(RenderImage::new([image::Frame::new(pixels)]));
+            let used = {
+                let mut state = stream.state.lock().unwrap();
+                state.frame = Some(frame);
+                state.used
+            };
+            stream.frames.notify_all();
+            if used.elapsed() >= LINGER {
+                break;
+            }
+        }
+        camera.stop_stream()
+    })();
+
+    let mut streams = STREAMS.lock().unwrap();
+    match captured {
+        Ok(()) => {
+            if streams.get(&key).is_some_and(|current| Arc::ptr_eq(current, &stream)) {
+                streams.remove(&key);
+            }
+        }
+        // Failed streams stay listed, so they aren't reopened on every frame
+        Err(e) => {
+            eprintln!("Warning: camera '{}' stopped: {}", key, e);
+            stream.state.lock().unwrap().failed = Some((e.to_string(), Instant::now()));
+        }
+    }
+    stream.frames.notify_all();
+}
+
+/// The PNG bytes of the next frame of `device`, blocking
+pub fn snapshot(device: Option<&str>) -> Result<Vec<u8>, FsError> {
+    let failed = |message: String| FsError { code: "EIO", message };
+    let stream = stream(device);
+    // A frame captured after the call, not the one on screen
+    let previous = stream.state.lock().unwrap().frame.clone();
+    let fresh = |state: &StreamState| match (&state.frame, &previous) {
+        (Some(frame), Some(previous)) => !Arc::ptr_eq(frame, previous),
+        (frame, None) => frame.is_some(),
+        (None, Some(_)) => false,
+    };
+    let (state, _) = stream
+        .frames
+        .wait_timeout_while(stream.state.lock().unwrap(), SNAPSHOT_TIMEOUT, |state| {
+            state.failed.is_none() && !fresh(state)
+        })
+        .unwrap();
+    if let Some((message, _)) = &state.failed {
+        return Err(failed(format!("Can't capture from the camera: {}", message)));
+    }
+    let frame = state.frame.clone().ok_or_else(|| failed("The camera sent no frame".to_string()))?;
+    drop(state);
+
+    let size = frame.size(0);
+    let mut pixels = RgbaImage::from_raw(size.width.0 as u32, size.height.0 as u32, frame.as_bytes(0).unwrap_or_default().to_vec())
+        .ok_or_else(|| failed("The camera frame has no pixel data".to_string()))?;
+    for pixel in pixels.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+    let mut png = Vec::new();
+    pixels
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| failed(format!("Can't encode the snapshot: {}", e)))?;
+    Ok(png)
+}
+
+/// The capture devices found
+fn list() -> Vec<DeviceInfo> {
+    match nokhwa::query(ApiBackend::Auto) {
+        Ok(devices) => devices
+            .into_iter()
+            .map(|device| DeviceInfo { device_id: device.index().as_string(), label: device.human_name() })
+            .collect(),
+        Err(e) => {
+            eprintln!("Warning: can't list cameras: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// The latest frame of a `camera` element's device, filling the element
+pub fn render(view: &CameraView) -> AnyElement {
+    let stream = stream(view.device.as_deref());
+    let fit = view.fit;
+    canvas(
+        |_, window, _| window.request_animation_frame(),
+        move |bounds, _, window, cx| {
+            let (frame, superseded) = {
+                let mut state = stream.state.lock().unwrap();
+                state.used = Instant::now();
+                let Some(frame) = state.frame.clone() else {
+                    return;
+                };
+                let superseded = match &state.shown {
+                    Some(shown) if Arc::ptr_eq(shown, &frame) => None,
+                    _ => state.shown.replace(frame.clone()),
+                };
+                (frame, superseded)
+            };
+            if let Some(old) = superseded {
+                cx.drop_image(old, Some(window));
+            }
+            let bounds = fit.get_bounds(bounds, frame.size(0));
+            if let Err(e) = window.paint_image(bounds, Corners::default(), frame, 0, false) {
+                eprintln!("Warning: can't draw camera frame: {}", e);
+            }
+        },
+    )
+    .size_full()
+    .into_any_element()
+}
+
+/// Install `rasen.camera`, queueing snapshots on the runtime's file queue
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, files: &Files) -> rquickjs::Result<()> {
+    let namespace = native_api::namespace(ctx, rasen, "camera")?;
+
+    let queue = files.clone();
+    namespace.set(
+        "snapshot",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, device: Opt<Option<String>>| {
+            queue.submit_snapshot(&ctx, device.0.flatten())
+        })?,
+    )?;
+    namespace.set(
+        "list",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>| values::to_js(&ctx, &list()))?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_index() {
+        assert_eq!(index("1"), CameraIndex::Index(1));
+        assert_eq!(index("/dev/video2"), CameraIndex::String("/dev/video2".to_string()));
+    }
+}
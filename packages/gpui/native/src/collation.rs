@@ -0,0 +1,158 @@
+//! Locale-aware string comparison
+//!
+//! QuickJS ships without `Intl`, so `rasen.collator(locale, options)` wraps
+//! ICU4X's collator with its compiled-in data instead. Collators are built
+//! once per locale and options and kept for the life of the runtime, since
+//! apps typically create one per sort.
+
+use icu_collator::{CaseLevel, Collator, CollatorOptions, Numeric, Strength};
+use icu_locid::Locale;
+use rquickjs::{Ctx, Exception, Function, Object};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+
+/// Collators by `locale|sensitivity|numeric`
+static COLLATORS: Mutex<Option<HashMap<String, Arc<Collator>>>> = Mutex::new(None);
+
+/// Which differences make strings unequal, like `Intl.Collator`'s option
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sensitivity {
+    /// Only base letters: a = á = A
+    Base,
+    /// Base letters and accents: a ≠ á, a = A
+    Accent,
+    /// Base letters and case: a ≠ A, a = á
+    Case,
+    /// Everything: a ≠ á ≠ A
+    Variant,
+}
+
+impl Sensitivity {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "base" => Some(Sensitivity::Base),
+            "accent" => Some(Sensitivity::Accent),
+            "case" => Some(Sensitivity::Case),
+            "variant" => Some(Sensitivity::Variant),
+            _ => None,
+        }
+    }
+}
+
+/// Build a collator; `None` when ICU has no data for the locale
+pub fn collator(locale: &Locale, sensitivity: Sensitivity, numeric: bool) -> Option<Collator> {
+    let mut options = CollatorOptions::new();
+    match sensitivity {
+        Sensitivity::Base => options.strength = Some(Strength::Primary),
+        Sensitivity::Accent => options.strength = Some(Strength::Secondary),
+        Sensitivity::Case => {
+            options.strength = Some(Strength::Primary);
+            options.case_level = Some(CaseLevel::On);
+        }
+        Sensitivity::Variant => options.strength = Some(Strength::Tertiary),
+    }
+    if numeric {
+        options.numeric = Some(Numeric::On);
+    }
+    Collator::try_new(&locale.into(), options).ok()
+}
+
+/// The user's locale for a POSIX category like `LC_COLLATE` (`de_DE.UTF-8`
+/// → `de-DE`), or the root locale when it isn't set
+pub fn system_locale(category: &str) -> Locale {
+    ["LC_ALL", category, "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| {
+            let tag = value.split(['.', '@']).next().unwrap_or_default().replace('_', "-");
+            tag.parse::<Locale>().ok()
+        })
+        .unwrap_or(Locale::UND)
+}
+
+/// Install `rasen.collator(locale?, { sensitivity, numeric })`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    rasen.set(
+        "collator",
+        Function::new(
+            ctx.clone(),
+            |ctx: Ctx<'js>, locale: Option<String>, options: Option<Object<'js>>| -> rquickjs::Result<Object<'js>> {
+                let locale = match locale {
+                    Some(tag) => tag
+                        .parse::<Locale>()
+                        .map_err(|_| Exception::throw_range(&ctx, &format!("Invalid locale '{}'", tag)))?,
+                    None => system_locale("LC_COLLATE"),
+                };
+                let (sensitivity, numeric) = match &options {
+                    Some(options) => (
+                        options.get::<_, Option<String>>("sensitivity")?,
+                        options.get::<_, Option<bool>>("numeric")?.unwrap_or(false),
+                    ),
+                    None => (None, false),
+                };
+                let sensitivity = match sensitivity.as_deref() {
+                    Some(name) => Sensitivity::from_name(name).ok_or_else(|| {
+                        Exception::throw_range(&ctx, &format!("Invalid sensitivity '{}'", name))
+                    })?,
+                    None => Sensitivity::Variant,
+                };
+
+                let key = format!("{}|{:?}|{}", locale, sensitivity, numeric);
+                let mut collators = COLLATORS.lock().unwrap();
+                let collators = collators.get_or_insert_with(HashMap::new);
+                let collator = match collators.get(&key) {
+                    Some(cached) => cached.clone(),
+                    None => {
+                        let built = collator(&locale, sensitivity, numeric).ok_or_else(|| {
+                            Exception::throw_range(&ctx, &format!("No collation data for '{}'", locale))
+                        })?;
+                        collators.entry(key).or_insert(Arc::new(built)).clone()
+                    }
+                };
+
+                let object = Object::new(ctx.clone())?;
+                object.set("locale", locale.to_string())?;
+                // A plain function, so `list.sort(collator.compare)` works
+                object.set(
+                    "compare",
+                    Function::new(ctx.clone(), move |a: String, b: String| match collator.compare(&a, &b) {
+                        Ordering::Less => -1,
+                        Ordering::Equal => 0,
+                        Ordering::Greater => 1,
+                    })?,
+                )?;
+                Ok(object)
+            },
+        )?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(locale: &str, sensitivity: Sensitivity, numeric: bool, a: &str, b: &str) -> Ordering {
+        collator(&locale.parse().unwrap(), sensitivity, numeric).unwrap().compare(a, b)
+    }
+
+    #[test]
+    fn test_locale_order() {
+        // Swedish sorts ä after z; German sorts it with a
+        assert_eq!(compare("sv", Sensitivity::Variant, false, "ä", "z"), Ordering::Greater);
+        assert_eq!(compare("de", Sensitivity::Variant, false, "ä", "z"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sensitivity_and_numeric() {
+        assert_eq!(compare("en", Sensitivity::Base, false, "a", "Á"), Ordering::Equal);
+        assert_eq!(compare("en", Sensitivity::Accent, false, "a", "A"), Ordering::Equal);
+        assert_ne!(compare("en", Sensitivity::Accent, false, "a", "á"), Ordering::Equal);
+        assert_ne!(compare("en", Sensitivity::Case, false, "a", "A"), Ordering::Equal);
+        assert_eq!(compare("en", Sensitivity::Variant, true, "item 9", "item 10"), Ordering::Less);
+        assert_eq!(compare("en", Sensitivity::Variant, false, "item 9", "item 10"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,494 @@
+//! Color picker drawn for `colorPicker({ value, onChange })` elements
+//!
+//! The picker is a saturation/brightness square over the current hue, a hue
+//! bar and a hex field. Clicking or dragging on the square or bar, or typing
+//! into the field and pressing Enter, calls `onChange` with a `#rrggbb`
+//! string, which works as-is in arbitrary classes like `bg-[#3b82f6]`.
+//!
+//! The runtime has no text inputs, so the hex field takes its keys from the
+//! window while it is being edited (see [`PickerTracker::edit_key`]). It
+//! accepts anything the class parser understands as a color (`#abc`,
+//! `rgb(59 130 246)`, `hsl(...)`, named colors). The undo shortcuts undo
+//! and redo the draft's changes while it's edited (see [`PickerTracker::undo_draft`]).
+//!
+//! No platform backend exposes screen color sampling yet, so there is no
+//! eyedropper; `rasen.platform.supports('eyedropper')` reports this.
+
+use gpui::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::elements::RenderContext;
+use crate::tw_parser;
+use crate::undo::{self, History};
+
+/// Picker width, and the height of the saturation/brightness square
+const WIDTH: f32 = 224.0;
+const SURFACE_HEIGHT: f32 = 160.0;
+const HUE_HEIGHT: f32 = 12.0;
+
+/// Color as hue (degrees), saturation and value (0-1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsv {
+    pub h: f32,
+    pub s: f32,
+    pub v: f32,
+}
+
+impl Hsv {
+    pub fn from_rgba(color: Rgba) -> Self {
+        let max = color.r.max(color.g).max(color.b);
+        let min = color.r.min(color.g).min(color.b);
+        let delta = max - min;
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == color.r {
+            60.0 * ((color.g - color.b) / delta).rem_euclid(6.0)
+        } else if max == color.g {
+            60.0 * ((color.b - color.r) / delta + 2.0)
+        } else {
+            60.0 * ((color.r - color.g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        Self { h, s, v: max }
+    }
+
+    pub fn to_rgba(self) -> Rgba {
+        let c = self.v * self.s;
+        let h = self.h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = self.v - c;
+        Rgba { r: r + m, g: g + m, b: b + m, a: 1.0 }
+    }
+
+    /// `#rrggbb`, as accepted by arbitrary color classes
+    pub fn to_hex(self) -> String {
+        let color = self.to_rgba();
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        format!("#{:02x}{:02x}{:02x}", channel(color.r), channel(color.g), channel(color.b))
+    }
+}
+
+/// Parse any color the class parser understands
+pub fn parse(value: &str) -> Option<Hsv> {
+    tw_parser::parse_color(value.trim()).map(|color| Hsv::from_rgba(color.into()))
+}
+
+/// Part of the picker being dragged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Part {
+    Surface,
+    Hue,
+}
+
+/// Payload while dragging inside a picker
+#[derive(Debug, Clone)]
+struct PickerDrag {
+    picker: String,
+    part: Part,
+}
+
+/// Result of a key press while a hex field is being edited
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// The draft changed or the edit ended without a change
+    Updated,
+    /// Enter on a valid color: call the picker's `onChange` with it
+    Commit { picker: String, hex: String },
+}
+
+/// Per-picker state kept across frames
+///
+/// Works like [`SortTracker`](crate::sortable::SortTracker): cheap to clone,
+/// and pickers not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct PickerTracker {
+    pickers: Rc<RefCell<HashMap<String, PickerState>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+struct PickerState {
+    /// The picked color, which keeps hue and saturation when the hex value
+    /// can't (greys, black)
+    hsv: Hsv,
+    /// `hsv` as hex, to tell whether the app changed the value since
+    hex: String,
+    /// Draft text while the hex field is edited
+    editing: Option<String>,
+    /// Changes to the draft, as the text before and after each
+    drafts: History<(String, String)>,
+    /// Where the square and hue bar were last painted, for presses
+    bounds: HashMap<Part, Bounds<Pixels>>,
+}
+
+impl PickerTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The color to show for a picker whose value is `value`
+    fn current(&self, id: &str, value: &str) -> Hsv {
+        self.seen.borrow_mut().insert(id.to_string());
+        let mut pickers = self.pickers.borrow_mut();
+        match pickers.get(id) {
+            Some(state) if state.hex.eq_ignore_ascii_case(value.trim()) => state.hsv,
+            _ => {
+                let hsv = parse(value).unwrap_or(Hsv { h: 0.0, s: 0.0, v: 0.0 });
+                let (editing, drafts, bounds) = match pickers.remove(id) {
+                    Some(state) => (state.editing, state.drafts, state.bounds),
+                    None => (None, History::new(undo::MAX_DEPTH), HashMap::new()),
+                };
+                pickers.insert(id.to_string(), PickerState { hsv, hex: hsv.to_hex(), editing, drafts, bounds });
+                hsv
+            }
+        }
+    }
+
+    /// Record a picked color; returns its hex value
+    fn pick(&self, id: &str, hsv: Hsv) -> String {
+        let hex = hsv.to_hex();
+        if let Some(state) = self.pickers.borrow_mut().get_mut(id) {
+            state.hsv = hsv;
+            state.hex = hex.clone();
+        }
+        hex
+    }
+
+    fn hsv(&self, id: &str) -> Option<Hsv> {
+        self.pickers.borrow().get(id).map(|state| state.hsv)
+    }
+
+    fn bounds(&self, id: &str, part: Part) -> Option<Bounds<Pixels>> {
+        self.pickers.borrow().get(id).and_then(|state| state.bounds.get(&part).copied())
+    }
+
+    fn set_bounds(&self, id: &str, part: Part, bounds: Bounds<Pixels>) {
+        if let Some(state) = self.pickers.borrow_mut().get_mut(id) {
+            state.bounds.insert(part, bounds);
+        }
+    }
+
+    /// Pick the color under `position`; returns the hex value if it changed
+    fn pick_at(&self, id: &str, part: Part, bounds: Bounds<Pixels>, position: Point<Pixels>) -> Option<String> {
+        let current = self.hsv(id)?;
+        let picked = pick_at(part, current, bounds, position);
+        (picked != current).then(|| self.pick(id, picked))
+    }
+
+    fn editing(&self, id: &str) -> Option<String> {
+        self.pickers.borrow().get(id).and_then(|state| state.editing.clone())
+    }
+
+    fn set_editing(&self, id: &str, draft: Option<String>) {
+        if let Some(state) = self.pickers.borrow_mut().get_mut(id) {
+            state.editing = draft;
+            state.drafts.clear();
+        }
+    }
+
+    /// Handle a key press for the hex field being edited, if any
+    pub fn edit_key(&self, keystroke: &Keystroke) -> Option<Edit> {
+        let mut pickers = self.pickers.borrow_mut();
+        let (id, state) = pickers.iter_mut().find(|(_, state)| state.editing.is_some())?;
+        let draft = state.editing.as_mut()?;
+        let before = draft.clone();
+        let modified = keystroke.modifiers.control || keystroke.modifiers.platform || keystroke.modifiers.alt;
+        match keystroke.key.as_str() {
+            "escape" => state.editing = None,
+            "backspace" => {
+                draft.pop();
+            }
+            "enter" => {
+                let draft = state.editing.take()?;
+                if let Some(hsv) = parse(&draft) {
+                    state.hsv = hsv;
+                    state.hex = hsv.to_hex();
+                    return Some(Edit::Commit { picker: id.clone(), hex: state.hex.clone() });
+                }
+            }
+            _ if !modified => match &keystroke.key_char {
+                Some(text) if !text.chars().any(char::is_control) => draft.push_str(text),
+                _ => return None,
+            },
+            _ => return None,
+        }
+        match &state.editing {
+            Some(draft) if *draft != before => {
+                let after = draft.clone();
+                state.drafts.push((before, after));
+            }
+            Some(_) => {}
+            None => {
+                state.drafts.clear();
+            }
+        }
+        Some(Edit::Updated)
+    }
+
+    /// Undo or redo a change to the draft of the hex field being edited;
+    /// returns false when no field is, so the undo goes to the app
+    pub fn undo_draft(&self, redo: bool) -> bool {
+        let mut pickers = self.pickers.borrow_mut();
+        let Some(state) = pickers.values_mut().find(|state| state.editing.is_some()) else {
+            return false;
+        };
+        let draft = if redo {
+            state.drafts.redo().map(|(_, after)| after.clone())
+        } else {
+            state.drafts.undo().map(|(before, _)| before.clone())
+        };
+        if let Some(draft) = draft {
+            state.editing = Some(draft);
+        }
+        true
+    }
+
+    /// End a render pass, dropping state for pickers that are gone
+    pub fn finish_frame(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.pickers.borrow_mut().retain(|id, _| seen.contains(id));
+    }
+}
+
+/// Color at a position within the square or the hue bar
+fn pick_at(part: Part, hsv: Hsv, bounds: Bounds<Pixels>, position: Point<Pixels>) -> Hsv {
+    let fraction = |offset: Pixels, length: Pixels| (f32::from(offset) / f32::from(length).max(1.0)).clamp(0.0, 1.0);
+    let x = fraction(position.x - bounds.left(), bounds.size.width);
+    match part {
+        Part::Surface => Hsv { s: x, v: 1.0 - fraction(position.y - bounds.top(), bounds.size.height), ..hsv },
+        Part::Hue => Hsv { h: x * 360.0, ..hsv },
+    }
+}
+
+/// Draw the picker for element `id`
+pub fn render(
+    id: &str,
+    value: &str,
+    on_change: Option<Box<dyn Fn(String, &mut Window, &mut App) + 'static>>,
+    render_ctx: &RenderContext,
+) -> AnyElement {
+    let pickers = render_ctx.pickers;
+    let hsv = pickers.current(id, value);
+    let zoom = render_ctx.zoom;
+    let on_change: Rc<dyn Fn(String, &mut Window, &mut App)> = match on_change {
+        Some(on_change) => Rc::from(on_change),
+        None => Rc::new(|_, _, _| {}),
+    };
+
+    // Pick on press and while dragging; the drag itself draws nothing
+    let interactive = |part: Part, area: Div| {
+        let (tracker, picker) = (pickers.clone(), id.to_string());
+        let measure = canvas(move |bounds, _, _| tracker.set_bounds(&picker, part, bounds), |_, _, _, _| {})
+            .absolute()
+            .size_full();
+
+        let (tracker, picker, change) = (pickers.clone(), id.to_string(), on_change.clone());
+        let area = area.child(measure).on_mouse_down(MouseButton::Left, move |event, window, cx| {
+            let Some(bounds) = tracker.bounds(&picker, part) else {
+                return;
+            };
+            if let Some(hex) = tracker.pick_at(&picker, part, bounds, event.position) {
+                change(hex, window, cx);
+            }
+        });
+
+        let (tracker, picker, change) = (pickers.clone(), id.to_string(), on_change.clone());
+        let area = area.on_drag_move::<PickerDrag>(move |event, window, cx| {
+            let drag = event.drag(cx);
+            if drag.picker != picker || drag.part != part {
+                return;
+            }
+            if let Some(hex) = tracker.pick_at(&picker, part, event.bounds, event.event.position) {
+                change(hex, window, cx);
+            }
+        });
+        let dragged = PickerDrag { picker: id.to_string(), part };
+        area.id(ElementId::Name(format!("{}-{:?}", id, part).into()))
+            .on_drag(dragged, |_, _, _, cx| cx.new(|_| EmptyView))
+    };
+
+    let hue_color = Hsv { s: 1.0, v: 1.0, ..hsv }.to_rgba();
+    let marker = px(12.0 * zoom);
+    let surface = div()
+        .relative()
+        .w(px(WIDTH * zoom))
+        .h(px(SURFACE_HEIGHT * zoom))
+        .rounded(px(4.0 * zoom))
+        .overflow_hidden()
+        .bg(hue_color)
+        .child(div().absolute().size_full().bg(linear_gradient(
+            90.0,
+            linear_color_stop(white(), 0.0),
+            linear_color_stop(white().opacity(0.0), 1.0),
+        )))
+        .child(div().absolute().size_full().bg(linear_gradient(
+            180.0,
+            linear_color_stop(black().opacity(0.0), 0.0),
+            linear_color_stop(black(), 1.0),
+        )))
+        .child(
+            div()
+                .absolute()
+                .left(px(hsv.s * WIDTH * zoom) - marker / 2.0)
+                .top(px((1.0 - hsv.v) * SURFACE_HEIGHT * zoom) - marker / 2.0)
+                .size(marker)
+                .rounded_full()
+                .border_2()
+                .border_color(white()),
+        );
+
+    // Six two-stop gradients make up the hue spectrum
+    let mut hue = div().relative().flex().flex_row().w(px(WIDTH * zoom)).h(px(HUE_HEIGHT * zoom));
+    for segment in 0..6 {
+        let stop = |h: f32| Hsv { h, s: 1.0, v: 1.0 }.to_rgba();
+        hue = hue.child(div().flex_1().h_full().bg(linear_gradient(
+            90.0,
+            linear_color_stop(stop(segment as f32 * 60.0), 0.0),
+            linear_color_stop(stop((segment + 1) as f32 * 60.0), 1.0),
+        )));
+    }
+    let hue = hue.child(
+        div()
+            .absolute()
+            .top_0()
+            .left(px(hsv.h / 360.0 * WIDTH * zoom) - px(2.0 * zoom))
+            .w(px(4.0 * zoom))
+            .h_full()
+            .bg(white())
+            .border_1()
+            .border_color(black().opacity(0.4)),
+    );
+
+    // Hex field: click to type a color, Enter to apply, Escape to cancel
+    let editing = pickers.editing(id);
+    let field_text = match &editing {
+        Some(draft) => format!("{}|", draft),
+        None => hsv.to_hex(),
+    };
+    let (tracker, picker, start) = (pickers.clone(), id.to_string(), hsv.to_hex());
+    let (tracker_out, picker_out) = (pickers.clone(), id.to_string());
+    let field = div()
+        .id(ElementId::Name(format!("{}-hex", id).into()))
+        .flex_1()
+        .px(px(6.0 * zoom))
+        .py(px(2.0 * zoom))
+        .rounded(px(4.0 * zoom))
+        .border_1()
+        .border_color(if editing.is_some() { rgb(0x3b82f6) } else { rgb(0xd1d5db) })
+        .text_size(px(13.0 * zoom))
+        .cursor_text()
+        .child(field_text)
+        .on_mouse_down(MouseButton::Left, move |_, window, _| {
+            tracker.set_editing(&picker, Some(start.clone()));
+            window.refresh();
+        })
+        .on_mouse_down_out(move |_, window, _| {
+            if tracker_out.editing(&picker_out).is_some() {
+                tracker_out.set_editing(&picker_out, None);
+                window.refresh();
+            }
+        });
+    let swatch = div()
+        .size(px(24.0 * zoom))
+        .rounded(px(4.0 * zoom))
+        .border_1()
+        .border_color(rgb(0xd1d5db))
+        .bg(hsv.to_rgba());
+
+    div()
+        .flex()
+        .flex_col()
+        .gap(px(8.0 * zoom))
+        .child(interactive(Part::Surface, surface))
+        .child(interactive(Part::Hue, hue))
+        .child(div().flex().flex_row().items_center().gap(px(8.0 * zoom)).child(swatch).child(field))
+        .into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_round_trip() {
+        for hex in ["#3b82f6", "#000000", "#ffffff", "#ff0000", "#10b981", "#808080"] {
+            assert_eq!(parse(hex).unwrap().to_hex(), hex);
+        }
+        // Anything the class parser takes, written back as `#rrggbb`
+        assert_eq!(parse("rgb(59 130 246)").unwrap().to_hex(), "#3b82f6");
+        assert_eq!(parse("#f00").unwrap().to_hex(), "#ff0000");
+        assert_eq!(parse("tomato").unwrap().to_hex(), "#ff6347");
+        assert!(parse("not-a-color").is_none());
+    }
+
+    #[test]
+    fn test_pick_at() {
+        let bounds = Bounds::new(point(px(10.0), px(20.0)), size(px(100.0), px(50.0)));
+        let red = Hsv { h: 0.0, s: 1.0, v: 1.0 };
+
+        let picked = pick_at(Part::Surface, red, bounds, point(px(60.0), px(20.0)));
+        assert_eq!((picked.s, picked.v), (0.5, 1.0));
+        // Positions outside the area clamp to its edge
+        let picked = pick_at(Part::Surface, red, bounds, point(px(500.0), px(500.0)));
+        assert_eq!((picked.s, picked.v), (1.0, 0.0));
+
+        let picked = pick_at(Part::Hue, red, bounds, point(px(60.0), px(0.0)));
+        assert_eq!(picked.to_hex(), "#00ffff");
+    }
+
+    #[test]
+    fn test_edit_key() {
+        let tracker = PickerTracker::new();
+        tracker.current("picker", "#000000");
+        assert_eq!(tracker.edit_key(&Keystroke::parse("a").unwrap()), None);
+
+        tracker.set_editing("picker", Some(String::new()));
+        for key in ["#", "a", "b", "c", "d"] {
+            let mut keystroke = Keystroke::parse(key).unwrap();
+            keystroke.key_char = Some(key.to_string());
+            assert_eq!(tracker.edit_key(&keystroke), Some(Edit::Updated));
+        }
+        tracker.edit_key(&Keystroke::parse("backspace").unwrap());
+        assert_eq!(
+            tracker.edit_key(&Keystroke::parse("enter").unwrap()),
+            Some(Edit::Commit { picker: "picker".to_string(), hex: "#aabbcc".to_string() })
+        );
+        assert_eq!(tracker.editing("picker"), None);
+    }
+
+    #[test]
+    fn test_undo_draft() {
+        let tracker = PickerTracker::new();
+        tracker.current("picker", "#000000");
+        assert!(!tracker.undo_draft(false));
+
+        tracker.set_editing("picker", Some("#12".to_string()));
+        for key in ["3", "4"] {
+            let mut keystroke = Keystroke::parse(key).unwrap();
+            keystroke.key_char = Some(key.to_string());
+            tracker.edit_key(&keystroke);
+        }
+        assert!(tracker.undo_draft(false));
+        assert_eq!(tracker.editing("picker").as_deref(), Some("#123"));
+        tracker.undo_draft(false);
+        // Nothing left to undo: the field keeps the undo to itself
+        assert!(tracker.undo_draft(false));
+        assert_eq!(tracker.editing("picker").as_deref(), Some("#12"));
+        tracker.undo_draft(true);
+        assert_eq!(tracker.editing("picker").as_deref(), Some("#123"));
+
+        // Typing after an undo drops what was undone
+        tracker.edit_key(&Keystroke::parse("backspace").unwrap());
+        assert!(tracker.undo_draft(true));
+        assert_eq!(tracker.editing("picker").as_deref(), Some("#12"));
+    }
+}
@@ -0,0 +1,518 @@
+//! rasen.config.js parsing
+//!
+//! The config file is read before the JS runtime exists, so its sections are
+//! extracted with lightweight pattern matching rather than by evaluating it.
+
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Parsed rasen.config.js
+#[derive(Debug, Default)]
+pub struct RasenConfig {
+    /// Module aliases to bundle, e.g. `'@rasenjs/gpui': './dist/index.js'`
+    pub modules: HashMap<String, String>,
+    /// Window options (`window` section)
+    pub window: WindowConfig,
+    /// Application metadata (`app` section)
+    pub app: AppConfig,
+    /// Hardening options (`security` section)
+    pub security: SecurityConfig,
+    /// JS engine options (`runtime` section)
+    pub runtime: RuntimeConfig,
+    /// Startup tracing options (`startup` section)
+    pub startup: StartupConfig,
+    /// Design tokens for Tailwind classes (`theme` section)
+    pub theme: ThemeConfig,
+    /// File system sandbox (`fs` section)
+    pub fs: FsConfig,
+    /// JSX compilation (`jsx` section)
+    pub jsx: JsxConfig,
+    /// Native plugin libraries to load, relative to the project root
+    pub plugins: Vec<String>,
+}
+
+/// Design tokens from the `theme` section, as written in the config
+///
+/// Values are kept as strings here and parsed by `tw_parser::Theme`. Nested
+/// objects are flattened into dashed names, so `brand: { 600: '#4f46e5' }`
+/// defines `brand-600`; a `DEFAULT` key takes its parent's name.
+#[derive(Debug, Default)]
+pub struct ThemeConfig {
+    /// `colors`: `bg-brand-600`, `text-brand`, ...
+    pub colors: HashMap<String, String>,
+    /// `spacing`: `p-gutter`, `gap-18`, `w-sidebar`, ...
+    pub spacing: HashMap<String, String>,
+    /// `fontSize`: `text-hero`
+    pub font_sizes: HashMap<String, String>,
+    /// `screens` (or `breakpoints`): `3xl:flex-row`
+    pub screens: HashMap<String, String>,
+}
+
+/// Startup tracing options from the `startup` section
+#[derive(Debug, Default)]
+pub struct StartupConfig {
+    /// Per-phase budgets in milliseconds for `--trace-startup`, keyed like
+    /// `script` or `firstFrame`
+    pub budgets: HashMap<String, u64>,
+}
+
+/// JS engine options from the `runtime` section
+#[derive(Debug, Default)]
+pub struct RuntimeConfig {
+    /// Maximum QuickJS stack size in bytes; deeply nested component trees
+    /// may need more than the default
+    pub stack_size: Option<usize>,
+    /// Maximum QuickJS heap in bytes
+    pub memory_limit: Option<usize>,
+    /// Heap growth in bytes after which QuickJS collects garbage
+    pub gc_threshold: Option<usize>,
+    /// Milliseconds a single call into JS may run before it is interrupted
+    pub time_limit: Option<usize>,
+    /// Element count above which a render logs a warning (0 disables it)
+    pub large_tree_warning: Option<usize>,
+    /// Milliseconds a handler may block the UI thread before a warning is
+    /// logged (0 disables it)
+    pub slow_handler_warning: Option<usize>,
+}
+
+/// File system sandbox from the `fs` section
+#[derive(Debug, Default)]
+pub struct FsConfig {
+    /// Directories `rasen.fs` may access, relative to the project root or
+    /// starting with `~`; `None` allows everything outside hardened mode
+    pub allow: Option<Vec<String>>,
+}
+
+/// JSX options from the `jsx` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsxConfig {
+    /// Function elements compile to calls of, like `h('div', props, ...children)`
+    pub factory: String,
+    /// Component `<>...</>` compiles to
+    pub fragment: String,
+}
+
+impl Default for JsxConfig {
+    fn default() -> Self {
+        Self { factory: "h".to_string(), fragment: "Fragment".to_string() }
+    }
+}
+
+/// Hardening options from the `security` section
+#[derive(Debug, Default)]
+pub struct SecurityConfig {
+    /// Pinned SHA-256 (hex) of the bundle, as printed by `rasen-gpui hash`
+    pub sha256: Option<String>,
+    /// Ed25519 public key (hex) used to check `<entry>.sig`
+    pub public_key: Option<String>,
+    /// Verification mode; defaults to enforce in packaged builds, off otherwise
+    pub verify: Option<VerifyMode>,
+    /// Run without eval/Function and runtime module registration
+    pub hardened: bool,
+}
+
+/// What to do when bundle verification fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyMode {
+    Off,
+    Warn,
+    Enforce,
+}
+
+/// Application metadata from the `app` section, shown in the About panel
+#[derive(Debug, Default)]
+pub struct AppConfig {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Image shown in the About panel, relative to the project
+    pub icon: Option<String>,
+    /// Hand later launches over to the running instance (see `lifecycle`)
+    pub single_instance: bool,
+}
+
+/// Window options from the `window` section
+#[derive(Debug, Default)]
+pub struct WindowConfig {
+    /// Application ID: the Wayland `app_id` and the X11 `WM_CLASS`, used by
+    /// desktop environments and tiling window managers to match windows
+    pub app_id: Option<String>,
+    /// Client- or server-side decorations preference (Linux)
+    pub decorations: Option<Decorations>,
+}
+
+/// Who draws the window decorations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decorations {
+    Client,
+    Server,
+}
+
+impl RasenConfig {
+    /// Load rasen.config.js from a directory; a missing file yields the defaults
+    pub fn load(work_dir: &Path) -> Result<Self> {
+        let config_path = work_dir.join("rasen.config.js");
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&config_path)?;
+        Ok(Self::parse(&content))
+    }
+
+    /// Parse config file contents
+    pub fn parse(content: &str) -> Self {
+        let content = strip_comments(content);
+
+        // Older configs may list aliases at the top level
+        let modules = parse_aliases(section(&content, "modules").unwrap_or(&content));
+
+        let window = section(&content, "window")
+            .map(|window| WindowConfig {
+                app_id: string_value(window, "appId"),
+                decorations: match string_value(window, "decorations").as_deref() {
+                    Some("client") => Some(Decorations::Client),
+                    Some("server") => Some(Decorations::Server),
+                    _ => None,
+                },
+            })
+            .unwrap_or_default();
+
+        let app = section(&content, "app")
+            .map(|app| AppConfig {
+                name: string_value(app, "name"),
+                version: string_value(app, "version"),
+                icon: string_value(app, "icon"),
+                single_instance: bool_value(app, "singleInstance").unwrap_or(false),
+            })
+            .unwrap_or_default();
+
+        let security = section(&content, "security")
+            .map(|security| SecurityConfig {
+                sha256: string_value(security, "sha256"),
+                public_key: string_value(security, "publicKey"),
+                verify: match string_value(security, "verify").as_deref() {
+                    Some("off") => Some(VerifyMode::Off),
+                    Some("warn") => Some(VerifyMode::Warn),
+                    Some("enforce") => Some(VerifyMode::Enforce),
+                    _ => None,
+                },
+                hardened: bool_value(security, "hardened").unwrap_or(false),
+            })
+            .unwrap_or_default();
+
+        let runtime = section(&content, "runtime")
+            .map(|runtime| RuntimeConfig {
+                stack_size: number_value(runtime, "stackSize"),
+                memory_limit: number_value(runtime, "memoryLimit"),
+                gc_threshold: number_value(runtime, "gcThreshold"),
+                time_limit: number_value(runtime, "timeLimit"),
+                large_tree_warning: number_value(runtime, "largeTreeWarning"),
+                slow_handler_warning: number_value(runtime, "slowHandlerWarning"),
+            })
+            .unwrap_or_default();
+
+        let startup = section(&content, "startup")
+            .and_then(|startup| section(startup, "budgets"))
+            .map(|budgets| StartupConfig { budgets: number_map(budgets) })
+            .unwrap_or_default();
+
+        let theme = section(&content, "theme")
+            .map(|theme| {
+                let map = |name: &str| section(theme, name).map(string_map).unwrap_or_default();
+                ThemeConfig {
+                    colors: map("colors"),
+                    spacing: map("spacing"),
+                    font_sizes: map("fontSize"),
+                    screens: section(theme, "screens")
+                        .or_else(|| section(theme, "breakpoints"))
+                        .map(string_map)
+                        .unwrap_or_default(),
+                }
+            })
+            .unwrap_or_default();
+
+        let fs = section(&content, "fs")
+            .map(|fs| FsConfig { allow: string_list(fs, "allow") })
+            .unwrap_or_default();
+
+        let jsx = section(&content, "jsx")
+            .map(|jsx| {
+                let defaults = JsxConfig::default();
+                JsxConfig {
+                    factory: string_value(jsx, "factory").unwrap_or(defaults.factory),
+                    fragment: string_value(jsx, "fragment").unwrap_or(defaults.fragment),
+                }
+            })
+            .unwrap_or_default();
+
+        let plugins = string_list(&content, "plugins").unwrap_or_default();
+
+        Self { modules, window, app, security, runtime, startup, theme, fs, jsx, plugins }
+    }
+}
+
+/// Remove line and block comments
+fn strip_comments(content: &str) -> String {
+    let re_line_comment = Regex::new(r"//.*").unwrap();
+    let re_block_comment = Regex::new(r"/\*[\s\S]*?\*/").unwrap();
+    let content = re_line_comment.replace_all(content, "");
+    re_block_comment.replace_all(&content, "").to_string()
+}
+
+/// Extract the body of an object section like `window: { ... }`
+fn section<'a>(content: &'a str, name: &str) -> Option<&'a str> {
+    let re = Regex::new(&format!(r#"\b{}['"]?\s*:\s*\{{"#, regex::escape(name))).unwrap();
+    let start = re.find(content)?.end();
+    let end = closing_brace(&content[start..])?;
+    Some(&content[start..start + end])
+}
+
+/// Offset of the `}` closing an object whose `{` was just before `body`
+fn closing_brace(body: &str) -> Option<usize> {
+    let mut depth = 1;
+    for (i, c) in body.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Read the entries of an object section as strings, flattening nested
+/// objects into dashed keys (`brand: { 600: .. }` → `brand-600`)
+///
+/// Tailwind-style `[value, options]` arrays contribute their first element.
+fn string_map(section: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    collect_strings(section, "", &mut map);
+    map
+}
+
+fn collect_strings(section: &str, prefix: &str, map: &mut HashMap<String, String>) {
+    let re = Regex::new(
+        r#"['"]?([\w.-]+)['"]?\s*:\s*(?:'([^']*)'|"([^"]*)"|(-?[\d.]+)|\[\s*['"]([^'"]*)['"]|(\{))"#,
+    )
+    .unwrap();
+    let mut pos = 0;
+    while let Some(cap) = re.captures_at(section, pos) {
+        let end = cap.get(0).unwrap().end();
+        let key = match (prefix, &cap[1]) {
+            ("", key) => key.to_string(),
+            (prefix, "DEFAULT") => prefix.to_string(),
+            (prefix, key) => format!("{}-{}", prefix, key),
+        };
+        if cap.get(6).is_some() {
+            // Nested object: read its body, then continue after it
+            let body_end = closing_brace(&section[end..]).map_or(section.len(), |i| end + i);
+            collect_strings(&section[end..body_end], &key, map);
+            pos = (body_end + 1).min(section.len());
+            continue;
+        }
+        let value = cap.get(2).or(cap.get(3)).or(cap.get(4)).or(cap.get(5)).unwrap();
+        map.insert(key, value.as_str().to_string());
+        pos = end;
+        if cap.get(5).is_some() {
+            // Skip the rest of the array, including its options object
+            pos = section[end..].find(']').map_or(section.len(), |i| end + i + 1);
+        }
+    }
+}
+
+/// Read a string value like `appId: 'com.example.app'`
+fn string_value(section: &str, key: &str) -> Option<String> {
+    let re = Regex::new(&format!(r#"\b{}['"]?\s*:\s*['"]([^'"]*)['"]"#, regex::escape(key))).unwrap();
+    re.captures(section).map(|cap| cap[1].to_string())
+}
+
+/// Read an array of strings like `allow: ['./data', '~/Documents']`
+fn string_list(section: &str, key: &str) -> Option<Vec<String>> {
+    let re = Regex::new(&format!(r#"\b{}['"]?\s*:\s*\[([^\]]*)\]"#, regex::escape(key))).unwrap();
+    let items = re.captures(section)?;
+    let item = Regex::new(r#"'([^']*)'|"([^"]*)""#).unwrap();
+    Some(item.captures_iter(&items[1]).map(|cap| cap.get(1).or(cap.get(2)).unwrap().as_str().to_string()).collect())
+}
+
+/// Read a boolean value like `hardened: true`
+fn bool_value(section: &str, key: &str) -> Option<bool> {
+    let re = Regex::new(&format!(r#"\b{}['"]?\s*:\s*(true|false)\b"#, regex::escape(key))).unwrap();
+    re.captures(section).map(|cap| &cap[1] == "true")
+}
+
+/// Read an integer value like `stackSize: 4 * 1024 * 1024`
+///
+/// Numeric separators (`4_194_304`) and products of integers are accepted.
+fn number_value(section: &str, key: &str) -> Option<usize> {
+    let re = Regex::new(&format!(r#"\b{}['"]?\s*:\s*([\d_]+(?:\s*\*\s*[\d_]+)*)"#, regex::escape(key))).unwrap();
+    let cap = re.captures(section)?;
+    cap[1]
+        .split('*')
+        .map(|factor| factor.trim().replace('_', "").parse::<usize>().ok())
+        .try_fold(1usize, |product, factor| product.checked_mul(factor?))
+}
+
+/// Read every integer entry of an object section, like `script: 200`
+fn number_map(section: &str) -> HashMap<String, u64> {
+    let re = Regex::new(r#"['"]?(\w+)['"]?\s*:"#).unwrap();
+    re.captures_iter(section)
+        .filter_map(|cap| {
+            let value = number_value(section, &cap[1])?;
+            Some((cap[1].to_string(), value as u64))
+        })
+        .collect()
+}
+
+/// Extract `'name': 'path'` module aliases
+fn parse_aliases(content: &str) -> HashMap<String, String> {
+    let mut aliases = HashMap::new();
+
+    let re = Regex::new(r#"['"](@?[\w\-/]+)['"]\s*:\s*['"]([^'"]+)['"]"#).unwrap();
+
+    for cap in re.captures_iter(content) {
+        let name = cap[1].to_string();
+        let path = cap[2].to_string();
+        aliases.insert(name, path);
+    }
+
+    aliases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modules_and_window() {
+        let config = RasenConfig::parse(r#"
+            export default {
+              // comment: 'ignored'
+              modules: {
+                '@rasenjs/gpui': '../gpui/dist/index.js',
+              },
+              window: {
+                title: 'App',
+                appId: 'dev.rasen.example',
+                decorations: 'server',
+              },
+              app: { name: 'Example', singleInstance: true },
+            }
+        "#);
+        assert_eq!(config.modules.len(), 1);
+        assert_eq!(config.modules["@rasenjs/gpui"], "../gpui/dist/index.js");
+        assert_eq!(config.window.app_id.as_deref(), Some("dev.rasen.example"));
+        assert_eq!(config.window.decorations, Some(Decorations::Server));
+        assert!(config.app.single_instance);
+    }
+
+    #[test]
+    fn test_parse_security() {
+        let config = RasenConfig::parse(r#"
+            export default {
+              security: { verify: 'warn', hardened: true },
+            }
+        "#);
+        assert_eq!(config.security.verify, Some(VerifyMode::Warn));
+        assert!(config.security.hardened);
+        assert!(!RasenConfig::parse("export default {}").security.hardened);
+    }
+
+    #[test]
+    fn test_parse_jsx() {
+        let config = RasenConfig::parse("export default { jsx: { factory: 'createElement' } }");
+        assert_eq!(config.jsx.factory, "createElement");
+        assert_eq!(config.jsx.fragment, "Fragment");
+        assert_eq!(RasenConfig::parse("export default {}").jsx, JsxConfig::default());
+    }
+
+    #[test]
+    fn test_parse_plugins() {
+        let config = RasenConfig::parse("export default { plugins: ['./plugins/libhasher.so'] }");
+        assert_eq!(config.plugins, vec!["./plugins/libhasher.so".to_string()]);
+        assert!(RasenConfig::parse("export default {}").plugins.is_empty());
+    }
+
+    #[test]
+    fn test_parse_fs() {
+        let config = RasenConfig::parse(r#"
+            export default {
+              fs: { allow: ['./data', "~/Documents"] },
+            }
+        "#);
+        assert_eq!(config.fs.allow, Some(vec!["./data".to_string(), "~/Documents".to_string()]));
+        let config = RasenConfig::parse("export default { fs: { allow: [] } }");
+        assert_eq!(config.fs.allow, Some(Vec::new()));
+        assert_eq!(RasenConfig::parse("export default {}").fs.allow, None);
+    }
+
+    #[test]
+    fn test_parse_runtime() {
+        let config = RasenConfig::parse("export default { runtime: { stackSize: 4 * 1024 * 1024 } }");
+        assert_eq!(config.runtime.stack_size, Some(4 * 1024 * 1024));
+        let config = RasenConfig::parse("export default { runtime: { stackSize: 2_000_000 } }");
+        assert_eq!(config.runtime.stack_size, Some(2_000_000));
+        assert_eq!(RasenConfig::parse("export default {}").runtime.stack_size, None);
+        let config = RasenConfig::parse(
+            "export default { runtime: { memoryLimit: 256 * 1024 * 1024, gcThreshold: 8_000_000, timeLimit: 5000 } }",
+        );
+        assert_eq!(config.runtime.memory_limit, Some(256 * 1024 * 1024));
+        assert_eq!(config.runtime.gc_threshold, Some(8_000_000));
+        assert_eq!(config.runtime.time_limit, Some(5000));
+        let config = RasenConfig::parse(
+            "export default { runtime: { largeTreeWarning: 20_000, slowHandlerWarning: 0 } }",
+        );
+        assert_eq!(config.runtime.large_tree_warning, Some(20_000));
+        assert_eq!(config.runtime.slow_handler_warning, Some(0));
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let config = RasenConfig::parse(r#"
+            export default {
+                theme: {
+                    colors: {
+                        brand: { DEFAULT: '#6366f1', 600: '#4f46e5' },
+                        'accent-light': "rgb(255 200 0)",
+                    },
+                    spacing: { gutter: '24px', 18: '4.5rem' },
+                    fontSize: { hero: ['3rem', { lineHeight: '1' }], tiny: '10px' },
+                    screens: { '3xl': '1920px' },
+                },
+            }
+        "#);
+        let theme = &config.theme;
+        assert_eq!(theme.colors.get("brand").map(String::as_str), Some("#6366f1"));
+        assert_eq!(theme.colors.get("brand-600").map(String::as_str), Some("#4f46e5"));
+        assert_eq!(theme.colors.get("accent-light").map(String::as_str), Some("rgb(255 200 0)"));
+        assert_eq!(theme.spacing.get("18").map(String::as_str), Some("4.5rem"));
+        assert_eq!(theme.spacing.get("gutter").map(String::as_str), Some("24px"));
+        assert_eq!(theme.font_sizes.get("hero").map(String::as_str), Some("3rem"));
+        assert_eq!(theme.font_sizes.get("tiny").map(String::as_str), Some("10px"));
+        assert_eq!(theme.font_sizes.len(), 2);
+        assert_eq!(theme.screens.get("3xl").map(String::as_str), Some("1920px"));
+        assert!(RasenConfig::parse("export default {}").theme.colors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_startup_budgets() {
+        let config = RasenConfig::parse(r#"
+            export default {
+                startup: {
+                    budgets: { script: 150, 'firstFrame': 1_000 },
+                },
+            }
+        "#);
+        assert_eq!(config.startup.budgets.get("script"), Some(&150));
+        assert_eq!(config.startup.budgets.get("firstFrame"), Some(&1000));
+        assert_eq!(config.startup.budgets.len(), 2);
+        assert!(RasenConfig::parse("export default {}").startup.budgets.is_empty());
+    }
+}
@@ -0,0 +1,569 @@
+//! `console` for JS code
+//!
+//! `log`, `info` and `debug` print to stdout, `warn` and `error` to stderr.
+//! With `--automation`, stdout carries JSON-RPC responses, so everything goes
+//! to stderr instead. Arguments are formatted like Node's `console`: a
+//! leading string may hold `%s`/`%d`/`%i`/`%f`/`%o`/`%O`/`%j` placeholders,
+//! strings print as they are, and other values are inspected, with nested
+//! objects shown up to [`MAX_DEPTH`] levels and cycles marked `[Circular]`.
+//! Output is colorized when the stream is a terminal and `NO_COLOR` is unset.
+//!
+//! Messages are also kept, uncolored, in a bounded history that automation
+//! clients read with the `logs` method, and that a devtools log panel can
+//! show later.
+
+use rquickjs::function::Rest;
+use rquickjs::{Array, Ctx, Function, Object, Value};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Nesting shown before objects are abbreviated to `[Object]`
+const MAX_DEPTH: usize = 4;
+
+/// Array items and object properties shown before `... n more`
+const MAX_ENTRIES: usize = 100;
+
+/// Inspected values longer than this break across lines
+const LINE_WIDTH: usize = 72;
+
+/// Messages kept for `logs`
+const HISTORY_CAPACITY: usize = 1000;
+
+static HISTORY: Mutex<History> = Mutex::new(History { next_seq: 1, entries: VecDeque::new() });
+
+/// Send everything to stderr (for `--automation`)
+static STDERR_ONLY: AtomicBool = AtomicBool::new(false);
+
+struct History {
+    next_seq: u64,
+    entries: VecDeque<LogEntry>,
+}
+
+struct LogEntry {
+    seq: u64,
+    level: Level,
+    message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Log,
+    Info,
+    Debug,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Log => "log",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// An inspected JS value
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    BigInt(String),
+    String(String),
+    Symbol(String),
+    Function(String),
+    Error(String),
+    Array(Vec<Node>, usize),
+    /// Class name (if not a plain object), properties and how many were left out
+    Object(Option<String>, Vec<(String, Node)>, usize),
+    Circular,
+    /// Beyond [`MAX_DEPTH`]: `[Object]`, `[Array]`
+    Abbreviated(&'static str),
+}
+
+/// Whether stdout or stderr output gets ANSI colors
+fn colors(stderr: bool) -> bool {
+    let terminal = if stderr { io::stderr().is_terminal() } else { io::stdout().is_terminal() };
+    terminal && std::env::var_os("NO_COLOR").is_none()
+}
+
+fn paint(text: &str, code: &str, colors: bool) -> String {
+    if colors {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('\'');
+    for c in text.chars() {
+        match c {
+            '\'' => quoted.push_str("\\'"),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Property names print bare when they are identifiers
+fn key(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if identifier {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn number(value: f64) -> String {
+    if value == 0.0 && value.is_sign_negative() {
+        "-0".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+impl Node {
+    /// Format as Node's `util.inspect` would, `indent` levels deep
+    fn format(&self, indent: usize, colors: bool) -> String {
+        match self {
+            Node::Undefined => paint("undefined", "90", colors),
+            Node::Null => paint("null", "1", colors),
+            Node::Bool(value) => paint(&value.to_string(), "33", colors),
+            Node::Number(value) => paint(&number(*value), "33", colors),
+            Node::BigInt(value) => paint(&format!("{}n", value), "33", colors),
+            Node::String(value) => paint(&quote(value), "32", colors),
+            Node::Symbol(value) => paint(value, "32", colors),
+            Node::Function(name) if name.is_empty() => paint("[Function (anonymous)]", "36", colors),
+            Node::Function(name) => paint(&format!("[Function: {}]", name), "36", colors),
+            Node::Error(text) => text.clone(),
+            Node::Circular => paint("[Circular]", "36", colors),
+            Node::Abbreviated(kind) => paint(&format!("[{}]", kind), "36", colors),
+            Node::Array(items, more) => {
+                let mut parts: Vec<String> = items.iter().map(|item| item.format(indent + 1, colors)).collect();
+                if *more > 0 {
+                    parts.push(format!("... {} more items", more));
+                }
+                group("[", "]", parts, indent)
+            }
+            Node::Object(class, entries, more) => {
+                let mut parts: Vec<String> = entries
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", key(name), value.format(indent + 1, colors)))
+                    .collect();
+                if *more > 0 {
+                    parts.push(format!("... {} more properties", more));
+                }
+                let open = match class {
+                    Some(class) => format!("{} {{", class),
+                    None => "{".to_string(),
+                };
+                group(&open, "}", parts, indent)
+            }
+        }
+    }
+}
+
+/// Join items on one line if it's short enough, otherwise one per line
+fn group(open: &str, close: &str, parts: Vec<String>, indent: usize) -> String {
+    if parts.is_empty() {
+        return format!("{}{}", open, close);
+    }
+    // ANSI codes don't take up room on screen
+    let visible: usize = parts.iter().map(|part| visible_len(part) + 2).sum();
+    if visible + open.len() + indent * 2 <= LINE_WIDTH && !parts.iter().any(|part| part.contains('\n')) {
+        return format!("{} {} {}", open, parts.join(", "), close);
+    }
+    let padding = "  ".repeat(indent + 1);
+    let body: Vec<String> = parts.iter().map(|part| format!("{}{}", padding, part)).collect();
+    format!("{}\n{}\n{}{}", open, body.join(",\n"), "  ".repeat(indent), close)
+}
+
+fn visible_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut escape = false;
+    for c in text.chars() {
+        match c {
+            '\x1b' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {}
+            _ => len += 1,
+        }
+    }
+    len
+}
+
+/// Inspect a JS value; `seen` holds the objects around it, for cycles
+fn inspect<'js>(ctx: &Ctx<'js>, value: &Value<'js>, depth: usize, seen: &mut Vec<Value<'js>>) -> Node {
+    if value.is_undefined() {
+        return Node::Undefined;
+    }
+    if value.is_null() {
+        return Node::Null;
+    }
+    if let Some(value) = value.as_bool() {
+        return Node::Bool(value);
+    }
+    if let Some(value) = value.as_number() {
+        return Node::Number(value);
+    }
+    if let Some(value) = value.as_big_int() {
+        return Node::BigInt(value.clone().to_i64().map(|n| n.to_string()).unwrap_or_else(|_| "?".to_string()));
+    }
+    if let Some(value) = value.as_string() {
+        return Node::String(value.to_string().unwrap_or_default());
+    }
+    if let Some(symbol) = value.as_symbol() {
+        let description = symbol.description().ok().and_then(|d| d.as_string().and_then(|s| s.to_string().ok()));
+        return Node::Symbol(format!("Symbol({})", description.unwrap_or_default()));
+    }
+    if let Some(exception) = value.as_exception() {
+        let name: String = exception.get("name").unwrap_or_else(|_| "Error".to_string());
+        let heading = format!("{}: {}", name, exception.message().unwrap_or_default());
+        return Node::Error(match exception.stack() {
+            Some(stack) if !stack.is_empty() => format!("{}\n{}", heading, stack.trim_end()),
+            _ => heading,
+        });
+    }
+    if let Some(function) = value.as_function() {
+        return Node::Function(function.get::<_, String>("name").unwrap_or_default());
+    }
+    let Some(object) = value.as_object() else {
+        return Node::Undefined;
+    };
+    if seen.contains(value) {
+        return Node::Circular;
+    }
+    let class = object
+        .get::<_, Object>("constructor")
+        .and_then(|constructor| constructor.get::<_, String>("name"))
+        .ok()
+        .filter(|name| !name.is_empty());
+    if depth >= MAX_DEPTH {
+        return Node::Abbreviated(if object.is_array() { "Array" } else { "Object" });
+    }
+
+    seen.push(value.clone());
+    let node = if let Some(array) = object.as_array() {
+        let items = array.iter::<Value>().take(MAX_ENTRIES).map(|item| match item {
+            Ok(item) => inspect(ctx, &item, depth + 1, seen),
+            Err(_) => Node::Undefined,
+        });
+        Node::Array(items.collect(), array.len().saturating_sub(MAX_ENTRIES))
+    } else if matches!(class.as_deref(), Some("Map" | "Set")) {
+        // Entries via `Array.from`, as `[key, value]` pairs for maps
+        let entries = ctx
+            .globals()
+            .get::<_, Object>("Array")
+            .and_then(|array| array.get::<_, Function>("from"))
+            .and_then(|from| from.call::<_, Array>((value.clone(),)))
+            .map(|entries| entries.iter::<Value>().filter_map(Result::ok).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let class = format!("{}({})", class.unwrap_or_default(), entries.len());
+        let shown = entries.iter().take(MAX_ENTRIES).enumerate().map(|(index, entry)| match entry.as_array() {
+            Some(pair) if class.starts_with("Map") => {
+                let key = pair.get::<Value>(0).map(|key| inspect(ctx, &key, depth + 1, seen).format(0, false));
+                let value = pair.get::<Value>(1).map(|value| inspect(ctx, &value, depth + 1, seen));
+                (key.unwrap_or_default(), value.unwrap_or(Node::Undefined))
+            }
+            _ => (index.to_string(), inspect(ctx, entry, depth + 1, seen)),
+        });
+        Node::Object(Some(class.clone()), shown.collect(), entries.len().saturating_sub(MAX_ENTRIES))
+    } else {
+        let keys: Vec<String> = object.keys::<String>().filter_map(Result::ok).collect();
+        let entries = keys.iter().take(MAX_ENTRIES).map(|name| {
+            let value = object.get::<_, Value>(name.as_str()).map(|value| inspect(ctx, &value, depth + 1, seen));
+            (name.clone(), value.unwrap_or(Node::Undefined))
+        });
+        let class = class.filter(|name| name != "Object");
+        Node::Object(class, entries.collect(), keys.len().saturating_sub(MAX_ENTRIES))
+    };
+    seen.pop();
+    node
+}
+
+/// Format `console` arguments into one message
+fn format_args<'js>(ctx: &Ctx<'js>, args: &[Value<'js>], colors: bool) -> String {
+    let inspect_arg = |value: &Value<'js>| match value.as_string() {
+        Some(text) => text.to_string().unwrap_or_default(),
+        None => inspect(ctx, value, 0, &mut Vec::new()).format(0, colors),
+    };
+    let mut rest = args.iter();
+    let mut parts = Vec::new();
+
+    // Placeholders in a leading string consume arguments
+    if let Some(template) = args.first().and_then(|first| first.as_string()).and_then(|s| s.to_string().ok()) {
+        rest.next();
+        let mut message = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            let spec = if c == '%' { chars.peek().copied() } else { None };
+            match spec {
+                Some('%') => {
+                    chars.next();
+                    message.push('%');
+                }
+                Some(spec @ ('s' | 'd' | 'i' | 'f' | 'o' | 'O' | 'j')) => {
+                    let Some(arg) = rest.next() else {
+                        message.push(c);
+                        continue;
+                    };
+                    chars.next();
+                    let numeric = || arg.as_number().unwrap_or(f64::NAN);
+                    message.push_str(&match spec {
+                        's' => inspect_arg(arg),
+                        'i' => paint(&number(numeric().trunc()), "33", colors),
+                        'd' | 'f' => paint(&number(numeric()), "33", colors),
+                        'j' => ctx
+                            .json_stringify(arg.clone())
+                            .ok()
+                            .flatten()
+                            .and_then(|json| json.to_string().ok())
+                            .unwrap_or_else(|| "undefined".to_string()),
+                        _ => inspect(ctx, arg, 0, &mut Vec::new()).format(0, colors),
+                    });
+                }
+                _ => message.push(c),
+            }
+        }
+        parts.push(message);
+    }
+    parts.extend(rest.map(inspect_arg));
+    parts.join(" ")
+}
+
+/// Draw a box table, as `console.table` does
+fn table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            rows.iter()
+                .map(|row| row.get(column).map_or(0, |cell| visible_len(cell)))
+                .chain([visible_len(header)])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let rule = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|width| "─".repeat(width + 2)).collect();
+        format!("{}{}{}", left, segments.join(middle), right)
+    };
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(column, width)| {
+                let cell = cells.get(column).map(String::as_str).unwrap_or("");
+                format!(" {}{} ", cell, " ".repeat(width - visible_len(cell)))
+            })
+            .collect();
+        format!("│{}│", padded.join("│"))
+    };
+    let mut lines = vec![rule("┌", "┬", "┐"), line(headers), rule("├", "┼", "┤")];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.push(rule("└", "┴", "┘"));
+    lines.join("\n")
+}
+
+/// Rows of `console.table(data, columns)`: objects' properties become columns,
+/// other values go in a `Values` column
+fn table_of<'js>(ctx: &Ctx<'js>, data: &Object<'js>, columns: Option<Vec<String>>, colors: bool) -> String {
+    let cell = |value: &Value<'js>| inspect(ctx, value, MAX_DEPTH - 1, &mut Vec::new()).format(0, colors);
+    let mut headers = vec!["(index)".to_string()];
+    let mut keys: Vec<String> = columns.clone().unwrap_or_default();
+    let mut has_values = false;
+    let mut rows: Vec<(String, Vec<(String, String)>, Option<String>)> = Vec::new();
+
+    for index in data.keys::<String>().filter_map(Result::ok).take(MAX_ENTRIES) {
+        let Ok(value) = data.get::<_, Value>(index.as_str()) else {
+            continue;
+        };
+        match value.as_object().filter(|_| value.as_function().is_none()) {
+            Some(row) => {
+                let mut cells = Vec::new();
+                for name in row.keys::<String>().filter_map(Result::ok) {
+                    if columns.as_ref().is_some_and(|columns| !columns.contains(&name)) {
+                        continue;
+                    }
+                    if !keys.contains(&name) {
+                        keys.push(name.clone());
+                    }
+                    let value = row.get::<_, Value>(name.as_str()).ok();
+                    cells.push((name, value.map(|value| cell(&value)).unwrap_or_default()));
+                }
+                rows.push((index, cells, None));
+            }
+            None => {
+                has_values = true;
+                rows.push((index, Vec::new(), Some(cell(&value))));
+            }
+        }
+    }
+
+    headers.extend(keys.iter().cloned());
+    if has_values {
+        headers.push("Values".to_string());
+    }
+    let rows: Vec<Vec<String>> = rows
+        .into_iter()
+        .map(|(index, cells, value)| {
+            let mut line = vec![index];
+            for name in &keys {
+                let cell = cells.iter().find(|(key, _)| key == name).map(|(_, cell)| cell.clone());
+                line.push(cell.unwrap_or_default());
+            }
+            if has_values {
+                line.push(value.unwrap_or_default());
+            }
+            line
+        })
+        .collect();
+    table(&headers, &rows)
+}
+
+/// Print a message and add it to the history
+fn emit(level: Level, colored: String, plain: String) {
+    let stderr = matches!(level, Level::Warn | Level::Error) || STDERR_ONLY.load(Ordering::Relaxed);
+    // A closed pipe shouldn't take the app down
+    let _ = if stderr {
+        writeln!(io::stderr(), "{}", colored)
+    } else {
+        writeln!(io::stdout(), "{}", colored)
+    };
+
+    let mut history = HISTORY.lock().unwrap();
+    let seq = history.next_seq;
+    history.next_seq += 1;
+    if history.entries.len() == HISTORY_CAPACITY {
+        history.entries.pop_front();
+    }
+    history.entries.push_back(LogEntry { seq, level, message: plain });
+}
+
+/// Print everything to stderr, keeping stdout for automation responses
+pub fn use_stderr() {
+    STDERR_ONLY.store(true, Ordering::Relaxed);
+}
+
+/// Messages after sequence number `since`, for the automation `logs` method
+pub fn history(since: u64) -> serde_json::Value {
+    let history = HISTORY.lock().unwrap();
+    let entries: Vec<serde_json::Value> = history
+        .entries
+        .iter()
+        .filter(|entry| entry.seq > since)
+        .map(|entry| json!({ "seq": entry.seq, "level": entry.level.name(), "message": entry.message }))
+        .collect();
+    serde_json::Value::Array(entries)
+}
+
+/// Install the `console` global
+pub fn install<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+    let console = Object::new(ctx.clone())?;
+    for level in [Level::Log, Level::Info, Level::Debug, Level::Warn, Level::Error] {
+        console.set(
+            level.name(),
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>, args: Rest<Value<'js>>| {
+                let stderr = matches!(level, Level::Warn | Level::Error) || STDERR_ONLY.load(Ordering::Relaxed);
+                let plain = format_args(&ctx, &args.0, false);
+                let colored = if colors(stderr) { format_args(&ctx, &args.0, true) } else { plain.clone() };
+                emit(level, colored, plain);
+            })?,
+        )?;
+    }
+    console.set(
+        "table",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, data: Value<'js>, columns: Option<Vec<String>>| {
+            // Like Node, anything but an object logs normally
+            let Some(object) = data.as_object().filter(|_| data.as_function().is_none()) else {
+                let plain = format_args(&ctx, &[data.clone()], false);
+                let colored = if colors(false) { format_args(&ctx, &[data], true) } else { plain.clone() };
+                emit(Level::Log, colored, plain);
+                return;
+            };
+            let plain = table_of(&ctx, object, columns.clone(), false);
+            let colored = if colors(false) { table_of(&ctx, object, columns, true) } else { plain.clone() };
+            emit(Level::Log, colored, plain);
+        })?,
+    )?;
+    ctx.globals().set("console", console)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(entries: Vec<(&str, Node)>) -> Node {
+        Node::Object(None, entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect(), 0)
+    }
+
+    #[test]
+    fn test_format_values() {
+        let value = object(vec![
+            ("name", Node::String("it's".to_string())),
+            ("count", Node::Number(3.0)),
+            ("my-key", Node::Null),
+            ("items", Node::Array(vec![Node::Bool(true), Node::Undefined], 0)),
+            ("self", Node::Circular),
+        ]);
+        assert_eq!(
+            value.format(0, false),
+            "{ name: 'it\\'s', count: 3, 'my-key': null, items: [ true, undefined ], self: [Circular] }"
+        );
+        assert_eq!(Node::Array(Vec::new(), 0).format(0, false), "[]");
+        assert_eq!(Node::Function(String::new()).format(0, false), "[Function (anonymous)]");
+        assert_eq!(Node::Number(-0.0).format(0, false), "-0");
+        assert_eq!(Node::Number(0.5).format(0, true), "\x1b[33m0.5\x1b[0m");
+        assert_eq!(
+            Node::Object(Some("Map(1)".to_string()), vec![("'a'".to_string(), Node::Number(1.0))], 0).format(0, false),
+            "Map(1) { 'a': 1 }"
+        );
+    }
+
+    #[test]
+    fn test_long_values_break_lines() {
+        let long = Node::String("x".repeat(40));
+        let value = object(vec![("a", long.clone()), ("b", object(vec![("c", long)]))]);
+        let expected = format!("{{\n  a: '{x}',\n  b: {{ c: '{x}' }}\n}}", x = "x".repeat(40));
+        assert_eq!(value.format(0, false), expected);
+
+        let items = Node::Array(vec![Node::Number(1.0)], 150);
+        assert_eq!(items.format(0, false), "[ 1, ... 150 more items ]");
+    }
+
+    #[test]
+    fn test_table() {
+        let headers = vec!["(index)".to_string(), "a".to_string()];
+        let rows = vec![vec!["0".to_string(), "1".to_string()], vec!["1".to_string(), "'long'".to_string()]];
+        let expected = "\
+┌─────────┬────────┐
+│ (index) │ a      │
+├─────────┼────────┤
+│ 0       │ 1      │
+│ 1       │ 'long' │
+└─────────┴────────┘";
+        assert_eq!(table(&headers, &rows), expected);
+    }
+}
@@ -0,0 +1,357 @@
+//! Date field with a calendar popup for `datePicker({ value, onChange })` elements
+//!
+//! Clicking the field opens a month calendar below it. Picking a day, or
+//! moving to it with the keyboard and pressing Enter, calls `onChange` with
+//! its `YYYY-MM-DD` value and closes the popup. Days outside `min`/`max`
+//! can't be picked. Labels come from `dates`, in the picker's `locale`.
+//!
+//! Keys reach the open calendar from the window (see
+//! [`CalendarTracker::key`]): arrows move by a day or a week, Page Up/Down
+//! by a month (with Shift, a year), Home/End to the start or end of the
+//! week, and Escape closes it.
+
+use gpui::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::dates::{self, Day, Style};
+use crate::elements::RenderContext;
+
+/// Weeks shown per month, so the popup doesn't change height
+const WEEKS: i64 = 6;
+
+/// Props of a `datePicker` element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatePicker {
+    pub value: Option<Day>,
+    pub min: Option<Day>,
+    pub max: Option<Day>,
+    /// BCP 47 tag for labels; the user's locale if unset
+    pub locale: Option<String>,
+    /// Today in the app's local time, as JS sees it
+    pub today: Option<Day>,
+    /// Field text while there is no value
+    pub placeholder: Option<String>,
+}
+
+/// Result of a key press while a calendar is open
+#[derive(Debug, Clone, PartialEq)]
+pub enum CalendarKey {
+    /// The focused day moved or the calendar closed
+    Updated,
+    /// Enter on a day: call the picker's `onChange` with it
+    Commit { picker: String, value: String },
+}
+
+/// Open calendars, kept across frames
+///
+/// Works like [`PickerTracker`](crate::colorpicker::PickerTracker): cheap to
+/// clone, and pickers not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct CalendarTracker {
+    open: Rc<RefCell<HashMap<String, Calendar>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Calendar {
+    /// Day with the keyboard focus; its month is the one shown
+    focused: Day,
+    min: Option<Day>,
+    max: Option<Day>,
+    /// 0 = Sunday
+    first_weekday: u32,
+}
+
+impl Calendar {
+    /// First day of the week containing `day`
+    fn week_start(&self, day: Day) -> Day {
+        day.add_days(-(((day.weekday() + 7 - self.first_weekday) % 7) as i64))
+    }
+}
+
+impl CalendarTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn calendar(&self, id: &str) -> Option<Calendar> {
+        self.seen.borrow_mut().insert(id.to_string());
+        self.open.borrow().get(id).copied()
+    }
+
+    /// Open a picker's calendar, closing any other
+    fn open(&self, id: &str, calendar: Calendar) {
+        let mut open = self.open.borrow_mut();
+        open.clear();
+        open.insert(id.to_string(), calendar);
+    }
+
+    fn close(&self, id: &str) -> bool {
+        self.open.borrow_mut().remove(id).is_some()
+    }
+
+    /// Show another month, keeping the focus on the same day of the month
+    fn turn(&self, id: &str, months: i32) {
+        if let Some(calendar) = self.open.borrow_mut().get_mut(id) {
+            calendar.focused = calendar.focused.add_months(months);
+        }
+    }
+
+    /// Handle a key press for the open calendar, if any
+    pub fn key(&self, keystroke: &Keystroke) -> Option<CalendarKey> {
+        let mut open = self.open.borrow_mut();
+        let (id, calendar) = open.iter_mut().next()?;
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt {
+            return None;
+        }
+        let focused = calendar.focused;
+        let moved = match keystroke.key.as_str() {
+            "left" => focused.add_days(-1),
+            "right" => focused.add_days(1),
+            "up" => focused.add_days(-7),
+            "down" => focused.add_days(7),
+            "pageup" => focused.add_months(if modifiers.shift { -12 } else { -1 }),
+            "pagedown" => focused.add_months(if modifiers.shift { 12 } else { 1 }),
+            "home" => calendar.week_start(focused),
+            "end" => calendar.week_start(focused).add_days(6),
+            "enter" | "space" => {
+                let commit = CalendarKey::Commit { picker: id.clone(), value: focused.to_iso() };
+                open.clear();
+                return Some(commit);
+            }
+            "escape" => {
+                open.clear();
+                return Some(CalendarKey::Updated);
+            }
+            _ => return None,
+        };
+        calendar.focused = moved.clamp(calendar.min, calendar.max);
+        Some(CalendarKey::Updated)
+    }
+
+    /// End a render pass, closing calendars whose picker is gone
+    pub fn finish_frame(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.open.borrow_mut().retain(|id, _| seen.contains(id));
+    }
+}
+
+/// Draw the field, and the calendar below it while open
+pub fn render(
+    id: &str,
+    picker: &DatePicker,
+    on_change: Option<Box<dyn Fn(String, &mut Window, &mut App) + 'static>>,
+    render_ctx: &RenderContext,
+) -> AnyElement {
+    let calendars = render_ctx.calendars;
+    let labels = dates::labels(picker.locale.as_deref());
+    let zoom = render_ctx.zoom;
+    let border = if render_ctx.dark { rgb(0x4b5563) } else { rgb(0xd1d5db) };
+    let surface = if render_ctx.dark { rgb(0x1f2937) } else { rgb(0xffffff) };
+
+    let label = match (picker.value, &picker.placeholder) {
+        (Some(value), _) => labels.format(value, Style::Medium),
+        (None, Some(placeholder)) => placeholder.clone(),
+        (None, None) => "Select a date".to_string(),
+    };
+    let open = calendars.calendar(id);
+    let (tracker, picker_id) = (calendars.clone(), id.to_string());
+    let initial = Calendar {
+        focused: picker.value.or(picker.today).unwrap_or(Day { year: 2000, month: 1, day: 1 }).clamp(picker.min, picker.max),
+        min: picker.min,
+        max: picker.max,
+        first_weekday: labels.first_weekday,
+    };
+    let field = div()
+        .id(ElementId::Name(format!("{}-field", id).into()))
+        .px(px(8.0 * zoom))
+        .py(px(4.0 * zoom))
+        .rounded(px(4.0 * zoom))
+        .border_1()
+        .border_color(if open.is_some() { rgb(0x3b82f6) } else { border })
+        .bg(surface)
+        .text_size(px(14.0 * zoom))
+        .cursor_pointer()
+        .child(label)
+        .on_click(move |_, window, _| {
+            tracker.open(&picker_id, initial);
+            window.refresh();
+        });
+
+    let mut d = div().flex().flex_col().child(field);
+    let Some(calendar) = open else {
+        return d.into_any_element();
+    };
+    let on_change: Rc<dyn Fn(String, &mut Window, &mut App)> = match on_change {
+        Some(on_change) => Rc::from(on_change),
+        None => Rc::new(|_, _, _| {}),
+    };
+
+    // Header: previous month, heading, next month
+    let turn = |months: i32, glyph: &'static str| {
+        let (tracker, picker_id) = (calendars.clone(), id.to_string());
+        div()
+            .id(ElementId::Name(format!("{}-turn{}", id, months).into()))
+            .px(px(8.0 * zoom))
+            .rounded(px(4.0 * zoom))
+            .cursor_pointer()
+            .hover(|style| style.bg(Hsla::from(rgb(0x3b82f6)).opacity(0.15)))
+            .child(glyph)
+            .on_click(move |_, window, _| {
+                tracker.turn(&picker_id, months);
+                window.refresh();
+            })
+    };
+    let header = div()
+        .flex()
+        .flex_row()
+        .items_center()
+        .justify_between()
+        .child(turn(-1, "‹"))
+        .child(div().font_weight(FontWeight::SEMIBOLD).child(labels.month_year(calendar.focused)))
+        .child(turn(1, "›"));
+
+    let cell = px(32.0 * zoom);
+    let mut weekdays = div().flex().flex_row();
+    for offset in 0..7 {
+        weekdays = weekdays.child(
+            div()
+                .w(cell)
+                .flex()
+                .justify_center()
+                .text_size(px(12.0 * zoom))
+                .opacity(0.6)
+                .child(labels.weekday(calendar.first_weekday + offset).to_string()),
+        );
+    }
+
+    let month = calendar.focused.first_of_month();
+    let start = calendar.week_start(month);
+    let mut grid = div().flex().flex_col();
+    for week in 0..WEEKS {
+        let mut row = div().flex().flex_row();
+        for weekday in 0..7 {
+            let day = start.add_days(week * 7 + weekday);
+            let enabled = day.clamp(picker.min, picker.max) == day;
+            let selected = picker.value == Some(day);
+            let mut button = div()
+                .id(ElementId::Name(format!("{}-{}", id, day.to_iso()).into()))
+                .size(cell)
+                .flex()
+                .items_center()
+                .justify_center()
+                .rounded(px(4.0 * zoom))
+                .text_size(px(13.0 * zoom))
+                .child(day.day.to_string());
+            if day.month != month.month {
+                button = button.opacity(0.4);
+            }
+            if picker.today == Some(day) {
+                button = button.font_weight(FontWeight::BOLD);
+            }
+            if day == calendar.focused {
+                button = button.border_2().border_color(rgb(0x3b82f6));
+            }
+            if selected {
+                button = button.bg(rgb(0x3b82f6)).text_color(white());
+            }
+            if enabled {
+                let (tracker, picker_id, change) = (calendars.clone(), id.to_string(), on_change.clone());
+                button = button
+                    .cursor_pointer()
+                    .hover(|style| style.bg(Hsla::from(rgb(0x3b82f6)).opacity(0.15)))
+                    .on_click(move |_, window, cx| {
+                        tracker.close(&picker_id);
+                        change(day.to_iso(), window, cx);
+                    });
+            } else {
+                button = button.opacity(0.25);
+            }
+            row = row.child(button);
+        }
+        grid = grid.child(row);
+    }
+
+    // Clicks outside the popup close it
+    let (tracker, picker_id) = (calendars.clone(), id.to_string());
+    let popup = div()
+        .id(ElementId::Name(format!("{}-calendar", id).into()))
+        .mt(px(4.0 * zoom))
+        .p(px(8.0 * zoom))
+        .flex()
+        .flex_col()
+        .gap(px(4.0 * zoom))
+        .rounded(px(6.0 * zoom))
+        .border_1()
+        .border_color(border)
+        .bg(surface)
+        .shadow_lg()
+        .child(header)
+        .child(weekdays)
+        .child(grid)
+        .on_mouse_down_out(move |_, window, _| {
+            if tracker.close(&picker_id) {
+                window.refresh();
+            }
+        });
+    d = d.child(deferred(anchored().snap_to_window().child(popup)).with_priority(1));
+    d.into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(iso: &str) -> Day {
+        Day::parse(iso).unwrap()
+    }
+
+    fn press(tracker: &CalendarTracker, key: &str) -> Option<CalendarKey> {
+        tracker.key(&Keystroke::parse(key).unwrap())
+    }
+
+    #[test]
+    fn test_keyboard_navigation() {
+        let tracker = CalendarTracker::new();
+        assert_eq!(press(&tracker, "left"), None);
+
+        let calendar = Calendar {
+            focused: day("2024-03-01"),
+            min: Some(day("2024-02-20")),
+            max: Some(day("2024-06-30")),
+            first_weekday: 1,
+        };
+        tracker.open("picker", calendar);
+        press(&tracker, "right");
+        press(&tracker, "down");
+        assert_eq!(tracker.calendar("picker").unwrap().focused, day("2024-03-09"));
+        // Monday-first weeks
+        press(&tracker, "home");
+        assert_eq!(tracker.calendar("picker").unwrap().focused, day("2024-03-04"));
+        press(&tracker, "end");
+        assert_eq!(tracker.calendar("picker").unwrap().focused, day("2024-03-10"));
+        // Clamped to min and max
+        press(&tracker, "pageup");
+        assert_eq!(tracker.calendar("picker").unwrap().focused, day("2024-02-20"));
+        press(&tracker, "shift-pagedown");
+        assert_eq!(tracker.calendar("picker").unwrap().focused, day("2024-06-30"));
+
+        assert_eq!(
+            press(&tracker, "enter"),
+            Some(CalendarKey::Commit { picker: "picker".to_string(), value: "2024-06-30".to_string() })
+        );
+        assert!(tracker.calendar("picker").is_none());
+    }
+
+    #[test]
+    fn test_week_start() {
+        let sunday_first = Calendar { focused: day("2024-03-01"), min: None, max: None, first_weekday: 0 };
+        assert_eq!(sunday_first.week_start(day("2024-03-01")), day("2024-02-25"));
+        assert_eq!(sunday_first.week_start(day("2024-03-03")), day("2024-03-03"));
+        let monday_first = Calendar { first_weekday: 1, ..sunday_first };
+        assert_eq!(monday_first.week_start(day("2024-03-03")), day("2024-02-26"));
+    }
+}
@@ -0,0 +1,289 @@
+//! Calendar dates and locale-aware date labels
+//!
+//! Dates travel between JS and native code as ISO `YYYY-MM-DD` strings, which
+//! [`Day`] parses and does calendar arithmetic on. Like `collation`, labels
+//! come from ICU4X's compiled-in data, since QuickJS has no `Intl`:
+//! `rasen.formatDate(value, { locale, style })` for apps, and [`labels`] for
+//! the date picker's month headings and weekday names.
+
+use icu_calendar::week::WeekCalculator;
+use icu_calendar::{Date, DateTime, Gregorian};
+use icu_datetime::options::{components, length};
+use icu_datetime::{TypedDateFormatter, TypedDateTimeFormatter};
+use icu_locid::Locale;
+use rquickjs::{Ctx, Exception, Function, Object};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::collation;
+
+/// Labels by locale tag
+static LABELS: Mutex<Option<HashMap<String, Arc<DateLabels>>>> = Mutex::new(None);
+
+/// A day in the proleptic Gregorian calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Day {
+    pub year: i32,
+    /// 1-12
+    pub month: u32,
+    /// 1-31
+    pub day: u32,
+}
+
+impl Day {
+    /// Parse `YYYY-MM-DD`, ignoring a time part (`2024-03-01T12:00:00Z`)
+    pub fn parse(value: &str) -> Option<Self> {
+        let date = value.trim().split('T').next()?;
+        let mut parts = date.split('-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let day = Day { year: year.parse().ok()?, month: month.parse().ok()?, day: day.parse().ok()? };
+        let valid = (1..=12).contains(&day.month) && (1..=days_in_month(day.year, day.month)).contains(&day.day);
+        valid.then_some(day)
+    }
+
+    /// `YYYY-MM-DD`
+    pub fn to_iso(self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// Days since 1970-01-01
+    fn to_days(self) -> i64 {
+        // Howard Hinnant's days_from_civil, with years starting in March
+        let year = self.year as i64 - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Day { year: year as i32, month: month as u32, day: day as u32 }
+    }
+
+    pub fn add_days(self, days: i64) -> Self {
+        Self::from_days(self.to_days() + days)
+    }
+
+    /// Move by whole months, clamping the day to the target month's length
+    pub fn add_months(self, months: i32) -> Self {
+        let index = self.year * 12 + self.month as i32 - 1 + months;
+        let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+        Day { year, month, day: self.day.min(days_in_month(year, month)) }
+    }
+
+    /// Day of the week, 0 = Sunday
+    pub fn weekday(self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (self.to_days() + 4).rem_euclid(7) as u32
+    }
+
+    pub fn first_of_month(self) -> Self {
+        Day { day: 1, ..self }
+    }
+
+    /// Clamp to an optional range
+    pub fn clamp(self, min: Option<Day>, max: Option<Day>) -> Self {
+        let day = min.map_or(self, |min| self.max(min));
+        max.map_or(day, |max| day.min(max))
+    }
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Length of a formatted date, like `Intl.DateTimeFormat`'s `dateStyle`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// `3/1/24`
+    Short,
+    /// `Mar 1, 2024`
+    Medium,
+    /// `March 1, 2024`
+    Long,
+    /// `Friday, March 1, 2024`
+    Full,
+}
+
+impl Style {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "short" => Some(Style::Short),
+            "medium" => Some(Style::Medium),
+            "long" => Some(Style::Long),
+            "full" => Some(Style::Full),
+            _ => None,
+        }
+    }
+}
+
+/// Date formatters and calendar conventions of a locale
+pub struct DateLabels {
+    pub locale: Locale,
+    /// Formatters by [`Style`], in declaration order
+    dates: [TypedDateFormatter<Gregorian>; 4],
+    month_year: TypedDateTimeFormatter<Gregorian>,
+    /// Short weekday names, Sunday first
+    weekdays: [String; 7],
+    /// Day weeks start on, 0 = Sunday
+    pub first_weekday: u32,
+}
+
+impl DateLabels {
+    /// Build labels; `None` when ICU has no data for the locale
+    fn new(locale: &Locale) -> Option<Self> {
+        let data_locale = locale.into();
+        let date = |style| TypedDateFormatter::<Gregorian>::try_new_with_length(&data_locale, style).ok();
+        let dates = [
+            date(length::Date::Short)?,
+            date(length::Date::Medium)?,
+            date(length::Date::Long)?,
+            date(length::Date::Full)?,
+        ];
+
+        let mut month_year = components::Bag::default();
+        month_year.year = Some(components::Year::Numeric);
+        month_year.month = Some(components::Month::Long);
+        let month_year = TypedDateTimeFormatter::<Gregorian>::try_new_experimental(&data_locale, month_year.into()).ok()?;
+
+        let mut weekday = components::Bag::default();
+        weekday.weekday = Some(components::Text::Short);
+        let weekday = TypedDateTimeFormatter::<Gregorian>::try_new_experimental(&data_locale, weekday.into()).ok()?;
+        // 2023-01-01 was a Sunday
+        let weekdays = std::array::from_fn(|offset| {
+            DateTime::try_new_gregorian_datetime(2023, 1, 1 + offset as u8, 0, 0, 0)
+                .map(|date| weekday.format_to_string(&date))
+                .unwrap_or_default()
+        });
+
+        let first_weekday = WeekCalculator::try_new(&data_locale).map_or(1, |week| week.first_weekday as u32 % 7);
+        Some(Self { locale: locale.clone(), dates, month_year, weekdays, first_weekday })
+    }
+
+    pub fn format(&self, day: Day, style: Style) -> String {
+        match Date::try_new_gregorian_date(day.year, day.month as u8, day.day as u8) {
+            Ok(date) => self.dates[style as usize].format_to_string(&date),
+            Err(_) => day.to_iso(),
+        }
+    }
+
+    /// Heading for the month containing `day`, e.g. `March 2024`
+    pub fn month_year(&self, day: Day) -> String {
+        match DateTime::try_new_gregorian_datetime(day.year, day.month as u8, 1, 0, 0, 0) {
+            Ok(date) => self.month_year.format_to_string(&date),
+            Err(_) => format!("{:04}-{:02}", day.year, day.month),
+        }
+    }
+
+    /// Short name of a weekday, 0 = Sunday
+    pub fn weekday(&self, weekday: u32) -> &str {
+        &self.weekdays[weekday as usize % 7]
+    }
+}
+
+/// Labels for a BCP 47 locale tag, or the user's `LC_TIME` locale
+///
+/// Unknown or invalid locales fall back to the root locale's conventions.
+pub fn labels(locale: Option<&str>) -> Arc<DateLabels> {
+    let locale = locale
+        .and_then(|tag| tag.parse::<Locale>().ok())
+        .unwrap_or_else(|| collation::system_locale("LC_TIME"));
+    let key = locale.to_string();
+
+    let mut cache = LABELS.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some(labels) = cache.get(&key) {
+        return labels.clone();
+    }
+    let labels = DateLabels::new(&locale)
+        .or_else(|| DateLabels::new(&Locale::UND))
+        .expect("compiled ICU data covers the root locale");
+    cache.entry(key).or_insert(Arc::new(labels)).clone()
+}
+
+/// Install `rasen.formatDate(value, { locale, style })`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    rasen.set(
+        "formatDate",
+        Function::new(
+            ctx.clone(),
+            |ctx: Ctx<'js>, value: String, options: Option<Object<'js>>| -> rquickjs::Result<String> {
+                let day = Day::parse(&value)
+                    .ok_or_else(|| Exception::throw_range(&ctx, &format!("Invalid date '{}', expected YYYY-MM-DD", value)))?;
+                let (locale, style) = match &options {
+                    Some(options) => (
+                        options.get::<_, Option<String>>("locale")?,
+                        options.get::<_, Option<String>>("style")?,
+                    ),
+                    None => (None, None),
+                };
+                if let Some(tag) = &locale {
+                    tag.parse::<Locale>()
+                        .map_err(|_| Exception::throw_range(&ctx, &format!("Invalid locale '{}'", tag)))?;
+                }
+                let style = match style.as_deref() {
+                    Some(name) => Style::from_name(name)
+                        .ok_or_else(|| Exception::throw_range(&ctx, &format!("Invalid style '{}'", name)))?,
+                    None => Style::Medium,
+                };
+                Ok(labels(locale.as_deref()).format(day, style))
+            },
+        )?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_arithmetic() {
+        let day = Day::parse("2024-02-29").unwrap();
+        assert_eq!(day.to_iso(), "2024-02-29");
+        assert_eq!(Day::parse("2024-03-01T12:00:00Z"), Some(Day { year: 2024, month: 3, day: 1 }));
+        assert_eq!(Day::parse("2023-02-29"), None);
+        assert_eq!(Day::parse("2024-13-01"), None);
+        assert_eq!(Day::parse("24-1-1"), None);
+
+        assert_eq!(day.add_days(1).to_iso(), "2024-03-01");
+        assert_eq!(day.add_days(-366).to_iso(), "2023-02-28");
+        assert_eq!(Day::parse("2024-01-31").unwrap().add_months(1).to_iso(), "2024-02-29");
+        assert_eq!(Day::parse("2024-01-15").unwrap().add_months(-13).to_iso(), "2022-12-15");
+        // Thursday
+        assert_eq!(Day::parse("1970-01-01").unwrap().weekday(), 4);
+        assert_eq!(day.weekday(), 4);
+    }
+
+    #[test]
+    fn test_labels() {
+        let day = Day::parse("2024-03-01").unwrap();
+        let en = labels(Some("en-US"));
+        assert_eq!(en.format(day, Style::Medium), "Mar 1, 2024");
+        assert_eq!(en.month_year(day), "March 2024");
+        assert_eq!(en.weekday(0), "Sun");
+        assert_eq!(en.first_weekday, 0);
+
+        let de = labels(Some("de-DE"));
+        assert_eq!(de.month_year(day), "März 2024");
+        assert_eq!(de.first_weekday, 1);
+    }
+}
@@ -0,0 +1,672 @@
+//! Serialized element descriptors coming from the JS side
+//!
+//! Instead of walking the JS object graph property by property, the runtime
+//! asks JS to encode the whole tree once per render (`__encodeTree`) and
+//! deserializes it here with serde. Functions can't travel through JSON, so
+//! handlers are replaced by stable handler IDs assigned on the JS side.
+
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use crate::camera::CameraView;
+use crate::dates::Day;
+use crate::datepicker::DatePicker;
+use crate::elements::{Element, DivElement, TextElement, EventHandlers};
+use crate::event_manager::HandlerId;
+use crate::files;
+use crate::images::{self, ImageSrc, ImageView};
+use crate::keymap::Keymap;
+use crate::sanitize;
+use crate::selectable::Selection;
+use crate::shader::ShaderView;
+use crate::throttle::Rate;
+use crate::tree::{TreeRow, TreeView};
+use crate::tw_parser::{self, ParsedStyles};
+
+/// Trees with fewer nodes than this resolve styles on the calling thread,
+/// where spawning rayon work would cost more than it saves
+const PARALLEL_THRESHOLD: usize = 256;
+
+/// Deepest element nesting accepted from JS
+///
+/// Rendering and GPUI layout still recurse per level, so this keeps them well
+/// within the native stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// Default element count above which a render logs a large-tree warning
+pub const LARGE_TREE_WARNING: usize = 5_000;
+
+/// Element descriptor as produced by `__encodeTree`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ElementDescriptor {
+    Div {
+        #[serde(default)]
+        class: String,
+        #[serde(default, rename = "testId")]
+        test_id: Option<String>,
+        #[serde(default)]
+        key: Option<String>,
+        #[serde(default)]
+        children: Vec<ElementDescriptor>,
+        #[serde(default)]
+        handlers: DescriptorHandlers,
+        #[serde(default)]
+        rates: DescriptorRates,
+        #[serde(default)]
+        keymap: Option<DescriptorKeymap>,
+        #[serde(default, rename = "dragHandle")]
+        drag_handle: bool,
+        #[serde(default, rename = "colorPicker")]
+        color_picker: Option<DescriptorColorPicker>,
+        #[serde(default, rename = "datePicker")]
+        date_picker: Option<DescriptorDatePicker>,
+        #[serde(default)]
+        tree: Option<DescriptorTree>,
+        #[serde(default)]
+        selectable: Option<DescriptorSelectable>,
+        #[serde(default)]
+        image: Option<DescriptorImage>,
+        #[serde(default)]
+        camera: Option<DescriptorCamera>,
+        #[serde(default)]
+        shader: Option<DescriptorShader>,
+        #[serde(default, rename = "ref")]
+        element_ref: Option<String>,
+    },
+    Text {
+        #[serde(default)]
+        class: String,
+        #[serde(default, rename = "testId")]
+        test_id: Option<String>,
+        #[serde(default)]
+        key: Option<String>,
+        #[serde(default)]
+        text: String,
+    },
+    /// A child left out by `show()`: it renders nothing but holds its
+    /// position, so the IDs of its siblings don't change when it toggles
+    Empty,
+    /// A `memo()` subtree whose inputs didn't change: the element on screen
+    /// with this ID is kept as it is (see `diff`)
+    Skip {
+        #[serde(default)]
+        key: Option<String>,
+    },
+}
+
+/// Element count of a tree and its widest elements, for the large-tree warning
+#[derive(Debug)]
+pub struct TreeSize {
+    /// Number of elements in the tree
+    pub total: usize,
+    /// Elements with the most children, widest first
+    pub widest: Vec<Subtree>,
+}
+
+/// An element with many children, usually a list
+#[derive(Debug)]
+pub struct Subtree {
+    /// Path ID, as assigned by `into_element` (`elem_0.2.1`)
+    pub id: String,
+    pub test_id: Option<String>,
+    pub class: String,
+    /// Direct children
+    pub children: usize,
+    /// Elements in the subtree, including this one
+    pub elements: usize,
+}
+
+/// Handler IDs assigned by `__registerHandler`
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorHandlers {
+    pub click: Option<HandlerId>,
+    pub mouseenter: Option<HandlerId>,
+    pub mouseleave: Option<HandlerId>,
+    pub mousedown: Option<HandlerId>,
+    pub mousemove: Option<HandlerId>,
+    pub mouseup: Option<HandlerId>,
+    pub reorder: Option<HandlerId>,
+    pub change: Option<HandlerId>,
+    pub toggle: Option<HandlerId>,
+    pub select: Option<HandlerId>,
+    pub selectionchange: Option<HandlerId>,
+    pub cut: Option<HandlerId>,
+    pub copy: Option<HandlerId>,
+    pub paste: Option<HandlerId>,
+    pub selectall: Option<HandlerId>,
+}
+
+/// Throttle or debounce intervals of handlers, in milliseconds (see `throttle`)
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorRates {
+    pub mousemove: Option<DescriptorRate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DescriptorRate {
+    pub throttle: Option<f64>,
+    pub debounce: Option<f64>,
+}
+
+impl DescriptorRate {
+    fn into_rate(self) -> Option<Rate> {
+        Rate::from_millis(self.throttle, self.debounce)
+    }
+}
+
+/// Bindings of a `keymap` element, with handler IDs as above
+#[derive(Debug, Deserialize)]
+pub struct DescriptorKeymap {
+    #[serde(default)]
+    pub scope: String,
+    /// `null` blocks the key from outer scopes
+    #[serde(default)]
+    pub bindings: BTreeMap<String, Option<HandlerId>>,
+}
+
+/// Current value of a `colorPicker` element
+#[derive(Debug, Deserialize)]
+pub struct DescriptorColorPicker {
+    #[serde(default)]
+    pub value: String,
+}
+
+/// Props of a `datePicker` element, with dates as `YYYY-MM-DD`
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorDatePicker {
+    pub value: Option<String>,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    pub locale: Option<String>,
+    pub today: Option<String>,
+    pub placeholder: Option<String>,
+}
+
+impl DescriptorDatePicker {
+    fn into_picker(self) -> DatePicker {
+        let day = |value: Option<String>| value.as_deref().and_then(Day::parse);
+        DatePicker {
+            value: day(self.value),
+            min: day(self.min),
+            max: day(self.max),
+            locale: self.locale,
+            today: day(self.today),
+            placeholder: self.placeholder,
+        }
+    }
+}
+
+/// Visible rows of a `tree` element, flattened on the JS side
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorTree {
+    #[serde(default)]
+    pub rows: Vec<TreeRow>,
+    pub selected: Option<String>,
+}
+
+/// Item keys and selected keys of a `selectable` element
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorSelectable {
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub selected: Vec<String>,
+}
+
+/// Props of an `img` element: a `rasen.image` handle ID or a file path
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorImage {
+    pub id: Option<u32>,
+    pub path: Option<String>,
+    pub fit: Option<String>,
+}
+
+impl DescriptorImage {
+    fn into_view(self) -> ImageView {
+        let src = match (self.id, self.path) {
+            (Some(id), _) => Some(ImageSrc::Handle(id)),
+            // Files are shown only from the directories scripts may read
+            (None, Some(path)) => match files::check(&path) {
+                Ok(path) => Some(ImageSrc::Path(path)),
+                Err(error) => {
+                    eprintln!("Warning: not showing image: {}", error.message);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+        ImageView { src, fit: images::object_fit(self.fit.as_deref()) }
+    }
+}
+
+/// Props of a `camera` element
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorCamera {
+    #[serde(rename = "deviceId")]
+    pub device_id: Option<String>,
+    pub fit: Option<String>,
+}
+
+impl DescriptorCamera {
+    fn into_view(self) -> CameraView {
+        CameraView { device: self.device_id, fit: images::object_fit(self.fit.as_deref()) }
+    }
+}
+
+/// Props of a `shader` element
+#[derive(Debug, Default, Deserialize)]
+pub struct DescriptorShader {
+    pub fragment: String,
+    #[serde(default)]
+    pub uniforms: BTreeMap<String, DescriptorUniform>,
+    #[serde(default)]
+    pub animate: bool,
+}
+
+/// A uniform's value: a number, or an array of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DescriptorUniform {
+    Number(f32),
+    Vector(Vec<f32>),
+}
+
+impl DescriptorShader {
+    fn into_view(self) -> ShaderView {
+        let uniforms = self
+            .uniforms
+            .into_iter()
+            .map(|(name, value)| match value {
+                DescriptorUniform::Number(value) => (name, vec![value]),
+                DescriptorUniform::Vector(values) => (name, values),
+            })
+            .collect();
+        ShaderView { fragment: self.fragment, uniforms, animate: self.animate }
+    }
+}
+
+impl ElementDescriptor {
+    pub fn class(&self) -> &str {
+        match self {
+            ElementDescriptor::Div { class, .. } | ElementDescriptor::Text { class, .. } => class,
+            ElementDescriptor::Empty | ElementDescriptor::Skip { .. } => "",
+        }
+    }
+    
+    fn test_id(&self) -> Option<&str> {
+        match self {
+            ElementDescriptor::Div { test_id, .. } | ElementDescriptor::Text { test_id, .. } => test_id.as_deref(),
+            ElementDescriptor::Empty | ElementDescriptor::Skip { .. } => None,
+        }
+    }
+    
+    fn key(&self) -> Option<&str> {
+        match self {
+            ElementDescriptor::Div { key, .. } | ElementDescriptor::Text { key, .. } | ElementDescriptor::Skip { key } => key.as_deref(),
+            ElementDescriptor::Empty => None,
+        }
+    }
+    
+    fn children(&self) -> &[ElementDescriptor] {
+        match self {
+            ElementDescriptor::Div { children, .. } => children,
+            ElementDescriptor::Text { .. } | ElementDescriptor::Empty | ElementDescriptor::Skip { .. } => &[],
+        }
+    }
+    
+    /// Whether this is a placeholder left by `show()`
+    pub fn is_empty(&self) -> bool {
+        matches!(self, ElementDescriptor::Empty)
+    }
+    
+    /// Whether the tree has skipped `memo()` subtrees, which only a diff
+    /// against the tree on screen can fill in
+    pub fn has_skips(&self) -> bool {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if matches!(node, ElementDescriptor::Skip { .. }) {
+                return true;
+            }
+            stack.extend(node.children());
+        }
+        false
+    }
+    
+    /// Number of elements in the tree
+    pub fn count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.children().iter().filter(|child| !child.is_empty()));
+        }
+        count
+    }
+    
+    /// Count the elements of the tree and find its `limit` widest elements
+    pub fn size(&self, limit: usize) -> TreeSize {
+        // Flatten in pre-order as (parent index, ID, node)
+        let mut nodes: Vec<(Option<usize>, String, &ElementDescriptor)> = Vec::new();
+        let mut stack = vec![(None, "elem_0".to_string(), self)];
+        while let Some((parent, id, node)) = stack.pop() {
+            let index = nodes.len();
+            for (child_id, child) in child_ids(&id, node.children()).into_iter().zip(node.children()).rev() {
+                if !child.is_empty() {
+                    stack.push((Some(index), child_id, child));
+                }
+            }
+            nodes.push((parent, id, node));
+        }
+        
+        // Subtree sizes, children before parents
+        let mut elements = vec![1; nodes.len()];
+        for index in (1..nodes.len()).rev() {
+            if let Some(parent) = nodes[index].0 {
+                elements[parent] += elements[index];
+            }
+        }
+        
+        let mut widest: Vec<usize> = (0..nodes.len()).filter(|&i| !nodes[i].2.children().is_empty()).collect();
+        widest.sort_by_key(|&i| std::cmp::Reverse((nodes[i].2.children().len(), elements[i])));
+        widest.truncate(limit);
+        let widest = widest
+            .into_iter()
+            .map(|index| {
+                let node = nodes[index].2;
+                Subtree {
+                    id: nodes[index].1.clone(),
+                    test_id: node.test_id().map(str::to_string),
+                    class: node.class().to_string(),
+                    children: node.children().len(),
+                    elements: elements[index],
+                }
+            })
+            .collect();
+        
+        TreeSize { total: nodes.len(), widest }
+    }
+    
+    /// Convert into a renderable element
+    ///
+    /// Class strings are resolved up front (in parallel for large trees) since
+    /// parsing is a pure function of the class string, and repeated strings
+    /// share one cached `ParsedStyles`. The tree is walked with
+    /// explicit stacks rather than recursion so deep trees can't overflow the
+    /// native stack.
+    ///
+    /// IDs follow the path from the root (see [`child_ids`]), so an element
+    /// keeps its ID across re-renders and GPUI state (hover, active,
+    /// transitions) carries over.
+    pub fn into_element(self) -> Element {
+        self.into_element_at("elem_0".to_string())
+    }
+    
+    /// Convert into a renderable element with ID `id`, for subtrees added by
+    /// a re-render (see `diff`)
+    pub fn into_element_at(self, id: String) -> Element {
+        // Flatten in pre-order as (parent index, ID, descriptor without children)
+        let mut nodes: Vec<(Option<usize>, String, ElementDescriptor)> = Vec::new();
+        let mut stack = vec![(None, id, self)];
+        while let Some((parent, id, mut descriptor)) = stack.pop() {
+            let index = nodes.len();
+            if let ElementDescriptor::Div { children, .. } = &mut descriptor {
+                let ids = child_ids(&id, children);
+                // Reversed, so the first child is visited next
+                for (child_id, child) in ids.into_iter().zip(std::mem::take(children)).rev() {
+                    // Placeholders only count for their siblings' IDs
+                    if !child.is_empty() {
+                        stack.push((Some(index), child_id, child));
+                    }
+                }
+            }
+            nodes.push((parent, id, descriptor));
+        }
+        
+        let styles: Vec<Arc<ParsedStyles>> = if nodes.len() < PARALLEL_THRESHOLD {
+            nodes.iter().map(|(_, _, node)| tw_parser::parse_cached(node.class())).collect()
+        } else {
+            nodes.par_iter().map(|(_, _, node)| tw_parser::parse_cached(node.class())).collect()
+        };
+        
+        // Rebuild bottom-up: in reverse pre-order, children come before parents
+        let mut children: Vec<Vec<Element>> = nodes.iter().map(|_| Vec::new()).collect();
+        let mut root = None;
+        for (index, ((parent, id, descriptor), resolved)) in nodes.into_iter().zip(styles).enumerate().rev() {
+            let (mut element, _) = descriptor.into_node(id, resolved);
+            if let Element::Div(div) = &mut element {
+                div.children = std::mem::take(&mut children[index]);
+                div.children.reverse();
+            }
+            match parent {
+                Some(parent) => children[parent].push(element),
+                None => root = Some(element),
+            }
+        }
+        root.expect("the root is always flattened first")
+    }
+    
+    /// Convert this element alone, without children; returns the
+    /// descriptors of its children alongside
+    pub fn into_node(self, id: String, styles: Arc<ParsedStyles>) -> (Element, Vec<ElementDescriptor>) {
+        match self {
+            ElementDescriptor::Div { children, handlers, rates, keymap, drag_handle, color_picker, date_picker, tree, selectable, image, camera, shader, element_ref, test_id, .. } => {
+                let element = Element::Div(DivElement {
+                    id,
+                    test_id,
+                    styles,
+                    children: Vec::new(),
+                    handlers: EventHandlers {
+                        on_click: handlers.click,
+                        on_mouse_enter: handlers.mouseenter,
+                        on_mouse_leave: handlers.mouseleave,
+                        on_mouse_down: handlers.mousedown,
+                        on_mouse_move: handlers.mousemove,
+                        mouse_move_rate: rates.mousemove.and_then(DescriptorRate::into_rate),
+                        on_mouse_up: handlers.mouseup,
+                        on_reorder: handlers.reorder,
+                        on_change: handlers.change,
+                        on_toggle: handlers.toggle,
+                        on_select: handlers.select,
+                        on_selection_change: handlers.selectionchange,
+                        on_cut: handlers.cut,
+                        on_copy: handlers.copy,
+                        on_paste: handlers.paste,
+                        on_select_all: handlers.selectall,
+                    },
+                    keymap: keymap.map(|keymap| Keymap::new(keymap.scope, keymap.bindings)),
+                    drag_handle,
+                    color_picker: color_picker.map(|picker| picker.value),
+                    date_picker: date_picker.map(DescriptorDatePicker::into_picker),
+                    tree: tree.map(|tree| TreeView { rows: Arc::new(tree.rows), selected: tree.selected }),
+                    selectable: selectable.map(|s| Selection { keys: s.keys, selected: s.selected }),
+                    image: image.map(DescriptorImage::into_view),
+                    camera: camera.map(DescriptorCamera::into_view),
+                    shader: shader.map(DescriptorShader::into_view),
+                    element_ref,
+                });
+                (element, children)
+            }
+            ElementDescriptor::Text { text, test_id, .. } => {
+                let element = Element::Text(TextElement {
+                    id,
+                    test_id,
+                    text: sanitize::text(&text),
+                    styles,
+                });
+                (element, Vec::new())
+            }
+            // Only reached for a placeholder at the root, or a skipped
+            // subtree `diff` didn't fill in
+            ElementDescriptor::Empty | ElementDescriptor::Skip { .. } => {
+                let element = Element::Text(TextElement { id, test_id: None, text: String::new(), styles });
+                (element, Vec::new())
+            }
+        }
+    }
+}
+
+/// IDs of the children of element `parent`
+///
+/// Children with a `key` are named by it (`elem_0.2[todo-7]`), so they keep
+/// their ID, and their GPUI state, when a list is reordered or filtered.
+/// Others are named by position (`elem_0.2.1`). A key repeated among
+/// siblings names only the first; the rest fall back to their position.
+pub fn child_ids(parent: &str, children: &[ElementDescriptor]) -> Vec<String> {
+    let mut keys = HashSet::new();
+    children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| match child.key() {
+            Some(key) if keys.insert(key) => format!("{}[{}]", parent, key),
+            _ => format!("{}.{}", parent, i),
+        })
+        .collect()
+}
+
+/// Decode the JSON produced by `__encodeTree`
+///
+/// Trees nested deeper than [`MAX_DEPTH`] elements are rejected with a clear
+/// error instead of exhausting the stack while parsing or laying out.
+pub fn decode(json: &str) -> serde_json::Result<ElementDescriptor> {
+    // Each element level is an object plus its `children` array
+    if json_depth(json) > 2 * MAX_DEPTH + 1 {
+        return Err(serde::de::Error::custom(format!(
+            "element tree is nested deeper than {} levels; flatten the layout or render long chains as lists",
+            MAX_DEPTH
+        )));
+    }
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    // The depth check above replaces serde_json's limit of 128 JSON levels
+    deserializer.disable_recursion_limit();
+    let descriptor = ElementDescriptor::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(descriptor)
+}
+
+/// Maximum nesting of arrays and objects in a JSON document
+fn json_depth(json: &str) -> usize {
+    let (mut depth, mut max) = (0usize, 0usize);
+    let (mut in_string, mut escaped) = (false, false);
+    for byte in json.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_handler_ids() {
+        let json = r#"{"type":"div","class":"flex","handlers":{"click":42},"children":[{"type":"text","class":"","text":"hi"}]}"#;
+        let element = decode(json).unwrap().into_element();
+        assert_eq!(element.handler_ids(), std::collections::HashSet::from([42]));
+        match element {
+            Element::Div(div) => {
+                assert_eq!(div.handlers.on_click, Some(42));
+                assert!(div.handlers.on_mouse_enter.is_none());
+                assert_eq!(div.children.len(), 1);
+            }
+            Element::Text(_) => panic!("expected div"),
+        }
+    }
+
+    #[test]
+    fn test_decode_shader() {
+        let json = r#"{"type":"div","class":"","shader":{"fragment":"fn fragment","uniforms":{"speed":2,"tint":[1,0.5,0]}}}"#;
+        match decode(json).unwrap().into_element() {
+            Element::Div(div) => {
+                let shader = div.shader.unwrap();
+                assert_eq!(shader.uniforms["speed"], vec![2.0]);
+                assert_eq!(shader.uniforms["tint"], vec![1.0, 0.5, 0.0]);
+                assert!(!shader.animate);
+            }
+            Element::Text(_) => panic!("expected div"),
+        }
+    }
+
+    #[test]
+    fn test_deep_trees() {
+        let nest = |depth: usize| {
+            let mut json = r#"{"type":"text","text":"leaf"}"#.to_string();
+            for _ in 0..depth {
+                json = format!(r#"{{"type":"div","class":"","children":[{}]}}"#, json);
+            }
+            json
+        };
+        
+        // Well past serde_json's default recursion limit
+        let mut element = decode(&nest(300)).unwrap().into_element();
+        let mut depth = 0;
+        while let Element::Div(mut div) = element {
+            element = div.children.remove(0);
+            depth += 1;
+        }
+        assert_eq!(depth, 300);
+        
+        let error = decode(&nest(MAX_DEPTH + 1)).err().unwrap();
+        assert!(error.to_string().contains("nested deeper than"));
+    }
+
+    #[test]
+    fn test_tree_size() {
+        let rows = vec![r#"{"type":"div","children":[{"type":"text","text":"a"},{"type":"text","text":"b"}]}"#; 10];
+        let json = format!(
+            r#"{{"type":"div","children":[{{"type":"text","text":"title"}},{{"type":"div","class":"flex-col","testId":"rows","children":[{}]}}]}}"#,
+            rows.join(",")
+        );
+        let descriptor = decode(&json).unwrap();
+        assert_eq!(descriptor.count(), 33);
+        
+        let size = descriptor.size(2);
+        assert_eq!(size.total, 33);
+        assert_eq!(size.widest.len(), 2);
+        let list = &size.widest[0];
+        assert_eq!(list.id, "elem_0.1");
+        assert_eq!(list.test_id.as_deref(), Some("rows"));
+        assert_eq!(list.class, "flex-col");
+        assert_eq!((list.children, list.elements), (10, 31));
+        assert_eq!(size.widest[1].children, 2);
+    }
+
+    #[test]
+    fn test_json_depth() {
+        assert_eq!(json_depth(r#"{"a":[1,{"b":"[{"}]}"#), 3);
+        assert_eq!(json_depth(r#""\"{""#), 0);
+    }
+
+    #[test]
+    fn test_ids_stable_across_decodes() {
+        let json = r#"{"type":"div","children":[{"type":"text","text":"a"},{"type":"div"}]}"#;
+        let ids = |element: Element| match element {
+            Element::Div(div) => div.children.iter().map(|child| match child {
+                Element::Div(div) => div.id.clone(),
+                Element::Text(text) => text.id.clone(),
+            }).collect::<Vec<_>>(),
+            Element::Text(_) => panic!("expected div"),
+        };
+        let first = ids(decode(json).unwrap().into_element());
+        assert_eq!(first, vec!["elem_0.0", "elem_0.1"]);
+        assert_eq!(first, ids(decode(json).unwrap().into_element()));
+    }
+}
@@ -0,0 +1,306 @@
+//! Native file and message dialogs on `rasen.dialog`
+//!
+//! `open`, `save` and `message` show the system's dialogs through rfd and
+//! return promises. Each dialog is shown from its own thread, waiting on
+//! rfd's async dialogs, which run on the main thread where the platform
+//! needs it. When the user answers, the choice is queued for the runtime,
+//! which wakes and settles the promise on the UI thread, like
+//! `rasen.notify` clicks. `resolve`/`reject` pairs stay on the JS side in a
+//! table keyed by dialog ID until then.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rfd::{AsyncFileDialog, AsyncMessageDialog, FileHandle, MessageButtons, MessageDialogResult, MessageLevel};
+use rquickjs::{Array, Ctx, Exception, Function, IntoJs, Object, Value};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::{native_api, values};
+
+/// Global holding `[resolve, reject]` by dialog ID
+const PENDING: &str = "__dialogPending";
+
+/// Whether rfd has a dialog backend here (the XDG portal on Linux), which
+/// `rasen.platform.supports('file-dialogs')` reports
+pub const SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "windows", target_os = "linux"));
+
+/// A dialog to show, with its JS options
+#[derive(Debug)]
+enum Dialog {
+    Open(OpenOptions),
+    Save(FileOptions),
+    Message(MessageOptions),
+}
+
+/// Options shared by the open and save dialogs
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct FileOptions {
+    title: Option<String>,
+    filters: Vec<Filter>,
+    /// Directory to start in, or a file name to suggest when saving
+    default_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Filter {
+    #[serde(default)]
+    name: String,
+    extensions: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct OpenOptions {
+    #[serde(flatten)]
+    file: FileOptions,
+    multiple: bool,
+    directory: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageOptions {
+    title: Option<String>,
+    message: String,
+    #[serde(default)]
+    level: Level,
+    #[serde(default)]
+    buttons: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+/// What the user chose, waiting for delivery to JS
+#[derive(Debug, PartialEq)]
+enum Choice {
+    Cancelled,
+    Path(String),
+    Paths(Vec<String>),
+    Button(String),
+}
+
+/// A runtime's open dialogs; clones share them
+#[derive(Clone)]
+pub struct Dialogs {
+    state: Arc<Mutex<DialogState>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct DialogState {
+    next_id: u32,
+    choices: Vec<(u32, Choice)>,
+}
+
+impl Dialogs {
+    /// Create an empty set; the receiver wakes when a dialog is answered
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { state: Arc::default(), wake }, wakeups)
+    }
+
+    /// Show `dialog`; returns its promise
+    fn show<'js>(&self, ctx: &Ctx<'js>, dialog: Dialog) -> rquickjs::Result<Value<'js>> {
+        let (promise, resolve, reject) = ctx.promise()?;
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            // IDs start at 1, like timers
+            state.next_id += 1;
+            state.next_id
+        };
+        let pending: Object = ctx.globals().get(PENDING)?;
+        let entry = Array::new(ctx.clone())?;
+        entry.set(0, resolve)?;
+        entry.set(1, reject)?;
+        pending.set(id.to_string(), entry)?;
+
+        let dialogs = self.clone();
+        thread::spawn(move || {
+            let choice = futures::executor::block_on(run(dialog));
+            dialogs.state.lock().unwrap().choices.push((id, choice));
+            let _ = dialogs.wake.unbounded_send(());
+        });
+        promise.into_js(ctx)
+    }
+}
+
+impl FileOptions {
+    fn dialog(self) -> AsyncFileDialog {
+        let mut dialog = AsyncFileDialog::new();
+        if let Some(title) = self.title {
+            dialog = dialog.set_title(title);
+        }
+        for filter in &self.filters {
+            let extensions: Vec<String> = filter.extensions.iter().map(|e| extension(e)).collect();
+            dialog = dialog.add_filter(&filter.name, extensions.as_slice());
+        }
+        dialog
+    }
+}
+
+fn path(file: FileHandle) -> String {
+    file.path().to_string_lossy().into_owned()
+}
+
+/// Show `dialog` and wait for the user's choice
+async fn run(dialog: Dialog) -> Choice {
+    match dialog {
+        Dialog::Open(OpenOptions { mut file, multiple, directory }) => {
+            let start = file.default_path.take();
+            let mut dialog = file.dialog();
+            if let Some(start) = start {
+                dialog = dialog.set_directory(start);
+            }
+            let picked = match (multiple, directory) {
+                (false, false) => dialog.pick_file().await.map(|file| vec![file]),
+                (false, true) => dialog.pick_folder().await.map(|file| vec![file]),
+                (true, false) => dialog.pick_files().await,
+                (true, true) => dialog.pick_folders().await,
+            };
+            match picked {
+                Some(files) if multiple => Choice::Paths(files.into_iter().map(path).collect()),
+                Some(files) => files.into_iter().next().map_or(Choice::Cancelled, |file| Choice::Path(path(file))),
+                None => Choice::Cancelled,
+            }
+        }
+        Dialog::Save(mut file) => {
+            let default = file.default_path.take();
+            let mut dialog = file.dialog();
+            if let Some(default) = default.as_deref().map(std::path::Path::new) {
+                if let Some(dir) = default.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    dialog = dialog.set_directory(dir);
+                }
+                if let Some(name) = default.file_name() {
+                    dialog = dialog.set_file_name(name.to_string_lossy());
+                }
+            }
+            dialog.save_file().await.map_or(Choice::Cancelled, |file| Choice::Path(path(file)))
+        }
+        Dialog::Message(MessageOptions { title, message, level, buttons }) => {
+            let level = match level {
+                Level::Info => MessageLevel::Info,
+                Level::Warning => MessageLevel::Warning,
+                Level::Error => MessageLevel::Error,
+            };
+            let mut dialog = AsyncMessageDialog::new().set_level(level).set_description(message);
+            if let Some(title) = title {
+                dialog = dialog.set_title(title);
+            }
+            let result = dialog.set_buttons(message_buttons(&buttons)).show().await;
+            Choice::Button(button_label(result, &buttons))
+        }
+    }
+}
+
+/// The buttons for up to three labels, the first being the default
+fn message_buttons(labels: &[String]) -> MessageButtons {
+    match labels {
+        [] => MessageButtons::Ok,
+        [ok] => MessageButtons::OkCustom(ok.clone()),
+        [ok, cancel] => MessageButtons::OkCancelCustom(ok.clone(), cancel.clone()),
+        [yes, no, cancel, ..] => MessageButtons::YesNoCancelCustom(yes.clone(), no.clone(), cancel.clone()),
+    }
+}
+
+/// The label of the button the user clicked
+///
+/// Some platforms report custom buttons by their role, so map the role back
+/// to the label in that position.
+fn button_label(result: MessageDialogResult, labels: &[String]) -> String {
+    let label = |index: usize, fallback: &str| labels.get(index).cloned().unwrap_or_else(|| fallback.to_string());
+    match result {
+        MessageDialogResult::Custom(clicked) => clicked,
+        MessageDialogResult::Ok => label(0, "OK"),
+        MessageDialogResult::Yes => label(0, "Yes"),
+        MessageDialogResult::No => label(1, "No"),
+        MessageDialogResult::Cancel => label(labels.len().max(1) - 1, "Cancel"),
+    }
+}
+
+/// Strip a leading `*.` or `.` from a filter extension, as users write both
+fn extension(text: &str) -> String {
+    text.trim_start_matches('*').trim_start_matches('.').to_string()
+}
+
+/// Settle the promises of answered dialogs; returns whether any settled
+pub fn deliver(ctx: &Ctx, dialogs: &Dialogs) -> bool {
+    let choices = std::mem::take(&mut dialogs.state.lock().unwrap().choices);
+    let Ok(pending) = ctx.globals().get::<_, Object>(PENDING) else {
+        return false;
+    };
+
+    let mut delivered = false;
+    for (id, choice) in choices {
+        let Ok(entry) = pending.get::<_, Array>(id.to_string()) else {
+            continue;
+        };
+        let _ = pending.remove(id.to_string());
+        let value = match choice {
+            Choice::Cancelled => Ok(Value::new_null(ctx.clone())),
+            Choice::Path(text) | Choice::Button(text) => text.into_js(ctx),
+            Choice::Paths(paths) => paths.into_js(ctx),
+        };
+        if let Err(e) = value.and_then(|value| entry.get::<Function>(0)?.call::<_, ()>((value,))) {
+            eprintln!("Error settling rasen.dialog {}: {:?}", id, e);
+        }
+        delivered = true;
+    }
+    delivered
+}
+
+/// Install `rasen.dialog` for a runtime's dialogs
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, dialogs: &Dialogs) -> rquickjs::Result<()> {
+    ctx.globals().set(PENDING, Object::new(ctx.clone())?)?;
+    let namespace = native_api::namespace(ctx, rasen, "dialog")?;
+
+    let shown = dialogs.clone();
+    namespace.set(
+        "open",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, options: Option<Value<'js>>| {
+            shown.show(&ctx, Dialog::Open(values::options(&ctx, options)?))
+        })?,
+    )?;
+    let shown = dialogs.clone();
+    namespace.set(
+        "save",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, options: Option<Value<'js>>| {
+            shown.show(&ctx, Dialog::Save(values::options(&ctx, options)?))
+        })?,
+    )?;
+    let shown = dialogs.clone();
+    namespace.set(
+        "message",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, options: Value<'js>| {
+            let options: MessageOptions = values::from_js(&ctx, &options)?;
+            if options.buttons.len() > 3 {
+                return Err(Exception::throw_range(&ctx, "dialog.message shows at most 3 buttons"));
+            }
+            shown.show(&ctx, Dialog::Message(options))
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_labels() {
+        let labels = ["Save".to_string(), "Discard".to_string(), "Cancel".to_string()];
+        assert_eq!(button_label(MessageDialogResult::Custom("Discard".into()), &labels), "Discard");
+        assert_eq!(button_label(MessageDialogResult::Yes, &labels), "Save");
+        assert_eq!(button_label(MessageDialogResult::No, &labels), "Discard");
+        assert_eq!(button_label(MessageDialogResult::Cancel, &labels), "Cancel");
+        assert_eq!(button_label(MessageDialogResult::Ok, &[]), "OK");
+        assert_eq!(button_label(MessageDialogResult::Cancel, &labels[..2]), "Discard");
+        assert_eq!(extension("*.png"), "png");
+        assert_eq!(extension(".jpg"), "jpg");
+    }
+}
@@ -0,0 +1,325 @@
+//! Reconciling re-rendered element trees with the one on screen
+//!
+//! A re-render produces a full descriptor tree, but most of it usually
+//! matches the tree already shown. [`diff`] walks both together and lists
+//! the subtrees that changed as patches, and [`apply`] patches the current
+//! tree in place, so unchanged elements aren't rebuilt and keep their
+//! `Arc`s (styles, tree rows) from the last render. Only new elements need
+//! their classes checked.
+//!
+//! Children are matched by ID (see `descriptor::child_ids`). Unkeyed
+//! children are matched by position: an element inserted in the middle of
+//! an unkeyed list updates the ones after it and appends the last. Keyed
+//! children are matched by key, so a reordered list moves its elements.
+//! Placeholders left by `show()` take part in IDs but aren't elements, so
+//! toggling one leaves its siblings' IDs, and their subtrees, alone.
+//!
+//! A `memo()` subtree whose inputs didn't change is sent as a skip marker,
+//! keyed by the memo, and the element on screen with that ID is kept without
+//! comparing it. A marker without one on screen can't be filled in, and
+//! [`diff`] gives up so the caller can render again in full.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::descriptor::{self, ElementDescriptor};
+use crate::elements::{DivElement, Element};
+use crate::tw_parser;
+
+/// A change to the current tree, at the child indices in `path`
+pub enum Patch {
+    /// The element changed kind; it's rebuilt with its subtree
+    Replace { path: Vec<usize>, element: Element },
+    /// The element's own props changed; its children are kept
+    Update { path: Vec<usize>, element: Element },
+    /// Children from `len` on were removed
+    Truncate { path: Vec<usize>, len: usize },
+    /// Children were added at the end
+    Append { path: Vec<usize>, children: Vec<Element> },
+    /// Keyed children were added, removed or moved
+    Rearrange { path: Vec<usize>, children: Vec<Slot> },
+}
+
+/// A child after rearranging
+pub enum Slot {
+    /// The current child at this index
+    Keep(usize),
+    New(Element),
+}
+
+impl Patch {
+    /// The elements this patch adds to the tree
+    pub fn elements(&self) -> Vec<&Element> {
+        match self {
+            Patch::Replace { element, .. } | Patch::Update { element, .. } => vec![element],
+            Patch::Truncate { .. } => Vec::new(),
+            Patch::Append { children, .. } => children.iter().collect(),
+            Patch::Rearrange { children, .. } => children
+                .iter()
+                .filter_map(|slot| match slot {
+                    Slot::New(element) => Some(element),
+                    Slot::Keep(_) => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether two divs have the same props, ignoring children
+fn same_div(a: &DivElement, b: &DivElement) -> bool {
+    Arc::ptr_eq(&a.styles, &b.styles)
+        && a.test_id == b.test_id
+        && a.handlers == b.handlers
+        && a.keymap == b.keymap
+        && a.drag_handle == b.drag_handle
+        && a.color_picker == b.color_picker
+        && a.date_picker == b.date_picker
+        && a.tree == b.tree
+        && a.selectable == b.selectable
+        && a.image == b.image
+        && a.camera == b.camera
+        && a.shader == b.shader
+        && a.element_ref == b.element_ref
+}
+
+/// The patches turning `current` into the tree `next` describes, or `None`
+/// if `next` skips a subtree that isn't on screen
+///
+/// Like `into_element`, this walks with an explicit stack, so deep trees
+/// can't overflow the native stack.
+pub fn diff(current: &Element, next: ElementDescriptor) -> Option<Vec<Patch>> {
+    let mut patches = Vec::new();
+    let mut stack = vec![(Vec::new(), current, next)];
+    while let Some((path, current, next)) = stack.pop() {
+        // The element with the marker's ID is the memo's last render
+        if let ElementDescriptor::Skip { .. } = next {
+            continue;
+        }
+        let id = current.id().to_string();
+        let same_kind = matches!(
+            (current, &next),
+            (Element::Div(_), ElementDescriptor::Div { .. }) | (Element::Text(_), ElementDescriptor::Text { .. })
+        );
+        if !same_kind {
+            if next.has_skips() {
+                return None;
+            }
+            patches.push(Patch::Replace { path, element: next.into_element_at(id) });
+            continue;
+        }
+
+        let styles = tw_parser::parse_cached(next.class());
+        let (element, children) = next.into_node(id.clone(), styles);
+        match (current, &element) {
+            (Element::Text(a), Element::Text(b)) => {
+                if a.text != b.text || a.test_id != b.test_id || !Arc::ptr_eq(&a.styles, &b.styles) {
+                    patches.push(Patch::Update { path, element });
+                }
+            }
+            (Element::Div(a), Element::Div(b)) => {
+                if !same_div(a, b) {
+                    patches.push(Patch::Update { path: path.clone(), element });
+                }
+                // The current child each new one continues, by ID; placeholders
+                // only count for IDs
+                let (ids, children): (Vec<String>, Vec<ElementDescriptor>) = descriptor::child_ids(&id, &children)
+                    .into_iter()
+                    .zip(children)
+                    .filter(|(_, child)| !child.is_empty())
+                    .unzip();
+                let current: HashMap<&str, usize> = a.children.iter().enumerate().map(|(j, child)| (child.id(), j)).collect();
+                let kept: Vec<Option<usize>> = ids.iter().map(|id| current.get(id.as_str()).copied()).collect();
+                let common = a.children.len().min(children.len());
+                let in_place = kept.iter().enumerate().all(|(i, j)| if i < common { *j == Some(i) } else { j.is_none() });
+                
+                let mut slots = Vec::new();
+                for (i, ((child_id, child), j)) in ids.into_iter().zip(children).zip(kept).enumerate() {
+                    match j {
+                        Some(j) => {
+                            let mut child_path = path.clone();
+                            child_path.push(i);
+                            stack.push((child_path, &a.children[j], child));
+                            slots.push(Slot::Keep(j));
+                        }
+                        None if child.has_skips() => return None,
+                        None => slots.push(Slot::New(child.into_element_at(child_id))),
+                    }
+                }
+                if !in_place {
+                    patches.push(Patch::Rearrange { path, children: slots });
+                    continue;
+                }
+                if a.children.len() > common {
+                    patches.push(Patch::Truncate { path: path.clone(), len: common });
+                }
+                let added: Vec<Element> = slots
+                    .into_iter()
+                    .filter_map(|slot| match slot {
+                        Slot::New(element) => Some(element),
+                        Slot::Keep(_) => None,
+                    })
+                    .collect();
+                if !added.is_empty() {
+                    patches.push(Patch::Append { path, children: added });
+                }
+            }
+            _ => unreachable!("kinds were matched above"),
+        }
+    }
+    Some(patches)
+}
+
+/// The element at the child indices in `path`
+fn at<'a>(mut node: &'a mut Element, path: &[usize]) -> &'a mut Element {
+    for &index in path {
+        node = match node {
+            Element::Div(div) => &mut div.children[index],
+            Element::Text(_) => unreachable!("patch paths only go through divs"),
+        };
+    }
+    node
+}
+
+/// Apply `patches` from [`diff`] to `tree`
+pub fn apply(tree: &mut Element, patches: Vec<Patch>) {
+    for patch in patches {
+        match patch {
+            Patch::Replace { path, element } => *at(tree, &path) = element,
+            Patch::Update { path, mut element } => {
+                let node = at(tree, &path);
+                if let (Element::Div(old), Element::Div(new)) = (&mut *node, &mut element) {
+                    new.children = std::mem::take(&mut old.children);
+                }
+                *node = element;
+            }
+            Patch::Truncate { path, len } => {
+                if let Element::Div(div) = at(tree, &path) {
+                    div.children.truncate(len);
+                }
+            }
+            Patch::Append { path, children } => {
+                if let Element::Div(div) = at(tree, &path) {
+                    div.children.extend(children);
+                }
+            }
+            Patch::Rearrange { path, children } => {
+                if let Element::Div(div) = at(tree, &path) {
+                    let mut current: Vec<Option<Element>> = std::mem::take(&mut div.children).into_iter().map(Some).collect();
+                    div.children = children
+                        .into_iter()
+                        .map(|slot| match slot {
+                            Slot::Keep(j) => current[j].take().expect("IDs are unique among siblings"),
+                            Slot::New(element) => element,
+                        })
+                        .collect();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::decode;
+
+    /// IDs and texts of a tree, in pre-order
+    fn outline(element: &Element) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut stack = vec![element];
+        while let Some(element) = stack.pop() {
+            match element {
+                Element::Div(div) => {
+                    lines.push(format!("{} {}", div.id, div.handlers.on_click.unwrap_or(0)));
+                    stack.extend(div.children.iter().rev());
+                }
+                Element::Text(text) => lines.push(format!("{} '{}'", text.id, text.text)),
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let a = r#"{"type":"div","class":"flex","children":[
+            {"type":"text","class":"","text":"title"},
+            {"type":"div","class":"p-2","handlers":{"click":1},"children":[{"type":"text","class":"","text":"one"}]},
+            {"type":"div","class":"p-2","children":[{"type":"text","class":"","text":"two"}]}
+        ]}"#;
+        let b = r#"{"type":"div","class":"flex","children":[
+            {"type":"text","class":"","text":"title"},
+            {"type":"div","class":"p-2","handlers":{"click":2},"children":[{"type":"text","class":"","text":"one"}]},
+            {"type":"text","class":"","text":"two"},
+            {"type":"div","class":"p-4","children":[{"type":"text","class":"","text":"three"}]}
+        ]}"#;
+        let mut tree = decode(a).unwrap().into_element();
+        let patches = diff(&tree, decode(b).unwrap()).unwrap();
+        // Handler change, kind change, appended child; unchanged texts are left alone
+        assert_eq!(patches.len(), 3);
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), outline(&decode(b).unwrap().into_element()));
+
+        let patches = diff(&tree, decode(a).unwrap()).unwrap();
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), outline(&decode(a).unwrap().into_element()));
+        assert!(diff(&tree, decode(a).unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_keyed_children_move() {
+        let list = |keys: &[&str]| {
+            let items: Vec<String> = keys
+                .iter()
+                .map(|key| format!(r#"{{"type":"text","key":"{}","text":"{}"}}"#, key, key))
+                .collect();
+            format!(r#"{{"type":"div","children":[{}]}}"#, items.join(","))
+        };
+        let mut tree = decode(&list(&["a", "b", "c"])).unwrap().into_element();
+        assert_eq!(outline(&tree)[1], "elem_0[a] 'a'");
+
+        let patches = diff(&tree, decode(&list(&["c", "a", "d"])).unwrap()).unwrap();
+        // One rearrangement; the moved texts are unchanged
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].elements().len(), 1);
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), vec!["elem_0 0", "elem_0[c] 'c'", "elem_0[a] 'a'", "elem_0[d] 'd'"]);
+
+        // Repeated keys fall back to positions
+        let tree = decode(&list(&["a", "a"])).unwrap().into_element();
+        assert_eq!(outline(&tree)[2], "elem_0.1 'a'");
+    }
+
+    #[test]
+    fn test_placeholders_keep_sibling_ids() {
+        let shown = r#"{"type":"div","children":[{"type":"text","text":"a"},{"type":"text","text":"b"},{"type":"text","text":"c"}]}"#;
+        let hidden = r#"{"type":"div","children":[{"type":"text","text":"a"},{"type":"empty"},{"type":"text","text":"c"}]}"#;
+        let mut tree = decode(shown).unwrap().into_element();
+        let patches = diff(&tree, decode(hidden).unwrap()).unwrap();
+        // `c` keeps its ID, so hiding `b` only moves it
+        assert_eq!(patches.len(), 1);
+        assert!(patches[0].elements().is_empty());
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), vec!["elem_0 0", "elem_0.0 'a'", "elem_0.2 'c'"]);
+        assert_eq!(outline(&tree), outline(&decode(hidden).unwrap().into_element()));
+
+        let patches = diff(&tree, decode(shown).unwrap()).unwrap();
+        assert_eq!(patches[0].elements().len(), 1);
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), outline(&decode(shown).unwrap().into_element()));
+    }
+
+    #[test]
+    fn test_skipped_subtrees() {
+        let a = r#"{"type":"div","children":[{"type":"text","text":"title"},{"type":"div","key":"memo1","children":[{"type":"text","text":"slow"}]}]}"#;
+        let skipped = r#"{"type":"div","children":[{"type":"text","text":"new title"},{"type":"skip","key":"memo1"}]}"#;
+        let mut tree = decode(a).unwrap().into_element();
+        let patches = diff(&tree, decode(skipped).unwrap()).unwrap();
+        // Only the title changed; the memo's subtree is kept as it is
+        assert_eq!(patches.len(), 1);
+        apply(&mut tree, patches);
+        assert_eq!(outline(&tree), vec!["elem_0 0", "elem_0.0 'new title'", "elem_0[memo1] 0", "elem_0[memo1].0 'slow'"]);
+
+        // Nothing on screen to keep for a memo that moved
+        let moved = r#"{"type":"div","children":[{"type":"div","children":[{"type":"skip","key":"memo1"}]}]}"#;
+        assert!(diff(&tree, decode(moved).unwrap()).is_none());
+    }
+}
@@ -1,8 +1,26 @@
 //! Element types for GPUI rendering
 
 use gpui::*;
-use crate::tw_parser::ParsedStyles;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::colorpicker::{self, PickerTracker};
+use crate::datepicker::{self, CalendarTracker, DatePicker};
+use crate::tree::{self, TreeTracker, TreeView};
+use crate::animation::{self, Animate, AnimatedValues, Interactions, Transition, TransitionDriver, TransitionProperty};
+use crate::tw_parser::{GridSpan, ParsedStyles, SizeValue, TextDecoration, TextTransform};
 use crate::event_manager::HandlerId;
+use crate::focus::FocusTracker;
+use crate::images::{self, ImageView};
+use crate::camera::{self, CameraView};
+use crate::keymap::Keymap;
+use crate::layout::Layout;
+use crate::pointer::{self, MouseInfo, PointerTracker};
+use crate::selectable::{self, SelectTracker, Selection};
+use crate::shader::{self, ShaderTracker, ShaderView};
+use crate::sortable::{self, DragPreview, DraggedItem, SortItem, SortTracker};
+use crate::throttle::{Limit, Rate};
 
 /// Element tree node
 #[derive(Clone)]
@@ -11,50 +29,791 @@ pub enum Element {
     Text(TextElement),
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, PartialEq)]
 pub struct EventHandlers {
     pub on_click: Option<HandlerId>,
     pub on_mouse_enter: Option<HandlerId>,
     pub on_mouse_leave: Option<HandlerId>,
+    /// Mouse handlers, called with the event (see `pointer`)
+    pub on_mouse_down: Option<HandlerId>,
+    pub on_mouse_move: Option<HandlerId>,
+    pub on_mouse_up: Option<HandlerId>,
+    /// `onMouseMove: { handler, throttle }` (see `throttle`)
+    pub mouse_move_rate: Option<Rate>,
+    /// Makes the div a sortable list, called with `(from, to)` (see `sortable`)
+    pub on_reorder: Option<HandlerId>,
+    /// Called with the picked value of a color or date picker
+    pub on_change: Option<HandlerId>,
+    /// Called with a tree node's ID to expand or collapse it
+    pub on_toggle: Option<HandlerId>,
+    /// Called with a tree node's ID to select it
+    pub on_select: Option<HandlerId>,
+    /// Called with the selected keys of a selectable container (see `selectable`)
+    pub on_selection_change: Option<HandlerId>,
+    /// Edit menu actions on the focused element (see `menus::Edit`)
+    pub on_cut: Option<HandlerId>,
+    pub on_copy: Option<HandlerId>,
+    pub on_paste: Option<HandlerId>,
+    pub on_select_all: Option<HandlerId>,
+}
+
+impl EventHandlers {
+    fn ids(&self) -> impl Iterator<Item = HandlerId> {
+        [
+            self.on_click,
+            self.on_mouse_enter,
+            self.on_mouse_leave,
+            self.on_mouse_down,
+            self.on_mouse_move,
+            self.on_mouse_up,
+            self.on_reorder,
+            self.on_change,
+            self.on_toggle,
+            self.on_select,
+            self.on_selection_change,
+            self.on_cut,
+            self.on_copy,
+            self.on_paste,
+            self.on_select_all,
+        ]
+        .into_iter()
+        .flatten()
+    }
 }
 
 #[derive(Clone)]
 pub struct DivElement {
     pub id: String,
-    pub styles: ParsedStyles,
+    /// `testId` prop, used to address the element from automation
+    pub test_id: Option<String>,
+    pub styles: Arc<ParsedStyles>,
     pub children: Vec<Element>,
     pub handlers: EventHandlers,
+    /// Key bindings of a `keymap` element (see `keymap`)
+    pub keymap: Option<Keymap>,
+    /// Drags the enclosing sortable list item
+    pub drag_handle: bool,
+    /// Current value of a `colorPicker` element (see `colorpicker`)
+    pub color_picker: Option<String>,
+    /// Props of a `datePicker` element (see `datepicker`)
+    pub date_picker: Option<DatePicker>,
+    /// Rows of a `tree` element (see `tree`)
+    pub tree: Option<TreeView>,
+    /// Item keys and selection of a `selectable` element (see `selectable`)
+    pub selectable: Option<Selection>,
+    /// Props of an `img` element (see `images`)
+    pub image: Option<ImageView>,
+    /// Props of a `camera` element (see `camera`)
+    pub camera: Option<CameraView>,
+    /// Props of a `shader` element (see `shader`)
+    pub shader: Option<ShaderView>,
+    /// ID of the `elementRef()` measuring this element (see `layout`)
+    pub element_ref: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct TextElement {
+    pub id: String,
+    pub test_id: Option<String>,
     pub text: String,
-    pub styles: ParsedStyles,
+    pub styles: Arc<ParsedStyles>,
 }
 
 /// Context for rendering elements with event binding capability
 pub struct RenderContext<'a> {
     pub click_handler: &'a dyn Fn(HandlerId) -> Box<dyn Fn(&ClickEvent, &mut Window, &mut App) + 'static>,
+    /// Builds a sortable list's drop callback, called with `(from, to)`
+    pub reorder_handler: &'a dyn Fn(HandlerId) -> Box<dyn Fn(usize, usize, &mut Window, &mut App) + 'static>,
+    /// Builds the drag preview for a list item, given its ID and list ID
+    pub preview_handler: &'a dyn Fn(String, String) -> Box<dyn Fn(&DraggedItem, &mut Window, &mut App) -> Entity<DragPreview> + 'static>,
+    /// Builds a callback passing a string to a handler, like a picker's new value
+    pub value_handler: &'a dyn Fn(HandlerId) -> Box<dyn Fn(String, &mut Window, &mut App) + 'static>,
+    /// Builds a callback passing a selectable container's selected keys
+    pub selection_handler: &'a dyn Fn(HandlerId) -> Box<dyn Fn(Vec<String>, &mut Window, &mut App) + 'static>,
+    /// Builds a callback passing a mouse event to a handler, rate-limited
+    /// if given a limit
+    pub mouse_handler: &'a dyn Fn(HandlerId, Option<Limit>) -> Box<dyn Fn(MouseInfo, &mut Window, &mut App) + 'static>,
+    /// Current window width, used to pick responsive variants and resolve `vw`
+    pub viewport_width: Pixels,
+    /// Current window height, used to resolve `vh`
+    pub viewport_height: Pixels,
+    /// Window zoom factor applied to pixel lengths (see `zoom`)
+    pub zoom: f32,
+    /// Whether the window appearance is dark, used to pick `dark:` variants
+    pub dark: bool,
+    /// Whether the OS asks for more contrast, used to pick `contrast-more:` variants
+    pub high_contrast: bool,
+    /// Interpolates `transition` properties between renders
+    pub transitions: &'a TransitionDriver,
+    /// Drop positions and scrolling of sortable lists
+    pub sorting: &'a SortTracker,
+    /// The sortable list item being rendered, if inside one
+    pub sort_item: Option<SortItem<'a>>,
+    /// Colors and hex field drafts of color pickers
+    pub pickers: &'a PickerTracker,
+    /// Open date picker calendars
+    pub calendars: &'a CalendarTracker,
+    /// Scrolling of trees and the one taking arrow keys
+    pub trees: &'a TreeTracker,
+    /// Item bounds and marquee drags of selectable containers
+    pub selections: &'a SelectTracker,
+    /// The element holding the pointer capture
+    pub pointers: &'a PointerTracker,
+    /// Offscreen frames of shader elements
+    pub shaders: &'a ShaderTracker,
+    /// Focus handles of the elements that take focus
+    pub focus: &'a FocusTracker,
+    /// ID of the focused element, for the `focus:` styles of transitioning elements
+    pub focused: Option<&'a str>,
+    /// Case change set on an ancestor; GPUI passes the other text styles
+    /// down by itself
+    pub text_transform: Option<TextTransform>,
+    /// Where elements with a `ref` were laid out
+    pub layout: &'a Layout,
 }
 
 impl Element {
+    pub fn id(&self) -> &str {
+        match self {
+            Element::Div(div_elem) => &div_elem.id,
+            Element::Text(text_elem) => &text_elem.id,
+        }
+    }
+    
+    pub fn styles(&self) -> &ParsedStyles {
+        match self {
+            Element::Div(div_elem) => &div_elem.styles,
+            Element::Text(text_elem) => &text_elem.styles,
+        }
+    }
+    
+    /// IDs of the handlers in the tree, key bindings included
+    pub fn handler_ids(&self) -> HashSet<HandlerId> {
+        let mut ids = HashSet::new();
+        let mut stack = vec![self];
+        while let Some(element) = stack.pop() {
+            if let Element::Div(div) = element {
+                ids.extend(div.handlers.ids());
+                if let Some(keymap) = &div.keymap {
+                    ids.extend(keymap.bindings.iter().filter_map(|(_, handler)| *handler));
+                }
+                stack.extend(&div.children);
+            }
+        }
+        ids
+    }
+    
     pub fn render_with_events(&self, render_ctx: &RenderContext) -> AnyElement {
         match self {
             Element::Div(div_elem) => render_div_with_events(div_elem, render_ctx),
-            Element::Text(text_elem) => render_text(text_elem).into_any_element(),
+            Element::Text(text_elem) => render_text(text_elem, render_ctx),
         }
     }
 }
 
 fn render_div_with_events(elem: &DivElement, render_ctx: &RenderContext) -> AnyElement {
-    let mut d = div();
+    let layers = active_layers(&elem.styles, render_ctx);
+    let tracked = state_layers(&elem.id, &layers, render_ctx);
+    let styled = tracked.as_deref().unwrap_or(&layers);
+    let mut d = apply_transition(apply_layers(div(), styled, render_ctx), &elem.id, styled, render_ctx);
     
-    // Apply styles from ParsedStyles
-    let styles = &elem.styles;
+    // Clickable elements get a pointer unless a `cursor-*` class says otherwise
+    if elem.handlers.on_click.is_some() && resolved(&layers, |s| s.cursor).is_none() {
+        d = d.cursor_pointer();
+    }
     
-    // Display & Flex
-    if matches!(styles.display, Some(Display::Flex)) {
-        d = d.flex();
+    // Hover & focus variants, unless they're among the styled layers
+    let hover = variant_layers(&layers, |s| s.hover.as_deref());
+    if !hover.is_empty() && tracked.is_none() {
+        d = d.hover(|style| apply_variant(style, &layers, &hover, render_ctx));
+    }
+    let focus = variant_layers(&layers, |s| s.focus.as_deref());
+    if !focus.is_empty() && tracked.is_none() {
+        d = d.focus(|style| apply_variant(style, &layers, &focus, render_ctx));
+    }
+    // Clicking an element that takes focus focuses it
+    if let Some(handle) = render_ctx.focus.handle(&elem.id) {
+        d = d.track_focus(&handle);
+    }
+
+    // Images, cameras, shaders, pickers and trees draw their own content in place of children
+    if let Some(image) = elem.image.as_ref().and_then(images::render) {
+        d = d.child(image);
+    }
+    if let Some(camera) = &elem.camera {
+        d = d.child(camera::render(camera));
+    }
+    if let Some(view) = &elem.shader {
+        d = d.child(shader::render(&elem.id, view, render_ctx));
+    }
+    if let Some(value) = &elem.color_picker {
+        let on_change = elem.handlers.on_change.map(|handler_id| (render_ctx.value_handler)(handler_id));
+        d = d.child(colorpicker::render(&elem.id, value, on_change, render_ctx));
+    }
+    if let Some(picker) = &elem.date_picker {
+        let on_change = elem.handlers.on_change.map(|handler_id| (render_ctx.value_handler)(handler_id));
+        d = d.child(datepicker::render(&elem.id, picker, on_change, render_ctx));
+    }
+    if let Some(view) = &elem.tree {
+        let on_toggle = elem.handlers.on_toggle.map(|handler_id| (render_ctx.value_handler)(handler_id));
+        let on_select = elem.handlers.on_select.map(|handler_id| (render_ctx.value_handler)(handler_id));
+        d = d.child(tree::render(&elem.id, view, on_toggle, on_select, render_ctx));
+    }
+
+    // Children, in `order-*` order (stable, so unordered children keep theirs).
+    // Sortable lists and selectable containers keep document order, since
+    // item indices and keys follow it.
+    let children_ctx = RenderContext {
+        text_transform: resolved(&layers, |s| s.text_transform).or(render_ctx.text_transform),
+        ..*render_ctx
+    };
+    let mut children: Vec<&Element> = elem.children.iter().collect();
+    if elem.handlers.on_reorder.is_some() {
+        for (index, child) in children.into_iter().enumerate() {
+            let item_ctx = RenderContext {
+                sort_item: Some(SortItem {
+                    list: &elem.id,
+                    item: child.id(),
+                    index,
+                    handled: sortable::has_handle(child),
+                }),
+                ..children_ctx
+            };
+            d = d.child(child.render_with_events(&item_ctx));
+        }
+    } else {
+        if elem.selectable.is_none() && children.iter().any(|child| child.styles().order.is_some()) {
+            children.sort_by_key(|child| resolved(&active_layers(child.styles(), render_ctx), |s| s.order).unwrap_or(0));
+        }
+        for child in children {
+            d = d.child(child.render_with_events(&children_ctx));
+        }
+    }
+    
+    if let Some(selection) = &elem.selectable {
+        let on_change = elem.handlers.on_selection_change.map(|handler_id| (render_ctx.selection_handler)(handler_id));
+        d = selectable::container(d, &elem.id, selection, on_change, render_ctx);
+    }
+    
+    let handlers = &elem.handlers;
+    if handlers.on_mouse_down.is_some() || handlers.on_mouse_move.is_some() || handlers.on_mouse_up.is_some() {
+        d = pointer::listeners(d, &elem.id, handlers, render_ctx);
+    }
+    if let Some(element_ref) = &elem.element_ref {
+        let scroll = render_ctx.sort_item.map(|item| render_ctx.sorting.scroll_handle(item.list));
+        d = render_ctx.layout.measure(d, element_ref, render_ctx.zoom, scroll);
+    }
+    
+    // Sortable lists run along their flex direction
+    let horizontal = matches!(resolved(&layers, |s| s.flex_direction), Some(FlexDirection::Row));
+    if let Some(handler_id) = elem.handlers.on_reorder {
+        d = sortable::list(d, &elem.id, horizontal, (render_ctx.reorder_handler)(handler_id), render_ctx.sorting);
+    }
+    
+    // The item itself, or its handle, starts dragging a sortable list item
+    let drag_source = render_ctx
+        .sort_item
+        .filter(|item| if item.handled { elem.drag_handle } else { item.item == elem.id });
+    
+    // Click handlers, active styles, dragging, scrolling and tracked states
+    // need a stateful (identified) element
+    let active = variant_layers(&layers, |s| s.active.as_deref());
+    let animate = resolved(&layers, |s| s.animate);
+    if elem.handlers.on_click.is_none()
+        && active.is_empty()
+        && elem.handlers.on_reorder.is_none()
+        && drag_source.is_none()
+        && tracked.is_none()
+    {
+        return animate_element(d, &elem.id, animate, styled, render_ctx);
+    }
+    
+    let element_id = ElementId::Name(elem.id.clone().into());
+    let mut d = d.id(element_id);
+    
+    if tracked.is_some() {
+        d = track_interactions(d, &elem.id, render_ctx);
+    } else if !active.is_empty() {
+        d = d.active(|style| apply_variant(style, &layers, &active, render_ctx));
+    }
+    
+    // Apply click handler if present
+    if let Some(handler_id) = elem.handlers.on_click {
+        let handler = (render_ctx.click_handler)(handler_id);
+        d = d.on_click(handler);
+    }
+    
+    if elem.handlers.on_reorder.is_some() {
+        d = sortable::scroll(d, &elem.id, horizontal, render_ctx.sorting);
+    }
+    if let Some(item) = drag_source {
+        let preview = (render_ctx.preview_handler)(item.item.to_string(), item.list.to_string());
+        let dragged = DraggedItem { list: item.list.to_string(), from: item.index };
+        d = d.on_drag(dragged, move |dragged, _, window, cx| preview(dragged, window, cx));
+        if resolved(&layers, |s| s.cursor).is_none() {
+            d = d.cursor_grab();
+        }
+    }
+    
+    animate_element(d, &elem.id, animate, styled, render_ctx)
+}
+
+fn render_text(elem: &TextElement, render_ctx: &RenderContext) -> AnyElement {
+    let layers = active_layers(&elem.styles, render_ctx);
+    let text = match resolved(&layers, |s| s.text_transform).or(render_ctx.text_transform) {
+        Some(transform) => transform.apply(&elem.text),
+        None => elem.text.clone(),
+    };
+    let tracked = state_layers(&elem.id, &layers, render_ctx);
+    let styled = tracked.as_deref().unwrap_or(&layers);
+    let mut d = apply_transition(apply_layers(div(), styled, render_ctx), &elem.id, styled, render_ctx)
+        .child(text);
+    let animate = resolved(&layers, |s| s.animate);
+    // Clicking text with `focus:` styles focuses it
+    if let Some(handle) = render_ctx.focus.handle(&elem.id) {
+        d = d.track_focus(&handle);
+    }
+    
+    if tracked.is_some() {
+        let d = track_interactions(d.id(ElementId::Name(elem.id.clone().into())), &elem.id, render_ctx);
+        return animate_element(d, &elem.id, animate, styled, render_ctx);
+    }
+    let hover = variant_layers(&layers, |s| s.hover.as_deref());
+    if !hover.is_empty() {
+        d = d.hover(|style| apply_variant(style, &layers, &hover, render_ctx));
+    }
+    let focus = variant_layers(&layers, |s| s.focus.as_deref());
+    if !focus.is_empty() {
+        d = d.focus(|style| apply_variant(style, &layers, &focus, render_ctx));
+    }
+    // Active styles need a stateful (identified) element
+    let active = variant_layers(&layers, |s| s.active.as_deref());
+    if active.is_empty() {
+        return animate_element(d, &elem.id, animate, styled, render_ctx);
+    }
+    let d = d
+        .id(ElementId::Name(elem.id.clone().into()))
+        .active(|style| apply_variant(style, &layers, &active, render_ctx));
+    animate_element(d, &elem.id, animate, styled, render_ctx)
+}
+
+/// The layers a transitioning element with state variants is drawn with:
+/// its base layers followed by the variants of the states it's in
+///
+/// Scaled elements are drawn this way too, since refinements can't resize
+/// them (see [`transform`]). `None` for other elements, whose variants GPUI
+/// applies as refinements (see `animation`). The states are tracked by
+/// [`track_interactions`].
+fn state_layers<'a>(id: &str, layers: &[&'a ParsedStyles], render_ctx: &RenderContext) -> Option<Vec<&'a ParsedStyles>> {
+    let transitions = !matches!(resolved(layers, |s| s.transition), Some(TransitionProperty::None) | None);
+    if !transitions && !scales(layers) {
+        return None;
+    }
+    let hover = variant_layers(layers, |s| s.hover.as_deref());
+    let focus = variant_layers(layers, |s| s.focus.as_deref());
+    let active = variant_layers(layers, |s| s.active.as_deref());
+    if hover.is_empty() && focus.is_empty() && active.is_empty() {
+        return None;
+    }
+    
+    // In Tailwind's variant order: hover, then focus, then active
+    let interactions = render_ctx.transitions.interactions();
+    let mut styled = layers.to_vec();
+    if interactions.hovered(id) {
+        styled.extend(hover);
+    }
+    if render_ctx.focused == Some(id) {
+        styled.extend(focus);
+    }
+    if interactions.pressed(id) {
+        styled.extend(active);
+    }
+    Some(styled)
+}
+
+/// Track whether an element is hovered and pressed, re-rendering as that changes
+fn track_interactions(d: Stateful<Div>, id: &str, render_ctx: &RenderContext) -> Stateful<Div> {
+    let interactions = render_ctx.transitions.interactions();
+    let hovered = interactions.clone();
+    let hovered_id = id.to_string();
+    d.on_hover(move |hovering, window, _| {
+        if hovered.set_hovered(&hovered_id, *hovering) {
+            window.refresh();
+        }
+    })
+    .on_mouse_down(MouseButton::Left, press_listener(&interactions, id, true))
+    .on_mouse_up(MouseButton::Left, press_listener(&interactions, id, false))
+    .on_mouse_up_out(MouseButton::Left, press_listener(&interactions, id, false))
+}
+
+fn press_listener<E: 'static>(interactions: &Interactions, id: &str, pressed: bool) -> impl Fn(&E, &mut Window, &mut App) + 'static {
+    let (interactions, id) = (interactions.clone(), id.to_string());
+    move |_, window, _| {
+        if interactions.set_pressed(&id, pressed) {
+            window.refresh();
+        }
+    }
+}
+
+/// Override animatable properties with their in-flight transition values,
+/// and scale the element (see [`transform`])
+fn apply_transition(mut d: Div, id: &str, layers: &[&ParsedStyles], render_ctx: &RenderContext) -> Div {
+    let property = match resolved(layers, |s| s.transition) {
+        Some(TransitionProperty::None) | None => {
+            let (x, y) = (resolved(layers, |s| s.translate_x), resolved(layers, |s| s.translate_y));
+            return transform(d, id, x, y, resolved(layers, |s| s.scale), layers, render_ctx);
+        }
+        Some(property) => property,
+    };
+    let transition = Transition {
+        property,
+        duration: resolved(layers, |s| s.transition_duration).unwrap_or(animation::DEFAULT_DURATION),
+        easing: resolved(layers, |s| s.transition_easing).unwrap_or(animation::Easing::InOut),
+    };
+    let (background, text_color, border_color) = filtered_colors(layers);
+    let target = AnimatedValues {
+        background,
+        text_color,
+        border_color,
+        opacity: resolved(layers, |s| s.opacity),
+        translate_x: resolved(layers, |s| s.translate_x),
+        translate_y: resolved(layers, |s| s.translate_y),
+        scale: resolved(layers, |s| s.scale),
+    };
+    
+    let current = render_ctx.transitions.resolve(id, transition, target);
+    if let Some(bg) = current.background {
+        d = d.bg(bg);
+    }
+    if let Some(color) = current.text_color {
+        d = d.text_color(color);
+    }
+    if let Some(color) = current.border_color {
+        d = d.border_color(color);
+    }
+    if let Some(opacity) = current.opacity {
+        d = d.opacity(opacity);
+    }
+    transform(d, id, current.translate_x, current.translate_y, current.scale, layers, render_ctx)
+}
+
+/// Offset an element from where it's laid out, for `translate-*`
+fn translate<E: Styled>(mut d: E, x: Option<Pixels>, y: Option<Pixels>, zoom: f32) -> E {
+    if x.is_none() && y.is_none() {
+        return d;
+    }
+    d = d.relative();
+    if let Some(x) = x {
+        d = d.left(x * zoom);
+    }
+    if let Some(y) = y {
+        d = d.top(y * zoom);
+    }
+    d
+}
+
+/// Offset and scale an element, for `translate-*` and `scale-*`
+///
+/// GPUI can't scale elements, so a scaled element's box is resized around
+/// its center, with its right and bottom margins making up the difference
+/// so its siblings stay put; its content keeps its size. The natural size
+/// is measured as the element is drawn, so it's scaled from its second
+/// frame on.
+fn transform(
+    mut d: Div,
+    id: &str,
+    mut x: Option<Pixels>,
+    mut y: Option<Pixels>,
+    scale: Option<f32>,
+    layers: &[&ParsedStyles],
+    render_ctx: &RenderContext,
+) -> Div {
+    let zoom = render_ctx.zoom;
+    if !scales(layers) {
+        return translate(d, x, y, zoom);
+    }
+    render_ctx.transitions.keep(id);
+    let scale = scale.unwrap_or(1.0);
+    let interactions = render_ctx.transitions.interactions();
+    if let Some(size) = interactions.scale(id, scale).filter(|_| scale != 1.0) {
+        let shrunk = size.map(|length| length * (1.0 - scale));
+        x = Some(x.unwrap_or_default() + shrunk.width * (0.5 / zoom));
+        y = Some(y.unwrap_or_default() + shrunk.height * (0.5 / zoom));
+        d = d.w(size.width * scale).h(size.height * scale);
+        // Rems, fractions and `auto` can't be added to
+        if let Some(right) = margin(layers, |m| &m.right, zoom) {
+            d = d.mr(right + shrunk.width);
+        }
+        if let Some(bottom) = margin(layers, |m| &m.bottom, zoom) {
+            d = d.mb(bottom + shrunk.height);
+        }
+    }
+    let id = id.to_string();
+    translate(d, x, y, zoom).child(
+        canvas(
+            move |bounds, window, _| {
+                if interactions.measured(&id, bounds.size) {
+                    window.refresh();
+                }
+            },
+            |_, _, _, _| {},
+        )
+        .absolute()
+        .top_0()
+        .left_0()
+        .size_full(),
+    )
+}
+
+/// Whether `scale-*` applies to an element in any state
+fn scales(layers: &[&ParsedStyles]) -> bool {
+    layers.iter().any(|layer| {
+        [Some(*layer), layer.hover.as_deref(), layer.focus.as_deref(), layer.active.as_deref()]
+            .into_iter()
+            .flatten()
+            .any(|styles| styles.scale.is_some())
+    })
+}
+
+/// A margin side in zoomed pixels, zero if unset
+fn margin(layers: &[&ParsedStyles], side: impl Fn(&Edges<Length>) -> &Length, zoom: f32) -> Option<Pixels> {
+    match resolved(layers, |s| s.margin.as_ref().map(|m| side(m).clone())) {
+        None | Some(Length::Auto) => Some(px(0.0)),
+        Some(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(pixels)))) => Some(pixels * zoom),
+        Some(_) => None,
+    }
+}
+
+/// Wrap an element in its `animate-*` keyframes
+///
+/// GPUI can't rotate arbitrary elements, so `animate-spin` turns the border
+/// alone, drawn as a three-quarter arc like the usual spinner (content stays
+/// put, and elements without a border don't spin), and `animate-ping` only fades.
+fn animate_element<E>(d: E, id: &str, animate: Option<Animate>, layers: &[&ParsedStyles], render_ctx: &RenderContext) -> AnyElement
+where
+    E: IntoElement + Styled + ParentElement + 'static,
+{
+    let animation_id = ElementId::Name(format!("{}-animate", id).into());
+    match animate {
+        Some(Animate::Pulse) => d
+            .with_animation(
+                animation_id,
+                Animation::new(Duration::from_secs(2)).repeat(),
+                |d, delta| d.opacity(1.0 - 0.5 * (PI * delta).sin()),
+            )
+            .into_any_element(),
+        Some(Animate::Ping) => d
+            .with_animation(
+                animation_id,
+                Animation::new(Duration::from_secs(1)).repeat(),
+                |d, delta| d.opacity(1.0 - delta),
+            )
+            .into_any_element(),
+        Some(Animate::Bounce) => d
+            .with_animation(
+                animation_id,
+                Animation::new(Duration::from_secs(1)).repeat(),
+                |d, delta| d.relative().top(px(-8.0 * (PI * delta).sin())),
+            )
+            .into_any_element(),
+        Some(Animate::Spin) => {
+            let Some(width) = resolved(layers, |s| s.border_width.top).filter(|width| *width > px(0.0)) else {
+                return d.into_any_element();
+            };
+            let width = width * render_ctx.zoom;
+            let (_, text_color, border_color) = filtered_colors(layers);
+            let color = border_color.or(text_color).unwrap_or_else(black);
+            d.border_color(transparent_black())
+                .with_animation(
+                    animation_id,
+                    Animation::new(Duration::from_secs(1)).repeat(),
+                    move |d, delta| {
+                        d.child(
+                            canvas(|_, _, _| {}, move |bounds, _, window, _| paint_arc(window, bounds, width, color, delta))
+                                .absolute()
+                                .top_0()
+                                .left_0()
+                                .size_full(),
+                        )
+                    },
+                )
+                .into_any_element()
+        }
+        Some(Animate::None) | None => d.into_any_element(),
+    }
+}
+
+/// Paint a three-quarter arc `width` thick around the padding box `bounds`,
+/// over the border, starting `turn` of the way round
+fn paint_arc(window: &mut Window, bounds: Bounds<Pixels>, width: Pixels, color: Hsla, turn: f32) {
+    const SEGMENTS: usize = 36;
+    let center = bounds.center();
+    let inner = f32::from(bounds.size.width).min(f32::from(bounds.size.height)) / 2.0;
+    let outer = inner + f32::from(width);
+    let at = |radius: f32, angle: f32| point(center.x + px(radius * angle.cos()), center.y + px(radius * angle.sin()));
+    let angle = |segment: usize| 2.0 * PI * turn + 1.5 * PI * segment as f32 / SEGMENTS as f32;
+    for segment in 0..SEGMENTS {
+        let (from, to) = (angle(segment), angle(segment + 1));
+        let mut path = Path::new(at(outer, from));
+        path.line_to(at(outer, to));
+        path.line_to(at(inner, to));
+        path.line_to(at(inner, from));
+        window.paint_path(path, color);
+    }
+}
+
+/// The value of a property after cascading all layers
+fn resolved<T>(layers: &[&ParsedStyles], property: impl Fn(&ParsedStyles) -> Option<T>) -> Option<T> {
+    layers.iter().rev().find_map(|layer| property(layer))
+}
+
+/// Style layers that apply in the current context: the base styles followed
+/// by the responsive variants matching the window width (smallest first) and
+/// the dark variant when the appearance is dark, and the high-contrast
+/// variant when the OS asks for more contrast
+fn active_layers<'a>(styles: &'a ParsedStyles, render_ctx: &RenderContext) -> Vec<&'a ParsedStyles> {
+    let mut layers = Vec::new();
+    collect_layers(styles, render_ctx, &mut layers);
+    layers
+}
+
+fn collect_layers<'a>(styles: &'a ParsedStyles, render_ctx: &RenderContext, layers: &mut Vec<&'a ParsedStyles>) {
+    layers.push(styles);
+    for (min_width, breakpoint) in &styles.breakpoints {
+        if render_ctx.viewport_width >= *min_width {
+            collect_layers(breakpoint, render_ctx, layers);
+        }
+    }
+    if render_ctx.dark {
+        if let Some(dark) = &styles.dark {
+            collect_layers(dark, render_ctx, layers);
+        }
+    }
+    if render_ctx.high_contrast {
+        if let Some(contrast) = &styles.contrast_more {
+            collect_layers(contrast, render_ctx, layers);
+        }
+    }
+}
+
+/// Collect a state variant (hover, active, ...) from every active layer
+fn variant_layers<'a>(
+    layers: &[&'a ParsedStyles],
+    variant: impl Fn(&'a ParsedStyles) -> Option<&'a ParsedStyles>,
+) -> Vec<&'a ParsedStyles> {
+    layers.iter().filter_map(|layer| variant(*layer)).collect()
+}
+
+/// Apply style layers in order, later layers overriding earlier ones
+fn apply_layers<E: Styled>(d: E, layers: &[&ParsedStyles], render_ctx: &RenderContext) -> E {
+    let d = layers.iter().fold(d, |d, styles| apply_styles(d, styles, render_ctx));
+    apply_filters(apply_ring(d, layers, render_ctx), layers)
+}
+
+/// Apply a state variant over the element's base layers
+///
+/// Filters and rings see the whole cascade, so `hover:brightness-110`
+/// brightens the base background and `focus:ring-blue-500` colors a base
+/// `ring-2`.
+fn apply_variant(
+    style: StyleRefinement,
+    base: &[&ParsedStyles],
+    variant: &[&ParsedStyles],
+    render_ctx: &RenderContext,
+) -> StyleRefinement {
+    let style = variant.iter().fold(style, |style, styles| apply_styles(style, styles, render_ctx));
+    let cascade = [base, variant].concat();
+    apply_filters(apply_ring(style, &cascade, render_ctx), &cascade)
+}
+
+/// Approximate the CSS filters GPUI can't paint
+///
+/// GPUI has no way to filter painted content, so `brightness-*` scales the
+/// element's own background, text and border colors (children keep theirs).
+/// `blur-*` has no effect, and `backdrop-blur-*` only takes effect on the
+/// root element, where it blurs the window background (see `AppRoot`).
+fn apply_filters<E: Styled>(mut d: E, layers: &[&ParsedStyles]) -> E {
+    if resolved(layers, |s| s.brightness).is_none() {
+        return d;
+    }
+    let (background, text_color, border_color) = filtered_colors(layers);
+    if let Some(color) = background {
+        d = d.bg(color);
+    }
+    if let Some(color) = text_color {
+        d = d.text_color(color);
+    }
+    if let Some(color) = border_color {
+        d = d.border_color(color);
+    }
+    d
+}
+
+/// Background, text and border colors with `brightness-*` applied
+fn filtered_colors(layers: &[&ParsedStyles]) -> (Option<Hsla>, Option<Hsla>, Option<Hsla>) {
+    let brightness = resolved(layers, |s| s.brightness).unwrap_or(1.0);
+    let filter = |color: Hsla| {
+        if brightness == 1.0 {
+            return color;
+        }
+        // Like the CSS filter: scale each channel, clamped to white
+        let color = Rgba::from(color);
+        Hsla::from(Rgba {
+            r: (color.r * brightness).min(1.0),
+            g: (color.g * brightness).min(1.0),
+            b: (color.b * brightness).min(1.0),
+            a: color.a,
+        })
+    };
+    (
+        resolved(layers, |s| s.background).map(filter),
+        resolved(layers, |s| s.text_color).map(filter),
+        resolved(layers, |s| s.border_color).map(filter),
+    )
+}
+
+/// Draw `ring-*` as solid shadows on top of the box shadow, like Tailwind:
+/// the ring spreads past the offset, which is painted over it in the offset color
+///
+/// Width, color and offset resolve across the whole cascade, and rings
+/// combine with its box shadow, so `shadow-md focus:ring-2` keeps the
+/// shadow while focused.
+fn apply_ring<E: Styled>(d: E, layers: &[&ParsedStyles], render_ctx: &RenderContext) -> E {
+    let mut shadows = zoomed_shadows(&resolved(layers, |s| s.shadow.clone()).unwrap_or_default(), render_ctx.zoom);
+    let width = match resolved(layers, |s| s.ring_width) {
+        Some(width) if width > px(0.0) => width,
+        // `ring-0` takes away a ring the cascade drew
+        Some(_) => return d.shadow(shadows),
+        None => return d,
+    };
+    let offset = resolved(layers, |s| s.ring_offset_width).unwrap_or(px(0.0));
+    // Tailwind's default ring is blue-500 at 50%
+    let color = resolved(layers, |s| s.ring_color).unwrap_or_else(|| Hsla::from(rgb(0x3b82f6)).opacity(0.5));
+    let offset_color = resolved(layers, |s| s.ring_offset_color).unwrap_or_else(white);
+    
+    let ring = |spread: Pixels, color: Hsla| BoxShadow {
+        color,
+        offset: point(px(0.0), px(0.0)),
+        blur_radius: px(0.0),
+        spread_radius: spread * render_ctx.zoom,
+    };
+    shadows.push(ring(offset + width, color));
+    if offset > px(0.0) {
+        shadows.push(ring(offset, offset_color));
+    }
+    d.shadow(shadows)
+}
+
+/// Apply parsed styles to anything styleable
+///
+/// Works on elements as well as on `StyleRefinement`s, which is how the
+/// hover/active/focus variants are applied.
+fn apply_styles<E: Styled>(mut d: E, styles: &ParsedStyles, render_ctx: &RenderContext) -> E {
+    // Display
+    match styles.display {
+        Some(Display::Flex) => d = d.flex(),
+        Some(Display::Grid) => d = d.grid(),
+        _ => {}
     }
     
     if let Some(dir) = &styles.flex_direction {
@@ -88,110 +847,134 @@ fn render_div_with_events(elem: &DivElement, render_ctx: &RenderContext) -> AnyE
         };
     }
     
+    // Per-item alignment overriding the parent's `items-*`
+    if let Some(align) = styles.align_self {
+        d.style().align_self = Some(align);
+    }
+    
+    // Grid
+    if let Some(cols) = styles.grid_cols {
+        d = d.grid_cols(cols);
+    }
+    if let Some(rows) = styles.grid_rows {
+        d = d.grid_rows(rows);
+    }
+    match styles.col_span {
+        Some(GridSpan::Tracks(span)) => d = d.col_span(span),
+        Some(GridSpan::Full) => d = d.col_span_full(),
+        None => {}
+    }
+    match styles.row_span {
+        Some(GridSpan::Tracks(span)) => d = d.row_span(span),
+        Some(GridSpan::Full) => d = d.row_span_full(),
+        None => {}
+    }
+    
     // Sizing
+    let zoom = render_ctx.zoom;
+    let resolve = |value: &SizeValue| match value {
+        SizeValue::Length(length) => zoomed(length.clone(), zoom),
+        SizeValue::Vw(fraction) => (render_ctx.viewport_width * *fraction * zoom).into(),
+        SizeValue::Vh(fraction) => (render_ctx.viewport_height * *fraction * zoom).into(),
+    };
     if let Some(w) = &styles.width {
-        d = d.w(w.clone());
+        d = d.w(resolve(w));
     }
     if let Some(h) = &styles.height {
-        d = d.h(h.clone());
+        d = d.h(resolve(h));
+    }
+    if let Some(w) = &styles.min_width {
+        d = d.min_w(resolve(w));
+    }
+    if let Some(h) = &styles.min_height {
+        d = d.min_h(resolve(h));
+    }
+    if let Some(w) = &styles.max_width {
+        d = d.max_w(resolve(w));
+    }
+    if let Some(h) = &styles.max_height {
+        d = d.max_h(resolve(h));
     }
     
-    // Gap - convert Length to DefiniteLength
+    // Gap - convert Length to DefiniteLength (applies to flex and grid)
     if let Some(gap) = &styles.gap {
         if let Length::Definite(def_len) = gap {
-            d = d.gap(def_len.clone());
+            d = d.gap(zoomed_definite(def_len.clone(), zoom));
         }
     }
+    if let Some(Length::Definite(def_len)) = &styles.gap_x {
+        d = d.gap_x(zoomed_definite(def_len.clone(), zoom));
+    }
+    if let Some(Length::Definite(def_len)) = &styles.gap_y {
+        d = d.gap_y(zoomed_definite(def_len.clone(), zoom));
+    }
     
     // Background
     if let Some(bg) = &styles.background {
         d = d.bg(*bg);
     }
     
-    // Border
-    if let Some(bw) = &styles.border_width {
-        d = d.border(*bw);
+    // Border - widths per edge, radii per corner
+    let widths = &styles.border_width;
+    let border_widths = &mut d.style().border_widths;
+    for (width, slot) in [
+        (widths.top, &mut border_widths.top),
+        (widths.right, &mut border_widths.right),
+        (widths.bottom, &mut border_widths.bottom),
+        (widths.left, &mut border_widths.left),
+    ] {
+        if let Some(width) = width {
+            *slot = Some((width * zoom).into());
+        }
     }
     if let Some(bc) = &styles.border_color {
         d = d.border_color(*bc);
     }
-    if let Some(br) = &styles.border_radius {
-        d = d.rounded(*br);
+    let radii = &styles.border_radius;
+    let corner_radii = &mut d.style().corner_radii;
+    for (radius, slot) in [
+        (radii.top_left, &mut corner_radii.top_left),
+        (radii.top_right, &mut corner_radii.top_right),
+        (radii.bottom_right, &mut corner_radii.bottom_right),
+        (radii.bottom_left, &mut corner_radii.bottom_left),
+    ] {
+        if let Some(radius) = radius {
+            *slot = Some((radius * zoom).into());
+        }
     }
     
     // Padding - apply individual sides if definite
     if let Some(p) = &styles.padding {
         if let Length::Definite(def) = &p.top {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.pt(*px_val);
-            }
+            d = d.pt(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &p.bottom {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.pb(*px_val);
-            }
+            d = d.pb(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &p.left {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.pl(*px_val);
-            }
+            d = d.pl(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &p.right {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.pr(*px_val);
-            }
+            d = d.pr(zoomed_definite(def.clone(), zoom));
         }
     }
 
     // Margin - apply individual sides if definite
     if let Some(m) = &styles.margin {
         if let Length::Definite(def) = &m.top {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.mt(*px_val);
-            }
+            d = d.mt(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &m.bottom {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.mb(*px_val);
-            }
+            d = d.mb(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &m.left {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.ml(*px_val);
-            }
+            d = d.ml(zoomed_definite(def.clone(), zoom));
         }
         if let Length::Definite(def) = &m.right {
-            if let DefiniteLength::Absolute(AbsoluteLength::Pixels(px_val)) = def {
-                d = d.mr(*px_val);
-            }
+            d = d.mr(zoomed_definite(def.clone(), zoom));
         }
     }
     
-    // Cursor style for clickable elements
-    if elem.handlers.on_click.is_some() {
-        d = d.cursor_pointer();
-    }
-
-    // Children
-    for child in &elem.children {
-        d = d.child(child.render_with_events(render_ctx));
-    }
-    
-    // Apply click handler if present
-    if let Some(handler_id) = elem.handlers.on_click {
-        let handler = (render_ctx.click_handler)(handler_id);
-        let element_id = ElementId::Name(elem.id.clone().into());
-        return d.id(element_id).on_click(handler).into_any_element();
-    }
-    
-    d.into_any_element()
-}
-
-fn render_text(elem: &TextElement) -> Div {
-    let mut d = div().child(elem.text.clone());
-    
-    let styles = &elem.styles;
-    
     // Text color
     if let Some(color) = &styles.text_color {
         d = d.text_color(*color);
@@ -199,7 +982,12 @@ fn render_text(elem: &TextElement) -> Div {
     
     // Font size
     if let Some(size) = &styles.font_size {
-        d = d.text_size(*size);
+        d = d.text_size(*size * zoom);
+    }
+    
+    // Line height
+    if let Some(height) = &styles.line_height {
+        d = d.line_height(zoomed_definite(height.clone(), zoom));
     }
     
     // Font weight
@@ -207,5 +995,86 @@ fn render_text(elem: &TextElement) -> Div {
         d = d.font_weight(*weight);
     }
     
-    d
+    // Font style & decoration
+    match styles.font_style {
+        Some(FontStyle::Italic) | Some(FontStyle::Oblique) => d = d.italic(),
+        Some(FontStyle::Normal) => d = d.not_italic(),
+        None => {}
+    }
+    match styles.text_decoration {
+        Some(TextDecoration::Underline) => d = d.underline(),
+        Some(TextDecoration::LineThrough) => d = d.line_through(),
+        Some(TextDecoration::None) => d = d.text_decoration_none(),
+        None => {}
+    }
+    
+    // Whitespace & truncation
+    match styles.white_space {
+        Some(WhiteSpace::Nowrap) => d = d.whitespace_nowrap(),
+        Some(WhiteSpace::Normal) => d = d.whitespace_normal(),
+        None => {}
+    }
+    if styles.text_ellipsis {
+        d = d.text_ellipsis();
+    }
+    if let Some(lines) = styles.line_clamp {
+        d = d.line_clamp(lines);
+    }
+    
+    // Overflow - hidden also lets flex items shrink below their content,
+    // which single-line truncation relies on
+    if let Some(overflow) = styles.overflow {
+        d.style().overflow.x = Some(overflow);
+        d.style().overflow.y = Some(overflow);
+    }
+    
+    // Box shadow
+    if let Some(shadows) = &styles.shadow {
+        d = d.shadow(zoomed_shadows(shadows, zoom));
+    }
+    
+    // Cursor
+    if let Some(cursor) = styles.cursor {
+        d = d.cursor(cursor);
+    }
+    
+    // Opacity
+    if let Some(opacity) = styles.opacity {
+        d = d.opacity(opacity);
+    }
+    
+    translate(d, styles.translate_x, styles.translate_y, zoom)
+}
+
+/// Scale shadow offsets and radii by the window zoom
+fn zoomed_shadows(shadows: &[BoxShadow], zoom: f32) -> Vec<BoxShadow> {
+    shadows
+        .iter()
+        .map(|shadow| BoxShadow {
+            offset: shadow.offset.map(|o| o * zoom),
+            blur_radius: shadow.blur_radius * zoom,
+            spread_radius: shadow.spread_radius * zoom,
+            ..shadow.clone()
+        })
+        .collect()
+}
+
+/// Scale an absolute pixel length by the window zoom
+///
+/// Relative lengths follow their parent and rem lengths the window's rem
+/// size, so only pixels need scaling here.
+fn zoomed(length: Length, zoom: f32) -> Length {
+    match length {
+        Length::Definite(definite) => Length::Definite(zoomed_definite(definite, zoom)),
+        Length::Auto => Length::Auto,
+    }
+}
+
+fn zoomed_definite(length: DefiniteLength, zoom: f32) -> DefiniteLength {
+    match length {
+        DefiniteLength::Absolute(AbsoluteLength::Pixels(pixels)) => {
+            DefiniteLength::Absolute(AbsoluteLength::Pixels(pixels * zoom))
+        }
+        other => other,
+    }
 }
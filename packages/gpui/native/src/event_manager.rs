@@ -1,78 +1,226 @@
 //! Event management system for bridging JS callbacks to GPUI events
+//!
+//! Handler functions stay on the JS side, in a table keyed by handler ID.
+//! The native side keeps one entry point, `__handlerFor`, that looks a
+//! handler up when an event fires, and the set of IDs the table holds, so a
+//! render only hands over the IDs of new handlers rather than a saved
+//! function for each.
+//!
+//! Windows share the table. Each keeps the set of handlers its tree uses,
+//! and only calls those, so an event is routed to the window it came from;
+//! a handler goes once no window uses it. Render requests cover every
+//! window, as state is shared between them too.
+//!
+//! Every call is timed: a handler that blocks the UI thread for longer than
+//! `runtime.slowHandlerWarning` logs a warning, and the counts and durations
+//! per handler are kept for the `handlerStats` automation method.
 
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}};
-use rquickjs::{Context, Function, Persistent};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use rquickjs::function::IntoArgs;
+use rquickjs::{Ctx, Function, Persistent, Value};
+use crate::window::{WindowId, MAIN_WINDOW};
 
 /// Unique ID for each event handler
+///
+/// Assigned on the JS side by `__registerHandler`, stable for the lifetime of
+/// the underlying function.
 pub type HandlerId = u64;
 
-/// Global counter for generating unique handler IDs
-static HANDLER_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+/// Default time a handler may block the UI thread before a warning is logged
+pub const SLOW_HANDLER_WARNING: Duration = Duration::from_millis(50);
 
-/// Generate a new unique handler ID
-pub fn next_handler_id() -> HandlerId {
-    HANDLER_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
+/// Calls of a handler and their durations, without the async part
+#[derive(Debug, Clone, PartialEq)]
+pub struct HandlerStats {
+    pub id: HandlerId,
+    /// The JS function's name, if it has one
+    pub name: Option<String>,
+    pub calls: u64,
+    pub total: Duration,
+    pub longest: Duration,
 }
 
-/// Stores a persistent reference to a JS function
-pub struct JsCallback {
-    pub func: Persistent<Function<'static>>,
+impl HandlerStats {
+    fn new(id: HandlerId, name: Option<String>) -> Self {
+        Self { id, name, calls: 0, total: Duration::ZERO, longest: Duration::ZERO }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        self.calls += 1;
+        self.total += elapsed;
+        self.longest = self.longest.max(elapsed);
+    }
+
+    /// `handler 12 (onDrag)`, for messages
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("handler {} ({})", self.id, name),
+            None => format!("handler {}", self.id),
+        }
+    }
 }
 
-/// Thread-safe event manager that stores JS callbacks
+/// Thread-safe event manager that dispatches to JS handlers
 #[derive(Clone)]
 pub struct EventManager {
     inner: Arc<RwLock<EventManagerInner>>,
+    /// Wakes the render scheduler when a render is requested
+    wake: UnboundedSender<()>,
 }
 
 struct EventManagerInner {
-    /// Map of handler ID -> JS callback
-    handlers: HashMap<HandlerId, JsCallback>,
-    /// Flag indicating if the UI needs to be re-rendered
-    needs_render: bool,
+    /// IDs in the JS handler table
+    handlers: HashSet<HandlerId>,
+    /// Open windows, with the handlers their trees use
+    windows: HashMap<WindowId, HashSet<HandlerId>>,
+    /// `__handlerFor(id)`, returning the handler with an ID
+    dispatch: Option<Persistent<Function<'static>>>,
+    /// Windows that need re-rendering
+    needs_render: HashSet<WindowId>,
+    /// Whether the reactive layer reports state changes (see `state_changed`)
+    tracks_changes: bool,
+    /// Whether state changed since the flag was last taken
+    changed: bool,
+    /// Calls of the handlers in the table
+    stats: HashMap<HandlerId, HandlerStats>,
+    /// How long a call may take before it's logged (zero disables it)
+    slow_handler_warning: Duration,
 }
 
 impl EventManager {
-    pub fn new() -> Self {
-        Self {
+    /// Create an empty manager; the receiver wakes when a render is requested
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        let manager = Self {
             inner: Arc::new(RwLock::new(EventManagerInner {
-                handlers: HashMap::new(),
-                needs_render: false,
+                handlers: HashSet::new(),
+                windows: HashMap::from([(MAIN_WINDOW, HashSet::new())]),
+                dispatch: None,
+                needs_render: HashSet::new(),
+                tracks_changes: false,
+                changed: false,
+                stats: HashMap::new(),
+                slow_handler_warning: SLOW_HANDLER_WARNING,
             })),
-        }
+            wake,
+        };
+        (manager, wakeups)
     }
 
-    /// Register a JS function as an event handler
-    pub fn register_handler(&self, id: HandlerId, func: Persistent<Function<'static>>) {
+    /// Set the JS function that looks handlers up by ID
+    pub fn set_dispatch(&self, dispatch: Persistent<Function<'static>>) {
         let mut inner = self.inner.write().unwrap();
-        inner.handlers.insert(id, JsCallback { func });
+        inner.dispatch = Some(dispatch);
+    }
+
+    /// Log handler calls that take longer than `threshold` (zero disables it)
+    pub fn set_slow_handler_warning(&self, threshold: Duration) {
+        let mut inner = self.inner.write().unwrap();
+        inner.slow_handler_warning = threshold;
+    }
+
+    /// Note a handler JS added to its table
+    pub fn register_handler(&self, id: HandlerId) {
+        let mut inner = self.inner.write().unwrap();
+        inner.handlers.insert(id);
     }
 
     /// Check if a handler exists
     pub fn has_handler(&self, id: HandlerId) -> bool {
         let inner = self.inner.read().unwrap();
-        inner.handlers.contains_key(&id)
+        inner.handlers.contains(&id)
     }
 
-    /// Remove a handler
-    pub fn remove_handler(&self, id: HandlerId) {
+    /// Note a window opened with `rasen.window.open`
+    pub fn add_window(&self, window: WindowId) {
         let mut inner = self.inner.write().unwrap();
-        inner.handlers.remove(&id);
+        inner.windows.insert(window, HashSet::new());
     }
 
-    /// Mark that UI needs re-rendering
+    /// Forget a closed window; returns the IDs of the handlers only it used,
+    /// for JS to drop from its table
+    pub fn remove_window(&self, window: WindowId) -> Vec<HandlerId> {
+        let mut inner = self.inner.write().unwrap();
+        inner.windows.remove(&window);
+        inner.needs_render.remove(&window);
+        inner.drop_unused()
+    }
+
+    /// Set the handlers `window`'s tree uses, `live`, and remove those no
+    /// window uses; returns their IDs, for JS to drop from its table
+    pub fn retain(&self, window: WindowId, live: HashSet<HandlerId>) -> Vec<HandlerId> {
+        let mut inner = self.inner.write().unwrap();
+        inner.windows.insert(window, live);
+        inner.drop_unused()
+    }
+
+    /// Remove every handler and the entry point, before the runtime they
+    /// belong to is freed
+    pub fn clear(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.handlers.clear();
+        inner.stats.clear();
+        inner.dispatch = None;
+    }
+
+    /// Mark that every window needs re-rendering, on the next frame
+    ///
+    /// Requests coalesce: the scheduler is woken once until every window
+    /// has taken its flag.
     pub fn request_render(&self) {
         let mut inner = self.inner.write().unwrap();
-        inner.needs_render = true;
+        if inner.needs_render.is_empty() {
+            let _ = self.wake.unbounded_send(());
+        }
+        let windows: Vec<WindowId> = inner.windows.keys().copied().collect();
+        inner.needs_render.extend(windows);
     }
 
-    /// Check and clear the render flag
-    pub fn take_render_request(&self) -> bool {
+    /// Check and clear `window`'s render flag
+    pub fn take_render_request(&self, window: WindowId) -> bool {
         let mut inner = self.inner.write().unwrap();
-        let needs = inner.needs_render;
-        inner.needs_render = false;
-        needs
+        inner.needs_render.remove(&window)
+    }
+
+    /// Rely on `state_changed` to learn of state changes, once the reactive
+    /// layer reports every one
+    pub fn track_state_changes(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.tracks_changes = true;
+    }
+
+    /// Take every handler as a change again, for a reactive layer that
+    /// can't see the writes
+    pub fn untrack_state_changes(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.tracks_changes = false;
+    }
+
+    /// Note that a ref or reactive property changed
+    pub fn state_changed(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.changed = true;
+    }
+
+    /// Check and clear the change flag; always true until the reactive layer
+    /// reports changes, as state may then change unnoticed
+    pub fn take_state_change(&self) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        let changed = inner.changed || !inner.tracks_changes;
+        inner.changed = false;
+        changed
+    }
+
+    /// Check and clear the change flag; false unless the reactive layer
+    /// reports changes
+    pub fn take_tracked_state_change(&self) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        let changed = inner.changed && inner.tracks_changes;
+        inner.changed = false;
+        changed
     }
 
     /// Get all handler IDs (for debugging)
@@ -81,35 +229,159 @@ impl EventManager {
         inner.handlers.len()
     }
 
-    /// Execute a handler by ID
-    pub fn invoke_handler(&self, id: HandlerId, ctx: &Context) -> bool {
-        let func = {
+    /// Calls of the handlers that ran, the most time taken first
+    pub fn handler_stats(&self) -> Vec<HandlerStats> {
+        let inner = self.inner.read().unwrap();
+        let mut stats: Vec<HandlerStats> = inner.stats.values().cloned().collect();
+        stats.sort_by(|a, b| b.total.cmp(&a.total).then(a.id.cmp(&b.id)));
+        stats
+    }
+
+    /// Count a call of handler `id`, warning if it took too long
+    fn record_call(&self, id: HandlerId, func: &Function, elapsed: Duration) {
+        let mut inner = self.inner.write().unwrap();
+        let threshold = inner.slow_handler_warning;
+        let stats = inner.stats.entry(id).or_insert_with(|| {
+            let name = func.get::<_, Option<String>>("name").ok().flatten().filter(|name| !name.is_empty());
+            HandlerStats::new(id, name)
+        });
+        stats.record(elapsed);
+        if !threshold.is_zero() && elapsed > threshold {
+            eprintln!(
+                "Warning: {} blocked the UI thread for {} ms (runtime.slowHandlerWarning is {})",
+                stats.label(),
+                elapsed.as_millis(),
+                threshold.as_millis()
+            );
+        }
+    }
+
+    /// Call a handler of `window` by ID with arguments, e.g. a list's
+    /// `(from, to)`; returns what it returned, or `None` if it isn't there
+    ///
+    /// An error the handler throws is left pending on `ctx` for the caller
+    /// to catch (see `JsRuntime::invoke_handler`).
+    pub fn call_handler<'js, A>(&self, ctx: &Ctx<'js>, window: WindowId, id: HandlerId, args: A) -> rquickjs::Result<Option<Value<'js>>>
+    where
+        A: IntoArgs<'js>,
+    {
+        let dispatch = {
             let inner = self.inner.read().unwrap();
-            inner.handlers.get(&id).map(|cb| cb.func.clone())
+            let routed = inner.windows.get(&window).is_some_and(|live| live.contains(&id));
+            inner.dispatch.clone().filter(|_| routed && inner.handlers.contains(&id))
         };
+        let Some(dispatch) = dispatch else {
+            eprintln!("Handler {} not found in window {}", id, window);
+            return Ok(None);
+        };
+        let func: Function = dispatch.restore(ctx)?.call((id,))?;
+        let start = Instant::now();
+        let result = func.call::<_, Value>(args);
+        self.record_call(id, &func, start.elapsed());
+        let result = result?;
+        // Async handlers finish as promise jobs run; JS re-renders when they
+        // settle and reports rejections
+        if result.as_object().is_some_and(|o| o.get::<_, Function>("then").is_ok()) {
+            let settle: Function = ctx.globals().get("__settleHandler")?;
+            settle.call::<_, ()>((result.clone(), id))?;
+        }
+        // The caller re-renders if state changed (see `take_state_change`)
+        Ok(Some(result))
+    }
+}
 
-        if let Some(persistent_func) = func {
-            ctx.with(|ctx| {
-                if let Ok(func) = persistent_func.restore(&ctx) {
-                    if let Err(e) = func.call::<_, ()>(()) {
-                        eprintln!("Error invoking handler {}: {:?}", id, e);
-                    } else {
-                        // Handler executed successfully, request render
-                        self.request_render();
-                        return true;
-                    }
-                }
-                false
-            })
-        } else {
-            eprintln!("Handler {} not found", id);
-            false
+impl EventManagerInner {
+    /// Remove the handlers no window uses; returns their IDs
+    fn drop_unused(&mut self) -> Vec<HandlerId> {
+        let dead: Vec<HandlerId> = self
+            .handlers
+            .iter()
+            .filter(|id| !self.windows.values().any(|live| live.contains(id)))
+            .copied()
+            .collect();
+        for id in &dead {
+            self.handlers.remove(id);
+            self.stats.remove(id);
         }
+        dead
     }
 }
 
 impl Default for EventManager {
     fn default() -> Self {
-        Self::new()
+        Self::new().0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactivity;
+    use rquickjs::{Context, Runtime};
+
+    #[test]
+    fn test_handler_changing_nothing_schedules_no_render() {
+        let (events, mut wakeups) = EventManager::new();
+        events.track_state_changes();
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            ctx.eval::<(), _>("var __modules = {};").unwrap();
+            let changes = events.clone();
+            reactivity::report_changes(&ctx, move || changes.state_changed()).unwrap();
+            reactivity::install(&ctx).unwrap();
+            ctx.eval::<(), _>(
+                "var r = __modules['@rasenjs/reactive-signals'];
+                 var count = r.ref(0), state = r.reactive({ hovered: false });
+                 function onMouseMove() { count.value; state.hovered = false; count.value = 0; }
+                 function onClick() { count.value++; }",
+            )
+            .unwrap();
+            // Reads, and writes of the values already there, aren't changes
+            ctx.eval::<(), _>("onMouseMove()").unwrap();
+            assert!(!events.take_state_change());
+            ctx.eval::<(), _>("onClick()").unwrap();
+            assert!(events.take_state_change());
+            assert!(!events.take_state_change());
+        });
+        assert!(wakeups.try_next().is_err());
+    }
+
+    #[test]
+    fn test_retain_drops_handlers_no_window_uses() {
+        let (events, _) = EventManager::new();
+        events.add_window(2);
+        events.register_handler(1);
+        events.register_handler(2);
+        assert!(events.retain(MAIN_WINDOW, HashSet::from([1, 2])).is_empty());
+        events.register_handler(3);
+        events.register_handler(4);
+        assert_eq!(events.retain(2, HashSet::from([2, 3])), vec![4]);
+        assert_eq!(events.retain(MAIN_WINDOW, HashSet::from([2])), vec![1]);
+        assert!(!events.has_handler(1) && !events.has_handler(4));
+
+        // Handler 2 is still used by the main window
+        assert_eq!(events.remove_window(2), vec![3]);
+        assert!(events.has_handler(2));
+        assert_eq!(events.handler_count(), 1);
+    }
+
+    #[test]
+    fn test_tracked_state_change() {
+        let (events, _) = EventManager::new();
+        // Until the reactive layer reports changes, every handler may have changed state
+        assert!(events.take_state_change());
+        assert!(!events.take_tracked_state_change());
+
+        events.track_state_changes();
+        assert!(!events.take_state_change());
+        events.state_changed();
+        assert!(events.take_tracked_state_change());
+        assert!(!events.take_state_change());
+
+        events.state_changed();
+        events.untrack_state_changes();
+        assert!(!events.take_tracked_state_change());
+        assert!(events.take_state_change());
     }
 }
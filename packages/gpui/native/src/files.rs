@@ -0,0 +1,500 @@
+//! File system access for JS on `rasen.fs`
+//!
+//! `readFile`, `writeFile`, `readdir`, `stat`, `mkdir` and `remove` return
+//! promises, like `fs/promises`. The JS functions only queue a [`Request`];
+//! the window's file task runs it on GPUI's background executor and settles
+//! the promise through the runtime, re-rendering afterwards. Like `timers`,
+//! each promise's `resolve`/`reject` pair stays on the JS side in a table
+//! keyed by request ID.
+//!
+//! Every path is checked against the [`Sandbox`] before it's touched. Files
+//! are read and written as UTF-8 text.
+//!
+//! The queue also runs the `rasen.image` operations (see `images`) and
+//! camera snapshots (see `camera`), which are blocking work too.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rquickjs::{Array, Ctx, Exception, Function, IntoJs, Object, TypedArray, Value};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+use crate::images::{self, ImageInfo, ImageOp};
+use crate::{builtins, camera, native_api};
+
+/// Global holding `[resolve, reject]` by request ID
+const PENDING: &str = "__fsPending";
+
+static SANDBOX: OnceLock<Sandbox> = OnceLock::new();
+
+/// Directories JS code may access
+///
+/// `fs.allow` in rasen.config.js lists them, relative to the project root or
+/// starting with `~` for the home directory. Without it everything is
+/// accessible, except in hardened mode, where nothing is.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    /// Resolved allowed directories, or `None` for no restriction
+    allowed: Option<Vec<PathBuf>>,
+}
+
+impl Sandbox {
+    pub fn new(allow: Option<&[String]>, base: &Path, hardened: bool) -> Self {
+        let allowed = match allow {
+            Some(dirs) => Some(dirs.iter().map(|dir| resolve(&expand_home(dir), base)).collect()),
+            None if hardened => Some(Vec::new()),
+            None => None,
+        };
+        Self { allowed }
+    }
+
+    /// A sandbox allowing `dir` alone, e.g. for scripts the app loads itself
+    pub fn within(dir: &Path) -> Self {
+        Self { allowed: Some(vec![resolve(dir, &env::current_dir().unwrap_or_default())]) }
+    }
+
+    /// Resolve `path` against the working directory and check that it's allowed
+    pub fn check(&self, path: &str) -> Result<PathBuf, FsError> {
+        self.check_in(path, &env::current_dir().unwrap_or_default())
+    }
+
+    /// Resolve `path` against `base` and check that it's allowed
+    pub fn check_in(&self, path: &str, base: &Path) -> Result<PathBuf, FsError> {
+        self.allow(path, resolve(Path::new(path), base))
+    }
+
+    /// Like [`check`](Self::check), but a symlink at `path` stays a link, so
+    /// removing it doesn't remove its target
+    pub fn check_link(&self, path: &str) -> Result<PathBuf, FsError> {
+        let absolute = env::current_dir().unwrap_or_default().join(path);
+        let resolved = match (absolute.parent(), absolute.file_name()) {
+            (Some(parent), Some(name)) => resolve(parent, Path::new("/")).join(name),
+            _ => resolve(&absolute, Path::new("/")),
+        };
+        self.allow(path, resolved)
+    }
+
+    fn allow(&self, path: &str, resolved: PathBuf) -> Result<PathBuf, FsError> {
+        match &self.allowed {
+            Some(dirs) if !dirs.iter().any(|dir| resolved.starts_with(dir)) => Err(FsError {
+                code: "EPERM",
+                message: format!("'{}' is outside the directories in fs.allow", path),
+            }),
+            _ => Ok(resolved),
+        }
+    }
+}
+
+/// Set the process-wide sandbox (once, before any script runs)
+pub fn init(sandbox: Sandbox) {
+    let _ = SANDBOX.set(sandbox);
+}
+
+/// Check `path` against the process-wide sandbox
+pub fn check(path: &str) -> Result<PathBuf, FsError> {
+    SANDBOX.get_or_init(Sandbox::default).check(path)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => match builtins::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+            None => PathBuf::from(path),
+        },
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Absolute form of `path` with `.` and `..` removed and the symlinks of its
+/// existing part resolved, so neither can lead out of an allowed directory
+fn resolve(path: &Path, base: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            other => absolute.push(other),
+        }
+    }
+
+    // Paths being created don't exist yet: canonicalize their nearest ancestor
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest.iter().rev().fold(canonical, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return absolute,
+        }
+    }
+}
+
+/// A failed operation, rejected as an `Error` with a Node-style `code`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FsError {
+    pub(crate) fn io(action: &str, path: &Path, error: io::Error) -> Self {
+        let code = match error.kind() {
+            io::ErrorKind::NotFound => "ENOENT",
+            io::ErrorKind::PermissionDenied => "EACCES",
+            io::ErrorKind::AlreadyExists => "EEXIST",
+            io::ErrorKind::InvalidData => "EILSEQ",
+            _ => "EIO",
+        };
+        Self { code, message: format!("Can't {} '{}': {}", action, path.display(), error) }
+    }
+}
+
+/// An entry of a directory listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    pub name: String,
+    /// `dir` joined with `name`
+    pub path: String,
+    /// Follows symlinks, so links to directories count as directories
+    pub is_directory: bool,
+}
+
+/// List `dir`; entries whose names aren't valid UTF-8 are left out
+pub fn read_dir(dir: &Path) -> io::Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let path = entry.path();
+        entries.push(DirEntry {
+            name,
+            is_directory: path.is_dir(),
+            path: path.to_string_lossy().into_owned(),
+        });
+    }
+    Ok(entries)
+}
+
+/// What `stat` reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stat {
+    pub size: u64,
+    pub is_file: bool,
+    pub is_directory: bool,
+    /// Last modification, in milliseconds since the Unix epoch
+    pub mtime_ms: f64,
+}
+
+#[derive(Debug)]
+enum Op {
+    ReadFile(String),
+    WriteFile(String, String),
+    ReadDir(String),
+    Stat(String),
+    Mkdir { path: String, recursive: bool },
+    Remove { path: String, recursive: bool },
+    Image(ImageOp),
+    /// A camera frame, from a device or the default one
+    Snapshot(Option<String>),
+}
+
+/// Result of an operation, converted to JS when its promise settles
+#[derive(Debug)]
+pub enum Output {
+    Text(String),
+    Entries(Vec<DirEntry>),
+    Stat(Stat),
+    Image(ImageInfo),
+    /// Resolved as a `Uint8Array`
+    Bytes(Vec<u8>),
+    Done,
+}
+
+/// A queued operation
+#[derive(Debug)]
+pub struct Request {
+    id: u32,
+    op: Op,
+}
+
+impl Request {
+    /// Run the operation, blocking; returns the request ID with the result
+    pub fn run(self) -> (u32, Result<Output, FsError>) {
+        let sandbox = SANDBOX.get_or_init(Sandbox::default);
+        let result = match self.op {
+            Op::ReadFile(path) => sandbox.check(&path).and_then(|path| {
+                fs::read_to_string(&path).map(Output::Text).map_err(|e| FsError::io("read", &path, e))
+            }),
+            Op::WriteFile(path, data) => sandbox.check(&path).and_then(|path| {
+                fs::write(&path, data).map(|_| Output::Done).map_err(|e| FsError::io("write", &path, e))
+            }),
+            Op::ReadDir(path) => sandbox.check(&path).and_then(|path| {
+                read_dir(&path).map(Output::Entries).map_err(|e| FsError::io("read directory", &path, e))
+            }),
+            Op::Stat(path) => sandbox.check(&path).and_then(|path| {
+                // Like Node's `stat`, this describes what a link points to
+                let stat = |path: &Path| -> io::Result<Stat> {
+                    let metadata = fs::metadata(path)?;
+                    let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    Ok(Stat {
+                        size: metadata.len(),
+                        is_file: metadata.is_file(),
+                        is_directory: metadata.is_dir(),
+                        mtime_ms: modified.as_secs_f64() * 1000.0,
+                    })
+                };
+                stat(&path).map(Output::Stat).map_err(|e| FsError::io("stat", &path, e))
+            }),
+            Op::Mkdir { path, recursive } => sandbox.check(&path).and_then(|path| {
+                let result = if recursive { fs::create_dir_all(&path) } else { fs::create_dir(&path) };
+                result.map(|_| Output::Done).map_err(|e| FsError::io("create directory", &path, e))
+            }),
+            Op::Remove { path, recursive } => sandbox.check_link(&path).and_then(|path| {
+                let result = match fs::symlink_metadata(&path) {
+                    Ok(metadata) if metadata.is_dir() && recursive => fs::remove_dir_all(&path),
+                    Ok(metadata) if metadata.is_dir() => fs::remove_dir(&path),
+                    Ok(_) => fs::remove_file(&path),
+                    Err(e) => Err(e),
+                };
+                result.map(|_| Output::Done).map_err(|e| FsError::io("remove", &path, e))
+            }),
+            Op::Image(op) => images::run(op, sandbox).map(Output::Image),
+            Op::Snapshot(device) => camera::snapshot(device.as_deref()).map(Output::Bytes),
+        };
+        (self.id, result)
+    }
+}
+
+/// A runtime's queued file operations; clones share the queue
+#[derive(Clone)]
+pub struct Files {
+    queue: Arc<Mutex<FileQueue>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct FileQueue {
+    next_id: u32,
+    requests: Vec<Request>,
+}
+
+impl Files {
+    /// Create an empty queue; the receiver wakes when a request is queued
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { queue: Arc::default(), wake }, wakeups)
+    }
+
+    /// Take the requests queued since the last call
+    pub fn take_requests(&self) -> Vec<Request> {
+        std::mem::take(&mut self.queue.lock().unwrap().requests)
+    }
+
+    /// Queue an operation; returns its promise
+    fn submit<'js>(&self, ctx: &Ctx<'js>, op: Op) -> rquickjs::Result<Value<'js>> {
+        let (promise, resolve, reject) = ctx.promise()?;
+        let id = {
+            let mut queue = self.queue.lock().unwrap();
+            // IDs start at 1, like timer IDs
+            queue.next_id += 1;
+            let id = queue.next_id;
+            queue.requests.push(Request { id, op });
+            id
+        };
+        let pending: Object = ctx.globals().get(PENDING)?;
+        let entry = Array::new(ctx.clone())?;
+        entry.set(0, resolve)?;
+        entry.set(1, reject)?;
+        pending.set(id.to_string(), entry)?;
+
+        let _ = self.wake.unbounded_send(());
+        promise.into_js(ctx)
+    }
+
+    /// Queue an image operation; returns its promise
+    pub fn submit_image<'js>(&self, ctx: &Ctx<'js>, op: ImageOp) -> rquickjs::Result<Value<'js>> {
+        self.submit(ctx, Op::Image(op))
+    }
+
+    /// Queue a camera snapshot; returns its promise
+    pub fn submit_snapshot<'js>(&self, ctx: &Ctx<'js>, device: Option<String>) -> rquickjs::Result<Value<'js>> {
+        self.submit(ctx, Op::Snapshot(device))
+    }
+}
+
+/// Resolve or reject the promise of request `id`
+pub fn settle(ctx: &Ctx, id: u32, result: Result<Output, FsError>) {
+    let Ok(pending) = ctx.globals().get::<_, Object>(PENDING) else {
+        return;
+    };
+    let Ok(entry) = pending.get::<_, Array>(id.to_string()) else {
+        return;
+    };
+    let _ = pending.remove(id.to_string());
+
+    let settled = match result {
+        Ok(output) => output_to_js(ctx, output)
+            .and_then(|value| entry.get::<Function>(0)?.call::<_, ()>((value,))),
+        Err(error) => Exception::from_message(ctx.clone(), &error.message)
+            .and_then(|exception| {
+                exception.set("code", error.code)?;
+                entry.get::<Function>(1)?.call::<_, ()>((exception,))
+            }),
+    };
+    if let Err(e) = settled {
+        eprintln!("Error settling rasen.fs request {}: {:?}", id, e);
+    }
+}
+
+fn output_to_js<'js>(ctx: &Ctx<'js>, output: Output) -> rquickjs::Result<Value<'js>> {
+    match output {
+        Output::Text(text) => text.into_js(ctx),
+        Output::Entries(entries) => {
+            let list = Array::new(ctx.clone())?;
+            for (index, entry) in entries.into_iter().enumerate() {
+                let object = Object::new(ctx.clone())?;
+                object.set("name", entry.name)?;
+                object.set("path", entry.path)?;
+                object.set("isDirectory", entry.is_directory)?;
+                list.set(index, object)?;
+            }
+            Ok(list.into_value())
+        }
+        Output::Stat(stat) => {
+            let object = Object::new(ctx.clone())?;
+            // Sizes past 2^53 aren't exact in JS anyway
+            object.set("size", stat.size as f64)?;
+            object.set("isFile", stat.is_file)?;
+            object.set("isDirectory", stat.is_directory)?;
+            object.set("mtimeMs", stat.mtime_ms)?;
+            Ok(object.into_value())
+        }
+        Output::Image(image) => {
+            let object = Object::new(ctx.clone())?;
+            object.set("id", image.id)?;
+            object.set("width", image.width)?;
+            object.set("height", image.height)?;
+            Ok(object.into_value())
+        }
+        Output::Bytes(bytes) => TypedArray::<u8>::new(ctx.clone(), bytes)?.into_js(ctx),
+        Output::Done => Ok(Value::new_undefined(ctx.clone())),
+    }
+}
+
+/// Read `{ recursive }` from an options object
+fn recursive(options: Option<Object>) -> rquickjs::Result<bool> {
+    Ok(match options {
+        Some(options) => options.get::<_, Option<bool>>("recursive")?.unwrap_or(false),
+        None => false,
+    })
+}
+
+/// Install `rasen.fs` for a runtime's queue
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, files: &Files) -> rquickjs::Result<()> {
+    ctx.globals().set(PENDING, Object::new(ctx.clone())?)?;
+    let namespace = native_api::namespace(ctx, rasen, "fs")?;
+
+    let queue = files.clone();
+    namespace.set(
+        "readFile",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String| queue.submit(&ctx, Op::ReadFile(path)))?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "writeFile",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String, data: String| {
+            queue.submit(&ctx, Op::WriteFile(path, data))
+        })?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "readdir",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String| queue.submit(&ctx, Op::ReadDir(path)))?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "stat",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String| queue.submit(&ctx, Op::Stat(path)))?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "mkdir",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String, options: Option<Object<'js>>| {
+            let recursive = recursive(options)?;
+            queue.submit(&ctx, Op::Mkdir { path, recursive })
+        })?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "remove",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String, options: Option<Object<'js>>| {
+            let recursive = recursive(options)?;
+            queue.submit(&ctx, Op::Remove { path, recursive })
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rasen-files-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("data/nested")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = temp_dir("resolve");
+        assert_eq!(resolve(Path::new("data/./nested/../x.txt"), &dir), dir.join("data/x.txt"));
+        // Missing parts are appended to the canonical existing ancestor
+        assert_eq!(resolve(Path::new("data/new/deeper"), &dir), dir.join("data/new/deeper"));
+        assert_eq!(resolve(&dir.join("data"), Path::new("/elsewhere")), dir.join("data"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let dir = temp_dir("sandbox");
+        let sandbox = Sandbox::new(Some(&["data".to_string()]), &dir, false);
+        let inside = dir.join("data/nested/file.txt");
+        assert_eq!(sandbox.check(inside.to_str().unwrap()), Ok(inside));
+        let escape = dir.join("data/../secret.txt");
+        assert_eq!(sandbox.check(escape.to_str().unwrap()).unwrap_err().code, "EPERM");
+        let scripts = Sandbox::within(&dir.join("data"));
+        assert_eq!(scripts.check_in("nested/file.txt", &dir.join("data")), Ok(dir.join("data/nested/file.txt")));
+        assert!(scripts.check_in("../secret.txt", &dir.join("data")).is_err());
+        assert!(scripts.check_in(dir.join("secret.txt").to_str().unwrap(), &dir.join("data")).is_err());
+
+        #[cfg(unix)]
+        {
+            // A link inside pointing outside doesn't count as inside
+            std::os::unix::fs::symlink(&dir, dir.join("data/link")).unwrap();
+            let linked = dir.join("data/link/secret.txt");
+            assert_eq!(sandbox.check(linked.to_str().unwrap()).unwrap_err().code, "EPERM");
+            // ...but the link itself can be removed
+            let link = dir.join("data/link");
+            assert_eq!(sandbox.check_link(link.to_str().unwrap()), Ok(link));
+        }
+
+        let open = Sandbox::new(None, &dir, false);
+        assert!(open.check(dir.join("secret.txt").to_str().unwrap()).is_ok());
+        let hardened = Sandbox::new(None, &dir, true);
+        assert!(hardened.check(dir.to_str().unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
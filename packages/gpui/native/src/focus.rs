@@ -0,0 +1,80 @@
+//! Keyboard focus within a window
+//!
+//! The window's root tracks a focus handle, focused when the window opens,
+//! so keys and menu actions reach the app. Elements that take keys
+//! (`keymap` elements, color and date pickers, trees, Edit menu handlers) or
+//! have `focus:` styles, text included, track one of their own, kept by
+//! element ID across renders.
+//! Clicking such an element focuses it, which applies its `focus:` styles
+//! and decides which keymaps apply (see `keymap`).
+
+use gpui::{App, FocusHandle, Window};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use crate::elements::{DivElement, Element};
+use crate::tw_parser::ParsedStyles;
+
+/// Focus handles of the elements that take focus, by element ID
+#[derive(Default)]
+pub struct FocusTracker {
+    handles: RefCell<HashMap<String, FocusHandle>>,
+}
+
+impl FocusTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give the elements of `root` that take focus a handle, keeping the
+    /// ones they had and dropping those of elements that are gone
+    pub fn update(&self, root: &Element, cx: &mut App) {
+        let mut handles = self.handles.borrow_mut();
+        let mut kept = HashMap::new();
+        let mut stack = vec![root];
+        while let Some(element) = stack.pop() {
+            let focusable = match element {
+                Element::Div(div) => {
+                    stack.extend(&div.children);
+                    takes_focus(div)
+                }
+                Element::Text(text) => has_focus_styles(&text.styles),
+            };
+            if focusable {
+                let id = element.id();
+                let handle = handles.remove(id).unwrap_or_else(|| cx.focus_handle());
+                kept.insert(id.to_string(), handle);
+            }
+        }
+        *handles = kept;
+    }
+
+    /// The handle of element `id`, if it takes focus
+    pub fn handle(&self, id: &str) -> Option<FocusHandle> {
+        self.handles.borrow().get(id).cloned()
+    }
+
+    /// The ID of the focused element, if any is
+    pub fn focused(&self, window: &Window) -> Option<String> {
+        let handles = self.handles.borrow();
+        handles.iter().find(|(_, handle)| handle.is_focused(window)).map(|(id, _)| id.clone())
+    }
+}
+
+fn takes_focus(div: &DivElement) -> bool {
+    div.keymap.is_some()
+        || div.color_picker.is_some()
+        || div.date_picker.is_some()
+        || div.tree.is_some()
+        || [div.handlers.on_cut, div.handlers.on_copy, div.handlers.on_paste, div.handlers.on_select_all]
+            .iter()
+            .any(Option::is_some)
+        || has_focus_styles(&div.styles)
+}
+
+/// Whether `styles` has `focus:` classes, under any other variant too
+fn has_focus_styles(styles: &ParsedStyles) -> bool {
+    styles.focus.is_some()
+        || styles.breakpoints.iter().any(|(_, breakpoint)| has_focus_styles(breakpoint))
+        || styles.dark.as_deref().is_some_and(has_focus_styles)
+        || styles.contrast_more.as_deref().is_some_and(has_focus_styles)
+}
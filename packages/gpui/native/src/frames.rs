@@ -0,0 +1,153 @@
+//! `requestAnimationFrame` / `cancelAnimationFrame`
+//!
+//! Each runtime owns an [`AnimationFrames`] queue. Requesting a frame wakes
+//! the window, which runs the callbacks at the start of its next render,
+//! re-renders with their changes and keeps asking GPUI for frames while
+//! callbacks are pending. JS animations and game loops are paced by the
+//! display that way, without timers or busy loops.
+//!
+//! Like browsers, callbacks requested while callbacks run wait for the next
+//! frame, and every callback of a frame gets the same timestamp.
+//! Callbacks stay on the JS side in a table keyed by request ID, like timers.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::js_runtime;
+
+/// Global holding callbacks by request ID
+const CALLBACKS: &str = "__animationFrames";
+
+/// A runtime's requested frames; clones share the queue
+#[derive(Clone)]
+pub struct AnimationFrames {
+    queue: Arc<Mutex<FrameQueue>>,
+    wake: UnboundedSender<()>,
+    /// Time origin of the timestamps passed to callbacks
+    origin: Instant,
+}
+
+#[derive(Debug, Default)]
+struct FrameQueue {
+    next_id: u32,
+    /// Request IDs in request order
+    pending: Vec<u32>,
+}
+
+impl FrameQueue {
+    fn add(&mut self) -> u32 {
+        // IDs start at 1, so they are always truthy
+        self.next_id += 1;
+        self.pending.push(self.next_id);
+        self.next_id
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.pending.retain(|pending| *pending != id);
+    }
+
+    /// Take the requests for this frame; later ones wait for the next
+    fn take(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+impl AnimationFrames {
+    /// Create an empty queue; the receiver wakes when a frame is requested
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { queue: Arc::default(), wake, origin: Instant::now() }, wakeups)
+    }
+
+    /// Whether any callback waits for the next frame
+    pub fn pending(&self) -> bool {
+        !self.queue.lock().unwrap().pending.is_empty()
+    }
+
+    /// Run the callbacks requested for this frame; returns whether any ran
+    pub fn fire(&self, ctx: &Ctx) -> bool {
+        let ids = self.queue.lock().unwrap().take();
+        if ids.is_empty() {
+            return false;
+        }
+        let Ok(callbacks) = ctx.globals().get::<_, Object>(CALLBACKS) else {
+            return false;
+        };
+        let timestamp = self.origin.elapsed().as_secs_f64() * 1000.0;
+
+        let mut fired = false;
+        for id in ids {
+            // Cancelled by an earlier callback in this frame
+            let Ok(callback) = callbacks.get::<_, Function>(id.to_string()) else {
+                continue;
+            };
+            let _ = callbacks.remove(id.to_string());
+            match callback.call::<_, Value>((timestamp,)) {
+                Ok(result) => js_runtime::report_rejection(ctx, &result, format!("async animation frame {}", id)),
+                Err(e) => eprintln!("Error in animation frame callback {}: {:?}", id, e),
+            }
+            fired = true;
+        }
+        fired
+    }
+
+    fn request<'js>(&self, ctx: &Ctx<'js>, callback: Value<'js>) -> rquickjs::Result<u32> {
+        let Some(callback) = callback.into_function() else {
+            return Err(Exception::throw_type(ctx, "Animation frame callback must be a function"));
+        };
+        let id = self.queue.lock().unwrap().add();
+        let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+        callbacks.set(id.to_string(), callback)?;
+
+        let _ = self.wake.unbounded_send(());
+        Ok(id)
+    }
+}
+
+/// Install the animation frame globals for a runtime's queue
+pub fn install<'js>(ctx: &Ctx<'js>, frames: &AnimationFrames) -> rquickjs::Result<()> {
+    let globals = ctx.globals();
+    globals.set(CALLBACKS, Object::new(ctx.clone())?)?;
+
+    let requests = frames.clone();
+    globals.set(
+        "requestAnimationFrame",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, callback: Value<'js>| requests.request(&ctx, callback))?,
+    )?;
+    let frames = frames.clone();
+    globals.set(
+        "cancelAnimationFrame",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, id: Opt<Value<'js>>| -> rquickjs::Result<()> {
+            let Some(id) = id.0.and_then(|id| id.as_number()).filter(|id| *id >= 1.0) else {
+                return Ok(());
+            };
+            let id = id as u32;
+            frames.queue.lock().unwrap().remove(id);
+            let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+            callbacks.remove(id.to_string())
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_run_once_in_request_order() {
+        let mut queue = FrameQueue::default();
+        let first = queue.add();
+        let cancelled = queue.add();
+        let last = queue.add();
+        queue.remove(cancelled);
+        assert_eq!(queue.take(), vec![first, last]);
+
+        // Requested during the frame: left for the next one
+        let next = queue.add();
+        assert_eq!(queue.take(), vec![next]);
+        assert!(queue.take().is_empty());
+    }
+}
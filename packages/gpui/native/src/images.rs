@@ -0,0 +1,281 @@
+//! Image decoding and resizing for `rasen.image`, shown with the `img` element
+//!
+//! `decode`, `open`, `resize` and `thumbnail` return promises and run on the
+//! background executor through the `rasen.fs` queue, so a gallery can
+//! process a whole folder without blocking the UI. Each resolves with a
+//! handle `{ id, width, height }` to an image kept natively, ready to draw;
+//! JS passes handles to `img({ src })` and frees them with `release`.
+//!
+//! Images are stored as single frames of BGRA pixels, the order GPUI draws.
+//! Resizing works on that data directly, since filters treat each channel
+//! the same.
+
+use gpui::*;
+use image::imageops::{self, FilterType};
+use image::{Frame, RgbaImage};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use crate::files::{Files, FsError, Sandbox};
+use crate::native_api;
+
+static IMAGES: OnceLock<Mutex<HashMap<u32, Arc<RenderImage>>>> = OnceLock::new();
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+fn images() -> &'static Mutex<HashMap<u32, Arc<RenderImage>>> {
+    IMAGES.get_or_init(Mutex::default)
+}
+
+/// A stored image, as passed to JS
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageInfo {
+    pub id: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An image to work on: a stored one or a file
+#[derive(Debug)]
+pub enum Source {
+    Handle(u32),
+    Path(String),
+}
+
+#[derive(Debug)]
+pub enum ImageOp {
+    /// Decode encoded bytes (PNG, JPEG, GIF, WebP, BMP)
+    Decode(Vec<u8>),
+    /// Read and decode a file
+    Open(String),
+    /// Scale to `width` and `height`, or to `width` keeping the aspect ratio
+    Resize { id: u32, width: u32, height: Option<u32> },
+    /// Shrink to fit in a `size` square, keeping the aspect ratio
+    Thumbnail { source: Source, size: u32 },
+}
+
+/// Stored image `id`, if it hasn't been released
+pub fn get(id: u32) -> Option<Arc<RenderImage>> {
+    images().lock().unwrap().get(&id).cloned()
+}
+
+/// Free stored image `id`; returns whether it existed
+pub fn release(id: u32) -> bool {
+    images().lock().unwrap().remove(&id).is_some()
+}
+
+fn invalid(message: String) -> FsError {
+    FsError { code: "EINVAL", message }
+}
+
+/// Store BGRA pixels as a new image
+fn store(pixels: RgbaImage) -> ImageInfo {
+    let (width, height) = pixels.dimensions();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let image = Arc::new(RenderImage::new([Frame::new(pixels)]));
+    images().lock().unwrap().insert(id, image);
+    ImageInfo { id, width, height }
+}
+
+/// Decoded RGBA pixels reordered to BGRA
+fn to_bgra(image: image::DynamicImage) -> RgbaImage {
+    let mut pixels = image.into_rgba8();
+    for pixel in pixels.pixels_mut() {
+        pixel.0.swap(0, 2);
+    }
+    pixels
+}
+
+/// Pixels of stored image `id`
+fn pixels(id: u32) -> Result<RgbaImage, FsError> {
+    let image = get(id).ok_or_else(|| invalid(format!("No image with ID {} (was it released?)", id)))?;
+    let size = image.size(0);
+    let bytes = image.as_bytes(0).unwrap_or_default().to_vec();
+    RgbaImage::from_raw(size.width.0 as u32, size.height.0 as u32, bytes)
+        .ok_or_else(|| invalid(format!("Image {} has no pixel data", id)))
+}
+
+fn open(path: &str, sandbox: &Sandbox) -> Result<RgbaImage, FsError> {
+    let path = sandbox.check(path)?;
+    let image = image::open(&path).map_err(|e| match e {
+        image::ImageError::IoError(e) => FsError::io("read", &path, e),
+        e => invalid(format!("Can't decode '{}': {}", path.display(), e)),
+    })?;
+    Ok(to_bgra(image))
+}
+
+/// Size of a `width` x `height` image scaled down to fit in a `size` square
+pub fn fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+    if width <= size && height <= size {
+        return (width, height);
+    }
+    let scale = size as f64 / width.max(height) as f64;
+    let scaled = |length: u32| ((length as f64 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// Run an operation, blocking
+pub fn run(op: ImageOp, sandbox: &Sandbox) -> Result<ImageInfo, FsError> {
+    match op {
+        ImageOp::Decode(bytes) => {
+            let image = image::load_from_memory(&bytes).map_err(|e| invalid(format!("Can't decode image: {}", e)))?;
+            Ok(store(to_bgra(image)))
+        }
+        ImageOp::Open(path) => Ok(store(open(&path, sandbox)?)),
+        ImageOp::Resize { id, width, height } => {
+            let pixels = pixels(id)?;
+            let height = height.unwrap_or_else(|| {
+                (pixels.height() as f64 * width as f64 / pixels.width().max(1) as f64).round() as u32
+            });
+            if width == 0 || height == 0 {
+                return Err(invalid(format!("Can't resize image {} to {}x{}", id, width, height)));
+            }
+            Ok(store(imageops::resize(&pixels, width, height, FilterType::Lanczos3)))
+        }
+        ImageOp::Thumbnail { source, size } => {
+            let pixels = match source {
+                Source::Handle(id) => pixels(id)?,
+                Source::Path(path) => open(&path, sandbox)?,
+            };
+            let (width, height) = fit(pixels.width(), pixels.height(), size.max(1));
+            // `thumbnail` averages pixels, which is fast and fine for shrinking
+            Ok(store(imageops::thumbnail(&pixels, width, height)))
+        }
+    }
+}
+
+/// ID of a handle passed from JS: `{ id }` or the ID itself
+fn handle_id<'js>(ctx: &Ctx<'js>, value: &Value<'js>) -> rquickjs::Result<u32> {
+    let id = match value.as_object() {
+        Some(handle) => handle.get::<_, Option<f64>>("id")?,
+        None => value.as_number(),
+    };
+    id.map(|id| id as u32).ok_or_else(|| Exception::throw_type(ctx, "Expected an image handle"))
+}
+
+/// Install `rasen.image`, queueing operations on the runtime's file queue
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, files: &Files) -> rquickjs::Result<()> {
+    let namespace = native_api::namespace(ctx, rasen, "image")?;
+
+    let queue = files.clone();
+    namespace.set(
+        "decode",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, bytes: Object<'js>| {
+            // An ArrayBuffer or a Uint8Array
+            let bytes = match (bytes.as_array_buffer(), bytes.as_typed_array::<u8>()) {
+                (Some(buffer), _) => buffer.as_bytes().map(<[u8]>::to_vec),
+                (_, Some(array)) => array.as_bytes().map(<[u8]>::to_vec),
+                _ => None,
+            };
+            let bytes = bytes.ok_or_else(|| Exception::throw_type(&ctx, "decode expects an ArrayBuffer or Uint8Array"))?;
+            queue.submit_image(&ctx, ImageOp::Decode(bytes))
+        })?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "open",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String| queue.submit_image(&ctx, ImageOp::Open(path)))?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "resize",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, image: Value<'js>, width: f64, height: Opt<f64>| {
+            let id = handle_id(&ctx, &image)?;
+            let op = ImageOp::Resize { id, width: width.max(0.0) as u32, height: height.0.map(|h| h.max(0.0) as u32) };
+            queue.submit_image(&ctx, op)
+        })?,
+    )?;
+    let queue = files.clone();
+    namespace.set(
+        "thumbnail",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, source: Value<'js>, size: f64| {
+            let source = match source.as_string() {
+                Some(path) => Source::Path(path.to_string()?),
+                None => Source::Handle(handle_id(&ctx, &source)?),
+            };
+            queue.submit_image(&ctx, ImageOp::Thumbnail { source, size: size.max(0.0) as u32 })
+        })?,
+    )?;
+    namespace.set(
+        "release",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, image: Value<'js>| -> rquickjs::Result<bool> {
+            Ok(release(handle_id(&ctx, &image)?))
+        })?,
+    )?;
+    Ok(())
+}
+
+/// What an `img` element shows
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSrc {
+    Handle(u32),
+    Path(PathBuf),
+}
+
+/// Props of an `img` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageView {
+    /// `None` if the path was outside the `fs.allow` directories
+    pub src: Option<ImageSrc>,
+    pub fit: ObjectFit,
+}
+
+/// Parse an `img` element's `fit` prop; `contain` by default
+pub fn object_fit(name: Option<&str>) -> ObjectFit {
+    match name {
+        Some("cover") => ObjectFit::Cover,
+        Some("fill") => ObjectFit::Fill,
+        Some("none") => ObjectFit::None,
+        Some("scale-down") => ObjectFit::ScaleDown,
+        _ => ObjectFit::Contain,
+    }
+}
+
+/// The image filling an `img` element, or nothing for a released handle
+pub fn render(view: &ImageView) -> Option<AnyElement> {
+    let source = match view.src.as_ref()? {
+        ImageSrc::Handle(id) => ImageSource::Render(get(*id)?),
+        ImageSrc::Path(path) => ImageSource::from(path.clone()),
+    };
+    Some(img(source).size_full().object_fit(view.fit).into_any_element())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit(400, 200, 100), (100, 50));
+        assert_eq!(fit(200, 400, 100), (50, 100));
+        assert_eq!(fit(80, 60, 100), (80, 60));
+        assert_eq!(fit(1000, 1, 100), (100, 1));
+    }
+
+    #[test]
+    fn test_decode_resize_and_release() {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(40, 20, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let sandbox = Sandbox::default();
+
+        let decoded = run(ImageOp::Decode(png), &sandbox).unwrap();
+        assert_eq!((decoded.width, decoded.height), (40, 20));
+        // Red is stored in the last color channel
+        assert_eq!(pixels(decoded.id).unwrap().get_pixel(0, 0).0, [0, 0, 255, 255]);
+
+        let resized = run(ImageOp::Resize { id: decoded.id, width: 10, height: None }, &sandbox).unwrap();
+        assert_eq!((resized.width, resized.height), (10, 5));
+        let thumbnail = run(ImageOp::Thumbnail { source: Source::Handle(decoded.id), size: 8 }, &sandbox).unwrap();
+        assert_eq!((thumbnail.width, thumbnail.height), (8, 4));
+
+        assert!(release(decoded.id));
+        assert!(!release(decoded.id));
+        assert_eq!(run(ImageOp::Resize { id: decoded.id, width: 10, height: None }, &sandbox).unwrap_err().code, "EINVAL");
+        assert!(run(ImageOp::Decode(b"not an image".to_vec()), &sandbox).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Bundle integrity verification
+//!
+//! Before evaluation, every file the app may run is hashed into a manifest:
+//! the config, the entry script, the modules bundled from the config's
+//! `modules`, the native plugin libraries, and the scripts and `.wasm`
+//! files under the entry script's directory, which imports and workers
+//! load later. The manifest's digest
+//! is checked against a pinned SHA-256 and/or an Ed25519 signature, and
+//! files loaded after startup are checked against the manifest. Packaged
+//! apps bake the public key into the binary at compile time
+//! (`RASEN_BUNDLE_PUBLIC_KEY`, hex), which enforces the signature whatever
+//! the config says, so a tampered bundle is refused.
+
+use anyhow::Result;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use crate::config::{SecurityConfig, VerifyMode};
+
+/// Public key baked in by packaged builds
+const EMBEDDED_PUBLIC_KEY: Option<&str> = option_env!("RASEN_BUNDLE_PUBLIC_KEY");
+
+/// The config file, which decides what else runs
+const CONFIG_FILE: &str = "rasen.config.js";
+
+/// Files under the entry script's directory that imports and workers may load
+const CODE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "ts", "mts", "cts", "tsx", "jsx", "wasm"];
+
+/// Directories not searched for code; what runs from them is bundled
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// The verified manifest and mode, for files loaded after startup
+static VERIFIED: Mutex<Option<(Manifest, VerifyMode)>> = Mutex::new(None);
+
+/// Whether the manifest's signature checked out
+static SIGNED: AtomicBool = AtomicBool::new(false);
+
+/// The files an app may run, with their length and SHA-256
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Manifest {
+    /// Paths are keyed relative to this directory
+    root: PathBuf,
+    files: BTreeMap<String, (u64, [u8; 32])>,
+}
+
+impl Manifest {
+    /// Hash the files of the app in `work_dir` with entry `script_file`, the
+    /// modules `bundled` from the config and its `plugins`
+    pub fn collect(work_dir: &Path, script_file: &Path, bundled: &[PathBuf], plugins: &[String]) -> Result<Self> {
+        let mut manifest = Self { root: work_dir.canonicalize()?, files: BTreeMap::new() };
+        let config = work_dir.join(CONFIG_FILE);
+        if config.exists() {
+            manifest.add_file(&config)?;
+        }
+        manifest.add_file(script_file)?;
+        for path in bundled {
+            manifest.add_file(path)?;
+        }
+        for path in plugins {
+            manifest.add_file(&work_dir.join(path))?;
+        }
+        if let Some(dir) = script_file.parent() {
+            manifest.add_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })?;
+        }
+        Ok(manifest)
+    }
+
+    /// The manifest key of `path`: relative to the root with `/`
+    /// separators, or the full path for files outside it
+    fn key(&self, path: &Path) -> String {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => {
+                let parts: Vec<_> = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+                parts.join("/")
+            }
+            Err(_) => path.to_string_lossy().into_owned(),
+        }
+    }
+
+    fn add(&mut self, key: String, bytes: &[u8]) {
+        self.files.insert(key, (bytes.len() as u64, Sha256::digest(bytes).into()));
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        let bytes = fs::read(path)?;
+        self.add(self.key(path), &bytes);
+        Ok(())
+    }
+
+    fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            if path.is_dir() {
+                if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                    self.add_dir(&path)?;
+                }
+            } else if path.extension().is_some_and(|ext| CODE_EXTENSIONS.iter().any(|code| ext == *code)) {
+                self.add_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// SHA-256 over the files in path order, each as its length-prefixed
+    /// path, its length and its content's hash
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for (path, (length, hash)) in &self.files {
+            hasher.update((path.len() as u64).to_le_bytes());
+            hasher.update(path.as_bytes());
+            hasher.update(length.to_le_bytes());
+            hasher.update(hash);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Whether `bytes` are what the manifest recorded for `path`
+    fn matches(&self, path: &Path, bytes: &[u8]) -> bool {
+        let hash: [u8; 32] = Sha256::digest(bytes).into();
+        self.files.get(&self.key(path)) == Some(&(bytes.len() as u64, hash))
+    }
+}
+
+/// Verify the files about to be evaluated
+///
+/// The signature is read from `<script>.sig` (hex) next to the entry file.
+/// With a key embedded in the binary, the config's `verify`, `publicKey`
+/// and `sha256` are ignored.
+pub fn verify(security: &SecurityConfig, manifest: &Manifest, script_file: &Path) -> Result<()> {
+    let (mode, public_key, pinned) = match EMBEDDED_PUBLIC_KEY {
+        // The config could be tampered with as easily as the scripts
+        Some(key) => (VerifyMode::Enforce, Some(key.to_string()), None),
+        None => (security.verify.unwrap_or(VerifyMode::Off), security.public_key.clone(), security.sha256.clone()),
+    };
+    if mode == VerifyMode::Off {
+        return Ok(());
+    }
+
+    let digest = manifest.digest();
+    let mut problems = Vec::new();
+
+    if let Some(expected) = &pinned {
+        if !expected.eq_ignore_ascii_case(&to_hex(&digest)) {
+            problems.push(format!("bundle hash {} does not match the pinned sha256", to_hex(&digest)));
+        }
+    }
+
+    if let Some(public_key) = &public_key {
+        let mut sig_path = script_file.as_os_str().to_owned();
+        sig_path.push(".sig");
+        match fs::read_to_string(&sig_path) {
+            Ok(signature) => {
+                let valid = match (from_hex(public_key), from_hex(signature.trim())) {
+                    (Some(key), Some(signature)) => UnparsedPublicKey::new(&ED25519, key)
+                        .verify(&digest, &signature)
+                        .is_ok(),
+                    _ => false,
+                };
+                if valid {
+                    SIGNED.store(true, Ordering::SeqCst);
+                } else {
+                    problems.push("bundle signature is invalid".to_string());
+                }
+            }
+            Err(_) => problems.push(format!("signature file {:?} not found", sig_path)),
+        }
+    }
+
+    if pinned.is_none() && public_key.is_none() {
+        problems.push("verification is enabled but no sha256 or publicKey is configured".to_string());
+    }
+
+    if !problems.is_empty() {
+        let message = format!("Bundle integrity check failed: {}", problems.join("; "));
+        if mode == VerifyMode::Enforce {
+            anyhow::bail!("{}. Refusing to load.", message);
+        }
+        eprintln!("Warning: {}", message);
+    }
+    *VERIFIED.lock().unwrap() = Some((manifest.clone(), mode));
+    Ok(())
+}
+
+/// Whether the bundle, plugins included, carries a valid signature
+pub fn signed() -> bool {
+    SIGNED.load(Ordering::SeqCst)
+}
+
+/// Check a file loaded after startup against the verified manifest
+///
+/// Passes when verification is off; in `warn` mode a mismatch only warns.
+pub fn check(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let verified = VERIFIED.lock().unwrap();
+    let Some((manifest, mode)) = verified.as_ref() else {
+        return Ok(());
+    };
+    if manifest.matches(path, bytes) {
+        return Ok(());
+    }
+    let message = format!("{} is not part of the verified bundle", path.display());
+    match mode {
+        VerifyMode::Enforce => Err(message),
+        _ => {
+            eprintln!("Warning: {}", message);
+            Ok(())
+        }
+    }
+}
+
+/// Lowercase hex encoding
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_separates_files() {
+        let manifest = |files: &[(&str, &str)]| {
+            let mut manifest = Manifest::default();
+            for (path, content) in files {
+                manifest.add(path.to_string(), content.as_bytes());
+            }
+            manifest
+        };
+        let split = manifest(&[("a.js", "ab"), ("b.js", "c")]);
+        assert_ne!(split.digest(), manifest(&[("a.js", "a"), ("b.js", "bc")]).digest());
+        assert_ne!(split.digest(), manifest(&[("a.js", "ab"), ("c.js", "c")]).digest());
+        assert_eq!(split.digest(), manifest(&[("b.js", "c"), ("a.js", "ab")]).digest());
+        assert!(split.matches(Path::new("a.js"), b"ab"));
+        assert!(!split.matches(Path::new("a.js"), b"abc"));
+    }
+}
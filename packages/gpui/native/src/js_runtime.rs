@@ -1,55 +1,583 @@
 //! JavaScript runtime using QuickJS with reactive event system
 
-use anyhow::Result;
-use rquickjs::{Context, Runtime, Value, Persistent};
-use std::sync::{Arc, RwLock};
-use crate::elements::{Element, DivElement, TextElement, EventHandlers};
-use crate::tw_parser;
+use anyhow::{Context as _, Result};
+use rquickjs::function::{IntoArgs, This};
+use rquickjs::{Array, Context, Ctx, Function, Runtime, Value, Persistent};
+use futures::channel::mpsc::UnboundedReceiver;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use crate::bus;
+use crate::files::{self, Files};
+use crate::layout::Layout;
+use crate::console;
+use crate::builtins;
+use crate::urls;
+use crate::values;
+use crate::wasm;
+use crate::descriptor;
+use crate::diff::{self, Patch};
+use crate::startup::{self, Phase};
+use crate::timers::{self, Timers};
+use crate::frames::{self, AnimationFrames};
+use crate::limits::Watchdog;
+use crate::process::{self, Processes};
+use crate::notifications::{self, Notifications};
+use crate::dialogs::{self, Dialogs};
+use crate::workers::{self, Workers};
+use crate::lifecycle::{self, SecondInstance};
+use crate::platform;
+use crate::native_api;
+use crate::native_modules::{ModuleRegistry, NativeModule, NativePlugin};
+use crate::overlay::ScriptError;
+use crate::reactivity;
+use crate::elements::Element;
 use crate::module_loader::ModuleLoader;
-use crate::event_manager::{EventManager, next_handler_id};
+use crate::window::{WindowId, WindowRequest, WindowSpec, Windows, MAIN_WINDOW};
+use crate::event_manager::{EventManager, HandlerId};
+
+/// Options controlling how the JS runtime is set up
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeOptions {
+    /// Disable `eval`/`Function` and freeze the module registry before the
+    /// user script runs, for apps that render untrusted content
+    pub hardened: bool,
+    /// Maximum QuickJS stack size in bytes, if not the engine default
+    pub stack_size: Option<usize>,
+    /// Maximum QuickJS heap in bytes
+    pub memory_limit: Option<usize>,
+    /// Heap growth in bytes that triggers a garbage collection
+    pub gc_threshold: Option<usize>,
+    /// How long a single call into JS may run before it is interrupted
+    pub time_limit: Option<Duration>,
+    /// Warn when a render produces more elements than this (0 disables it)
+    pub large_tree_warning: usize,
+    /// Warn when a handler blocks the UI thread for longer (zero disables it)
+    pub slow_handler_warning: Duration,
+    /// Fail renders that use classes the style parser doesn't understand,
+    /// instead of warning about them
+    pub strict_styles: bool,
+    /// Directory of the entry script, which its `.wasm` imports are
+    /// relative to
+    pub script_dir: PathBuf,
+}
+
+/// What wakes the app to run a runtime's callbacks or queued work
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wakeup {
+    /// A render was requested
+    Render,
+    /// Bus messages arrived
+    Bus,
+    /// A timer was added
+    Timers,
+    /// An animation frame was requested
+    Frames,
+    /// A file operation was queued
+    Files,
+    /// A child process has news
+    Processes,
+    /// Notifications were clicked or closed
+    Notifications,
+    /// A dialog was answered
+    Dialogs,
+    /// JS opened or closed a window
+    Windows,
+    /// A worker posted a message
+    Workers,
+}
 
 /// Shared state between JS runtime and GPUI
 pub struct JsRuntime {
     runtime: Runtime,
     context: Context,
     event_manager: EventManager,
+    /// Receivers waking the app for each queue, until the app takes them
+    wakeups: Mutex<HashMap<Wakeup, UnboundedReceiver<()>>>,
+    /// Windows whose last re-render failed, leaving what `memo()` subtrees
+    /// rendered off screen
+    render_failed: Mutex<HashSet<WindowId>>,
+    options: RuntimeOptions,
+    /// Endpoint on the app-wide message bus
+    bus_id: bus::RuntimeId,
+    /// Pending `setTimeout`/`setInterval` timers
+    timers: Timers,
+    /// Pending `requestAnimationFrame` callbacks
+    frames: AnimationFrames,
+    /// Queued `rasen.fs` operations
+    files: Files,
+    /// Child processes spawned with `rasen.process`
+    processes: Processes,
+    /// Notifications posted with `rasen.notify`
+    notifications: Notifications,
+    /// Dialogs shown with `rasen.dialog`
+    dialogs: Dialogs,
+    /// Windows opened or closed with `rasen.window`
+    windows: Windows,
+    /// Workers started with `new Worker`
+    workers: Workers,
+    /// Where elements with a `ref` were laid out, for `rasen.layout`
+    layout: Layout,
+    /// Native modules registered by the embedder and plugins
+    native_modules: Mutex<ModuleRegistry>,
+    /// Interrupts calls into JS that run over `time_limit`
+    watchdog: Watchdog,
+    /// The user script as evaluated, for the code frames of its errors
+    script: Mutex<String>,
     /// Flag indicating JS context has been initialized
     initialized: Arc<RwLock<bool>>,
 }
 
 impl JsRuntime {
-    pub fn new() -> Self {
+    pub fn new(options: RuntimeOptions) -> Self {
         let runtime = Runtime::new().expect("Failed to create JS runtime");
+        if let Some(stack_size) = options.stack_size {
+            runtime.set_max_stack_size(stack_size);
+        }
+        if let Some(memory_limit) = options.memory_limit {
+            runtime.set_memory_limit(memory_limit);
+        }
+        if let Some(gc_threshold) = options.gc_threshold {
+            runtime.set_gc_threshold(gc_threshold);
+        }
+        let watchdog = Watchdog::new(options.time_limit);
+        watchdog.attach(&runtime);
         let context = Context::full(&runtime).expect("Failed to create JS context");
+        let (bus_id, bus_wakeups) = bus::register();
+        let (timers, timer_wakeups) = Timers::new();
+        let (frames, frame_wakeups) = AnimationFrames::new();
+        let (files, file_wakeups) = Files::new();
+        let (processes, process_wakeups) = Processes::new();
+        let (notifications, notification_wakeups) = Notifications::new();
+        let (dialogs, dialog_wakeups) = Dialogs::new();
+        let (windows, window_wakeups) = Windows::new();
+        let (workers, worker_wakeups) = Workers::new();
+        let (event_manager, render_wakeups) = EventManager::new();
+        event_manager.set_slow_handler_warning(options.slow_handler_warning);
         
         Self { 
             runtime, 
             context,
-            event_manager: EventManager::new(),
+            event_manager,
+            wakeups: Mutex::new(HashMap::from([
+                (Wakeup::Render, render_wakeups),
+                (Wakeup::Bus, bus_wakeups),
+                (Wakeup::Timers, timer_wakeups),
+                (Wakeup::Frames, frame_wakeups),
+                (Wakeup::Files, file_wakeups),
+                (Wakeup::Processes, process_wakeups),
+                (Wakeup::Notifications, notification_wakeups),
+                (Wakeup::Dialogs, dialog_wakeups),
+                (Wakeup::Windows, window_wakeups),
+                (Wakeup::Workers, worker_wakeups),
+            ])),
+            render_failed: Mutex::default(),
+            options,
+            bus_id,
+            timers,
+            frames,
+            files,
+            processes,
+            notifications,
+            dialogs,
+            windows,
+            workers,
+            layout: Layout::new(),
+            native_modules: Mutex::default(),
+            watchdog,
+            script: Mutex::default(),
             initialized: Arc::new(RwLock::new(false)),
         }
     }
     
+    /// Take the receiver that wakes when `wakeup` has work (once)
+    pub fn take_wakeups(&self, wakeup: Wakeup) -> Option<UnboundedReceiver<()>> {
+        self.wakeups.lock().unwrap().remove(&wakeup)
+    }
+    
+    /// Run bus listeners for queued messages; returns whether any ran
+    pub fn deliver_bus(&self) -> bool {
+        self.enter(|ctx| bus::deliver(&ctx, self.bus_id))
+    }
+    
+    /// When the earliest pending timer is due
+    pub fn next_timer(&self) -> Option<Instant> {
+        self.timers.next_due()
+    }
+    
+    /// Run the callbacks of due timers; returns whether any ran
+    pub fn fire_timers(&self) -> bool {
+        self.enter(|ctx| self.timers.fire(&ctx))
+    }
+    
+    /// Whether `requestAnimationFrame` callbacks wait for the next frame
+    pub fn wants_frame(&self) -> bool {
+        self.frames.pending()
+    }
+    
+    /// Run the callbacks requested for this frame; returns whether any ran
+    pub fn fire_frames(&self) -> bool {
+        self.enter(|ctx| self.frames.fire(&ctx))
+    }
+    
+    /// Take the file operations queued since the last call, to run them
+    pub fn take_file_requests(&self) -> Vec<files::Request> {
+        self.files.take_requests()
+    }
+    
+    /// Settle the promise of a finished file operation
+    pub fn settle_file(&self, id: u32, result: Result<files::Output, files::FsError>) {
+        self.enter(|ctx| files::settle(&ctx, id, result))
+    }
+    
+    /// Call the callbacks for child process output and exits; returns
+    /// whether any ran
+    pub fn deliver_processes(&self) -> bool {
+        self.enter(|ctx| process::deliver(&ctx, &self.processes))
+    }
+    
+    /// Call `onClick` for clicked notifications; returns whether any ran
+    pub fn deliver_notifications(&self) -> bool {
+        self.enter(|ctx| notifications::deliver(&ctx, &self.notifications))
+    }
+    
+    /// Settle the promises of answered dialogs; returns whether any settled
+    pub fn deliver_dialogs(&self) -> bool {
+        self.enter(|ctx| dialogs::deliver(&ctx, &self.dialogs))
+    }
+    
+    /// Take the windows JS asked to open or close, in order
+    pub fn take_window_requests(&self) -> Vec<WindowRequest> {
+        self.windows.take_requests()
+    }
+    
+    /// Start quitting: ask the `before-quit` listeners, which answer through
+    /// the receiver whether to quit; `None` while they decide an earlier quit
+    pub fn before_quit(&self) -> Option<futures::channel::oneshot::Receiver<bool>> {
+        let decision = self.enter(|ctx| lifecycle::before_quit(&ctx));
+        self.run_jobs();
+        decision
+    }
+    
+    /// Call the `quit` listeners, as the app exits
+    pub fn quit(&self) {
+        self.enter(|ctx| lifecycle::quit(&ctx));
+        self.run_jobs();
+    }
+    
+    /// Call the `second-instance` listeners with a launch handed over
+    pub fn deliver_second_instance(&self, launch: &SecondInstance) {
+        self.enter(|ctx| lifecycle::second_instance(&ctx, launch));
+        self.run_jobs();
+    }
+    
+    /// Call the `high-contrast-change` listeners
+    pub fn deliver_high_contrast(&self, high_contrast: bool) {
+        self.enter(|ctx| platform::high_contrast_changed(&ctx, high_contrast));
+        self.run_jobs();
+    }
+    
+    /// Call `onmessage`/`onerror` for what workers sent; returns whether any ran
+    pub fn deliver_workers(&self) -> bool {
+        self.enter(|ctx| workers::deliver(&ctx, &self.workers))
+    }
+    
+    /// Install the native globals and APIs the shims and the script build on
+    fn install_all(&self, ctx: &Ctx) -> Result<()> {
+        native_api::install(ctx, self.bus_id, &self.files, &self.layout, &self.processes, &self.notifications, &self.dialogs, &self.windows)
+            .context("Failed to install native APIs")?;
+        // `requestRender()`, for changes made outside event handlers
+        let requests = self.event_manager.clone();
+        ctx.globals()
+            .set("__requestRender", Function::new(ctx.clone(), move || requests.request_render()))
+            .context("Failed to install requestRender")?;
+        timers::install(ctx, &self.timers).context("Failed to install timers")?;
+        frames::install(ctx, &self.frames).context("Failed to install animation frames")?;
+        console::install(ctx).context("Failed to install console")?;
+        builtins::install(ctx).context("Failed to install built-in modules")?;
+        urls::install(ctx).context("Failed to install URL")?;
+        values::install(ctx).context("Failed to install structuredClone")?;
+        wasm::install(ctx).context("Failed to install WebAssembly loading")?;
+        workers::install(ctx, &self.workers, &self.options.script_dir, self.options.hardened)
+            .context("Failed to install Worker")?;
+        self.native_modules.lock().unwrap().install(ctx).context("Failed to install native modules")?;
+        // The reactive layer, built in or bundled, reports every change, so
+        // handlers that change no state don't re-render
+        let changes = self.event_manager.clone();
+        reactivity::report_changes(ctx, move || changes.state_changed()).context("Failed to install change reporting")?;
+        let untracked = self.event_manager.clone();
+        ctx.globals()
+            .set("__stateUntracked", Function::new(ctx.clone(), move || untracked.untrack_state_changes()))
+            .context("Failed to install change reporting")?;
+        self.event_manager.track_state_changes();
+        Ok(())
+    }
+    
+    /// Call into JS, timed by the watchdog
+    fn enter<F, R>(&self, f: F) -> R
+    where F: FnOnce(Ctx) -> R
+    {
+        self.watchdog.enter();
+        self.context.with(f)
+    }
+    
+    /// Run queued promise jobs (`await` continuations and `.then` callbacks)
+    /// until none are left; returns whether any ran
+    ///
+    /// QuickJS only queues these jobs, so they run when the runtime drains
+    /// them: before every re-render and after the user script.
+    pub fn run_jobs(&self) -> bool {
+        let mut ran = false;
+        loop {
+            self.watchdog.enter();
+            match self.runtime.execute_pending_job() {
+                Ok(true) => ran = true,
+                Ok(false) => break,
+                Err(failed) => {
+                    let exc = failed.0.with(|ctx| format!("{:?}", ctx.catch()));
+                    eprintln!("Error in promise job: {}", exc);
+                    ran = true;
+                }
+            }
+        }
+        ran
+    }
+    
+    /// Expose Rust functions to JS as module `name` (see `native_modules`)
+    ///
+    /// Must be called before the script runs.
+    pub fn register_native_module(&self, name: &str, build: impl FnOnce(&mut NativeModule)) -> Result<()> {
+        if *self.initialized.read().unwrap() {
+            anyhow::bail!("Native module '{}' must be registered before the script runs", name);
+        }
+        self.native_modules.lock().unwrap().module(name, build);
+        Ok(())
+    }
+    
+    /// Register the native modules of a plugin, before the script runs
+    pub fn register_plugin(&self, plugin: &dyn NativePlugin) -> Result<()> {
+        if *self.initialized.read().unwrap() {
+            anyhow::bail!("Plugin '{}' must be registered before the script runs", plugin.name());
+        }
+        plugin.register(&mut self.native_modules.lock().unwrap());
+        Ok(())
+    }
+    
     /// Get the event manager for binding to GPUI events
     pub fn event_manager(&self) -> EventManager {
         self.event_manager.clone()
     }
     
+    /// Get the element measurements, for the window to record layout in
+    pub fn layout(&self) -> Layout {
+        self.layout.clone()
+    }
+    
     /// Get direct access to the context for invoking handlers
     pub fn with_context<F, R>(&self, f: F) -> R 
     where F: FnOnce(&Context) -> R 
     {
+        self.watchdog.enter();
         f(&self.context)
     }
     
     /// Re-render: re-run the App function to get updated UI tree
     /// This preserves JS state (refs, etc.) while getting new element descriptions
     pub fn re_render(&self) -> Result<Element> {
+        self.render_window(MAIN_WINDOW)
+    }
+    
+    /// Render `window`'s component in full, as there's no tree to diff
+    /// against, e.g. for a window that's opening
+    pub fn render_window(&self, window: WindowId) -> Result<Element> {
+        // Let async code resumed by the last event settle first
+        self.run_jobs();
         let event_manager = self.event_manager.clone();
-        self.context.with(|ctx| {
-            // Call __rerender() which re-executes the App and returns new element tree
-            let result: Value = ctx.eval("__rerender()")?;
-            js_to_element(&ctx, &result, &event_manager)
+        let element = self.enter(|ctx| {
+            // Call __rerender() which re-executes the App and returns new
+            // element tree
+            let result: Value = match ctx.eval(render_script(window, true)) {
+                Ok(result) => result,
+                Err(e) => return Err(self.script_error(&ctx, e).into()),
+            };
+            js_to_element(&ctx, &result, &event_manager, &self.options)
+        });
+        self.set_render_failed(window, element.is_err());
+        if let Ok(element) = &element {
+            self.collect_handlers(window, element);
+        }
+        element
+    }
+    
+    fn set_render_failed(&self, window: WindowId, failed: bool) {
+        let mut render_failed = self.render_failed.lock().unwrap();
+        if failed {
+            render_failed.insert(window);
+        } else {
+            render_failed.remove(&window);
+        }
+    }
+    
+    /// Drop the handlers no window uses now that `tree` is on screen in
+    /// `window`
+    ///
+    /// Each render creates new closures, so without this the handler table
+    /// would keep every past render's alive. A dropped function that's
+    /// rendered again is registered anew.
+    fn collect_handlers(&self, window: WindowId, tree: &Element) {
+        self.forget_handlers(self.event_manager.retain(window, tree.handler_ids()));
+    }
+    
+    /// Drop handlers from the JS handler table
+    fn forget_handlers(&self, dropped: Vec<HandlerId>) {
+        if dropped.is_empty() {
+            return;
+        }
+        self.enter(|ctx| {
+            let forget = ctx.globals().get::<_, Function>("__forgetHandlers");
+            if let Err(e) = forget.and_then(|forget| forget.call::<_, ()>((dropped,))) {
+                eprintln!("Error dropping handlers: {:?}", e);
+            }
+        });
+    }
+    
+    /// Note that `window` opened, before its first render
+    pub fn open_window(&self, window: WindowId) {
+        self.event_manager.add_window(window);
+    }
+    
+    /// Unmount the component of a window that closed, and drop the
+    /// handlers only it used
+    pub fn close_window(&self, window: WindowId) {
+        self.render_failed.lock().unwrap().remove(&window);
+        self.forget_handlers(self.event_manager.remove_window(window));
+        self.enter(|ctx| {
+            let closed = ctx.globals().get::<_, Function>("__windowClosed");
+            if let Err(e) = closed.and_then(|closed| closed.call::<_, ()>((window,))) {
+                eprintln!("Error closing window {}: {:?}", window, e);
+            }
+        });
+        self.run_jobs();
+    }
+    
+    /// Re-render into `tree`, the tree on screen in `window`, patching only
+    /// the subtrees that changed (see `diff`); returns whether any did
+    ///
+    /// On error `tree` is left as it was. Either way `nextTick` callbacks run
+    /// afterwards.
+    pub fn re_render_into(&self, window: WindowId, tree: &mut Element) -> Result<bool> {
+        self.run_jobs();
+        let event_manager = self.event_manager.clone();
+        let patches = self.enter(|ctx| {
+            let render = |script: &str| {
+                let result: Value = match ctx.eval(script) {
+                    Ok(result) => result,
+                    Err(e) => {
+                        // Only what the error handlers change counts
+                        event_manager.take_state_change();
+                        let error = self.handle_error(&ctx, e, "render").err();
+                        return Err(error.unwrap_or_else(|| ErrorHandled.into()));
+                    }
+                };
+                js_to_descriptor(&ctx, &result, &event_manager, &self.options)
+            };
+            // Memos skip only when what they rendered last time is on screen
+            let full = self.render_failed.lock().unwrap().contains(&window);
+            let patches = match diff::diff(tree, render(&render_script(window, full))?) {
+                Some(patches) => patches,
+                // A memo skipped a subtree that isn't on screen; render in full
+                None => diff::diff(tree, render(&render_script(window, true))?)
+                    .ok_or_else(|| anyhow::anyhow!("Re-render skipped a subtree with nothing to keep"))?,
+            };
+            check_styles(patches.iter().flat_map(Patch::elements), self.options.strict_styles)?;
+            Ok(patches)
+        });
+        self.set_render_failed(window, patches.is_err());
+        let changed = match patches {
+            Ok(patches) => {
+                let changed = !patches.is_empty();
+                diff::apply(tree, patches);
+                self.collect_handlers(window, tree);
+                Ok(changed)
+            }
+            // An `onError` handler took it; show what it changed, e.g. a fallback
+            Err(e) if e.is::<ErrorHandled>() => {
+                if self.event_manager.take_tracked_state_change() {
+                    self.event_manager.request_render();
+                }
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        };
+        self.flush_next_ticks();
+        changed
+    }
+    
+    /// Run the `nextTick` callbacks waiting for a re-render
+    fn flush_next_ticks(&self) {
+        self.enter(|ctx| {
+            let flush = ctx.globals().get::<_, Function>("__flushNextTicks");
+            if let Err(e) = flush.and_then(|flush| flush.call::<_, ()>(())) {
+                eprintln!("Error running nextTick callbacks: {:?}", e);
+            }
+        });
+        self.run_jobs();
+    }
+    
+    /// Invoke a handler of `window` by ID with arguments, e.g. a list's
+    /// `(from, to)`; returns the string it returned, if it returned one
+    ///
+    /// An error it throws goes to the `onError` handlers, and is returned
+    /// for the error overlay if none handled it.
+    pub fn invoke_handler<A>(&self, window: WindowId, id: HandlerId, args: A) -> Result<Option<String>>
+    where
+        A: for<'js> IntoArgs<'js>,
+    {
+        self.enter(|ctx| match self.event_manager.call_handler(&ctx, window, id, args) {
+            Ok(result) => Ok(result.and_then(|result| result.as_string()?.to_string().ok())),
+            Err(e) => self.handle_error(&ctx, e, &format!("handler {}", id)).map(|()| None),
+        })
+    }
+    
+    /// Pass the error thrown by user code to the `onError` handlers (see
+    /// `__handleError`); returns it, with its stack, if none handled it
+    fn handle_error(&self, ctx: &Ctx, e: rquickjs::Error, source: &str) -> Result<()> {
+        let exc = ctx.catch();
+        if exc.is_undefined() || exc.is_null() {
+            return Err(ScriptError::new(e.to_string()).into());
+        }
+        let handle = ctx.globals().get::<_, Function>("__handleError");
+        if handle.and_then(|handle| handle.call::<_, bool>((exc.clone(), source))).unwrap_or(false) {
+            return Ok(());
+        }
+        Err(ScriptError::from_exception(&exc, &self.script.lock().unwrap()).into())
+    }
+    
+    /// The error thrown by evaluating user code, with its stack
+    fn script_error(&self, ctx: &Ctx, e: rquickjs::Error) -> ScriptError {
+        let exc = ctx.catch();
+        if exc.is_undefined() || exc.is_null() {
+            return ScriptError::new(e.to_string());
+        }
+        ScriptError::from_exception(&exc, &self.script.lock().unwrap())
+    }
+    
+    /// The window options the script passed to `run`, or the defaults
+    pub fn window_spec(&self) -> WindowSpec {
+        let json = self.enter(|ctx| {
+            let options: Value = ctx.globals().get("__windowOptions")?;
+            ctx.json_stringify(options)?.map(|json| json.to_string()).transpose()
+        });
+        let spec = match json {
+            Ok(Some(json)) => WindowSpec::parse(&json).map_err(anyhow::Error::from),
+            Ok(None) => Ok(WindowSpec::default()),
+            Err(e) => Err(e.into()),
+        };
+        spec.unwrap_or_else(|e| {
+            eprintln!("Warning: ignoring window options: {:#}", e);
+            WindowSpec::default()
         })
     }
     
@@ -69,35 +597,155 @@ impl JsRuntime {
     
     fn execute_with_modules_internal(&self, script: &str, loader: &ModuleLoader) -> Result<Element> {
         let event_manager = self.event_manager.clone();
-        self.context.with(|ctx| {
+        startup::mark(Phase::Setup);
+        self.enter(|ctx| {
             // Inject base runtime with handler registry
             let base_shim = r#"
                 var __rootElement = null;
+                var __pendingApp = null;
                 var __elements = [];
-                var __handlers = {};
+                var __handlerIds = new WeakMap();
+                // Handlers by ID, looked up by the native side as events fire
+                var __handlers = new Map();
                 var __handlerIdCounter = 1;
                 var __modules = {};
                 
+                // Assign a stable ID to a handler function. The same closure
+                // always maps to the same ID, so the native side only needs to
+                // persist functions it hasn't seen before.
                 function __registerHandler(fn) {
                     if (typeof fn !== 'function') return null;
-                    var id = __handlerIdCounter++;
-                    __handlers[id] = fn;
+                    var id = __handlerIds.get(fn);
+                    if (id === undefined) {
+                        id = __handlerIdCounter++;
+                        __handlerIds.set(fn, id);
+                        __handlers.set(id, fn);
+                    }
                     return id;
                 }
                 
-                function __invokeHandler(id) {
-                    var fn = __handlers[id];
-                    if (fn) fn();
+                function __handlerFor(id) {
+                    return __handlers.get(id);
+                }
+
+                // Error handlers added with `onError`, newest first
+                var __errorHandlers = [];
+                function __onError(handler) {
+                    __errorHandlers.unshift(handler);
+                    return function() {
+                        var index = __errorHandlers.indexOf(handler);
+                        if (index >= 0) __errorHandlers.splice(index, 1);
+                    };
+                }
+
+                // Pass an error to the `onError` handlers, then to
+                // `onerror(message, source, line, column, error)`, until one
+                // returns true; returns whether one did
+                function __handleError(error, source) {
+                    var handlers = __errorHandlers.slice();
+                    for (var i = 0; i < handlers.length; i++) {
+                        try {
+                            if (handlers[i](error, source) === true) return true;
+                        } catch (e) {
+                            console.error('Error in onError handler:', e);
+                        }
+                    }
+                    if (typeof globalThis.onerror !== 'function') return false;
+                    var message = error instanceof Error ? error.message : String(error);
+                    try {
+                        return globalThis.onerror(message, source, 0, 0, error) === true;
+                    } catch (e) {
+                        console.error('Error in onerror:', e);
+                        return false;
+                    }
+                }
+
+                // Handle an error nothing else catches, logging it if unhandled
+                function __reportError(error, source) {
+                    if (__handleError(error, source)) return;
+                    console.error('Error in ' + source + ':', error);
+                }
+
+                // Await an async handler: re-render once it settles, reporting
+                // a rejection
+                function __settleHandler(promise, id) {
+                    promise.then(function() {
+                        __requestRender();
+                    }, function(error) {
+                        __requestRender();
+                        __reportError(error, 'async handler ' + id);
+                    });
+                }
+                
+                // Drop handlers the rendered tree no longer uses; rendered
+                // again, a function gets a new ID
+                function __forgetHandlers(ids) {
+                    for (var i = 0; i < ids.length; i++) {
+                        var fn = __handlers.get(ids[i]);
+                        __handlers.delete(ids[i]);
+                        if (fn) __handlerIds.delete(fn);
+                    }
                 }
                 
                 function require(name) {
                     if (__modules[name]) return __modules[name];
                     throw new Error('Module not found: ' + name);
                 }
+                
+                // `nextTick(callback)`: run after the next re-render, requesting
+                // one; the promise resolves then too
+                var __nextTicks = [];
+                function __nextTick(callback) {
+                    __requestRender();
+                    return new Promise(function(resolve) {
+                        __nextTicks.push({ callback: callback, resolve: resolve });
+                    });
+                }
+                
+                function __flushNextTicks() {
+                    var ticks = __nextTicks;
+                    __nextTicks = [];
+                    for (var i = 0; i < ticks.length; i++) {
+                        try {
+                            if (ticks[i].callback) ticks[i].callback();
+                        } catch (e) {
+                            console.error('Error in nextTick callback:', e);
+                        }
+                        ticks[i].resolve();
+                    }
+                }
+                
+                // Encode an element tree as [json, fresh] for the native side.
+                // Handler functions are replaced by their stable IDs; `fresh`
+                // holds the IDs of handlers not registered before. Handlers
+                // are the functions in `handlers` and keymap `bindings`, or
+                // under `on*` keys; other functions are left out, so they
+                // aren't kept alive as handlers no event can call.
+                function __encodeTree(root) {
+                    var fresh = [];
+                    var tables = new WeakSet();
+                    var json = JSON.stringify(root, function(key, value) {
+                        var table = key === 'handlers' || key === 'bindings';
+                        if (table && value && typeof value === 'object') tables.add(value);
+                        if (typeof value === 'function') {
+                            if (!tables.has(this) && !/^on[A-Z]/.test(key)) return undefined;
+                            var known = __handlerIds.has(value);
+                            var id = __registerHandler(value);
+                            if (!known) fresh.push(id);
+                            return id;
+                        }
+                        return value;
+                    });
+                    return [json, fresh];
+                }
             "#;
             if let Err(e) = ctx.eval::<(), _>(base_shim) {
                 anyhow::bail!("Failed to eval base_shim: {:?}", e);
             }
+            let dispatch: Function = ctx.globals().get("__handlerFor")?;
+            self.event_manager.set_dispatch(Persistent::save(&ctx, dispatch));
+            
+            self.install_all(&ctx)?;
             
             // Execute the bundled runtime (all modules combined)
             let has_bundled_runtime = if let Some(runtime) = loader.get_bundled_runtime() {
@@ -119,6 +767,9 @@ impl JsRuntime {
             // If no @rasenjs/gpui loaded, use built-in fallback
             if !has_bundled_runtime || !has_gpui {
                 eprintln!("Warning: @rasenjs/gpui not found in bundled modules, using built-in fallback");
+                if let Err(e) = reactivity::install(&ctx) {
+                    anyhow::bail!("Failed to install built-in reactivity: {:?}", e);
+                }
                 let gpui_shim = generate_builtin_gpui_module();
                 if let Err(e) = ctx.eval::<(), _>(gpui_shim.as_str()) {
                     let exc = ctx.catch();
@@ -129,79 +780,116 @@ impl JsRuntime {
                 }
             }
             
-            // Transform and execute
-            let transformed = transform_imports(script);
-            if let Err(e) = ctx.eval::<(), _>(transformed.as_str()) {
-                let exc = ctx.catch();
-                if !exc.is_undefined() && !exc.is_null() {
-                    anyhow::bail!("Failed to eval user script: {:?}", exc);
+            // JS modules of native plugins, which may build on @rasenjs/gpui
+            for (name, source) in self.native_modules.lock().unwrap().scripts() {
+                if let Err(e) = ctx.eval::<(), _>(wrap_module(name, source)) {
+                    let exc = ctx.catch();
+                    anyhow::bail!("Failed to eval plugin module '{}': {:?} {:?}", name, e, exc);
                 }
-                anyhow::bail!("Failed to eval user script: {:?}", e);
             }
             
+            // Lock the runtime down before any user code runs
+            if self.options.hardened {
+                if let Err(e) = ctx.eval::<(), _>(HARDENING_SHIM) {
+                    let exc = ctx.catch();
+                    anyhow::bail!("Failed to eval hardening shim: {:?} {:?}", e, exc);
+                }
+            }
+            
+            startup::mark(Phase::Shims);
+            
+            // Transform and execute
+            let transformed = transform_imports(script, &self.options.script_dir);
+            *self.script.lock().unwrap() = transformed.clone();
+            if let Err(e) = ctx.eval::<(), _>(transformed.as_str()) {
+                return Err(self.script_error(&ctx, e).into());
+            }
+            // An async App shows an empty root until it resolves
+            let pending: Value = ctx.globals().get("__pendingApp")?;
+            report_rejection(&ctx, &pending, "async App".to_string());
+            Ok(())
+        })?;
+        
+        // Run what the script's async code can do right away, e.g. an async
+        // App that doesn't wait on timers or events
+        self.run_jobs();
+        startup::mark(Phase::Script);
+        
+        self.enter(|ctx| {
             // Get root element and register handlers
             let root: Value = ctx.eval("__rootElement")?;
-            let element = js_to_element(&ctx, &root, &event_manager)?;
+            let element = js_to_element(&ctx, &root, &event_manager, &self.options)?;
+            startup::mark(Phase::Encode);
             
             Ok(element)
         })
     }
 }
 
+/// A render error an `onError` handler took, so the tree on screen stays
+#[derive(Debug)]
+struct ErrorHandled;
+
+impl std::fmt::Display for ErrorHandled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("error handled by onError")
+    }
+}
+
+impl std::error::Error for ErrorHandled {}
+
+impl Drop for JsRuntime {
+    fn drop(&mut self) {
+        bus::unregister(self.bus_id);
+        self.workers.terminate_all();
+        // QuickJS requires saved functions to be released before the runtime
+        self.event_manager.clear();
+    }
+}
+
+/// Hardened mode: no code generation from strings and no runtime module registration
+///
+/// `eval` and the Function constructors (including the generator/async ones
+/// reachable through `.constructor`) are replaced by throwing stubs, and the
+/// module registry is frozen so only bundled modules can be required.
+pub(crate) const HARDENING_SHIM: &str = r#"
+(function() {
+    function blocked() {
+        throw new EvalError('Code generation from strings is disabled in hardened mode');
+    }
+    
+    Object.defineProperty(globalThis, 'eval', { value: blocked, writable: false, configurable: false });
+    
+    var constructors = [
+        Function,
+        (function*() {}).constructor,
+        (async function() {}).constructor,
+        (async function*() {}).constructor
+    ];
+    for (var i = 0; i < constructors.length; i++) {
+        var original = constructors[i];
+        var stub = function() { blocked(); };
+        stub.prototype = original.prototype;
+        Object.defineProperty(original.prototype, 'constructor', { value: stub, writable: false, configurable: false });
+    }
+    Object.defineProperty(globalThis, 'Function', { value: Function.prototype.constructor, writable: false, configurable: false });
+    
+    Object.freeze(__modules);
+    Object.defineProperty(globalThis, 'require', { value: require, writable: false, configurable: false });
+})();
+"#;
+
 /// Generate GPUI module following Rasen three-phase pattern
 fn generate_builtin_gpui_module() -> String {
     // Wrap in IIFE to avoid polluting global scope
     r#"
 (function() {
         // ========== Reactivity ==========
-        var __currentEffect = null;
-        
-        function RefImpl(value) {
-            this._value = value;
-            this._subscribers = [];
-        }
-        RefImpl.prototype = {
-            get value() {
-                if (__currentEffect) {
-                    if (this._subscribers.indexOf(__currentEffect) === -1) {
-                        this._subscribers.push(__currentEffect);
-                    }
-                }
-                return this._value;
-            },
-            set value(newValue) {
-                if (this._value !== newValue) {
-                    this._value = newValue;
-                    for (var i = 0; i < this._subscribers.length; i++) {
-                        this._subscribers[i]();
-                    }
-                }
-            }
-        };
-        
-        function ref(v) { return new RefImpl(v); }
-        
-        function unrefValue(v) {
-            if (v && typeof v === 'object' && 'value' in v) {
-                return v.value;
-            }
-            return v;
-        }
-        
-        function isRef(v) { return v instanceof RefImpl; }
-        
-        __modules['@rasenjs/reactive-signals'] = {
-            ref: ref,
-            computed: function(fn) { return new RefImpl(fn()); },
-            watch: function(src, cb) { 
-                var old; 
-                var runFn = function() { var n = src(); cb(n, old); old = n; }; 
-                runFn(); 
-                return function() {}; 
-            },
-            unref: unrefValue,
-            isRef: isRef
-        };
+        // Built-in `@rasenjs/reactive-signals` (see `reactivity`)
+        var __reactivity = __modules['@rasenjs/reactive-signals'];
+        var ref = __reactivity.ref;
+        var unrefValue = __reactivity.unref;
+        var isRef = __reactivity.isRef;
         
         // ========== GpuiHost ==========
         function createHost() {
@@ -233,9 +921,20 @@ fn generate_builtin_gpui_module() -> String {
                     handlers: {}
                 };
                 
+                if (props.testId) desc.testId = props.testId;
+                if (props.key !== undefined) desc.key = String(props.key);
                 if (props.onClick) desc.handlers.click = props.onClick;
                 if (props.onMouseEnter) desc.handlers.mouseenter = props.onMouseEnter;
                 if (props.onMouseLeave) desc.handlers.mouseleave = props.onMouseLeave;
+                if (props.onMouseDown) desc.handlers.mousedown = props.onMouseDown;
+                var move = props.onMouseMove;
+                if (typeof move === 'function') desc.handlers.mousemove = move;
+                else if (move) {
+                    desc.handlers.mousemove = move.handler;
+                    desc.rates = { mousemove: { throttle: move.throttle, debounce: move.debounce } };
+                }
+                if (props.onMouseUp) desc.handlers.mouseup = props.onMouseUp;
+                if (props.ref) desc.ref = props.ref.id;
                 
                 var children = props.children || [];
                 for (var i = 0; i < children.length; i++) {
@@ -270,6 +969,8 @@ fn generate_builtin_gpui_module() -> String {
                     class: unrefValue(props.class) || '',
                     text: t != null ? String(t) : ''
                 };
+                if (props.testId) desc.testId = props.testId;
+                if (props.key !== undefined) desc.key = String(props.key);
                 host.appendChild(desc);
                 return function unmount() {};
             };
@@ -285,6 +986,540 @@ fn generate_builtin_gpui_module() -> String {
             return div(newProps);
         }
         
+        function keymap(props) {
+            props = props || {};
+            var mountDiv = div(props);
+            return function mount(host) {
+                var keymapHost = createHost();
+                var unmount = mountDiv(keymapHost);
+                var desc = keymapHost.getElements()[0];
+                desc.keymap = { scope: props.scope || '', bindings: props.bindings || {} };
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function sortableList(props) {
+            props = props || {};
+            return function mount(host) {
+                var items = unrefValue(props.items) || [];
+                var children = [];
+                for (var i = 0; i < items.length; i++) {
+                    children.push(props.render(items[i], i));
+                }
+                var listHost = createHost();
+                var unmount = div({ class: props.class, testId: props.testId, children: children })(listHost);
+                var desc = listHost.getElements()[0];
+                desc.handlers.reorder = props.onReorder;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function selectable(props) {
+            props = props || {};
+            return function mount(host) {
+                var items = unrefValue(props.items) || [];
+                var selected = unrefValue(props.selected) || [];
+                var keys = [];
+                var children = [];
+                for (var i = 0; i < items.length; i++) {
+                    keys.push(props.key(items[i]));
+                    children.push(props.render(items[i], selected.indexOf(keys[i]) >= 0));
+                }
+                var selectableHost = createHost();
+                var unmount = div({ class: props.class, testId: props.testId, children: children })(selectableHost);
+                var desc = selectableHost.getElements()[0];
+                desc.selectable = { keys: keys, selected: selected };
+                desc.handlers.selectionchange = props.onSelectionChange;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function dragHandle(props) {
+            props = props || {};
+            var mountDiv = div(props);
+            return function mount(host) {
+                var handleHost = createHost();
+                var unmount = mountDiv(handleHost);
+                var desc = handleHost.getElements()[0];
+                desc.dragHandle = true;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function img(props) {
+            props = props || {};
+            var mountDiv = div({ class: props.class, testId: props.testId });
+            return function mount(host) {
+                var imageHost = createHost();
+                var unmount = mountDiv(imageHost);
+                var desc = imageHost.getElements()[0];
+                var src = unrefValue(props.src);
+                desc.image = typeof src === 'string' ? { path: src, fit: props.fit } : { id: src.id, fit: props.fit };
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function camera(props) {
+            props = props || {};
+            var mountDiv = div({ class: props.class, testId: props.testId });
+            return function mount(host) {
+                var cameraHost = createHost();
+                var unmount = mountDiv(cameraHost);
+                var desc = cameraHost.getElements()[0];
+                desc.camera = { deviceId: unrefValue(props.deviceId), fit: props.fit };
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        camera.snapshot = function(deviceId) { return rasen.camera.snapshot(deviceId); };
+        
+        function shaderUniforms(uniforms) {
+            var values = {};
+            uniforms = unrefValue(uniforms) || {};
+            for (var name in uniforms) {
+                var value = unrefValue(uniforms[name]);
+                values[name] = typeof value === 'number' ? value : Array.from(value);
+            }
+            return values;
+        }
+        
+        function shader(props) {
+            props = props || {};
+            var mountDiv = div({ class: props.class, testId: props.testId });
+            return function mount(host) {
+                var shaderHost = createHost();
+                var unmount = mountDiv(shaderHost);
+                var desc = shaderHost.getElements()[0];
+                desc.shader = {
+                    fragment: unrefValue(props.fragment),
+                    uniforms: shaderUniforms(props.uniforms),
+                    animate: !!props.animate
+                };
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function colorPicker(props) {
+            props = props || {};
+            var mountDiv = div({ class: props.class, testId: props.testId });
+            return function mount(host) {
+                var pickerHost = createHost();
+                var unmount = mountDiv(pickerHost);
+                var desc = pickerHost.getElements()[0];
+                desc.colorPicker = { value: unrefValue(props.value) || '' };
+                desc.handlers.change = props.onChange;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function datePicker(props) {
+            props = props || {};
+            var mountDiv = div({ class: props.class, testId: props.testId });
+            return function mount(host) {
+                var pickerHost = createHost();
+                var unmount = mountDiv(pickerHost);
+                var desc = pickerHost.getElements()[0];
+                var now = new Date();
+                var pad = function(n) { return (n < 10 ? '0' : '') + n; };
+                desc.datePicker = {
+                    value: unrefValue(props.value) || undefined,
+                    min: unrefValue(props.min),
+                    max: unrefValue(props.max),
+                    locale: props.locale,
+                    today: now.getFullYear() + '-' + pad(now.getMonth() + 1) + '-' + pad(now.getDate()),
+                    placeholder: props.placeholder
+                };
+                desc.handlers.change = props.onChange;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function tree(props) {
+            props = props || {};
+            var expanded = {};
+            var loaded = {};
+            var loading = {};
+            var nodes = {};
+            var selected = null;
+            
+            function childrenOf(node) {
+                return node.children || loaded[node.id];
+            }
+            
+            function toggle(id) {
+                var node = nodes[id];
+                if (!node) return;
+                if (expanded[id]) {
+                    delete expanded[id];
+                    return;
+                }
+                expanded[id] = true;
+                if (childrenOf(node) || !node.hasChildren || !props.loadChildren || loading[id]) return;
+                loading[id] = true;
+                // The native side re-renders once the promise settles
+                return Promise.resolve(props.loadChildren(node)).then(function(children) {
+                    loaded[id] = children;
+                    delete loading[id];
+                }, function(error) {
+                    delete expanded[id];
+                    delete loading[id];
+                    throw error;
+                });
+            }
+            
+            function select(id) {
+                var node = nodes[id];
+                if (!node) return;
+                selected = id;
+                if (props.onSelect) props.onSelect(node);
+            }
+            
+            return function mount(host) {
+                var rows = [];
+                nodes = {};
+                function visit(list, depth) {
+                    for (var i = 0; i < list.length; i++) {
+                        var node = list[i];
+                        var known = childrenOf(node);
+                        nodes[node.id] = node;
+                        rows.push({
+                            id: node.id,
+                            label: node.label,
+                            depth: depth,
+                            expandable: known ? known.length > 0 : !!node.hasChildren,
+                            expanded: !!expanded[node.id],
+                            loading: !!loading[node.id]
+                        });
+                        if (expanded[node.id] && known) visit(known, depth + 1);
+                    }
+                }
+                visit(unrefValue(props.roots) || [], 0);
+                
+                var treeHost = createHost();
+                var unmount = div({ class: props.class, testId: props.testId })(treeHost);
+                var desc = treeHost.getElements()[0];
+                desc.tree = { rows: rows, selected: selected };
+                desc.handlers.toggle = toggle;
+                desc.handlers.select = select;
+                host.appendChild(desc);
+                return unmount;
+            };
+        }
+        
+        function filePicker(props) {
+            var collator = rasen.collator(undefined, { sensitivity: 'base', numeric: true });
+            var rootName = props.root.split(/[\\/]/).filter(Boolean).pop() || props.root;
+            // Every path listed so far, with its parent directory for the breadcrumbs
+            var entries = {};
+            entries[props.root] = { name: rootName, isDirectory: true };
+            // Listing of `dir`, updated in place so the tree sees changes
+            var roots = [];
+            var dir = props.root;
+            var selected = props.root;
+            var error = null;
+            
+            function matches(name) {
+                if (!props.filters || !props.filters.length) return true;
+                var dot = name.lastIndexOf('.');
+                var extension = dot >= 0 ? name.slice(dot + 1).toLowerCase() : '';
+                return props.filters.some(function(filter) {
+                    return filter.extensions.some(function(ext) { return ext.toLowerCase() === extension; });
+                });
+            }
+            
+            function list(path) {
+                return rasen.fs.readdir(path).then(function(listed) {
+                    return listed.filter(function(entry) {
+                        return (props.showHidden || entry.name.charAt(0) !== '.') && (entry.isDirectory || matches(entry.name));
+                    }).sort(function(a, b) {
+                        return Number(b.isDirectory) - Number(a.isDirectory) || collator.compare(a.name, b.name);
+                    }).map(function(entry) {
+                        entries[entry.path] = { name: entry.name, parent: path, isDirectory: entry.isDirectory };
+                        return { id: entry.path, label: entry.name, hasChildren: entry.isDirectory };
+                    });
+                });
+            }
+            
+            function open(path) {
+                return list(path).then(function(nodes) {
+                    roots.splice.apply(roots, [0, roots.length].concat(nodes));
+                    dir = selected = path;
+                    error = null;
+                }, function(e) {
+                    error = e && e.message ? e.message : String(e);
+                });
+            }
+            
+            var listing = tree({
+                class: 'flex-1',
+                roots: roots,
+                loadChildren: function(node) { return list(node.id); },
+                onSelect: function(node) {
+                    selected = node.id;
+                    if (!node.hasChildren && props.onSelect) props.onSelect(node.id);
+                }
+            });
+            open(props.root);
+            
+            return function mount(host) {
+                var trail = [];
+                for (var path = selected; path !== undefined; path = entries[path] && entries[path].parent) {
+                    trail.unshift(path);
+                }
+                var crumbs = [];
+                trail.forEach(function(path, index) {
+                    var entry = entries[path];
+                    if (index > 0) crumbs.push(text({ class: 'text-gray-400', text: '›' }));
+                    crumbs.push(entry.isDirectory ? button({
+                        class: path === dir ? 'font-semibold' : 'text-blue-600',
+                        onClick: function() { return open(path); },
+                        children: [text({ text: entry.name })]
+                    }) : text({ text: entry.name }));
+                });
+                
+                return div({
+                    class: 'flex flex-col ' + (unrefValue(props.class) || ''),
+                    testId: props.testId,
+                    children: [
+                        div({ class: 'flex flex-row items-center gap-1 px-2 py-1 text-sm', children: crumbs }),
+                        error ? text({ class: 'p-2 text-red-600 text-sm', text: error }) : listing
+                    ]
+                })(host);
+            };
+        }
+        
+        function moveItem(items, from, to) {
+            var moved = items.slice();
+            moved.splice(to, 0, moved.splice(from, 1)[0]);
+            return moved;
+        }
+        
+        var __elementRefCounter = 0;
+        
+        function elementRef() {
+            var id = 'ref' + (++__elementRefCounter);
+            function convert(name) {
+                return function(x, y) { return rasen.layout[name](id, x, y); };
+            }
+            return {
+                id: id,
+                toLocal: convert('toLocal'),
+                toScreen: convert('toScreen'),
+                fromWindow: convert('fromWindow'),
+                toWindow: convert('toWindow'),
+                bounds: function() { return rasen.layout.bounds(id); },
+                scrollIntoView: function() {
+                    rasen.layout.scrollIntoView(id);
+                    __requestRender();
+                }
+            };
+        }
+        
+        // ========== JSX ==========
+        
+        var __intrinsics = {
+            div: div, text: text, button: button, img: img, camera: camera, shader: shader, keymap: keymap,
+            sortableList: sortableList, selectable: selectable, dragHandle: dragHandle,
+            colorPicker: colorPicker, datePicker: datePicker, tree: tree, filePicker: filePicker
+        };
+        
+        function __flattenChildren(children, out) {
+            for (var i = 0; i < children.length; i++) {
+                var child = children[i];
+                if (Array.isArray(child)) __flattenChildren(child, out);
+                else if (child !== null && child !== undefined && typeof child !== 'boolean') out.push(child);
+            }
+            return out;
+        }
+        
+        function h(type, props) {
+            var component = typeof type === 'string' ? __intrinsics[type] : type;
+            if (!component) throw new Error('Unknown JSX element <' + type + '>');
+            var flat = __flattenChildren(Array.prototype.slice.call(arguments, 2), []);
+            var merged = Object.assign({}, props);
+            if (component === text) {
+                merged.children = flat.length === 1 ? flat[0] : {
+                    get value() {
+                        return flat.map(function(part) { return String(unrefValue(part)); }).join('');
+                    }
+                };
+                return text(merged);
+            }
+            if (flat.length > 0) {
+                merged.children = flat.map(function(child) {
+                    return typeof child === 'function' ? child : text({ children: child });
+                });
+            }
+            var result = component(merged);
+            return result && typeof result.then === 'function' ? __deferred(result) : result;
+        }
+        
+        function Fragment(props) {
+            return function mount(host) {
+                var unmounts = (props.children || []).map(function(child) { return child(host); });
+                return function() {
+                    unmounts.forEach(function(unmount) { if (typeof unmount === 'function') unmount(); });
+                };
+            };
+        }
+        
+        function show(cond, render, fallback) {
+            return function mount(host) {
+                if (unrefValue(cond)) return render()(host);
+                if (fallback) return fallback()(host);
+                host.appendChild({ type: 'empty', class: '' });
+                return function() {};
+            };
+        }
+        
+        function each(items, render, key) {
+            var rows = new Map();
+            return function mount(host) {
+                var next = new Map();
+                var unmounts = (unrefValue(items) || []).map(function(item, index) {
+                    var rowKey = String(key(item, index));
+                    var row = rows.get(rowKey);
+                    if (!row || row.item !== item) row = { item: item, mount: render(item, index) };
+                    next.set(rowKey, row);
+                    var rowHost = createHost();
+                    var unmount = row.mount(rowHost);
+                    rowHost.getElements().forEach(function(desc) {
+                        if (desc.key === undefined) desc.key = rowKey;
+                        host.appendChild(desc);
+                    });
+                    return unmount;
+                });
+                rows = next;
+                return function() {
+                    unmounts.forEach(function(unmount) { if (typeof unmount === 'function') unmount(); });
+                };
+            };
+        }
+        
+        // Mounts `fallback` (or a placeholder) until `setup` resolves; a
+        // rejection is thrown when mounting, like a render error
+        function __deferred(setup, fallback) {
+            var resolved = null;
+            var failed = null;
+            setup.then(function(mount) {
+                resolved = mount;
+                __requestRender();
+            }, function(error) {
+                failed = { error: error };
+                __requestRender();
+            });
+            var mountable = function mount(host) {
+                if (failed) throw failed.error;
+                if (resolved) return resolved(host);
+                if (fallback) return fallback(host);
+                host.appendChild({ type: 'empty', class: '' });
+                return function() {};
+            };
+            mountable.pending = function() { return !resolved && !failed; };
+            return mountable;
+        }
+        
+        var __renderCount = 0;
+        var __fullRender = false;
+        var __memoCounter = 0;
+        
+        // Mounts `mountable` again only when a dep changed since the last
+        // render, and otherwise sends skip markers for what it rendered
+        function memo(mountable, deps) {
+            var id = 'memo' + (++__memoCounter);
+            var last = null;
+            return function mount(host) {
+                var values = typeof deps === 'function' ? deps() : deps.map(function(dep) { return unrefValue(dep); });
+                // Skipping relies on the last render being on screen
+                var unchanged = last && !__fullRender && last.render === __renderCount - 1 &&
+                    values.length === last.values.length &&
+                    values.every(function(value, i) { return Object.is(value, last.values[i]); });
+                if (unchanged) {
+                    last.render = __renderCount;
+                    last.keys.forEach(function(key) { host.appendChild({ type: 'skip', class: '', key: key }); });
+                    return function() {};
+                }
+                var memoHost = createHost();
+                var unmount = mountable(memoHost);
+                var keys = memoHost.getElements().map(function(desc, i) {
+                    if (desc.key === undefined) desc.key = i === 0 ? id : id + ':' + i;
+                    host.appendChild(desc);
+                    return desc.key;
+                });
+                last = { values: values, keys: keys, render: __renderCount };
+                return unmount;
+            };
+        }
+        
+        function suspense(setup, fallback) {
+            var result = setup();
+            return result && typeof result.then === 'function' ? __deferred(result, fallback) : result;
+        }
+        
+        function Suspense(props) {
+            var children = props.children || [];
+            var mountChildren = Fragment({ children: children });
+            return function mount(host) {
+                var pending = children.some(function(child) { return child.pending && child.pending(); });
+                if (!pending) return mountChildren(host);
+                if (props.fallback) return props.fallback(host);
+                host.appendChild({ type: 'empty', class: '' });
+                return function() {};
+            };
+        }
+        
+        // ========== Stores ==========
+        function createStore(initial) {
+            var state = initial;
+            var listeners = [];
+            
+            function subscribe(selectorOrListener, listener) {
+                var wrapped = selectorOrListener;
+                if (listener) {
+                    wrapped = function(next, previous) {
+                        var selected = selectorOrListener(next);
+                        var before = selectorOrListener(previous);
+                        if (!Object.is(selected, before)) listener(selected, before);
+                    };
+                }
+                listeners.push(wrapped);
+                return function() {
+                    var index = listeners.indexOf(wrapped);
+                    if (index !== -1) listeners.splice(index, 1);
+                };
+            }
+            
+            return {
+                get: function() { return state; },
+                set: function(update) {
+                    var previous = state;
+                    state = typeof update === 'function' ? update(state) : update;
+                    if (Object.is(state, previous)) return;
+                    var current = listeners.slice();
+                    for (var i = 0; i < current.length; i++) {
+                        try {
+                            current[i](state, previous);
+                        } catch (e) {
+                            console.error('Error in store listener:', e);
+                        }
+                    }
+                    __requestRender();
+                },
+                subscribe: subscribe,
+                select: function(selector) {
+                    return { get value() { return selector(state); } };
+                }
+            };
+        }
+        
         // ========== App Runner with Proper Three-Phase Pattern ==========
         // 
         // Rasen three-phase pattern:
@@ -300,17 +1535,54 @@ fn generate_builtin_gpui_module() -> String {
         var __mountFn = null;   // The mount function from App's setup phase
         var __unmountFn = null; // The current unmount function
         
-        function run(App) {
+        // Mounts inside an effect, so writing a ref the last render read
+        // asks the native side for a re-render, wherever the write came from.
+        // Writes until the next render share one request.
+        var __renderQueued = false;
+        var __mountEffect = __reactivity.effect(function() {
+            __renderQueued = false;
+            var rootHost = createHost();
+            __unmountFn = __mountFn(rootHost);
+            return rootHost;
+        }, {
+            lazy: true,
+            scheduler: function() {
+                if (!__renderQueued) {
+                    __renderQueued = true;
+                    __requestRender();
+                }
+            }
+        });
+        
+        function run(App, options) {
+            // Read when the window opens, after the script (see `window`)
+            globalThis.__windowOptions = options || null;
             // Execute App's setup phase ONCE - this creates refs
-            __mountFn = App();
+            var app = App();
+            if (app && typeof app.then === 'function') {
+                // Async setup: render an empty root until it resolves. The
+                // native side drains promise jobs and reports a rejection.
+                __rootElement = { type: 'div', class: 'size-full' };
+                globalThis.__pendingApp = app.then(function(mount) {
+                    __mountFn = mount;
+                    globalThis.__pendingApp = null;
+                });
+                return;
+            }
+            __mountFn = app;
             // Initial render
             __rerender();
         }
         
         // Re-render function: re-executes ONLY the mount function
         // This preserves refs because they live in the App closure
-        function __rerender() {
-            if (!__mountFn) return null;
+        function __rerender(full) {
+            // Still waiting for an async App: keep its placeholder
+            if (!__mountFn) return __rootElement;
+            
+            // `full` when the native side has no tree for memos to skip against
+            __renderCount++;
+            __fullRender = !!full;
             
             // Call previous unmount if exists
             if (__unmountFn) {
@@ -318,8 +1590,7 @@ fn generate_builtin_gpui_module() -> String {
             }
             
             // Create fresh host and mount
-            var rootHost = createHost();
-            __unmountFn = __mountFn(rootHost);
+            var rootHost = __mountEffect();
             
             var elements = rootHost.getElements();
             __rootElement = elements[0] || null;
@@ -329,22 +1600,121 @@ fn generate_builtin_gpui_module() -> String {
         // Make __rerender globally accessible
         globalThis.__rerender = __rerender;
         
+        // ========== Windows from rasen.window.open ==========
+        // Each has its own component, mounted like run()'s, by window ID.
+        // Render counts are per window, so its memos only compare against
+        // its own renders.
+        var __windows = {};
+        
+        globalThis.__mountWindow = function(id, Component, route) {
+            var win = { mount: null, unmount: null, root: null, renders: 0, queued: false };
+            win.effect = __reactivity.effect(function() {
+                win.queued = false;
+                var rootHost = createHost();
+                win.unmount = win.mount(rootHost);
+                return rootHost;
+            }, {
+                lazy: true,
+                scheduler: function() {
+                    if (win.queued) return;
+                    win.queued = true;
+                    __requestRender();
+                }
+            });
+            var app = Component({ id: id, route: route });
+            __windows[id] = win;
+            if (app && typeof app.then === 'function') {
+                win.root = { type: 'div', class: 'size-full' };
+                app.then(function(mount) {
+                    win.mount = mount;
+                    __requestRender();
+                }, function(error) {
+                    __reportError(error, 'window ' + id);
+                });
+                return;
+            }
+            win.mount = app;
+        };
+        
+        globalThis.__rerenderWindow = function(id, full) {
+            var win = __windows[id];
+            if (!win || !win.mount) return win ? win.root : null;
+            var renders = __renderCount;
+            __renderCount = ++win.renders;
+            __fullRender = !!full;
+            try {
+                if (win.unmount) win.unmount();
+                var elements = win.effect().getElements();
+                win.root = elements[0] || null;
+                return win.root;
+            } finally {
+                __renderCount = renders;
+            }
+        };
+        
+        globalThis.__windowClosed = function(id) {
+            var win = __windows[id];
+            if (!win) return;
+            delete __windows[id];
+            if (win.unmount) win.unmount();
+            win.effect.stop();
+        };
+        
         // ========== Export ==========
         __modules['@rasenjs/gpui'] = {
             ref: ref,
-            computed: __modules['@rasenjs/reactive-signals'].computed,
-            watch: __modules['@rasenjs/reactive-signals'].watch,
+            computed: __reactivity.computed,
+            watch: __reactivity.watch,
+            effect: __reactivity.effect,
+            watchEffect: __reactivity.watchEffect,
+            reactive: __reactivity.reactive,
+            isReactive: __reactivity.isReactive,
+            toRaw: __reactivity.toRaw,
+            requestRender: function() { __requestRender(); },
+            nextTick: __nextTick,
+            onError: __onError,
+            createStore: createStore,
             unref: unrefValue,
             isRef: isRef,
             div: div,
             text: text,
             button: button,
+            img: img,
+            camera: camera,
+            shader: shader,
+            keymap: keymap,
+            sortableList: sortableList,
+            dragHandle: dragHandle,
+            moveItem: moveItem,
+            colorPicker: colorPicker,
+            datePicker: datePicker,
+            tree: tree,
+            filePicker: filePicker,
+            selectable: selectable,
+            elementRef: elementRef,
+            h: h,
+            Fragment: Fragment,
+            show: show,
+            when: show,
+            each: each,
+            suspense: suspense,
+            memo: memo,
+            Suspense: Suspense,
             run: run
         };
 })();
     "#.to_string()
 }
 
+/// The script re-rendering `window`; `full` renders `memo()` subtrees too
+fn render_script(window: WindowId, full: bool) -> String {
+    match (window, full) {
+        (MAIN_WINDOW, true) => "__rerender(true)".to_string(),
+        (MAIN_WINDOW, false) => "__rerender()".to_string(),
+        (window, full) => format!("__rerenderWindow({}, {})", window, full),
+    }
+}
+
 fn wrap_module(name: &str, source: &str) -> String {
     format!(r#"
 (function() {{
@@ -356,7 +1726,9 @@ fn wrap_module(name: &str, source: &str) -> String {
 "#, name = name, source = source)
 }
 
-fn transform_imports(script: &str) -> String {
+/// Turn the entry script's imports into reads of `__modules`, or loads of
+/// `.wasm` files relative to `script_dir`
+pub(crate) fn transform_imports(script: &str, script_dir: &Path) -> String {
     let mut result = String::new();
     
     for line in script.lines() {
@@ -367,18 +1739,25 @@ fn transform_imports(script: &str) -> String {
                 let import_part = &trimmed[7..from_idx];
                 let module_part = &trimmed[from_idx + 6..];
                 let module_name = module_part.trim().trim_matches(|c| c == '\'' || c == '"' || c == ';');
+                let wasm = module_name.ends_with(".wasm").then(|| {
+                    format!("__wasm.load({:?})", script_dir.join(module_name).to_string_lossy())
+                });
                 
                 if let Some(start) = import_part.find('{') {
                     if let Some(end) = import_part.find('}') {
                         let names = &import_part[start + 1..end];
-                        result.push_str(&format!("const {{{names}}} = __modules['{module_name}'];\n"));
+                        let module = wasm.unwrap_or_else(|| format!("__modules['{module_name}']"));
+                        result.push_str(&format!("const {{{names}}} = {module};\n"));
                         continue;
                     }
                 }
                 
                 let default_name = import_part.trim();
                 if !default_name.is_empty() && !default_name.contains('{') {
-                    result.push_str(&format!("const {default_name} = __modules['{module_name}'].default || __modules['{module_name}'];\n"));
+                    match wasm {
+                        Some(module) => result.push_str(&format!("const {default_name} = {module};\n")),
+                        None => result.push_str(&format!("const {default_name} = __modules['{module_name}'].default || __modules['{module_name}'];\n")),
+                    }
                     continue;
                 }
             }
@@ -391,92 +1770,143 @@ fn transform_imports(script: &str) -> String {
     result
 }
 
-fn js_to_element<'js>(ctx: &rquickjs::Ctx<'js>, value: &Value<'js>, event_manager: &EventManager) -> Result<Element> {
-    static ELEMENT_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
-    
+/// Log the rejection of `value` if it's a promise (or other thenable)
+///
+/// Async handlers and timer callbacks return promises nobody awaits, so
+/// their errors would otherwise vanish.
+pub fn report_rejection<'js>(ctx: &Ctx<'js>, value: &Value<'js>, what: String) {
+    let Some(object) = value.as_object() else {
+        return;
+    };
+    let Ok(then) = object.get::<_, Function>("then") else {
+        return;
+    };
+    let result = Function::new(ctx.clone(), move |error: Value<'js>| {
+        eprintln!("Error in {}: {:?}", what, error);
+    })
+    .and_then(|on_rejected| then.call::<_, ()>((This(object.clone()), Value::new_undefined(ctx.clone()), on_rejected)));
+    if let Err(e) = result {
+        eprintln!("Failed to watch {} for errors: {:?}", what, e);
+    }
+}
+
+fn js_to_element<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    value: &Value<'js>,
+    event_manager: &EventManager,
+    options: &RuntimeOptions,
+) -> Result<Element> {
+    let element = js_to_descriptor(ctx, value, event_manager, options)?.into_element();
+    check_styles([&element], options.strict_styles)?;
+    Ok(element)
+}
+
+/// Encode a JS element tree and register its new handlers
+fn js_to_descriptor<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    value: &Value<'js>,
+    event_manager: &EventManager,
+    options: &RuntimeOptions,
+) -> Result<descriptor::ElementDescriptor> {
     if value.is_null() || value.is_undefined() {
         anyhow::bail!("Root element is null or undefined");
     }
     
-    let obj = value.as_object().ok_or_else(|| anyhow::anyhow!("Expected object"))?;
+    // Encode the whole tree in one call instead of reading it property by property
+    let encode: Function = ctx.globals().get("__encodeTree")?;
+    let encoded: Array = match encode.call((value.clone(),)) {
+        Ok(encoded) => encoded,
+        Err(e) => {
+            let exc = ctx.catch();
+            let message = format!("{:?} {:?}", e, exc);
+            if message.contains("stack overflow") {
+                anyhow::bail!(
+                    "Element tree is nested too deeply for the JS stack; \
+                     raise `runtime.stackSize` in rasen.config.js ({})",
+                    message
+                );
+            }
+            anyhow::bail!("Failed to encode element tree: {}", message);
+        }
+    };
+    let json: String = encoded.get(0)?;
+    let fresh: Array = encoded.get(1)?;
     
-    let element_type: String = obj.get("type")?;
-    let class_str: String = obj.get("class").unwrap_or_default();
-    let styles = tw_parser::parse(&class_str);
+    // JS keeps the functions; note only the IDs it added to its table
+    for i in 0..fresh.len() {
+        let handler_id: HandlerId = fresh.get(i)?;
+        event_manager.register_handler(handler_id);
+    }
     
-    match element_type.as_str() {
-        "div" => {
-            // Generate unique element ID
-            let element_id = format!("elem_{}", ELEMENT_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst));
-            
-            // Extract handlers
-            let mut handlers = EventHandlers::default();
-            
-            // Get handlers object from JS
-            let handlers_val: Value = obj.get("handlers")?;
-            if !handlers_val.is_null() && !handlers_val.is_undefined() {
-                if let Some(handlers_obj) = handlers_val.as_object() {
-                    // Click handler
-                    let click_val: Value = handlers_obj.get("click")?;
-                    if click_val.is_function() {
-                        let handler_id = next_handler_id();
-                        // Store the function as a persistent reference
-                        if let Some(func) = click_val.as_function() {
-                            let persistent = Persistent::save(ctx, func.clone());
-                            event_manager.register_handler(handler_id, persistent);
-                            handlers.on_click = Some(handler_id);
-                        }
-                    }
-                    
-                    // Mouse enter handler
-                    let enter_val: Value = handlers_obj.get("mouseenter")?;
-                    if enter_val.is_function() {
-                        let handler_id = next_handler_id();
-                        if let Some(func) = enter_val.as_function() {
-                            let persistent = Persistent::save(ctx, func.clone());
-                            event_manager.register_handler(handler_id, persistent);
-                            handlers.on_mouse_enter = Some(handler_id);
-                        }
-                    }
-                    
-                    // Mouse leave handler  
-                    let leave_val: Value = handlers_obj.get("mouseleave")?;
-                    if leave_val.is_function() {
-                        let handler_id = next_handler_id();
-                        if let Some(func) = leave_val.as_function() {
-                            let persistent = Persistent::save(ctx, func.clone());
-                            event_manager.register_handler(handler_id, persistent);
-                            handlers.on_mouse_leave = Some(handler_id);
-                        }
-                    }
-                }
-            }
-            
-            // Process children
-            let children_val: Value = obj.get("children")?;
-            let children = if children_val.is_array() {
-                let arr = children_val.as_array().unwrap();
-                let mut result = Vec::new();
-                for i in 0..arr.len() {
-                    let child: Value = arr.get(i)?;
-                    result.push(js_to_element(ctx, &child, event_manager)?);
-                }
-                result
-            } else {
-                Vec::new()
-            };
-            
-            Ok(Element::Div(DivElement { 
-                id: element_id,
-                styles, 
-                children,
-                handlers,
-            }))
+    let descriptor = descriptor::decode(&json)?;
+    if options.large_tree_warning > 0 {
+        warn_if_large(&descriptor, options.large_tree_warning);
+    }
+    Ok(descriptor)
+}
+
+/// Unknown classes already warned about
+static REPORTED_CLASSES: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Report classes the style parser didn't understand
+///
+/// Each unknown class is warned about once per run, with the first element
+/// using it. In strict mode the render fails instead, listing every unknown
+/// class in the tree.
+fn check_styles<'a>(roots: impl IntoIterator<Item = &'a Element>, strict: bool) -> Result<()> {
+    let mut unknown: Vec<(&str, &str)> = Vec::new();
+    let mut stack: Vec<&Element> = roots.into_iter().collect();
+    stack.reverse();
+    while let Some(element) = stack.pop() {
+        for class in &element.styles().unknown_classes {
+            unknown.push((class, element.id()));
+        }
+        if let Element::Div(div) = element {
+            stack.extend(div.children.iter().rev());
         }
-        "text" => {
-            let text: String = obj.get("text").unwrap_or_default();
-            Ok(Element::Text(TextElement { text, styles }))
+    }
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    
+    if strict {
+        let list: Vec<String> = unknown.iter().map(|(class, id)| format!("'{}' ({})", class, id)).collect();
+        anyhow::bail!("Unknown classes (--strict-styles): {}", list.join(", "));
+    }
+    let mut reported = REPORTED_CLASSES.lock().unwrap();
+    let reported = reported.get_or_insert_with(HashSet::new);
+    for (class, id) in unknown {
+        if reported.insert(class.to_string()) {
+            eprintln!("Warning: unknown class '{}' on {} is ignored", class, id);
         }
-        _ => anyhow::bail!("Unknown element type: {}", element_type),
     }
+    Ok(())
+}
+
+/// Element count of the last large-tree warning
+static LAST_LARGE_TREE: AtomicUsize = AtomicUsize::new(0);
+
+/// Warn when a render produces more than `threshold` elements, pointing at
+/// the widest elements, which are usually lists that should be virtualized
+///
+/// Re-renders of the same tree would repeat the warning every frame, so it's
+/// logged again only once the tree has doubled since the last warning.
+fn warn_if_large(descriptor: &descriptor::ElementDescriptor, threshold: usize) {
+    let count = descriptor.count();
+    let last = LAST_LARGE_TREE.load(Ordering::Relaxed);
+    if count <= threshold || (last > 0 && count < 2 * last) {
+        return;
+    }
+    LAST_LARGE_TREE.store(count, Ordering::Relaxed);
+    
+    let size = descriptor.size(3);
+    eprintln!("Warning: render produced {} elements (runtime.largeTreeWarning is {})", size.total, threshold);
+    for subtree in &size.widest {
+        let test_id = subtree.test_id.as_deref().map(|id| format!(" testId={:?}", id)).unwrap_or_default();
+        eprintln!(
+            "  {}{} class={:?}: {} children, {} elements",
+            subtree.id, test_id, subtree.class, subtree.children, subtree.elements,
+        );
+    }
+    eprintln!("  Render only the rows in view (virtualize long lists) to keep layout and painting fast");
 }
@@ -0,0 +1,127 @@
+//! Declarative key bindings from `keymap({ scope, bindings })` elements
+//!
+//! Bindings live on the element tree, so they are registered exactly while
+//! their keymap is mounted. When a key is pressed, the keymaps around the
+//! focused element apply (see `focus`; clicking a keymap's element or
+//! something inside it focuses it), and the innermost one that binds the key
+//! wins. With nothing focused, only the root's keymap applies. Keys an inner
+//! keymap doesn't bind fall through to outer ones, and binding a key to
+//! `null` stops it there without running anything.
+
+use gpui::Keystroke;
+use std::collections::BTreeMap;
+use crate::elements::Element;
+use crate::event_manager::HandlerId;
+
+/// Bindings of a mounted `keymap` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    /// Name of the scope, for diagnostics
+    pub scope: String,
+    /// Keystrokes and their handlers; `None` blocks the key from outer scopes
+    pub bindings: Vec<(Keystroke, Option<HandlerId>)>,
+}
+
+/// The binding chosen for a keystroke
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding<'a> {
+    pub scope: &'a str,
+    pub handler: Option<HandlerId>,
+}
+
+impl Keymap {
+    /// Parse bindings like `cmd-s` or `ctrl-shift-p`, skipping invalid ones with a warning
+    pub fn new(scope: String, bindings: BTreeMap<String, Option<HandlerId>>) -> Self {
+        let bindings = bindings
+            .into_iter()
+            .filter_map(|(source, handler)| match Keystroke::parse(&source) {
+                Ok(keystroke) => Some((keystroke, handler)),
+                Err(_) => {
+                    eprintln!("Warning: invalid key binding '{}' in keymap '{}' is ignored", source, scope);
+                    None
+                }
+            })
+            .collect();
+        Self { scope, bindings }
+    }
+
+    fn lookup(&self, pressed: &Keystroke) -> Option<Option<HandlerId>> {
+        self.bindings
+            .iter()
+            .find(|(keystroke, _)| keystroke.key == pressed.key && keystroke.modifiers == pressed.modifiers)
+            .map(|(_, handler)| *handler)
+    }
+}
+
+/// Find the innermost binding for a keystroke around the focused element
+pub fn resolve<'a>(root: &'a Element, focused: Option<&str>, pressed: &Keystroke) -> Option<Binding<'a>> {
+    ancestors(root, focused).into_iter().rev().find_map(|element| {
+        let Element::Div(div) = element else {
+            return None;
+        };
+        let keymap = div.keymap.as_ref()?;
+        keymap.lookup(pressed).map(|handler| Binding { scope: &keymap.scope, handler })
+    })
+}
+
+/// The elements from `root` down to element `id`, or just `root` without it
+pub fn ancestors<'a>(root: &'a Element, id: Option<&str>) -> Vec<&'a Element> {
+    let Some(id) = id else {
+        return vec![root];
+    };
+    let mut path = Vec::new();
+    let mut stack = vec![(root, 0)];
+    while let Some((element, depth)) = stack.pop() {
+        path.truncate(depth);
+        path.push(element);
+        if element.id() == id {
+            return path;
+        }
+        if let Element::Div(div) = element {
+            let children = div.children.iter().filter(|child| id.starts_with(child.id()));
+            stack.extend(children.map(|child| (child, depth + 1)));
+        }
+    }
+    vec![root]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor;
+
+    fn resolve_json(json: &str, focused: Option<&str>, key: &str) -> Option<(String, Option<HandlerId>)> {
+        let element = descriptor::decode(json).unwrap().into_element();
+        resolve(&element, focused, &Keystroke::parse(key).unwrap()).map(|b| (b.scope.to_string(), b.handler))
+    }
+
+    #[test]
+    fn test_innermost_scope_wins() {
+        let json = r#"{"type":"div","keymap":{"scope":"app","bindings":{"cmd-s":1,"escape":2,"cmd-k":3}},"children":[
+            {"type":"div","keymap":{"scope":"editor","bindings":{"cmd-s":4,"enter":6}}},
+            {"type":"div","keymap":{"scope":"dialog","bindings":{"escape":5,"enter":7,"cmd-k":null}},"children":[
+                {"type":"div","key":"ok"}
+            ]}
+        ]}"#;
+        let dialog = Some("elem_0.1[ok]");
+        assert_eq!(resolve_json(json, dialog, "escape"), Some(("dialog".to_string(), Some(5))));
+        assert_eq!(resolve_json(json, dialog, "enter"), Some(("dialog".to_string(), Some(7))));
+        // Falls through to the app
+        assert_eq!(resolve_json(json, dialog, "cmd-s"), Some(("app".to_string(), Some(1))));
+        // Blocked by the dialog
+        assert_eq!(resolve_json(json, dialog, "cmd-k"), Some(("dialog".to_string(), None)));
+        assert_eq!(resolve_json(json, dialog, "cmd-q"), None);
+        assert_eq!(resolve_json(json, Some("elem_0.0"), "cmd-s"), Some(("editor".to_string(), Some(4))));
+    }
+
+    #[test]
+    fn test_unfocused_uses_root() {
+        let json = r#"{"type":"div","keymap":{"scope":"app","bindings":{"enter":1}},"children":[
+            {"type":"div","keymap":{"scope":"list","bindings":{"enter":2}}}
+        ]}"#;
+        assert_eq!(resolve_json(json, None, "enter"), Some(("app".to_string(), Some(1))));
+        // A sibling's keymap doesn't apply to what's focused elsewhere
+        assert_eq!(resolve_json(json, Some("elem_0.5"), "enter"), Some(("app".to_string(), Some(1))));
+        assert_eq!(resolve_json(json, Some("elem_0.0"), "enter"), Some(("list".to_string(), Some(2))));
+    }
+}
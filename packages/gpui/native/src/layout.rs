@@ -0,0 +1,255 @@
+//! Element measurement for `elementRef()` on `rasen.layout`
+//!
+//! A div with a `ref` records where it was laid out each frame, with the
+//! window's position on screen and the zoom factor at the time. JS converts
+//! points between the element's, the window's and screen coordinates with
+//! that, so canvas overlays and drag logic don't have to track layout.
+//!
+//! `scrollIntoView` is a request the element answers the next time it's
+//! laid out: it scrolls the sortable list it's in just enough to show it.
+//! Outside a scrolling list there's nothing to scroll, and it does nothing.
+//!
+//! Element and window coordinates are in the app's pixels, like mouse
+//! events, so they don't change with the zoom factor; screen coordinates are
+//! the platform's logical pixels.
+
+use gpui::*;
+use rquickjs::{Ctx, Function, Object};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use crate::native_api;
+use crate::window::WindowId;
+
+/// Where an element was last laid out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// Bounds in window coordinates, including the zoom factor
+    pub bounds: Bounds<Pixels>,
+    /// Window position on screen
+    pub window_origin: Point<Pixels>,
+    pub zoom: f32,
+}
+
+impl Measurement {
+    /// Screen point to element coordinates
+    pub fn screen_to_local(&self, screen: Point<f32>) -> Point<f32> {
+        self.window_to_local(point(
+            (screen.x - f32::from(self.window_origin.x)) / self.zoom,
+            (screen.y - f32::from(self.window_origin.y)) / self.zoom,
+        ))
+    }
+
+    /// Element point to screen coordinates
+    pub fn local_to_screen(&self, local: Point<f32>) -> Point<f32> {
+        let window = self.local_to_window(local);
+        point(
+            f32::from(self.window_origin.x) + window.x * self.zoom,
+            f32::from(self.window_origin.y) + window.y * self.zoom,
+        )
+    }
+
+    /// Window point (as in mouse events) to element coordinates
+    pub fn window_to_local(&self, window: Point<f32>) -> Point<f32> {
+        point(
+            window.x - f32::from(self.bounds.origin.x) / self.zoom,
+            window.y - f32::from(self.bounds.origin.y) / self.zoom,
+        )
+    }
+
+    /// Element point to window coordinates
+    pub fn local_to_window(&self, local: Point<f32>) -> Point<f32> {
+        point(
+            local.x + f32::from(self.bounds.origin.x) / self.zoom,
+            local.y + f32::from(self.bounds.origin.y) / self.zoom,
+        )
+    }
+}
+
+/// Measurements of the elements with a `ref`, by ref ID
+///
+/// Shared by the runtime, which answers JS queries, and the windows, which
+/// record layout through [`for_window`](Self::for_window). Refs a window
+/// didn't render in a frame are dropped by [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct Layout {
+    /// The window this handle records for
+    window: WindowId,
+    /// Measurements, with the window they were laid out in
+    measured: Arc<Mutex<HashMap<String, (WindowId, Measurement)>>>,
+    /// Refs rendered in each window's current frame
+    seen: Arc<Mutex<HashMap<WindowId, HashSet<String>>>>,
+    /// Refs to scroll into view when next laid out
+    scroll_requests: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A handle recording the layout of `window`
+    pub fn for_window(&self, window: WindowId) -> Self {
+        Self { window, ..self.clone() }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Measurement> {
+        self.measured.lock().unwrap().get(id).map(|(_, measurement)| *measurement)
+    }
+
+    fn record(&self, id: &str, measurement: Measurement) {
+        self.measured.lock().unwrap().insert(id.to_string(), (self.window, measurement));
+    }
+
+    /// Scroll the element with ref `id` into view when it's next laid out
+    pub fn request_scroll(&self, id: &str) {
+        self.scroll_requests.lock().unwrap().insert(id.to_string());
+    }
+
+    /// End the window's render pass, dropping measurements of, and scroll
+    /// requests for, refs it no longer renders
+    pub fn finish_frame(&self) {
+        let seen = self.seen.lock().unwrap().remove(&self.window).unwrap_or_default();
+        let mut measured = self.measured.lock().unwrap();
+        measured.retain(|id, (window, _)| *window != self.window || seen.contains(id));
+        let elsewhere = |id: &String| measured.get(id).is_some_and(|(window, _)| *window != self.window);
+        self.scroll_requests.lock().unwrap().retain(|id| seen.contains(id) || elsewhere(id));
+    }
+
+    /// Make `d` record its layout under ref `id`, and answer scroll requests
+    /// through `scroll`, the handle of the list it's in
+    ///
+    /// Measures through an overlay filling the element, so the bounds are
+    /// those of its padding box.
+    pub fn measure(&self, d: Div, id: &str, zoom: f32, scroll: Option<ScrollHandle>) -> Div {
+        self.seen.lock().unwrap().entry(self.window).or_default().insert(id.to_string());
+        let (layout, id) = (self.clone(), id.to_string());
+        d.child(
+            canvas(
+                move |bounds, window, _| {
+                    layout.record(&id, Measurement { bounds, window_origin: window.bounds().origin, zoom });
+                    if !layout.scroll_requests.lock().unwrap().remove(&id) {
+                        return;
+                    }
+                    if let Some(scroll) = scroll {
+                        let offset = scrolled_into_view(scroll.bounds(), scroll.offset(), scroll.max_offset(), bounds);
+                        if offset != scroll.offset() {
+                            scroll.set_offset(offset);
+                            window.refresh();
+                        }
+                    }
+                },
+                |_, _, _, _| {},
+            )
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full(),
+        )
+    }
+}
+
+/// The scroll offset showing `element` within `viewport`, changed as little
+/// as possible; an element larger than the viewport is aligned at its start
+fn scrolled_into_view(viewport: Bounds<Pixels>, offset: Point<Pixels>, max: Size<Pixels>, element: Bounds<Pixels>) -> Point<Pixels> {
+    // Offsets are negative as content scrolls toward its end
+    let axis = |offset: Pixels, max: Pixels, start: Pixels, end: Pixels, view_start: Pixels, view_end: Pixels| {
+        let shift = if start < view_start || end - start > view_end - view_start {
+            view_start - start
+        } else if end > view_end {
+            view_end - end
+        } else {
+            return offset;
+        };
+        px(f32::from(offset + shift).clamp(-f32::from(max), 0.0))
+    };
+    point(
+        axis(offset.x, max.width, element.left(), element.right(), viewport.left(), viewport.right()),
+        axis(offset.y, max.height, element.top(), element.bottom(), viewport.top(), viewport.bottom()),
+    )
+}
+
+/// Install `rasen.layout`, answering for the refs measured in `layout`
+///
+/// Each conversion takes a ref ID and a point, and returns `{ x, y }`, or
+/// `null` if the element hasn't been laid out. `bounds(id)` returns the
+/// element's `{ x, y, width, height }` in window coordinates, or `null`.
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, layout: &Layout) -> rquickjs::Result<()> {
+    let namespace = native_api::namespace(ctx, rasen, "layout")?;
+    let measured = layout.clone();
+    namespace.set(
+        "bounds",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, id: String| -> rquickjs::Result<Option<Object<'js>>> {
+            let Some(measurement) = measured.get(&id) else {
+                return Ok(None);
+            };
+            let origin = measurement.local_to_window(point(0.0, 0.0));
+            let result = Object::new(ctx)?;
+            result.set("x", origin.x)?;
+            result.set("y", origin.y)?;
+            result.set("width", f32::from(measurement.bounds.size.width) / measurement.zoom)?;
+            result.set("height", f32::from(measurement.bounds.size.height) / measurement.zoom)?;
+            Ok(Some(result))
+        })?,
+    )?;
+    let requests = layout.clone();
+    namespace.set(
+        "scrollIntoView",
+        Function::new(ctx.clone(), move |id: String| requests.request_scroll(&id))?,
+    )?;
+    let conversions: [(&str, fn(&Measurement, Point<f32>) -> Point<f32>); 4] = [
+        ("toLocal", Measurement::screen_to_local),
+        ("toScreen", Measurement::local_to_screen),
+        ("fromWindow", Measurement::window_to_local),
+        ("toWindow", Measurement::local_to_window),
+    ];
+    for (name, convert) in conversions {
+        let layout = layout.clone();
+        namespace.set(
+            name,
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>, id: String, x: f64, y: f64| -> rquickjs::Result<Option<Object<'js>>> {
+                let Some(measurement) = layout.get(&id) else {
+                    return Ok(None);
+                };
+                let converted = convert(&measurement, point(x as f32, y as f32));
+                let result = Object::new(ctx)?;
+                result.set("x", converted.x)?;
+                result.set("y", converted.y)?;
+                Ok(Some(result))
+            })?,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        // At (100, 50) in a window at (300, 200) on screen, zoomed 2x
+        let measurement = Measurement {
+            bounds: Bounds::new(point(px(100.0), px(50.0)), size(px(40.0), px(40.0))),
+            window_origin: point(px(300.0), px(200.0)),
+            zoom: 2.0,
+        };
+        assert_eq!(measurement.local_to_screen(point(5.0, 10.0)), point(410.0, 270.0));
+        assert_eq!(measurement.screen_to_local(point(410.0, 270.0)), point(5.0, 10.0));
+        assert_eq!(measurement.local_to_window(point(5.0, 10.0)), point(55.0, 35.0));
+        assert_eq!(measurement.window_to_local(point(55.0, 35.0)), point(5.0, 10.0));
+    }
+
+    #[test]
+    fn test_scrolled_into_view() {
+        let viewport = Bounds::new(point(px(0.0), px(100.0)), size(px(200.0), px(100.0)));
+        let max = size(px(0.0), px(500.0));
+        let row = |top: f32, height: f32| Bounds::new(point(px(0.0), px(top)), size(px(200.0), px(height)));
+        let offset = point(px(0.0), px(-50.0));
+        // Visible rows stay put; others scroll to the nearest edge
+        assert_eq!(scrolled_into_view(viewport, offset, max, row(120.0, 20.0)), offset);
+        assert_eq!(scrolled_into_view(viewport, offset, max, row(250.0, 20.0)), point(px(0.0), px(-120.0)));
+        assert_eq!(scrolled_into_view(viewport, offset, max, row(80.0, 20.0)), point(px(0.0), px(-30.0)));
+        // Clamped to the content
+        assert_eq!(scrolled_into_view(viewport, offset, max, row(0.0, 20.0)), point(px(0.0), px(0.0)));
+    }
+}
@@ -0,0 +1,295 @@
+//! App lifecycle on `rasen.app`: quitting and a single instance
+//!
+//! `rasen.app.on('before-quit', listener)` runs before the app quits, from
+//! the Quit menu item or `rasen.app.quit()`. A listener cancels with
+//! `event.preventDefault()` and defers by returning a promise, so state can
+//! be saved first; the app quits once every promise settles, or after
+//! [`QUIT_TIMEOUT`]. `'quit'` listeners run as the app exits, however it
+//! does, and can't stop it.
+//!
+//! With `app.singleInstance` in the config, the first instance listens on a
+//! local port, recorded with a token in a directory keyed by the project's
+//! path. Later launches
+//! hand their arguments and working directory over to it and exit, and it
+//! passes them to `'second-instance'` listeners.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use ring::rand::{SecureRandom, SystemRandom};
+use rquickjs::{Ctx, Function, Object};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::{integrity, native_api};
+
+/// Global holding the listeners and the functions emitting events to them
+const LIFECYCLE: &str = "__appLifecycle";
+
+/// How long `before-quit` listeners may defer quitting
+pub const QUIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// File in the instance directory with the running instance's port and token
+const INSTANCE_FILE: &str = "instance";
+
+/// How long a launch waits for the running instance to answer
+const HANDOVER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Wakes the app for `rasen.app.quit()`
+static QUIT_REQUESTS: Mutex<Option<UnboundedSender<()>>> = Mutex::new(None);
+
+/// Whether `before-quit` listeners are deciding
+static QUITTING: AtomicBool = AtomicBool::new(false);
+
+const SHIM: &str = r#"
+(function() {
+    var listeners = { 'before-quit': [], 'quit': [], 'second-instance': [] };
+
+    // Call the listeners of `event`; returns what they returned
+    function emit(event, args) {
+        var called = listeners[event].slice();
+        var results = [];
+        for (var i = 0; i < called.length; i++) {
+            try {
+                results.push(called[i].apply(null, args));
+            } catch (e) {
+                __reportError(e, event + ' listener');
+            }
+        }
+        return results;
+    }
+
+    return {
+        on: function(event, listener) {
+            if (!listeners[event]) throw new TypeError("Unknown app event '" + event + "'");
+            if (typeof listener !== 'function') {
+                throw new TypeError('App event listener must be a function');
+            }
+            listeners[event].push(listener);
+            return function() {
+                var index = listeners[event].indexOf(listener);
+                if (index >= 0) listeners[event].splice(index, 1);
+            };
+        },
+        // Ask the before-quit listeners, then call `done(quit)`
+        beforeQuit: function(done) {
+            var prevented = false;
+            var event = { preventDefault: function() { prevented = true; } };
+            var waits = emit('before-quit', [event]).filter(function(result) {
+                return result && typeof result.then === 'function';
+            }).map(function(result) {
+                return result.then(null, function(error) {
+                    __reportError(error, 'before-quit listener');
+                });
+            });
+            if (waits.length === 0) return done(!prevented);
+            Promise.all(waits).then(function() { done(!prevented); });
+        },
+        quit: function() { emit('quit', []); },
+        secondInstance: function(args, cwd) { emit('second-instance', [args, cwd]); }
+    };
+})()
+"#;
+
+/// A later launch handed over to the running instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecondInstance {
+    /// Command-line arguments, without the program
+    pub args: Vec<String>,
+    pub cwd: String,
+}
+
+impl SecondInstance {
+    /// This launch
+    pub fn current() -> Self {
+        Self {
+            args: std::env::args().skip(1).collect(),
+            cwd: std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Make this the app's only instance, given its instance directory
+///
+/// Returns `None` after handing this launch over to the instance already
+/// running, and otherwise the launches later instances hand over.
+pub fn claim(dir: &Path) -> io::Result<Option<UnboundedReceiver<SecondInstance>>> {
+    let path = dir.join(INSTANCE_FILE);
+    if let Some((port, token)) = fs::read_to_string(&path).ok().as_deref().and_then(parse_instance) {
+        if hand_over(port, &token, &SecondInstance::current()).is_ok() {
+            return Ok(None);
+        }
+        // Nobody answered: the file is left from an instance that's gone
+    }
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let token = new_token()?;
+    fs::create_dir_all(dir)?;
+    fs::write(&path, format!("{} {}", listener.local_addr()?.port(), token))?;
+
+    let (sender, receiver) = unbounded();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            match receive(stream, &token) {
+                Ok(launch) => {
+                    if sender.unbounded_send(launch).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("Warning: ignoring a second instance: {}", e),
+            }
+        }
+    });
+    Ok(Some(receiver))
+}
+
+/// Read the `<port> <token>` of the instance file
+fn parse_instance(content: &str) -> Option<(u16, String)> {
+    let (port, token) = content.trim().split_once(' ')?;
+    Some((port.parse().ok()?, token.to_string()))
+}
+
+fn new_token() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| io::Error::other("no random numbers for the instance token"))?;
+    Ok(integrity::to_hex(&bytes))
+}
+
+/// Send `launch` to the instance on `port`, which answers `ok` if it's the app's
+fn hand_over(port: u16, token: &str, launch: &SecondInstance) -> io::Result<()> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)), HANDOVER_TIMEOUT)?;
+    stream.set_read_timeout(Some(HANDOVER_TIMEOUT))?;
+    writeln!(stream, "{}", token)?;
+    writeln!(stream, "{}", serde_json::to_string(launch)?)?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    if answer.trim() != "ok" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not the app's instance"));
+    }
+    Ok(())
+}
+
+/// Read a launch handed over on `stream`, checking its token
+fn receive(stream: TcpStream, token: &str) -> io::Result<SecondInstance> {
+    stream.set_read_timeout(Some(HANDOVER_TIMEOUT))?;
+    let mut lines = BufReader::new(stream.try_clone()?).lines();
+    if lines.next().transpose()?.as_deref() != Some(token) {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "wrong token"));
+    }
+    let launch = serde_json::from_str(&lines.next().transpose()?.unwrap_or_default())?;
+    writeln!(&stream, "ok")?;
+    Ok(launch)
+}
+
+/// Receive the requests to quit: `rasen.app.quit()` calls of every runtime
+/// and the Quit action
+pub fn listen_quit() -> UnboundedReceiver<()> {
+    let (sender, receiver) = unbounded();
+    *QUIT_REQUESTS.lock().unwrap() = Some(sender);
+    receiver
+}
+
+/// Ask to quit, through the `before-quit` listeners; returns `false` when
+/// nothing listens for requests yet
+pub fn request_quit() -> bool {
+    match QUIT_REQUESTS.lock().unwrap().as_ref() {
+        Some(requests) => requests.unbounded_send(()).is_ok(),
+        None => false,
+    }
+}
+
+/// Start quitting: ask the `before-quit` listeners, which answer through
+/// the receiver whether to quit
+///
+/// Returns `None` while they're still deciding on an earlier quit; call
+/// [`finish_quit`] once they have.
+pub fn before_quit(ctx: &Ctx) -> Option<oneshot::Receiver<bool>> {
+    if QUITTING.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let (done, decision) = oneshot::channel();
+    let done = Arc::new(Mutex::new(Some(done)));
+    let answer = done.clone();
+    let asked = Function::new(ctx.clone(), move |quit: bool| {
+        if let Some(done) = answer.lock().unwrap().take() {
+            let _ = done.send(quit);
+        }
+    })
+    .and_then(|answer| {
+        let lifecycle: Object = ctx.globals().get(LIFECYCLE)?;
+        lifecycle.get::<_, Function>("beforeQuit")?.call::<_, ()>((answer,))
+    });
+    if let Err(e) = asked {
+        // Listeners that can't be asked don't keep the app open
+        eprintln!("Error in before-quit listeners: {:?}", e);
+        if let Some(done) = done.lock().unwrap().take() {
+            let _ = done.send(true);
+        }
+    }
+    Some(decision)
+}
+
+/// Allow the next quit, once the `before-quit` listeners have decided
+pub fn finish_quit() {
+    QUITTING.store(false, Ordering::SeqCst);
+}
+
+/// Call the `quit` listeners, as the app exits
+pub fn quit(ctx: &Ctx) {
+    if let Err(e) = emit(ctx, "quit", ()) {
+        eprintln!("Error in quit listeners: {:?}", e);
+    }
+}
+
+/// Call the `second-instance` listeners with a launch handed over
+pub fn second_instance(ctx: &Ctx, launch: &SecondInstance) {
+    if let Err(e) = emit(ctx, "secondInstance", (launch.args.clone(), launch.cwd.clone())) {
+        eprintln!("Error in second-instance listeners: {:?}", e);
+    }
+}
+
+fn emit<'js, A: rquickjs::function::IntoArgs<'js>>(ctx: &Ctx<'js>, name: &str, args: A) -> rquickjs::Result<()> {
+    let lifecycle: Object = ctx.globals().get(LIFECYCLE)?;
+    lifecycle.get::<_, Function>(name)?.call(args)
+}
+
+/// Install `rasen.app`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let lifecycle: Object = ctx.eval(SHIM)?;
+    ctx.globals().set(LIFECYCLE, lifecycle.clone())?;
+
+    let app = native_api::namespace(ctx, rasen, "app")?;
+    app.set("on", lifecycle.get::<_, Function>("on")?)?;
+    app.set(
+        "quit",
+        Function::new(ctx.clone(), || {
+            request_quit();
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_second_launch_is_handed_over() {
+        let dir = std::env::temp_dir().join(format!("rasen-instance-{}", std::process::id()));
+        let mut launches = claim(&dir).unwrap().expect("the first launch runs");
+        assert!(claim(&dir).unwrap().is_none());
+        assert_eq!(futures::executor::block_on(launches.next()), Some(SecondInstance::current()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_instance_file() {
+        assert_eq!(parse_instance("41234 00ff\n"), Some((41234, "00ff".to_string())));
+        assert_eq!(parse_instance("garbage"), None);
+    }
+}
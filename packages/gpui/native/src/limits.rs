@@ -0,0 +1,102 @@
+//! Resource limits for the JS engine, and `rasen.gc()`
+//!
+//! The `runtime` section of the config caps the QuickJS heap
+//! (`memoryLimit`) and, with `timeLimit`, how long a single call into JS
+//! (the script, a render, a handler, a timer...) may run before it is
+//! interrupted. A script that runs out of memory or loops forever then fails
+//! with an error, shown in the error overlay, instead of exhausting memory
+//! or freezing the window.
+
+use rquickjs::{qjs, Ctx, Function, Object, Runtime};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Times calls into JS, interrupting those that run over the time limit
+#[derive(Clone)]
+pub struct Watchdog {
+    limit: Option<Duration>,
+    /// When the current call into JS started
+    entered: Arc<Mutex<Instant>>,
+    /// Whether the current call was interrupted, to report it once
+    fired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    pub fn new(limit: Option<Duration>) -> Self {
+        Self { limit, entered: Arc::new(Mutex::new(Instant::now())), fired: Arc::default() }
+    }
+
+    /// Start timing a call into JS
+    pub fn enter(&self) {
+        if self.limit.is_some() {
+            *self.entered.lock().unwrap() = Instant::now();
+            self.fired.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether the current call has run longer than the limit
+    fn expired(&self) -> bool {
+        self.limit.is_some_and(|limit| self.entered.lock().unwrap().elapsed() > limit)
+    }
+
+    /// Let QuickJS ask the watchdog whether to interrupt, if there's a limit
+    pub fn attach(&self, runtime: &Runtime) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+        let watchdog = self.clone();
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            if !watchdog.expired() {
+                return false;
+            }
+            if !watchdog.fired.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "Error: script ran for more than {} ms without returning; interrupted (`runtime.timeLimit`)",
+                    limit.as_millis()
+                );
+            }
+            true
+        })));
+    }
+}
+
+/// Collect garbage now; returns the bytes the heap uses afterwards
+fn collect(ctx: &Ctx) -> f64 {
+    // As quickjs-libc's `std.gc()`: the runtime is already locked by the
+    // call into JS, so use it directly rather than through `Runtime`
+    unsafe {
+        let runtime = qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+        qjs::JS_RunGC(runtime);
+        let mut usage = std::mem::zeroed::<qjs::JSMemoryUsage>();
+        qjs::JS_ComputeMemoryUsage(runtime, &mut usage);
+        usage.malloc_size as f64
+    }
+}
+
+/// Install `rasen.gc()`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    rasen.set("gc", Function::new(ctx.clone(), |ctx: Ctx<'js>| collect(&ctx))?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_expires_per_call() {
+        let watchdog = Watchdog::new(Some(Duration::from_millis(20)));
+        watchdog.enter();
+        assert!(!watchdog.expired());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(watchdog.expired());
+        // The next call starts over
+        watchdog.enter();
+        assert!(!watchdog.expired());
+
+        let unlimited = Watchdog::new(None);
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!unlimited.expired());
+    }
+}
@@ -1,15 +1,76 @@
 mod tw_parser;
+mod tw_colors;
 mod js_runtime;
 mod elements;
 mod module_loader;
 mod event_manager;
+mod descriptor;
+mod diff;
+mod config;
+mod native_api;
+mod platform;
+mod menus;
+mod integrity;
+mod animation;
+mod sanitize;
+mod automation;
+mod session;
+mod activity;
+mod print;
+mod zoom;
+mod undo;
+mod startup;
+mod collation;
+mod bus;
+mod keymap;
+mod sortable;
+mod timers;
+mod colorpicker;
+mod dates;
+mod datepicker;
+mod tree;
+mod console;
+mod files;
+mod selectable;
+mod builtins;
+mod pointer;
+mod layout;
+mod storage;
+mod images;
+mod camera;
+mod frames;
+mod process;
+mod urls;
+mod typescript;
+mod overlay;
+mod limits;
+mod native_modules;
+mod plugins;
+mod wasm;
+mod notifications;
+mod dialogs;
+mod shell;
+mod values;
+mod workers;
+mod reactivity;
+mod throttle;
+mod window;
+mod screen;
+mod lifecycle;
+mod focus;
+mod shader;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use futures::future::{self, Either};
+use futures::StreamExt;
 use gpui::*;
+use js_runtime::{JsRuntime, Wakeup};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "rasen-gpui")]
@@ -27,6 +88,18 @@ enum Commands {
         /// Path to the script file or project directory (default: current directory)
         #[arg(default_value = ".")]
         path: PathBuf,
+        /// Disable eval/Function and runtime module registration
+        #[arg(long)]
+        hardened: bool,
+        /// Accept JSON-RPC automation requests on stdin (responses on stdout)
+        #[arg(long)]
+        automation: bool,
+        /// Print how long each startup phase took, flagging phases over budget
+        #[arg(long)]
+        trace_startup: bool,
+        /// Fail renders that use unknown Tailwind classes instead of warning
+        #[arg(long)]
+        strict_styles: bool,
     },
     /// Initialize a new project
     Init {
@@ -40,37 +113,34 @@ enum Commands {
         #[arg(short, long, default_value = "dist")]
         outdir: String,
     },
+    /// Print the SHA-256 of the code `run` would evaluate (for `security.sha256`)
+    Hash {
+        /// Path to the script file or project directory (default: current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+    },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run { path } => run_script(&path),
+        Commands::Run { path, hardened, automation, trace_startup, strict_styles } => {
+            run_script(&path, hardened, automation, trace_startup, strict_styles)
+        }
         Commands::Init { name } => init_project(&name),
         Commands::Build { outdir } => build_project(&outdir),
+        Commands::Hash { path } => hash_bundle(&path),
     }
 }
 
-fn run_script(path: &PathBuf) -> Result<()> {
-    // Determine script file and working directory
-    let (script_file, work_dir) = if path.is_dir() {
-        // Directory provided - look for src/main.ts or src/main.js
-        let main_ts = path.join("src/main.ts");
-        let main_js = path.join("src/main.js");
-        let index_ts = path.join("src/index.ts");
-        let index_js = path.join("src/index.js");
-        
-        let script = if main_ts.exists() {
-            main_ts
-        } else if main_js.exists() {
-            main_js
-        } else if index_ts.exists() {
-            index_ts
-        } else if index_js.exists() {
-            index_js
-        } else {
-            anyhow::bail!("No entry file found. Expected src/main.ts, src/main.js, src/index.ts, or src/index.js");
+/// Determine the entry script and working directory for a path
+fn resolve_entry(path: &PathBuf) -> Result<(PathBuf, PathBuf)> {
+    let entry = if path.is_dir() {
+        // Directory provided - look for src/main or src/index, in TypeScript, TSX or JS
+        let candidates = ["src/main.ts", "src/main.tsx", "src/main.js", "src/index.ts", "src/index.tsx", "src/index.js"];
+        let Some(script) = candidates.iter().map(|candidate| path.join(candidate)).find(|script| script.exists()) else {
+            anyhow::bail!("No entry file found. Expected src/main.ts, src/main.tsx, src/main.js, src/index.ts, src/index.tsx, or src/index.js");
         };
         (script, path.clone())
     } else {
@@ -78,40 +148,204 @@ fn run_script(path: &PathBuf) -> Result<()> {
         let dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| PathBuf::from("."));
         (path.clone(), dir)
     };
+    Ok(entry)
+}
+
+/// Where the app's script is and how to run it, kept to run it again when
+/// the error overlay reloads
+#[derive(Clone)]
+struct Launch {
+    script_file: PathBuf,
+    work_dir: PathBuf,
+    options: js_runtime::RuntimeOptions,
+    /// Native modules for every runtime (see `native_modules`)
+    plugins: Vec<Arc<dyn native_modules::NativePlugin>>,
+}
+
+impl Launch {
+    /// Read the entry script and bundle the config's modules, ready to run
+    fn load(&self, config: &config::RasenConfig) -> Result<(String, module_loader::ModuleLoader)> {
+        let script = fs::read_to_string(&self.script_file)?;
+        let mut loader = module_loader::ModuleLoader::new(&self.work_dir);
+        loader.load_modules(config)?;
+        startup::mark(startup::Phase::Bundle);
+        
+        // Refuse tampered bundles before anything is evaluated
+        let manifest = integrity::Manifest::collect(&self.work_dir, &self.script_file, loader.files(), &config.plugins)?;
+        integrity::verify(&config.security, &manifest, &self.script_file)?;
+        // Hashes cover the source as written, so compile TypeScript and JSX afterwards
+        let script = if typescript::needs_compile(&self.script_file) {
+            typescript::compile(&script, &self.script_file, &config.jsx).map_err(|errors| {
+                let errors: Vec<String> = errors.iter().map(|error| format!("\n  {}", error)).collect();
+                anyhow::anyhow!("Can't compile {}:{}", self.script_file.display(), errors.concat())
+            })?
+        } else {
+            script
+        };
+        Ok((script, loader))
+    }
     
-    let script = fs::read_to_string(&script_file)?;
+    /// A fresh runtime with the plugins' native modules
+    fn runtime(&self) -> Arc<js_runtime::JsRuntime> {
+        let runtime = js_runtime::JsRuntime::new(self.options.clone());
+        for plugin in &self.plugins {
+            runtime.register_plugin(plugin.as_ref()).expect("nothing has run in a fresh runtime");
+        }
+        Arc::new(runtime)
+    }
     
-    // Load modules from config in work_dir (cwd)
-    let mut loader = module_loader::ModuleLoader::new(&work_dir);
-    loader.load_modules(&script)?;
+    /// Load the script again and run it in a fresh runtime
+    fn reload(&self) -> Result<(Arc<js_runtime::JsRuntime>, Result<elements::Element>)> {
+        let config = config::RasenConfig::load(&self.work_dir)?;
+        let (script, loader) = self.load(&config)?;
+        let runtime = self.runtime();
+        let root = runtime.execute_with_modules(&script, &loader);
+        Ok((runtime, root))
+    }
+}
+
+fn run_script(
+    path: &PathBuf,
+    hardened: bool,
+    automation: bool,
+    trace_startup: bool,
+    strict_styles: bool,
+) -> Result<()> {
+    if trace_startup {
+        startup::begin();
+    }
+    if automation {
+        // stdout carries the JSON-RPC responses
+        console::use_stderr();
+    }
+    
+    let (script_file, work_dir) = resolve_entry(path)?;
+    
+    // Load config and bundle modules from work_dir (cwd)
+    let config = config::RasenConfig::load(&work_dir)?;
+    // A single-instance app hands this launch to the one running, if any
+    let second_instances = if config.app.single_instance {
+        match lifecycle::claim(&session::instance_dir(&work_dir)) {
+            Ok(Some(launches)) => Some(launches),
+            Ok(None) => {
+                eprintln!("Handed the launch over to the running instance");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Warning: can't keep to a single instance: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    tw_parser::set_theme(tw_parser::Theme::from_config(&config.theme).map_err(anyhow::Error::msg)?);
+    startup::set_budgets(config.startup.budgets.clone());
+    startup::mark(startup::Phase::Config);
+    
+    let options = js_runtime::RuntimeOptions {
+        hardened: hardened || config.security.hardened,
+        stack_size: config.runtime.stack_size,
+        memory_limit: config.runtime.memory_limit,
+        gc_threshold: config.runtime.gc_threshold,
+        time_limit: config.runtime.time_limit.map(|ms| Duration::from_millis(ms as u64)),
+        large_tree_warning: config.runtime.large_tree_warning.unwrap_or(descriptor::LARGE_TREE_WARNING),
+        slow_handler_warning: config.runtime.slow_handler_warning
+            .map(|ms| Duration::from_millis(ms as u64))
+            .unwrap_or(event_manager::SLOW_HANDLER_WARNING),
+        strict_styles,
+        script_dir: script_file.parent().map(PathBuf::from).unwrap_or_default(),
+    };
+    let mut launch = Launch { script_file, work_dir, options, plugins: Vec::new() };
+    let (script, loader) = launch.load(&config)?;
+    // The config's plugin libraries, loaded once they're verified; embedders
+    // can add their own `native_modules::NativePlugin`s
+    launch.plugins = plugins::load_all(&config.plugins, &launch.work_dir, launch.options.hardened)?;
+    
+    session::init(&config, &launch.work_dir);
+    storage::init(session::app_dir(&config, &launch.work_dir).join("storage.json"));
+    files::init(files::Sandbox::new(config.fs.allow.as_deref(), &launch.work_dir, launch.options.hardened));
+    process::init(launch.options.hardened);
+    shell::init(launch.options.hardened);
+    notifications::init(config.app.name.as_deref());
+    let mut contrast_changes = platform::watch_high_contrast();
 
     Application::new().run(move |cx: &mut App| {
-        // Initialize JS runtime with loaded modules
-        let runtime = Arc::new(js_runtime::JsRuntime::new());
-        
-        // Execute the script and get the root element
-        let root = runtime.execute_with_modules(&script, &loader)
-            .expect("Failed to execute script");
+        menus::init(cx, &config.app, &launch.work_dir);
+        zoom::init(cx);
+        undo::init(cx);
         
-        let event_manager = runtime.event_manager();
+        // Initialize JS runtime with loaded modules, and run the script to
+        // get the root element; its errors show in the window
+        let runtime = launch.runtime();
+        screen::update(cx);
+        let root = runtime.execute_with_modules(&script, &loader);
 
-        // Open window with the rendered element
-        let bounds = Bounds::centered(None, size(px(800.), px(600.)), cx);
-        cx.open_window(
+        // Open window with the rendered element, as `run` asked and where
+        // the previous session left off
+        let spec = runtime.window_spec();
+        let (bounds, display_id) = spec.placement(session::main_window_bounds(), cx);
+        let window = cx.open_window(
             WindowOptions {
-                window_bounds: Some(WindowBounds::Windowed(bounds)),
+                window_bounds: Some(bounds),
+                display_id,
+                titlebar: Some(spec.titlebar(config.app.name.as_deref())),
+                is_resizable: spec.resizable(),
+                window_min_size: spec.min_size(),
+                app_id: config.window.app_id.clone(),
+                window_decorations: config.window.decorations.map(|d| match d {
+                    config::Decorations::Client => WindowDecorations::Client,
+                    config::Decorations::Server => WindowDecorations::Server,
+                }),
                 ..Default::default()
             },
-            |_, cx| {
-                cx.new(|_| AppRoot { 
-                    element: root,
-                    runtime: runtime.clone(),
-                    event_manager: event_manager.clone(),
-                })
+            |window, cx| {
+                window.on_next_frame(|_, _| startup::finish());
+                cx.new(|cx| AppRoot::new(window::MAIN_WINDOW, None, root, launch.clone(), runtime.clone(), window, cx))
             },
         )
         .unwrap();
         
+        // `rasen.app.quit()` and the Quit action ask the main window's listeners
+        let mut quit_requests = lifecycle::listen_quit();
+        cx.spawn(async move |cx| {
+            while quit_requests.next().await.is_some() {
+                let _ = window.update(cx, |root, _, cx| root.request_quit(cx));
+            }
+        })
+        .detach();
+        
+        // The contrast setting is read off the UI thread; a change restyles
+        // `contrast-more:` variants in every window and reaches JS listeners
+        cx.spawn(async move |cx| {
+            while let Some(high_contrast) = contrast_changes.next().await {
+                let _ = window.update(cx, |root, _, cx| root.high_contrast_changed(high_contrast, cx));
+            }
+        })
+        .detach();
+        
+        if let Some(mut launches) = second_instances {
+            cx.spawn(async move |cx| {
+                while let Some(launch) = launches.next().await {
+                    let _ = window.update(cx, |root, window, cx| root.second_instance(&launch, window, cx));
+                }
+            })
+            .detach();
+        }
+        
+        if automation {
+            let mut requests = automation::listen();
+            cx.spawn(async move |cx| {
+                while let Some(request) = requests.next().await {
+                    let result = window
+                        .update(cx, |root, window, cx| root.automate(&request, window, cx))
+                        .unwrap_or_else(|e| Err(automation::RpcError::new(automation::SERVER_ERROR, e.to_string())));
+                    automation::respond(request.id, result);
+                }
+            })
+            .detach();
+        }
+        
         cx.activate(true);
     });
 
@@ -182,6 +416,18 @@ run(App)
     Ok(())
 }
 
+fn hash_bundle(path: &PathBuf) -> Result<()> {
+    let (script_file, work_dir) = resolve_entry(path)?;
+    
+    let config = config::RasenConfig::load(&work_dir)?;
+    let mut loader = module_loader::ModuleLoader::new(&work_dir);
+    loader.load_modules(&config)?;
+    
+    let manifest = integrity::Manifest::collect(&work_dir, &script_file, loader.files(), &config.plugins)?;
+    println!("{}", integrity::to_hex(&manifest.digest()));
+    Ok(())
+}
+
 fn build_project(_outdir: &str) -> Result<()> {
     // TODO: Bundle JS and assets
     println!("Build not implemented yet");
@@ -189,43 +435,919 @@ fn build_project(_outdir: &str) -> Result<()> {
 }
 
 struct AppRoot {
+    /// Which of the runtime's windows this is the root of
+    window_id: window::WindowId,
+    /// The main window's root, in windows opened with `rasen.window.open`
+    main: Option<WeakEntity<AppRoot>>,
+    /// The windows opened with `rasen.window.open`, in the main window
+    windows: HashMap<window::WindowId, WindowHandle<AppRoot>>,
+    /// Fullscreen state JS asked for, applied on the next frame
+    fullscreen: Option<bool>,
     element: elements::Element,
+    /// Whether `element` is a tree the script rendered, which dismissing
+    /// the error overlay goes back to
+    rendered: bool,
+    /// Script error shown over the window (see `overlay`)
+    error: Option<overlay::ScriptError>,
+    launch: Launch,
     runtime: Arc<js_runtime::JsRuntime>,
     event_manager: event_manager::EventManager,
+    transitions: animation::TransitionDriver,
+    /// Whether the window background is blurred for a root `backdrop-blur-*`
+    background_blurred: bool,
+    /// Drag state of sortable lists
+    sorting: sortable::SortTracker,
+    /// Colors and hex field drafts of color pickers
+    pickers: colorpicker::PickerTracker,
+    /// Open date picker calendars
+    calendars: datepicker::CalendarTracker,
+    /// Scrolling of trees and the one taking arrow keys
+    trees: tree::TreeTracker,
+    /// Item bounds and marquee drags of selectable containers
+    selections: selectable::SelectTracker,
+    pointers: pointer::PointerTracker,
+    /// Offscreen frames of shader elements
+    shaders: shader::ShaderTracker,
+    /// The root's focus handle, so keys and actions reach the window
+    focus_handle: FocusHandle,
+    /// Focus handles of the elements that take focus
+    focus: focus::FocusTracker,
+    /// Mouse events held back by throttled and debounced handlers
+    rate_limits: throttle::RateLimiter<(event_manager::HandlerId, pointer::MouseInfo)>,
+    /// Measurements of elements with a `ref`, shared with the runtime
+    layout: layout::Layout,
+    _subscriptions: Vec<Subscription>,
+    _tasks: Vec<Task<()>>,
+}
+
+impl AppRoot {
+    /// The root of window `window_id`, showing `root` or the error that
+    /// rendering it failed with; `main` is the main window's root for the
+    /// windows opened with `rasen.window.open`
+    fn new(
+        window_id: window::WindowId,
+        main: Option<WeakEntity<AppRoot>>,
+        root: Result<elements::Element>,
+        launch: Launch,
+        runtime: Arc<js_runtime::JsRuntime>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let (element, error) = match root {
+            Ok(root) => (root, None),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                (overlay::blank(), Some(overlay::ScriptError::from_error(&e)))
+            }
+        };
+        let focus_handle = cx.focus_handle();
+        window.focus(&focus_handle);
+        let this = cx.entity().downgrade();
+        let mut subscriptions = vec![
+            // Restyle `dark:` variants when the system theme changes, and read
+            // the contrast setting again, as it may have changed with it
+            cx.observe_window_appearance(window, |_, _, cx| {
+                platform::recheck_high_contrast();
+                cx.notify();
+            }),
+            // Keys count as user activity (pointer input is observed in render)
+            cx.observe_keystrokes(move |_, _, cx| {
+                let _ = this.update(cx, |this, cx| this.record_activity(cx));
+            }),
+        ];
+        // The runtime runs its tasks in the main window, and the other
+        // windows unmount their component as they close
+        let tasks = if window_id == window::MAIN_WINDOW {
+            subscriptions.push(cx.observe_window_bounds(window, |_, window, _| {
+                session::set_window_bounds(window::MAIN_WINDOW, window.window_bounds());
+            }));
+            subscriptions.push(cx.on_app_quit(|this, _| {
+                session::finish();
+                this.runtime.quit();
+                storage::flush();
+                async {}
+            }));
+            AppRoot::tasks(&runtime, cx)
+        } else {
+            subscriptions.push(cx.observe_window_bounds(window, move |_, window, _| {
+                session::set_window_bounds(window_id, window.window_bounds());
+            }));
+            subscriptions.push(cx.on_release(|this, _| {
+                session::close_window(this.window_id);
+                this.runtime.close_window(this.window_id);
+            }));
+            Vec::new()
+        };
+        AppRoot {
+            window_id,
+            main,
+            windows: HashMap::new(),
+            fullscreen: None,
+            rendered: error.is_none(),
+            element,
+            error,
+            launch,
+            event_manager: runtime.event_manager(),
+            layout: runtime.layout().for_window(window_id),
+            runtime,
+            transitions: animation::TransitionDriver::new(),
+            background_blurred: false,
+            sorting: sortable::SortTracker::new(),
+            pickers: colorpicker::PickerTracker::new(),
+            calendars: datepicker::CalendarTracker::new(),
+            trees: tree::TreeTracker::new(),
+            selections: selectable::SelectTracker::new(),
+            pointers: pointer::PointerTracker::new(),
+            shaders: shader::ShaderTracker::new(),
+            focus_handle,
+            focus: focus::FocusTracker::new(),
+            rate_limits: throttle::RateLimiter::new(),
+            _subscriptions: subscriptions,
+            _tasks: tasks,
+        }
+    }
+    
+    /// Invoke a JS handler, then schedule a re-render if it changed state
+    fn dispatch(&mut self, handler_id: event_manager::HandlerId, cx: &mut Context<Self>) {
+        self.dispatch_with(handler_id, (), cx);
+    }
+    
+    /// Invoke a JS handler with arguments, then schedule a re-render if it
+    /// changed state
+    fn dispatch_with<A>(&mut self, handler_id: event_manager::HandlerId, args: A, cx: &mut Context<Self>)
+    where
+        A: for<'js> rquickjs::function::IntoArgs<'js>,
+    {
+        self.dispatch_for_text(handler_id, args, cx);
+    }
+    
+    /// Like `dispatch_with`; returns the string the handler returned, if any
+    fn dispatch_for_text<A>(&mut self, handler_id: event_manager::HandlerId, args: A, cx: &mut Context<Self>) -> Option<String>
+    where
+        A: for<'js> rquickjs::function::IntoArgs<'js>,
+    {
+        // Only changes the handler makes count
+        self.event_manager.take_state_change();
+        // Invoke the JS handler (this modifies ref values); show an error
+        // no `onError` handler took over the last good tree
+        let text = match self.runtime.invoke_handler(self.window_id, handler_id, args) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("Error: handler {} failed: {:#}", handler_id, e);
+                self.error = Some(overlay::ScriptError::from_error(&e));
+                cx.notify();
+                None
+            }
+        };
+        // Run what an async handler can do right away, up to its first wait
+        self.runtime.run_jobs();
+        // Handlers that changed nothing, e.g. most mouse moves, don't re-render
+        if self.event_manager.take_state_change() {
+            self.refresh(cx);
+        }
+        text
+    }
+    
+    /// Act on a mouse event offered to a rate-limited handler: invoke it, or
+    /// come back when the event held back is due
+    fn dispatch_limited(
+        &mut self,
+        key: String,
+        offer: throttle::Offer<(event_manager::HandlerId, pointer::MouseInfo)>,
+        cx: &mut Context<Self>,
+    ) {
+        match offer {
+            throttle::Offer::Call((handler_id, event)) => self.dispatch_with(handler_id, (event,), cx),
+            throttle::Offer::Schedule(delay) => {
+                cx.spawn(async move |this, cx| {
+                    cx.background_executor().timer(delay).await;
+                    let _ = this.update(cx, |this, cx| {
+                        let offer = this.rate_limits.fire(&key, Instant::now());
+                        this.dispatch_limited(key, offer, cx);
+                    });
+                })
+                .detach();
+            }
+            throttle::Offer::Queued => {}
+        }
+    }
+    
+    /// Re-render with the updated state on the next frame
+    ///
+    /// Handlers, timers and callbacks running before the frame share one
+    /// re-render (see `render`).
+    fn refresh(&mut self, cx: &mut Context<Self>) {
+        self.event_manager.request_render();
+        cx.notify();
+    }
+    
+    /// Re-render now if one is pending, before looking up handlers in the tree
+    fn flush_render(&mut self) {
+        if self.event_manager.take_render_request(self.window_id) {
+            self.re_render();
+        }
+    }
+    
+    /// Re-render into the tree on screen, patching what changed, or show the
+    /// error over the last good tree
+    fn re_render(&mut self) {
+        // This render covers what was requested so far
+        self.event_manager.take_render_request(self.window_id);
+        match self.runtime.re_render_into(self.window_id, &mut self.element) {
+            Ok(_) => {
+                self.rendered = true;
+                self.error = None;
+            }
+            Err(e) => {
+                eprintln!("Error: re-render failed: {:#}", e);
+                self.error = Some(overlay::ScriptError::from_error(&e));
+            }
+        }
+    }
+    
+    /// Run the script again in a fresh runtime, from the error overlay
+    ///
+    /// All JS state starts over; native state tied to the old tree is reset.
+    /// Windows opened with `rasen.window.open` close, and reload the main
+    /// window.
+    fn reload(&mut self, cx: &mut Context<Self>) {
+        if let Some(main) = self.main.clone() {
+            // After this update, as the main window closes this one
+            cx.defer(move |cx| {
+                let _ = main.update(cx, |main, cx| main.reload(cx));
+            });
+            return;
+        }
+        let (runtime, result) = match self.launch.reload() {
+            Ok(started) => started,
+            // Bundling or compiling failed: nothing ran
+            Err(e) => {
+                eprintln!("Error: reload failed: {:#}", e);
+                self.error = Some(overlay::ScriptError::from_error(&e));
+                cx.notify();
+                return;
+            }
+        };
+        match result {
+            Ok(element) => {
+                self.element = element;
+                self.rendered = true;
+                self.error = None;
+            }
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                self.element = overlay::blank();
+                self.rendered = false;
+                self.error = Some(overlay::ScriptError::from_error(&e));
+            }
+        }
+        for (_, handle) in self.windows.drain() {
+            let _ = handle.update(cx, |_, window, _| window.remove_window());
+        }
+        self.event_manager = runtime.event_manager();
+        self.layout = runtime.layout().for_window(self.window_id);
+        self._tasks = Self::tasks(&runtime, cx);
+        self.runtime = runtime;
+        self.transitions = animation::TransitionDriver::new();
+        self.sorting = sortable::SortTracker::new();
+        self.pickers = colorpicker::PickerTracker::new();
+        self.calendars = datepicker::CalendarTracker::new();
+        self.trees = tree::TreeTracker::new();
+        self.selections = selectable::SelectTracker::new();
+        self.pointers = pointer::PointerTracker::new();
+        self.shaders = shader::ShaderTracker::new();
+        self.rate_limits = throttle::RateLimiter::new();
+        cx.notify();
+    }
+    
+    /// Quit once the `before-quit` listeners agree, for the Quit menu item
+    /// and `rasen.app.quit()`
+    fn request_quit(&mut self, cx: &mut Context<Self>) {
+        let Some(decision) = self.runtime.before_quit() else {
+            return;
+        };
+        // Listeners may have changed state, e.g. to show they're saving
+        self.refresh(cx);
+        cx.spawn(async move |_, cx| {
+            let timeout = cx.background_executor().timer(lifecycle::QUIT_TIMEOUT);
+            let quit = match future::select(decision, timeout).await {
+                // A runtime that reloaded meanwhile never answers
+                Either::Left((decision, _)) => decision.unwrap_or(false),
+                Either::Right(_) => {
+                    eprintln!(
+                        "Warning: before-quit listeners took over {} s; quitting",
+                        lifecycle::QUIT_TIMEOUT.as_secs()
+                    );
+                    true
+                }
+            };
+            lifecycle::finish_quit();
+            if quit {
+                let _ = cx.update(|cx| cx.quit());
+            }
+        })
+        .detach();
+    }
+    
+    /// Pass a launch handed over by a later instance to `second-instance`
+    /// listeners, and bring the app to the front
+    fn second_instance(&mut self, launch: &lifecycle::SecondInstance, window: &mut Window, cx: &mut Context<Self>) {
+        self.runtime.deliver_second_instance(launch);
+        window.activate_window();
+        cx.activate(true);
+        self.refresh(cx);
+    }
+    
+    /// Tell JS the contrast setting changed, and restyle every window
+    fn high_contrast_changed(&mut self, high_contrast: bool, cx: &mut Context<Self>) {
+        self.runtime.deliver_high_contrast(high_contrast);
+        self.refresh(cx);
+        cx.refresh_windows();
+    }
+    
+    /// Hide the error overlay, back to the last tree that rendered
+    fn dismiss_error(&mut self, cx: &mut Context<Self>) {
+        self.error = None;
+        cx.notify();
+    }
+    
+    /// Call a handler of element `id` with a string, for keyboard input
+    /// handled natively
+    fn call<F>(&mut self, id: &str, handler: F, value: String, cx: &mut Context<Self>)
+    where
+        F: Fn(&elements::EventHandlers) -> Option<event_manager::HandlerId>,
+    {
+        self.flush_render();
+        let handler = sortable::find(&self.element, id).and_then(|element| match element {
+            elements::Element::Div(div) => handler(&div.handlers),
+            elements::Element::Text(_) => None,
+        });
+        match handler {
+            Some(handler_id) => self.dispatch_with(handler_id, (value,), cx),
+            None => cx.notify(),
+        }
+    }
+    
+    /// Run the innermost `keymap` binding around the focused element for a
+    /// key press, if any
+    fn key_down(&mut self, event: &KeyDownEvent, window: &Window, cx: &mut Context<Self>) {
+        // cmd/ctrl-R reloads while the error overlay is up
+        if self.error.is_some() {
+            let modifiers = &event.keystroke.modifiers;
+            if event.keystroke.key == "r" && (modifiers.platform || modifiers.control) && !modifiers.alt && !modifiers.shift {
+                cx.stop_propagation();
+                self.reload(cx);
+            }
+            return;
+        }
+        // A color picker's hex field being edited, an open calendar or a
+        // clicked tree take keys before keymaps
+        if let Some(edit) = self.pickers.edit_key(&event.keystroke) {
+            cx.stop_propagation();
+            match edit {
+                colorpicker::Edit::Updated => cx.notify(),
+                colorpicker::Edit::Commit { picker, hex } => self.call(&picker, |h| h.on_change, hex, cx),
+            }
+            return;
+        }
+        if let Some(key) = self.calendars.key(&event.keystroke) {
+            cx.stop_propagation();
+            match key {
+                datepicker::CalendarKey::Updated => cx.notify(),
+                datepicker::CalendarKey::Commit { picker, value } => self.call(&picker, |h| h.on_change, value, cx),
+            }
+            return;
+        }
+        if let Some((tree, action)) = self.trees.key(&event.keystroke) {
+            cx.stop_propagation();
+            match action {
+                tree::TreeAction::Select(node) => self.call(&tree, |h| h.on_select, node, cx),
+                tree::TreeAction::Toggle(node) => self.call(&tree, |h| h.on_toggle, node, cx),
+            }
+            return;
+        }
+        self.flush_render();
+        let focused = self.focus.focused(window);
+        let Some(binding) = keymap::resolve(&self.element, focused.as_deref(), &event.keystroke) else {
+            return;
+        };
+        let handler = binding.handler;
+        cx.stop_propagation();
+        if let Some(handler_id) = handler {
+            self.dispatch(handler_id, cx);
+        }
+    }
+    
+    /// Run an Edit menu action on the innermost element around the focused
+    /// one that handles it, through the clipboard
+    fn edit(&mut self, edit: menus::Edit, window: &Window, cx: &mut Context<Self>) {
+        if self.error.is_some() {
+            return;
+        }
+        self.flush_render();
+        let focused = self.focus.focused(window);
+        let handler = keymap::ancestors(&self.element, focused.as_deref()).into_iter().rev().find_map(|element| match element {
+            elements::Element::Div(div) => edit.handler(&div.handlers),
+            elements::Element::Text(_) => None,
+        });
+        let Some(handler_id) = handler else {
+            return;
+        };
+        match edit {
+            menus::Edit::Cut | menus::Edit::Copy => {
+                if let Some(text) = self.dispatch_for_text(handler_id, (), cx) {
+                    cx.write_to_clipboard(ClipboardItem::new_string(text));
+                }
+            }
+            menus::Edit::Paste => {
+                let text = cx.read_from_clipboard().and_then(|item| item.text()).unwrap_or_default();
+                self.dispatch_with(handler_id, (text,), cx);
+            }
+            menus::Edit::SelectAll => self.dispatch(handler_id, cx),
+        }
+    }
+    
+    /// Note user input, waking `onActive` watchers if the user was idle
+    fn record_activity(&mut self, cx: &mut Context<Self>) {
+        let ids = activity::record_input();
+        if !ids.is_empty() {
+            self.runtime.with_context(|ctx| ctx.with(|ctx| activity::fire(&ctx, &ids)));
+            self.refresh(cx);
+        }
+    }
+    
+    /// Walk the app's undo stack from the Edit menu or shortcuts, or the
+    /// draft of a color picker's hex field while it's edited
+    fn undo(&mut self, redo: bool, cx: &mut Context<Self>) {
+        if self.pickers.undo_draft(redo) {
+            cx.notify();
+            return;
+        }
+        let changed = self.runtime.with_context(|ctx| {
+            ctx.with(|ctx| if redo { undo::redo(&ctx) } else { undo::undo(&ctx) })
+        });
+        if changed {
+            self.refresh(cx);
+        }
+    }
+    
+    /// Tasks running a runtime's timers, callbacks and queues
+    fn tasks(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Vec<Task<()>> {
+        vec![
+            AppRoot::poll_activity(cx),
+            AppRoot::listen(runtime, Wakeup::Bus, JsRuntime::deliver_bus, cx),
+            AppRoot::run_timers(runtime, cx),
+            AppRoot::wake_for_frames(runtime, cx),
+            AppRoot::schedule_renders(runtime, cx),
+            AppRoot::listen(runtime, Wakeup::Processes, JsRuntime::deliver_processes, cx),
+            AppRoot::listen(runtime, Wakeup::Notifications, JsRuntime::deliver_notifications, cx),
+            AppRoot::listen(runtime, Wakeup::Dialogs, JsRuntime::deliver_dialogs, cx),
+            AppRoot::listen(runtime, Wakeup::Workers, JsRuntime::deliver_workers, cx),
+            AppRoot::run_files(runtime, cx),
+            AppRoot::listen_windows(runtime, cx),
+        ]
+    }
+    
+    /// Run a runtime's callbacks with `deliver` whenever `wakeup` fires,
+    /// re-rendering if any ran: bus listeners, process output, ...
+    fn listen(
+        runtime: &JsRuntime,
+        wakeup: Wakeup,
+        deliver: fn(&JsRuntime) -> bool,
+        cx: &mut Context<Self>,
+    ) -> Task<()> {
+        let wakeups = runtime.take_wakeups(wakeup);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            while wakeups.next().await.is_some() {
+                let result = this.update(cx, |this, cx| {
+                    if deliver(&this.runtime) {
+                        this.refresh(cx);
+                    }
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+    
+    /// Open and close the windows JS asks for with `rasen.window`
+    fn listen_windows(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Task<()> {
+        let wakeups = runtime.take_wakeups(Wakeup::Windows);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            while wakeups.next().await.is_some() {
+                let result = this.update(cx, |this, cx| {
+                    for request in this.runtime.take_window_requests() {
+                        match request {
+                            window::WindowRequest::Open(id, spec) => this.open_window(id, spec, cx),
+                            window::WindowRequest::Close(id) => {
+                                if let Some(handle) = this.windows.remove(&id) {
+                                    let _ = handle.update(cx, |_, window, _| window.remove_window());
+                                }
+                            }
+                            window::WindowRequest::Fullscreen(id, on) => this.set_fullscreen(id, on, cx),
+                        }
+                    }
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+    
+    /// Enter or leave fullscreen in window `id` on its next frame
+    fn set_fullscreen(&mut self, id: window::WindowId, on: bool, cx: &mut Context<Self>) {
+        if id == self.window_id {
+            self.fullscreen = Some(on);
+            cx.notify();
+        } else if let Some(handle) = self.windows.get(&id) {
+            let _ = handle.update(cx, |root, _, cx| root.set_fullscreen(id, on, cx));
+        }
+    }
+    
+    /// Open window `id` of `rasen.window.open`, rendering its component
+    fn open_window(&mut self, id: window::WindowId, spec: window::WindowSpec, cx: &mut Context<Self>) {
+        self.runtime.open_window(id);
+        let root = self.runtime.render_window(id);
+        let (main, launch, runtime) = (cx.entity().downgrade(), self.launch.clone(), self.runtime.clone());
+        let saved = spec.name.as_deref().and_then(|name| session::open_window(id, name, spec.route.clone()));
+        let (bounds, display_id) = spec.placement(saved, cx);
+        let options = WindowOptions {
+            window_bounds: Some(bounds),
+            display_id,
+            titlebar: Some(spec.titlebar(None)),
+            is_resizable: spec.resizable(),
+            window_min_size: spec.min_size(),
+            ..Default::default()
+        };
+        let opened = cx.open_window(options, |window, cx| {
+            cx.new(|cx| AppRoot::new(id, Some(main), root, launch, runtime, window, cx))
+        });
+        match opened {
+            Ok(handle) => {
+                self.windows.insert(id, handle);
+            }
+            Err(e) => {
+                eprintln!("Error: opening window {} failed: {:#}", id, e);
+                self.runtime.close_window(id);
+            }
+        }
+    }
+    
+    /// Periodically fire `onIdle` watchers whose timeout has elapsed
+    fn poll_activity(cx: &mut Context<Self>) -> Task<()> {
+        cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(Duration::from_millis(250)).await;
+            let result = this.update(cx, |this, cx| {
+                let ids = activity::due();
+                if !ids.is_empty() {
+                    this.runtime.with_context(|ctx| ctx.with(|ctx| activity::fire(&ctx, &ids)));
+                    this.refresh(cx);
+                }
+            });
+            if result.is_err() {
+                break;
+            }
+        })
+    }
+    
+    /// Run `rasen.fs` operations on the background executor as they're queued,
+    /// settling their promises as they finish
+    fn run_files(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Task<()> {
+        let wakeups = runtime.take_wakeups(Wakeup::Files);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            while wakeups.next().await.is_some() {
+                let Ok(requests) = this.update(cx, |this, _| this.runtime.take_file_requests()) else {
+                    break;
+                };
+                for request in requests {
+                    let work = cx.background_executor().spawn(async move { request.run() });
+                    let this = this.clone();
+                    cx.spawn(async move |cx| {
+                        let (id, result) = work.await;
+                        let _ = this.update(cx, |this, cx| {
+                            this.runtime.settle_file(id, result);
+                            this.refresh(cx);
+                        });
+                    })
+                    .detach();
+                }
+            }
+        })
+    }
+    
+    /// Fire `setTimeout`/`setInterval` callbacks as they come due
+    fn run_timers(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Task<()> {
+        let wakeups = runtime.take_wakeups(Wakeup::Timers);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            loop {
+                let Ok(next) = this.update(cx, |this, _| this.runtime.next_timer()) else {
+                    break;
+                };
+                // Sleep until the next timer is due, or a new one is added
+                match next {
+                    Some(due) => {
+                        let timer = cx.background_executor().timer(due.saturating_duration_since(Instant::now()));
+                        if let Either::Right((None, _)) = future::select(timer, wakeups.next()).await {
+                            break;
+                        }
+                    }
+                    None => {
+                        if wakeups.next().await.is_none() {
+                            break;
+                        }
+                    }
+                }
+                let result = this.update(cx, |this, cx| {
+                    if this.runtime.fire_timers() {
+                        this.refresh(cx);
+                    }
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+    
+    /// Schedule a render when `requestAnimationFrame` is called, which runs
+    /// the callbacks (see `render`)
+    fn wake_for_frames(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Task<()> {
+        let wakeups = runtime.take_wakeups(Wakeup::Frames);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            while wakeups.next().await.is_some() {
+                if this.update(cx, |_, cx| cx.notify()).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+    
+    /// Schedule a frame in every window when a render is requested, whatever
+    /// requested it: a ref written from a promise, `requestRender()`, ...
+    /// (see `render`)
+    fn schedule_renders(runtime: &js_runtime::JsRuntime, cx: &mut Context<Self>) -> Task<()> {
+        let wakeups = runtime.take_wakeups(Wakeup::Render);
+        cx.spawn(async move |this, cx| {
+            let Some(mut wakeups) = wakeups else {
+                return;
+            };
+            while wakeups.next().await.is_some() {
+                if this.update(cx, |_, cx| cx.refresh_windows()).is_err() {
+                    break;
+                }
+            }
+        })
+    }
+    
+    /// Handle an automation request (see `automation`)
+    fn automate(
+        &mut self,
+        request: &automation::Request,
+        window: &Window,
+        cx: &mut Context<Self>,
+    ) -> Result<serde_json::Value, automation::RpcError> {
+        use automation::{RpcError, INVALID_PARAMS, METHOD_NOT_FOUND};
+        
+        let param = |name: &str| {
+            request.params.get(name).ok_or_else(|| {
+                RpcError::new(INVALID_PARAMS, format!("Missing parameter '{}'", name))
+            })
+        };
+        
+        // Requests see the tree as of the last change
+        self.flush_render();
+        match request.method.as_str() {
+            "dumpTree" => Ok(automation::dump(&self.element)),
+            "click" => {
+                let test_id = param("testId")?.as_str().unwrap_or_default();
+                let handler_id = match automation::find(&self.element, test_id) {
+                    Some(elements::Element::Div(div)) => div.handlers.on_click,
+                    Some(elements::Element::Text(_)) => None,
+                    None => {
+                        return Err(RpcError::new(INVALID_PARAMS, format!("No element with testId '{}'", test_id)));
+                    }
+                };
+                let handler_id = handler_id.ok_or_else(|| {
+                    RpcError::new(INVALID_PARAMS, format!("Element '{}' has no click handler", test_id))
+                })?;
+                self.dispatch(handler_id, cx);
+                Ok(serde_json::Value::Null)
+            }
+            "setRef" => {
+                let name = param("name")?.as_str().unwrap_or_default().to_string();
+                let value = param("value")?;
+                self.runtime.with_context(|ctx| {
+                    ctx.with(|ctx| automation::set_ref(&ctx, &name, value))
+                })?;
+                self.refresh(cx);
+                Ok(serde_json::Value::Null)
+            }
+            "logs" => {
+                let since = request.params.get("since").and_then(|since| since.as_u64()).unwrap_or(0);
+                Ok(console::history(since))
+            }
+            "handlerStats" => Ok(automation::handler_stats(&self.event_manager.handler_stats())),
+            "screenshot" => {
+                let path = param("path")?
+                    .as_str()
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "'path' must be a string"))?;
+                automation::screenshot(window.bounds(), window.scale_factor(), std::path::Path::new(path))?;
+                Ok(serde_json::Value::Null)
+            }
+            method => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        }
+    }
 }
 
 
 impl Render for AppRoot {
-    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        let runtime = self.runtime.clone();
-        let event_manager = self.event_manager.clone();
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        // `requestAnimationFrame` callbacks run right before the main
+        // window's frame, so it shows their changes, as do renders requested
+        // since the last one
+        let frames = self.window_id == window::MAIN_WINDOW && self.runtime.fire_frames();
+        if frames || self.event_manager.take_render_request(self.window_id) {
+            self.re_render();
+        }
+        
+        if let Some(fullscreen) = self.fullscreen.take() {
+            if window.is_fullscreen() != fullscreen {
+                window.toggle_fullscreen();
+            }
+        }
+        screen::record_window(window, cx);
+        
+        // Elements taking focus keep their handles; when the focused one is
+        // gone, the root takes keys again
+        self.focus.update(&self.element, cx);
+        if window.focused(cx).is_none() {
+            window.focus(&self.focus_handle);
+        }
+        
+        let focused = self.focus.focused(window);
         let entity = cx.entity().clone();
+        let zoom = zoom::get();
+        window.set_rem_size(px(16.0 * zoom));
+        
+        let settings = sortable::PreviewSettings {
+            // Zoom lays the tree out for a proportionally smaller viewport
+            viewport_width: px(f32::from(window.viewport_size().width) / zoom),
+            viewport_height: px(f32::from(window.viewport_size().height) / zoom),
+            zoom,
+            dark: matches!(
+                window.appearance(),
+                WindowAppearance::Dark | WindowAppearance::VibrantDark
+            ),
+            high_contrast: platform::prefers_high_contrast(),
+        };
+        // A drag that ended outside any list leaves no insertion line behind
+        if !cx.has_active_drag() {
+            self.sorting.clear_targets();
+        }
         
-        // Create render context with click handler factory
+        // Create render context with handler factories
         let render_ctx = elements::RenderContext {
             click_handler: &|handler_id: event_manager::HandlerId| {
-                let runtime = runtime.clone();
-                let event_manager = event_manager.clone();
                 let entity = entity.clone();
                 
                 Box::new(move |_event: &ClickEvent, _window: &mut Window, cx: &mut App| {
-                    // Invoke the JS handler (this modifies ref values)
-                    runtime.with_context(|ctx| {
-                        event_manager.invoke_handler(handler_id, ctx);
+                    entity.update(cx, |this: &mut AppRoot, cx| this.dispatch(handler_id, cx));
+                })
+            },
+            reorder_handler: &|handler_id: event_manager::HandlerId| {
+                let entity = entity.clone();
+                
+                Box::new(move |from: usize, to: usize, _window: &mut Window, cx: &mut App| {
+                    entity.update(cx, |this: &mut AppRoot, cx| {
+                        this.dispatch_with(handler_id, (from as u32, to as u32), cx)
                     });
-                    
-                    // Re-render: call App() again to get fresh UI with updated state
-                    // The ref values persist because they are in closures
-                    if let Ok(new_element) = runtime.re_render() {
-                        let _ = entity.update(cx, |this: &mut AppRoot, cx| {
-                            this.element = new_element;
-                            cx.notify();
-                        });
-                    }
                 })
             },
+            // The preview copies the item when the drag starts
+            preview_handler: &|item: String, list: String| {
+                let entity = entity.clone();
+                
+                Box::new(move |dragged: &sortable::DraggedItem, _window: &mut Window, cx: &mut App| {
+                    let (element, size) = {
+                        let root = entity.read(cx);
+                        (sortable::find(&root.element, &item).cloned(), root.sorting.item_size(&list, dragged.from))
+                    };
+                    cx.new(|_| sortable::DragPreview::new(element, size, settings))
+                })
+            },
+            value_handler: &|handler_id: event_manager::HandlerId| {
+                let entity = entity.clone();
+                
+                Box::new(move |value: String, _window: &mut Window, cx: &mut App| {
+                    entity.update(cx, |this: &mut AppRoot, cx| this.dispatch_with(handler_id, (value,), cx));
+                })
+            },
+            selection_handler: &|handler_id: event_manager::HandlerId| {
+                let entity = entity.clone();
+                
+                Box::new(move |keys: Vec<String>, _window: &mut Window, cx: &mut App| {
+                    entity.update(cx, |this: &mut AppRoot, cx| this.dispatch_with(handler_id, (keys,), cx));
+                })
+            },
+            mouse_handler: &|handler_id: event_manager::HandlerId, limit: Option<throttle::Limit>| {
+                let entity = entity.clone();
+                
+                Box::new(move |event: pointer::MouseInfo, _window: &mut Window, cx: &mut App| {
+                    entity.update(cx, |this: &mut AppRoot, cx| match &limit {
+                        Some(limit) => {
+                            let offer = this.rate_limits.offer(limit, (handler_id, event), Instant::now());
+                            this.dispatch_limited(limit.key.clone(), offer, cx);
+                        }
+                        None => this.dispatch_with(handler_id, (event,), cx),
+                    });
+                })
+            },
+            viewport_width: settings.viewport_width,
+            viewport_height: settings.viewport_height,
+            zoom,
+            dark: settings.dark,
+            high_contrast: settings.high_contrast,
+            transitions: &self.transitions,
+            sorting: &self.sorting,
+            sort_item: None,
+            pickers: &self.pickers,
+            calendars: &self.calendars,
+            trees: &self.trees,
+            selections: &self.selections,
+            pointers: &self.pointers,
+            shaders: &self.shaders,
+            focus: &self.focus,
+            focused: focused.as_deref(),
+            text_transform: None,
+            layout: &self.layout,
         };
         
-        self.element.render_with_events(&render_ctx)
+        // GPUI can only blur behind the whole window, so that's where a root
+        // `backdrop-blur-*` goes; a translucent root background shows it
+        let blurred = self.element.styles().backdrop_blur.is_some_and(|radius| radius > px(0.0));
+        if blurred != self.background_blurred {
+            self.background_blurred = blurred;
+            window.set_background_appearance(if blurred {
+                WindowBackgroundAppearance::Blurred
+            } else {
+                WindowBackgroundAppearance::Opaque
+            });
+        }
+        
+        let element = self.element.render_with_events(&render_ctx);
+        let capture_layer = pointer::capture_layer(&render_ctx);
+        let error_overlay = self.error.as_ref().map(|error| {
+            let reload = cx.listener(|this, _: &ClickEvent, _, cx| this.reload(cx));
+            let dismiss = self.rendered.then(|| cx.listener(|this, _: &ClickEvent, _, cx| this.dismiss_error(cx)));
+            overlay::render(error, reload, dismiss)
+        });
+        let wants_frame = self.window_id == window::MAIN_WINDOW && self.runtime.wants_frame();
+        if self.transitions.finish_frame() || wants_frame {
+            window.request_animation_frame();
+        }
+        self.sorting.finish_frame();
+        self.pickers.finish_frame();
+        self.calendars.finish_frame();
+        self.trees.finish_frame();
+        self.selections.finish_frame();
+        self.pointers.finish_frame();
+        self.shaders.finish_frame(window, cx);
+        self.layout.finish_frame();
+        
+        // Full-window wrapper observing pointer input for activity detection
+        // and handling app-wide actions
+        div()
+            .size_full()
+            .on_mouse_move(cx.listener(|this, _: &MouseMoveEvent, _, cx| this.record_activity(cx)))
+            .on_any_mouse_down(cx.listener(|this, _: &MouseDownEvent, _, cx| this.record_activity(cx)))
+            .on_scroll_wheel(cx.listener(|this, _: &ScrollWheelEvent, _, cx| this.record_activity(cx)))
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| this.key_down(event, window, cx)))
+            .on_action(cx.listener(|this, _: &menus::Undo, _, cx| this.undo(false, cx)))
+            .on_action(cx.listener(|this, _: &menus::Redo, _, cx| this.undo(true, cx)))
+            .on_action(cx.listener(|this, _: &menus::Cut, window, cx| this.edit(menus::Edit::Cut, window, cx)))
+            .on_action(cx.listener(|this, _: &menus::Copy, window, cx| this.edit(menus::Edit::Copy, window, cx)))
+            .on_action(cx.listener(|this, _: &menus::Paste, window, cx| this.edit(menus::Edit::Paste, window, cx)))
+            .on_action(cx.listener(|this, _: &menus::SelectAll, window, cx| this.edit(menus::Edit::SelectAll, window, cx)))
+            .child(element)
+            .child(capture_layer)
+            .children(error_overlay)
     }
 }
@@ -0,0 +1,200 @@
+//! Default application menus and key bindings (macOS)
+//!
+//! Packaged macOS apps are expected to have the standard app, Edit and Window
+//! menus. Cut, Copy, Paste and Select All (also Ctrl+X / C / V / A on other
+//! platforms) go to the focused element, or the innermost one around it with
+//! an `onCut`, `onCopy`, `onPaste` or `onSelectAll` handler (see [`Edit`]).
+//! The About panel shows the app's `icon`, name and version.
+
+use gpui::*;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::config::AppConfig;
+use crate::elements::EventHandlers;
+use crate::event_manager::HandlerId;
+use crate::lifecycle;
+use crate::platform::{self, Capability};
+
+actions!(
+    rasen,
+    [
+        About,
+        Hide,
+        HideOthers,
+        ShowAll,
+        Quit,
+        Undo,
+        Redo,
+        Cut,
+        Copy,
+        Paste,
+        SelectAll,
+        Minimize,
+        Zoom,
+    ]
+);
+
+/// An Edit menu action on the focused element
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    /// The handler returns the text to put on the clipboard, and removes it
+    Cut,
+    /// The handler returns the text to put on the clipboard
+    Copy,
+    /// The handler is called with the clipboard's text
+    Paste,
+    SelectAll,
+}
+
+impl Edit {
+    /// The element's handler for this action, if it has one
+    pub fn handler(self, handlers: &EventHandlers) -> Option<HandlerId> {
+        match self {
+            Edit::Cut => handlers.on_cut,
+            Edit::Copy => handlers.on_copy,
+            Edit::Paste => handlers.on_paste,
+            Edit::SelectAll => handlers.on_select_all,
+        }
+    }
+}
+
+/// Install the default menus, key bindings and app-level action handlers;
+/// a relative `icon` is found in `work_dir`
+pub fn init(cx: &mut App, app: &AppConfig, work_dir: &Path) {
+    if !platform::supports(Capability::MenuBar) {
+        // The Edit menu binds these on macOS
+        cx.bind_keys([
+            KeyBinding::new("ctrl-x", Cut, None),
+            KeyBinding::new("ctrl-c", Copy, None),
+            KeyBinding::new("ctrl-v", Paste, None),
+            KeyBinding::new("ctrl-a", SelectAll, None),
+        ]);
+        return;
+    }
+    
+    let name = app.name.clone().unwrap_or_else(|| "Rasen App".to_string());
+    let about = AboutPanel {
+        icon: app.icon.as_ref().map(|icon| work_dir.join(icon)),
+        name: name.clone(),
+        version: app.version.clone(),
+    };
+    let open: Rc<RefCell<Option<WindowHandle<AboutPanel>>>> = Rc::default();
+    cx.on_action(move |_: &About, cx| {
+        // Bring the panel forward if it's already open
+        let handle = *open.borrow();
+        let shown = handle.is_some_and(|handle| handle.update(cx, |_, window, _| window.activate_window()).is_ok());
+        if !shown {
+            *open.borrow_mut() = open_about(about.clone(), cx);
+        }
+    });
+    cx.on_action(|_: &Hide, cx| cx.hide());
+    cx.on_action(|_: &HideOthers, cx| cx.hide_other_apps());
+    cx.on_action(|_: &ShowAll, cx| cx.unhide_other_apps());
+    // `before-quit` listeners decide first (see `lifecycle`), whichever
+    // window has focus; before the app listens, there are none to ask
+    cx.on_action(|_: &Quit, cx| {
+        if !lifecycle::request_quit() {
+            cx.quit();
+        }
+    });
+    cx.on_action(|_: &Minimize, cx| with_active_window(cx, |window, _| window.minimize_window()));
+    cx.on_action(|_: &Zoom, cx| with_active_window(cx, |window, _| window.zoom_window()));
+    
+    cx.bind_keys([
+        KeyBinding::new("cmd-h", Hide, None),
+        KeyBinding::new("alt-cmd-h", HideOthers, None),
+        KeyBinding::new("cmd-q", Quit, None),
+        KeyBinding::new("cmd-z", Undo, None),
+        KeyBinding::new("shift-cmd-z", Redo, None),
+        KeyBinding::new("cmd-x", Cut, None),
+        KeyBinding::new("cmd-c", Copy, None),
+        KeyBinding::new("cmd-v", Paste, None),
+        KeyBinding::new("cmd-a", SelectAll, None),
+        KeyBinding::new("cmd-m", Minimize, None),
+    ]);
+    
+    cx.set_menus(vec![
+        Menu {
+            name: name.clone().into(),
+            items: vec![
+                MenuItem::action(format!("About {}", name), About),
+                MenuItem::separator(),
+                MenuItem::action(format!("Hide {}", name), Hide),
+                MenuItem::action("Hide Others", HideOthers),
+                MenuItem::action("Show All", ShowAll),
+                MenuItem::separator(),
+                MenuItem::action(format!("Quit {}", name), Quit),
+            ],
+        },
+        Menu {
+            name: "Edit".into(),
+            items: vec![
+                MenuItem::os_action("Undo", Undo, OsAction::Undo),
+                MenuItem::os_action("Redo", Redo, OsAction::Redo),
+                MenuItem::separator(),
+                MenuItem::os_action("Cut", Cut, OsAction::Cut),
+                MenuItem::os_action("Copy", Copy, OsAction::Copy),
+                MenuItem::os_action("Paste", Paste, OsAction::Paste),
+                MenuItem::os_action("Select All", SelectAll, OsAction::SelectAll),
+            ],
+        },
+        Menu {
+            name: "Window".into(),
+            items: vec![
+                MenuItem::action("Minimize", Minimize),
+                MenuItem::action("Zoom", Zoom),
+            ],
+        },
+    ]);
+}
+
+/// The About panel: the app's icon, name and version
+#[derive(Clone)]
+struct AboutPanel {
+    icon: Option<PathBuf>,
+    name: String,
+    version: Option<String>,
+}
+
+impl Render for AboutPanel {
+    fn render(&mut self, window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        let dark = matches!(window.appearance(), WindowAppearance::Dark | WindowAppearance::VibrantDark);
+        let (background, foreground) = if dark { (rgb(0x262626), rgb(0xf5f5f5)) } else { (rgb(0xf5f5f5), rgb(0x171717)) };
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .items_center()
+            .justify_center()
+            .gap_2()
+            .bg(background)
+            .text_color(foreground)
+            .children(self.icon.clone().map(|icon| img(icon).size(px(64.0)).object_fit(ObjectFit::Contain)))
+            .child(div().text_base().font_weight(FontWeight::BOLD).child(self.name.clone()))
+            .children(self.version.as_ref().map(|version| div().text_sm().child(format!("Version {}", version))))
+    }
+}
+
+fn open_about(panel: AboutPanel, cx: &mut App) -> Option<WindowHandle<AboutPanel>> {
+    let options = WindowOptions {
+        window_bounds: Some(WindowBounds::Windowed(Bounds::centered(None, size(px(280.0), px(200.0)), cx))),
+        titlebar: Some(TitlebarOptions { title: Some(format!("About {}", panel.name).into()), ..Default::default() }),
+        is_resizable: false,
+        ..Default::default()
+    };
+    match cx.open_window(options, |_, cx| cx.new(|_| panel)) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            eprintln!("Warning: can't open the About panel: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Run a closure against the active window, if any
+fn with_active_window(cx: &mut App, f: impl FnOnce(&mut Window, &mut App)) {
+    if let Some(handle) = cx.active_window() {
+        let _ = handle.update(cx, |_, window, cx| f(window, cx));
+    }
+}
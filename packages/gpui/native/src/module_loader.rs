@@ -1,19 +1,28 @@
 //! Module loader that bundles npm packages for QuickJS
 //!
 //! Uses oxc_resolver for module resolution and transforms ESM to QuickJS-compatible format.
+//! TypeScript and JSX modules are compiled to JS first (see `typescript`), and
+//! `.wasm` imports load WebAssembly natively (see `wasm`). Plain JS modules
+//! are parsed too, so every module's syntax errors are reported together,
+//! with their locations.
 
-use anyhow::{Context as AnyhowContext, Result};
+use anyhow::Result;
 use oxc_resolver::{ResolveOptions, Resolver};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use crate::config::{JsxConfig, RasenConfig};
+use crate::typescript;
 
 /// Module loader that reads config and bundles dependencies
 pub struct ModuleLoader {
     /// Working directory (where to find config)
     work_dir: PathBuf,
     bundled_runtime: Option<String>,
+    /// Files the bundle was built from, in load order
+    files: Vec<PathBuf>,
 }
 
 impl ModuleLoader {
@@ -21,25 +30,20 @@ impl ModuleLoader {
         Self {
             work_dir: work_dir.clone(),
             bundled_runtime: None,
+            files: Vec::new(),
         }
     }
     
-    /// Load modules based on config file in work_dir
-    pub fn load_modules(&mut self, _script: &str) -> Result<()> {
-        // Look for rasen.config.js in work_dir
-        let config_path = self.work_dir.join("rasen.config.js");
-        if !config_path.exists() {
+    /// Bundle the modules listed in the config's `modules` section
+    pub fn load_modules(&mut self, config: &RasenConfig) -> Result<()> {
+        if config.modules.is_empty() {
             return Ok(());
         }
         
-        // Parse the config file to extract aliases
-        let config_content = fs::read_to_string(&config_path)?;
-        
-        let aliases = parse_config(&config_content);
-        
         // Bundle all modules using work_dir as base for resolving paths
-        let bundle = bundle_modules(&self.work_dir, &aliases)?;
+        let (bundle, files) = bundle_modules(&self.work_dir, &config.modules, &config.jsx)?;
         self.bundled_runtime = Some(bundle);
+        self.files = files;
         
         Ok(())
     }
@@ -48,56 +52,87 @@ impl ModuleLoader {
     pub fn get_bundled_runtime(&self) -> Option<&str> {
         self.bundled_runtime.as_deref()
     }
+
+    /// The files bundled, as read before compiling
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
 }
 
-/// Parse rasen.config.js to extract module aliases
-fn parse_config(content: &str) -> HashMap<String, String> {
-    let mut aliases = HashMap::new();
-    
-    // Remove comments first
-    let re_line_comment = Regex::new(r"//.*").unwrap();
-    let re_block_comment = Regex::new(r"/\*[\s\S]*?\*/").unwrap();
-    let content = re_line_comment.replace_all(content, "");
-    let content = re_block_comment.replace_all(&content, "");
-    
-    // Extract 'name': 'path' patterns from modules object
-    let re = Regex::new(r#"['"](@?[\w\-/]+)['"]\s*:\s*['"]([^'"]+)['"]"#).unwrap();
-    
-    for cap in re.captures_iter(&content) {
-        let name = cap[1].to_string();
-        let path = cap[2].to_string();
-        aliases.insert(name, path);
+/// A problem found in a single module while bundling
+#[derive(Debug)]
+pub struct BundleError {
+    /// File the problem was found in
+    pub path: PathBuf,
+    /// 1-based line and column, when the problem points at specific code
+    pub span: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some((line, col)) => write!(f, "{}:{}:{}: {}", self.path.display(), line, col, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+/// Every problem found while bundling, so they can be fixed in one pass
+#[derive(Debug)]
+pub struct BundleErrors(pub Vec<BundleError>);
+
+impl fmt::Display for BundleErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.0.len();
+        write!(f, "{} error{} while bundling modules:", count, if count == 1 { "" } else { "s" })?;
+        for error in &self.0 {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
     }
-    
-    aliases
 }
 
+impl std::error::Error for BundleErrors {}
+
 /// A loaded module with its transformed code
 #[derive(Debug)]
 struct Module {
     /// Canonical path (unique identifier)
     path: PathBuf,
-    /// Original source code
+    /// Source code, compiled to JS for TypeScript modules
     source: String,
     /// Dependencies (canonical paths)
     #[allow(dead_code)]
     dependencies: Vec<PathBuf>,
 }
 
-/// Loads and bundles modules starting from entry points
-fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<String> {
+/// Loads and bundles modules starting from entry points; returns the bundle
+/// and the files in it
+fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>, jsx: &JsxConfig) -> Result<(String, Vec<PathBuf>)> {
     let resolver = create_resolver(base_dir, aliases);
 
     // Track loaded modules and their order
     let mut modules: HashMap<PathBuf, Module> = HashMap::new();
     let mut load_order: Vec<PathBuf> = Vec::new();
 
+    // Errors are collected across the whole graph instead of stopping at the first
+    let mut errors: Vec<BundleError> = Vec::new();
+
     // Load entry points
     for (name, path) in aliases {
         let full_path = base_dir.join(path);
-        let canonical = full_path
-            .canonicalize()
-            .with_context(|| format!("Cannot resolve entry '{}'", name))?;
+        let canonical = match full_path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                errors.push(BundleError {
+                    path: full_path,
+                    span: None,
+                    message: format!("cannot resolve entry '{}' ({})", name, e),
+                });
+                continue;
+            }
+        };
 
         load_module_recursive(
             &canonical,
@@ -105,7 +140,9 @@ fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<
             &mut modules,
             &mut load_order,
             &mut HashSet::new(),
-        )?;
+            jsx,
+            &mut errors,
+        );
     }
 
     // Build the bundle
@@ -118,6 +155,8 @@ fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<
     // require function
     bundle.push_str("  function __require(id) {\n");
     bundle.push_str("    if (__cache[id]) return __cache[id].exports;\n");
+    // Bare imports of built-ins (`path`, `os`, `url`) aren't bundled
+    bundle.push_str("    if (!__modules[id] && globalThis.__modules && globalThis.__modules[id]) return globalThis.__modules[id];\n");
     bundle.push_str("    var module = { exports: {} };\n");
     bundle.push_str("    __cache[id] = module;\n");
     bundle.push_str("    __modules[id](module, module.exports, __require);\n");
@@ -127,7 +166,17 @@ fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<
     // Define modules in dependency order (leaves first)
     for path in &load_order {
         let module = modules.get(path).unwrap();
-        let transformed = transform_module(&module.source, path, &modules)?;
+        let transformed = match transform_module(&module.source, path, &modules) {
+            Ok(transformed) => transformed,
+            Err(e) => {
+                errors.push(BundleError {
+                    path: path.clone(),
+                    span: None,
+                    message: format!("cannot transform module ({})", e),
+                });
+                continue;
+            }
+        };
 
         // Use path string as module ID
         let id = path.to_string_lossy();
@@ -139,6 +188,10 @@ fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<
         bundle.push_str("\n  };\n\n");
     }
 
+    if !errors.is_empty() {
+        return Err(BundleErrors(errors).into());
+    }
+
     // Map aliases to their canonical paths
     bundle.push_str("  var __aliases = {\n");
     for (name, rel_path) in aliases {
@@ -173,32 +226,72 @@ fn bundle_modules(base_dir: &Path, aliases: &HashMap<String, String>) -> Result<
 
     bundle.push_str("})();\n");
 
-    Ok(bundle)
+    Ok((bundle, load_order))
 }
 
 /// Recursively load a module and its dependencies
+///
+/// Problems are pushed to `errors` and loading continues with the rest of the graph.
 fn load_module_recursive(
     path: &PathBuf,
     resolver: &Resolver,
     modules: &mut HashMap<PathBuf, Module>,
     load_order: &mut Vec<PathBuf>,
     visiting: &mut HashSet<PathBuf>,
-) -> Result<()> {
+    jsx: &JsxConfig,
+    errors: &mut Vec<BundleError>,
+) {
     // Already loaded?
     if modules.contains_key(path) {
-        return Ok(());
+        return;
     }
 
     // Circular dependency check
     if visiting.contains(path) {
         // Not an error, just skip
-        return Ok(());
+        return;
     }
 
     visiting.insert(path.clone());
 
-    let source = fs::read_to_string(path)
-        .with_context(|| format!("Cannot read {:?}", path))?;
+    // WebAssembly is instantiated natively when the module is required
+    if path.extension().is_some_and(|ext| ext == "wasm") {
+        let source = format!("module.exports = __wasm.load({:?});", path.to_string_lossy());
+        modules.insert(path.clone(), Module { path: path.clone(), source, dependencies: Vec::new() });
+        load_order.push(path.clone());
+        visiting.remove(path);
+        return;
+    }
+
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            errors.push(BundleError {
+                path: path.clone(),
+                span: None,
+                message: format!("cannot read file ({})", e),
+            });
+            visiting.remove(path);
+            return;
+        }
+    };
+
+    let source = if typescript::needs_compile(path) {
+        match typescript::compile(&source, path, jsx) {
+            Ok(code) => code,
+            Err(compile_errors) => {
+                errors.extend(compile_errors);
+                visiting.remove(path);
+                return;
+            }
+        }
+    } else if let Err(syntax_errors) = typescript::check(&source, path) {
+        errors.extend(syntax_errors);
+        visiting.remove(path);
+        return;
+    } else {
+        source
+    };
 
     // Parse imports
     let imports = parse_imports(&source);
@@ -207,15 +300,25 @@ fn load_module_recursive(
     let mut dependencies = Vec::new();
     let dir = path.parent().unwrap();
 
-    for import in &imports {
-        if let Some(resolved) = resolve_import(resolver, dir, import) {
-            dependencies.push(resolved);
+    for (import, offset) in &imports {
+        match resolve_import(resolver, dir, import) {
+            Some(resolved) => dependencies.push(resolved),
+            // Bare specifiers may still be provided at runtime via __modules,
+            // but a relative import that doesn't resolve is always a broken file
+            None if import.starts_with("./") || import.starts_with("../") => {
+                errors.push(BundleError {
+                    path: path.clone(),
+                    span: Some(line_col(&source, *offset)),
+                    message: format!("cannot resolve import '{}'", import),
+                });
+            }
+            None => {}
         }
     }
 
     // Load dependencies first (DFS)
     for dep in &dependencies {
-        load_module_recursive(dep, resolver, modules, load_order, visiting)?;
+        load_module_recursive(dep, resolver, modules, load_order, visiting, jsx, errors);
     }
 
     // Add this module
@@ -230,29 +333,39 @@ fn load_module_recursive(
     load_order.push(path.clone());
 
     visiting.remove(path);
-    Ok(())
 }
 
-/// Parse import specifiers from ESM source
-fn parse_imports(source: &str) -> Vec<String> {
+/// Convert a byte offset into a 1-based line and column
+pub(crate) fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rfind('\n').map_or(before.len(), |nl| before.len() - nl - 1) + 1;
+    (line, col)
+}
+
+/// Parse import specifiers from ESM source, with the byte offset of each specifier
+fn parse_imports(source: &str) -> Vec<(String, usize)> {
     let mut imports = Vec::new();
 
     // import ... from "..."
     let re_import = Regex::new(r#"import\s+.*?\s+from\s+['"]([^'"]+)['"]"#).unwrap();
     for cap in re_import.captures_iter(source) {
-        imports.push(cap[1].to_string());
+        let spec = cap.get(1).unwrap();
+        imports.push((spec.as_str().to_string(), spec.start()));
     }
 
     // export ... from "..."
     let re_export = Regex::new(r#"export\s+.*?\s+from\s+['"]([^'"]+)['"]"#).unwrap();
     for cap in re_export.captures_iter(source) {
-        imports.push(cap[1].to_string());
+        let spec = cap.get(1).unwrap();
+        imports.push((spec.as_str().to_string(), spec.start()));
     }
 
     // import("...")
     let re_dynamic = Regex::new(r#"import\s*\(\s*['"]([^'"]+)['"]\s*\)"#).unwrap();
     for cap in re_dynamic.captures_iter(source) {
-        imports.push(cap[1].to_string());
+        let spec = cap.get(1).unwrap();
+        imports.push((spec.as_str().to_string(), spec.start()));
     }
 
     imports
@@ -281,7 +394,7 @@ fn transform_module(
 
     // Create resolver just for path mapping
     let resolver = Resolver::new(ResolveOptions {
-        extensions: vec![".js".into(), ".mjs".into(), ".cjs".into()],
+        extensions: vec![".js".into(), ".mjs".into(), ".cjs".into(), ".ts".into(), ".mts".into(), ".cts".into(), ".tsx".into(), ".jsx".into()],
         main_fields: vec!["module".into(), "main".into()],
         condition_names: vec!["import".into(), "require".into(), "default".into()],
         ..Default::default()
@@ -289,7 +402,7 @@ fn transform_module(
 
     // Map specifiers to canonical paths
     let mut spec_to_path: HashMap<String, String> = HashMap::new();
-    for spec in &imports {
+    for (spec, _) in &imports {
         if let Some(resolved) = resolve_import(&resolver, dir, spec) {
             spec_to_path.insert(spec.clone(), resolved.to_string_lossy().to_string());
         }
@@ -429,9 +542,36 @@ fn create_resolver(base_dir: &Path, aliases: &HashMap<String, String>) -> Resolv
 
     Resolver::new(ResolveOptions {
         alias: alias_list,
-        extensions: vec![".js".into(), ".mjs".into(), ".cjs".into()],
+        extensions: vec![".js".into(), ".mjs".into(), ".cjs".into(), ".ts".into(), ".mts".into(), ".cts".into(), ".tsx".into(), ".jsx".into()],
         main_fields: vec!["module".into(), "main".into()],
         condition_names: vec!["import".into(), "require".into(), "default".into()],
         ..Default::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_of_every_module() {
+        let dir = std::env::temp_dir().join(format!("rasen-bundle-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.js"), "import { a } from './a.ts'\nimport { b } from './b.js'\n").unwrap();
+        fs::write(dir.join("a.ts"), "export const a: number = (;\n").unwrap();
+        fs::write(dir.join("b.js"), "export const b = 1\nconst c = {;\n").unwrap();
+
+        let aliases = HashMap::from([("app".to_string(), "main.js".to_string())]);
+        let error = bundle_modules(&dir, &aliases, &JsxConfig::default()).unwrap_err();
+        let BundleErrors(mut errors) = error.downcast::<BundleErrors>().unwrap();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut found: Vec<(&str, Option<usize>)> = errors
+            .iter()
+            .map(|error| (error.path.file_name().unwrap().to_str().unwrap(), error.span.map(|(line, _)| line)))
+            .collect();
+        found.dedup();
+        assert_eq!(found, vec![("a.ts", Some(1)), ("b.js", Some(2))]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
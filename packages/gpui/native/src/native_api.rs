@@ -0,0 +1,67 @@
+//! Native APIs exposed to JS on the `rasen` global
+//!
+//! Each API lives in its own module with an `install` function that adds its
+//! namespace to the shared `rasen` object.
+
+use rquickjs::{Ctx, Object};
+use crate::{activity, automation, bus, camera, collation, dates, dialogs, files, images, layout, lifecycle, limits, notifications, platform, print, process, screen, session, shell, storage, undo, window, zoom};
+
+/// Create the `rasen` global and install all native APIs on it
+///
+/// `bus_id` is the runtime's endpoint on the message bus, `files` its
+/// queue of `rasen.fs` operations, `layout` its element measurements and
+/// `processes` its child processes, `notifications` the notifications
+/// it posted, `dialogs` the dialogs it shows and `windows` the windows
+/// it asked to open or close.
+#[allow(clippy::too_many_arguments)]
+pub fn install(
+    ctx: &Ctx,
+    bus_id: bus::RuntimeId,
+    files: &files::Files,
+    layout: &layout::Layout,
+    processes: &process::Processes,
+    notifications: &notifications::Notifications,
+    dialogs: &dialogs::Dialogs,
+    windows: &window::Windows,
+) -> rquickjs::Result<()> {
+    let rasen = Object::new(ctx.clone())?;
+    
+    platform::install(ctx, &rasen)?;
+    automation::install(ctx, &rasen)?;
+    session::install(ctx, &rasen)?;
+    storage::install(ctx, &rasen)?;
+    activity::install(ctx, &rasen)?;
+    print::install(ctx, &rasen)?;
+    zoom::install(ctx, &rasen)?;
+    undo::install(ctx, &rasen)?;
+    collation::install(ctx, &rasen)?;
+    dates::install(ctx, &rasen)?;
+    files::install(ctx, &rasen, files)?;
+    images::install(ctx, &rasen, files)?;
+    camera::install(ctx, &rasen, files)?;
+    layout::install(ctx, &rasen, layout)?;
+    process::install(ctx, &rasen, processes)?;
+    notifications::install(ctx, &rasen, notifications)?;
+    dialogs::install(ctx, &rasen, dialogs)?;
+    shell::install(ctx, &rasen)?;
+    bus::install(ctx, &rasen, bus_id)?;
+    limits::install(ctx, &rasen)?;
+    window::install(ctx, &rasen, windows)?;
+    screen::install(ctx, &rasen)?;
+    lifecycle::install(ctx, &rasen)?;
+    
+    ctx.globals().set("rasen", rasen)?;
+    Ok(())
+}
+
+/// Get the namespace object `rasen[name]`, creating it if needed
+///
+/// For namespaces shared by several modules, such as `rasen.window`.
+pub fn namespace<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, name: &str) -> rquickjs::Result<Object<'js>> {
+    if let Some(namespace) = rasen.get::<_, Option<Object>>(name)? {
+        return Ok(namespace);
+    }
+    let namespace = Object::new(ctx.clone())?;
+    rasen.set(name, namespace.clone())?;
+    Ok(namespace)
+}
@@ -0,0 +1,164 @@
+//! Native modules: Rust functions exposed to JS as importable modules
+//!
+//! Embedders build a [`NativeModule`] with [`JsRuntime::register_native_module`]
+//! or a [`NativePlugin`], adding plain Rust functions:
+//!
+//! ```ignore
+//! runtime.register_native_module("hasher", |module| {
+//!     module.function("sha256", |(text,): (String,)| Ok(hex(sha256(text))));
+//! });
+//! ```
+//!
+//! and scripts import it like any other module:
+//! `import { sha256 } from 'hasher'`. Arguments and return values are
+//! marshaled through JSON with serde, so any `Deserialize` type (a tuple for
+//! several arguments) and `Serialize` result work. An `Err` is thrown as an
+//! `Error`, and arguments that don't match the types as a `TypeError`.
+//!
+//! Modules are installed into `__modules` when the script starts, before
+//! the bundled runtime, so they must be registered before that. Plugins can
+//! also add JS modules, evaluated after `@rasenjs/gpui` is loaded.
+//!
+//! [`JsRuntime::register_native_module`]: crate::js_runtime::JsRuntime::register_native_module
+
+use rquickjs::function::Rest;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as Json;
+use std::sync::Arc;
+use crate::values;
+
+/// Why a native function failed
+#[derive(Debug, PartialEq)]
+pub enum CallError {
+    /// The arguments don't deserialize to the function's parameter type
+    Arguments(String),
+    /// The function returned an error
+    Failed(String),
+}
+
+type Handler = dyn Fn(Json) -> Result<Json, CallError> + Send + Sync;
+
+/// A module of native functions, importable by name
+pub struct NativeModule {
+    name: String,
+    functions: Vec<(String, Arc<Handler>)>,
+}
+
+/// A set of native modules, registered together
+///
+/// Implemented by embedders, and by the dynamic library plugins the config
+/// lists.
+pub trait NativePlugin: Send + Sync {
+    /// Name shown in errors
+    fn name(&self) -> &str;
+
+    /// Add the plugin's modules to `registry`
+    fn register(&self, registry: &mut ModuleRegistry);
+}
+
+/// Modules registered for a runtime, installed when its script starts
+#[derive(Default)]
+pub struct ModuleRegistry {
+    modules: Vec<NativeModule>,
+    /// JS modules as (name, source), evaluated after `@rasenjs/gpui`
+    scripts: Vec<(String, String)>,
+}
+
+impl ModuleRegistry {
+    /// Add module `name`, built by `build`
+    pub fn module(&mut self, name: &str, build: impl FnOnce(&mut NativeModule)) {
+        let mut module = NativeModule { name: name.to_string(), functions: Vec::new() };
+        build(&mut module);
+        self.modules.push(module);
+    }
+
+    /// Add JS module `name`, e.g. a plugin's components
+    pub fn script(&mut self, name: &str, source: &str) {
+        self.scripts.push((name.to_string(), source.to_string()));
+    }
+
+    /// The JS modules, as (name, source)
+    pub fn scripts(&self) -> &[(String, String)] {
+        &self.scripts
+    }
+
+    /// Install every native module into `__modules`
+    ///
+    /// Runs after `builtins`, which creates `__modules`.
+    pub fn install<'js>(&self, ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+        let modules: Object = ctx.globals().get("__modules")?;
+        for module in &self.modules {
+            modules.set(module.name.as_str(), module.exports(ctx)?)?;
+        }
+        Ok(())
+    }
+}
+
+impl NativeModule {
+    /// Export `f` as function `name`
+    ///
+    /// `A` is deserialized from the array of JS arguments, so a tuple takes
+    /// them positionally; `undefined` arguments are `null`.
+    pub fn function<A, R, F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        A: DeserializeOwned,
+        R: Serialize,
+        F: Fn(A) -> Result<R, String> + Send + Sync + 'static,
+    {
+        let handler = move |args: Json| -> Result<Json, CallError> {
+            let args = serde_json::from_value(args).map_err(|e| CallError::Arguments(e.to_string()))?;
+            let result = f(args).map_err(CallError::Failed)?;
+            serde_json::to_value(result).map_err(|e| CallError::Failed(e.to_string()))
+        };
+        self.functions.push((name.to_string(), Arc::new(handler)));
+        self
+    }
+
+    /// The module's exports object
+    fn exports<'js>(&self, ctx: &Ctx<'js>) -> rquickjs::Result<Object<'js>> {
+        let exports = Object::new(ctx.clone())?;
+        for (name, handler) in &self.functions {
+            let handler = handler.clone();
+            let label = format!("{}.{}", self.name, name);
+            let function = Function::new(ctx.clone(), move |ctx: Ctx<'js>, args: Rest<Value<'js>>| {
+                let args = args.0.iter().map(|arg| values::to_json(&ctx, arg)).collect::<rquickjs::Result<_>>()?;
+                match handler(Json::Array(args)) {
+                    Ok(Json::Null) => Ok(Value::new_undefined(ctx.clone())),
+                    Ok(result) => values::from_json(&ctx, &result),
+                    Err(CallError::Arguments(message)) => {
+                        Err(Exception::throw_type(&ctx, &format!("{}: invalid arguments: {}", label, message)))
+                    }
+                    Err(CallError::Failed(message)) => Err(Exception::throw_message(&ctx, &message)),
+                }
+            })?;
+            exports.set(name.as_str(), function)?;
+        }
+        Ok(exports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_marshaling() {
+        let mut registry = ModuleRegistry::default();
+        registry.module("math", |module| {
+            module
+                .function("add", |(a, b): (f64, f64)| Ok(a + b))
+                .function("sqrt", |(x,): (f64,)| if x < 0.0 { Err(format!("{} is negative", x)) } else { Ok(x.sqrt()) });
+        });
+        let math = &registry.modules[0];
+        let call = |name: &str, args: Json| {
+            math.functions.iter().find(|(function, _)| function == name).map(|(_, handler)| handler(args))
+        };
+        assert_eq!(call("add", json!([1, 2.5])), Some(Ok(json!(3.5))));
+        assert_eq!(call("sqrt", json!([-1])), Some(Err(CallError::Failed("-1 is negative".to_string()))));
+        assert!(matches!(call("add", json!(["a", 1])), Some(Err(CallError::Arguments(_)))));
+        assert!(call("missing", json!([])).is_none());
+    }
+}
@@ -0,0 +1,158 @@
+//! Native OS notifications for `rasen.notify`
+//!
+//! `notify({ title, body, icon, onClick })` posts a notification through
+//! notify-rust: Notification Center on macOS, toasts on Windows and the
+//! freedesktop notification server (libnotify) on Linux. Notifications are
+//! posted from a thread, since the servers can be slow to answer.
+//!
+//! Clicks are reported by freedesktop servers only. There, a thread waits
+//! for the notification to be clicked or closed and queues the event for
+//! the runtime, which wakes and calls `onClick` on the UI thread, like
+//! `rasen.process` callbacks. Callbacks stay on the JS side in a table keyed
+//! by notification ID until then.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use notify_rust::Notification;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use crate::js_runtime;
+
+/// Global holding `onClick` callbacks by notification ID
+const CALLBACKS: &str = "__notifications";
+
+/// Whether the platform's notifications report clicks
+pub const REPORTS_CLICKS: bool = cfg!(all(unix, not(target_os = "macos")));
+
+static APP_NAME: OnceLock<String> = OnceLock::new();
+
+/// Set the app name notifications are posted under (once, at startup)
+pub fn init(app_name: Option<&str>) {
+    let _ = APP_NAME.set(app_name.unwrap_or("Rasen App").to_string());
+}
+
+/// What happened to a notification, waiting for delivery to JS
+#[derive(Debug, PartialEq)]
+pub enum NotificationEvent {
+    Clicked(u32),
+    /// Dismissed or expired; its callback is dropped
+    Closed(u32),
+}
+
+/// A runtime's posted notifications; clones share them
+#[derive(Clone)]
+pub struct Notifications {
+    state: Arc<Mutex<NotificationState>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct NotificationState {
+    next_id: u32,
+    events: Vec<NotificationEvent>,
+}
+
+impl Notifications {
+    /// Create an empty set; the receiver wakes when a notification is
+    /// clicked or closed
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { state: Arc::default(), wake }, wakeups)
+    }
+
+    fn push(&self, event: NotificationEvent) {
+        self.state.lock().unwrap().events.push(event);
+        let _ = self.wake.unbounded_send(());
+    }
+
+    /// Post `notification` as notification `id`
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn post(&self, mut notification: Notification, id: u32) {
+        let notifications = self.clone();
+        thread::spawn(move || match notification.action("default", "Open").show() {
+            Ok(handle) => handle.wait_for_action(|action| {
+                notifications.push(if action == "default" {
+                    NotificationEvent::Clicked(id)
+                } else {
+                    NotificationEvent::Closed(id)
+                });
+            }),
+            Err(e) => {
+                eprintln!("Warning: can't post notification: {}", e);
+                notifications.push(NotificationEvent::Closed(id));
+            }
+        });
+    }
+
+    /// Post `notification` as notification `id`
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn post(&self, notification: Notification, _id: u32) {
+        thread::spawn(move || {
+            if let Err(e) = notification.show() {
+                eprintln!("Warning: can't post notification: {}", e);
+            }
+        });
+    }
+}
+
+/// Call the `onClick` callbacks of clicked notifications; returns whether
+/// any ran
+pub fn deliver(ctx: &Ctx, notifications: &Notifications) -> bool {
+    let events = std::mem::take(&mut notifications.state.lock().unwrap().events);
+    let Ok(callbacks) = ctx.globals().get::<_, Object>(CALLBACKS) else {
+        return false;
+    };
+
+    let mut delivered = false;
+    for event in events {
+        let (NotificationEvent::Clicked(id) | NotificationEvent::Closed(id)) = event;
+        let callback = callbacks.get::<_, Option<Function>>(id.to_string()).ok().flatten();
+        let _ = callbacks.remove(id.to_string());
+        let (NotificationEvent::Clicked(_), Some(callback)) = (event, callback) else {
+            continue;
+        };
+        match callback.call::<_, Value>(()) {
+            Ok(result) => js_runtime::report_rejection(ctx, &result, format!("async notification {} onClick", id)),
+            Err(e) => eprintln!("Error in notification {} onClick: {:?}", id, e),
+        }
+        delivered = true;
+    }
+    delivered
+}
+
+/// Install `rasen.notify` for a runtime's notifications
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, notifications: &Notifications) -> rquickjs::Result<()> {
+    ctx.globals().set(CALLBACKS, Object::new(ctx.clone())?)?;
+
+    let notifications = notifications.clone();
+    rasen.set(
+        "notify",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, options: Object<'js>| -> rquickjs::Result<()> {
+            let Some(title) = options.get::<_, Option<String>>("title")? else {
+                return Err(Exception::throw_type(&ctx, "notify needs a title"));
+            };
+            let mut notification = Notification::new();
+            notification.appname(APP_NAME.get().map_or("Rasen App", String::as_str)).summary(&title);
+            if let Some(body) = options.get::<_, Option<String>>("body")? {
+                notification.body(&body);
+            }
+            if let Some(icon) = options.get::<_, Option<String>>("icon")? {
+                notification.icon(&icon);
+            }
+
+            let id = {
+                let mut state = notifications.state.lock().unwrap();
+                // IDs start at 1, like timers
+                state.next_id += 1;
+                state.next_id
+            };
+            if let (true, Some(on_click)) = (REPORTS_CLICKS, options.get::<_, Option<Function>>("onClick")?) {
+                let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+                callbacks.set(id.to_string(), on_click)?;
+            }
+            notifications.post(notification, id);
+            Ok(())
+        })?,
+    )?;
+    Ok(())
+}
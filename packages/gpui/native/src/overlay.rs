@@ -0,0 +1,232 @@
+//! In-window error overlay for script errors
+//!
+//! When the user script throws while it's evaluated or while the App
+//! re-renders, the window stays open and shows the error on a red screen:
+//! the message, the code around the line that threw and the JS stack. Fix
+//! the script and reload (the overlay's button or cmd/ctrl-R) to run it
+//! again in a fresh runtime, or dismiss the overlay to keep using the last
+//! tree that rendered.
+//!
+//! QuickJS reports every evaluated string as `eval_script`, so the code
+//! frame is taken from the first stack entry whose line is in the user
+//! script. For TypeScript and JSX it shows the compiled JS.
+
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use rquickjs::convert::Coerced;
+use rquickjs::Value;
+use std::fmt;
+use crate::descriptor::ElementDescriptor;
+use crate::elements::Element;
+
+/// File name QuickJS gives code evaluated from a string
+const EVAL_FILE: &str = "eval_script:";
+/// Lines shown before and after the line that threw
+const CONTEXT_LINES: usize = 2;
+
+#[cfg(target_os = "macos")]
+const MONOSPACE: &str = "Menlo";
+#[cfg(target_os = "windows")]
+const MONOSPACE: &str = "Consolas";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const MONOSPACE: &str = "DejaVu Sans Mono";
+
+/// An error thrown by the user script, as the overlay shows it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    /// `name: message` of the thrown error
+    pub message: String,
+    pub stack: Option<String>,
+    pub frame: Option<CodeFrame>,
+}
+
+/// Lines of the script around the line that threw
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeFrame {
+    /// 1-based line and column that threw; QuickJS may only report the line
+    pub line: usize,
+    pub column: Option<usize>,
+    /// Line numbers and text, the line that threw included
+    pub lines: Vec<(usize, String)>,
+}
+
+impl ScriptError {
+    /// An error without a stack, e.g. from the native side
+    pub fn new(message: String) -> Self {
+        Self { message, stack: None, frame: None }
+    }
+
+    /// Describe an exception thrown by the user script, with a code frame
+    /// from `source`, the script as evaluated
+    pub fn from_exception(exception: &Value, source: &str) -> Self {
+        let Some(error) = exception.as_object() else {
+            // `throw "text"` and other non-errors
+            let message = exception.get::<Coerced<String>>().map(|text| text.0).unwrap_or_default();
+            return Self::new(message);
+        };
+        let text = |name: &str| error.get::<_, Option<String>>(name).ok().flatten().filter(|text| !text.is_empty());
+        let message = match (text("name"), text("message")) {
+            (Some(name), Some(message)) => format!("{}: {}", name, message),
+            (name, message) => name.or(message).unwrap_or_else(|| "Unknown error".to_string()),
+        };
+        let stack = text("stack").map(|stack| stack.trim_end().to_string());
+        let frame = stack.as_deref().and_then(|stack| {
+            locations(stack).find_map(|(line, column)| code_frame(source, line, column))
+        });
+        Self { message, stack, frame }
+    }
+
+    /// The script error behind `error`, or one with its message
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        error.downcast_ref::<ScriptError>().cloned().unwrap_or_else(|| Self::new(format!("{:#}", error)))
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(stack) = &self.stack {
+            write!(f, "\n{}", stack)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Lines and columns of the evaluated code in a QuickJS stack, innermost
+/// first (`at App (eval_script:12:5)`)
+fn locations(stack: &str) -> impl Iterator<Item = (usize, Option<usize>)> + '_ {
+    stack.lines().filter_map(|entry| {
+        let start = entry.find(EVAL_FILE)? + EVAL_FILE.len();
+        let mut numbers = entry[start..]
+            .split(|c: char| !c.is_ascii_digit() && c != ':')
+            .next()?
+            .split(':')
+            .map(|number| number.parse::<usize>().ok());
+        let line = numbers.next().flatten()?;
+        Some((line, numbers.next().flatten()))
+    })
+}
+
+/// The lines of `source` around `line`, if it has that line
+pub fn code_frame(source: &str, line: usize, column: Option<usize>) -> Option<CodeFrame> {
+    let count = source.lines().count();
+    if line == 0 || line > count {
+        return None;
+    }
+    let first = line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (line + CONTEXT_LINES).min(count);
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| (index + 1, text.to_string()))
+        .skip(first - 1)
+        .take(last - first + 1)
+        .collect();
+    Some(CodeFrame { line, column, lines })
+}
+
+/// An empty root, shown under the overlay when the script never rendered
+pub fn blank() -> Element {
+    ElementDescriptor::Text { class: String::new(), test_id: None, key: None, text: String::new() }.into_element()
+}
+
+fn button(id: &'static str, label: &'static str, on_click: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static) -> Stateful<Div> {
+    div()
+        .id(id)
+        .px_3()
+        .py_1()
+        .rounded_md()
+        .bg(rgb(0xfef2f2))
+        .text_color(rgb(0x7f1d1d))
+        .font_weight(FontWeight::SEMIBOLD)
+        .cursor_pointer()
+        .hover(|style| style.bg(rgb(0xffffff)))
+        .on_click(on_click)
+        .child(label)
+}
+
+fn render_frame(frame: &CodeFrame) -> Div {
+    let width = frame.lines.last().map_or(1, |(number, _)| number.to_string().len());
+    let mut code = div().flex().flex_col().p_3().rounded_md().bg(rgb(0x450a0a)).font_family(MONOSPACE).text_sm();
+    for (number, text) in &frame.lines {
+        let current = *number == frame.line;
+        let marker = if current { ">" } else { " " };
+        code = code.child(
+            div()
+                .when(current, |row| row.bg(rgb(0x991b1b)))
+                .child(format!("{} {:>width$} | {}", marker, number, text, width = width)),
+        );
+        if let (true, Some(column)) = (current, frame.column) {
+            code = code.child(format!("  {} | {}^", " ".repeat(width), " ".repeat(column.saturating_sub(1))));
+        }
+    }
+    code
+}
+
+/// The full-window overlay for `error`
+///
+/// `dismiss` is given when there's a tree to go back to.
+pub fn render(
+    error: &ScriptError,
+    reload: impl Fn(&ClickEvent, &mut Window, &mut App) + 'static,
+    dismiss: Option<impl Fn(&ClickEvent, &mut Window, &mut App) + 'static>,
+) -> AnyElement {
+    let stack = error.stack.as_deref().unwrap_or_default();
+    div()
+        .id("rasen-error-overlay")
+        .absolute()
+        .top_0()
+        .left_0()
+        .size_full()
+        .overflow_y_scroll()
+        .flex()
+        .flex_col()
+        .gap_4()
+        .p_8()
+        .bg(rgb(0x7f1d1d))
+        .text_color(rgb(0xfef2f2))
+        .child(div().text_sm().font_weight(FontWeight::SEMIBOLD).child("Uncaught error"))
+        .child(div().text_xl().font_weight(FontWeight::BOLD).child(error.message.clone()))
+        .when_some(error.frame.as_ref(), |overlay, frame| overlay.child(render_frame(frame)))
+        .when(!stack.is_empty(), |overlay| {
+            overlay.child(
+                div()
+                    .flex()
+                    .flex_col()
+                    .font_family(MONOSPACE)
+                    .text_sm()
+                    .children(stack.lines().map(|entry| div().child(entry.to_string()))),
+            )
+        })
+        .child(
+            div()
+                .flex()
+                .gap_2()
+                .child(button("rasen-error-reload", "Reload", reload))
+                .when_some(dismiss, |buttons, dismiss| buttons.child(button("rasen-error-dismiss", "Dismiss", dismiss))),
+        )
+        .into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stack_locations() {
+        let stack = "    at <anonymous> (@rasenjs/gpui)\n    at App (eval_script:12:5)\n    at __rerender (eval_script:930)\n";
+        assert_eq!(locations(stack).collect::<Vec<_>>(), vec![(12, Some(5)), (930, None)]);
+    }
+
+    #[test]
+    fn test_code_frame() {
+        let source = "a\nb\nc\nd\ne\nf";
+        let frame = code_frame(source, 2, Some(3)).unwrap();
+        assert_eq!(frame.lines, vec![(1, "a".to_string()), (2, "b".to_string()), (3, "c".to_string()), (4, "d".to_string())]);
+        assert_eq!(code_frame(source, 6, None).unwrap().lines.first(), Some(&(4, "d".to_string())));
+        // A line of the bundled runtime, past the end of the script
+        assert!(code_frame(source, 930, None).is_none());
+    }
+}
@@ -0,0 +1,271 @@
+//! Platform capability detection
+//!
+//! Not every native feature exists on every OS. Apps query capabilities with
+//! `rasen.platform.supports('tray')` and feature-detect instead of crashing.
+//!
+//! The OS contrast setting is read on a thread of its own (see
+//! [`watch_high_contrast`]), and `rasen.platform.on('high-contrast-change',
+//! listener)` hears when it changes.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+use rquickjs::{Ctx, Function, Object};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use crate::{camera, dialogs, shader};
+
+/// Global holding the `high-contrast-change` listeners
+const PLATFORM_EVENTS: &str = "__platformEvents";
+
+/// How often the contrast setting is read again, since not every platform
+/// changes the window appearance with it
+const CONTRAST_POLL: Duration = Duration::from_secs(5);
+
+/// The contrast setting as last read
+static HIGH_CONTRAST: AtomicBool = AtomicBool::new(false);
+
+/// Wakes the thread reading the contrast setting
+static CONTRAST_RECHECKS: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+const SHIM: &str = r#"
+(function() {
+    var listeners = [];
+    return {
+        on: function(event, listener) {
+            if (event !== 'high-contrast-change') {
+                throw new TypeError("Unknown platform event '" + event + "'");
+            }
+            if (typeof listener !== 'function') {
+                throw new TypeError('Platform event listener must be a function');
+            }
+            listeners.push(listener);
+            return function() {
+                var index = listeners.indexOf(listener);
+                if (index >= 0) listeners.splice(index, 1);
+            };
+        },
+        highContrastChange: function(highContrast) {
+            var called = listeners.slice();
+            for (var i = 0; i < called.length; i++) {
+                try {
+                    called[i](highContrast);
+                } catch (e) {
+                    __reportError(e, 'high-contrast-change listener');
+                }
+            }
+        }
+    };
+})()
+"#;
+
+/// Optional platform features
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Capability {
+    FileDialogs,
+    Tray,
+    MenuBar,
+    GlobalShortcuts,
+    WindowVibrancy,
+    Camera,
+    Eyedropper,
+    Shaders,
+}
+
+impl Capability {
+    /// Parse the name used on the JS side, e.g. `file-dialogs`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file-dialogs" => Some(Capability::FileDialogs),
+            "tray" => Some(Capability::Tray),
+            "menu-bar" => Some(Capability::MenuBar),
+            "global-shortcuts" => Some(Capability::GlobalShortcuts),
+            "window-vibrancy" => Some(Capability::WindowVibrancy),
+            "camera" => Some(Capability::Camera),
+            "eyedropper" => Some(Capability::Eyedropper),
+            "shaders" => Some(Capability::Shaders),
+            _ => None,
+        }
+    }
+}
+
+/// Name of the current platform as reported to JS
+pub fn name() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "linux") {
+        "linux"
+    } else {
+        "unknown"
+    }
+}
+
+/// Whether the current platform supports a capability
+///
+/// Mirrors what the GPUI platform backends provide:
+/// - file dialogs: `rasen.dialog`, wherever rfd has a backend (see
+///   `dialogs::SUPPORTED`)
+/// - menu bar: only macOS has an application menu bar
+/// - vibrancy: blurred window backgrounds on macOS and Windows
+/// - tray and global shortcuts: not provided by any backend yet
+/// - camera: `camera()` elements and `rasen.camera`, wherever nokhwa has a
+///   capture backend (see `camera::SUPPORTED`)
+/// - eyedropper: no backend can sample screen colors, so color pickers
+///   have no eyedropper button
+/// - shaders: `shader()` elements, wherever wgpu finds a GPU adapter to
+///   draw them offscreen on (see `shader`); asking opens it
+pub fn supports(capability: Capability) -> bool {
+    match capability {
+        Capability::FileDialogs => dialogs::SUPPORTED,
+        Capability::MenuBar => cfg!(target_os = "macos"),
+        Capability::WindowVibrancy => cfg!(any(target_os = "macos", target_os = "windows")),
+        Capability::Camera => camera::SUPPORTED,
+        Capability::Shaders => shader::supported(),
+        Capability::Tray | Capability::GlobalShortcuts | Capability::Eyedropper => false,
+    }
+}
+
+/// Whether the user asked the OS for more contrast, as last read
+///
+/// `false` until [`watch_high_contrast`] has read the setting once.
+pub fn prefers_high_contrast() -> bool {
+    HIGH_CONTRAST.load(Ordering::SeqCst)
+}
+
+/// Read the contrast setting on a thread of its own: right away, every
+/// [`CONTRAST_POLL`] and on [`recheck_high_contrast`]; the receiver gets
+/// each change
+pub fn watch_high_contrast() -> UnboundedReceiver<bool> {
+    let (changes, receiver) = unbounded();
+    let (rechecks, wakeups) = mpsc::channel();
+    *CONTRAST_RECHECKS.lock().unwrap() = Some(rechecks);
+    thread::spawn(move || loop {
+        let high_contrast = read_high_contrast();
+        let changed = HIGH_CONTRAST.swap(high_contrast, Ordering::SeqCst) != high_contrast;
+        if changed && changes.unbounded_send(high_contrast).is_err() {
+            break;
+        }
+        if wakeups.recv_timeout(CONTRAST_POLL) == Err(RecvTimeoutError::Disconnected) {
+            break;
+        }
+    });
+    receiver
+}
+
+/// Read the contrast setting again soon, e.g. as the window appearance changes
+pub fn recheck_high_contrast() {
+    if let Some(rechecks) = CONTRAST_RECHECKS.lock().unwrap().as_ref() {
+        let _ = rechecks.send(());
+    }
+}
+
+/// Read the OS contrast setting
+///
+/// GPUI doesn't expose it, so it is read from the platform settings:
+/// "Increase contrast" on macOS, the high-contrast theme on Windows and the
+/// GNOME high-contrast setting on Linux. Spawns a process, so it's only
+/// called off the UI thread.
+fn read_high_contrast() -> bool {
+    let output = |program: &str, args: &[&str]| -> Option<String> {
+        let output = Command::new(program).args(args).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    
+    if cfg!(target_os = "macos") {
+        output("defaults", &["read", "com.apple.universalaccess", "increaseContrast"]).as_deref() == Some("1")
+    } else if cfg!(target_os = "windows") {
+        // HCF_HIGHCONTRASTON is bit 0 of the Flags value
+        output("reg", &["query", r"HKCU\Control Panel\Accessibility\HighContrast", "/v", "Flags"])
+            .and_then(|out| out.split_whitespace().last()?.parse::<u32>().ok())
+            .is_some_and(|flags| flags & 1 != 0)
+    } else {
+        output("gsettings", &["get", "org.gnome.desktop.a11y.interface", "high-contrast"]).as_deref() == Some("true")
+    }
+}
+
+/// Call the `high-contrast-change` listeners
+pub fn high_contrast_changed(ctx: &Ctx, high_contrast: bool) {
+    let emitted = ctx
+        .globals()
+        .get::<_, Object>(PLATFORM_EVENTS)
+        .and_then(|events| events.get::<_, Function>("highContrastChange")?.call::<_, ()>((high_contrast,)));
+    if let Err(e) = emitted {
+        eprintln!("Error in high-contrast-change listeners: {:?}", e);
+    }
+}
+
+/// Install `rasen.platform`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let events: Object = ctx.eval(SHIM)?;
+    ctx.globals().set(PLATFORM_EVENTS, events.clone())?;
+    
+    let platform = Object::new(ctx.clone())?;
+    platform.set("name", name())?;
+    platform.set(
+        "supports",
+        Function::new(ctx.clone(), |capability: String| {
+            // Unknown capabilities are reported as unsupported
+            Capability::from_name(&capability).is_some_and(supports)
+        })?,
+    )?;
+    platform.set("prefersHighContrast", Function::new(ctx.clone(), prefers_high_contrast)?)?;
+    platform.set("on", events.get::<_, Function>("on")?)?;
+    rasen.set("platform", platform)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rquickjs::{Context, Runtime};
+
+    #[test]
+    fn test_capability_names() {
+        let names = [
+            ("file-dialogs", Capability::FileDialogs),
+            ("tray", Capability::Tray),
+            ("menu-bar", Capability::MenuBar),
+            ("global-shortcuts", Capability::GlobalShortcuts),
+            ("window-vibrancy", Capability::WindowVibrancy),
+            ("camera", Capability::Camera),
+            ("eyedropper", Capability::Eyedropper),
+            ("shaders", Capability::Shaders),
+        ];
+        for (name, capability) in names {
+            assert_eq!(Capability::from_name(name), Some(capability));
+        }
+        assert_eq!(Capability::from_name("Tray"), None);
+        assert_eq!(Capability::from_name("file_dialogs"), None);
+    }
+
+    #[test]
+    fn test_supports_per_platform() {
+        assert_eq!(supports(Capability::FileDialogs), dialogs::SUPPORTED);
+        assert_eq!(supports(Capability::MenuBar), cfg!(target_os = "macos"));
+        assert_eq!(supports(Capability::WindowVibrancy), cfg!(any(target_os = "macos", target_os = "windows")));
+        assert_eq!(supports(Capability::Camera), camera::SUPPORTED);
+        assert!(!supports(Capability::Tray));
+        assert!(!supports(Capability::GlobalShortcuts));
+        assert!(!supports(Capability::Eyedropper));
+    }
+
+    #[test]
+    fn test_unknown_capability_is_unsupported() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            let rasen = Object::new(ctx.clone()).unwrap();
+            install(&ctx, &rasen).unwrap();
+            ctx.globals().set("rasen", rasen).unwrap();
+            assert!(!ctx.eval::<bool, _>("rasen.platform.supports('teleport')").unwrap());
+            assert_eq!(
+                ctx.eval::<bool, _>("rasen.platform.supports('menu-bar')").unwrap(),
+                cfg!(target_os = "macos")
+            );
+        });
+    }
+}
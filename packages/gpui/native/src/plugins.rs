@@ -0,0 +1,221 @@
+//! Native plugins loaded from dynamic libraries
+//!
+//! The config's `plugins` list names shared libraries (paths relative to the
+//! project root), usually Rust `cdylib` crates. Each exports
+//! `rasen_plugin_v1`, a C function returning a static [`RasenPlugin`]
+//! description: its native modules, whose functions take and return JSON
+//! strings, and JS modules evaluated after `@rasenjs/gpui`, where custom
+//! element types are written as components built from the native modules
+//! and the built-in elements. The ABI is plain C, so plugins don't have to
+//! be built with the same compiler as the runtime; its version is checked
+//! when the library loads.
+//!
+//! Libraries stay loaded for the life of the process. They're part of the
+//! bundle `integrity` verifies, before any of them loads, and in hardened
+//! mode they only load from a signed bundle.
+
+use anyhow::{Context as _, Result};
+use libloading::Library;
+use serde_json::Value as Json;
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+use std::sync::Arc;
+use crate::integrity;
+use crate::native_modules::{ModuleRegistry, NativePlugin};
+
+/// Version of the ABI below; plugins built for another one are refused
+pub const ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin exports: `extern "C" fn() -> *const RasenPlugin`
+const ENTRY: &[u8] = b"rasen_plugin_v1";
+
+/// Call a native function with its arguments as a JSON array
+///
+/// Returns a JSON string allocated by the plugin, or an error message with
+/// `*failed` set; either is released with `RasenPlugin::free`. Null means
+/// `undefined`.
+pub type CallFn = unsafe extern "C" fn(args: *const c_char, failed: *mut bool) -> *mut c_char;
+/// Release a string returned by a `CallFn`
+pub type FreeFn = unsafe extern "C" fn(string: *mut c_char);
+
+/// What a plugin provides; all strings are NUL-terminated UTF-8 and all
+/// pointers stay valid while the library is loaded
+#[repr(C)]
+pub struct RasenPlugin {
+    /// [`ABI_VERSION`]
+    pub abi_version: u32,
+    pub name: *const c_char,
+    pub modules: *const RasenModule,
+    pub module_count: usize,
+    pub scripts: *const RasenScript,
+    pub script_count: usize,
+    pub free: FreeFn,
+}
+
+/// A native module, importable by `name`
+#[repr(C)]
+pub struct RasenModule {
+    pub name: *const c_char,
+    pub functions: *const RasenFunction,
+    pub function_count: usize,
+}
+
+#[repr(C)]
+pub struct RasenFunction {
+    pub name: *const c_char,
+    pub call: CallFn,
+}
+
+/// A JS module, importable by `name`, e.g. components for custom elements
+#[repr(C)]
+pub struct RasenScript {
+    pub name: *const c_char,
+    pub source: *const c_char,
+}
+
+/// A loaded plugin, with its description copied out of the library
+pub struct DylibPlugin {
+    name: String,
+    modules: Vec<(String, Vec<(String, CallFn)>)>,
+    scripts: Vec<(String, String)>,
+    free: FreeFn,
+    /// Keeps the functions above loaded
+    _library: Arc<Library>,
+}
+
+/// Safety: `pointer` must be null or a NUL-terminated string
+unsafe fn text(pointer: *const c_char) -> String {
+    if pointer.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(pointer).to_string_lossy().into_owned()
+}
+
+/// Safety: `pointer` must be null or point to `count` items
+unsafe fn items<'a, T>(pointer: *const T, count: usize) -> &'a [T] {
+    if pointer.is_null() {
+        return &[];
+    }
+    std::slice::from_raw_parts(pointer, count)
+}
+
+/// Call a plugin function with JSON arguments
+fn call(function: CallFn, free: FreeFn, args: &Json) -> Result<Json, String> {
+    // Serialized JSON escapes NUL, so this can't fail
+    let args = CString::new(args.to_string()).unwrap_or_default();
+    let mut failed = false;
+    let result = unsafe { function(args.as_ptr(), &mut failed) };
+    if result.is_null() {
+        return if failed { Err("native function failed".to_string()) } else { Ok(Json::Null) };
+    }
+    let output = unsafe { text(result) };
+    unsafe { free(result) };
+    if failed {
+        return Err(output);
+    }
+    serde_json::from_str(&output).map_err(|e| format!("native function returned invalid JSON: {}", e))
+}
+
+/// Load the plugin library at `path`
+pub fn load(path: &Path) -> Result<DylibPlugin> {
+    let bytes = std::fs::read(path).with_context(|| format!("Can't read plugin {}", path.display()))?;
+    integrity::check(path, &bytes).map_err(anyhow::Error::msg)?;
+    let library = unsafe { Library::new(path) }.with_context(|| format!("Can't load plugin {}", path.display()))?;
+    let entry = unsafe { library.get::<unsafe extern "C" fn() -> *const RasenPlugin>(ENTRY) }
+        .with_context(|| format!("{} is not a Rasen plugin (no `rasen_plugin_v1`)", path.display()))?;
+    let plugin = unsafe { entry().as_ref() }
+        .with_context(|| format!("Plugin {} returned no description", path.display()))?;
+    if plugin.abi_version != ABI_VERSION {
+        anyhow::bail!(
+            "Plugin {} was built for plugin ABI {}, this runtime supports {}",
+            path.display(),
+            plugin.abi_version,
+            ABI_VERSION
+        );
+    }
+
+    // Safety: the plugin promises valid pointers while the library is loaded
+    let (name, modules, scripts) = unsafe {
+        let modules = items(plugin.modules, plugin.module_count)
+            .iter()
+            .map(|module| {
+                let functions = items(module.functions, module.function_count)
+                    .iter()
+                    .map(|function| (text(function.name), function.call))
+                    .collect();
+                (text(module.name), functions)
+            })
+            .collect();
+        let scripts = items(plugin.scripts, plugin.script_count)
+            .iter()
+            .map(|script| (text(script.name), text(script.source)))
+            .collect();
+        (text(plugin.name), modules, scripts)
+    };
+    let free = plugin.free;
+    Ok(DylibPlugin { name, modules, scripts, free, _library: Arc::new(library) })
+}
+
+/// Load the config's plugins, with paths relative to `work_dir`
+///
+/// Call after the bundle is verified. Hardened mode refuses plugins unless
+/// the bundle is signed, since they run native code unchecked.
+pub fn load_all(paths: &[String], work_dir: &Path, hardened: bool) -> Result<Vec<Arc<dyn NativePlugin>>> {
+    if hardened && !paths.is_empty() && !integrity::signed() {
+        anyhow::bail!("Native plugins only load in hardened mode when the bundle is signed");
+    }
+    paths
+        .iter()
+        .map(|path| load(&work_dir.join(path)).map(|plugin| Arc::new(plugin) as Arc<dyn NativePlugin>))
+        .collect()
+}
+
+impl NativePlugin for DylibPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn register(&self, registry: &mut ModuleRegistry) {
+        for (name, functions) in &self.modules {
+            registry.module(name, |module| {
+                for (function, call_fn) in functions {
+                    let (call_fn, free) = (*call_fn, self.free);
+                    module.function(function, move |args: Json| call(call_fn, free, &args));
+                }
+            });
+        }
+        for (name, source) in &self.scripts {
+            registry.script(name, source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    unsafe extern "C" fn sum(args: *const c_char, failed: *mut bool) -> *mut c_char {
+        let args: Vec<f64> = serde_json::from_str(&text(args)).unwrap_or_default();
+        if args.is_empty() {
+            *failed = true;
+            return CString::new("nothing to sum").unwrap().into_raw();
+        }
+        CString::new(args.iter().sum::<f64>().to_string()).unwrap().into_raw()
+    }
+
+    unsafe extern "C" fn nothing(_: *const c_char, _: *mut bool) -> *mut c_char {
+        std::ptr::null_mut()
+    }
+
+    unsafe extern "C" fn free(string: *mut c_char) {
+        drop(CString::from_raw(string));
+    }
+
+    #[test]
+    fn test_call() {
+        assert_eq!(call(sum, free, &json!([1, 2, 3.5])), Ok(json!(6.5)));
+        assert_eq!(call(sum, free, &json!([])), Err("nothing to sum".to_string()));
+        assert_eq!(call(nothing, free, &json!([])), Ok(Json::Null));
+    }
+}
@@ -0,0 +1,272 @@
+//! Mouse events and pointer capture for `div({ onMouseDown, onMouseMove, onMouseUp })`
+//!
+//! Mouse handlers are called with an event holding the pointer position in
+//! window coordinates, the button and the modifier keys. Calling
+//! `event.capturePointer()` sends the following moves and the button
+//! release to the element's own handlers, wherever the pointer goes, until
+//! `event.releasePointer()` or the release itself. Knobs, joysticks and
+//! custom sliders are built that way in JS, without a native widget each.
+//!
+//! While the pointer is captured, the window's capture layer takes moves and
+//! releases in the capture phase and stops them there, so nothing under the
+//! pointer reacts to them, like in the DOM.
+
+use gpui::*;
+use rquickjs::{Ctx, Function, IntoJs, Object, Value};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::elements::{EventHandlers, RenderContext};
+use crate::event_manager::HandlerId;
+use crate::throttle::{Limit, Rate};
+
+/// An element's handlers that receive the pointer while it's captured
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub element: String,
+    pub on_move: Option<HandlerId>,
+    pub move_rate: Option<Rate>,
+    pub on_up: Option<HandlerId>,
+}
+
+impl Capture {
+    /// Where moves queue if the move handler is rate-limited
+    fn move_limit(&self) -> Option<Limit> {
+        self.move_rate.map(|rate| Limit::new(&self.element, "mousemove", rate))
+    }
+}
+
+/// The element holding the pointer capture, if any
+///
+/// Cheap to clone; the clones share the capture, including those held by
+/// the JS events' `capturePointer` functions. A capture follows its
+/// element's current handlers, and is released by
+/// [`finish_frame`](Self::finish_frame) once the element is gone.
+#[derive(Clone, Default)]
+pub struct PointerTracker {
+    captured: Arc<Mutex<Option<Capture>>>,
+    seen: Arc<AtomicBool>,
+}
+
+impl PointerTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn captured(&self) -> Option<Capture> {
+        self.captured.lock().unwrap().clone()
+    }
+
+    fn capture(&self, capture: Capture) {
+        *self.captured.lock().unwrap() = Some(capture);
+    }
+
+    /// Release the capture if `element` holds it
+    fn release(&self, element: &str) {
+        let mut captured = self.captured.lock().unwrap();
+        if captured.as_ref().is_some_and(|capture| capture.element == element) {
+            *captured = None;
+        }
+    }
+
+    /// Note that an element with mouse handlers was rendered
+    fn track(&self, capture: &Capture) {
+        if let Some(captured) = self.captured.lock().unwrap().as_mut().filter(|c| c.element == capture.element) {
+            *captured = capture.clone();
+            self.seen.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// End a render pass, releasing a capture whose element is gone
+    pub fn finish_frame(&self) {
+        if !self.seen.swap(false, Ordering::Relaxed) {
+            *self.captured.lock().unwrap() = None;
+        }
+    }
+}
+
+/// A mouse event as passed to JS
+pub struct MouseInfo {
+    /// Position in window coordinates, without the zoom factor
+    position: Point<Pixels>,
+    button: Option<MouseButton>,
+    modifiers: Modifiers,
+    /// What `capturePointer()` captures
+    capture: Capture,
+    tracker: PointerTracker,
+}
+
+impl MouseInfo {
+    fn new(
+        position: Point<Pixels>,
+        button: Option<MouseButton>,
+        modifiers: Modifiers,
+        zoom: f32,
+        capture: &Capture,
+        tracker: &PointerTracker,
+    ) -> Self {
+        Self {
+            position: point(position.x / zoom, position.y / zoom),
+            button,
+            modifiers,
+            capture: capture.clone(),
+            tracker: tracker.clone(),
+        }
+    }
+}
+
+fn button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Right => "right",
+        MouseButton::Middle => "middle",
+        MouseButton::Navigate(NavigationDirection::Back) => "back",
+        MouseButton::Navigate(NavigationDirection::Forward) => "forward",
+    }
+}
+
+impl<'js> IntoJs<'js> for MouseInfo {
+    fn into_js(self, ctx: &Ctx<'js>) -> rquickjs::Result<Value<'js>> {
+        let event = Object::new(ctx.clone())?;
+        event.set("x", f32::from(self.position.x))?;
+        event.set("y", f32::from(self.position.y))?;
+        event.set("button", self.button.map(button_name))?;
+        event.set("shiftKey", self.modifiers.shift)?;
+        event.set("ctrlKey", self.modifiers.control)?;
+        event.set("altKey", self.modifiers.alt)?;
+        event.set("metaKey", self.modifiers.platform)?;
+        let (tracker, capture) = (self.tracker.clone(), self.capture.clone());
+        event.set("capturePointer", Function::new(ctx.clone(), move || tracker.capture(capture.clone()))?)?;
+        let (tracker, element) = (self.tracker, self.capture.element);
+        event.set("releasePointer", Function::new(ctx.clone(), move || tracker.release(&element))?)?;
+        event.into_js(ctx)
+    }
+}
+
+type MouseCallback = Rc<dyn Fn(MouseInfo, &mut Window, &mut App)>;
+
+/// Add the element's mouse handlers to `d`
+pub fn listeners(mut d: Div, id: &str, handlers: &EventHandlers, render_ctx: &RenderContext) -> Div {
+    let tracker = render_ctx.pointers;
+    let capture = Capture {
+        element: id.to_string(),
+        on_move: handlers.on_mouse_move,
+        move_rate: handlers.mouse_move_rate,
+        on_up: handlers.on_mouse_up,
+    };
+    tracker.track(&capture);
+    let zoom = render_ctx.zoom;
+
+    if let Some(handler_id) = handlers.on_mouse_down {
+        let (handler, capture, tracker) = ((render_ctx.mouse_handler)(handler_id, None), capture.clone(), tracker.clone());
+        d = d.on_any_mouse_down(move |event, window, cx| {
+            let info = MouseInfo::new(event.position, Some(event.button), event.modifiers, zoom, &capture, &tracker);
+            handler(info, window, cx);
+        });
+    }
+    if let Some(handler_id) = handlers.on_mouse_move {
+        let handler = (render_ctx.mouse_handler)(handler_id, capture.move_limit());
+        let (capture, tracker) = (capture.clone(), tracker.clone());
+        d = d.on_mouse_move(move |event, window, cx| {
+            let info = MouseInfo::new(event.position, event.pressed_button, event.modifiers, zoom, &capture, &tracker);
+            handler(info, window, cx);
+        });
+    }
+    if let Some(handler_id) = handlers.on_mouse_up {
+        let (handler, capture, tracker) = ((render_ctx.mouse_handler)(handler_id, None), capture, tracker.clone());
+        d = d.on_any_mouse_up(move |event, window, cx| {
+            let info = MouseInfo::new(event.position, Some(event.button), event.modifiers, zoom, &capture, &tracker);
+            handler(info, window, cx);
+        });
+    }
+    d
+}
+
+/// Window-wide listeners delivering the pointer to the capturing element
+///
+/// Render it after the element tree, so the capture has its element's
+/// current handlers.
+pub fn capture_layer(render_ctx: &RenderContext) -> impl IntoElement {
+    let tracker = render_ctx.pointers.clone();
+    let captured = tracker.captured();
+    let callback = |handler_id: Option<HandlerId>, limit: Option<Limit>| -> Option<MouseCallback> {
+        handler_id.map(|handler_id| Rc::from((render_ctx.mouse_handler)(handler_id, limit)))
+    };
+    let on_move = captured.as_ref().and_then(|capture| callback(capture.on_move, capture.move_limit()));
+    let on_up = captured.as_ref().and_then(|capture| callback(capture.on_up, None));
+    let zoom = render_ctx.zoom;
+
+    canvas(
+        |_, _, _| {},
+        move |_, _, window, _| {
+            let Some(capture) = captured else {
+                return;
+            };
+            let (tracker, moved, on_move, on_up) = (tracker.clone(), capture.clone(), on_move, on_up.clone());
+            window.on_mouse_event(move |event: &MouseMoveEvent, phase, window, cx| {
+                if phase != DispatchPhase::Capture || tracker.captured().as_ref() != Some(&moved) {
+                    return;
+                }
+                cx.stop_propagation();
+                let info = MouseInfo::new(event.position, event.pressed_button, event.modifiers, zoom, &moved, &tracker);
+                // The button was released where the window didn't see it
+                if event.pressed_button.is_none() {
+                    tracker.release(&moved.element);
+                    if let Some(on_up) = &on_up {
+                        on_up(info, window, cx);
+                    }
+                } else if let Some(on_move) = &on_move {
+                    on_move(info, window, cx);
+                }
+            });
+            let (tracker, on_up) = (tracker.clone(), on_up);
+            window.on_mouse_event(move |event: &MouseUpEvent, phase, window, cx| {
+                if phase != DispatchPhase::Capture || tracker.captured().as_ref() != Some(&capture) {
+                    return;
+                }
+                cx.stop_propagation();
+                tracker.release(&capture.element);
+                if let Some(on_up) = &on_up {
+                    let info = MouseInfo::new(event.position, Some(event.button), event.modifiers, zoom, &capture, &tracker);
+                    on_up(info, window, cx);
+                }
+            });
+        },
+    )
+    .absolute()
+    .size_full()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(element: &str, on_move: Option<HandlerId>) -> Capture {
+        Capture { element: element.to_string(), on_move, move_rate: None, on_up: None }
+    }
+
+    #[test]
+    fn test_capture_follows_its_element() {
+        let tracker = PointerTracker::new();
+        tracker.capture(capture("knob", Some(1)));
+        // Re-rendering updates the handlers...
+        tracker.track(&capture("other", Some(5)));
+        tracker.track(&capture("knob", Some(2)));
+        tracker.finish_frame();
+        assert_eq!(tracker.captured(), Some(capture("knob", Some(2))));
+        // ...and only the capturing element can release it
+        tracker.release("other");
+        assert!(tracker.captured().is_some());
+        tracker.release("knob");
+        assert!(tracker.captured().is_none());
+    }
+
+    #[test]
+    fn test_capture_released_when_element_is_gone() {
+        let tracker = PointerTracker::new();
+        tracker.capture(capture("knob", None));
+        tracker.track(&capture("other", None));
+        tracker.finish_frame();
+        assert!(tracker.captured().is_none());
+    }
+}
@@ -0,0 +1,362 @@
+//! Paged output: PDF export and printing
+//!
+//! GPUI only draws to windows, so paged output uses its own simplified flow
+//! layout instead of the window's: text elements are laid out top to bottom
+//! in document order with the font size, weight, color and case they set or
+//! inherit, wrapped to the page width and broken across pages. It's meant
+//! for text-heavy reports; backgrounds, borders, images, row layouts and the
+//! app's fonts are not reproduced.
+//!
+//! The PDF is written directly using the standard Helvetica fonts, which
+//! every PDF reader provides, so no font files are embedded.
+
+use gpui::Rgba;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::Command;
+use crate::{descriptor, native_api};
+use crate::elements::Element;
+use crate::tw_parser::{ParsedStyles, TextTransform};
+
+/// Page margin in points
+const MARGIN: f32 = 48.0;
+/// Text size when an element doesn't set one (16px)
+const DEFAULT_FONT_SIZE: f32 = 12.0;
+/// Points per CSS pixel
+const PT_PER_PX: f32 = 0.75;
+
+/// Page size in points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PageSize {
+    pub const A4: Self = Self { width: 595.28, height: 841.89 };
+    pub const LETTER: Self = Self { width: 612.0, height: 792.0 };
+
+    /// Look up a page size by name: `A3`, `A4`, `A5`, `letter` or `legal`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "a3" => Some(Self { width: 841.89, height: 1190.55 }),
+            "a4" => Some(Self::A4),
+            "a5" => Some(Self { width: 419.53, height: 595.28 }),
+            "letter" => Some(Self::LETTER),
+            "legal" => Some(Self { width: 612.0, height: 1008.0 }),
+            _ => None,
+        }
+    }
+}
+
+/// Text style set on an element or inherited from its ancestors
+#[derive(Clone, Copy)]
+struct TextStyle {
+    size: f32,
+    bold: bool,
+    color: Rgba,
+    transform: Option<TextTransform>,
+}
+
+impl TextStyle {
+    const DEFAULT: Self = Self {
+        size: DEFAULT_FONT_SIZE,
+        bold: false,
+        color: Rgba { r: 0.0, g: 0.0, b: 0.0, a: 1.0 },
+        transform: None,
+    };
+
+    /// This style with what `styles` sets over it
+    fn with(self, styles: &ParsedStyles) -> Self {
+        Self {
+            size: styles.font_size.map(|s| f32::from(s) * PT_PER_PX).unwrap_or(self.size),
+            bold: styles.font_weight.map(|w| w.0 >= 600.0).unwrap_or(self.bold),
+            color: styles.text_color.map(Rgba::from).unwrap_or(self.color),
+            transform: styles.text_transform.or(self.transform),
+        }
+    }
+}
+
+/// One laid-out line of text
+struct Line {
+    text: String,
+    size: f32,
+    bold: bool,
+    color: Rgba,
+}
+
+/// Render an element tree to a PDF document
+pub fn export_pdf(root: &Element, page: PageSize) -> Vec<u8> {
+    let mut lines = Vec::new();
+    collect_lines(root, page.width - 2.0 * MARGIN, &mut lines);
+
+    // Break lines into pages; `None` marks paragraph spacing
+    let mut pages: Vec<String> = Vec::new();
+    let mut content = String::new();
+    let mut y = page.height - MARGIN;
+    for line in &lines {
+        let Some(line) = line else {
+            y -= DEFAULT_FONT_SIZE * 0.5;
+            continue;
+        };
+        let advance = line.size * 1.4;
+        if y - advance < MARGIN && !content.is_empty() {
+            pages.push(std::mem::take(&mut content));
+            y = page.height - MARGIN;
+        }
+        y -= advance;
+        let _ = writeln!(
+            content,
+            "BT /{} {:.2} Tf {:.3} {:.3} {:.3} rg {:.2} {:.2} Td ({}) Tj ET",
+            if line.bold { "F2" } else { "F1" },
+            line.size,
+            line.color.r,
+            line.color.g,
+            line.color.b,
+            MARGIN,
+            y,
+            escape(&line.text),
+        );
+    }
+    pages.push(content);
+
+    write_document(&pages, page)
+}
+
+/// Lay out the text elements of a tree as wrapped lines, in document order
+fn collect_lines(root: &Element, width: f32, lines: &mut Vec<Option<Line>>) {
+    let mut stack = vec![(root, TextStyle::DEFAULT)];
+    while let Some((element, inherited)) = stack.pop() {
+        let style = inherited.with(element.styles());
+        let text = match element {
+            Element::Div(div) => {
+                stack.extend(div.children.iter().rev().map(|child| (child, style)));
+                continue;
+            }
+            Element::Text(text) => text,
+        };
+        let TextStyle { size, bold, color, transform } = style;
+        // Helvetica averages about half an em per character
+        let max_chars = ((width / (size * if bold { 0.55 } else { 0.5 })) as usize).max(1);
+
+        let content = match transform {
+            Some(transform) => transform.apply(&text.text),
+            None => text.text.clone(),
+        };
+        for paragraph in content.lines() {
+            for wrapped in wrap(paragraph, max_chars) {
+                lines.push(Some(Line { text: wrapped, size, bold, color }));
+            }
+        }
+        lines.push(None);
+    }
+}
+
+/// Greedy word wrap; words longer than a line are split
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word.to_string();
+        while word.chars().count() > max_chars {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            let split: String = word.chars().take(max_chars).collect();
+            word = word.chars().skip(max_chars).collect();
+            lines.push(split);
+        }
+        let needed = current.chars().count() + usize::from(!current.is_empty()) + word.chars().count();
+        if needed > max_chars && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Escape a PDF string literal; characters outside Latin-1 become `?`
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            '\u{a0}'..='\u{ff}' => {
+                let _ = write!(escaped, "\\{:03o}", c as u32);
+            }
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+/// Assemble the PDF objects and cross-reference table
+fn write_document(pages: &[String], page: PageSize) -> Vec<u8> {
+    let mut objects: Vec<String> = Vec::new();
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 5 + 2 * i).collect();
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()));
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string());
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string());
+    for (content, id) in pages.iter().zip(&page_ids) {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            id + 1,
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        let _ = write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object);
+    }
+    let xref = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = write!(pdf, "{:010} 00000 n \n", offset);
+    }
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref,
+    );
+    pdf.into_bytes()
+}
+
+/// Render the tree given in `options.tree`, or the current root, to PDF
+fn render_options<'js>(ctx: &Ctx<'js>, options: &Object<'js>) -> rquickjs::Result<Vec<u8>> {
+    let page = match options.get::<_, Option<String>>("pageSize")? {
+        Some(name) => PageSize::from_name(&name)
+            .ok_or_else(|| Exception::throw_message(ctx, &format!("Unknown page size '{}'", name)))?,
+        None => PageSize::A4,
+    };
+    let tree: Value = match options.get::<_, Option<Value>>("tree")? {
+        Some(tree) => tree,
+        None => ctx.globals().get("__rootElement")?,
+    };
+
+    // Handlers are functions and drop out of the JSON, which is what we want here
+    let json: Option<String> = ctx.json_stringify(tree)?.map(|s| s.to_string()).transpose()?;
+    let element = json
+        .as_deref()
+        .and_then(|json| descriptor::decode(json).ok())
+        .ok_or_else(|| Exception::throw_message(ctx, "Nothing to export: no element tree"))?
+        .into_element();
+    Ok(export_pdf(&element, page))
+}
+
+/// Send a PDF to the default printer, through a temporary file removed
+/// once the print command is done with it
+fn print_pdf(pdf: &[u8]) -> std::io::Result<()> {
+    let mut file = tempfile::Builder::new().prefix("rasen-print-").suffix(".pdf").tempfile()?;
+    file.write_all(pdf)?;
+    file.flush()?;
+    print_file(file.path())
+}
+
+/// Send a PDF file to the default printer
+fn print_file(path: &Path) -> std::io::Result<()> {
+    let status = if cfg!(target_os = "windows") {
+        // Waiting for the print handler keeps the file until it has read it
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", "Start-Process", "-Wait", "-Verb", "Print", "-FilePath"])
+            .arg(path)
+            .status()?
+    } else {
+        Command::new("lp").arg(path).status()?
+    };
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("print command exited with {}", status)))
+    }
+}
+
+/// Install `rasen.window.print` and `rasen.window.exportPdf`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let window = native_api::namespace(ctx, rasen, "window")?;
+
+    window.set(
+        "exportPdf",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, options: Object<'js>| -> rquickjs::Result<()> {
+            let path: String = options
+                .get::<_, Option<String>>("path")?
+                .ok_or_else(|| Exception::throw_message(&ctx, "exportPdf requires a path"))?;
+            let pdf = render_options(&ctx, &options)?;
+            fs::write(&path, pdf)
+                .map_err(|e| Exception::throw_message(&ctx, &format!("Failed to write {}: {}", path, e)))
+        })?,
+    )?;
+    window.set(
+        "print",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, options: Option<Object<'js>>| -> rquickjs::Result<()> {
+            let options = match options {
+                Some(options) => options,
+                None => Object::new(ctx.clone())?,
+            };
+            let pdf = render_options(&ctx, &options)?;
+            print_pdf(&pdf).map_err(|e| Exception::throw_message(&ctx, &format!("Failed to print: {}", e)))
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("", 10), vec![""]);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a (b) \\"), "a \\(b\\) \\\\");
+        assert_eq!(escape("café ✓"), "caf\\351 ?");
+    }
+
+    #[test]
+    fn test_export_paginates() {
+        let json = format!(
+            r#"{{"type":"div","children":[{}]}}"#,
+            vec![r#"{"type":"text","text":"line"}"#; 100].join(",")
+        );
+        let element = descriptor::decode(&json).unwrap().into_element();
+        let pdf = String::from_utf8(export_pdf(&element, PageSize::A4)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/Count 4 "));
+    }
+
+    #[test]
+    fn test_text_style_is_inherited() {
+        let json = r#"{"type":"div","class":"text-2xl font-bold uppercase","children":[{"type":"text","text":"total"}]}"#;
+        let element = descriptor::decode(json).unwrap().into_element();
+        let pdf = String::from_utf8(export_pdf(&element, PageSize::A4)).unwrap();
+        assert!(pdf.contains("/F2 18.00 Tf"));
+        assert!(pdf.contains("(TOTAL) Tj"));
+    }
+}
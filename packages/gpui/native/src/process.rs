@@ -0,0 +1,348 @@
+//! Child processes for `rasen.process.spawn`
+//!
+//! `spawn(command, args, { onStdout, onStderr, onExit })` starts a process
+//! with piped output and returns `{ pid, kill }` right away. Threads read
+//! each stream and queue its text for the runtime, which wakes and calls
+//! the callbacks on the UI thread, re-rendering afterwards, so build runners
+//! and git clients can stream output into the UI. `onExit` comes after all
+//! of the process's output.
+//!
+//! Like `bus`, the callbacks stay on the JS side in a table keyed by process
+//! ID. Hardened mode disables spawning.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::files::FsError;
+use crate::{js_runtime, native_api};
+
+/// Global holding `{ onStdout, onStderr, onExit }` by process ID
+const CALLBACKS: &str = "__processes";
+
+/// How often a running process is checked for exit and kill requests
+const POLL: Duration = Duration::from_millis(20);
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Allow or forbid spawning (once, before any script runs)
+pub fn init(hardened: bool) {
+    DISABLED.store(hardened, Ordering::Relaxed);
+}
+
+/// Something a child process did, waiting for delivery to JS
+#[derive(Debug, PartialEq)]
+pub enum ProcessEvent {
+    Stdout(u32, String),
+    Stderr(u32, String),
+    /// Exit code, or the signal that ended the process
+    Exit { id: u32, code: Option<i32>, signal: Option<i32> },
+}
+
+/// A runtime's child processes; clones share them
+#[derive(Clone)]
+pub struct Processes {
+    state: Arc<Mutex<ProcessState>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct ProcessState {
+    next_id: u32,
+    events: Vec<ProcessEvent>,
+    /// Kill switches of running processes
+    running: HashMap<u32, mpsc::Sender<()>>,
+}
+
+/// Take the text decodable from the start of `buffer`, leaving a UTF-8
+/// sequence cut off at the end for the next read
+///
+/// Invalid bytes are replaced with U+FFFD.
+pub fn decode(buffer: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut start = 0;
+    loop {
+        match std::str::from_utf8(&buffer[start..]) {
+            Ok(valid) => {
+                text.push_str(valid);
+                start = buffer.len();
+                break;
+            }
+            Err(e) => {
+                let valid_end = start + e.valid_up_to();
+                text.push_str(std::str::from_utf8(&buffer[start..valid_end]).unwrap_or_default());
+                match e.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        start = valid_end + len;
+                    }
+                    // Incomplete sequence at the end
+                    None => {
+                        start = valid_end;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    buffer.drain(..start);
+    text
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn signal(_: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Name of a signal as Node reports it, e.g. `SIGTERM`
+fn signal_name(signal: i32) -> String {
+    match signal {
+        1 => "SIGHUP".to_string(),
+        2 => "SIGINT".to_string(),
+        3 => "SIGQUIT".to_string(),
+        6 => "SIGABRT".to_string(),
+        9 => "SIGKILL".to_string(),
+        13 => "SIGPIPE".to_string(),
+        15 => "SIGTERM".to_string(),
+        other => format!("SIG{}", other),
+    }
+}
+
+impl Processes {
+    /// Create an empty set; the receiver wakes when a process has news
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { state: Arc::default(), wake }, wakeups)
+    }
+
+    fn push(&self, event: ProcessEvent) {
+        self.state.lock().unwrap().events.push(event);
+        let _ = self.wake.unbounded_send(());
+    }
+
+    /// Queue what `stream` prints until it closes
+    fn read(&self, mut stream: impl Read + Send + 'static, id: u32, stderr: bool) -> thread::JoinHandle<()> {
+        let processes = self.clone();
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let mut chunk = [0; 8192];
+            loop {
+                let read = stream.read(&mut chunk).unwrap_or(0);
+                let text = if read == 0 {
+                    // A sequence cut off at the end can't be completed anymore
+                    String::from_utf8_lossy(&buffer).into_owned()
+                } else {
+                    buffer.extend_from_slice(&chunk[..read]);
+                    decode(&mut buffer)
+                };
+                if !text.is_empty() {
+                    processes.push(if stderr { ProcessEvent::Stderr(id, text) } else { ProcessEvent::Stdout(id, text) });
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Wait for `child` to exit, killing it when asked, then queue its exit
+    /// after the rest of its output
+    fn watch(&self, mut child: Child, id: u32, kills: mpsc::Receiver<()>, readers: Vec<thread::JoinHandle<()>>) {
+        let processes = self.clone();
+        thread::spawn(move || {
+            let status = loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break Some(status),
+                    Ok(None) => {}
+                    Err(_) => break None,
+                }
+                match kills.recv_timeout(POLL) {
+                    Ok(()) => {
+                        let _ = child.kill();
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(POLL),
+                }
+            };
+            for reader in readers {
+                let _ = reader.join();
+            }
+            processes.state.lock().unwrap().running.remove(&id);
+            let code = status.and_then(|status| status.code());
+            let signal = status.as_ref().and_then(signal);
+            processes.push(ProcessEvent::Exit { id, code, signal });
+        });
+    }
+
+    fn spawn(&self, command: &str, args: &[String], cwd: Option<&str>, env: &[(String, String)]) -> Result<(u32, u32), FsError> {
+        let mut builder = Command::new(command);
+        builder.args(args).envs(env.iter().cloned()).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        if let Some(cwd) = cwd {
+            builder.current_dir(cwd);
+        }
+        let mut child = builder.spawn().map_err(|e| FsError::io("run", Path::new(command), e))?;
+
+        let (kill, kills) = mpsc::channel();
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            // IDs start at 1, like timer IDs
+            state.next_id += 1;
+            let id = state.next_id;
+            state.running.insert(id, kill);
+            id
+        };
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(self.read(stdout, id, false));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(self.read(stderr, id, true));
+        }
+        let pid = child.id();
+        self.watch(child, id, kills, readers);
+        Ok((id, pid))
+    }
+
+    /// Ask process `id` to stop; returns whether it was still running
+    fn kill(&self, id: u32) -> bool {
+        self.state.lock().unwrap().running.get(&id).is_some_and(|kill| kill.send(()).is_ok())
+    }
+}
+
+/// Call the callbacks for queued process events; returns whether any ran
+pub fn deliver(ctx: &Ctx, processes: &Processes) -> bool {
+    let events = std::mem::take(&mut processes.state.lock().unwrap().events);
+    let Ok(callbacks) = ctx.globals().get::<_, Object>(CALLBACKS) else {
+        return false;
+    };
+
+    let mut delivered = false;
+    for event in events {
+        let (id, name) = match &event {
+            ProcessEvent::Stdout(id, _) => (*id, "onStdout"),
+            ProcessEvent::Stderr(id, _) => (*id, "onStderr"),
+            ProcessEvent::Exit { id, .. } => (*id, "onExit"),
+        };
+        let Ok(entry) = callbacks.get::<_, Object>(id.to_string()) else {
+            continue;
+        };
+        let callback = entry.get::<_, Option<Function>>(name).ok().flatten();
+        let result = match (event, callback) {
+            (ProcessEvent::Stdout(_, text) | ProcessEvent::Stderr(_, text), Some(callback)) => callback.call::<_, Value>((text,)),
+            (ProcessEvent::Exit { code, signal, .. }, callback) => {
+                let _ = callbacks.remove(id.to_string());
+                match callback {
+                    Some(callback) => callback.call::<_, Value>((code, signal.map(signal_name))),
+                    None => continue,
+                }
+            }
+            (_, None) => continue,
+        };
+        match result {
+            Ok(result) => js_runtime::report_rejection(ctx, &result, format!("async process {} {}", id, name)),
+            Err(e) => eprintln!("Error in process {} {}: {:?}", id, name, e),
+        }
+        delivered = true;
+    }
+    delivered
+}
+
+/// Install `rasen.process` for a runtime's processes
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, processes: &Processes) -> rquickjs::Result<()> {
+    ctx.globals().set(CALLBACKS, Object::new(ctx.clone())?)?;
+    let namespace = native_api::namespace(ctx, rasen, "process")?;
+
+    let processes = processes.clone();
+    namespace.set(
+        "spawn",
+        Function::new(
+            ctx.clone(),
+            move |ctx: Ctx<'js>, command: String, args: Opt<Vec<String>>, options: Opt<Object<'js>>| -> rquickjs::Result<Object<'js>> {
+                let throw = |error: FsError| {
+                    let exception = Exception::from_message(ctx.clone(), &error.message)?;
+                    exception.set("code", error.code)?;
+                    Err(ctx.throw(exception.into_value()))
+                };
+                if DISABLED.load(Ordering::Relaxed) {
+                    return throw(FsError { code: "EPERM", message: "Spawning processes is disabled in hardened mode".to_string() });
+                }
+                let options = match options.0 {
+                    Some(options) => options,
+                    None => Object::new(ctx.clone())?,
+                };
+                let cwd: Option<String> = options.get("cwd")?;
+                let env: Vec<(String, String)> = match options.get::<_, Option<Object>>("env")? {
+                    Some(env) => env.props::<String, String>().collect::<rquickjs::Result<_>>()?,
+                    None => Vec::new(),
+                };
+                let (id, pid) = match processes.spawn(&command, &args.0.unwrap_or_default(), cwd.as_deref(), &env) {
+                    Ok(spawned) => spawned,
+                    Err(error) => return throw(error),
+                };
+
+                let entry = Object::new(ctx.clone())?;
+                for name in ["onStdout", "onStderr", "onExit"] {
+                    entry.set(name, options.get::<_, Option<Function>>(name)?)?;
+                }
+                let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+                callbacks.set(id.to_string(), entry)?;
+
+                let handle = Object::new(ctx.clone())?;
+                handle.set("pid", pid)?;
+                let processes = processes.clone();
+                handle.set("kill", Function::new(ctx.clone(), move || processes.kill(id))?)?;
+                Ok(handle)
+            },
+        )?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_keeps_cut_off_characters() {
+        let mut buffer = "héllo".as_bytes().to_vec();
+        let tail = buffer.split_off(2);
+        assert_eq!(decode(&mut buffer), "h");
+        assert_eq!(buffer, vec![0xC3]);
+        buffer.extend(tail);
+        assert_eq!(decode(&mut buffer), "éllo");
+        assert!(buffer.is_empty());
+
+        let mut invalid = vec![b'a', 0xFF, b'b'];
+        assert_eq!(decode(&mut invalid), "a\u{FFFD}b");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_output_comes_before_exit() {
+        let (processes, _wakeups) = Processes::new();
+        let args = ["-c".to_string(), "printf out; printf err >&2; exit 3".to_string()];
+        let (id, _) = processes.spawn("sh", &args, None, &[]).unwrap();
+        let exit = ProcessEvent::Exit { id, code: Some(3), signal: None };
+        while !processes.state.lock().unwrap().events.contains(&exit) {
+            thread::sleep(POLL);
+        }
+        let events = std::mem::take(&mut processes.state.lock().unwrap().events);
+        assert!(events.contains(&ProcessEvent::Stdout(id, "out".to_string())));
+        assert!(events.contains(&ProcessEvent::Stderr(id, "err".to_string())));
+        assert_eq!(events.last(), Some(&exit));
+        assert!(!processes.kill(id));
+    }
+}
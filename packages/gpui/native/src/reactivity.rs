@@ -0,0 +1,417 @@
+//! Built-in reactivity, for scripts bundled without `@rasenjs/reactive-signals`
+//!
+//! Installed with the built-in `@rasenjs/gpui` fallback, as the
+//! `@rasenjs/reactive-signals` module it re-exports. Reads of a ref or
+//! computed inside a tracked function (a computed's getter) subscribe that
+//! function; writes notify the subscribers.
+//!
+//! `reactive(object)` wraps plain objects and arrays in a proxy tracking
+//! each property, nested objects included, so `state.todos[0].done = true`
+//! notifies what read it without a ref per field. Maps, sets and class
+//! instances with private state aren't tracked inside.
+//!
+//! Computed refs are lazy: the getter runs on the first read, and again on
+//! the first read after one of the values it read last time changed.
+//! Effects (`effect`, `watchEffect`, `watch`) run again right away, or call
+//! their `scheduler` instead. Dependencies are collected anew on every run,
+//! so a branch not taken doesn't keep a computed or effect subscribed.
+//!
+//! The built-in `@rasenjs/gpui` renders inside an effect whose scheduler
+//! requests a native re-render, so writing a ref the last render read
+//! repaints the window, whatever the write came from.
+//!
+//! Every change to a ref or reactive property also calls the `on_change`
+//! given to [`report_changes`], so the window can tell handlers that changed
+//! nothing and skip re-rendering after them.
+
+use rquickjs::{Ctx, Function};
+
+const REACTIVITY: &str = r#"
+(function() {
+    // The function being tracked, and the subscriber lists it joined
+    var currentSubscriber = null;
+    var changed = __stateChanged;
+
+    function track(subscribers) {
+        var subscriber = currentSubscriber;
+        if (subscriber && subscribers.indexOf(subscriber) === -1) {
+            subscribers.push(subscriber);
+            subscriber.deps.push(subscribers);
+        }
+    }
+
+    function trigger(subscribers) {
+        // Subscribers may unsubscribe while notified
+        var pending = subscribers.slice();
+        for (var i = 0; i < pending.length; i++) pending[i]();
+    }
+
+    function unsubscribe(subscriber) {
+        for (var i = 0; i < subscriber.deps.length; i++) {
+            var subscribers = subscriber.deps[i];
+            subscribers.splice(subscribers.indexOf(subscriber), 1);
+        }
+        subscriber.deps = [];
+    }
+
+    // Run `fn` with `subscriber` collecting its dependencies afresh
+    function tracked(subscriber, fn) {
+        unsubscribe(subscriber);
+        var outer = currentSubscriber;
+        currentSubscriber = subscriber;
+        try {
+            return fn();
+        } finally {
+            currentSubscriber = outer;
+        }
+    }
+
+    function RefImpl(value) {
+        this._value = value;
+        this._subscribers = [];
+    }
+    RefImpl.prototype = {
+        get value() {
+            track(this._subscribers);
+            return this._value;
+        },
+        set value(newValue) {
+            if (this._value !== newValue) {
+                this._value = newValue;
+                changed();
+                trigger(this._subscribers);
+            }
+        }
+    };
+
+    function ComputedRefImpl(getter, setter) {
+        var self = this;
+        this._getter = getter;
+        this._setter = setter;
+        this._value = undefined;
+        this._dirty = true;
+        this._subscribers = [];
+        // Marks the value stale when a dependency changes; recomputed on read
+        this._invalidate = function() {
+            if (!self._dirty) {
+                self._dirty = true;
+                trigger(self._subscribers);
+            }
+        };
+        this._invalidate.deps = [];
+    }
+    ComputedRefImpl.prototype = {
+        get value() {
+            track(this._subscribers);
+            if (this._dirty) {
+                this._value = tracked(this._invalidate, this._getter);
+                this._dirty = false;
+            }
+            return this._value;
+        },
+        set value(newValue) {
+            if (!this._setter) throw new TypeError('computed value is read-only');
+            this._setter(newValue);
+        }
+    };
+
+    // Run `fn` now (unless `lazy`) and whenever what it read changes; returns
+    // a runner, which runs it and returns its result, with `stop()`
+    function effect(fn, options) {
+        options = options || {};
+        var active = true;
+        var job = function() {
+            if (options.scheduler) options.scheduler();
+            else runner();
+        };
+        job.deps = [];
+        var runner = function() {
+            return active ? tracked(job, fn) : fn();
+        };
+        runner.stop = function() {
+            active = false;
+            unsubscribe(job);
+        };
+        if (!options.lazy) runner();
+        return runner;
+    }
+
+    // Run `fn` now and whenever what it read changes; returns a stop function
+    function watchEffect(fn) {
+        return effect(fn).stop;
+    }
+
+    // Call `callback(value, oldValue)` when the source's value changes, like
+    // @rasenjs/reactive-signals; `immediate` calls it right away too
+    function watch(source, callback, options) {
+        var getter = typeof source === 'function' ? source : function() { return source.value; };
+        var oldValue;
+        var runner = effect(getter, {
+            lazy: true,
+            scheduler: function() {
+                var value = runner();
+                if (value !== oldValue) {
+                    var previous = oldValue;
+                    oldValue = value;
+                    callback(value, previous);
+                }
+            }
+        });
+        oldValue = runner();
+        if (options && options.immediate) callback(oldValue, oldValue);
+        return runner.stop;
+    }
+
+    // Proxies by target, and subscriber lists by target and property
+    var proxies = new WeakMap();
+    var targets = new WeakMap();
+    var propertySubscribers = new WeakMap();
+    // Stands for "the set of keys", read by iteration and changed by adding
+    // or deleting properties
+    var KEYS = Symbol('keys');
+
+    function subscribersOf(target, key) {
+        var byKey = propertySubscribers.get(target);
+        if (!byKey) {
+            byKey = new Map();
+            propertySubscribers.set(target, byKey);
+        }
+        var subscribers = byKey.get(key);
+        if (!subscribers) {
+            subscribers = [];
+            byKey.set(key, subscribers);
+        }
+        return subscribers;
+    }
+
+    function trackProperty(target, key) {
+        if (currentSubscriber) track(subscribersOf(target, key));
+    }
+
+    // Called only for actual changes
+    function triggerProperty(target, key) {
+        changed();
+        var byKey = propertySubscribers.get(target);
+        var subscribers = byKey && byKey.get(key);
+        if (subscribers) trigger(subscribers);
+    }
+
+    function isPlain(value) {
+        if (Array.isArray(value)) return true;
+        var proto = Object.getPrototypeOf(value);
+        return proto === Object.prototype || proto === null;
+    }
+
+    var handlers = {
+        get: function(target, key, receiver) {
+            var value = Reflect.get(target, key, receiver);
+            if (typeof key === 'symbol') return value;
+            trackProperty(target, key);
+            return value !== null && typeof value === 'object' ? reactive(value) : value;
+        },
+        set: function(target, key, value, receiver) {
+            var had = Object.prototype.hasOwnProperty.call(target, key);
+            var old = target[key];
+            var length = Array.isArray(target) ? target.length : 0;
+            var result = Reflect.set(target, key, toRaw(value), receiver);
+            if (!had) {
+                triggerProperty(target, key);
+                triggerProperty(target, KEYS);
+            } else if (old !== target[key]) {
+                triggerProperty(target, key);
+            }
+            if (Array.isArray(target) && target.length !== length) {
+                triggerProperty(target, 'length');
+                triggerProperty(target, KEYS);
+            }
+            return result;
+        },
+        deleteProperty: function(target, key) {
+            var had = Object.prototype.hasOwnProperty.call(target, key);
+            var result = Reflect.deleteProperty(target, key);
+            if (had && result) {
+                triggerProperty(target, key);
+                triggerProperty(target, KEYS);
+            }
+            return result;
+        },
+        has: function(target, key) {
+            trackProperty(target, key);
+            return Reflect.has(target, key);
+        },
+        ownKeys: function(target) {
+            trackProperty(target, KEYS);
+            if (Array.isArray(target)) trackProperty(target, 'length');
+            return Reflect.ownKeys(target);
+        }
+    };
+
+    // A deep reactive proxy of a plain object or array; other values are
+    // returned as they are
+    function reactive(target) {
+        if (target === null || typeof target !== 'object' || targets.has(target) || !isPlain(target)) return target;
+        var proxy = proxies.get(target);
+        if (!proxy) {
+            proxy = new Proxy(target, handlers);
+            proxies.set(target, proxy);
+            targets.set(proxy, target);
+        }
+        return proxy;
+    }
+
+    function isReactive(value) { return value !== null && typeof value === 'object' && targets.has(value); }
+
+    function toRaw(value) {
+        return isReactive(value) ? targets.get(value) : value;
+    }
+
+    function ref(value) { return new RefImpl(value); }
+
+    // computed(getter) or computed({ get, set })
+    function computed(options) {
+        if (typeof options === 'function') return new ComputedRefImpl(options, null);
+        return new ComputedRefImpl(options.get, options.set);
+    }
+
+    function isRef(value) { return value instanceof RefImpl || value instanceof ComputedRefImpl; }
+
+    function unref(value) {
+        if (value && typeof value === 'object' && 'value' in value) {
+            return value.value;
+        }
+        return value;
+    }
+
+    __modules['@rasenjs/reactive-signals'] = {
+        ref: ref,
+        computed: computed,
+        effect: effect,
+        watchEffect: watchEffect,
+        watch: watch,
+        reactive: reactive,
+        isReactive: isReactive,
+        toRaw: toRaw,
+        unref: unref,
+        isRef: isRef
+    };
+})();
+"#;
+
+/// Call `on_change` whenever a ref or reactive property changes, as the
+/// built-in module or a bundled `@rasenjs/gpui` reports it
+pub fn report_changes<F>(ctx: &Ctx, on_change: F) -> rquickjs::Result<()>
+where
+    F: Fn() + Send + 'static,
+{
+    ctx.globals().set("__stateChanged", Function::new(ctx.clone(), on_change)?)
+}
+
+/// Define the built-in `@rasenjs/reactive-signals` module, reporting
+/// changes through [`report_changes`]
+pub fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(REACTIVITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rquickjs::{Context, Runtime};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_computed_tracks_dependencies() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            ctx.eval::<(), _>("var __modules = {};").unwrap();
+            report_changes(&ctx, || {}).unwrap();
+            install(&ctx).unwrap();
+            let runs: Vec<i32> = ctx
+                .eval(
+                    "var r = __modules['@rasenjs/reactive-signals'];
+                     var useA = r.ref(true), a = r.ref(1), b = r.ref(10), runs = 0;
+                     var picked = r.computed(function() { runs++; return useA.value ? a.value : b.value; });
+                     var doubled = r.computed(function() { return picked.value * 2; });
+                     var seen = [doubled.value, runs];
+                     seen.push(doubled.value, runs);
+                     a.value = 2;
+                     seen.push(doubled.value, runs);
+                     useA.value = false;
+                     a.value = 3;
+                     seen.push(doubled.value, runs);
+                     a.value = 4;
+                     seen.push(picked.value, runs);
+                     seen",
+                )
+                .unwrap();
+            // Cached until a dependency changes; `a` is dropped once unread
+            assert_eq!(runs, vec![2, 1, 2, 1, 4, 2, 20, 3, 10, 3]);
+        });
+    }
+
+    #[test]
+    fn test_effects_and_watch() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counted = changes.clone();
+        context.with(|ctx| {
+            ctx.eval::<(), _>("var __modules = {};").unwrap();
+            report_changes(&ctx, move || {
+                counted.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+            install(&ctx).unwrap();
+            let log: Vec<String> = ctx
+                .eval(
+                    "var r = __modules['@rasenjs/reactive-signals'];
+                     var count = r.ref(1), log = [], scheduled = 0;
+                     var stop = r.watchEffect(function() { log.push('effect ' + count.value); });
+                     r.watch(count, function(value, old) { log.push('watch ' + old + '->' + value); });
+                     var render = r.effect(function() { return count.value; }, { lazy: true, scheduler: function() { scheduled++; } });
+                     render();
+                     count.value = 2;
+                     stop();
+                     count.value = 3;
+                     count.value = 3;
+                     log.push('scheduled ' + scheduled);
+                     log",
+                )
+                .unwrap();
+            assert_eq!(log, vec!["effect 1", "effect 2", "watch 1->2", "watch 2->3", "scheduled 2"]);
+        });
+        // Writing the value a ref already has isn't a change
+        assert_eq!(changes.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_reactive_objects() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            ctx.eval::<(), _>("var __modules = {};").unwrap();
+            report_changes(&ctx, || {}).unwrap();
+            install(&ctx).unwrap();
+            let log: Vec<String> = ctx
+                .eval(
+                    "var r = __modules['@rasenjs/reactive-signals'];
+                     var state = r.reactive({ todos: [{ title: 'a', done: false }], filter: 'all' }), log = [];
+                     var open = r.computed(function() {
+                         return state.todos.filter(function(todo) { return !todo.done; }).length;
+                     });
+                     r.watchEffect(function() { log.push(open.value + ' open'); });
+                     state.todos[0].done = true;
+                     state.todos.push({ title: 'b', done: false });
+                     state.filter = 'done';
+                     r.watchEffect(function() { log.push(Object.keys(state).join()); });
+                     delete state.filter;
+                     log.push(String(r.isReactive(state.todos[1]) && r.toRaw(state.todos) !== state.todos));
+                     log",
+                )
+                .unwrap();
+            // Changing `filter`, which nothing counted, doesn't re-run the count
+            assert_eq!(log, vec!["1 open", "0 open", "1 open", "todos,filter", "todos", "true"]);
+        });
+    }
+}
@@ -0,0 +1,79 @@
+//! Sanitization of text coming from JS
+//!
+//! Text props may carry user-generated content. Before it reaches the text
+//! system it is cleaned up so it can't spoof surrounding UI: bidi overrides
+//! that reorder what's displayed are dropped, control characters are shown
+//! as visible symbols, and very long strings are cut off.
+
+/// Longest text (in chars) rendered by a single text element
+pub const MAX_TEXT_LEN: usize = 10_000;
+
+/// Clean up a text prop for display
+pub fn text(input: &str) -> String {
+    let mut output = String::with_capacity(input.len().min(MAX_TEXT_LEN));
+    let mut chars = input.chars().peekable();
+    let mut len = 0;
+
+    while let Some(c) = chars.next() {
+        if len == MAX_TEXT_LEN {
+            output.push('…');
+            break;
+        }
+        match c {
+            // Normalize line endings
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                output.push('\n');
+            }
+            '\n' | '\t' => output.push(c),
+            c if is_bidi_control(c) => continue,
+            // C0 controls and DEL become their Control Pictures (␀, ␛, ␡)
+            '\u{0}'..='\u{1f}' => output.push(char::from_u32(0x2400 + c as u32).unwrap_or('\u{fffd}')),
+            '\u{7f}' => output.push('\u{2421}'),
+            '\u{80}'..='\u{9f}' => output.push('\u{fffd}'),
+            c => output.push(c),
+        }
+        len += 1;
+    }
+
+    output
+}
+
+/// Explicit directional formatting characters (embeddings, overrides,
+/// isolates and marks)
+fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_bidi_overrides() {
+        assert_eq!(text("invoice\u{202e}fdp.exe"), "invoicefdp.exe");
+        assert_eq!(text("\u{2067}abc\u{2069}"), "abc");
+        assert_eq!(text("שלום"), "שלום");
+    }
+
+    #[test]
+    fn test_escapes_control_characters() {
+        assert_eq!(text("a\u{0}b\u{1b}[31m\u{7f}"), "a␀b␛[31m␡");
+        assert_eq!(text("line\r\nnext\rlast\ttab"), "line\nnext\nlast\ttab");
+        assert_eq!(text("\u{85}"), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_truncates_long_text() {
+        let long = "x".repeat(MAX_TEXT_LEN + 5);
+        let result = text(&long);
+        assert_eq!(result.chars().count(), MAX_TEXT_LEN + 1);
+        assert!(result.ends_with('…'));
+        assert_eq!(text(&"x".repeat(MAX_TEXT_LEN)).chars().count(), MAX_TEXT_LEN);
+    }
+}
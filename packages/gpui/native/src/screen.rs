@@ -0,0 +1,102 @@
+//! Displays on `rasen.screen.getDisplays()`
+//!
+//! JS can't ask the platform from inside a call, so the app records the
+//! displays before the script runs and the windows again as they render, and
+//! `getDisplays()` answers from the last record. The platform only reports a
+//! display's scale factor through a window on it, so `scaleFactor` is `null`
+//! for a display none of the app's windows has been on.
+
+use gpui::*;
+use rquickjs::{Ctx, Function, Object};
+use serde::Serialize;
+use std::rc::Rc;
+use std::sync::Mutex;
+use crate::{native_api, values};
+
+static DISPLAYS: Mutex<Vec<DisplayInfo>> = Mutex::new(Vec::new());
+
+/// A display as JS sees it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayInfo {
+    /// What the `display` window option takes
+    pub id: u32,
+    /// Bounds on the desktop in logical pixels
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub scale_factor: Option<f32>,
+    pub primary: bool,
+}
+
+/// Record the displays connected now
+pub fn update(cx: &App) {
+    let primary = cx.primary_display().map(|display| u32::from(display.id()));
+    let mut displays = DISPLAYS.lock().unwrap();
+    let scale_factor = |id: u32| displays.iter().find(|known| known.id == id).and_then(|known| known.scale_factor);
+    let connected: Vec<DisplayInfo> = cx
+        .displays()
+        .iter()
+        .map(|display| {
+            let id = u32::from(display.id());
+            let bounds = display.bounds();
+            DisplayInfo {
+                id,
+                x: bounds.origin.x.into(),
+                y: bounds.origin.y.into(),
+                width: bounds.size.width.into(),
+                height: bounds.size.height.into(),
+                scale_factor: scale_factor(id),
+                primary: Some(id) == primary,
+            }
+        })
+        .collect();
+    *displays = connected;
+}
+
+/// Record the displays, with the scale factor of the one `window` is on
+pub fn record_window(window: &Window, cx: &App) {
+    update(cx);
+    let Some(id) = window.display(cx).map(|display| u32::from(display.id())) else {
+        return;
+    };
+    if let Some(display) = DISPLAYS.lock().unwrap().iter_mut().find(|display| display.id == id) {
+        display.scale_factor = Some(window.scale_factor());
+    }
+}
+
+/// The connected display with the ID `getDisplays()` reported, if any
+pub fn find(id: u32, cx: &App) -> Option<Rc<dyn PlatformDisplay>> {
+    cx.displays().into_iter().find(|display| u32::from(display.id()) == id)
+}
+
+/// Install `rasen.screen`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let screen = native_api::namespace(ctx, rasen, "screen")?;
+    screen.set(
+        "getDisplays",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>| {
+            let displays = DISPLAYS.lock().unwrap().clone();
+            values::to_js(&ctx, &displays)
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_json() {
+        let display = DisplayInfo { id: 2, x: -1440.0, y: 0.0, width: 1440.0, height: 900.0, scale_factor: None, primary: false };
+        assert_eq!(
+            serde_json::to_value(&display).unwrap(),
+            serde_json::json!({
+                "id": 2, "x": -1440.0, "y": 0.0, "width": 1440.0, "height": 900.0,
+                "scaleFactor": null, "primary": false,
+            })
+        );
+    }
+}
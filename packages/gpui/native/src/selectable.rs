@@ -0,0 +1,279 @@
+//! Rubber band selection for `selectable({ items, key, render, onSelectionChange })`
+//!
+//! Dragging across a selectable container draws a marquee and selects the
+//! items (its children) that it touches, hit-tested against the bounds they
+//! were last painted at. Shift, Ctrl or Cmd add to the selection instead of
+//! replacing it. A click without dragging selects the item under the pointer
+//! (toggles it, with a modifier), or clears the selection on empty space.
+//!
+//! The JS side owns the selection: it passes the item keys and selected keys
+//! with each render and is called with the new keys whenever they change.
+
+use gpui::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::elements::RenderContext;
+
+/// Pointer travel before a press becomes a marquee drag
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Item keys and selection of a `selectable` element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    /// Keys of the children, in document order
+    pub keys: Vec<String>,
+    pub selected: Vec<String>,
+}
+
+/// Rectangle spanned by two corners
+pub fn marquee(a: Point<Pixels>, b: Point<Pixels>) -> Bounds<Pixels> {
+    let origin = point(a.x.min(b.x), a.y.min(b.y));
+    Bounds { origin, size: size(a.x.max(b.x) - origin.x, a.y.max(b.y) - origin.y) }
+}
+
+/// Keys of the items touched by `rect`, added to `base`, in item order
+pub fn select(keys: &[String], items: &[Bounds<Pixels>], base: &[String], rect: Bounds<Pixels>) -> Vec<String> {
+    keys.iter()
+        .zip(items)
+        .filter(|(key, bounds)| base.contains(key) || bounds.intersects(&rect))
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// Selection after a click at `position`, toggling with `additive`
+pub fn click(selection: &Selection, items: &[Bounds<Pixels>], position: Point<Pixels>, additive: bool) -> Vec<String> {
+    let hit = selection.keys.iter().zip(items).find(|(_, bounds)| bounds.contains(&position)).map(|(key, _)| key);
+    match hit {
+        Some(key) if additive && selection.selected.contains(key) => {
+            selection.selected.iter().filter(|selected| *selected != key).cloned().collect()
+        }
+        Some(key) if additive => {
+            // Keep item order
+            selection.keys.iter().filter(|k| *k == key || selection.selected.contains(k)).cloned().collect()
+        }
+        Some(key) => vec![key.clone()],
+        None if additive => selection.selected.clone(),
+        None => Vec::new(),
+    }
+}
+
+/// Item bounds and marquee drags of selectable containers, kept across frames
+///
+/// Works like [`SortTracker`](crate::sortable::SortTracker): cheap to clone,
+/// and containers not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct SelectTracker {
+    containers: Rc<RefCell<HashMap<String, Tracked>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+#[derive(Default)]
+struct Tracked {
+    selection: Selection,
+    /// Where the children were last painted, in document order
+    items: Vec<Bounds<Pixels>>,
+    band: Option<Band>,
+}
+
+/// A press that may become a marquee drag
+struct Band {
+    origin: Point<Pixels>,
+    current: Point<Pixels>,
+    additive: bool,
+    dragged: bool,
+    /// Last selection reported during the drag
+    reported: Vec<String>,
+}
+
+type SelectionCallback = Rc<dyn Fn(Vec<String>, &mut Window, &mut App)>;
+
+impl SelectTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn track(&self, id: &str, selection: &Selection) {
+        self.seen.borrow_mut().insert(id.to_string());
+        self.containers.borrow_mut().entry(id.to_string()).or_default().selection = selection.clone();
+    }
+
+    fn set_items(&self, id: &str, items: Vec<Bounds<Pixels>>) {
+        if let Some(tracked) = self.containers.borrow_mut().get_mut(id) {
+            tracked.items = items;
+        }
+    }
+
+    fn begin(&self, id: &str, position: Point<Pixels>, additive: bool) {
+        if let Some(tracked) = self.containers.borrow_mut().get_mut(id) {
+            let reported = tracked.selection.selected.clone();
+            tracked.band = Some(Band { origin: position, current: position, additive, dragged: false, reported });
+        }
+    }
+
+    /// The marquee being dragged in container `id`
+    fn marquee(&self, id: &str) -> Option<Bounds<Pixels>> {
+        let containers = self.containers.borrow();
+        let band = containers.get(id)?.band.as_ref().filter(|band| band.dragged)?;
+        Some(marquee(band.origin, band.current))
+    }
+
+    fn dragging(&self, id: &str) -> bool {
+        self.containers.borrow().get(id).is_some_and(|tracked| tracked.band.is_some())
+    }
+
+    /// Follow the pointer; returns the new selection if it changed
+    fn drag_to(&self, id: &str, position: Point<Pixels>) -> Option<Vec<String>> {
+        let mut containers = self.containers.borrow_mut();
+        let tracked = containers.get_mut(id)?;
+        let band = tracked.band.as_mut()?;
+        band.current = position;
+        let delta = position - band.origin;
+        if !band.dragged && f32::from(delta.x).hypot(f32::from(delta.y)) < DRAG_THRESHOLD {
+            return None;
+        }
+        band.dragged = true;
+        let base: &[String] = if band.additive { &tracked.selection.selected } else { &[] };
+        let selected = select(&tracked.selection.keys, &tracked.items, base, marquee(band.origin, band.current));
+        if selected == band.reported {
+            return None;
+        }
+        band.reported = selected.clone();
+        Some(selected)
+    }
+
+    /// End a press; a click (no drag) returns the new selection if it changed
+    fn end(&self, id: &str) -> Option<Vec<String>> {
+        let mut containers = self.containers.borrow_mut();
+        let tracked = containers.get_mut(id)?;
+        let band = tracked.band.take()?;
+        if band.dragged {
+            return None;
+        }
+        let selected = click(&tracked.selection, &tracked.items, band.origin, band.additive);
+        (selected != tracked.selection.selected).then_some(selected)
+    }
+
+    /// End a render pass, dropping state for containers that are gone
+    pub fn finish_frame(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.containers.borrow_mut().retain(|id, _| seen.contains(id));
+    }
+}
+
+/// Make `container` a selectable container with the given selection
+pub fn container(
+    container: Div,
+    id: &str,
+    selection: &Selection,
+    on_change: Option<Box<dyn Fn(Vec<String>, &mut Window, &mut App) + 'static>>,
+    render_ctx: &RenderContext,
+) -> Div {
+    let tracker = render_ctx.selections;
+    tracker.track(id, selection);
+    let on_change: SelectionCallback = match on_change {
+        Some(on_change) => Rc::from(on_change),
+        None => Rc::new(|_, _, _| {}),
+    };
+    let dark = render_ctx.dark;
+
+    // Paints the marquee, and follows the pointer anywhere in the window
+    // while a press lasts
+    let (painter, container_id) = (tracker.clone(), id.to_string());
+    let overlay = canvas(
+        |_, _, _| {},
+        move |_, _, window, _| {
+            if let Some(rect) = painter.marquee(&container_id) {
+                let (background, border) = if dark {
+                    (hsla(0.6, 0.9, 0.6, 0.15), hsla(0.6, 0.9, 0.6, 0.8))
+                } else {
+                    (hsla(0.6, 0.9, 0.5, 0.12), hsla(0.6, 0.9, 0.5, 0.8))
+                };
+                window.paint_quad(fill(rect, background).border_widths(px(1.0)).border_color(border));
+            }
+            if !painter.dragging(&container_id) {
+                return;
+            }
+            let (tracker, id, change) = (painter.clone(), container_id.clone(), on_change.clone());
+            window.on_mouse_event(move |event: &MouseMoveEvent, phase, window, cx| {
+                if phase != DispatchPhase::Bubble {
+                    return;
+                }
+                // The button was released before this listener was registered
+                let selected = if event.pressed_button == Some(MouseButton::Left) {
+                    tracker.drag_to(&id, event.position)
+                } else {
+                    tracker.end(&id)
+                };
+                if let Some(selected) = selected {
+                    change(selected, window, cx);
+                }
+                window.refresh();
+            });
+            let (tracker, id, change) = (painter.clone(), container_id.clone(), on_change.clone());
+            window.on_mouse_event(move |event: &MouseUpEvent, phase, window, cx| {
+                if phase != DispatchPhase::Bubble || event.button != MouseButton::Left {
+                    return;
+                }
+                if let Some(selected) = tracker.end(&id) {
+                    change(selected, window, cx);
+                }
+                window.refresh();
+            });
+        },
+    )
+    .absolute()
+    .size_full();
+
+    let (measure, press, container_id) = (tracker.clone(), tracker.clone(), id.to_string());
+    let count = selection.keys.len();
+    let id = id.to_string();
+    container
+        .child(overlay)
+        .on_children_prepainted(move |bounds, _, _| {
+            measure.set_items(&container_id, bounds.into_iter().take(count).collect());
+        })
+        .on_mouse_down(MouseButton::Left, move |event, window, _| {
+            let modifiers = &event.modifiers;
+            press.begin(&id, event.position, modifiers.shift || modifiers.control || modifiers.platform);
+            window.refresh();
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<String> {
+        vec!["a".to_string(), "b".to_string(), "c".to_string()]
+    }
+
+    /// Three 10x10 items in a row, 10px apart
+    fn items() -> Vec<Bounds<Pixels>> {
+        (0..3).map(|i| Bounds::new(point(px(i as f32 * 20.0), px(0.0)), size(px(10.0), px(10.0)))).collect()
+    }
+
+    #[test]
+    fn test_marquee_selects_touched_items() {
+        let rect = marquee(point(px(25.0), px(5.0)), point(px(5.0), px(20.0)));
+        assert_eq!(rect.origin, point(px(5.0), px(5.0)));
+        assert_eq!(select(&keys(), &items(), &[], rect), vec!["a", "b"]);
+        // Additive drags keep the selection, in item order
+        assert_eq!(select(&keys(), &items(), &["c".to_string()], rect), vec!["a", "b", "c"]);
+        let gap = marquee(point(px(12.0), px(0.0)), point(px(18.0), px(10.0)));
+        assert!(select(&keys(), &items(), &[], gap).is_empty());
+    }
+
+    #[test]
+    fn test_click() {
+        let selection = Selection { keys: keys(), selected: vec!["c".to_string()] };
+        let on_b = point(px(25.0), px(5.0));
+        let empty = point(px(15.0), px(5.0));
+        assert_eq!(click(&selection, &items(), on_b, false), vec!["b"]);
+        assert_eq!(click(&selection, &items(), on_b, true), vec!["b", "c"]);
+        assert!(click(&selection, &items(), point(px(45.0), px(5.0)), true).is_empty());
+        assert!(click(&selection, &items(), empty, false).is_empty());
+        assert_eq!(click(&selection, &items(), empty, true), vec!["c"]);
+    }
+}
@@ -0,0 +1,321 @@
+//! Session restore
+//!
+//! The runtime keeps a small session file per app recording where its
+//! windows were and which route each reported through
+//! `rasen.session.setRoute` (or a `createSessionHistory` router). It holds
+//! the main window and the windows `rasen.window.open` opened with a `name`,
+//! and is written as things change rather than on quit, so the previous
+//! layout comes back after an update or a crash.
+//!
+//! The main window reopens where it was. On startup,
+//! `rasen.session.restore(components)` reopens the other windows, each with
+//! the component registered under its name, at its bounds and route.
+
+use gpui::{point, px, size, Bounds, WindowBounds};
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Function, Object};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use crate::config::RasenConfig;
+use crate::integrity;
+use crate::window::{WindowId, MAIN_WINDOW};
+
+static STORE: OnceLock<SessionStore> = OnceLock::new();
+
+/// Reopens the saved windows with the app's components, once
+const RESTORE: &str = r#"
+(function(windows, rasen) {
+    var restored = false;
+    return function(components) {
+        if (components && !restored) {
+            restored = true;
+            windows.slice(1).forEach(function(saved) {
+                var Component = components[saved.name];
+                if (typeof Component !== 'function') return;
+                rasen.window.open(Component, { name: saved.name, route: saved.route });
+            });
+        }
+        return windows;
+    };
+})
+"#;
+
+/// Saved state of all windows, the main window first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub windows: Vec<WindowSession>,
+}
+
+/// Saved state of one window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WindowSession {
+    /// Name `rasen.window.open` gave it; `None` for the main window
+    #[serde(default)]
+    pub name: Option<String>,
+    pub bounds: Option<SavedBounds>,
+    pub route: Option<String>,
+}
+
+/// Window placement in logical pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SavedBounds {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+impl SavedBounds {
+    pub fn from_window_bounds(bounds: WindowBounds) -> Self {
+        let (rect, maximized) = match bounds {
+            WindowBounds::Windowed(rect) => (rect, false),
+            WindowBounds::Maximized(rect) | WindowBounds::Fullscreen(rect) => (rect, true),
+        };
+        Self {
+            x: rect.origin.x.into(),
+            y: rect.origin.y.into(),
+            width: rect.size.width.into(),
+            height: rect.size.height.into(),
+            maximized,
+        }
+    }
+
+    pub fn to_window_bounds(self) -> WindowBounds {
+        let rect = Bounds::new(point(px(self.x), px(self.y)), size(px(self.width), px(self.height)));
+        if self.maximized {
+            WindowBounds::Maximized(rect)
+        } else {
+            WindowBounds::Windowed(rect)
+        }
+    }
+}
+
+/// The session as it was at launch plus the one being recorded
+struct SessionStore {
+    path: PathBuf,
+    previous: Session,
+    /// Windows of the previous session no window has reopened yet
+    unclaimed: Mutex<Vec<WindowSession>>,
+    current: Mutex<BTreeMap<WindowId, WindowSession>>,
+    /// Set as the app quits, so the windows closing then stay saved
+    finished: AtomicBool,
+}
+
+/// Load the previous session for this app
+///
+/// The session lives in the app's directory (see [`app_dir`]).
+pub fn init(config: &RasenConfig, project: &Path) {
+    let path = app_dir(config, project).join("session.json");
+
+    let previous: Session = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let main = WindowSession { name: None, ..previous.windows.first().cloned().unwrap_or_default() };
+
+    let _ = STORE.set(SessionStore {
+        path,
+        unclaimed: Mutex::new(previous.windows.iter().skip(1).cloned().collect()),
+        previous,
+        current: Mutex::new(BTreeMap::from([(MAIN_WINDOW, main)])),
+        finished: AtomicBool::new(false),
+    });
+}
+
+/// Directory for the files the runtime keeps for the app at `project`
+///
+/// Lives in the platform's per-user data directory, keyed by the project's
+/// path (see [`project_key`]) after the configured app ID or name, which
+/// only make it easier to find.
+pub fn app_dir(config: &RasenConfig, project: &Path) -> PathBuf {
+    let name = config
+        .window
+        .app_id
+        .clone()
+        .or_else(|| config.app.name.clone())
+        .unwrap_or_else(|| "app".to_string());
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    data_dir().join("rasen").join(format!("{}-{}", name, project_key(project)))
+}
+
+/// Key for the project at `project`: a hash of its canonical path, so apps
+/// in different projects never share files
+pub fn project_key(project: &Path) -> String {
+    let path = project.canonicalize().unwrap_or_else(|_| project.to_path_buf());
+    let digest = Sha256::digest(path.to_string_lossy().as_bytes());
+    integrity::to_hex(&digest[..8])
+}
+
+/// Directory of the single-instance lock of the project at `project`
+pub fn instance_dir(project: &Path) -> PathBuf {
+    data_dir().join("rasen").join("instances").join(project_key(project))
+}
+
+/// Saved bounds of the main window, if any
+pub fn main_window_bounds() -> Option<WindowBounds> {
+    let store = STORE.get()?;
+    let bounds = store.previous.windows.first()?.bounds?;
+    Some(bounds.to_window_bounds())
+}
+
+/// Track window `id`, opened under `name` at `route`; returns the bounds of
+/// the first window of the previous session with that name, whose place it
+/// takes
+pub fn open_window(id: WindowId, name: &str, route: Option<String>) -> Option<WindowBounds> {
+    let saved = STORE.get().and_then(|store| {
+        let mut unclaimed = store.unclaimed.lock().unwrap();
+        let index = unclaimed.iter().position(|saved| saved.name.as_deref() == Some(name))?;
+        Some(unclaimed.remove(index))
+    });
+    let bounds = saved.and_then(|saved| saved.bounds);
+    let window = WindowSession { name: Some(name.to_string()), bounds, route };
+    update(|windows| {
+        windows.insert(id, window);
+    });
+    bounds.map(SavedBounds::to_window_bounds)
+}
+
+/// Stop tracking window `id`, closed before the app quits
+pub fn close_window(id: WindowId) {
+    if STORE.get().is_some_and(|store| store.finished.load(Ordering::SeqCst)) {
+        return;
+    }
+    update(|windows| {
+        windows.remove(&id);
+    });
+}
+
+/// Keep the session as it is while the app quits
+pub fn finish() {
+    if let Some(store) = STORE.get() {
+        store.finished.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Record the bounds of window `id`, if it's tracked
+pub fn set_window_bounds(id: WindowId, bounds: WindowBounds) {
+    update(|windows| {
+        if let Some(window) = windows.get_mut(&id) {
+            window.bounds = Some(SavedBounds::from_window_bounds(bounds));
+        }
+    });
+}
+
+fn update(f: impl FnOnce(&mut BTreeMap<WindowId, WindowSession>)) {
+    let Some(store) = STORE.get() else { return };
+    let mut windows = store.current.lock().unwrap();
+    f(&mut windows);
+
+    let session = Session { windows: windows.values().cloned().collect() };
+    let result = fs::create_dir_all(store.path.parent().unwrap())
+        .and_then(|_| fs::write(&store.path, serde_json::to_string_pretty(&session).unwrap()));
+    if let Err(e) = result {
+        eprintln!("Warning: failed to save session to {}: {}", store.path.display(), e);
+    }
+}
+
+/// Per-user data directory of the platform
+fn data_dir() -> PathBuf {
+    let env = |name: &str| std::env::var_os(name).map(PathBuf::from);
+    let home = env("HOME").unwrap_or_else(|| PathBuf::from("."));
+    if cfg!(target_os = "macos") {
+        home.join("Library/Application Support")
+    } else if cfg!(target_os = "windows") {
+        env("APPDATA").unwrap_or(home)
+    } else {
+        env("XDG_STATE_HOME").unwrap_or_else(|| home.join(".local/state"))
+    }
+}
+
+/// Install `rasen.session`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let session = Object::new(ctx.clone())?;
+    // Windows of the previous run as [{ name, route }]
+    let windows: Vec<_> = STORE
+        .get()
+        .map(|store| {
+            let windows = store.previous.windows.iter();
+            windows.map(|w| serde_json::json!({ "name": w.name, "route": w.route })).collect()
+        })
+        .unwrap_or_default();
+    let windows = ctx.json_parse(serde_json::to_string(&windows).unwrap())?;
+    let restore: Function = ctx.eval(RESTORE)?;
+    session.set("restore", restore.call::<_, Function>((windows, rasen.clone()))?)?;
+    session.set(
+        "setRoute",
+        Function::new(ctx.clone(), |route: Option<String>, window: Opt<WindowId>| {
+            let id = window.0.unwrap_or(MAIN_WINDOW);
+            update(|windows| {
+                if let Some(window) = windows.get_mut(&id) {
+                    window.route = route;
+                }
+            });
+        })?,
+    )?;
+    session.set(
+        "getRoute",
+        Function::new(ctx.clone(), |window: Opt<WindowId>| {
+            let id = window.0.unwrap_or(MAIN_WINDOW);
+            let store = STORE.get()?;
+            let windows = store.current.lock().unwrap();
+            windows.get(&id)?.route.clone()
+        })?,
+    )?;
+    rasen.set("session", session)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_dir_per_project() {
+        let config = RasenConfig::default();
+        let project = std::env::temp_dir().join(format!("rasen-project-{}", std::process::id()));
+        fs::create_dir_all(project.join("nested")).unwrap();
+        assert_eq!(app_dir(&config, &project), app_dir(&config, &project.join("nested/..")));
+        assert_ne!(app_dir(&config, &project), app_dir(&config, &project.join("nested")));
+        fs::remove_dir_all(&project).unwrap();
+    }
+
+    #[test]
+    fn test_named_windows_are_saved() {
+        let path = std::env::temp_dir().join(format!("rasen-session-{}.json", std::process::id()));
+        let bounds = SavedBounds { x: 10.0, y: 20.0, width: 300.0, height: 200.0, maximized: false };
+        let inspector = WindowSession { name: Some("inspector".into()), bounds: Some(bounds), route: Some("/a".into()) };
+        let previous = Session { windows: vec![WindowSession::default(), inspector] };
+        let _ = STORE.set(SessionStore {
+            path: path.clone(),
+            unclaimed: Mutex::new(previous.windows[1..].to_vec()),
+            previous,
+            current: Mutex::new(BTreeMap::from([(MAIN_WINDOW, WindowSession::default())])),
+            finished: AtomicBool::new(false),
+        });
+
+        // The first window under the name takes the saved one's place
+        assert!(open_window(1, "inspector", Some("/a".into())).is_some());
+        assert!(open_window(2, "inspector", Some("/b".into())).is_none());
+        close_window(2);
+        let saved: Session = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.windows.len(), 2);
+        assert_eq!(saved.windows[1].route.as_deref(), Some("/a"));
+
+        // Windows closing as the app quits stay saved
+        finish();
+        close_window(1);
+        let saved: Session = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.windows.len(), 2);
+        let _ = fs::remove_file(&path);
+    }
+}
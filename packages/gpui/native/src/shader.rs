@@ -0,0 +1,500 @@
+//! User WGSL fragment shaders for the `shader` element
+//!
+//! GPUI draws through a fixed set of pipelines of its own, so a `shader`
+//! element is rendered offscreen instead: its fragment is compiled with wgpu
+//! on a device of its own, drawn into a texture the size of the element (in
+//! device pixels), and the pixels are copied back into an image GPUI draws
+//! in the element's place. A frame is drawn again when the fragment, size or
+//! uniforms change, and on every animation frame with `animate`. Frames are
+//! drawn on the UI thread, so a fragment should take well under a frame.
+//!
+//! The fragment defines `fn fragment(position: vec2<f32>) -> vec4<f32>`,
+//! called for every pixel with its position from the element's top left
+//! corner, in device pixels, and returning its sRGB color. It can read
+//! `uniforms.resolution` (the element's size in device pixels),
+//! `uniforms.time` (seconds since the element was first shown with
+//! `animate`, 0 without) and the element's own uniforms by name: a number
+//! is an `f32`, an array of two to four numbers a `vec2<f32>` to
+//! `vec4<f32>`. The declarations are appended after the fragment, so
+//! compile errors point at its lines as written.
+//!
+//! A fragment that doesn't compile logs why once and draws nothing.
+
+use gpui::*;
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::Instant;
+use crate::elements::RenderContext;
+
+/// Pixel format of the offscreen texture, the one `RenderImage` takes
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8Unorm;
+
+/// Uniforms set for every fragment
+const BUILTIN_UNIFORMS: [&str; 2] = ["resolution", "time"];
+
+/// Compiled fragments kept before the cache is emptied
+const MAX_PIPELINES: usize = 32;
+
+/// The device shaders render on; `None` if no GPU adapter could be opened
+static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// Binds the uniform buffer, the same for every fragment
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    /// Pipelines by WGSL source, or why they don't compile
+    pipelines: Mutex<HashMap<String, Result<Arc<wgpu::RenderPipeline>, String>>>,
+}
+
+/// Props of a `shader` element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderView {
+    /// WGSL defining `fragment`
+    pub fragment: String,
+    /// Values by name, of one to four components each
+    pub uniforms: BTreeMap<String, Vec<f32>>,
+    /// Whether to draw on every frame, advancing `uniforms.time`
+    pub animate: bool,
+}
+
+/// The shared device, opened on first use
+fn gpu() -> Option<&'static Gpu> {
+    GPU.get_or_init(|| match Gpu::open() {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            eprintln!("Warning: shaders are unavailable: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+/// Whether `shader` elements can draw, opening the device the first time
+pub fn supported() -> bool {
+    gpu().is_some()
+}
+
+impl Gpu {
+    fn open() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .map_err(|e| e.to_string())?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("shader elements"),
+            required_limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        }))
+        .map_err(|e| e.to_string())?;
+        // Errors not caught in an error scope would otherwise panic
+        device.on_uncaptured_error(Box::new(|e| eprintln!("Warning: shader error: {}", e)));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shader uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shader element"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        Ok(Self { device, queue, bind_group_layout, pipeline_layout, pipelines: Mutex::new(HashMap::new()) })
+    }
+
+    /// The pipeline for `view`'s fragment and uniforms, with its cache key;
+    /// `None` if it doesn't compile, logged the first time
+    fn pipeline(&self, view: &ShaderView) -> Option<(String, Arc<wgpu::RenderPipeline>)> {
+        let source = source(view);
+        let key = match &source {
+            Ok(source) => source.clone(),
+            Err(e) => format!("{}\n{}", view.fragment, e),
+        };
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if !pipelines.contains_key(&key) {
+            if pipelines.len() >= MAX_PIPELINES {
+                pipelines.clear();
+            }
+            let compiled = source.and_then(|source| self.compile(&source)).map(Arc::new);
+            if let Err(e) = &compiled {
+                eprintln!("Warning: shader doesn't compile: {}", e);
+            }
+            pipelines.insert(key.clone(), compiled);
+        }
+        let pipeline = pipelines[&key].as_ref().ok()?.clone();
+        Some((key, pipeline))
+    }
+
+    fn compile(&self, source: &str) -> Result<wgpu::RenderPipeline, String> {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader element"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shader element"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("rasen_vertex"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("rasen_fragment"),
+                compilation_options: Default::default(),
+                targets: &[Some(FORMAT.into())],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: None,
+            cache: None,
+        });
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(e) => Err(e.to_string()),
+            None => Ok(pipeline),
+        }
+    }
+
+    /// Draw `pipeline` into `target` with `uniforms`; returns the BGRA pixels
+    fn draw(&self, pipeline: &wgpu::RenderPipeline, target: &Target, uniforms: &[u8]) -> Result<Vec<u8>, String> {
+        self.queue.write_buffer(&target.uniforms, 0, uniforms);
+        let view = target.texture.create_view(&Default::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("shader element"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &target.bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        encoder.copy_texture_to_buffer(
+            target.texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &target.readback,
+                layout: wgpu::TexelCopyBufferLayout { offset: 0, bytes_per_row: Some(target.row_bytes), rows_per_image: None },
+            },
+            target.texture.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = target.readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            let _ = sender.send(mapped);
+        });
+        self.device.poll(wgpu::PollType::Wait).map_err(|e| e.to_string())?;
+        receiver.recv().map_err(|e| e.to_string())?.map_err(|e| e.to_string())?;
+        let width = target.width as usize * 4;
+        let pixels = slice.get_mapped_range().chunks(target.row_bytes as usize).flat_map(|row| &row[..width]).copied().collect();
+        target.readback.unmap();
+        Ok(pixels)
+    }
+}
+
+/// A texture to draw into and the buffers around it, for one element size
+struct Target {
+    width: u32,
+    height: u32,
+    texture: wgpu::Texture,
+    /// Rows padded to what texture copies take
+    row_bytes: u32,
+    readback: wgpu::Buffer,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Target {
+    fn new(gpu: &Gpu, width: u32, height: u32, uniform_size: usize) -> Self {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shader element"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let row_bytes = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shader element pixels"),
+            size: row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let uniforms = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shader uniforms"),
+            size: uniform_size as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shader uniforms"),
+            layout: &gpu.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() }],
+        });
+        Self { width, height, texture, row_bytes, readback, uniforms, bind_group }
+    }
+
+    fn fits(&self, width: u32, height: u32, uniform_size: usize) -> bool {
+        self.width == width && self.height == height && self.uniforms.size() == uniform_size as u64
+    }
+}
+
+/// The fragment with the uniform declarations and entry points appended,
+/// or why the uniforms can't be declared
+fn source(view: &ShaderView) -> Result<String, String> {
+    let mut fields = String::new();
+    for (name, value) in &view.uniforms {
+        let identifier = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !identifier || BUILTIN_UNIFORMS.contains(&name.as_str()) {
+            return Err(format!("'{}' can't be the name of a uniform", name));
+        }
+        let ty = match value.len() {
+            1 => "f32",
+            2 => "vec2<f32>",
+            3 => "vec3<f32>",
+            4 => "vec4<f32>",
+            n => return Err(format!("Uniform '{}' has {} components; it takes one to four", name, n)),
+        };
+        fields.push_str(&format!("    {}: {},\n", name, ty));
+    }
+    Ok(format!(
+        "{}
+
+struct RasenUniforms {{
+    resolution: vec2<f32>,
+    time: f32,
+{}}}
+
+@group(0) @binding(0) var<uniform> uniforms: RasenUniforms;
+
+@vertex
+fn rasen_vertex(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {{
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}}
+
+@fragment
+fn rasen_fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {{
+    return fragment(position.xy);
+}}
+",
+        view.fragment, fields
+    ))
+}
+
+/// The uniform buffer's contents, laid out like `RasenUniforms` in WGSL:
+/// `vec2` aligned to 8 bytes, `vec3` and `vec4` to 16
+fn uniform_bytes(resolution: [f32; 2], time: f32, uniforms: &BTreeMap<String, Vec<f32>>) -> Vec<u8> {
+    let mut values = vec![resolution[0], resolution[1], time];
+    for value in uniforms.values() {
+        let align = if value.len() > 2 { 4 } else { value.len() };
+        values.resize(values.len().next_multiple_of(align), 0.0);
+        values.extend(value);
+    }
+    values.resize(values.len().next_multiple_of(4), 0.0);
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Offscreen frames of the `shader` elements on screen
+///
+/// Works like [`TreeTracker`](crate::tree::TreeTracker): cheap to clone,
+/// and elements not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct ShaderTracker {
+    surfaces: Rc<RefCell<HashMap<String, Surface>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+struct Surface {
+    /// When the element was first shown, for `uniforms.time`
+    shown: Instant,
+    target: Option<Target>,
+    /// Frame drawn last, freed from the sprite atlas once superseded
+    frame: Option<Arc<RenderImage>>,
+    /// Pipeline key and uniform buffer `frame` was drawn with
+    drawn: Option<(String, Vec<u8>)>,
+    /// Whether the last draw failed, so failures are logged once
+    failed: bool,
+}
+
+impl ShaderTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn track(&self, id: &str) {
+        self.seen.borrow_mut().insert(id.to_string());
+        self.surfaces.borrow_mut().entry(id.to_string()).or_insert_with(|| Surface {
+            shown: Instant::now(),
+            target: None,
+            frame: None,
+            drawn: None,
+            failed: false,
+        });
+    }
+
+    /// Draw element `id` over `bounds`, drawing a new frame if it's stale
+    fn paint(&self, id: &str, view: &ShaderView, bounds: Bounds<Pixels>, window: &mut Window, cx: &mut App) {
+        let Some(gpu) = gpu() else {
+            return;
+        };
+        let Some((key, pipeline)) = gpu.pipeline(view) else {
+            return;
+        };
+        let mut surfaces = self.surfaces.borrow_mut();
+        let Some(surface) = surfaces.get_mut(id) else {
+            return;
+        };
+        let max = gpu.device.limits().max_texture_dimension_2d;
+        let scale = window.scale_factor();
+        let width = ((f32::from(bounds.size.width) * scale).round() as u32).min(max);
+        let height = ((f32::from(bounds.size.height) * scale).round() as u32).min(max);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let time = if view.animate { surface.shown.elapsed().as_secs_f32() } else { 0.0 };
+        let uniforms = uniform_bytes([width as f32, height as f32], time, &view.uniforms);
+        let stale = surface.drawn.as_ref().is_none_or(|(drawn, bytes)| *drawn != key || *bytes != uniforms);
+        if stale {
+            if !surface.target.as_ref().is_some_and(|target| target.fits(width, height, uniforms.len())) {
+                surface.target = Some(Target::new(gpu, width, height, uniforms.len()));
+            }
+            let target = surface.target.as_ref().expect("set above");
+            let pixels = gpu.draw(&pipeline, target, &uniforms).and_then(|pixels| {
+                RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "the frame has no pixel data".to_string())
+            });
+            match pixels {
+                Ok(pixels) => {
+                    let frame = Arc::new(RenderImage::new([image::Frame::new(pixels)]));
+                    if let Some(old) = surface.frame.replace(frame) {
+                        cx.drop_image(old, Some(window));
+                    }
+                    surface.drawn = Some((key, uniforms));
+                    surface.failed = false;
+                }
+                Err(e) => {
+                    if !surface.failed {
+                        eprintln!("Warning: can't draw shader: {}", e);
+                    }
+                    surface.failed = true;
+                }
+            }
+        }
+
+        if let Some(frame) = surface.frame.clone() {
+            if let Err(e) = window.paint_image(bounds, Corners::default(), frame, 0, false) {
+                eprintln!("Warning: can't draw shader frame: {}", e);
+            }
+        }
+    }
+
+    /// End a render pass, freeing the frames of elements that are gone
+    pub fn finish_frame(&self, window: &mut Window, cx: &mut App) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        let mut surfaces = self.surfaces.borrow_mut();
+        let gone: Vec<String> = surfaces.keys().filter(|id| !seen.contains(*id)).cloned().collect();
+        for id in gone {
+            if let Some(frame) = surfaces.remove(&id).and_then(|surface| surface.frame) {
+                cx.drop_image(frame, Some(window));
+            }
+        }
+    }
+}
+
+/// The frames of `shader` element `id`, filling the element
+pub fn render(id: &str, view: &ShaderView, render_ctx: &RenderContext) -> AnyElement {
+    let shaders = render_ctx.shaders.clone();
+    shaders.track(id);
+    let id = id.to_string();
+    let view = view.clone();
+    let animate = view.animate;
+    canvas(
+        move |_, window, _| {
+            if animate {
+                window.request_animation_frame();
+            }
+        },
+        move |bounds, _, window, cx| shaders.paint(&id, &view, bounds, window, cx),
+    )
+    .size_full()
+    .into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(uniforms: &[(&str, &[f32])]) -> ShaderView {
+        ShaderView {
+            fragment: "fn fragment(position: vec2<f32>) -> vec4<f32> { return vec4<f32>(1.0, 0.0, 0.0, 1.0); }".to_string(),
+            uniforms: uniforms.iter().map(|(name, value)| (name.to_string(), value.to_vec())).collect(),
+            animate: false,
+        }
+    }
+
+    #[test]
+    fn test_uniform_layout() {
+        let uniforms = view(&[("a", &[1.0]), ("b", &[2.0, 3.0, 4.0]), ("c", &[5.0, 6.0])]).uniforms;
+        let bytes = uniform_bytes([640.0, 480.0], 0.5, &uniforms);
+        let values: Vec<f32> = bytes.chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        // `a` fills the slot after `time`, `b` starts a 16-byte row and `c` follows it 8-byte aligned
+        assert_eq!(values, [640.0, 480.0, 0.5, 1.0, 2.0, 3.0, 4.0, 0.0, 5.0, 6.0, 0.0, 0.0]);
+        assert_eq!(uniform_bytes([1.0, 1.0], 0.0, &BTreeMap::new()).len(), 16);
+    }
+
+    #[test]
+    fn test_uniform_declarations() {
+        let wgsl = source(&view(&[("color", &[1.0, 0.5, 0.0]), ("speed", &[2.0])])).unwrap();
+        assert!(wgsl.starts_with("fn fragment("));
+        assert!(wgsl.contains("    color: vec3<f32>,\n    speed: f32,\n"));
+
+        assert!(source(&view(&[("time", &[1.0])])).is_err());
+        assert!(source(&view(&[("2d", &[1.0])])).is_err());
+        assert!(source(&view(&[("matrix", &[0.0; 9])])).is_err());
+        assert!(source(&view(&[("empty", &[])])).is_err());
+    }
+
+    #[test]
+    fn test_draw() {
+        // Needs a GPU adapter, which CI machines may not have
+        let Some(gpu) = gpu() else {
+            return;
+        };
+        let view = view(&[]);
+        let (_, pipeline) = gpu.pipeline(&view).unwrap();
+        let uniforms = uniform_bytes([3.0, 2.0], 0.0, &view.uniforms);
+        let target = Target::new(gpu, 3, 2, uniforms.len());
+        let pixels = gpu.draw(&pipeline, &target, &uniforms).unwrap();
+        assert_eq!(pixels, [0, 0, 255, 255].repeat(6));
+
+        let broken = ShaderView { fragment: "fn fragment() {}".to_string(), ..view };
+        assert!(gpu.pipeline(&broken).is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! Handing URLs and files to other apps, on `rasen.shell`
+//!
+//! `open(target)` opens a URL in the default browser (or the app handling
+//! its scheme) and a path in the app the desktop associates with it.
+//! `showInFolder(path)` opens the file manager with the file selected. Both
+//! run the platform's opener (`open`, `explorer`, `xdg-open`) without
+//! waiting for it.
+//!
+//! Paths are checked against the `fs.allow` sandbox. In hardened mode only
+//! web and mail links can be opened.
+
+use rquickjs::{Ctx, Exception, Function, Object};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use crate::files::{self, FsError};
+use crate::native_api;
+
+/// Schemes `open` accepts in hardened mode
+const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+static HARDENED: AtomicBool = AtomicBool::new(false);
+
+/// Restrict `open` to web links in hardened mode (once, before any script runs)
+pub fn init(hardened: bool) {
+    HARDENED.store(hardened, Ordering::Relaxed);
+}
+
+/// The scheme of `target` if it's a URL rather than a path
+///
+/// Single letters are Windows drive letters (`C:\...`), not schemes.
+fn url_scheme(target: &str) -> Option<&str> {
+    let (scheme, _) = target.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = scheme.len() > 1
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// The command opening `target` with its default app
+fn opener(target: &str) -> Command {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        Command::new("explorer")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(target);
+    command
+}
+
+/// The command showing `path` selected in the file manager
+fn revealer(path: &Path) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        // Desktop file managers implement the freedesktop FileManager1 interface
+        let mut command = Command::new("dbus-send");
+        command.args([
+            "--session",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ]);
+        let uri = url::Url::from_file_path(path).map_or_else(|_| path.display().to_string(), String::from);
+        command.arg(format!("array:string:{}", uri)).arg("string:");
+        command
+    }
+}
+
+/// Start `command` and reap it from a thread; fails if it can't start
+fn launch(mut command: Command, what: &str) -> Result<(), FsError> {
+    let mut child = command.spawn().map_err(|e| {
+        let code = if e.kind() == io::ErrorKind::NotFound { "ENOENT" } else { "EIO" };
+        FsError { code, message: format!("Can't {}: {}", what, e) }
+    })?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+fn open(target: &str) -> Result<(), FsError> {
+    match url_scheme(target) {
+        Some(scheme) if HARDENED.load(Ordering::Relaxed) && !SAFE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) => {
+            Err(FsError { code: "EPERM", message: format!("Opening {}: links is disabled in hardened mode", scheme) })
+        }
+        Some(_) => launch(opener(target), &format!("open {}", target)),
+        None => {
+            let path = files::check(target)?;
+            if !path.exists() {
+                return Err(FsError { code: "ENOENT", message: format!("'{}' doesn't exist", target) });
+            }
+            launch(opener(&path.to_string_lossy()), &format!("open '{}'", target))
+        }
+    }
+}
+
+fn show_in_folder(target: &str) -> Result<(), FsError> {
+    let path = files::check(target)?;
+    if !path.exists() {
+        return Err(FsError { code: "ENOENT", message: format!("'{}' doesn't exist", target) });
+    }
+    let result = launch(revealer(&path), &format!("show '{}'", target));
+    if cfg!(all(unix, not(target_os = "macos"))) && result.is_err() {
+        // Without D-Bus, at least open the folder
+        let folder = path.parent().unwrap_or(&path);
+        return launch(opener(&folder.to_string_lossy()), &format!("show '{}'", target));
+    }
+    result
+}
+
+/// Throw `error` as an `Error` with its `code`
+fn throw(ctx: &Ctx, error: FsError) -> rquickjs::Error {
+    match Exception::from_message(ctx.clone(), &error.message).and_then(|exception| {
+        exception.set("code", error.code)?;
+        Ok(exception)
+    }) {
+        Ok(exception) => ctx.throw(exception.into_value()),
+        Err(e) => e,
+    }
+}
+
+/// Install `rasen.shell`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let namespace = native_api::namespace(ctx, rasen, "shell")?;
+    namespace.set(
+        "open",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, target: String| open(&target).map_err(|e| throw(&ctx, e)))?,
+    )?;
+    namespace.set(
+        "showInFolder",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, path: String| show_in_folder(&path).map_err(|e| throw(&ctx, e)))?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_scheme() {
+        assert_eq!(url_scheme("https://rasen.dev/docs"), Some("https"));
+        assert_eq!(url_scheme("mailto:team@example.com"), Some("mailto"));
+        assert_eq!(url_scheme("vscode-insiders://file/x"), Some("vscode-insiders"));
+        assert_eq!(url_scheme("C:\\Users\\me\\report.pdf"), None);
+        assert_eq!(url_scheme("./exports/report.pdf"), None);
+        assert_eq!(url_scheme("/tmp/a:b"), None);
+    }
+}
@@ -0,0 +1,468 @@
+//! Drag-to-reorder lists for `sortableList({ items, onReorder })`
+//!
+//! A sortable list is a div with a `reorder` handler; its direct children are
+//! the items. Items are dragged by their `dragHandle` descendants, or as a
+//! whole when they have none. While dragging, the list works out the drop
+//! position from the items' painted bounds, draws an insertion line there and
+//! scrolls when the pointer nears its edges. Dropping calls the handler with
+//! the item's old index and its index in the reordered list.
+
+use gpui::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::animation::TransitionDriver;
+use crate::colorpicker::PickerTracker;
+use crate::datepicker::CalendarTracker;
+use crate::layout::Layout;
+use crate::pointer::PointerTracker;
+use crate::selectable::SelectTracker;
+use crate::shader::ShaderTracker;
+use crate::tree::TreeTracker;
+use crate::elements::{Element, RenderContext};
+use crate::focus::FocusTracker;
+
+/// Distance from a list's edge where dragging scrolls it
+const SCROLL_EDGE: f32 = 32.0;
+/// Largest scroll step per pointer move, at the very edge
+const SCROLL_STEP: f32 = 16.0;
+/// Insertion line thickness
+const INDICATOR_WIDTH: f32 = 2.0;
+
+/// Payload of an item being dragged
+#[derive(Debug, Clone)]
+pub struct DraggedItem {
+    /// ID of the list the item belongs to
+    pub list: String,
+    pub from: usize,
+}
+
+/// Marks an element within a sortable list item, for its drag source
+#[derive(Debug, Clone, Copy)]
+pub struct SortItem<'a> {
+    /// ID of the list
+    pub list: &'a str,
+    /// ID of the item
+    pub item: &'a str,
+    pub index: usize,
+    /// Whether the item is dragged by a handle rather than as a whole
+    pub handled: bool,
+}
+
+/// Per-list drag state, kept across frames
+///
+/// Like [`TransitionDriver`], lists not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame). Cheap to clone; clones share state,
+/// so event listeners can hold one.
+#[derive(Clone, Default)]
+pub struct SortTracker {
+    lists: Rc<RefCell<HashMap<String, ListState>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+#[derive(Default)]
+struct ListState {
+    scroll: ScrollHandle,
+    /// Item bounds from the last paint, in window coordinates
+    items: Vec<Bounds<Pixels>>,
+    /// Insertion slot (0..=len) while an item is dragged over the list
+    target: Option<usize>,
+}
+
+impl SortTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scroll handle of a list, created on first use
+    pub fn scroll_handle(&self, list: &str) -> ScrollHandle {
+        self.seen.borrow_mut().insert(list.to_string());
+        self.lists.borrow_mut().entry(list.to_string()).or_default().scroll.clone()
+    }
+
+    fn existing_scroll_handle(&self, list: &str) -> Option<ScrollHandle> {
+        self.lists.borrow().get(list).map(|state| state.scroll.clone())
+    }
+
+    fn set_items(&self, list: &str, items: Vec<Bounds<Pixels>>) {
+        if let Some(state) = self.lists.borrow_mut().get_mut(list) {
+            state.items = items;
+        }
+    }
+
+    /// Size of an item as last painted
+    pub fn item_size(&self, list: &str, index: usize) -> Option<Size<Pixels>> {
+        self.lists.borrow().get(list).and_then(|state| state.items.get(index)).map(|bounds| bounds.size)
+    }
+
+    /// Update the insertion slot for a pointer position, or clear it when the
+    /// pointer is outside the list; returns whether it changed
+    fn track(&self, list: &str, position: Option<Point<Pixels>>, horizontal: bool) -> bool {
+        let mut lists = self.lists.borrow_mut();
+        let Some(state) = lists.get_mut(list) else {
+            return false;
+        };
+        let target = position.map(|position| drop_slot(&state.items, position, horizontal));
+        std::mem::replace(&mut state.target, target) != target
+    }
+
+    fn target(&self, list: &str) -> Option<usize> {
+        self.lists.borrow().get(list).and_then(|state| state.target)
+    }
+
+    fn take_target(&self, list: &str) -> Option<usize> {
+        self.lists.borrow_mut().get_mut(list).and_then(|state| state.target.take())
+    }
+
+    /// Forget insertion slots once no drag is active, e.g. after dropping elsewhere
+    pub fn clear_targets(&self) {
+        for state in self.lists.borrow_mut().values_mut() {
+            state.target = None;
+        }
+    }
+
+    /// End a render pass, dropping state for lists that are gone
+    pub fn finish_frame(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.lists.borrow_mut().retain(|id, _| seen.contains(id));
+    }
+}
+
+/// Insertion slot for a pointer position: the number of items whose midpoint
+/// is before it along the list's axis
+pub fn drop_slot(items: &[Bounds<Pixels>], position: Point<Pixels>, horizontal: bool) -> usize {
+    items
+        .iter()
+        .take_while(|bounds| {
+            if horizontal {
+                position.x > bounds.center().x
+            } else {
+                position.y > bounds.center().y
+            }
+        })
+        .count()
+}
+
+/// Index in the reordered list for an item moved from `from` to insertion slot `slot`
+pub fn reorder_index(from: usize, slot: usize) -> usize {
+    if slot > from {
+        slot - 1
+    } else {
+        slot
+    }
+}
+
+/// Whether an item has a drag handle, not counting those of nested lists
+pub fn has_handle(item: &Element) -> bool {
+    let mut stack = vec![item];
+    while let Some(element) = stack.pop() {
+        let Element::Div(div) = element else {
+            continue;
+        };
+        if div.drag_handle {
+            return true;
+        }
+        if div.handlers.on_reorder.is_none() || std::ptr::eq(element, item) {
+            stack.extend(div.children.iter());
+        }
+    }
+    false
+}
+
+/// Find an element by its ID (`elem_0.2.1`, or `elem_0.2[todo-7]` when keyed)
+///
+/// Only children whose ID starts the one looked for are searched.
+pub fn find<'a>(root: &'a Element, id: &str) -> Option<&'a Element> {
+    let mut stack = vec![root];
+    while let Some(element) = stack.pop() {
+        if element.id() == id {
+            return Some(element);
+        }
+        if let Element::Div(div) = element {
+            stack.extend(div.children.iter().filter(|child| id.starts_with(child.id())));
+        }
+    }
+    None
+}
+
+/// Make a div a sortable list: track item bounds and drops, and draw the insertion line
+pub fn list(
+    d: Div,
+    id: &str,
+    horizontal: bool,
+    reorder: Box<dyn Fn(usize, usize, &mut Window, &mut App) + 'static>,
+    sorting: &SortTracker,
+) -> Div {
+    let scroll = sorting.scroll_handle(id);
+    let indicator = sorting.target(id).map(|slot| indicator(sorting, id, slot, horizontal, &scroll));
+
+    let (tracker, list) = (sorting.clone(), id.to_string());
+    let d = d.on_children_prepainted(move |bounds, _, _| tracker.set_items(&list, bounds));
+
+    let (tracker, list) = (sorting.clone(), id.to_string());
+    let d = d.on_drag_move::<DraggedItem>(move |event, window, cx| {
+        // Items can only be dropped within their own list
+        if event.drag(cx).list != list {
+            return;
+        }
+        let position = event.event.position;
+        let inside = event.bounds.contains(&position);
+        let mut changed = tracker.track(&list, inside.then_some(position), horizontal);
+        if let Some(scroll) = tracker.existing_scroll_handle(&list).filter(|_| inside) {
+            changed |= auto_scroll(&scroll, event.bounds, position, horizontal);
+        }
+        if changed {
+            window.refresh();
+        }
+    });
+
+    let (tracker, list) = (sorting.clone(), id.to_string());
+    let d = d.on_drop::<DraggedItem>(move |dragged, window, cx| {
+        let Some(slot) = tracker.take_target(&list) else {
+            return;
+        };
+        let to = reorder_index(dragged.from, slot);
+        if dragged.list == list && to != dragged.from {
+            reorder(dragged.from, to, window, cx);
+        }
+        window.refresh();
+    });
+
+    match indicator {
+        Some(indicator) => d.relative().child(indicator),
+        None => d,
+    }
+}
+
+/// Scroll a list whose content overflows along its axis
+pub fn scroll(d: Stateful<Div>, id: &str, horizontal: bool, sorting: &SortTracker) -> Stateful<Div> {
+    let d = d.track_scroll(&sorting.scroll_handle(id));
+    if horizontal {
+        d.overflow_x_scroll()
+    } else {
+        d.overflow_y_scroll()
+    }
+}
+
+/// Nudge the scroll offset while the pointer is near a list's edge; returns whether it moved
+fn auto_scroll(scroll: &ScrollHandle, bounds: Bounds<Pixels>, position: Point<Pixels>, horizontal: bool) -> bool {
+    let (pointer, start, end) = if horizontal {
+        (position.x, bounds.left(), bounds.right())
+    } else {
+        (position.y, bounds.top(), bounds.bottom())
+    };
+    // Positive scrolls toward the end, faster closer to the edge
+    let step = if pointer < start + px(SCROLL_EDGE) {
+        -SCROLL_STEP * (1.0 - f32::from(pointer - start) / SCROLL_EDGE)
+    } else if pointer > end - px(SCROLL_EDGE) {
+        SCROLL_STEP * (1.0 - f32::from(end - pointer) / SCROLL_EDGE)
+    } else {
+        return false;
+    };
+
+    let mut offset = scroll.offset();
+    let max = scroll.max_offset();
+    // Offsets are negative as content scrolls toward its end
+    let clamp = |offset: Pixels, max: Pixels| px((f32::from(offset) - step).clamp(-f32::from(max), 0.0));
+    if horizontal {
+        offset.x = clamp(offset.x, max.width);
+    } else {
+        offset.y = clamp(offset.y, max.height);
+    }
+    if offset == scroll.offset() {
+        return false;
+    }
+    scroll.set_offset(offset);
+    true
+}
+
+/// Insertion line at a slot, positioned within the list's scrolled content
+fn indicator(sorting: &SortTracker, id: &str, slot: usize, horizontal: bool, scroll: &ScrollHandle) -> AnyElement {
+    let lists = sorting.lists.borrow();
+    let items = lists.get(id).map(|state| state.items.as_slice()).unwrap_or_default();
+    let (Some(first), Some(last)) = (items.first(), items.last()) else {
+        return div().into_any_element();
+    };
+    let bounds = scroll.bounds();
+    // Content origin: the list's origin shifted by the scroll offset
+    let origin = bounds.origin + scroll.offset();
+    let color = Hsla::from(rgb(0x3b82f6));
+
+    let line = div().absolute().bg(color);
+    let line = if horizontal {
+        let edge = items.get(slot).map(|item| item.left()).unwrap_or(last.right());
+        line.left(edge - origin.x - px(INDICATOR_WIDTH / 2.0))
+            .top(first.top() - origin.y)
+            .w(px(INDICATOR_WIDTH))
+            .h(first.size.height)
+    } else {
+        let edge = items.get(slot).map(|item| item.top()).unwrap_or(last.bottom());
+        line.top(edge - origin.y - px(INDICATOR_WIDTH / 2.0))
+            .left(first.left() - origin.x)
+            .h(px(INDICATOR_WIDTH))
+            .w(first.size.width)
+    };
+    line.into_any_element()
+}
+
+/// What a drag preview needs to render an item like the window does
+#[derive(Clone, Copy)]
+pub struct PreviewSettings {
+    pub viewport_width: Pixels,
+    pub viewport_height: Pixels,
+    pub zoom: f32,
+    pub dark: bool,
+    pub high_contrast: bool,
+}
+
+/// Translucent copy of the dragged item that follows the pointer
+pub struct DragPreview {
+    item: Option<Element>,
+    size: Option<Size<Pixels>>,
+    settings: PreviewSettings,
+    transitions: TransitionDriver,
+    sorting: SortTracker,
+    pickers: PickerTracker,
+    calendars: CalendarTracker,
+    trees: TreeTracker,
+    selections: SelectTracker,
+    pointers: PointerTracker,
+    shaders: ShaderTracker,
+    focus: FocusTracker,
+    layout: Layout,
+}
+
+impl DragPreview {
+    pub fn new(item: Option<Element>, size: Option<Size<Pixels>>, settings: PreviewSettings) -> Self {
+        Self {
+            item,
+            size,
+            settings,
+            transitions: TransitionDriver::new(),
+            sorting: SortTracker::new(),
+            pickers: PickerTracker::new(),
+            calendars: CalendarTracker::new(),
+            trees: TreeTracker::new(),
+            selections: SelectTracker::new(),
+            pointers: PointerTracker::new(),
+            shaders: ShaderTracker::new(),
+            focus: FocusTracker::new(),
+            layout: Layout::new(),
+        }
+    }
+}
+
+impl Render for DragPreview {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut preview = div().opacity(0.8);
+        if let Some(size) = self.size {
+            preview = preview.w(size.width).h(size.height);
+        }
+        let Some(item) = &self.item else {
+            return preview;
+        };
+        // The preview only shows the item; its handlers stay with the original
+        let settings = self.settings;
+        let render_ctx = RenderContext {
+            click_handler: &|_| Box::new(|_, _, _| {}),
+            reorder_handler: &|_| Box::new(|_, _, _, _| {}),
+            preview_handler: &|_, _| Box::new(move |_, _, cx| cx.new(|_| DragPreview::new(None, None, settings))),
+            value_handler: &|_| Box::new(|_, _, _| {}),
+            selection_handler: &|_| Box::new(|_, _, _| {}),
+            mouse_handler: &|_, _| Box::new(|_, _, _| {}),
+            viewport_width: self.settings.viewport_width,
+            viewport_height: self.settings.viewport_height,
+            zoom: self.settings.zoom,
+            dark: self.settings.dark,
+            high_contrast: self.settings.high_contrast,
+            transitions: &self.transitions,
+            sorting: &self.sorting,
+            sort_item: None,
+            pickers: &self.pickers,
+            calendars: &self.calendars,
+            trees: &self.trees,
+            selections: &self.selections,
+            pointers: &self.pointers,
+            shaders: &self.shaders,
+            focus: &self.focus,
+            focused: None,
+            text_transform: None,
+            layout: &self.layout,
+        };
+        let element = item.render_with_events(&render_ctx);
+        self.transitions.finish_frame();
+        self.pickers.finish_frame();
+        self.calendars.finish_frame();
+        self.trees.finish_frame();
+        self.selections.finish_frame();
+        self.pointers.finish_frame();
+        self.shaders.finish_frame(window, cx);
+        self.layout.finish_frame();
+        preview.child(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor;
+
+    fn rows(count: usize) -> Vec<Bounds<Pixels>> {
+        (0..count)
+            .map(|i| Bounds::new(point(px(0.0), px(i as f32 * 20.0)), size(px(100.0), px(20.0))))
+            .collect()
+    }
+
+    #[test]
+    fn test_drop_slot() {
+        let items = rows(3);
+        assert_eq!(drop_slot(&items, point(px(50.0), px(5.0)), false), 0);
+        assert_eq!(drop_slot(&items, point(px(50.0), px(15.0)), false), 1);
+        assert_eq!(drop_slot(&items, point(px(50.0), px(45.0)), false), 2);
+        assert_eq!(drop_slot(&items, point(px(50.0), px(55.0)), false), 3);
+        assert_eq!(drop_slot(&[], point(px(0.0), px(0.0)), false), 0);
+    }
+
+    #[test]
+    fn test_reorder_index() {
+        // Moving down: the slot counts the item itself
+        assert_eq!(reorder_index(0, 3), 2);
+        assert_eq!(reorder_index(2, 0), 0);
+        // Dropping next to itself is a no-op
+        assert_eq!(reorder_index(1, 1), 1);
+        assert_eq!(reorder_index(1, 2), 1);
+    }
+
+    #[test]
+    fn test_handles_and_find() {
+        let json = r#"{"type":"div","handlers":{"reorder":1},"children":[
+            {"type":"div","children":[{"type":"div","dragHandle":true},{"type":"text","text":"a"}]},
+            {"type":"div","children":[{"type":"div","handlers":{"reorder":2},"children":[
+                {"type":"div","children":[{"type":"div","dragHandle":true}]}
+            ]}]}
+        ]}"#;
+        let root = descriptor::decode(json).unwrap().into_element();
+        let item = |id| find(&root, id).unwrap();
+        assert!(has_handle(item("elem_0.0")));
+        // The handle belongs to the nested list's item
+        assert!(!has_handle(item("elem_0.1")));
+        assert!(has_handle(item("elem_0.1.0.0")));
+        assert!(find(&root, "elem_0.5").is_none());
+        assert!(matches!(find(&root, "elem_0.0.1"), Some(Element::Text(_))));
+    }
+
+    #[test]
+    fn test_find_keyed() {
+        let json = r#"{"type":"div","children":[
+            {"type":"div","key":"row-1","children":[{"type":"text","text":"a"}]},
+            {"type":"div","key":"row-10","children":[{"type":"text","text":"b"}]}
+        ]}"#;
+        let root = descriptor::decode(json).unwrap().into_element();
+        assert_eq!(find(&root, "elem_0[row-10]").map(Element::id), Some("elem_0[row-10]"));
+        let Some(Element::Text(text)) = find(&root, "elem_0[row-1].0") else {
+            panic!("keyed row's child not found");
+        };
+        assert_eq!(text.text, "a");
+        assert!(find(&root, "elem_0[row-2]").is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! Cold-start tracing for `rasen-gpui run --trace-startup`
+//!
+//! Startup is split into back-to-back phases, each timed from the end of the
+//! previous one, so the breakdown adds up to the time from launch to the
+//! first frame. Phases over their budget are called out with a warning;
+//! budgets default to [`Phase::default_budget`] and can be overridden in the
+//! `startup.budgets` section of rasen.config.js (milliseconds per phase key).
+//!
+//! Until [`begin`] is called [`mark`] is a no-op, so the markers can stay in
+//! the startup path unconditionally.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+/// A startup phase, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Reading rasen.config.js
+    Config,
+    /// Resolving and bundling modules
+    Bundle,
+    /// Bundle verification, app launch and JS runtime creation
+    Setup,
+    /// Evaluating the base shim, native APIs and bundled runtime
+    Shims,
+    /// Evaluating the user script, including the first App() call
+    Script,
+    /// Encoding and decoding the first element tree
+    Encode,
+    /// Opening the window and drawing the first frame
+    FirstFrame,
+}
+
+impl Phase {
+    /// Key used for the phase in `startup.budgets`
+    pub fn key(self) -> &'static str {
+        match self {
+            Phase::Config => "config",
+            Phase::Bundle => "bundle",
+            Phase::Setup => "setup",
+            Phase::Shims => "shims",
+            Phase::Script => "script",
+            Phase::Encode => "encode",
+            Phase::FirstFrame => "firstFrame",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Phase::Config => "config parse",
+            Phase::Bundle => "bundling",
+            Phase::Setup => "setup",
+            Phase::Shims => "shim eval",
+            Phase::Script => "user script eval",
+            Phase::Encode => "first js_to_element",
+            Phase::FirstFrame => "first frame",
+        }
+    }
+
+    /// Budget used when the config doesn't set one
+    pub fn default_budget(self) -> Duration {
+        Duration::from_millis(match self {
+            Phase::Config => 20,
+            Phase::Bundle => 300,
+            Phase::Setup => 100,
+            Phase::Shims => 100,
+            Phase::Script => 200,
+            Phase::Encode => 50,
+            Phase::FirstFrame => 150,
+        })
+    }
+}
+
+struct Trace {
+    started: Instant,
+    last: Instant,
+    budgets: HashMap<String, u64>,
+    phases: Vec<(Phase, Duration)>,
+}
+
+/// Start tracing from now
+pub fn begin() {
+    let now = Instant::now();
+    *TRACE.lock().unwrap() = Some(Trace {
+        started: now,
+        last: now,
+        budgets: HashMap::new(),
+        phases: Vec::new(),
+    });
+}
+
+/// Set per-phase budgets in milliseconds, keyed by [`Phase::key`]
+pub fn set_budgets(budgets: HashMap<String, u64>) {
+    if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+        trace.budgets = budgets;
+    }
+}
+
+/// Record that `phase` just finished
+pub fn mark(phase: Phase) {
+    if let Some(trace) = TRACE.lock().unwrap().as_mut() {
+        let now = Instant::now();
+        trace.phases.push((phase, now - trace.last));
+        trace.last = now;
+    }
+}
+
+/// Record the first frame and print the breakdown to stderr
+pub fn finish() {
+    mark(Phase::FirstFrame);
+    let Some(trace) = TRACE.lock().unwrap().take() else {
+        return;
+    };
+
+    let mut over = Vec::new();
+    eprintln!("Startup trace:");
+    for &(phase, elapsed) in &trace.phases {
+        let budget = trace
+            .budgets
+            .get(phase.key())
+            .map(|&ms| Duration::from_millis(ms))
+            .unwrap_or_else(|| phase.default_budget());
+        let flag = if elapsed > budget {
+            over.push((phase, elapsed, budget));
+            "  over budget"
+        } else {
+            ""
+        };
+        eprintln!("  {:<22}{:>9.1}ms{}", phase.label(), millis(elapsed), flag);
+    }
+    eprintln!("  {:<22}{:>9.1}ms", "total", millis(trace.last - trace.started));
+
+    for (phase, elapsed, budget) in over {
+        eprintln!(
+            "Warning: startup phase '{}' took {:.1}ms, over its {}ms budget (startup.budgets.{})",
+            phase.label(),
+            millis(elapsed),
+            budget.as_millis(),
+            phase.key(),
+        );
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
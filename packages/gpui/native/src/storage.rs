@@ -0,0 +1,249 @@
+//! Persistent key-value storage for JS on `localStorage` and `rasen.storage`
+//!
+//! Works like the browser's `localStorage`: string keys and values, read and
+//! written synchronously. The items are loaded from `storage.json` in the
+//! app's directory at startup. Changes are saved by a background thread
+//! shortly after they're made, so a burst of `setItem` calls writes the file
+//! once and the UI thread never waits on the disk, and on quit.
+
+use rquickjs::convert::Coerced;
+use rquickjs::object::Accessor;
+use rquickjs::{Ctx, Exception, Function, Object};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// Most bytes of keys and values together, as in browsers
+const QUOTA: usize = 5 * 1024 * 1024;
+
+/// How long after a change the items are saved, gathering the changes made
+/// meanwhile into one write
+const SAVE_DELAY: Duration = Duration::from_millis(250);
+
+static STORE: OnceLock<Store> = OnceLock::new();
+
+/// Stored items, in key order
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Items {
+    items: BTreeMap<String, String>,
+    /// Bytes of keys and values
+    used: usize,
+}
+
+impl Items {
+    /// Items saved at `path`; none if the file is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        let items: BTreeMap<String, String> = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        let used = items.iter().map(|(key, value)| key.len() + value.len()).sum();
+        Self { items, used }
+    }
+
+    /// Write the items to `path`, through a temporary file so a crash can't
+    /// leave a truncated one
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_string(&self.items)?)?;
+        fs::rename(&temp, path)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.items.get(key).map(String::as_str)
+    }
+
+    /// Set an item; fails without changing anything if it would exceed the quota
+    pub fn set(&mut self, key: String, value: String) -> Result<(), String> {
+        let previous = self.items.get(&key).map_or(0, |old| key.len() + old.len());
+        let used = self.used - previous + key.len() + value.len();
+        if used > QUOTA {
+            return Err(format!("Setting '{}' exceeds the storage quota of {} bytes", key, QUOTA));
+        }
+        self.items.insert(key, value);
+        self.used = used;
+        Ok(())
+    }
+
+    /// Remove an item; returns whether it existed
+    pub fn remove(&mut self, key: &str) -> bool {
+        match self.items.remove(key) {
+            Some(value) => {
+                self.used -= key.len() + value.len();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.used = 0;
+    }
+
+    /// The `index`th key, in key order
+    pub fn key(&self, index: usize) -> Option<&str> {
+        self.items.keys().nth(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+struct Store {
+    path: PathBuf,
+    items: Mutex<Items>,
+    /// Whether there are changes to save
+    dirty: AtomicBool,
+    /// Wakes the saving thread after a change
+    changes: Mutex<Sender<()>>,
+    /// Held while saving, so saves write in the order they read the items
+    saving: Mutex<()>,
+}
+
+/// Load the app's stored items from `path` (once, before any script runs)
+pub fn init(path: PathBuf) {
+    let items = Mutex::new(Items::load(&path));
+    let (changes, changed) = mpsc::channel();
+    let store = Store { path, items, dirty: AtomicBool::new(false), changes: Mutex::new(changes), saving: Mutex::new(()) };
+    if STORE.set(store).is_ok() {
+        thread::spawn(move || save_changes(changed));
+    }
+}
+
+/// The saving thread: save a while after each change
+fn save_changes(changed: Receiver<()>) {
+    while changed.recv().is_ok() {
+        thread::sleep(SAVE_DELAY);
+        while changed.try_recv().is_ok() {}
+        flush();
+    }
+}
+
+/// Save the changes not saved yet, e.g. as the app quits
+pub fn flush() {
+    let Some(store) = STORE.get() else {
+        return;
+    };
+    let _saving = store.saving.lock().unwrap();
+    if !store.dirty.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    let items = store.items.lock().unwrap().clone();
+    if let Err(e) = items.save(&store.path) {
+        eprintln!("Warning: failed to save storage to {}: {}", store.path.display(), e);
+    }
+}
+
+fn read<R: Default>(f: impl FnOnce(&Items) -> R) -> R {
+    STORE.get().map(|store| f(&store.items.lock().unwrap())).unwrap_or_default()
+}
+
+/// Change the items, saving them soon if `f` reports a change
+fn update(f: impl FnOnce(&mut Items) -> Result<bool, String>) -> Result<(), String> {
+    let Some(store) = STORE.get() else {
+        return Ok(());
+    };
+    let mut items = store.items.lock().unwrap();
+    if f(&mut items)? {
+        store.dirty.store(true, Ordering::SeqCst);
+        let _ = store.changes.lock().unwrap().send(());
+    }
+    Ok(())
+}
+
+/// Install `rasen.storage`, also available as the `localStorage` global
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let storage = Object::new(ctx.clone())?;
+    storage.set(
+        "getItem",
+        Function::new(ctx.clone(), |key: Coerced<String>| read(|items| items.get(&key.0).map(str::to_string)))?,
+    )?;
+    storage.set(
+        "setItem",
+        Function::new(
+            ctx.clone(),
+            |ctx: Ctx<'js>, key: Coerced<String>, value: Coerced<String>| -> rquickjs::Result<()> {
+                update(|items| {
+                    let changed = items.get(&key.0) != Some(value.0.as_str());
+                    items.set(key.0, value.0).map(|_| changed)
+                })
+                .map_err(|message| Exception::throw_range(&ctx, &message))
+            },
+        )?,
+    )?;
+    storage.set(
+        "removeItem",
+        Function::new(ctx.clone(), |key: Coerced<String>| {
+            let _ = update(|items| Ok(items.remove(&key.0)));
+        })?,
+    )?;
+    storage.set(
+        "clear",
+        Function::new(ctx.clone(), || {
+            let _ = update(|items| {
+                let changed = !items.is_empty();
+                items.clear();
+                Ok(changed)
+            });
+        })?,
+    )?;
+    storage.set(
+        "key",
+        Function::new(ctx.clone(), |index: f64| {
+            read(|items| (index >= 0.0).then(|| items.key(index as usize)).flatten().map(str::to_string))
+        })?,
+    )?;
+    storage.prop("length", Accessor::new_get(|| read(Items::len)))?;
+
+    ctx.globals().set("localStorage", storage.clone())?;
+    rasen.set("storage", storage)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let mut items = Items::default();
+        items.set("theme".to_string(), "dark".to_string()).unwrap();
+        items.set("zoom".to_string(), "1.25".to_string()).unwrap();
+        items.set("theme".to_string(), "light".to_string()).unwrap();
+        assert_eq!(items.get("theme"), Some("light"));
+        assert_eq!(items.key(1), Some("zoom"));
+        assert_eq!(items.used, "theme".len() + "light".len() + "zoom".len() + "1.25".len());
+
+        // Over the quota nothing changes
+        assert!(items.set("big".to_string(), "x".repeat(QUOTA)).is_err());
+        assert_eq!(items.len(), 2);
+
+        assert!(items.remove("zoom"));
+        assert!(!items.remove("zoom"));
+        assert_eq!(items.used, "theme".len() + "light".len());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("rasen-storage-{}", std::process::id())).join("storage.json");
+        let mut items = Items::default();
+        items.set("name".to_string(), "Ada \"the\" first\n".to_string()).unwrap();
+        items.save(&path).unwrap();
+        assert_eq!(Items::load(&path), items);
+        assert!(Items::load(&path.with_file_name("missing.json")).is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+//! Throttled and debounced handlers, `onMouseMove: { handler, throttle: 16 }`
+//!
+//! High-frequency events are rate-limited before they reach JS, so a
+//! listener on every mouse move doesn't cost a call into QuickJS per event.
+//! A throttled handler runs at most once per interval: the first event runs
+//! it right away, and the last one held back runs when the interval ends,
+//! so the final position isn't lost. A debounced handler runs once events
+//! stop for the delay, with the last one.
+//!
+//! Events queue per element and event name rather than per handler, since
+//! an inline function gets a new handler ID whenever it's rendered again.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often a handler may run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rate {
+    /// At most once per interval
+    Throttle(Duration),
+    /// Once events stop for the delay
+    Debounce(Duration),
+}
+
+impl Rate {
+    /// The rate of `{ throttle, debounce }` in milliseconds; throttling
+    /// wins if both are set
+    pub fn from_millis(throttle: Option<f64>, debounce: Option<f64>) -> Option<Self> {
+        let millis = |ms: f64| Duration::from_secs_f64(ms.max(0.0) / 1000.0);
+        match (throttle, debounce) {
+            (Some(ms), _) => Some(Rate::Throttle(millis(ms))),
+            (None, Some(ms)) => Some(Rate::Debounce(millis(ms))),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A rate-limited handler's queue: its element and event name, and rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limit {
+    pub key: String,
+    pub rate: Rate,
+}
+
+impl Limit {
+    pub fn new(element: &str, event: &str, rate: Rate) -> Self {
+        Self { key: format!("{}:{}", element, event), rate }
+    }
+}
+
+/// What to do with an event offered to a [`RateLimiter`]
+#[derive(Debug, PartialEq)]
+pub enum Offer<T> {
+    /// Run the handler with it now
+    Call(T),
+    /// Held back; call [`RateLimiter::fire`] after the delay
+    Schedule(Duration),
+    /// Held back, and a `fire` is already scheduled
+    Queued,
+}
+
+struct Queue<T> {
+    rate: Rate,
+    /// When the handler last ran
+    last: Option<Instant>,
+    /// The event held back, and when it may run
+    pending: Option<(T, Instant)>,
+}
+
+impl<T> Queue<T> {
+    /// Whether the queue makes no difference anymore, so it can go
+    fn idle(&self, now: Instant) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+        match (self.rate, self.last) {
+            (Rate::Throttle(interval), Some(last)) => now >= last + interval,
+            _ => true,
+        }
+    }
+}
+
+/// Events of rate-limited handlers, held back until they may run
+pub struct RateLimiter<T> {
+    queues: HashMap<String, Queue<T>>,
+}
+
+impl<T> Default for RateLimiter<T> {
+    fn default() -> Self {
+        Self { queues: HashMap::new() }
+    }
+}
+
+impl<T> RateLimiter<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offer an event, replacing any held back in the same queue
+    pub fn offer(&mut self, limit: &Limit, event: T, now: Instant) -> Offer<T> {
+        if !self.queues.contains_key(&limit.key) {
+            self.queues.retain(|_, queue| !queue.idle(now));
+        }
+        let queue = self.queues.entry(limit.key.clone()).or_insert(Queue { rate: limit.rate, last: None, pending: None });
+        queue.rate = limit.rate;
+        let due = match (limit.rate, queue.last) {
+            (Rate::Throttle(interval), Some(last)) => last + interval,
+            (Rate::Throttle(_), None) => now,
+            (Rate::Debounce(delay), _) => now + delay,
+        };
+        let scheduled = queue.pending.is_some();
+        if !scheduled && due <= now {
+            queue.last = Some(now);
+            return Offer::Call(event);
+        }
+        // A throttled event keeps the slot the first one held back got
+        let due = match (&queue.pending, limit.rate) {
+            (Some((_, pending_due)), Rate::Throttle(_)) => *pending_due,
+            _ => due,
+        };
+        queue.pending = Some((event, due));
+        if scheduled {
+            Offer::Queued
+        } else {
+            Offer::Schedule(due - now)
+        }
+    }
+
+    /// Run the event held back in the queue if it's due, or schedule
+    /// another `fire` for when it is
+    pub fn fire(&mut self, key: &str, now: Instant) -> Offer<T> {
+        let Some(queue) = self.queues.get_mut(key) else {
+            return Offer::Queued;
+        };
+        match queue.pending.take() {
+            Some((event, due)) if due <= now => {
+                queue.last = Some(now);
+                Offer::Call(event)
+            }
+            Some((event, due)) => {
+                queue.pending = Some((event, due));
+                Offer::Schedule(due - now)
+            }
+            None => Offer::Queued,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_throttle_runs_first_and_last_event() {
+        let mut limiter = RateLimiter::new();
+        let limit = Limit::new("knob", "mousemove", Rate::Throttle(ms(16)));
+        let start = Instant::now();
+        assert_eq!(limiter.offer(&limit, 1, start), Offer::Call(1));
+        assert_eq!(limiter.offer(&limit, 2, start + ms(4)), Offer::Schedule(ms(12)));
+        assert_eq!(limiter.offer(&limit, 3, start + ms(8)), Offer::Queued);
+        assert_eq!(limiter.fire(&limit.key, start + ms(16)), Offer::Call(3));
+        // Nothing left to run
+        assert_eq!(limiter.fire(&limit.key, start + ms(32)), Offer::Queued);
+        assert_eq!(limiter.offer(&limit, 4, start + ms(40)), Offer::Call(4));
+    }
+
+    #[test]
+    fn test_debounce_waits_for_events_to_stop() {
+        let mut limiter = RateLimiter::new();
+        let limit = Limit::new("search", "mousemove", Rate::Debounce(ms(100)));
+        let start = Instant::now();
+        assert_eq!(limiter.offer(&limit, 1, start), Offer::Schedule(ms(100)));
+        assert_eq!(limiter.offer(&limit, 2, start + ms(60)), Offer::Queued);
+        // The second event pushed the delay back
+        assert_eq!(limiter.fire(&limit.key, start + ms(100)), Offer::Schedule(ms(60)));
+        assert_eq!(limiter.fire(&limit.key, start + ms(160)), Offer::Call(2));
+    }
+}
@@ -0,0 +1,235 @@
+//! `setTimeout` / `setInterval` and their `clear*` counterparts
+//!
+//! Each runtime owns a [`Timers`] queue. The JS functions only record due
+//! times; the window's timer task sleeps on GPUI's executor until the next
+//! one is due (or a new timer wakes it) and calls [`Timers::fire`] through
+//! the runtime, re-rendering afterwards.
+//!
+//! Like `activity`, callbacks and their extra arguments stay on the JS side in
+//! a table keyed by timer ID.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use rquickjs::function::{Opt, Rest};
+use rquickjs::{Array, Ctx, Exception, Function, Object, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::js_runtime;
+
+/// Global holding `[callback, ...args]` by timer ID
+const CALLBACKS: &str = "__timers";
+
+/// Shortest interval period, so `setInterval(fn, 0)` can't spin the loop
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Longest delay, the most browsers store (2^31 - 1 ms, about 24.8 days)
+const MAX_DELAY_MS: f64 = 2_147_483_647.0;
+
+/// A runtime's pending timers; clones share the queue
+#[derive(Clone)]
+pub struct Timers {
+    queue: Arc<Mutex<TimerQueue>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct TimerQueue {
+    next_id: u32,
+    timers: Vec<Timer>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    id: u32,
+    due: Instant,
+    /// Period of `setInterval` timers
+    interval: Option<Duration>,
+}
+
+impl TimerQueue {
+    fn add(&mut self, now: Instant, delay: Duration, repeat: bool) -> u32 {
+        // IDs start at 1, so they are always truthy
+        self.next_id += 1;
+        let id = self.next_id;
+        let interval = repeat.then_some(delay.max(MIN_INTERVAL));
+        self.timers.push(Timer { id, due: now + delay, interval });
+        id
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.timers.retain(|timer| timer.id != id);
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.due).min()
+    }
+
+    /// Take the timers due at `now`, in due order; intervals are rescheduled
+    fn take_due(&mut self, now: Instant) -> Vec<Timer> {
+        let mut due: Vec<Timer> = self.timers.iter().filter(|timer| timer.due <= now).copied().collect();
+        due.sort_by_key(|timer| (timer.due, timer.id));
+        self.timers.retain(|timer| timer.due > now || timer.interval.is_some());
+        for timer in &mut self.timers {
+            if let Some(interval) = timer.interval.filter(|_| timer.due <= now) {
+                // Skip missed periods instead of firing them in a burst
+                timer.due = now + interval;
+            }
+        }
+        due
+    }
+}
+
+impl Timers {
+    /// Create an empty queue; the receiver wakes when a timer is added
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { queue: Arc::default(), wake }, wakeups)
+    }
+
+    /// When the earliest pending timer is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.lock().unwrap().next_due()
+    }
+
+    /// Run the callbacks of due timers; returns whether any ran
+    pub fn fire(&self, ctx: &Ctx) -> bool {
+        let due = self.queue.lock().unwrap().take_due(Instant::now());
+        let Ok(callbacks) = ctx.globals().get::<_, Object>(CALLBACKS) else {
+            return false;
+        };
+
+        let mut fired = false;
+        for timer in due {
+            // Cleared by an earlier callback in this batch
+            let Ok(entry) = callbacks.get::<_, Array>(timer.id.to_string()) else {
+                continue;
+            };
+            if timer.interval.is_none() {
+                let _ = callbacks.remove(timer.id.to_string());
+            }
+            let result = entry.get::<Function>(0).and_then(|callback| {
+                let args: Vec<Value> = entry.iter::<Value>().skip(1).collect::<rquickjs::Result<_>>()?;
+                callback.call::<_, Value>((Rest(args),))
+            });
+            match result {
+                Ok(result) => js_runtime::report_rejection(ctx, &result, format!("async timer callback {}", timer.id)),
+                Err(e) => eprintln!("Error in timer callback {}: {:?}", timer.id, e),
+            }
+            fired = true;
+        }
+        fired
+    }
+
+    fn schedule<'js>(&self, ctx: &Ctx<'js>, callback: Value<'js>, delay: Opt<f64>, args: Rest<Value<'js>>, repeat: bool) -> rquickjs::Result<u32> {
+        let Some(callback) = callback.into_function() else {
+            return Err(Exception::throw_type(ctx, "Timer callback must be a function"));
+        };
+        let id = self.queue.lock().unwrap().add(Instant::now(), delay_of(delay.0), repeat);
+        let entry = Array::new(ctx.clone())?;
+        entry.set(0, callback)?;
+        for (i, arg) in args.0.into_iter().enumerate() {
+            entry.set(i + 1, arg)?;
+        }
+        let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+        callbacks.set(id.to_string(), entry)?;
+
+        let _ = self.wake.unbounded_send(());
+        Ok(id)
+    }
+}
+
+/// The delay JS asked for in `ms`
+///
+/// Like browsers, missing, negative and non-finite delays mean "as soon as
+/// possible"; longer ones than [`MAX_DELAY_MS`] are cut to it, so they
+/// can't overflow the due time.
+pub fn delay_of(ms: Option<f64>) -> Duration {
+    let ms = ms.filter(|ms| ms.is_finite()).unwrap_or(0.0).clamp(0.0, MAX_DELAY_MS);
+    Duration::from_secs_f64(ms / 1000.0)
+}
+
+/// Install the timer globals for a runtime's queue
+pub fn install<'js>(ctx: &Ctx<'js>, timers: &Timers) -> rquickjs::Result<()> {
+    let globals = ctx.globals();
+    globals.set(CALLBACKS, Object::new(ctx.clone())?)?;
+
+    for (name, repeat) in [("setTimeout", false), ("setInterval", true)] {
+        let timers = timers.clone();
+        globals.set(
+            name,
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>, callback: Value<'js>, delay: Opt<f64>, args: Rest<Value<'js>>| {
+                timers.schedule(&ctx, callback, delay, args, repeat)
+            })?,
+        )?;
+    }
+    // Either clears both kinds, as in browsers
+    for name in ["clearTimeout", "clearInterval"] {
+        let timers = timers.clone();
+        globals.set(
+            name,
+            Function::new(ctx.clone(), move |ctx: Ctx<'js>, id: Opt<Value<'js>>| -> rquickjs::Result<()> {
+                let Some(id) = id.0.and_then(|id| id.as_number()).filter(|id| *id >= 1.0) else {
+                    return Ok(());
+                };
+                let id = id as u32;
+                timers.queue.lock().unwrap().remove(id);
+                let callbacks: Object = ctx.globals().get(CALLBACKS)?;
+                callbacks.remove(id.to_string())
+            })?,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeouts_fire_in_due_order() {
+        let start = Instant::now();
+        let mut queue = TimerQueue::default();
+        let late = queue.add(start, Duration::from_millis(20), false);
+        let early = queue.add(start, Duration::from_millis(10), false);
+        let cleared = queue.add(start, Duration::from_millis(5), false);
+        queue.remove(cleared);
+        assert_eq!(queue.next_due(), Some(start + Duration::from_millis(10)));
+
+        assert!(queue.take_due(start).is_empty());
+        let due: Vec<u32> = queue.take_due(start + Duration::from_millis(30)).iter().map(|t| t.id).collect();
+        assert_eq!(due, vec![early, late]);
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn test_intervals_reschedule() {
+        let start = Instant::now();
+        let mut queue = TimerQueue::default();
+        let id = queue.add(start, Duration::from_millis(10), true);
+
+        // Missed periods are skipped, not replayed
+        let now = start + Duration::from_millis(35);
+        assert_eq!(queue.take_due(now).len(), 1);
+        assert_eq!(queue.next_due(), Some(now + Duration::from_millis(10)));
+
+        let spin = queue.add(start, Duration::ZERO, true);
+        assert_eq!(queue.timers.iter().find(|t| t.id == spin).unwrap().interval, Some(MIN_INTERVAL));
+        queue.remove(id);
+        queue.remove(spin);
+        assert_eq!(queue.next_due(), None);
+    }
+
+    #[test]
+    fn test_delays_are_clamped() {
+        let max = Duration::from_millis(2_147_483_647);
+        assert_eq!(delay_of(Some(1e300)), max);
+        assert_eq!(delay_of(Some(f64::INFINITY)), Duration::ZERO);
+        assert_eq!(delay_of(Some(f64::NEG_INFINITY)), Duration::ZERO);
+        assert_eq!(delay_of(Some(f64::NAN)), Duration::ZERO);
+        assert_eq!(delay_of(None), Duration::ZERO);
+        assert_eq!(delay_of(Some(250.0)), Duration::from_millis(250));
+
+        let mut queue = TimerQueue::default();
+        queue.add(Instant::now(), delay_of(Some(1e300)), true);
+        assert!(queue.next_due().is_some());
+    }
+}
@@ -0,0 +1,298 @@
+//! Tree view drawn for `tree({ roots, loadChildren, onSelect })` elements
+//!
+//! The JS side owns the tree: which nodes are expanded, children loaded so
+//! far, and the selection. Each render it flattens the visible nodes into
+//! rows of plain data, so even large expanded trees encode cheaply, and this
+//! module draws them in a `uniform_list` that only lays out the rows in view.
+//! Rows call back into JS with a node's ID to toggle or select it.
+//!
+//! Clicking a tree makes it take arrow keys from the window (see
+//! [`TreeTracker::key`]) until the user clicks elsewhere.
+
+use gpui::*;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::elements::RenderContext;
+
+/// Indentation per level
+const INDENT: f32 = 16.0;
+const ROW_HEIGHT: f32 = 24.0;
+
+/// A visible node, as flattened by the JS side
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TreeRow {
+    pub id: String,
+    pub label: String,
+    /// Nesting level, 0 for roots
+    #[serde(default)]
+    pub depth: usize,
+    /// Has or may have children
+    #[serde(default)]
+    pub expandable: bool,
+    #[serde(default)]
+    pub expanded: bool,
+    /// Children are being loaded
+    #[serde(default)]
+    pub loading: bool,
+}
+
+/// Rows and selection of a `tree` element
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeView {
+    /// Shared with the row renderer, which runs after the render pass
+    pub rows: Arc<Vec<TreeRow>>,
+    pub selected: Option<String>,
+}
+
+/// What a key press asks the JS side to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeAction {
+    Select(String),
+    Toggle(String),
+}
+
+/// Navigate rows from the selected one
+///
+/// Up/Down move the selection, Right expands a collapsed node or moves to
+/// its first child, Left collapses an expanded node or moves to its parent,
+/// Home/End go to the first or last row, and Enter/Space toggle.
+pub fn navigate(rows: &[TreeRow], selected: Option<&str>, key: &str) -> Option<TreeAction> {
+    if rows.is_empty() {
+        return None;
+    }
+    let Some(index) = selected.and_then(|id| rows.iter().position(|row| row.id == id)) else {
+        // Nothing selected yet: any navigation key starts at the top
+        return matches!(key, "up" | "down" | "home" | "end" | "left" | "right")
+            .then(|| TreeAction::Select(rows[0].id.clone()));
+    };
+    let row = &rows[index];
+    let select = |index: usize| Some(TreeAction::Select(rows[index].id.clone()));
+    match key {
+        "up" => select(index.saturating_sub(1)),
+        "down" => select((index + 1).min(rows.len() - 1)),
+        "home" => select(0),
+        "end" => select(rows.len() - 1),
+        "right" if row.expandable && !row.expanded => Some(TreeAction::Toggle(row.id.clone())),
+        "right" => rows
+            .get(index + 1)
+            .filter(|child| row.expanded && child.depth > row.depth)
+            .map(|child| TreeAction::Select(child.id.clone())),
+        "left" if row.expanded => Some(TreeAction::Toggle(row.id.clone())),
+        "left" => rows[..index]
+            .iter()
+            .rposition(|parent| parent.depth < row.depth)
+            .and_then(select),
+        "enter" | "space" if row.expandable => Some(TreeAction::Toggle(row.id.clone())),
+        _ => None,
+    }
+}
+
+/// Scrolling of trees and the one taking keys, kept across frames
+///
+/// Works like [`SortTracker`](crate::sortable::SortTracker): cheap to clone,
+/// and trees not rendered in a frame are dropped by
+/// [`finish_frame`](Self::finish_frame).
+#[derive(Clone, Default)]
+pub struct TreeTracker {
+    trees: Rc<RefCell<HashMap<String, TrackedTree>>>,
+    /// The tree taking arrow keys
+    active: Rc<RefCell<Option<String>>>,
+    seen: Rc<RefCell<HashSet<String>>>,
+}
+
+struct TrackedTree {
+    scroll: UniformListScrollHandle,
+    /// Rows and selection as last rendered, for key presses
+    view: TreeView,
+}
+
+impl TreeTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn track(&self, id: &str, view: &TreeView) -> UniformListScrollHandle {
+        self.seen.borrow_mut().insert(id.to_string());
+        let mut trees = self.trees.borrow_mut();
+        let tree = trees.entry(id.to_string()).or_insert_with(|| TrackedTree {
+            scroll: UniformListScrollHandle::new(),
+            view: TreeView::default(),
+        });
+        tree.view = view.clone();
+        tree.scroll.clone()
+    }
+
+    fn activate(&self, id: Option<&str>) {
+        *self.active.borrow_mut() = id.map(str::to_string);
+    }
+
+    /// Handle a key press for the active tree; returns the tree's ID and what to do
+    pub fn key(&self, keystroke: &Keystroke) -> Option<(String, TreeAction)> {
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt || modifiers.shift {
+            return None;
+        }
+        let active = self.active.borrow().clone()?;
+        let trees = self.trees.borrow();
+        let tree = trees.get(&active)?;
+        let action = navigate(&tree.view.rows, tree.view.selected.as_deref(), &keystroke.key)?;
+        // Keep the new selection in view
+        if let TreeAction::Select(id) = &action {
+            if let Some(index) = tree.view.rows.iter().position(|row| &row.id == id) {
+                tree.scroll.scroll_to_item(index, ScrollStrategy::Top);
+            }
+        }
+        Some((active, action))
+    }
+
+    /// End a render pass, dropping state for trees that are gone
+    pub fn finish_frame(&self) {
+        let seen = std::mem::take(&mut *self.seen.borrow_mut());
+        self.trees.borrow_mut().retain(|id, _| seen.contains(id));
+        let mut active = self.active.borrow_mut();
+        if active.as_ref().is_some_and(|id| !seen.contains(id)) {
+            *active = None;
+        }
+    }
+}
+
+type RowCallback = Rc<dyn Fn(String, &mut Window, &mut App)>;
+
+/// Draw the visible rows of tree `id`
+pub fn render(
+    id: &str,
+    view: &TreeView,
+    on_toggle: Option<Box<dyn Fn(String, &mut Window, &mut App) + 'static>>,
+    on_select: Option<Box<dyn Fn(String, &mut Window, &mut App) + 'static>>,
+    render_ctx: &RenderContext,
+) -> AnyElement {
+    let trees = render_ctx.trees;
+    let scroll = trees.track(id, view);
+    let callback = |handler: Option<Box<dyn Fn(String, &mut Window, &mut App) + 'static>>| -> RowCallback {
+        match handler {
+            Some(handler) => Rc::from(handler),
+            None => Rc::new(|_, _, _| {}),
+        }
+    };
+    let (on_toggle, on_select) = (callback(on_toggle), callback(on_select));
+    let zoom = render_ctx.zoom;
+    let selected_bg = if render_ctx.dark { rgb(0x1e3a8a) } else { rgb(0xdbeafe) };
+    let (rows, selected, tree_id) = (view.rows.clone(), view.selected.clone(), id.to_string());
+
+    let list = uniform_list(ElementId::Name(format!("{}-rows", id).into()), rows.len(), move |range, _, _| {
+        range
+            .map(|index| {
+                let row = &rows[index];
+                let chevron = if row.loading {
+                    "…"
+                } else if !row.expandable {
+                    ""
+                } else if row.expanded {
+                    "▾"
+                } else {
+                    "▸"
+                };
+                let (toggle, node) = (on_toggle.clone(), row.id.clone());
+                let chevron = div()
+                    .id(ElementId::Name(format!("{}-toggle-{}", tree_id, index).into()))
+                    .w(px(INDENT * zoom))
+                    .flex_none()
+                    .flex()
+                    .justify_center()
+                    .child(chevron)
+                    .on_click(move |_, window, cx| {
+                        cx.stop_propagation();
+                        toggle(node.clone(), window, cx);
+                    });
+
+                let (select, node) = (on_select.clone(), row.id.clone());
+                let mut item = div()
+                    .id(ElementId::Name(format!("{}-row-{}", tree_id, index).into()))
+                    .h(px(ROW_HEIGHT * zoom))
+                    .flex()
+                    .flex_row()
+                    .items_center()
+                    .pl(px(row.depth as f32 * INDENT * zoom))
+                    .text_size(px(13.0 * zoom))
+                    .whitespace_nowrap()
+                    .child(chevron)
+                    .child(row.label.clone())
+                    .on_click(move |_, window, cx| select(node.clone(), window, cx));
+                if selected.as_deref() == Some(row.id.as_str()) {
+                    item = item.bg(selected_bg);
+                }
+                item
+            })
+            .collect()
+    })
+    .track_scroll(scroll)
+    .size_full();
+
+    // Presses inside the tree give it the keyboard, presses elsewhere take it away
+    let (inside, outside, tree_id) = (trees.clone(), trees.clone(), id.to_string());
+    div()
+        .size_full()
+        .on_mouse_down(MouseButton::Left, move |_, _, _| inside.activate(Some(&tree_id)))
+        .on_mouse_down_out(move |_, _, _| outside.activate(None))
+        .child(list)
+        .into_any_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<TreeRow> {
+        // src (expanded) > main.rs, lib (collapsed); docs (collapsed)
+        let row = |id: &str, depth, expandable, expanded| TreeRow {
+            id: id.to_string(),
+            label: id.to_string(),
+            depth,
+            expandable,
+            expanded,
+            loading: false,
+        };
+        vec![
+            row("src", 0, true, true),
+            row("main.rs", 1, false, false),
+            row("lib", 1, true, false),
+            row("docs", 0, true, false),
+        ]
+    }
+
+    fn select(id: &str) -> Option<TreeAction> {
+        Some(TreeAction::Select(id.to_string()))
+    }
+
+    fn toggle(id: &str) -> Option<TreeAction> {
+        Some(TreeAction::Toggle(id.to_string()))
+    }
+
+    #[test]
+    fn test_up_down() {
+        let rows = rows();
+        assert_eq!(navigate(&rows, None, "down"), select("src"));
+        assert_eq!(navigate(&rows, Some("src"), "down"), select("main.rs"));
+        assert_eq!(navigate(&rows, Some("src"), "up"), select("src"));
+        assert_eq!(navigate(&rows, Some("docs"), "down"), select("docs"));
+        assert_eq!(navigate(&rows, Some("main.rs"), "end"), select("docs"));
+        assert_eq!(navigate(&rows, Some("main.rs"), "x"), None);
+        assert_eq!(navigate(&[], None, "down"), None);
+    }
+
+    #[test]
+    fn test_left_right() {
+        let rows = rows();
+        assert_eq!(navigate(&rows, Some("src"), "right"), select("main.rs"));
+        assert_eq!(navigate(&rows, Some("lib"), "right"), toggle("lib"));
+        assert_eq!(navigate(&rows, Some("main.rs"), "right"), None);
+        assert_eq!(navigate(&rows, Some("src"), "left"), toggle("src"));
+        assert_eq!(navigate(&rows, Some("lib"), "left"), select("src"));
+        assert_eq!(navigate(&rows, Some("docs"), "left"), None);
+        assert_eq!(navigate(&rows, Some("docs"), "enter"), toggle("docs"));
+        assert_eq!(navigate(&rows, Some("main.rs"), "enter"), None);
+    }
+}
@@ -2,6 +2,86 @@
 //! Parses Tailwind-style class strings and converts them to GPUI style properties
 
 use gpui::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use crate::animation::{Animate, Easing, TransitionProperty};
+use crate::config::ThemeConfig;
+use crate::tw_colors;
+
+/// Theme installed with [`set_theme`]
+static THEME: RwLock<Option<Theme>> = RwLock::new(None);
+
+#[cfg(test)]
+thread_local! {
+    /// Theme for the current test alone, in place of [`THEME`], so tests
+    /// running in parallel don't parse with each other's themes
+    static TEST_THEME: std::cell::RefCell<Option<Theme>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Class strings whose parsed styles [`parse_cached`] keeps
+const CACHE_CAPACITY: usize = 4096;
+
+static CACHE: Mutex<Option<ParseCache>> = Mutex::new(None);
+
+/// Design tokens from the config's `theme` section
+///
+/// Consulted before the built-in tables, so a theme can add names
+/// (`bg-brand-600`, `p-gutter`, `text-hero`, `3xl:`) as well as override
+/// Tailwind's defaults.
+#[derive(Debug, Default)]
+pub struct Theme {
+    pub colors: HashMap<String, Hsla>,
+    pub spacing: HashMap<String, Length>,
+    pub font_sizes: HashMap<String, Pixels>,
+    pub screens: HashMap<String, Pixels>,
+}
+
+impl Theme {
+    /// Parse the config's values; the error names the first invalid entry
+    pub fn from_config(config: &ThemeConfig) -> Result<Self, String> {
+        fn convert<T>(
+            section: &str,
+            entries: &HashMap<String, String>,
+            parse: impl Fn(&str) -> Option<T>,
+        ) -> Result<HashMap<String, T>, String> {
+            entries
+                .iter()
+                .map(|(name, value)| match parse(value) {
+                    Some(parsed) => Ok((name.clone(), parsed)),
+                    None => Err(format!("Invalid theme.{} value for '{}': '{}'", section, name, value)),
+                })
+                .collect()
+        }
+        
+        Ok(Self {
+            colors: convert("colors", &config.colors, parse_color)?,
+            spacing: convert("spacing", &config.spacing, parse_length)?,
+            font_sizes: convert("fontSize", &config.font_sizes, parse_font_size)?,
+            // Screen widths take the same absolute units as font sizes
+            screens: convert("screens", &config.screens, parse_font_size)?,
+        })
+    }
+}
+
+/// Install the theme used by every later [`parse`]
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap() = Some(theme);
+    // Cached styles were resolved against the old theme
+    *CACHE.lock().unwrap() = None;
+}
+
+/// Look a value up in the installed theme
+#[cfg(not(test))]
+fn theme_value<T>(lookup: impl FnOnce(&Theme) -> Option<T>) -> Option<T> {
+    THEME.read().unwrap().as_ref().and_then(lookup)
+}
+
+/// Look a value up in the current test's theme
+#[cfg(test)]
+fn theme_value<T>(lookup: impl FnOnce(&Theme) -> Option<T>) -> Option<T> {
+    TEST_THEME.with(|theme| theme.borrow().as_ref().and_then(lookup))
+}
 
 /// Parsed style properties from Tailwind classes
 #[derive(Default, Debug, Clone)]
@@ -11,17 +91,26 @@ pub struct ParsedStyles {
     pub flex_direction: Option<FlexDirection>,
     pub justify_content: Option<JustifyContent>,
     pub align_items: Option<AlignItems>,
+    pub align_self: Option<AlignSelf>,
+    /// Position among siblings; applied by reordering children at render time
+    pub order: Option<i32>,
     pub flex_wrap: Option<FlexWrap>,
     pub flex_grow: Option<f32>,
     pub flex_shrink: Option<f32>,
     
+    // Grid
+    pub grid_cols: Option<u16>,
+    pub grid_rows: Option<u16>,
+    pub col_span: Option<GridSpan>,
+    pub row_span: Option<GridSpan>,
+    
     // Sizing
-    pub width: Option<Length>,
-    pub height: Option<Length>,
-    pub min_width: Option<Length>,
-    pub min_height: Option<Length>,
-    pub max_width: Option<Length>,
-    pub max_height: Option<Length>,
+    pub width: Option<SizeValue>,
+    pub height: Option<SizeValue>,
+    pub min_width: Option<SizeValue>,
+    pub min_height: Option<SizeValue>,
+    pub max_width: Option<SizeValue>,
+    pub max_height: Option<SizeValue>,
     
     // Spacing
     pub padding: Option<Edges<Length>>,
@@ -33,18 +122,182 @@ pub struct ParsedStyles {
     // Background & Border
     pub background: Option<Hsla>,
     pub border_color: Option<Hsla>,
-    pub border_width: Option<Pixels>,
-    pub border_radius: Option<Pixels>,
+    pub border_width: Edges<Option<Pixels>>,
+    pub border_radius: Corners<Option<Pixels>>,
     
     // Text
     pub text_color: Option<Hsla>,
     pub font_size: Option<Pixels>,
     pub font_weight: Option<FontWeight>,
+    pub line_height: Option<DefiniteLength>,
+    pub font_style: Option<FontStyle>,
+    pub text_decoration: Option<TextDecoration>,
+    pub text_transform: Option<TextTransform>,
+    pub white_space: Option<WhiteSpace>,
+    pub text_ellipsis: bool,
+    pub line_clamp: Option<usize>,
+    pub overflow: Option<Overflow>,
     
     // Effects
-    pub shadow: Option<BoxShadow>,
+    pub shadow: Option<Vec<BoxShadow>>,
+    /// Focus ring, drawn as solid shadows around the border box
+    pub ring_width: Option<Pixels>,
+    pub ring_color: Option<Hsla>,
+    pub ring_offset_width: Option<Pixels>,
+    pub ring_offset_color: Option<Hsla>,
     pub opacity: Option<f32>,
+    /// Filters; GPUI can't filter painted content, see `elements::apply_filters`
+    pub blur: Option<Pixels>,
+    pub backdrop_blur: Option<Pixels>,
+    pub brightness: Option<f32>,
     pub visibility: Option<Visibility>,
+    pub cursor: Option<CursorStyle>,
+    /// `translate-x-*`/`translate-y-*`, drawn as a relative offset
+    pub translate_x: Option<Pixels>,
+    pub translate_y: Option<Pixels>,
+    /// `scale-*` as a factor, drawn by resizing the box (see `elements`)
+    pub scale: Option<f32>,
+    
+    // Transitions & animation
+    pub transition: Option<TransitionProperty>,
+    pub transition_duration: Option<Duration>,
+    pub transition_easing: Option<Easing>,
+    pub animate: Option<Animate>,
+    
+    // State variants (hover:, active:, focus:)
+    pub hover: Option<Box<ParsedStyles>>,
+    pub active: Option<Box<ParsedStyles>>,
+    pub focus: Option<Box<ParsedStyles>>,
+    
+    // Dark mode variant (dark:), applied when the OS appearance is dark
+    pub dark: Option<Box<ParsedStyles>>,
+    
+    // High-contrast variant (contrast-more:), applied when the OS asks for
+    // more contrast
+    pub contrast_more: Option<Box<ParsedStyles>>,
+    
+    // Responsive variants (sm:, md:, ...) as (min window width, styles),
+    // sorted by ascending width so larger breakpoints win
+    pub breakpoints: Vec<(Pixels, ParsedStyles)>,
+    
+    // Classes the parser didn't understand, for diagnostics
+    pub unknown_classes: Vec<String>,
+}
+
+/// How many tracks a grid item spans
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridSpan {
+    Tracks(u16),
+    Full,
+}
+
+/// Line drawn through or under text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextDecoration {
+    None,
+    Underline,
+    LineThrough,
+}
+
+/// Case change applied to the text content at render time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextTransform {
+    None,
+    Uppercase,
+    Lowercase,
+    Capitalize,
+}
+
+impl TextTransform {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            TextTransform::None => text.to_string(),
+            TextTransform::Uppercase => text.to_uppercase(),
+            TextTransform::Lowercase => text.to_lowercase(),
+            TextTransform::Capitalize => {
+                // Uppercase the first letter of each word, leaving the rest as is
+                let mut result = String::with_capacity(text.len());
+                let mut word_start = true;
+                for c in text.chars() {
+                    if word_start && c.is_alphanumeric() {
+                        result.extend(c.to_uppercase());
+                    } else {
+                        result.push(c);
+                    }
+                    word_start = c.is_whitespace();
+                }
+                result
+            }
+        }
+    }
+}
+
+/// A sizing value; viewport units can only be resolved against the window at
+/// render time
+#[derive(Debug, Clone)]
+pub enum SizeValue {
+    Length(Length),
+    /// Fraction of the window width (`w-screen` is 1.0)
+    Vw(f32),
+    /// Fraction of the window height (`h-screen` is 1.0)
+    Vh(f32),
+}
+
+impl From<Length> for SizeValue {
+    fn from(length: Length) -> Self {
+        SizeValue::Length(length)
+    }
+}
+
+/// Parse a class string, reusing the styles of earlier calls with the same string
+///
+/// Most class strings repeat from one render to the next, so re-renders of
+/// large trees mostly skip parsing.
+pub fn parse_cached(class_string: &str) -> Arc<ParsedStyles> {
+    if let Some(styles) = CACHE.lock().unwrap().as_mut().and_then(|cache| cache.get(class_string)) {
+        return styles;
+    }
+    // Parse without holding the lock, so parallel resolution isn't serialized
+    let styles = Arc::new(parse(class_string));
+    CACHE
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| ParseCache::new(CACHE_CAPACITY))
+        .insert(class_string, styles.clone());
+    styles
+}
+
+/// Least-recently-used map from class string to parsed styles
+struct ParseCache {
+    capacity: usize,
+    /// Styles and the tick they were last used at
+    entries: HashMap<String, (Arc<ParsedStyles>, u64)>,
+    tick: u64,
+}
+
+impl ParseCache {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), tick: 0 }
+    }
+    
+    fn get(&mut self, class_string: &str) -> Option<Arc<ParsedStyles>> {
+        self.tick += 1;
+        let (styles, used) = self.entries.get_mut(class_string)?;
+        *used = self.tick;
+        Some(styles.clone())
+    }
+    
+    fn insert(&mut self, class_string: &str, styles: Arc<ParsedStyles>) {
+        self.tick += 1;
+        self.entries.insert(class_string.to_string(), (styles, self.tick));
+        if self.entries.len() > self.capacity {
+            // Evict the least recently used half at once, so eviction cost is
+            // spread over many inserts
+            let mut ticks: Vec<u64> = self.entries.values().map(|(_, used)| *used).collect();
+            let cutoff = *ticks.select_nth_unstable(self.entries.len() - self.capacity / 2).1;
+            self.entries.retain(|_, (_, used)| *used >= cutoff);
+        }
+    }
 }
 
 /// Parse a Tailwind class string into style properties
@@ -52,23 +305,71 @@ pub fn parse(class_string: &str) -> ParsedStyles {
     let mut styles = ParsedStyles::default();
     
     for class in class_string.split_whitespace() {
-        parse_class(class, &mut styles);
+        if !parse_class(class, &mut styles) {
+            styles.unknown_classes.push(class.to_string());
+        }
     }
     
     styles
 }
 
-fn parse_class(class: &str, styles: &mut ParsedStyles) {
-    // Handle arbitrary values like bg-[#505050] or size-[500px]
-    if let Some((prefix, value)) = parse_arbitrary(class) {
-        apply_arbitrary(prefix, value, styles);
-        return;
+/// Parse one class into `styles`; returns whether the class was understood
+fn parse_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    // Handle variant prefixes like hover:bg-blue-600
+    if let Some((variant, rest)) = split_variant(class) {
+        if let Some(min_width) = breakpoint_width(variant) {
+            let index = match styles.breakpoints.iter().position(|(w, _)| *w == min_width) {
+                Some(index) => index,
+                None => {
+                    let index = styles.breakpoints.partition_point(|(w, _)| *w < min_width);
+                    styles.breakpoints.insert(index, (min_width, ParsedStyles::default()));
+                    index
+                }
+            };
+            return parse_class(rest, &mut styles.breakpoints[index].1);
+        }
+        
+        let target = match variant {
+            "hover" => &mut styles.hover,
+            "active" => &mut styles.active,
+            "focus" => &mut styles.focus,
+            "dark" => &mut styles.dark,
+            "contrast-more" => &mut styles.contrast_more,
+            _ => return false,
+        };
+        return parse_class(rest, target.get_or_insert_with(Default::default));
+    }
+    
+    // Handle arbitrary values like bg-[#505050] or size-[500px],
+    // optionally followed by an alpha modifier (bg-[#000]/50)
+    let (arbitrary_class, alpha) = match class.rfind("]/") {
+        Some(idx) => (&class[..idx + 1], parse_alpha(&class[idx + 2..])),
+        None => (class, None),
+    };
+    if let Some((prefix, value)) = parse_arbitrary(arbitrary_class) {
+        return apply_arbitrary(prefix, value, alpha, styles).is_some();
+    }
+    
+    // Theme font sizes and spacing take precedence over the built-in scales
+    if let Some(name) = class.strip_prefix("text-") {
+        if let Some(size) = theme_value(|t| t.font_sizes.get(name).copied()) {
+            styles.font_size = Some(size);
+            return true;
+        }
+    }
+    if let Some((prefix, name)) = class.rsplit_once('-') {
+        if let Some(length) = theme_value(|t| t.spacing.get(name).cloned()) {
+            if apply_spacing(prefix, length, styles) {
+                return true;
+            }
+        }
     }
     
     match class {
         // Display
         "flex" => styles.display = Some(Display::Flex),
         "block" => styles.display = Some(Display::Block),
+        "grid" => styles.display = Some(Display::Grid),
         "hidden" => styles.visibility = Some(Visibility::Hidden),
         "visible" => styles.visibility = Some(Visibility::Visible),
         
@@ -98,6 +399,18 @@ fn parse_class(class: &str, styles: &mut ParsedStyles) {
         "items-baseline" => styles.align_items = Some(AlignItems::Baseline),
         "items-stretch" => styles.align_items = Some(AlignItems::Stretch),
         
+        // Align Self
+        "self-start" => styles.align_self = Some(AlignSelf::FlexStart),
+        "self-end" => styles.align_self = Some(AlignSelf::FlexEnd),
+        "self-center" => styles.align_self = Some(AlignSelf::Center),
+        "self-baseline" => styles.align_self = Some(AlignSelf::Baseline),
+        "self-stretch" => styles.align_self = Some(AlignSelf::Stretch),
+        
+        // Order
+        "order-first" => styles.order = Some(-9999),
+        "order-last" => styles.order = Some(9999),
+        "order-none" => styles.order = Some(0),
+        
         // Flex Grow/Shrink
         "flex-1" => {
             styles.flex_grow = Some(1.0);
@@ -116,45 +429,66 @@ fn parse_class(class: &str, styles: &mut ParsedStyles) {
         "shrink" => styles.flex_shrink = Some(1.0),
         "shrink-0" => styles.flex_shrink = Some(0.0),
         
-        // Sizing
-        "size-full" | "w-full" if class.starts_with("w-") => styles.width = Some(relative(1.0).into()),
-        "size-full" | "h-full" if class.starts_with("h-") => styles.height = Some(relative(1.0).into()),
-        "size-full" => {
-            styles.width = Some(relative(1.0).into());
-            styles.height = Some(relative(1.0).into());
-        }
-        
-        // Colors (common Tailwind colors)
-        "bg-white" => styles.background = Some(white()),
-        "bg-black" => styles.background = Some(black()),
-        "bg-red-500" => styles.background = Some(red()),
-        "bg-green-500" => styles.background = Some(green()),
-        "bg-blue-500" => styles.background = Some(blue()),
-        "bg-yellow-500" => styles.background = Some(yellow()),
-        
-        "text-white" => styles.text_color = Some(white()),
-        "text-black" => styles.text_color = Some(black()),
-        
-        // Border
-        "border" => styles.border_width = Some(px(1.0)),
-        "border-0" => styles.border_width = Some(px(0.0)),
-        "border-2" => styles.border_width = Some(px(2.0)),
-        "border-4" => styles.border_width = Some(px(4.0)),
-        "border-8" => styles.border_width = Some(px(8.0)),
-        
-        "border-white" => styles.border_color = Some(white()),
-        "border-black" => styles.border_color = Some(black()),
-        
-        // Border Radius
-        "rounded-none" => styles.border_radius = Some(px(0.0)),
-        "rounded-sm" => styles.border_radius = Some(px(2.0)),
-        "rounded" => styles.border_radius = Some(px(4.0)),
-        "rounded-md" => styles.border_radius = Some(px(6.0)),
-        "rounded-lg" => styles.border_radius = Some(px(8.0)),
-        "rounded-xl" => styles.border_radius = Some(px(12.0)),
-        "rounded-2xl" => styles.border_radius = Some(px(16.0)),
-        "rounded-3xl" => styles.border_radius = Some(px(24.0)),
-        "rounded-full" => styles.border_radius = Some(px(9999.0)),
+        // Grid Span
+        "col-span-full" => styles.col_span = Some(GridSpan::Full),
+        "row-span-full" => styles.row_span = Some(GridSpan::Full),
+        
+        // Box Shadow
+        "shadow-sm" => styles.shadow = Some(vec![shadow(1.0, 2.0, 0.0, 0.05)]),
+        "shadow" => styles.shadow = Some(vec![shadow(1.0, 3.0, 0.0, 0.1), shadow(1.0, 2.0, -1.0, 0.1)]),
+        "shadow-md" => styles.shadow = Some(vec![shadow(4.0, 6.0, -1.0, 0.1), shadow(2.0, 4.0, -2.0, 0.1)]),
+        "shadow-lg" => styles.shadow = Some(vec![shadow(10.0, 15.0, -3.0, 0.1), shadow(4.0, 6.0, -4.0, 0.1)]),
+        "shadow-xl" => styles.shadow = Some(vec![shadow(20.0, 25.0, -5.0, 0.1), shadow(8.0, 10.0, -6.0, 0.1)]),
+        "shadow-2xl" => styles.shadow = Some(vec![shadow(25.0, 50.0, -12.0, 0.25)]),
+        "shadow-none" => styles.shadow = Some(Vec::new()),
+        
+        // Ring (width 3px like Tailwind's `ring`)
+        "ring" => styles.ring_width = Some(px(3.0)),
+        // GPUI draws no focus outline, so there is nothing to remove
+        "outline-none" => {}
+        
+        // Cursor
+        "cursor-auto" | "cursor-default" => styles.cursor = Some(CursorStyle::Arrow),
+        "cursor-pointer" => styles.cursor = Some(CursorStyle::PointingHand),
+        "cursor-text" => styles.cursor = Some(CursorStyle::IBeam),
+        "cursor-vertical-text" => styles.cursor = Some(CursorStyle::IBeamCursorForVerticalLayout),
+        "cursor-crosshair" => styles.cursor = Some(CursorStyle::Crosshair),
+        "cursor-grab" => styles.cursor = Some(CursorStyle::OpenHand),
+        "cursor-grabbing" => styles.cursor = Some(CursorStyle::ClosedHand),
+        "cursor-not-allowed" | "cursor-no-drop" => styles.cursor = Some(CursorStyle::OperationNotAllowed),
+        "cursor-alias" => styles.cursor = Some(CursorStyle::DragLink),
+        "cursor-copy" => styles.cursor = Some(CursorStyle::DragCopy),
+        "cursor-context-menu" => styles.cursor = Some(CursorStyle::ContextualMenu),
+        "cursor-col-resize" => styles.cursor = Some(CursorStyle::ResizeColumn),
+        "cursor-row-resize" => styles.cursor = Some(CursorStyle::ResizeRow),
+        "cursor-ew-resize" => styles.cursor = Some(CursorStyle::ResizeLeftRight),
+        "cursor-ns-resize" => styles.cursor = Some(CursorStyle::ResizeUpDown),
+        "cursor-nesw-resize" => styles.cursor = Some(CursorStyle::ResizeUpRightDownLeft),
+        "cursor-nwse-resize" => styles.cursor = Some(CursorStyle::ResizeUpLeftDownRight),
+        "cursor-n-resize" => styles.cursor = Some(CursorStyle::ResizeUp),
+        "cursor-s-resize" => styles.cursor = Some(CursorStyle::ResizeDown),
+        "cursor-e-resize" => styles.cursor = Some(CursorStyle::ResizeRight),
+        "cursor-w-resize" => styles.cursor = Some(CursorStyle::ResizeLeft),
+        "cursor-none" => styles.cursor = Some(CursorStyle::None),
+        
+        // Transitions
+        "transition" => styles.transition = Some(TransitionProperty::Default),
+        "transition-none" => styles.transition = Some(TransitionProperty::None),
+        "transition-colors" => styles.transition = Some(TransitionProperty::Colors),
+        "transition-opacity" => styles.transition = Some(TransitionProperty::Opacity),
+        "transition-all" => styles.transition = Some(TransitionProperty::All),
+        "transition-transform" => styles.transition = Some(TransitionProperty::Transform),
+        "ease-linear" => styles.transition_easing = Some(Easing::Linear),
+        "ease-in" => styles.transition_easing = Some(Easing::In),
+        "ease-out" => styles.transition_easing = Some(Easing::Out),
+        "ease-in-out" => styles.transition_easing = Some(Easing::InOut),
+        
+        // Animation
+        "animate-none" => styles.animate = Some(Animate::None),
+        "animate-spin" => styles.animate = Some(Animate::Spin),
+        "animate-ping" => styles.animate = Some(Animate::Ping),
+        "animate-pulse" => styles.animate = Some(Animate::Pulse),
+        "animate-bounce" => styles.animate = Some(Animate::Bounce),
         
         // Text Size
         "text-xs" => styles.font_size = Some(px(12.0)),
@@ -176,18 +510,347 @@ fn parse_class(class: &str, styles: &mut ParsedStyles) {
         "font-extrabold" => styles.font_weight = Some(FontWeight::EXTRA_BOLD),
         "font-black" => styles.font_weight = Some(FontWeight::BLACK),
         
+        // Line Height (multiples of the font size)
+        "leading-none" => styles.line_height = Some(relative(1.0)),
+        "leading-tight" => styles.line_height = Some(relative(1.25)),
+        "leading-snug" => styles.line_height = Some(relative(1.375)),
+        "leading-normal" => styles.line_height = Some(relative(1.5)),
+        "leading-relaxed" => styles.line_height = Some(relative(1.625)),
+        "leading-loose" => styles.line_height = Some(relative(2.0)),
+        
+        // Font Style & Decoration
+        "italic" => styles.font_style = Some(FontStyle::Italic),
+        "not-italic" => styles.font_style = Some(FontStyle::Normal),
+        "underline" => styles.text_decoration = Some(TextDecoration::Underline),
+        "line-through" => styles.text_decoration = Some(TextDecoration::LineThrough),
+        "no-underline" => styles.text_decoration = Some(TextDecoration::None),
+        
+        // Text Transform
+        "uppercase" => styles.text_transform = Some(TextTransform::Uppercase),
+        "lowercase" => styles.text_transform = Some(TextTransform::Lowercase),
+        "capitalize" => styles.text_transform = Some(TextTransform::Capitalize),
+        "normal-case" => styles.text_transform = Some(TextTransform::None),
+        
+        // Whitespace & Truncation
+        "whitespace-nowrap" => styles.white_space = Some(WhiteSpace::Nowrap),
+        "whitespace-normal" | "break-words" => styles.white_space = Some(WhiteSpace::Normal),
+        "text-ellipsis" => styles.text_ellipsis = true,
+        "truncate" => {
+            styles.overflow = Some(Overflow::Hidden);
+            styles.white_space = Some(WhiteSpace::Nowrap);
+            styles.text_ellipsis = true;
+        }
+        
+        // Overflow
+        "overflow-hidden" => styles.overflow = Some(Overflow::Hidden),
+        "overflow-visible" => styles.overflow = Some(Overflow::Visible),
+        
         _ => {
+            // Border widths and radii, optionally per side: border-t-2, rounded-bl-md
+            if parse_border_class(class, styles) || parse_rounded_class(class, styles) {
+                return true;
+            }
+            // Fractions and named sizes: w-1/2, h-screen, max-w-md
+            if parse_size_class(class, styles) {
+                return true;
+            }
+            // Blur scale: blur-sm, backdrop-blur-md
+            if parse_blur_class(class, styles) {
+                return true;
+            }
+            // Ring widths like ring-2 or ring-offset-4
+            if parse_ring_class(class, styles) {
+                return true;
+            }
+            // Palette colors like bg-blue-500 or text-slate-200
+            if parse_color_class(class, styles) {
+                return true;
+            }
             // Parse numbered classes like gap-4, p-2, m-4, size-8, etc.
-            parse_numbered_class(class, styles);
+            return parse_numbered_class(class, styles);
+        }
+    }
+    true
+}
+
+/// Parse sizing keywords and fractions: w-1/2, h-full, w-screen, max-w-md, ...
+///
+/// Plain spacing-scale sizes like w-4 are left to the numbered class parser.
+fn parse_size_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    const PREFIXES: [&str; 7] = ["min-w-", "min-h-", "max-w-", "max-h-", "size-", "w-", "h-"];
+    let Some((prefix, value)) = PREFIXES
+        .iter()
+        .find_map(|prefix| Some((*prefix, class.strip_prefix(prefix)?)))
+    else {
+        return false;
+    };
+    let horizontal = matches!(prefix, "w-" | "min-w-" | "max-w-");
+    
+    let size: SizeValue = match value {
+        "full" => Length::from(relative(1.0)).into(),
+        "auto" | "none" => Length::Auto.into(),
+        "screen" if prefix == "size-" => return false,
+        "screen" if horizontal => SizeValue::Vw(1.0),
+        "screen" => SizeValue::Vh(1.0),
+        _ => {
+            if let Some((numerator, denominator)) = value.split_once('/') {
+                let (Ok(numerator), Ok(denominator)) = (numerator.parse::<f32>(), denominator.parse::<f32>()) else {
+                    return false;
+                };
+                if denominator == 0.0 {
+                    return false;
+                }
+                Length::from(relative(numerator / denominator)).into()
+            } else if let Some(width) = (prefix == "max-w-").then(|| max_width(value)).flatten() {
+                Length::from(width).into()
+            } else {
+                return false;
+            }
+        }
+    };
+    
+    match prefix {
+        "w-" => styles.width = Some(size),
+        "h-" => styles.height = Some(size),
+        "min-w-" => styles.min_width = Some(size),
+        "min-h-" => styles.min_height = Some(size),
+        "max-w-" => styles.max_width = Some(size),
+        "max-h-" => styles.max_height = Some(size),
+        _ => {
+            styles.width = Some(size.clone());
+            styles.height = Some(size);
+        }
+    }
+    true
+}
+
+/// Named max-width sizes: max-w-md, max-w-7xl, max-w-screen-lg, ...
+fn max_width(name: &str) -> Option<Pixels> {
+    if let Some(screen) = name.strip_prefix("screen-") {
+        return breakpoint_width(screen);
+    }
+    let rem = match name {
+        "xs" => 20.0,
+        "sm" => 24.0,
+        "md" => 28.0,
+        "lg" => 32.0,
+        "xl" => 36.0,
+        "2xl" => 42.0,
+        "3xl" => 48.0,
+        "4xl" => 56.0,
+        "5xl" => 64.0,
+        "6xl" => 72.0,
+        "7xl" => 80.0,
+        _ => return None,
+    };
+    Some(px(rem * 16.0))
+}
+
+/// Parse border width classes: border, border-2, border-t, border-x-4, ...
+fn parse_border_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    let Some(rest) = class.strip_prefix("border") else {
+        return false;
+    };
+    let rest = match rest.strip_prefix('-') {
+        Some(rest) => rest,
+        None if rest.is_empty() => rest,
+        None => return false,
+    };
+    let (sides, width) = match rest.split_once('-') {
+        Some((sides, width)) => (sides, width),
+        None if rest.parse::<u32>().is_ok() => ("", rest),
+        None => (rest, ""),
+    };
+    let width = match width {
+        "" => px(1.0),
+        "0" | "2" | "4" | "8" => px(width.parse::<f32>().unwrap()),
+        _ => return false,
+    };
+    set_edges(&mut styles.border_width, sides, width)
+}
+
+/// Parse border radius classes: rounded, rounded-lg, rounded-t, rounded-bl-md, ...
+fn parse_rounded_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    let Some(rest) = class.strip_prefix("rounded") else {
+        return false;
+    };
+    let rest = rest.strip_prefix('-').unwrap_or(rest);
+    let (corners, size) = match rest.split_once('-') {
+        Some((corners, size)) => (corners, size),
+        None if radius(rest).is_some() => ("", rest),
+        None => (rest, ""),
+    };
+    match radius(size) {
+        Some(radius) => set_corners(&mut styles.border_radius, corners, radius),
+        None => false,
+    }
+}
+
+/// Radius for a rounded-* size name
+fn radius(size: &str) -> Option<Pixels> {
+    match size {
+        "none" => Some(px(0.0)),
+        "sm" => Some(px(2.0)),
+        "" => Some(px(4.0)),
+        "md" => Some(px(6.0)),
+        "lg" => Some(px(8.0)),
+        "xl" => Some(px(12.0)),
+        "2xl" => Some(px(16.0)),
+        "3xl" => Some(px(24.0)),
+        "full" => Some(px(9999.0)),
+        _ => None,
+    }
+}
+
+/// Set the edges named by a side suffix ("" for all, t, r, b, l, x, y)
+fn set_edges(edges: &mut Edges<Option<Pixels>>, sides: &str, value: Pixels) -> bool {
+    let (top, right, bottom, left) = match sides {
+        "" => (true, true, true, true),
+        "t" => (true, false, false, false),
+        "r" => (false, true, false, false),
+        "b" => (false, false, true, false),
+        "l" => (false, false, false, true),
+        "x" => (false, true, false, true),
+        "y" => (true, false, true, false),
+        _ => return false,
+    };
+    for (selected, edge) in [
+        (top, &mut edges.top),
+        (right, &mut edges.right),
+        (bottom, &mut edges.bottom),
+        (left, &mut edges.left),
+    ] {
+        if selected {
+            *edge = Some(value);
+        }
+    }
+    true
+}
+
+/// Set the corners named by a suffix ("" for all, t, r, b, l, tl, tr, br, bl)
+fn set_corners(corners: &mut Corners<Option<Pixels>>, which: &str, value: Pixels) -> bool {
+    let (top_left, top_right, bottom_right, bottom_left) = match which {
+        "" => (true, true, true, true),
+        "t" => (true, true, false, false),
+        "r" => (false, true, true, false),
+        "b" => (false, false, true, true),
+        "l" => (true, false, false, true),
+        "tl" => (true, false, false, false),
+        "tr" => (false, true, false, false),
+        "br" => (false, false, true, false),
+        "bl" => (false, false, false, true),
+        _ => return false,
+    };
+    for (selected, corner) in [
+        (top_left, &mut corners.top_left),
+        (top_right, &mut corners.top_right),
+        (bottom_right, &mut corners.bottom_right),
+        (bottom_left, &mut corners.bottom_left),
+    ] {
+        if selected {
+            *corner = Some(value);
+        }
+    }
+    true
+}
+
+/// Parse palette color classes like bg-red-500, text-white, border-slate-200,
+/// with an optional alpha modifier (bg-black/50)
+fn parse_color_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    let (target, name) = if let Some(name) = class.strip_prefix("ring-offset-") {
+        (&mut styles.ring_offset_color, name)
+    } else if let Some(name) = class.strip_prefix("ring-") {
+        (&mut styles.ring_color, name)
+    } else if let Some(name) = class.strip_prefix("bg-") {
+        (&mut styles.background, name)
+    } else if let Some(name) = class.strip_prefix("text-") {
+        (&mut styles.text_color, name)
+    } else if let Some(name) = class.strip_prefix("border-") {
+        (&mut styles.border_color, name)
+    } else {
+        return false;
+    };
+    
+    let (name, alpha) = match name.split_once('/') {
+        Some((name, alpha)) => match parse_alpha(alpha) {
+            Some(alpha) => (name, Some(alpha)),
+            None => return false,
+        },
+        None => (name, None),
+    };
+    
+    let color = theme_value(|t| t.colors.get(name).copied()).or_else(|| tw_colors::lookup(name));
+    match color {
+        Some(color) => {
+            *target = Some(with_alpha(color, alpha));
+            true
+        }
+        None => false,
+    }
+}
+
+/// Parse the blur scale: blur-none, blur-sm, blur, blur-md, ... blur-3xl,
+/// and the same sizes for backdrop-blur
+fn parse_blur_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    let (target, size) = if let Some(size) = class.strip_prefix("backdrop-blur") {
+        (&mut styles.backdrop_blur, size)
+    } else if let Some(size) = class.strip_prefix("blur") {
+        (&mut styles.blur, size)
+    } else {
+        return false;
+    };
+    let radius = match size {
+        "-none" => 0.0,
+        "-sm" => 4.0,
+        "" => 8.0,
+        "-md" => 12.0,
+        "-lg" => 16.0,
+        "-xl" => 24.0,
+        "-2xl" => 40.0,
+        "-3xl" => 64.0,
+        _ => return false,
+    };
+    *target = Some(px(radius));
+    true
+}
+
+/// Parse ring widths in pixels: ring-0, ring-2, ring-offset-4, ...
+fn parse_ring_class(class: &str, styles: &mut ParsedStyles) -> bool {
+    let (target, width) = if let Some(width) = class.strip_prefix("ring-offset-") {
+        (&mut styles.ring_offset_width, width)
+    } else if let Some(width) = class.strip_prefix("ring-") {
+        (&mut styles.ring_width, width)
+    } else {
+        return false;
+    };
+    match width.parse::<u16>() {
+        Ok(width) => {
+            *target = Some(px(width as f32));
+            true
         }
+        Err(_) => false,
+    }
+}
+
+/// Parse an alpha modifier like the `50` in bg-black/50 (percent, 0-100)
+fn parse_alpha(value: &str) -> Option<f32> {
+    let percent: f32 = value.parse().ok()?;
+    (0.0..=100.0).contains(&percent).then_some(percent / 100.0)
+}
+
+/// Apply an optional alpha modifier to a color
+fn with_alpha(color: Hsla, alpha: Option<f32>) -> Hsla {
+    match alpha {
+        Some(alpha) => color.opacity(alpha),
+        None => color,
     }
 }
 
 /// Parse classes with numbers like gap-4, p-2, size-8
-fn parse_numbered_class(class: &str, styles: &mut ParsedStyles) {
+fn parse_numbered_class(class: &str, styles: &mut ParsedStyles) -> bool {
     let parts: Vec<&str> = class.rsplitn(2, '-').collect();
     if parts.len() != 2 {
-        return;
+        return false;
     }
     
     let (num_str, prefix) = (parts[0], parts[1]);
@@ -195,12 +858,105 @@ fn parse_numbered_class(class: &str, styles: &mut ParsedStyles) {
     // Parse the number (Tailwind uses 4px per unit, e.g., gap-4 = 16px)
     let num: f32 = match num_str.parse() {
         Ok(n) => n,
-        Err(_) => return,
+        Err(_) => return false,
     };
     
-    let value = px(num * 4.0);
-    let length: Length = value.into();
+    // Grid track counts and spans are plain integers
+    let tracks = || (num >= 1.0 && num.fract() == 0.0).then_some(num as u16);
+    match prefix {
+        "grid-cols" => {
+            styles.grid_cols = tracks();
+            return styles.grid_cols.is_some();
+        }
+        "grid-rows" => {
+            styles.grid_rows = tracks();
+            return styles.grid_rows.is_some();
+        }
+        "col-span" => {
+            styles.col_span = tracks().map(GridSpan::Tracks);
+            return styles.col_span.is_some();
+        }
+        "row-span" => {
+            styles.row_span = tracks().map(GridSpan::Tracks);
+            return styles.row_span.is_some();
+        }
+        "order" | "-order" if num.fract() == 0.0 => {
+            let order = num as i32;
+            styles.order = Some(if prefix == "-order" { -order } else { order });
+            return true;
+        }
+        _ => {}
+    }
+    
+    // Opacity is a percentage, not a spacing unit
+    if prefix == "opacity" {
+        let valid = (0.0..=100.0).contains(&num);
+        if valid {
+            styles.opacity = Some(num / 100.0);
+        }
+        return valid;
+    }
+    
+    // So is brightness, which may go past 100 (brightness-125)
+    if prefix == "brightness" {
+        let valid = num >= 0.0;
+        if valid {
+            styles.brightness = Some(num / 100.0);
+        }
+        return valid;
+    }
+    
+    // Scales are percentages too: scale-95 draws at 95%
+    if prefix == "scale" {
+        let valid = num >= 0.0;
+        if valid {
+            styles.scale = Some(num / 100.0);
+        }
+        return valid;
+    }
+    
+    // Line clamping cuts text off with an ellipsis after N lines
+    if prefix == "line-clamp" {
+        let valid = num >= 1.0 && num.fract() == 0.0;
+        if valid {
+            styles.line_clamp = Some(num as usize);
+            styles.overflow = Some(Overflow::Hidden);
+            styles.text_ellipsis = true;
+        }
+        return valid;
+    }
+    
+    // Translations are on the spacing scale, negative with a leading dash
+    if let Some(axis) = prefix.trim_start_matches('-').strip_prefix("translate-") {
+        let offset = px(num * 4.0);
+        return set_translate(axis, if prefix.starts_with('-') { -offset } else { offset }, styles);
+    }
     
+    // Durations are in milliseconds
+    if prefix == "duration" {
+        let valid = num >= 0.0;
+        if valid {
+            styles.transition_duration = Some(Duration::from_millis(num as u64));
+        }
+        return valid;
+    }
+    
+    apply_spacing(prefix, px(num * 4.0).into(), styles)
+}
+
+/// Set `translate-x`/`translate-y` by axis; returns whether the axis is one
+fn set_translate(axis: &str, offset: Pixels, styles: &mut ParsedStyles) -> bool {
+    match axis {
+        "x" => styles.translate_x = Some(offset),
+        "y" => styles.translate_y = Some(offset),
+        _ => return false,
+    }
+    true
+}
+
+/// Set a spacing-scale property (gap, size, padding, margin, leading) by its
+/// prefix, without the trailing dash; returns whether the prefix is one
+fn apply_spacing(prefix: &str, length: Length, styles: &mut ParsedStyles) -> bool {
     match prefix {
         // Gap
         "gap" => styles.gap = Some(length),
@@ -209,15 +965,15 @@ fn parse_numbered_class(class: &str, styles: &mut ParsedStyles) {
         
         // Size
         "size" => {
-            styles.width = Some(length.clone());
-            styles.height = Some(length);
+            styles.width = Some(length.clone().into());
+            styles.height = Some(length.into());
         }
-        "w" => styles.width = Some(length),
-        "h" => styles.height = Some(length),
-        "min-w" => styles.min_width = Some(length),
-        "min-h" => styles.min_height = Some(length),
-        "max-w" => styles.max_width = Some(length),
-        "max-h" => styles.max_height = Some(length),
+        "w" => styles.width = Some(length.into()),
+        "h" => styles.height = Some(length.into()),
+        "min-w" => styles.min_width = Some(length.into()),
+        "min-h" => styles.min_height = Some(length.into()),
+        "max-w" => styles.max_width = Some(length.into()),
+        "max-h" => styles.max_height = Some(length.into()),
         
         // Padding
         "p" => styles.padding = Some(Edges::all(length)),
@@ -253,10 +1009,46 @@ fn parse_numbered_class(class: &str, styles: &mut ParsedStyles) {
         "ml" => styles.margin.get_or_insert(Edges::default()).left = length,
         "mr" => styles.margin.get_or_insert(Edges::default()).right = length,
         
-        _ => {}
+        // Line height
+        "leading" => {
+            if let Length::Definite(length) = length {
+                styles.line_height = Some(length);
+            }
+        }
+        
+        _ => return false,
+    }
+    true
+}
+
+/// Minimum window width for a responsive variant (the theme's screens, then
+/// Tailwind's defaults)
+fn breakpoint_width(variant: &str) -> Option<Pixels> {
+    if let Some(width) = theme_value(|t| t.screens.get(variant).copied()) {
+        return Some(width);
+    }
+    match variant {
+        "sm" => Some(px(640.0)),
+        "md" => Some(px(768.0)),
+        "lg" => Some(px(1024.0)),
+        "xl" => Some(px(1280.0)),
+        "2xl" => Some(px(1536.0)),
+        _ => None,
     }
 }
 
+/// Split a variant prefix like `hover:` off a class
+/// Colons inside arbitrary values (e.g. `bg-[url:x]`) are not variant separators
+fn split_variant(class: &str) -> Option<(&str, &str)> {
+    let colon = class.find(':')?;
+    if let Some(bracket) = class.find('[') {
+        if bracket < colon {
+            return None;
+        }
+    }
+    Some((&class[..colon], &class[colon + 1..]))
+}
+
 /// Parse arbitrary values like bg-[#505050] or size-[500px]
 fn parse_arbitrary(class: &str) -> Option<(&str, &str)> {
     if let Some(bracket_start) = class.find('[') {
@@ -269,68 +1061,185 @@ fn parse_arbitrary(class: &str) -> Option<(&str, &str)> {
     None
 }
 
-/// Apply arbitrary value to styles
-fn apply_arbitrary(prefix: &str, value: &str, styles: &mut ParsedStyles) {
+/// Apply arbitrary value to styles; `None` if the prefix or value isn't understood
+fn apply_arbitrary(prefix: &str, value: &str, alpha: Option<f32>, styles: &mut ParsedStyles) -> Option<()> {
+    let color = || parse_color(value).map(|c| with_alpha(c, alpha));
     match prefix {
-        "bg-" => {
-            if let Some(color) = parse_color(value) {
-                styles.background = Some(color);
-            }
-        }
+        "bg-" => styles.background = Some(color()?),
         "text-" => {
-            if let Some(color) = parse_color(value) {
+            if let Some(color) = color() {
                 styles.text_color = Some(color);
+            } else {
+                styles.font_size = Some(parse_font_size(value)?);
+            }
+        }
+        "leading-" => {
+            // Unitless and em values are multiples of the font size
+            let factor = value.strip_suffix("em").filter(|v| !v.ends_with('r')).unwrap_or(value);
+            if let Ok(factor) = factor.parse::<f32>() {
+                styles.line_height = Some(relative(factor));
+            } else if let Some(Length::Definite(length)) = parse_length(value) {
+                styles.line_height = Some(length);
+            } else {
+                return None;
             }
         }
         "border-" => {
-            if let Some(color) = parse_color(value) {
+            if let Some(color) = color() {
                 styles.border_color = Some(color);
+            } else {
+                styles.border_width = Edges::all(Some(parse_pixels(value)?));
             }
         }
         "size-" => {
-            if let Some(size) = parse_length(value) {
-                styles.width = Some(size.clone());
-                styles.height = Some(size);
-            }
+            let size = parse_size(value)?;
+            styles.width = Some(size.clone());
+            styles.height = Some(size);
         }
-        "w-" => {
-            if let Some(size) = parse_length(value) {
-                styles.width = Some(size);
-            }
+        "w-" | "h-" | "min-w-" | "min-h-" | "max-w-" | "max-h-" => {
+            let size = parse_size(value)?;
+            let field = match prefix {
+                "w-" => &mut styles.width,
+                "h-" => &mut styles.height,
+                "min-w-" => &mut styles.min_width,
+                "min-h-" => &mut styles.min_height,
+                "max-w-" => &mut styles.max_width,
+                _ => &mut styles.max_height,
+            };
+            *field = Some(size);
         }
-        "h-" => {
-            if let Some(size) = parse_length(value) {
-                styles.height = Some(size);
-            }
+        "gap-" | "gap-x-" | "gap-y-" | "p-" | "px-" | "py-" | "pt-" | "pb-" | "pl-" | "pr-" | "m-" | "mx-"
+        | "my-" | "mt-" | "mb-" | "ml-" | "mr-" => {
+            apply_spacing(prefix.trim_end_matches('-'), parse_length(value)?, styles);
         }
-        "gap-" => {
-            if let Some(size) = parse_length(value) {
-                styles.gap = Some(size);
+        "blur-" | "backdrop-blur-" => {
+            let radius = parse_pixels(value)?;
+            match prefix {
+                "blur-" => styles.blur = Some(radius),
+                _ => styles.backdrop_blur = Some(radius),
             }
         }
-        "p-" => {
-            if let Some(size) = parse_length(value) {
-                styles.padding = Some(Edges::all(size));
+        "brightness-" => styles.brightness = Some(value.parse::<f32>().ok().filter(|f| *f >= 0.0)?),
+        "scale-" => styles.scale = Some(value.parse::<f32>().ok().filter(|f| *f >= 0.0)?),
+        "ring-" | "ring-offset-" => {
+            let (color, width) = match prefix {
+                "ring-" => (&mut styles.ring_color, &mut styles.ring_width),
+                _ => (&mut styles.ring_offset_color, &mut styles.ring_offset_width),
+            };
+            if let Some(value) = parse_color(value) {
+                *color = Some(with_alpha(value, alpha));
+            } else {
+                *width = Some(parse_pixels(value)?);
             }
         }
-        "m-" => {
-            if let Some(size) = parse_length(value) {
-                styles.margin = Some(Edges::all(size));
+        "shadow-" => styles.shadow = Some(parse_shadows(value)?),
+        "translate-x-" | "translate-y-" => {
+            set_translate(&prefix["translate-".len()..prefix.len() - 1], parse_pixels(value)?, styles);
+        }
+        _ => {
+            // Per-side sizes like border-t-[3px] or rounded-tl-[10px]
+            let Some(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(size)))) = parse_length(value) else {
+                return None;
+            };
+            let applied = if let Some(sides) = prefix.strip_prefix("border") {
+                set_edges(&mut styles.border_width, sides.trim_matches('-'), size)
+            } else if let Some(corners) = prefix.strip_prefix("rounded") {
+                set_corners(&mut styles.border_radius, corners.trim_matches('-'), size)
+            } else {
+                false
+            };
+            return applied.then_some(());
+        }
+    }
+    Some(())
+}
+
+/// Parse an arbitrary shadow like `0_4px_6px_-1px_#0000001a`
+///
+/// Underscores stand for spaces and commas separate multiple shadows. Each
+/// shadow is `x y [blur [spread]] [color]`; the color defaults to black at 10%.
+fn parse_shadows(value: &str) -> Option<Vec<BoxShadow>> {
+    let mut shadows = Vec::new();
+    
+    for part in split_top_level(value, ',') {
+        let mut lengths = Vec::new();
+        let mut color = None;
+        for token in part.split('_').filter(|t| !t.is_empty()) {
+            if let Some(len) = parse_pixels(token) {
+                lengths.push(len);
+            } else {
+                color = Some(parse_color(token)?);
             }
         }
-        "rounded-" => {
-            if let Some(size) = parse_length(value) {
-                if let Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(p))) = size {
-                    styles.border_radius = Some(p);
-                }
+        
+        if lengths.len() < 2 || lengths.len() > 4 {
+            return None;
+        }
+        shadows.push(BoxShadow {
+            color: color.unwrap_or_else(|| hsla(0.0, 0.0, 0.0, 0.1)),
+            offset: point(lengths[0], lengths[1]),
+            blur_radius: lengths.get(2).copied().unwrap_or(px(0.0)),
+            spread_radius: lengths.get(3).copied().unwrap_or(px(0.0)),
+        });
+    }
+    
+    Some(shadows)
+}
+
+/// Split on a separator, ignoring separators nested inside parentheses
+fn split_top_level(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
             }
+            _ => {}
         }
-        _ => {}
     }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// Parse a font size like 15px or 1.25rem into pixels (1rem = 16px)
+fn parse_font_size(value: &str) -> Option<Pixels> {
+    match parse_length(value)? {
+        Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(size))) => Some(size),
+        Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Rems(size))) => Some(px(size.0 * 16.0)),
+        _ => None,
+    }
+}
+
+/// Parse a pixel value like 4px, -1px, or a bare 0
+fn parse_pixels(value: &str) -> Option<Pixels> {
+    if value == "0" {
+        return Some(px(0.0));
+    }
+    let num: f32 = value.strip_suffix("px")?.parse().ok()?;
+    Some(px(num))
 }
 
-/// Parse color value like #505050, #333, or rgb(...)
-fn parse_color(value: &str) -> Option<Hsla> {
+/// Shadow layer with a black color, used by the shadow-* presets
+fn shadow(offset_y: f32, blur: f32, spread: f32, alpha: f32) -> BoxShadow {
+    BoxShadow {
+        color: hsla(0.0, 0.0, 0.0, alpha),
+        offset: point(px(0.0), px(offset_y)),
+        blur_radius: px(blur),
+        spread_radius: px(spread),
+    }
+}
+
+/// Parse a CSS color: hex (`#rgb`, `#rrggbb`), `rgb()`/`rgba()`,
+/// `hsl()`/`hsla()` or a named color like `tomato`
+///
+/// Underscores stand for spaces, so both `rgb(40,40,40)` and
+/// `rgb(40_40_40_/_50%)` work.
+pub(crate) fn parse_color(value: &str) -> Option<Hsla> {
     if value.starts_with('#') {
         let hex = value.trim_start_matches('#');
         
@@ -350,16 +1259,77 @@ fn parse_color(value: &str) -> Option<Hsla> {
         } else {
             None
         }
+    } else if let Some((function, args)) = value.strip_suffix(')').and_then(|v| v.split_once('(')) {
+        parse_color_function(function, &args.replace('_', " "))
     } else {
-        None
+        tw_colors::css_named(value)
     }
 }
 
-/// Parse length value like 500px or 100%
+/// Parse the arguments of `rgb()`, `rgba()`, `hsl()` or `hsla()`, in either
+/// the legacy comma syntax or the space syntax with an optional `/ alpha`
+fn parse_color_function(function: &str, args: &str) -> Option<Hsla> {
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (args, None),
+    };
+    let parts: Vec<&str> = channels
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .collect();
+    let alpha = match (parts.len(), alpha) {
+        (3, alpha) => alpha,
+        (4, None) => Some(parts[3]),
+        _ => return None,
+    };
+    let alpha = match alpha {
+        Some(alpha) => match alpha.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+            None => alpha.parse::<f32>().ok()?,
+        },
+        None => 1.0,
+    }
+    .clamp(0.0, 1.0);
+    let percent = |value: &str| Some((value.strip_suffix('%')?.parse::<f32>().ok()? / 100.0).clamp(0.0, 1.0));
+    
+    match function {
+        "rgb" | "rgba" => {
+            // Channels are 0-255 or percentages
+            let channel = |value: &str| match value.strip_suffix('%') {
+                Some(_) => percent(value),
+                None => Some((value.parse::<f32>().ok()? / 255.0).clamp(0.0, 1.0)),
+            };
+            Some(Rgba { r: channel(parts[0])?, g: channel(parts[1])?, b: channel(parts[2])?, a: alpha }.into())
+        }
+        "hsl" | "hsla" => {
+            let hue: f32 = parts[0].trim_end_matches("deg").parse().ok()?;
+            Some(hsla(hue.rem_euclid(360.0) / 360.0, percent(parts[1])?, percent(parts[2])?, alpha))
+        }
+        _ => None,
+    }
+}
+
+/// Parse a size like 500px, 100%, or a viewport unit like 50vw / 100vh
+fn parse_size(value: &str) -> Option<SizeValue> {
+    if let Some(num) = value.strip_suffix("vw") {
+        return Some(SizeValue::Vw(num.parse::<f32>().ok()? / 100.0));
+    }
+    if let Some(num) = value.strip_suffix("vh") {
+        return Some(SizeValue::Vh(num.parse::<f32>().ok()? / 100.0));
+    }
+    parse_length(value).map(SizeValue::from)
+}
+
+/// Parse length value like 500px, 1.5rem or 100%
+///
+/// GPUI has no font-relative lengths, so `em` is treated like `rem`.
 fn parse_length(value: &str) -> Option<Length> {
     if value.ends_with("px") {
         let num: f32 = value.trim_end_matches("px").parse().ok()?;
         Some(px(num).into())
+    } else if let Some(num) = value.strip_suffix("rem").or_else(|| value.strip_suffix("em")) {
+        let num: f32 = num.parse().ok()?;
+        Some(rems(num).into())
     } else if value.ends_with('%') {
         let num: f32 = value.trim_end_matches('%').parse().ok()?;
         Some(relative(num / 100.0).into())
@@ -371,31 +1341,6 @@ fn parse_length(value: &str) -> Option<Length> {
     }
 }
 
-// Helper color functions
-fn white() -> Hsla {
-    rgb(0xffffff).into()
-}
-
-fn black() -> Hsla {
-    rgb(0x000000).into()
-}
-
-fn red() -> Hsla {
-    rgb(0xef4444).into()
-}
-
-fn green() -> Hsla {
-    rgb(0x22c55e).into()
-}
-
-fn blue() -> Hsla {
-    rgb(0x3b82f6).into()
-}
-
-fn yellow() -> Hsla {
-    rgb(0xeab308).into()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -409,12 +1354,345 @@ mod tests {
         assert!(matches!(styles.align_items, Some(AlignItems::Center)));
     }
 
+    #[test]
+    fn test_parse_self_and_order() {
+        let styles = parse("self-center order-2");
+        assert!(matches!(styles.align_self, Some(AlignSelf::Center)));
+        assert_eq!(styles.order, Some(2));
+        assert_eq!(parse("-order-1").order, Some(-1));
+        assert_eq!(parse("order-first").order, Some(-9999));
+        assert_eq!(parse("order-last").order, Some(9999));
+        assert_eq!(parse("order-1.5").order, None);
+    }
+
+    #[test]
+    fn test_parse_cursor() {
+        assert_eq!(parse("cursor-pointer").cursor, Some(CursorStyle::PointingHand));
+        assert_eq!(parse("cursor-text").cursor, Some(CursorStyle::IBeam));
+        assert_eq!(parse("cursor-grab active:cursor-grabbing").active.unwrap().cursor, Some(CursorStyle::ClosedHand));
+        assert_eq!(parse("cursor-not-allowed").cursor, Some(CursorStyle::OperationNotAllowed));
+        assert_eq!(parse("cursor-default").cursor, Some(CursorStyle::Arrow));
+        assert!(parse("cursor-wait").cursor.is_none());
+    }
+
+    #[test]
+    fn test_parse_arbitrary_spacing_and_typography() {
+        let pixels = |length: &Length| match length {
+            Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(p))) => Some(*p),
+            _ => None,
+        };
+        
+        let styles = parse("px-[10px] pt-[3px] mb-[1.5rem] gap-y-[6px]");
+        let padding = styles.padding.as_ref().unwrap();
+        assert_eq!(pixels(&padding.left), Some(px(10.0)));
+        assert_eq!(pixels(&padding.right), Some(px(10.0)));
+        assert_eq!(pixels(&padding.top), Some(px(3.0)));
+        assert!(matches!(styles.margin.as_ref().unwrap().bottom, Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Rems(r))) if r.0 == 1.5));
+        assert_eq!(styles.gap_y.as_ref().and_then(pixels), Some(px(6.0)));
+        
+        assert_eq!(parse("text-[15px]").font_size, Some(px(15.0)));
+        assert_eq!(parse("text-[1.5rem]").font_size, Some(px(24.0)));
+        assert!(parse("text-[#ff0000]").font_size.is_none());
+        assert!(matches!(parse("leading-[1.3]").line_height, Some(DefiniteLength::Fraction(f)) if f == 1.3));
+        assert!(matches!(parse("leading-[20px]").line_height, Some(DefiniteLength::Absolute(AbsoluteLength::Pixels(p))) if p == px(20.0)));
+        assert!(matches!(parse("leading-tight").line_height, Some(DefiniteLength::Fraction(f)) if f == 1.25));
+        
+        assert!(matches!(parse("min-w-[200px]").min_width, Some(SizeValue::Length(_))));
+        assert!(matches!(parse("max-h-[50%]").max_height, Some(SizeValue::Length(Length::Definite(DefiniteLength::Fraction(f)))) if f == 0.5));
+    }
+
+    #[test]
+    fn test_unknown_classes() {
+        let styles = parse("flex p-4 bg-blue-500 colour-red hover:bg-nope md:flex-col p-[oops] w-1/2 shadow-[4px] foo:flex");
+        assert_eq!(styles.unknown_classes, vec!["colour-red", "hover:bg-nope", "p-[oops]", "shadow-[4px]", "foo:flex"]);
+        assert!(parse("grid-cols-0 opacity-150 line-clamp-2").unknown_classes == vec!["grid-cols-0", "opacity-150"]);
+        assert!(parse("ring-2 blur-md text-[15px] rounded-tl-[4px] animate-spin").unknown_classes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_cache() {
+        let mut cache = ParseCache::new(4);
+        for class in ["p-1", "p-2", "p-3", "p-4"] {
+            cache.insert(class, Arc::new(parse(class)));
+        }
+        let cached = cache.get("p-1").unwrap();
+        assert!(Arc::ptr_eq(&cached, &cache.get("p-1").unwrap()));
+        
+        // Overflowing evicts the least recently used half
+        cache.insert("p-5", Arc::new(parse("p-5")));
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.get("p-1").is_some());
+        assert!(cache.get("p-5").is_some());
+        assert!(cache.get("p-2").is_none());
+    }
+
+    #[test]
+    fn test_theme() {
+        let entries = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let config = ThemeConfig {
+            colors: entries(&[("brand-600", "#4f46e5")]),
+            spacing: entries(&[("gutter", "24px"), ("18", "4.5rem")]),
+            font_sizes: entries(&[("hero", "3rem")]),
+            screens: entries(&[("3xl", "1920px")]),
+        };
+        TEST_THEME.with(|theme| *theme.borrow_mut() = Some(Theme::from_config(&config).unwrap()));
+        
+        let styles = parse("bg-brand-600/50 ring-brand-600 p-gutter gap-18 text-hero 3xl:flex");
+        assert_eq!(styles.background, Some(Hsla::from(rgb(0x4f46e5)).opacity(0.5)));
+        assert_eq!(styles.ring_color, Some(rgb(0x4f46e5).into()));
+        assert!(matches!(
+            styles.padding.map(|p| p.top),
+            Some(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(p)))) if p == px(24.0)
+        ));
+        assert!(matches!(
+            styles.gap,
+            Some(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Rems(r)))) if r == rems(4.5)
+        ));
+        assert_eq!(styles.font_size, Some(px(48.0)));
+        assert_eq!(styles.breakpoints[0].0, px(1920.0));
+        // Names the theme doesn't define keep their built-in meaning
+        assert_eq!(parse("bg-blue-500 p-4").background, Some(rgb(0x3b82f6).into()));
+        
+        let invalid = ThemeConfig { colors: entries(&[("brand", "nope")]), ..Default::default() };
+        assert!(Theme::from_config(&invalid).unwrap_err().contains("theme.colors"));
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let styles = parse("blur-sm backdrop-blur-md brightness-125");
+        assert_eq!(styles.blur, Some(px(4.0)));
+        assert_eq!(styles.backdrop_blur, Some(px(12.0)));
+        assert_eq!(styles.brightness, Some(1.25));
+        
+        assert_eq!(parse("blur").blur, Some(px(8.0)));
+        assert_eq!(parse("backdrop-blur-none").backdrop_blur, Some(px(0.0)));
+        assert_eq!(parse("backdrop-blur-[6px]").backdrop_blur, Some(px(6.0)));
+        assert_eq!(parse("brightness-[.8]").brightness, Some(0.8));
+        assert_eq!(parse("brightness-50").brightness, Some(0.5));
+        assert!(parse("blur-huge").blur.is_none());
+    }
+
+    #[test]
+    fn test_parse_ring() {
+        let styles = parse("ring-2 ring-blue-500 ring-offset-2 ring-offset-slate-900 outline-none");
+        assert_eq!(styles.ring_width, Some(px(2.0)));
+        assert_eq!(styles.ring_color, Some(rgb(0x3b82f6).into()));
+        assert_eq!(styles.ring_offset_width, Some(px(2.0)));
+        assert_eq!(styles.ring_offset_color, Some(rgb(0x0f172a).into()));
+        
+        assert_eq!(parse("ring").ring_width, Some(px(3.0)));
+        assert_eq!(parse("ring-[5px]").ring_width, Some(px(5.0)));
+        assert_eq!(parse("ring-black/50").ring_color.map(|c| c.a), Some(0.5));
+        assert!(parse("focus:ring-4").focus.unwrap().ring_width.is_some());
+        assert!(parse("ring-huge").ring_width.is_none());
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let styles = parse("grid grid-cols-3 grid-rows-2 gap-4 col-span-2 row-span-full");
+        assert!(matches!(styles.display, Some(Display::Grid)));
+        assert_eq!(styles.grid_cols, Some(3));
+        assert_eq!(styles.grid_rows, Some(2));
+        assert_eq!(styles.col_span, Some(GridSpan::Tracks(2)));
+        assert_eq!(styles.row_span, Some(GridSpan::Full));
+        assert!(styles.gap.is_some());
+    }
+
     #[test]
     fn test_parse_arbitrary_color() {
         let styles = parse("bg-[#505050]");
         assert!(styles.background.is_some());
     }
 
+    #[test]
+    fn test_parse_css_colors() {
+        let close = |color: Option<Hsla>, expected: Hsla| {
+            let (color, expected) = (Rgba::from(color.unwrap()), Rgba::from(expected));
+            (color.r - expected.r).abs() < 0.01
+                && (color.g - expected.g).abs() < 0.01
+                && (color.b - expected.b).abs() < 0.01
+                && (color.a - expected.a).abs() < 0.01
+        };
+        
+        assert!(close(parse("bg-[rgb(40,40,40)]").background, rgb(0x282828).into()));
+        assert!(close(parse("bg-[rgb(40_40_40)]").background, rgb(0x282828).into()));
+        assert!(close(parse("bg-[rgba(0,0,0,0.5)]").background, hsla(0.0, 0.0, 0.0, 0.5)));
+        assert!(close(parse("bg-[rgb(0_0_0_/_25%)]").background, hsla(0.0, 0.0, 0.0, 0.25)));
+        assert!(close(parse("bg-[hsl(210,50%,40%)]").background, hsla(210.0 / 360.0, 0.5, 0.4, 1.0)));
+        assert!(close(parse("text-[hsla(120deg,100%,50%,0.8)]").text_color, hsla(1.0 / 3.0, 1.0, 0.5, 0.8)));
+        assert!(close(parse("border-[tomato]").border_color, rgb(0xff6347).into()));
+        assert!(close(parse("bg-[RebeccaPurple]/50").background, hsla(0.75, 0.5, 0.4, 0.5)));
+        
+        assert!(parse("bg-[rgb(1,2)]").background.is_none());
+        assert!(parse("bg-[hsl(10,20,30)]").background.is_none());
+        assert!(parse("bg-[notacolor]").background.is_none());
+    }
+
+    #[test]
+    fn test_parse_palette_colors() {
+        let styles = parse("bg-slate-900 text-emerald-400 border-rose-950");
+        assert_eq!(styles.background, Some(rgb(0x0f172a).into()));
+        assert_eq!(styles.text_color, Some(rgb(0x34d399).into()));
+        assert_eq!(styles.border_color, Some(rgb(0x4c0519).into()));
+        assert!(parse("bg-blue-550").background.is_none());
+    }
+
+    #[test]
+    fn test_parse_opacity_and_alpha() {
+        let styles = parse("opacity-50 bg-black/50 text-white/80 border-[#ff0000]/25");
+        assert_eq!(styles.opacity, Some(0.5));
+        assert_eq!(styles.background.map(|c| c.a), Some(0.5));
+        assert_eq!(styles.text_color.map(|c| c.a), Some(0.8));
+        assert_eq!(styles.border_color.map(|c| c.a), Some(0.25));
+        assert!(parse("bg-black/abc").background.is_none());
+    }
+
+    #[test]
+    fn test_parse_shadows() {
+        assert_eq!(parse("shadow-md").shadow.map(|s| s.len()), Some(2));
+        assert_eq!(parse("shadow-none").shadow.map(|s| s.len()), Some(0));
+        
+        let shadows = parse("shadow-[0_4px_6px_-1px_#000000,0_2px_4px]").shadow.unwrap();
+        assert_eq!(shadows.len(), 2);
+        assert_eq!(shadows[0].offset.y, px(4.0));
+        assert_eq!(shadows[0].spread_radius, px(-1.0));
+        assert_eq!(shadows[1].blur_radius, px(4.0));
+        assert!(parse("shadow-[4px]").shadow.is_none());
+    }
+
+    #[test]
+    fn test_parse_state_variants() {
+        let styles = parse("bg-white hover:bg-[#2563eb] active:bg-black focus:border-white");
+        assert!(styles.background.is_some());
+        assert!(styles.hover.as_ref().and_then(|h| h.background).is_some());
+        assert!(styles.active.as_ref().and_then(|a| a.background).is_some());
+        assert!(styles.focus.as_ref().and_then(|f| f.border_color).is_some());
+        assert!(styles.hover.as_ref().unwrap().hover.is_none());
+    }
+
+    #[test]
+    fn test_parse_breakpoints() {
+        let styles = parse("flex-col lg:gap-8 md:flex-row md:gap-4 sm:hover:bg-white");
+        let widths: Vec<Pixels> = styles.breakpoints.iter().map(|(w, _)| *w).collect();
+        assert_eq!(widths, vec![px(640.0), px(768.0), px(1024.0)]);
+        assert!(matches!(styles.breakpoints[1].1.flex_direction, Some(FlexDirection::Row)));
+        assert!(styles.breakpoints[1].1.gap.is_some());
+        assert!(styles.breakpoints[0].1.hover.is_some());
+    }
+
+    #[test]
+    fn test_parse_contrast_variant() {
+        let styles = parse("text-slate-500 contrast-more:text-black dark:contrast-more:text-white");
+        assert_eq!(styles.contrast_more.as_ref().unwrap().text_color, Some(rgb(0x000000).into()));
+        let dark = styles.dark.as_ref().unwrap();
+        assert_eq!(dark.contrast_more.as_ref().unwrap().text_color, Some(rgb(0xffffff).into()));
+    }
+
+    #[test]
+    fn test_parse_dark_variant() {
+        let styles = parse("bg-white dark:bg-slate-900 dark:md:text-white");
+        let dark = styles.dark.as_ref().unwrap();
+        assert_eq!(dark.background, Some(rgb(0x0f172a).into()));
+        assert!(dark.breakpoints[0].1.text_color.is_some());
+        assert!(styles.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn test_parse_transitions() {
+        let styles = parse("transition-colors duration-300 ease-out animate-pulse");
+        assert_eq!(styles.transition, Some(TransitionProperty::Colors));
+        assert_eq!(styles.transition_duration, Some(Duration::from_millis(300)));
+        assert_eq!(styles.transition_easing, Some(Easing::Out));
+        assert_eq!(styles.animate, Some(Animate::Pulse));
+        assert_eq!(parse("transition").transition, Some(TransitionProperty::Default));
+        
+        let moved = parse("transition-transform translate-x-2 -translate-y-1 hover:translate-x-[6px]");
+        assert_eq!(moved.transition, Some(TransitionProperty::Transform));
+        assert_eq!((moved.translate_x, moved.translate_y), (Some(px(8.0)), Some(px(-4.0))));
+        assert_eq!(moved.hover.unwrap().translate_x, Some(px(6.0)));
+        assert!(moved.unknown_classes.is_empty());
+        let pressed = parse("scale-105 active:scale-95 focus:scale-[.98]");
+        assert_eq!(pressed.scale, Some(1.05));
+        assert_eq!((pressed.active.unwrap().scale, pressed.focus.unwrap().scale), (Some(0.95), Some(0.98)));
+        assert!(parse("duration-300").padding.is_none());
+    }
+
+    #[test]
+    fn test_parse_truncation() {
+        let styles = parse("truncate");
+        assert_eq!(styles.white_space, Some(WhiteSpace::Nowrap));
+        assert!(matches!(styles.overflow, Some(Overflow::Hidden)));
+        assert!(styles.text_ellipsis);
+        
+        let styles = parse("line-clamp-2 break-words");
+        assert_eq!(styles.line_clamp, Some(2));
+        assert_eq!(styles.white_space, Some(WhiteSpace::Normal));
+        assert!(parse("line-clamp-0").line_clamp.is_none());
+    }
+
+    #[test]
+    fn test_parse_text_style() {
+        let styles = parse("italic underline uppercase");
+        assert!(matches!(styles.font_style, Some(FontStyle::Italic)));
+        assert_eq!(styles.text_decoration, Some(TextDecoration::Underline));
+        assert_eq!(styles.text_transform, Some(TextTransform::Uppercase));
+        assert_eq!(parse("underline line-through").text_decoration, Some(TextDecoration::LineThrough));
+        assert_eq!(parse("no-underline").text_decoration, Some(TextDecoration::None));
+        
+        assert_eq!(TextTransform::Uppercase.apply("straße"), "STRASSE");
+        assert_eq!(TextTransform::Lowercase.apply("Hello"), "hello");
+        assert_eq!(TextTransform::Capitalize.apply("hello big  world-wide"), "Hello Big  World-wide");
+    }
+
+    #[test]
+    fn test_parse_border_sides() {
+        let styles = parse("border border-t-4 border-x-0");
+        assert_eq!(styles.border_width.top, Some(px(4.0)));
+        assert_eq!(styles.border_width.bottom, Some(px(1.0)));
+        assert_eq!(styles.border_width.left, Some(px(0.0)));
+        
+        let styles = parse("border-b-2 border-l border-slate-200");
+        assert_eq!(styles.border_width.top, None);
+        assert_eq!(styles.border_width.bottom, Some(px(2.0)));
+        assert_eq!(styles.border_width.left, Some(px(1.0)));
+        assert!(styles.border_color.is_some());
+        
+        assert_eq!(parse("border-r-[3px]").border_width.right, Some(px(3.0)));
+        assert_eq!(parse("border-[3px]").border_width.left, Some(px(3.0)));
+        assert!(parse("borderx").border_width.top.is_none());
+        assert!(parse("border-[#ff0000]").border_width.top.is_none());
+    }
+
+    #[test]
+    fn test_parse_rounded_corners() {
+        let styles = parse("rounded-lg rounded-t-none rounded-bl-md");
+        assert_eq!(styles.border_radius.top_left, Some(px(0.0)));
+        assert_eq!(styles.border_radius.top_right, Some(px(0.0)));
+        assert_eq!(styles.border_radius.bottom_right, Some(px(8.0)));
+        assert_eq!(styles.border_radius.bottom_left, Some(px(6.0)));
+        
+        assert_eq!(parse("rounded").border_radius.top_left, Some(px(4.0)));
+        assert_eq!(parse("rounded-tr").border_radius.top_right, Some(px(4.0)));
+        assert_eq!(parse("rounded-[10px]").border_radius.bottom_left, Some(px(10.0)));
+        assert_eq!(parse("rounded-br-[3px]").border_radius.bottom_right, Some(px(3.0)));
+        assert!(parse("rounded-huge").border_radius.top_left.is_none());
+    }
+
+    #[test]
+    fn test_parse_fractions_and_named_sizes() {
+        let styles = parse("w-1/2 h-3/4 min-h-screen max-w-md");
+        assert!(matches!(styles.width, Some(SizeValue::Length(Length::Definite(DefiniteLength::Fraction(f)))) if f == 0.5));
+        assert!(matches!(styles.height, Some(SizeValue::Length(Length::Definite(DefiniteLength::Fraction(f)))) if f == 0.75));
+        assert!(matches!(styles.min_height, Some(SizeValue::Vh(f)) if f == 1.0));
+        assert!(matches!(styles.max_width, Some(SizeValue::Length(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(p))))) if p == px(448.0)));
+        
+        assert!(matches!(parse("w-screen").width, Some(SizeValue::Vw(_))));
+        assert!(matches!(parse("max-w-screen-lg").max_width, Some(SizeValue::Length(Length::Definite(DefiniteLength::Absolute(AbsoluteLength::Pixels(p))))) if p == px(1024.0)));
+        assert!(matches!(parse("w-[50vw]").width, Some(SizeValue::Vw(f)) if f == 0.5));
+        assert!(parse("w-1/0").width.is_none());
+        assert!(parse("max-h-md").max_height.is_none());
+    }
+
     #[test]
     fn test_parse_arbitrary_size() {
         let styles = parse("size-[500px]");
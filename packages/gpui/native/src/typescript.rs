@@ -0,0 +1,134 @@
+//! TypeScript and JSX support: compilation to plain JS with oxc
+//!
+//! TypeScript and JSX entry scripts and modules bundled from the config are
+//! parsed and transformed before anything else sees them, so type
+//! annotations, interfaces, `import type`, enums and parameter properties
+//! work as `tsc` would compile them. Types are not checked; run `tsc
+//! --noEmit` for that. Imports and exports are kept as they are, for the
+//! import transforms that come after.
+//!
+//! JSX uses the classic transform: `<div class="p-4">{x}</div>` becomes
+//! `h("div", { class: "p-4" }, x)`, with the factory and fragment names
+//! from the config's `jsx` section. `@rasenjs/gpui` exports `h` and
+//! `Fragment`, which map lowercase tags to its components.
+
+use oxc::allocator::Allocator;
+use oxc::codegen::Codegen;
+use oxc::diagnostics::OxcDiagnostic;
+use oxc::parser::Parser;
+use oxc::semantic::SemanticBuilder;
+use oxc::span::SourceType;
+use oxc::transformer::{JsxOptions, JsxRuntime, TransformOptions, Transformer};
+use std::path::Path;
+use crate::config::JsxConfig;
+use crate::module_loader::{self, BundleError};
+
+/// Whether `path` is a TypeScript or JSX file, which must be compiled
+pub fn needs_compile(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("ts" | "mts" | "cts" | "tsx" | "jsx"))
+}
+
+fn to_error(source: &str, path: &Path, diagnostic: &OxcDiagnostic) -> BundleError {
+    // The primary label points at the problem, others at related code
+    let label = diagnostic
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.iter().find(|label| label.primary()).or_else(|| labels.first()));
+    let offset = label.map(|label| label.offset());
+    BundleError {
+        path: path.to_path_buf(),
+        span: offset.map(|offset| module_loader::line_col(source, offset)),
+        message: diagnostic.message.to_string(),
+    }
+}
+
+/// Check the plain JS `source` of file `path` for syntax errors, which
+/// would otherwise only show when the bundle runs
+///
+/// `.js` files may be CommonJS or ESM, so they're parsed as either. Files
+/// other than `.js`, `.mjs` and `.cjs` pass.
+pub fn check(source: &str, path: &Path) -> Result<(), Vec<BundleError>> {
+    let source_type = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("js") => SourceType::unambiguous(),
+        Some("mjs") => SourceType::mjs(),
+        Some("cjs") => SourceType::cjs(),
+        _ => return Ok(()),
+    };
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if parsed.panicked || !parsed.errors.is_empty() {
+        return Err(parsed.errors.iter().map(|error| to_error(source, path, error)).collect());
+    }
+    Ok(())
+}
+
+/// Compile the TypeScript or JSX `source` of file `path` to JS
+///
+/// Fails with every syntax error found.
+pub fn compile(source: &str, path: &Path, jsx: &JsxConfig) -> Result<String, Vec<BundleError>> {
+    let allocator = Allocator::default();
+    let source_type = SourceType::from_path(path).unwrap_or_else(|_| SourceType::ts());
+    let parsed = Parser::new(&allocator, source, source_type).parse();
+    if parsed.panicked || !parsed.errors.is_empty() {
+        return Err(parsed.errors.iter().map(|error| to_error(source, path, error)).collect());
+    }
+    let mut program = parsed.program;
+
+    let scoping = SemanticBuilder::new().build(&program).semantic.into_scoping();
+    let options = TransformOptions {
+        jsx: JsxOptions {
+            runtime: JsxRuntime::Classic,
+            pragma: Some(jsx.factory.clone()),
+            pragma_frag: Some(jsx.fragment.clone()),
+            ..JsxOptions::default()
+        },
+        ..TransformOptions::default()
+    };
+    let transformed = Transformer::new(&allocator, path, &options).build_with_scoping(scoping, &mut program);
+    if !transformed.errors.is_empty() {
+        return Err(transformed.errors.iter().map(|error| to_error(source, path, error)).collect());
+    }
+    Ok(Codegen::new().build(&program).code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_types() {
+        let source = r#"
+            import { ref, type Ref } from '@rasenjs/gpui'
+            interface Props { count: Ref<number> }
+            enum Mode { Light, Dark }
+            const count: Ref<number> = ref(0)
+            function label(props: Props, mode = Mode.Dark): string {
+                return `${props.count.value as number} ${mode}`
+            }
+        "#;
+        let code = compile(source, Path::new("main.ts"), &JsxConfig::default()).unwrap();
+        assert!(code.contains("import { ref } from \"@rasenjs/gpui\""));
+        assert!(code.contains("const count = ref(0)"));
+        assert!(code.contains("function label(props, mode = Mode.Dark)"));
+        assert!(!code.contains("interface"));
+        assert!(!code.contains(": string"));
+    }
+
+    #[test]
+    fn test_syntax_errors_have_locations() {
+        let errors = compile("const a: number = 1\nconst b = (;\n", Path::new("bad.ts"), &JsxConfig::default()).unwrap_err();
+        assert_eq!(errors[0].span.map(|(line, _)| line), Some(2));
+        assert!(errors[0].to_string().starts_with("bad.ts:2:"));
+    }
+
+    #[test]
+    fn test_jsx_factory() {
+        let source = "const view = <div class=\"p-4\"><text>{count}</text><>x</></div>";
+        let code = compile(source, Path::new("main.tsx"), &JsxConfig::default()).unwrap();
+        assert!(code.contains("h(\"div\", { class: \"p-4\" }, h(\"text\", null, count), h(Fragment, null, \"x\"))"));
+
+        let jsx = JsxConfig { factory: "jsx".to_string(), fragment: "Group".to_string() };
+        let code = compile("<><Panel /></>", Path::new("view.jsx"), &jsx).unwrap();
+        assert!(code.contains("jsx(Group, null, jsx(Panel, null))"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Undo/redo stack
+//!
+//! Apps register undoable actions with `rasen.undoStack.push({ undo, redo })`;
+//! Cmd+Z / Shift+Cmd+Z (Ctrl+Z / Ctrl+Shift+Z / Ctrl+Y elsewhere) and
+//! `rasen.undoStack.undo()` / `redo()` walk the stack. Pushing a new action
+//! discards anything that was undone.
+//!
+//! Like `activity`, the callbacks stay on the JS side in a table keyed by
+//! action ID and the native side only keeps the order, in a [`History`].
+//! Native editors keep their own: the color picker's hex field undoes its
+//! draft while it's being edited (see `colorpicker`).
+
+use gpui::{App, KeyBinding};
+use rquickjs::{Ctx, Function, Object};
+use std::sync::Mutex;
+use crate::menus::{Redo, Undo};
+
+/// Global holding the actions by ID
+const ACTIONS: &str = "__undoActions";
+/// Oldest actions are dropped beyond this depth
+pub const MAX_DEPTH: usize = 100;
+
+static STATE: Mutex<UndoState> = Mutex::new(UndoState {
+    next_id: 1,
+    history: History::new(MAX_DEPTH),
+});
+
+struct UndoState {
+    next_id: u64,
+    history: History<u64>,
+}
+
+/// Edits that can be undone and redone, newest last
+///
+/// Pushing an edit discards what was undone, and the oldest edits beyond
+/// `depth`.
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    depth: usize,
+}
+
+impl<T> History<T> {
+    pub const fn new(depth: usize) -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), depth }
+    }
+
+    /// Record `edit`; returns the edits it discarded
+    pub fn push(&mut self, edit: T) -> Vec<T> {
+        let mut dropped = std::mem::take(&mut self.redo);
+        self.undo.push(edit);
+        if self.undo.len() > self.depth {
+            dropped.push(self.undo.remove(0));
+        }
+        dropped
+    }
+
+    /// Move the newest edit to the redo side; returns it
+    pub fn undo(&mut self) -> Option<&T> {
+        let edit = self.undo.pop()?;
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    /// Move the most recently undone edit back; returns it
+    pub fn redo(&mut self) -> Option<&T> {
+        let edit = self.redo.pop()?;
+        self.undo.push(edit);
+        self.undo.last()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forget every edit; returns them
+    pub fn clear(&mut self) -> Vec<T> {
+        let mut edits = std::mem::take(&mut self.undo);
+        edits.append(&mut self.redo);
+        edits
+    }
+}
+
+/// Bind the undo shortcuts on platforms without an Edit menu
+///
+/// On macOS the Edit menu provides them (see `menus`).
+pub fn init(cx: &mut App) {
+    if cfg!(target_os = "macos") {
+        return;
+    }
+    cx.bind_keys([
+        KeyBinding::new("ctrl-z", Undo, None),
+        KeyBinding::new("ctrl-shift-z", Redo, None),
+        KeyBinding::new("ctrl-y", Redo, None),
+    ]);
+}
+
+/// Undo the most recent action; returns whether there was one
+pub fn undo(ctx: &Ctx) -> bool {
+    step(ctx, "undo", |history| history.undo().copied())
+}
+
+/// Redo the most recently undone action; returns whether there was one
+pub fn redo(ctx: &Ctx) -> bool {
+    step(ctx, "redo", |history| history.redo().copied())
+}
+
+/// Move the top action to the other side and call its callback
+fn step(ctx: &Ctx, method: &str, take: impl Fn(&mut History<u64>) -> Option<u64>) -> bool {
+    // Don't hold the lock while calling into JS, which may push again
+    let Some(id) = take(&mut STATE.lock().unwrap().history) else {
+        return false;
+    };
+    
+    let callback = ctx
+        .globals()
+        .get::<_, Object>(ACTIONS)
+        .and_then(|actions| actions.get::<_, Object>(id.to_string()))
+        .and_then(|action| action.get::<_, Function>(method));
+    match callback {
+        Ok(callback) => {
+            if let Err(e) = callback.call::<_, ()>(()) {
+                eprintln!("Error in {} action {}: {:?}", method, id, e);
+            }
+        }
+        Err(e) => eprintln!("Missing {} callback for action {}: {:?}", method, id, e),
+    }
+    true
+}
+
+/// Forget actions, removing their callbacks
+fn discard(ctx: &Ctx, ids: Vec<u64>) -> rquickjs::Result<()> {
+    let actions: Object = ctx.globals().get(ACTIONS)?;
+    for id in ids {
+        actions.remove(id.to_string())?;
+    }
+    Ok(())
+}
+
+/// Install `rasen.undoStack`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    ctx.globals().set(ACTIONS, Object::new(ctx.clone())?)?;
+    
+    let stack = Object::new(ctx.clone())?;
+    stack.set(
+        "push",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, action: Object<'js>| -> rquickjs::Result<()> {
+            // Validate up front so a bad action fails at push, not at undo
+            action.get::<_, Function>("undo")?;
+            action.get::<_, Function>("redo")?;
+            
+            let (id, dropped) = {
+                let mut state = STATE.lock().unwrap();
+                let id = state.next_id;
+                state.next_id += 1;
+                (id, state.history.push(id))
+            };
+            discard(&ctx, dropped)?;
+            let actions: Object = ctx.globals().get(ACTIONS)?;
+            actions.set(id.to_string(), action)
+        })?,
+    )?;
+    stack.set("undo", Function::new(ctx.clone(), |ctx: Ctx<'js>| undo(&ctx))?)?;
+    stack.set("redo", Function::new(ctx.clone(), |ctx: Ctx<'js>| redo(&ctx))?)?;
+    stack.set("canUndo", Function::new(ctx.clone(), || STATE.lock().unwrap().history.can_undo())?)?;
+    stack.set("canRedo", Function::new(ctx.clone(), || STATE.lock().unwrap().history.can_redo())?)?;
+    stack.set(
+        "clear",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>| -> rquickjs::Result<()> {
+            let ids = STATE.lock().unwrap().history.clear();
+            discard(&ctx, ids)
+        })?,
+    )?;
+    rasen.set("undoStack", stack)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut history = History::new(MAX_DEPTH);
+        assert_eq!(history.undo(), None);
+        history.push(1);
+        history.push(2);
+        assert_eq!(history.undo(), Some(&2));
+        assert_eq!(history.undo(), Some(&1));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&1));
+        assert!(history.can_undo() && history.can_redo());
+        assert_eq!(history.redo(), Some(&2));
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn test_push_discards_undone_branch() {
+        let mut history = History::new(MAX_DEPTH);
+        history.push(1);
+        history.push(2);
+        history.push(3);
+        history.undo();
+        history.undo();
+        assert_eq!(history.push(4), vec![3, 2]);
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(), Some(&4));
+        assert_eq!(history.undo(), Some(&1));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut history = History::new(2);
+        assert!(history.push(1).is_empty());
+        history.push(2);
+        assert_eq!(history.push(3), vec![1]);
+        history.undo();
+        assert_eq!(history.clear(), vec![2, 3]);
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+}
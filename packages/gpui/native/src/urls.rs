@@ -0,0 +1,302 @@
+//! `URL` and `URLSearchParams` globals, backed by the `url` crate
+//!
+//! Parsing, resolving, the component setters and query string encoding
+//! follow the WHATWG URL standard through `url`'s `quirks` module, so they
+//! behave as in browsers. The JS classes are thin wrappers: a `URL` keeps
+//! its parsed components and asks the native side to rebuild them whenever
+//! one is set. They are also importable from `url` and `node:url`.
+
+use rquickjs::function::Opt;
+use rquickjs::{Ctx, Exception, Function, Object};
+use url::{form_urlencoded, quirks, Url};
+
+/// Hidden global with the native functions the classes call
+const NATIVE: &str = "__url";
+
+/// The `URL` and `URLSearchParams` classes
+const CLASSES: &str = r#"
+(function() {
+    var native = globalThis.__url;
+
+    function URLSearchParams(init) {
+        this._pairs = [];
+        this._url = null;
+        if (init === undefined || init === null) return;
+        if (typeof init === 'object' && typeof init[Symbol.iterator] === 'function') {
+            for (var pair of init) {
+                pair = Array.from(pair);
+                if (pair.length !== 2) throw new TypeError('Each query pair must have a name and a value');
+                this._pairs.push([String(pair[0]), String(pair[1])]);
+            }
+        } else if (typeof init === 'object') {
+            for (var key of Object.keys(init)) this._pairs.push([key, String(init[key])]);
+        } else {
+            this._pairs = native.parseQuery(String(init));
+        }
+    }
+    URLSearchParams.prototype._update = function() {
+        if (this._url) this._url._parts = native.set(this._url._parts.href, 'search', this.toString());
+    };
+    URLSearchParams.prototype.append = function(name, value) {
+        this._pairs.push([String(name), String(value)]);
+        this._update();
+    };
+    URLSearchParams.prototype.delete = function(name, value) {
+        name = String(name);
+        this._pairs = this._pairs.filter(function(pair) {
+            return pair[0] !== name || (value !== undefined && pair[1] !== String(value));
+        });
+        this._update();
+    };
+    URLSearchParams.prototype.get = function(name) {
+        name = String(name);
+        var pair = this._pairs.find(function(pair) { return pair[0] === name; });
+        return pair ? pair[1] : null;
+    };
+    URLSearchParams.prototype.getAll = function(name) {
+        name = String(name);
+        return this._pairs.filter(function(pair) { return pair[0] === name; }).map(function(pair) { return pair[1]; });
+    };
+    URLSearchParams.prototype.has = function(name, value) {
+        name = String(name);
+        return this._pairs.some(function(pair) {
+            return pair[0] === name && (value === undefined || pair[1] === String(value));
+        });
+    };
+    URLSearchParams.prototype.set = function(name, value) {
+        name = String(name);
+        var index = this._pairs.findIndex(function(pair) { return pair[0] === name; });
+        if (index < 0) {
+            this._pairs.push([name, String(value)]);
+        } else {
+            this._pairs[index] = [name, String(value)];
+            this._pairs = this._pairs.filter(function(pair, i) { return i <= index || pair[0] !== name; });
+        }
+        this._update();
+    };
+    URLSearchParams.prototype.sort = function() {
+        // Stable, by UTF-16 code units as the standard says
+        this._pairs.sort(function(a, b) { return a[0] < b[0] ? -1 : a[0] > b[0] ? 1 : 0; });
+        this._update();
+    };
+    URLSearchParams.prototype.forEach = function(callback, thisArg) {
+        for (var pair of this._pairs.slice()) callback.call(thisArg, pair[1], pair[0], this);
+    };
+    URLSearchParams.prototype.entries = function() {
+        return this._pairs.map(function(pair) { return [pair[0], pair[1]]; })[Symbol.iterator]();
+    };
+    URLSearchParams.prototype.keys = function() {
+        return this._pairs.map(function(pair) { return pair[0]; })[Symbol.iterator]();
+    };
+    URLSearchParams.prototype.values = function() {
+        return this._pairs.map(function(pair) { return pair[1]; })[Symbol.iterator]();
+    };
+    URLSearchParams.prototype[Symbol.iterator] = URLSearchParams.prototype.entries;
+    URLSearchParams.prototype.toString = function() {
+        return native.serializeQuery(this._pairs);
+    };
+    Object.defineProperty(URLSearchParams.prototype, 'size', {
+        get: function() { return this._pairs.length; },
+    });
+
+    function URL(input, base) {
+        this._parts = native.parse(String(input), base === undefined ? undefined : String(base));
+        this._params = null;
+    }
+    URL.canParse = function(input, base) {
+        try {
+            native.parse(String(input), base === undefined ? undefined : String(base));
+            return true;
+        } catch (e) {
+            return false;
+        }
+    };
+    ['href', 'protocol', 'username', 'password', 'host', 'hostname', 'port', 'pathname', 'search', 'hash'].forEach(function(name) {
+        Object.defineProperty(URL.prototype, name, {
+            get: function() { return this._parts[name]; },
+            set: function(value) {
+                this._parts = native.set(this._parts.href, name, String(value));
+                if (this._params && (name === 'href' || name === 'search')) {
+                    this._params._pairs = native.parseQuery(this._parts.search);
+                }
+            },
+        });
+    });
+    Object.defineProperty(URL.prototype, 'origin', {
+        get: function() { return this._parts.origin; },
+    });
+    Object.defineProperty(URL.prototype, 'searchParams', {
+        get: function() {
+            if (!this._params) {
+                this._params = new URLSearchParams(this._parts.search);
+                this._params._url = this;
+            }
+            return this._params;
+        },
+    });
+    URL.prototype.toString = function() { return this._parts.href; };
+    URL.prototype.toJSON = URL.prototype.toString;
+
+    globalThis.URL = URL;
+    globalThis.URLSearchParams = URLSearchParams;
+    var module = { URL: URL, URLSearchParams: URLSearchParams };
+    __modules['url'] = module;
+    __modules['node:url'] = module;
+})();
+"#;
+
+/// Parse `input` as `new URL(input, base)` does
+pub fn parse(input: &str, base: Option<&str>) -> Option<Url> {
+    match base {
+        Some(base) => Url::options().base_url(Some(&Url::parse(base).ok()?)).parse(input).ok(),
+        None => Url::parse(input).ok(),
+    }
+}
+
+/// Set component `name` like the `URL` setter of that name
+///
+/// Values a component can't take are ignored, as in browsers, except for
+/// `href`: an invalid one returns `false`.
+pub fn set(url: &mut Url, name: &str, value: &str) -> bool {
+    match name {
+        "href" => match Url::parse(value) {
+            Ok(parsed) => *url = parsed,
+            Err(_) => return false,
+        },
+        "protocol" => {
+            let _ = quirks::set_protocol(url, value);
+        }
+        "username" => {
+            let _ = quirks::set_username(url, value);
+        }
+        "password" => {
+            let _ = quirks::set_password(url, value);
+        }
+        "host" => {
+            let _ = quirks::set_host(url, value);
+        }
+        "hostname" => {
+            let _ = quirks::set_hostname(url, value);
+        }
+        "port" => {
+            let _ = quirks::set_port(url, value);
+        }
+        "pathname" => quirks::set_pathname(url, value),
+        "search" => quirks::set_search(url, value),
+        "hash" => quirks::set_hash(url, value),
+        _ => {}
+    }
+    true
+}
+
+/// The components of `url`, as the `URL` getters return them
+fn parts<'js>(ctx: &Ctx<'js>, url: &Url) -> rquickjs::Result<Object<'js>> {
+    let getters: [(&str, fn(&Url) -> &str); 10] = [
+        ("href", quirks::href),
+        ("protocol", quirks::protocol),
+        ("username", quirks::username),
+        ("password", quirks::password),
+        ("host", quirks::host),
+        ("hostname", quirks::hostname),
+        ("port", quirks::port),
+        ("pathname", quirks::pathname),
+        ("search", quirks::search),
+        ("hash", quirks::hash),
+    ];
+    let parts = Object::new(ctx.clone())?;
+    for (name, get) in getters {
+        parts.set(name, get(url))?;
+    }
+    parts.set("origin", quirks::origin(url))?;
+    Ok(parts)
+}
+
+/// Name/value pairs of a query string, with or without its leading `?`
+pub fn parse_query(query: &str) -> Vec<(String, String)> {
+    let query = query.strip_prefix('?').unwrap_or(query);
+    form_urlencoded::parse(query.as_bytes()).into_owned().collect()
+}
+
+/// Encode name/value pairs as `application/x-www-form-urlencoded`
+pub fn serialize_query(pairs: &[(String, String)]) -> String {
+    form_urlencoded::Serializer::new(String::new()).extend_pairs(pairs).finish()
+}
+
+/// Install the `URL` and `URLSearchParams` globals
+///
+/// Runs after `builtins`, which creates `__modules`.
+pub fn install<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+    let native = Object::new(ctx.clone())?;
+    native.set(
+        "parse",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, input: String, base: Opt<String>| {
+            let url = parse(&input, base.0.as_deref())
+                .ok_or_else(|| Exception::throw_type(&ctx, &format!("Invalid URL: '{}'", input)))?;
+            parts(&ctx, &url)
+        })?,
+    )?;
+    native.set(
+        "set",
+        Function::new(ctx.clone(), |ctx: Ctx<'js>, href: String, name: String, value: String| {
+            let mut url = Url::parse(&href).map_err(|_| Exception::throw_type(&ctx, &format!("Invalid URL: '{}'", href)))?;
+            if !set(&mut url, &name, &value) {
+                return Err(Exception::throw_type(&ctx, &format!("Invalid URL: '{}'", value)));
+            }
+            parts(&ctx, &url)
+        })?,
+    )?;
+    native.set(
+        "parseQuery",
+        Function::new(ctx.clone(), |query: String| {
+            parse_query(&query).into_iter().map(|(name, value)| vec![name, value]).collect::<Vec<_>>()
+        })?,
+    )?;
+    native.set(
+        "serializeQuery",
+        Function::new(ctx.clone(), |pairs: Vec<Vec<String>>| {
+            let pairs: Vec<(String, String)> = pairs
+                .into_iter()
+                .map(|pair| {
+                    let mut pair = pair.into_iter();
+                    (pair.next().unwrap_or_default(), pair.next().unwrap_or_default())
+                })
+                .collect();
+            serialize_query(&pairs)
+        })?,
+    )?;
+    ctx.globals().set(NATIVE, native)?;
+    ctx.eval::<(), _>(CLASSES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_set() {
+        let url = parse("../b?x=1#top", Some("https://example.com/a/c/d")).unwrap();
+        assert_eq!(quirks::href(&url), "https://example.com/a/b?x=1#top");
+        assert!(parse("/relative", None).is_none());
+        assert!(parse("a", Some("not a url")).is_none());
+
+        let mut url = Url::parse("https://example.com:8080/path").unwrap();
+        // Invalid values are ignored, and default ports dropped
+        assert!(set(&mut url, "port", "not a port"));
+        assert_eq!(quirks::port(&url), "8080");
+        assert!(set(&mut url, "port", "443"));
+        assert_eq!(quirks::host(&url), "example.com");
+        assert!(set(&mut url, "search", "q=a b"));
+        assert_eq!(quirks::search(&url), "?q=a%20b");
+        assert!(!set(&mut url, "href", "nope"));
+        assert_eq!(quirks::origin(&url), "https://example.com");
+    }
+
+    #[test]
+    fn test_query_strings() {
+        let pairs = parse_query("?a=1&b=x+y&a=%C3%A9&empty");
+        assert_eq!(pairs[1], ("b".to_string(), "x y".to_string()));
+        assert_eq!(pairs[2], ("a".to_string(), "é".to_string()));
+        assert_eq!(pairs[3], ("empty".to_string(), String::new()));
+        assert_eq!(serialize_query(&pairs), "a=1&b=x+y&a=%C3%A9&empty=");
+    }
+}
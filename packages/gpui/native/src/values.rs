@@ -0,0 +1,271 @@
+//! Conversions between JS values and Rust data, and `structuredClone`
+//!
+//! [`to_json`] and [`from_json`] convert between JS values and
+//! `serde_json::Value` directly, without a round trip through JSON text.
+//! [`from_js`] and [`to_js`] go on to any serde type, so native APIs take an
+//! options object as a `#[derive(Deserialize)]` struct instead of reading it
+//! property by property, and a wrong type is reported as a `TypeError` that
+//! names the field.
+//!
+//! The mapping follows `JSON.stringify`: `toJSON` is honored (dates become
+//! ISO strings), object properties holding `undefined`, functions or
+//! symbols are left out, and in arrays they become `null`, as do `NaN` and
+//! infinities. Circular structures throw.
+
+use rquickjs::function::This;
+use rquickjs::{Array, Ctx, Exception, Function, Object, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Number, Value as Json};
+
+/// Deepest nesting converted, well below the native stack's limit
+const MAX_DEPTH: usize = 256;
+
+/// The `structuredClone` global
+///
+/// Clones plain objects (class instances lose their prototype, as in
+/// browsers), arrays, dates, regexps, maps, sets, errors, array buffers and
+/// typed arrays, keeping shared references and cycles. Functions, symbols,
+/// promises and weak collections throw a `DataCloneError`.
+const STRUCTURED_CLONE: &str = r#"
+(function() {
+    function cloneError(message) {
+        var error = new Error(message);
+        error.name = 'DataCloneError';
+        return error;
+    }
+
+    function clone(value, seen) {
+        if (typeof value === 'function') throw cloneError((value.name || 'function') + ' could not be cloned');
+        if (typeof value === 'symbol') throw cloneError(String(value) + ' could not be cloned');
+        if (value === null || typeof value !== 'object') return value;
+        if (seen.has(value)) return seen.get(value);
+
+        var copy;
+        if (Array.isArray(value)) {
+            copy = new Array(value.length);
+            seen.set(value, copy);
+            for (var i = 0; i < value.length; i++) {
+                if (i in value) copy[i] = clone(value[i], seen);
+            }
+            return copy;
+        }
+        if (value instanceof Map) {
+            copy = new Map();
+            seen.set(value, copy);
+            value.forEach(function(item, key) { copy.set(clone(key, seen), clone(item, seen)); });
+            return copy;
+        }
+        if (value instanceof Set) {
+            copy = new Set();
+            seen.set(value, copy);
+            value.forEach(function(item) { copy.add(clone(item, seen)); });
+            return copy;
+        }
+        if (value instanceof Promise || value instanceof WeakMap || value instanceof WeakSet) {
+            throw cloneError(Object.prototype.toString.call(value) + ' could not be cloned');
+        }
+
+        if (value instanceof Date) {
+            copy = new Date(value.getTime());
+        } else if (value instanceof RegExp) {
+            copy = new RegExp(value.source, value.flags);
+        } else if (value instanceof ArrayBuffer) {
+            copy = value.slice(0);
+        } else if (ArrayBuffer.isView(value)) {
+            var buffer = clone(value.buffer, seen);
+            copy = value instanceof DataView
+                ? new DataView(buffer, value.byteOffset, value.byteLength)
+                : new value.constructor(buffer, value.byteOffset, value.length);
+        } else if (value instanceof Error) {
+            var Type = globalThis[value.name];
+            copy = typeof Type === 'function' && Type.prototype instanceof Error || Type === Error
+                ? new Type(value.message)
+                : new Error(value.message);
+            if (value.stack !== undefined) copy.stack = value.stack;
+        } else if (value instanceof Boolean || value instanceof Number || value instanceof String) {
+            copy = Object(value.valueOf());
+        } else {
+            copy = {};
+            seen.set(value, copy);
+            for (var key of Object.keys(value)) copy[key] = clone(value[key], seen);
+            return copy;
+        }
+        seen.set(value, copy);
+        return copy;
+    }
+
+    globalThis.structuredClone = function structuredClone(value) {
+        if (arguments.length === 0) throw new TypeError('structuredClone needs a value');
+        return clone(value, new Map());
+    };
+})();
+"#;
+
+/// Convert a JS value to JSON data, as `JSON.stringify` would
+pub fn to_json<'js>(ctx: &Ctx<'js>, value: &Value<'js>) -> rquickjs::Result<Json> {
+    convert(ctx, value, &mut Vec::new())
+}
+
+/// Whether `JSON.stringify` leaves `value` out of objects
+fn skipped(value: &Value) -> bool {
+    value.is_undefined() || value.is_function() || value.is_symbol()
+}
+
+fn number(value: f64) -> Json {
+    // Whole numbers stay integers, so they deserialize into integer fields
+    if value.fract() == 0.0 && value.abs() < 9_007_199_254_740_992.0 {
+        return Json::from(value as i64);
+    }
+    Number::from_f64(value).map_or(Json::Null, Json::Number)
+}
+
+/// Convert `value`; `seen` holds the objects around it, for cycles
+fn convert<'js>(ctx: &Ctx<'js>, value: &Value<'js>, seen: &mut Vec<Value<'js>>) -> rquickjs::Result<Json> {
+    if let Some(value) = value.as_bool() {
+        return Ok(Json::Bool(value));
+    }
+    if let Some(value) = value.as_int() {
+        return Ok(Json::from(value));
+    }
+    if let Some(value) = value.as_number() {
+        return Ok(number(value));
+    }
+    if let Some(value) = value.as_string() {
+        return Ok(Json::String(value.to_string()?));
+    }
+    if value.as_big_int().is_some() {
+        return Err(Exception::throw_type(ctx, "BigInt values can't be converted"));
+    }
+    let Some(object) = value.as_object().filter(|_| !skipped(value)) else {
+        return Ok(Json::Null);
+    };
+    if seen.contains(value) {
+        return Err(Exception::throw_type(ctx, "Can't convert a circular structure"));
+    }
+    if seen.len() == MAX_DEPTH {
+        return Err(Exception::throw_range(ctx, "Value is nested too deeply to convert"));
+    }
+
+    if let Ok(to_json) = object.get::<_, Function>("toJSON") {
+        let replaced: Value = to_json.call((This(object.clone()),))?;
+        if replaced != *value {
+            seen.push(value.clone());
+            let result = convert(ctx, &replaced, seen);
+            seen.pop();
+            return result;
+        }
+    }
+
+    seen.push(value.clone());
+    let result = if let Some(array) = object.as_array() {
+        let mut items = Vec::with_capacity(array.len());
+        for item in array.iter::<Value>() {
+            items.push(convert(ctx, &item?, seen)?);
+        }
+        Json::Array(items)
+    } else {
+        let mut map = Map::new();
+        for prop in object.props::<String, Value>() {
+            let (key, item) = prop?;
+            if !skipped(&item) {
+                map.insert(key, convert(ctx, &item, seen)?);
+            }
+        }
+        Json::Object(map)
+    };
+    seen.pop();
+    Ok(result)
+}
+
+/// Convert JSON data to a JS value
+pub fn from_json<'js>(ctx: &Ctx<'js>, json: &Json) -> rquickjs::Result<Value<'js>> {
+    Ok(match json {
+        Json::Null => Value::new_null(ctx.clone()),
+        Json::Bool(value) => Value::new_bool(ctx.clone(), *value),
+        Json::Number(value) => match value.as_i64().and_then(|value| i32::try_from(value).ok()) {
+            Some(value) => Value::new_int(ctx.clone(), value),
+            None => Value::new_number(ctx.clone(), value.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(value) => rquickjs::String::from_str(ctx.clone(), value)?.into_value(),
+        Json::Array(items) => {
+            let array = Array::new(ctx.clone())?;
+            for (index, item) in items.iter().enumerate() {
+                array.set(index, from_json(ctx, item)?)?;
+            }
+            array.into_value()
+        }
+        Json::Object(map) => {
+            let object = Object::new(ctx.clone())?;
+            for (key, item) in map {
+                object.set(key.as_str(), from_json(ctx, item)?)?;
+            }
+            object.into_value()
+        }
+    })
+}
+
+/// Deserialize a JS value; a mismatch throws a `TypeError`
+pub fn from_js<'js, T: DeserializeOwned>(ctx: &Ctx<'js>, value: &Value<'js>) -> rquickjs::Result<T> {
+    let json = to_json(ctx, value)?;
+    serde_json::from_value(json).map_err(|e| Exception::throw_type(ctx, &e.to_string()))
+}
+
+/// Deserialize an optional options object, with the defaults when absent
+pub fn options<'js, T: DeserializeOwned + Default>(ctx: &Ctx<'js>, value: Option<Value<'js>>) -> rquickjs::Result<T> {
+    match value {
+        Some(value) if !value.is_undefined() && !value.is_null() => from_js(ctx, &value),
+        _ => Ok(T::default()),
+    }
+}
+
+/// Serialize a Rust value to JS
+pub fn to_js<'js, T: Serialize>(ctx: &Ctx<'js>, value: &T) -> rquickjs::Result<Value<'js>> {
+    let json = serde_json::to_value(value).map_err(|e| Exception::throw_internal(ctx, &e.to_string()))?;
+    from_json(ctx, &json)
+}
+
+/// Install the `structuredClone` global
+pub fn install(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(STRUCTURED_CLONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rquickjs::{Context, Runtime};
+    use serde_json::json;
+
+    #[test]
+    fn test_conversions_and_clone() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        context.with(|ctx| {
+            install(&ctx).unwrap();
+            let value: Value = ctx
+                .eval("({ a: 1, b: [1.5, undefined, NaN], c: 'x', f() {}, d: new Date(0), n: null, u: undefined })")
+                .unwrap();
+            let json = to_json(&ctx, &value).unwrap();
+            assert_eq!(json, json!({ "a": 1, "b": [1.5, null, null], "c": "x", "d": "1970-01-01T00:00:00.000Z", "n": null }));
+            let back = from_json(&ctx, &json).unwrap();
+            assert_eq!(to_json(&ctx, &back).unwrap(), json);
+
+            let circular: Value = ctx.eval("var o = {}; o.self = o; o").unwrap();
+            assert!(to_json(&ctx, &circular).is_err());
+
+            let cloned: bool = ctx
+                .eval(
+                    "var m = new Map([[1, { d: new Date(5) }]]); var s = { m: m, again: m, list: [1, 2] }; s.self = s;
+                     var c = structuredClone(s);
+                     c !== s && c.self === c && c.m === c.again && c.m !== m && c.m.get(1).d.getTime() === 5
+                         && c.m.get(1).d instanceof Date && c.list[1] === 2",
+                )
+                .unwrap();
+            assert!(cloned);
+            let rejected: String = ctx
+                .eval("try { structuredClone({ f: function() {} }); 'cloned' } catch (e) { e.name }")
+                .unwrap();
+            assert_eq!(rejected, "DataCloneError");
+        });
+    }
+}
@@ -0,0 +1,205 @@
+//! WebAssembly modules, imported like JS modules
+//!
+//! `import lib from './lib.wasm'` (in the entry script or a bundled module)
+//! compiles the file with wasmi and instantiates it. The result has each
+//! exported function, called with and returning JS numbers, and each
+//! exported memory as `{ size(), read(ptr, len), write(ptr, bytes) }` for
+//! passing strings and buffers. Compute-heavy code (parsers, image
+//! processing) runs compiled instead of in the JS interpreter that way.
+//!
+//! Modules must be self-contained: imports (WASI, JS glue) aren't provided,
+//! so build with `wasm32-unknown-unknown` and no `wasm-bindgen`. `i64`
+//! results become JS numbers and lose precision past 2^53.
+
+use rquickjs::function::Rest;
+use rquickjs::{Array, Ctx, Exception, Function, Object, TypedArray, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wasmi::{Engine, Extern, Instance, Linker, Memory, Module, Store, Val, ValType};
+use crate::integrity;
+
+/// Global with `load`, which the import transforms call
+const NATIVE: &str = "__wasm";
+
+/// An instantiated module; calls lock it, since wasm state is mutable
+struct Instantiated {
+    store: Store<()>,
+    instance: Instance,
+}
+
+/// Compile and instantiate module `bytes`
+fn instantiate(bytes: &[u8]) -> Result<Instantiated, String> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, bytes).map_err(|e| e.to_string())?;
+    let mut store = Store::new(&engine, ());
+    let linker = <Linker<()>>::new(&engine);
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(|e| e.to_string())?;
+    Ok(Instantiated { store, instance })
+}
+
+/// Convert a JS argument to a wasm parameter of type `ty`
+fn to_wasm(value: &Value, ty: &ValType) -> Option<Val> {
+    let number = value.as_number().or_else(|| value.as_bool().map(|b| b as i32 as f64))?;
+    Some(match ty {
+        ValType::I32 => Val::I32(number as i64 as i32),
+        ValType::I64 => Val::I64(number as i64),
+        ValType::F32 => Val::F32((number as f32).into()),
+        ValType::F64 => Val::F64(number.into()),
+        _ => return None,
+    })
+}
+
+/// Convert a wasm result to a JS number
+fn to_number(value: &Val) -> Option<f64> {
+    match value {
+        Val::I32(value) => Some(*value as f64),
+        Val::I64(value) => Some(*value as f64),
+        Val::F32(value) => Some(f32::from(*value) as f64),
+        Val::F64(value) => Some(f64::from(*value)),
+        _ => None,
+    }
+}
+
+/// Call exported function `name` with JS arguments
+fn call<'js>(ctx: &Ctx<'js>, module: &Mutex<Instantiated>, name: &str, args: Vec<Value<'js>>) -> rquickjs::Result<Value<'js>> {
+    let mut module = module.lock().unwrap();
+    let Instantiated { store, instance } = &mut *module;
+    let func = instance.get_func(&*store, name).ok_or_else(|| Exception::throw_reference(ctx, &format!("No export '{}'", name)))?;
+    let ty = func.ty(&*store);
+
+    let params = ty
+        .params()
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| {
+            let arg = args.get(i).cloned().unwrap_or_else(|| Value::new_number(ctx.clone(), 0.0));
+            to_wasm(&arg, ty).ok_or_else(|| {
+                Exception::throw_type(ctx, &format!("{}: argument {} must be a number ({:?})", name, i + 1, ty))
+            })
+        })
+        .collect::<rquickjs::Result<Vec<_>>>()?;
+    let mut results: Vec<Val> = ty.results().iter().map(|ty| Val::default(*ty)).collect();
+    func.call(&mut *store, &params, &mut results)
+        .map_err(|e| Exception::throw_internal(ctx, &format!("{}: {}", name, e)))?;
+
+    let mut numbers = results.iter().map(|result| {
+        to_number(result).ok_or_else(|| Exception::throw_type(ctx, &format!("{}: results of this type aren't supported", name)))
+    });
+    match results.len() {
+        0 => Ok(Value::new_undefined(ctx.clone())),
+        1 => Ok(Value::new_number(ctx.clone(), numbers.next().unwrap()?)),
+        _ => {
+            let array = Array::new(ctx.clone())?;
+            for (i, number) in numbers.enumerate() {
+                array.set(i, number?)?;
+            }
+            Ok(array.into_value())
+        }
+    }
+}
+
+/// Bytes `ptr..ptr + len` of `memory`, or a `RangeError`
+fn range<'js>(ctx: &Ctx<'js>, data: &[u8], ptr: f64, len: f64) -> rquickjs::Result<std::ops::Range<usize>> {
+    let (start, len) = (ptr.max(0.0) as usize, len.max(0.0) as usize);
+    match start.checked_add(len) {
+        Some(end) if end <= data.len() => Ok(start..end),
+        _ => Err(Exception::throw_range(ctx, &format!("{}..{} is outside the memory ({} bytes)", start, start + len, data.len()))),
+    }
+}
+
+/// Accessors for an exported memory
+fn memory_object<'js>(ctx: &Ctx<'js>, module: &Arc<Mutex<Instantiated>>, memory: Memory) -> rquickjs::Result<Object<'js>> {
+    let object = Object::new(ctx.clone())?;
+    let shared = module.clone();
+    object.set(
+        "size",
+        Function::new(ctx.clone(), move || memory.data(&shared.lock().unwrap().store).len() as f64)?,
+    )?;
+    let shared = module.clone();
+    object.set(
+        "read",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, ptr: f64, len: f64| {
+            let module = shared.lock().unwrap();
+            let data = memory.data(&module.store);
+            TypedArray::<u8>::new(ctx.clone(), data[range(&ctx, data, ptr, len)?].to_vec())
+        })?,
+    )?;
+    let shared = module.clone();
+    object.set(
+        "write",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, ptr: f64, bytes: TypedArray<'js, u8>| -> rquickjs::Result<()> {
+            let bytes = bytes.as_bytes().unwrap_or_default();
+            let mut module = shared.lock().unwrap();
+            let data = memory.data_mut(&mut module.store);
+            let range = range(&ctx, data, ptr, bytes.len() as f64)?;
+            data[range].copy_from_slice(bytes);
+            Ok(())
+        })?,
+    )?;
+    Ok(object)
+}
+
+/// Load the module at `path` and build its JS exports object
+fn load<'js>(ctx: &Ctx<'js>, path: &str) -> rquickjs::Result<Object<'js>> {
+    let bytes = std::fs::read(path).map_err(|e| Exception::throw_message(ctx, &format!("Can't read {}: {}", path, e)))?;
+    integrity::check(Path::new(path), &bytes).map_err(|message| Exception::throw_message(ctx, &message))?;
+    let module = instantiate(&bytes).map_err(|e| Exception::throw_message(ctx, &format!("Can't instantiate {}: {}", path, e)))?;
+
+    let exports: Vec<(String, Extern)> = module
+        .instance
+        .exports(&module.store)
+        .map(|export| (export.name().to_string(), export.into_extern()))
+        .collect();
+    let module = Arc::new(Mutex::new(module));
+    let object = Object::new(ctx.clone())?;
+    for (name, export) in exports {
+        match export {
+            Extern::Func(_) => {
+                let shared = module.clone();
+                let export = name.clone();
+                let function = Function::new(ctx.clone(), move |ctx: Ctx<'js>, args: Rest<Value<'js>>| {
+                    call(&ctx, &shared, &export, args.0)
+                })?;
+                object.set(name.as_str(), function)?;
+            }
+            Extern::Memory(memory) => object.set(name.as_str(), memory_object(ctx, &module, memory)?)?,
+            // Globals and tables aren't exposed
+            _ => {}
+        }
+    }
+    Ok(object)
+}
+
+/// Install `__wasm.load(path)`, used by imports of `.wasm` files
+pub fn install<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<()> {
+    let native = Object::new(ctx.clone())?;
+    native.set("load", Function::new(ctx.clone(), |ctx: Ctx<'js>, path: String| load(&ctx, &path))?)?;
+    ctx.globals().set(NATIVE, native)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(module (func (export "add") (param i32 i32) (result i32) local.get 0 local.get 1 i32.add))`
+    const ADD: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type: (i32, i32) -> i32
+        0x03, 0x02, 0x01, 0x00, // function 0 has type 0
+        0x07, 0x07, 0x01, 0x03, b'a', b'd', b'd', 0x00, 0x00, // export "add"
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // body
+    ];
+
+    #[test]
+    fn test_instantiate_and_call() {
+        let mut module = instantiate(ADD).unwrap();
+        let func = module.instance.get_func(&module.store, "add").unwrap();
+        let mut results = [Val::I32(0)];
+        func.call(&mut module.store, &[Val::I32(2), Val::I32(40)], &mut results).unwrap();
+        assert_eq!(to_number(&results[0]), Some(42.0));
+        assert!(instantiate(b"not wasm").is_err());
+    }
+}
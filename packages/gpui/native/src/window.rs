@@ -0,0 +1,227 @@
+//! Window options from `run(App, { title, width, height, minSize, resizable, centered, display })`,
+//! more windows with `rasen.window.open(Component, options)`, and fullscreen
+//!
+//! The script runs before the window opens, so `run` only records its
+//! options in `__windowOptions`, and the window opens with them. Where the
+//! previous session left a resizable window (the main window, or one opened
+//! with a `name`) wins over `width`, `height`, `centered` and `display`;
+//! those set the size and place of its first launch.
+//!
+//! `rasen.window.open` runs the component's setup right away, like `run`,
+//! and queues the window for the app, which opens it on the UI thread with
+//! a root of its own. Windows share the runtime, so state, stores and
+//! handlers are shared between them, and any change re-renders them all.
+//! Closing a window, by the user or with the handle's `close()`, unmounts
+//! its component.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use gpui::*;
+use rquickjs::{Ctx, Function, Object, Value};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use crate::{native_api, screen, values};
+
+/// Identifies a window of the runtime
+pub type WindowId = u32;
+
+/// The window `run` renders into; those `rasen.window.open` opens count
+/// up from 1
+pub const MAIN_WINDOW: WindowId = 0;
+
+/// Size of a window whose options don't set one
+const DEFAULT_WIDTH: f32 = 800.0;
+const DEFAULT_HEIGHT: f32 = 600.0;
+
+/// The options passed to `run`, all optional
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WindowSpec {
+    pub title: Option<String>,
+    /// Content size in logical pixels
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+    pub min_size: Option<Extent>,
+    /// Whether the user can resize the window (default true)
+    pub resizable: Option<bool>,
+    /// Whether the window opens centered on its display (default true);
+    /// otherwise it opens at the display's top-left corner
+    pub centered: Option<bool>,
+    /// ID of the display to open on, from `rasen.screen.getDisplays()`; the
+    /// main display if not set or not connected
+    pub display: Option<u32>,
+    /// Name a `rasen.window.open` window is saved under in the session
+    /// (see `session`); unnamed ones aren't saved
+    pub name: Option<String>,
+    /// Route a named window starts at, recorded in the session
+    pub route: Option<String>,
+}
+
+/// A `{ width, height }` in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Extent {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl WindowSpec {
+    /// Decode the options as JSON; `null` gives the defaults
+    pub fn parse(json: &str) -> serde_json::Result<Self> {
+        Ok(serde_json::from_str::<Option<Self>>(json)?.unwrap_or_default())
+    }
+
+    /// Content size to open with, in logical pixels
+    pub fn size(&self) -> (f32, f32) {
+        let length = |value: Option<f32>, default| value.filter(|v| v.is_finite() && *v > 0.0).unwrap_or(default);
+        (length(self.width, DEFAULT_WIDTH), length(self.height, DEFAULT_HEIGHT))
+    }
+
+    pub fn resizable(&self) -> bool {
+        self.resizable.unwrap_or(true)
+    }
+
+    /// Bounds and display to open with, given where the last session left
+    /// the window; saved bounds are on the desktop, so they need no display
+    pub fn placement(&self, saved: Option<WindowBounds>, cx: &App) -> (WindowBounds, Option<DisplayId>) {
+        if let Some(saved) = saved.filter(|_| self.resizable()) {
+            return (saved, None);
+        }
+        let (width, height) = self.size();
+        let size = size(px(width), px(height));
+        let display = self.display.and_then(|id| screen::find(id, cx)).or_else(|| cx.primary_display());
+        let display_id = display.as_ref().map(|display| display.id());
+        let bounds = if self.centered.unwrap_or(true) {
+            Bounds::centered(display_id, size, cx)
+        } else {
+            Bounds::new(display.map(|display| display.bounds().origin).unwrap_or_default(), size)
+        };
+        (WindowBounds::Windowed(bounds), display_id)
+    }
+
+    /// Title bar with the title, or `fallback` (the app name) without one
+    pub fn titlebar(&self, fallback: Option<&str>) -> TitlebarOptions {
+        let title = self.title.as_deref().or(fallback).map(|title| SharedString::from(title.to_string()));
+        TitlebarOptions { title, ..Default::default() }
+    }
+
+    pub fn min_size(&self) -> Option<Size<Pixels>> {
+        self.min_size.map(|min| size(px(min.width.max(0.0)), px(min.height.max(0.0))))
+    }
+}
+
+/// A change to the open windows, asked for by JS
+#[derive(Debug, PartialEq)]
+pub enum WindowRequest {
+    Open(WindowId, WindowSpec),
+    Close(WindowId),
+    Fullscreen(WindowId, bool),
+}
+
+/// Windows JS asked to open or close, waiting for the app; clones share them
+#[derive(Clone)]
+pub struct Windows {
+    state: Arc<Mutex<WindowState>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct WindowState {
+    next_id: WindowId,
+    requests: Vec<WindowRequest>,
+}
+
+impl Windows {
+    /// Create an empty queue; the receiver wakes when a request is added
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { state: Arc::default(), wake }, wakeups)
+    }
+
+    fn next_id(&self) -> WindowId {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        state.next_id
+    }
+
+    fn request(&self, request: WindowRequest) {
+        self.state.lock().unwrap().requests.push(request);
+        let _ = self.wake.unbounded_send(());
+    }
+
+    /// Take the requests in the order they were made
+    pub fn take_requests(&self) -> Vec<WindowRequest> {
+        std::mem::take(&mut self.state.lock().unwrap().requests)
+    }
+}
+
+/// Install `rasen.window.open` and `rasen.window.setFullscreen`, which
+/// applies to the main window
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>, windows: &Windows) -> rquickjs::Result<()> {
+    let window = native_api::namespace(ctx, rasen, "window")?;
+    let requests = windows.clone();
+    window.set(
+        "setFullscreen",
+        Function::new(ctx.clone(), move |on: bool| requests.request(WindowRequest::Fullscreen(MAIN_WINDOW, on)))?,
+    )?;
+    let windows = windows.clone();
+    window.set(
+        "open",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, component: Function<'js>, options: Option<Value<'js>>| {
+            let spec: WindowSpec = values::options(&ctx, options)?;
+            // Set the component up now, like `run`, so its errors throw here
+            let id = windows.next_id();
+            let mount: Function = ctx.globals().get("__mountWindow")?;
+            mount.call::<_, ()>((id, component, spec.route.clone()))?;
+            windows.request(WindowRequest::Open(id, spec));
+
+            let handle = Object::new(ctx.clone())?;
+            handle.set("id", id)?;
+            let requests = windows.clone();
+            handle.set("close", Function::new(ctx.clone(), move || requests.request(WindowRequest::Close(id)))?)?;
+            let requests = windows.clone();
+            handle.set(
+                "setFullscreen",
+                Function::new(ctx.clone(), move |on: bool| requests.request(WindowRequest::Fullscreen(id, on)))?,
+            )?;
+            Ok::<_, rquickjs::Error>(handle)
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window_options() {
+        let json = r#"{"title":"Notes","width":1024,"minSize":{"width":400,"height":300},"resizable":false,"display":2}"#;
+        let spec = WindowSpec::parse(json).unwrap();
+        assert_eq!(spec.title.as_deref(), Some("Notes"));
+        assert_eq!(spec.min_size, Some(Extent { width: 400.0, height: 300.0 }));
+        assert!(!spec.resizable());
+        assert_eq!(spec.display, Some(2));
+        // Height keeps its default
+        assert_eq!(spec.size(), (1024.0, DEFAULT_HEIGHT));
+        assert_eq!(WindowSpec::parse("null").unwrap(), WindowSpec::default());
+    }
+
+    #[test]
+    fn test_invalid_sizes_use_defaults() {
+        let spec = WindowSpec { width: Some(-10.0), height: Some(f32::NAN), ..Default::default() };
+        assert_eq!(spec.size(), (DEFAULT_WIDTH, DEFAULT_HEIGHT));
+    }
+
+    #[test]
+    fn test_window_requests_keep_their_order() {
+        let (windows, _wakeups) = Windows::new();
+        let (first, second) = (windows.next_id(), windows.next_id());
+        assert_eq!((first, second), (1, 2));
+        windows.request(WindowRequest::Open(first, WindowSpec::default()));
+        windows.request(WindowRequest::Close(first));
+        assert_eq!(
+            windows.take_requests(),
+            vec![WindowRequest::Open(1, WindowSpec::default()), WindowRequest::Close(1)]
+        );
+        assert!(windows.take_requests().is_empty());
+    }
+}
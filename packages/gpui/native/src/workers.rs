@@ -0,0 +1,476 @@
+//! Background workers: scripts running in their own JS runtime and thread
+//!
+//! `new Worker('./worker.ts')` reads the file (relative to the entry
+//! script and inside its directory, TypeScript compiled like the entry
+//! script), checks it against the verified bundle (see `integrity`) and
+//! starts it in a second QuickJS runtime on a thread of its own, so heavy computation
+//! doesn't block rendering and events. The two sides talk like web workers:
+//! `worker.postMessage(data)` calls the worker's `onmessage` with
+//! `{ data }`, and the worker's `postMessage` calls `worker.onmessage` back.
+//!
+//! Messages are copied as JSON, through `values`. Messages from workers are
+//! queued for the runtime, which wakes and calls `onmessage` on the UI
+//! thread, like `rasen.process` callbacks; the worker objects stay on the JS
+//! side in a table keyed by worker ID until then.
+//!
+//! A worker gets `console`, timers, `structuredClone`, `URL`, the built-in
+//! modules, `.wasm` imports and an endpoint on `rasen.bus` (see `bus`), but
+//! no other `rasen` APIs and no UI, and is
+//! hardened like the runtime that started it. It runs until `terminate()`,
+//! its own `close()`, or the end of the runtime that started it.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+use rquickjs::function::This;
+use rquickjs::{Context, Ctx, Exception, Function, Object, Runtime, Value};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use crate::config::JsxConfig;
+use crate::files::Sandbox;
+use crate::timers::{self, Timers};
+use crate::{builtins, bus, console, integrity, js_runtime, typescript, urls, values, wasm};
+
+/// Global holding the `Worker` objects by worker ID
+const WORKERS: &str = "__workers";
+
+/// A worker's module registry, like the runtime's
+const MODULES: &str = r#"
+var __modules = {};
+function require(name) {
+    if (__modules[name]) return __modules[name];
+    throw new Error('Module not found: ' + name);
+}
+"#;
+
+/// The `Worker` class, wrapping the native functions on `__worker`
+const CLASS: &str = r#"
+(function() {
+    var native = globalThis.__worker;
+    function Worker(path) {
+        if (!(this instanceof Worker)) throw new TypeError("Worker must be called with 'new'");
+        this.onmessage = null;
+        this.onerror = null;
+        this._id = native.spawn(String(path));
+        globalThis.__workers[this._id] = this;
+    }
+    Worker.prototype.postMessage = function(data) {
+        native.post(this._id, data);
+    };
+    Worker.prototype.terminate = function() {
+        native.terminate(this._id);
+        delete globalThis.__workers[this._id];
+    };
+    globalThis.Worker = Worker;
+})();
+"#;
+
+/// What a worker sent back, waiting for delivery to JS
+#[derive(Debug, PartialEq)]
+enum WorkerEvent {
+    Message(u32, Json),
+    /// An uncaught error, passed to `onerror`
+    Error(u32, String),
+}
+
+/// A runtime's workers; clones share them
+#[derive(Clone)]
+pub struct Workers {
+    state: Arc<Mutex<WorkerState>>,
+    wake: UnboundedSender<()>,
+}
+
+#[derive(Default)]
+struct WorkerState {
+    next_id: u32,
+    events: Vec<WorkerEvent>,
+    /// Running workers by ID
+    running: HashMap<u32, Handle>,
+}
+
+/// What wakes a worker's loop
+enum Incoming {
+    /// `postMessage` data from the runtime that started it
+    Message(Json),
+    /// Messages for its bus endpoint
+    Bus,
+    /// `terminate()`, which set the flag the loop checks
+    Terminate,
+}
+
+/// The runtime's end of a worker
+struct Handle {
+    messages: Sender<Incoming>,
+    /// Set by `terminate()`; interrupts the worker's JS and ends its loop
+    terminated: Arc<AtomicBool>,
+}
+
+impl Workers {
+    /// Create an empty set; the receiver wakes when a worker posts a message
+    pub fn new() -> (Self, UnboundedReceiver<()>) {
+        let (wake, wakeups) = unbounded();
+        (Self { state: Arc::default(), wake }, wakeups)
+    }
+
+    fn push(&self, event: WorkerEvent) {
+        self.state.lock().unwrap().events.push(event);
+        let _ = self.wake.unbounded_send(());
+    }
+
+    /// Start `source` in a new worker, hardened or not; returns its ID
+    fn spawn(&self, source: String, script_dir: PathBuf, hardened: bool) -> u32 {
+        let (messages, inbox) = mpsc::channel();
+        let bus_wakes = messages.clone();
+        let terminated = Arc::new(AtomicBool::new(false));
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            // IDs start at 1, like timers
+            state.next_id += 1;
+            let handle = Handle { messages, terminated: terminated.clone() };
+            state.running.insert(state.next_id, handle);
+            state.next_id
+        };
+        let workers = self.clone();
+        thread::spawn(move || run(id, &source, &script_dir, hardened, (inbox, bus_wakes), terminated, &workers));
+        id
+    }
+
+    fn post(&self, id: u32, data: Json) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(handle) = state.running.get(&id) {
+            if handle.messages.send(Incoming::Message(data)).is_err() {
+                // The worker closed itself
+                state.running.remove(&id);
+            }
+        }
+    }
+
+    fn terminate(&self, id: u32) {
+        if let Some(handle) = self.state.lock().unwrap().running.remove(&id) {
+            handle.terminate();
+        }
+    }
+
+    /// Stop every worker, when their runtime goes away
+    pub fn terminate_all(&self) {
+        for (_, handle) in self.state.lock().unwrap().running.drain() {
+            handle.terminate();
+        }
+    }
+}
+
+impl Handle {
+    fn terminate(&self) {
+        self.terminated.store(true, Ordering::Relaxed);
+        let _ = self.messages.send(Incoming::Terminate);
+    }
+}
+
+/// The message of the exception `ctx` is throwing
+fn caught(ctx: &Ctx) -> String {
+    let exception = ctx.catch();
+    match exception.as_exception() {
+        Some(exception) => exception.message().unwrap_or_default(),
+        None => format!("{:?}", exception),
+    }
+}
+
+/// Install what a worker's global scope has
+fn install_worker<'js>(
+    ctx: &Ctx<'js>,
+    id: u32,
+    bus_id: bus::RuntimeId,
+    timers: &Timers,
+    workers: &Workers,
+    closed: &Arc<AtomicBool>,
+) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>(MODULES)?;
+    console::install(ctx)?;
+    builtins::install(ctx)?;
+    urls::install(ctx)?;
+    values::install(ctx)?;
+    wasm::install(ctx)?;
+    timers::install(ctx, timers)?;
+
+    let globals = ctx.globals();
+    globals.set("self", globals.clone())?;
+    let rasen = Object::new(ctx.clone())?;
+    bus::install(ctx, &rasen, bus_id)?;
+    globals.set("rasen", rasen)?;
+    let posted = workers.clone();
+    globals.set(
+        "postMessage",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, data: Value<'js>| -> rquickjs::Result<()> {
+            posted.push(WorkerEvent::Message(id, values::to_json(&ctx, &data)?));
+            Ok(())
+        })?,
+    )?;
+    let closed = closed.clone();
+    globals.set("close", Function::new(ctx.clone(), move || closed.store(true, Ordering::Relaxed))?)?;
+    Ok(())
+}
+
+/// Call the worker's `onmessage` with `data`
+fn receive(ctx: &Ctx, data: &Json) -> rquickjs::Result<()> {
+    let Ok(handler) = ctx.globals().get::<_, Function>("onmessage") else {
+        return Ok(());
+    };
+    let event = Object::new(ctx.clone())?;
+    event.set("data", values::from_json(ctx, data)?)?;
+    let result: Value = handler.call((This(ctx.globals()), event))?;
+    js_runtime::report_rejection(ctx, &result, "async worker onmessage".to_string());
+    Ok(())
+}
+
+/// A worker's thread: run the script, then its timers and messages
+///
+/// `inbox` comes with a sender for waking it when bus messages arrive.
+fn run(
+    id: u32,
+    source: &str,
+    script_dir: &Path,
+    hardened: bool,
+    (inbox, bus_wakes): (Receiver<Incoming>, Sender<Incoming>),
+    terminated: Arc<AtomicBool>,
+    workers: &Workers,
+) {
+    let fail = |message: String| workers.push(WorkerEvent::Error(id, message));
+    let Ok(runtime) = Runtime::new() else {
+        return fail("Can't create a JS runtime for the worker".to_string());
+    };
+    let stop = terminated.clone();
+    runtime.set_interrupt_handler(Some(Box::new(move || stop.load(Ordering::Relaxed))));
+    let Ok(context) = Context::full(&runtime) else {
+        return fail("Can't create a JS context for the worker".to_string());
+    };
+    // Timers are run by the loop below, which checks them after every call
+    let (timers, _wakeups) = Timers::new();
+    let closed = Arc::new(AtomicBool::new(false));
+    // Bus wakeups become inbox entries; the forwarding ends with the endpoint
+    let (bus_id, mut bus_wakeups) = bus::register();
+    thread::spawn(move || {
+        while futures::executor::block_on(bus_wakeups.next()).is_some() {
+            if bus_wakes.send(Incoming::Bus).is_err() {
+                break;
+            }
+        }
+    });
+
+    let source = js_runtime::transform_imports(source, script_dir);
+    context.with(|ctx| {
+        let installed = install_worker(&ctx, id, bus_id, &timers, workers, &closed)
+            .and_then(|()| if hardened { ctx.eval::<(), _>(js_runtime::HARDENING_SHIM) } else { Ok(()) });
+        if let Err(e) = installed {
+            fail(format!("Can't set up the worker: {:?}", e));
+        } else if ctx.eval::<(), _>(source).is_err() {
+            fail(caught(&ctx));
+        }
+    });
+
+    loop {
+        loop {
+            match runtime.execute_pending_job() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(failed) => fail(failed.0.with(|ctx| caught(&ctx))),
+            }
+        }
+        if context.with(|ctx| timers.fire(&ctx)) {
+            continue;
+        }
+        if closed.load(Ordering::Relaxed) || terminated.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // `terminate()` sends a wakeup after setting its flag
+        let incoming = match timers.next_due() {
+            Some(due) => match inbox.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                Ok(incoming) => incoming,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match inbox.recv() {
+                Ok(incoming) => incoming,
+                Err(_) => break,
+            },
+        };
+        context.with(|ctx| match incoming {
+            Incoming::Message(data) => {
+                if receive(&ctx, &data).is_err() {
+                    fail(caught(&ctx));
+                }
+            }
+            Incoming::Bus => {
+                bus::deliver(&ctx, bus_id);
+            }
+            Incoming::Terminate => {}
+        });
+    }
+    bus::unregister(bus_id);
+}
+
+/// Call `onmessage` and `onerror` for what workers sent; returns whether any ran
+pub fn deliver(ctx: &Ctx, workers: &Workers) -> bool {
+    let events = std::mem::take(&mut workers.state.lock().unwrap().events);
+    let Ok(objects) = ctx.globals().get::<_, Object>(WORKERS) else {
+        return false;
+    };
+
+    let mut delivered = false;
+    for event in events {
+        let (WorkerEvent::Message(id, _) | WorkerEvent::Error(id, _)) = event;
+        // Terminated since
+        let Ok(worker) = objects.get::<_, Object>(id.to_string()) else {
+            continue;
+        };
+        let result = (|| -> rquickjs::Result<()> {
+            let payload = Object::new(ctx.clone())?;
+            let name = match event {
+                WorkerEvent::Message(_, data) => {
+                    payload.set("data", values::from_json(ctx, &data)?)?;
+                    "onmessage"
+                }
+                WorkerEvent::Error(_, ref message) => {
+                    payload.set("message", message.as_str())?;
+                    "onerror"
+                }
+            };
+            match (worker.get::<_, Option<Function>>(name)?, event) {
+                (Some(handler), _) => {
+                    let result: Value = handler.call((This(worker.clone()), payload))?;
+                    js_runtime::report_rejection(ctx, &result, format!("async worker {} {}", id, name));
+                }
+                (None, WorkerEvent::Error(_, message)) => eprintln!("Error in worker {}: {}", id, message),
+                (None, WorkerEvent::Message(..)) => {}
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            eprintln!("Error in worker {} callback: {:?}", id, e);
+        }
+        delivered = true;
+    }
+    delivered
+}
+
+/// Install the `Worker` class for a runtime's workers, with worker scripts
+/// inside `script_dir`
+pub fn install<'js>(ctx: &Ctx<'js>, workers: &Workers, script_dir: &Path, hardened: bool) -> rquickjs::Result<()> {
+    ctx.globals().set(WORKERS, Object::new(ctx.clone())?)?;
+    let native = Object::new(ctx.clone())?;
+
+    let spawned = workers.clone();
+    let dir = script_dir.to_path_buf();
+    let sandbox = Sandbox::within(script_dir);
+    native.set(
+        "spawn",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, path: String| -> rquickjs::Result<u32> {
+            let file = sandbox.check_in(&path, &dir).map_err(|_| {
+                Exception::throw_message(&ctx, &format!("Worker {} is outside {}", path, dir.display()))
+            })?;
+            let source = std::fs::read_to_string(&file)
+                .map_err(|e| Exception::throw_message(&ctx, &format!("Can't read worker {}: {}", file.display(), e)))?;
+            integrity::check(&file, source.as_bytes()).map_err(|message| Exception::throw_message(&ctx, &message))?;
+            let source = if typescript::needs_compile(&file) {
+                typescript::compile(&source, &file, &JsxConfig::default()).map_err(|errors| {
+                    let errors: Vec<String> = errors.iter().map(|error| format!("\n  {}", error)).collect();
+                    Exception::throw_syntax(&ctx, &format!("Can't compile worker {}:{}", file.display(), errors.concat()))
+                })?
+            } else {
+                source
+            };
+            let worker_dir = file.parent().map(Path::to_path_buf).unwrap_or_else(|| dir.clone());
+            Ok(spawned.spawn(source, worker_dir, hardened))
+        })?,
+    )?;
+    let posted = workers.clone();
+    native.set(
+        "post",
+        Function::new(ctx.clone(), move |ctx: Ctx<'js>, id: u32, data: Value<'js>| -> rquickjs::Result<()> {
+            posted.post(id, values::to_json(&ctx, &data)?);
+            Ok(())
+        })?,
+    )?;
+    let terminated = workers.clone();
+    native.set(
+        "terminate",
+        Function::new(ctx.clone(), move |id: u32| terminated.terminate(id))?,
+    )?;
+    ctx.globals().set("__worker", native)?;
+    ctx.eval::<(), _>(CLASS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Wait for the events of the workers
+    fn events(workers: &Workers) -> Vec<WorkerEvent> {
+        let started = Instant::now();
+        while workers.state.lock().unwrap().events.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        std::mem::take(&mut workers.state.lock().unwrap().events)
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let (workers, _wakeups) = Workers::new();
+        let source = "onmessage = (event) => setTimeout(() => postMessage({ doubled: event.data.n * 2 }), 1)";
+        let id = workers.spawn(source.to_string(), PathBuf::new(), false);
+        workers.post(id, json!({ "n": 21 }));
+        assert_eq!(events(&workers), vec![WorkerEvent::Message(id, json!({ "doubled": 42 }))]);
+        workers.terminate_all();
+    }
+
+    #[test]
+    fn test_bus_round_trip() {
+        let runtime = Runtime::new().unwrap();
+        let context = Context::full(&runtime).unwrap();
+        let (main_id, _wakeups) = bus::register();
+        context.with(|ctx| {
+            let rasen = Object::new(ctx.clone()).unwrap();
+            bus::install(&ctx, &rasen, main_id).unwrap();
+            ctx.globals().set("rasen", rasen).unwrap();
+            ctx.eval::<(), _>(
+                "rasen.bus.on('test-worker-ready', () => rasen.bus.emit('test-worker-ping', 21));
+                 rasen.bus.on('test-worker-pong', (n) => { globalThis.pong = n })",
+            )
+            .unwrap();
+        });
+
+        let (workers, _wakeups) = Workers::new();
+        let source = "rasen.bus.on('test-worker-ping', (n) => rasen.bus.emit('test-worker-pong', n * 2));
+                      rasen.bus.emit('test-worker-ready')";
+        workers.spawn(source.to_string(), PathBuf::new(), false);
+
+        let started = Instant::now();
+        let mut pong = None;
+        while pong.is_none() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+            pong = context.with(|ctx| {
+                bus::deliver(&ctx, main_id);
+                ctx.globals().get::<_, Option<f64>>("pong").unwrap()
+            });
+        }
+        assert_eq!(pong, Some(42.0));
+        workers.terminate_all();
+        bus::unregister(main_id);
+    }
+
+    #[test]
+    fn test_hardened_worker_cant_eval() {
+        let (workers, _wakeups) = Workers::new();
+        let source = "try { eval('1'); postMessage('ran') } catch (e) { postMessage(e.name) }";
+        let id = workers.spawn(source.to_string(), PathBuf::new(), true);
+        assert_eq!(events(&workers), vec![WorkerEvent::Message(id, json!("EvalError"))]);
+        workers.terminate_all();
+    }
+}
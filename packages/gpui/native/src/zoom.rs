@@ -0,0 +1,75 @@
+//! Window content zoom
+//!
+//! Works like browser zoom: the tree is laid out for a viewport that is
+//! smaller (or larger) by the zoom factor, so breakpoints and `vw`/`vh`
+//! re-resolve, and every pixel length is then scaled back up when applied.
+//! Rem-based lengths and default text follow through the window's rem size.
+
+use gpui::*;
+use rquickjs::{Ctx, Function, Object};
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::native_api;
+
+actions!(rasen, [ZoomIn, ZoomOut, ResetZoom]);
+
+/// Zoom steps used by the keyboard shortcuts
+const LEVELS: [f32; 13] = [0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+const MIN: f32 = 0.25;
+const MAX: f32 = 5.0;
+
+/// Current zoom factor as f32 bits
+static ZOOM: AtomicU32 = AtomicU32::new(0x3f80_0000); // 1.0
+
+/// Current zoom factor
+pub fn get() -> f32 {
+    f32::from_bits(ZOOM.load(Ordering::Relaxed))
+}
+
+/// Set the zoom factor, clamped to 25%–500%
+pub fn set(factor: f32) {
+    let factor = if factor.is_finite() { factor.clamp(MIN, MAX) } else { 1.0 };
+    ZOOM.store(factor.to_bits(), Ordering::Relaxed);
+}
+
+/// Move to the next zoom level up or down
+fn step(up: bool) {
+    let current = get();
+    let next = if up {
+        LEVELS.iter().copied().find(|level| *level > current + 0.001).unwrap_or(LEVELS[LEVELS.len() - 1])
+    } else {
+        LEVELS.iter().rev().copied().find(|level| *level < current - 0.001).unwrap_or(LEVELS[0])
+    };
+    set(next);
+}
+
+/// Bind the zoom shortcuts: Cmd+= / Cmd+- / Cmd+0 (Ctrl on Windows and Linux)
+pub fn init(cx: &mut App) {
+    cx.on_action(|_: &ZoomIn, cx| {
+        step(true);
+        cx.refresh_windows();
+    });
+    cx.on_action(|_: &ZoomOut, cx| {
+        step(false);
+        cx.refresh_windows();
+    });
+    cx.on_action(|_: &ResetZoom, cx| {
+        set(1.0);
+        cx.refresh_windows();
+    });
+    
+    let modifier = if cfg!(target_os = "macos") { "cmd" } else { "ctrl" };
+    cx.bind_keys([
+        KeyBinding::new(&format!("{}-=", modifier), ZoomIn, None),
+        KeyBinding::new(&format!("{}-+", modifier), ZoomIn, None),
+        KeyBinding::new(&format!("{}--", modifier), ZoomOut, None),
+        KeyBinding::new(&format!("{}-0", modifier), ResetZoom, None),
+    ]);
+}
+
+/// Install `rasen.window.setZoom` and `rasen.window.getZoom`
+pub fn install<'js>(ctx: &Ctx<'js>, rasen: &Object<'js>) -> rquickjs::Result<()> {
+    let window = native_api::namespace(ctx, rasen, "window")?;
+    window.set("setZoom", Function::new(ctx.clone(), |factor: f64| set(factor as f32))?)?;
+    window.set("getZoom", Function::new(ctx.clone(), || get() as f64)?)?;
+    Ok(())
+}
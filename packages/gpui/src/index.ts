@@ -10,7 +10,8 @@
  * Users choose their own reactivity (e.g., @rasenjs/reactive-signals).
  */
 
-import type { SyncComponent, PropValue, Mountable, Ref } from '@rasenjs/core'
+import { getReactiveRuntime } from '@rasenjs/core'
+import type { SyncComponent, PropValue, Mountable, Ref, ReadonlyRef, ReactiveRuntime } from '@rasenjs/core'
 
 // ============ GPUI Host Type ============
 
@@ -30,25 +31,257 @@ export interface GpuiHost {
  * Element descriptor passed to the native runtime
  */
 export interface ElementDescriptor {
-  type: 'div' | 'text'
+  /**
+   * `empty` holds the place of a child `show()` left out; `skip` stands for
+   * what a `memo()` rendered last time
+   */
+  type: 'div' | 'text' | 'empty' | 'skip'
   class: string
   text?: string
+  /** Stable identifier for automation (`click { testId }`) */
+  testId?: string
+  /** Identity among siblings, kept when the list is reordered */
+  key?: string
   children?: ElementDescriptor[]
   handlers?: Record<string, () => void>
+  /** Throttle or debounce intervals of handlers, in milliseconds */
+  rates?: Record<string, { throttle?: number; debounce?: number }>
+  /** Key bindings of a `keymap` element */
+  keymap?: { scope: string; bindings: Record<string, (() => void) | null> }
+  /** Drags the enclosing `sortableList` item */
+  dragHandle?: boolean
+  /** Current value of a `colorPicker` element */
+  colorPicker?: { value: string }
+  /** Props of a `datePicker` element, with dates as `YYYY-MM-DD` */
+  datePicker?: {
+    value?: string
+    min?: string
+    max?: string
+    locale?: string
+    today: string
+    placeholder?: string
+  }
+  /** Visible rows of a `tree` element */
+  tree?: { rows: TreeRow[]; selected: string | null }
+  /** Keys of a `selectable` element's children, in order, and the selected ones */
+  selectable?: { keys: string[]; selected: string[] }
+  /** ID of the `elementRef()` measuring this element */
+  ref?: string
+  /** Props of an `img` element: a `rasen.image` handle ID or a file path */
+  image?: { id?: number; path?: string; fit?: string }
+  /** Props of a `camera` element */
+  camera?: { deviceId?: string; fit?: string }
+  /** Props of a `shader` element, with the uniforms' current values */
+  shader?: { fragment: string; uniforms: Record<string, number | number[]>; animate: boolean }
+}
+
+/**
+ * A visible `tree` node, flattened for the native side
+ */
+export interface TreeRow {
+  id: string
+  label: string
+  depth: number
+  expandable: boolean
+  expanded: boolean
+  loading: boolean
 }
 
 // ============ Component Props ============
 
+/**
+ * Event passed to mouse handlers
+ */
+export interface GpuiMouseEvent {
+  /** Pointer position in window coordinates */
+  x: number
+  y: number
+  /** Button pressed or released; absent on moves without a button held */
+  button?: 'left' | 'right' | 'middle' | 'back' | 'forward'
+  shiftKey: boolean
+  ctrlKey: boolean
+  altKey: boolean
+  /** Cmd on macOS, the Windows key elsewhere */
+  metaKey: boolean
+  /**
+   * Send the following moves and the button release to this element's
+   * handlers, even outside it, until `releasePointer()` or the release
+   */
+  capturePointer(): void
+  releasePointer(): void
+}
+
+/**
+ * A handler the runtime calls at most once per `throttle` ms, or once
+ * events stop for `debounce` ms, with the latest event
+ */
+export interface RateLimited<F> {
+  handler: F
+  throttle?: number
+  debounce?: number
+}
+
 export interface DivProps {
   class?: PropValue<string>
+  testId?: string
+  /** Identity among siblings: keyed elements keep their state when a list is reordered */
+  key?: string | number
   onClick?: () => void
   onMouseEnter?: () => void
   onMouseLeave?: () => void
+  onMouseDown?: (event: GpuiMouseEvent) => void
+  onMouseMove?: ((event: GpuiMouseEvent) => void) | RateLimited<(event: GpuiMouseEvent) => void>
+  onMouseUp?: (event: GpuiMouseEvent) => void
+  /** Cut while this element or one inside it has focus; return the text cut for the clipboard */
+  onCut?: () => string | undefined
+  /** Copy while this element or one inside it has focus; return the text for the clipboard */
+  onCopy?: () => string | undefined
+  /** Paste while this element or one inside it has focus, with the clipboard's text */
+  onPaste?: (text: string) => void
+  /** Select All while this element or one inside it has focus */
+  onSelectAll?: () => void
+  /** Measure this element with an `elementRef()` */
+  ref?: ElementRef
   children?: Mountable<GpuiHost>[]
 }
 
+export interface KeymapProps extends DivProps {
+  /** Name of the scope, for diagnostics */
+  scope: string
+  /**
+   * Handlers by keystroke (`cmd-s`, `ctrl-shift-p`, `escape`); `null` stops
+   * a key from reaching the keymaps around this one
+   */
+  bindings: Record<string, (() => void) | null>
+}
+
+export interface SortableListProps<T> {
+  class?: PropValue<string>
+  testId?: string
+  items: PropValue<T[]>
+  /** Render one item; put a `dragHandle` in it to drag by the handle only */
+  render: (item: T, index: number) => Mountable<GpuiHost>
+  /** Called on drop with the item's old index and its index after the move */
+  onReorder: (from: number, to: number) => void
+}
+
+export interface SelectableProps<T> {
+  class?: PropValue<string>
+  testId?: string
+  items: PropValue<T[]>
+  /** Unique key of an item, as reported in the selection */
+  key: (item: T) => string
+  /** Render one item; `selected` tells whether to draw it as selected */
+  render: (item: T, selected: boolean) => Mountable<GpuiHost>
+  /** Keys of the selected items */
+  selected: PropValue<string[]>
+  /** Called with the selected keys, in item order, as they change */
+  onSelectionChange: (keys: string[]) => void
+}
+
+export interface ImgProps {
+  class?: PropValue<string>
+  testId?: string
+  /** A `rasen.image` handle, or the path of an image file */
+  src: PropValue<ImageHandle | string>
+  /** How the image fills the element, as CSS `object-fit`; `contain` by default */
+  fit?: 'contain' | 'cover' | 'fill' | 'none' | 'scale-down'
+}
+
+export interface CameraProps {
+  class?: PropValue<string>
+  testId?: string
+  /** A device from `rasen.camera.list()`; the default camera if left out */
+  deviceId?: PropValue<string>
+  /** How frames fill the element, as CSS `object-fit`; `contain` by default */
+  fit?: 'contain' | 'cover' | 'fill' | 'none' | 'scale-down'
+}
+
+export interface ShaderProps {
+  class?: PropValue<string>
+  testId?: string
+  /** WGSL defining `fn fragment(position: vec2<f32>) -> vec4<f32>` */
+  fragment: PropValue<string>
+  /**
+   * Values the fragment reads as `uniforms.<name>`: a number is an `f32`,
+   * an array of two to four numbers a `vec2<f32>` to `vec4<f32>`
+   */
+  uniforms?: PropValue<Record<string, PropValue<number | ArrayLike<number>>>>
+  /** Draw on every frame, advancing `uniforms.time` */
+  animate?: boolean
+}
+
+export interface ColorPickerProps {
+  class?: PropValue<string>
+  testId?: string
+  /** Current color: hex, `rgb()`, `hsl()` or a named color */
+  value: PropValue<string>
+  /** Called with the picked color as `#rrggbb`, usable in `bg-[...]` classes */
+  onChange: (value: string) => void
+}
+
+export interface DatePickerProps {
+  class?: PropValue<string>
+  testId?: string
+  /** Selected date as `YYYY-MM-DD`, or empty for none */
+  value: PropValue<string | null | undefined>
+  /** Called with the picked date as `YYYY-MM-DD` */
+  onChange: (value: string) => void
+  /** BCP 47 locale for labels and the first day of the week; the user's by default */
+  locale?: string
+  /** Earliest date that can be picked, as `YYYY-MM-DD` */
+  min?: PropValue<string | undefined>
+  /** Latest date that can be picked, as `YYYY-MM-DD` */
+  max?: PropValue<string | undefined>
+  /** Field text while no date is selected */
+  placeholder?: string
+}
+
+export interface TreeNode {
+  /** Unique among all nodes of the tree */
+  id: string
+  label: string
+  /** Known children; leave out and set `hasChildren` to load them on expand */
+  children?: TreeNode[]
+  /** The node has children that `loadChildren` provides */
+  hasChildren?: boolean
+}
+
+export interface TreeProps {
+  /** Give the tree a height (`h-96`, `flex-1`): only the rows in view are drawn */
+  class?: PropValue<string>
+  testId?: string
+  roots: PropValue<TreeNode[]>
+  /** Children of a `hasChildren` node, loaded the first time it's expanded */
+  loadChildren?: (node: TreeNode) => TreeNode[] | Promise<TreeNode[]>
+  onSelect?: (node: TreeNode) => void
+}
+
+export interface FileFilter {
+  /** Shown to users, e.g. `Images` */
+  name: string
+  /** Extensions without the dot, e.g. `['png', 'jpg']` */
+  extensions: string[]
+}
+
+export interface FilePickerProps {
+  /** Give the picker a height (`h-96`, `flex-1`) for its list to scroll */
+  class?: PropValue<string>
+  testId?: string
+  /** Directory to browse; the breadcrumbs don't go above it */
+  root: string
+  /** Only show files matching one of these; directories always show */
+  filters?: FileFilter[]
+  /** Show names starting with a dot */
+  showHidden?: boolean
+  /** Called with the path of a file when it's selected */
+  onSelect?: (path: string) => void
+}
+
 export interface TextProps {
   class?: PropValue<string>
+  testId?: string
+  key?: string | number
   children: PropValue<string | number>
 }
 
@@ -101,6 +334,9 @@ export const div: SyncComponent<GpuiHost, [DivProps]> = (props) => {
     
     const cleanups: (() => void)[] = []
     
+    if (props.testId) descriptor.testId = props.testId
+    if (props.key !== undefined) descriptor.key = String(props.key)
+    
     if (props.onClick) {
       descriptor.handlers!.click = props.onClick
       cleanups.push(host.on('click', props.onClick))
@@ -113,6 +349,20 @@ export const div: SyncComponent<GpuiHost, [DivProps]> = (props) => {
       descriptor.handlers!.mouseleave = props.onMouseLeave
       cleanups.push(host.on('mouseleave', props.onMouseLeave))
     }
+    if (props.onMouseDown) descriptor.handlers!.mousedown = props.onMouseDown as () => void
+    if (typeof props.onMouseMove === 'function') {
+      descriptor.handlers!.mousemove = props.onMouseMove as () => void
+    } else if (props.onMouseMove) {
+      const { handler, throttle, debounce } = props.onMouseMove
+      descriptor.handlers!.mousemove = handler as () => void
+      descriptor.rates = { mousemove: { throttle, debounce } }
+    }
+    if (props.onMouseUp) descriptor.handlers!.mouseup = props.onMouseUp as () => void
+    if (props.onCut) descriptor.handlers!.cut = props.onCut as () => void
+    if (props.onCopy) descriptor.handlers!.copy = props.onCopy as () => void
+    if (props.onPaste) descriptor.handlers!.paste = props.onPaste as () => void
+    if (props.onSelectAll) descriptor.handlers!.selectall = props.onSelectAll
+    if (props.ref) descriptor.ref = props.ref.id
     
     // Mount children into this descriptor's children array
     if (props.children) {
@@ -148,6 +398,8 @@ export const text: SyncComponent<GpuiHost, [TextProps]> = (props) => {
       class: unrefValue(props.class) || '',
       text: String(unrefValue(props.children)),
     }
+    if (props.testId) descriptor.testId = props.testId
+    if (props.key !== undefined) descriptor.key = String(props.key)
     
     host.appendChild(descriptor)
     
@@ -174,6 +426,9 @@ export const button: SyncComponent<GpuiHost, [DivProps & { label?: PropValue<str
     
     const cleanups: (() => void)[] = []
     
+    if (props.testId) descriptor.testId = props.testId
+    if (props.key !== undefined) descriptor.key = String(props.key)
+    
     if (props.onClick) {
       descriptor.handlers!.click = props.onClick
       cleanups.push(host.on('click', props.onClick))
@@ -196,6 +451,718 @@ export const button: SyncComponent<GpuiHost, [DivProps & { label?: PropValue<str
   }
 }
 
+/**
+ * keymap - Container whose key bindings are active while it is mounted
+ *
+ * The innermost keymap binding a key wins; keys it doesn't bind fall through
+ * to the keymaps around it.
+ */
+export const keymap: SyncComponent<GpuiHost, [KeymapProps]> = (props) => {
+  const { scope, bindings, ...divProps } = props
+  const mountDiv = div(divProps)
+  return (host: GpuiHost) => {
+    // Tag the div's descriptor as it's appended
+    const keymapHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.keymap = { scope, bindings }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(keymapHost)
+  }
+}
+
+/**
+ * sortableList - List whose items can be reordered by dragging
+ *
+ * Items run along the list's flex direction (`flex-col` or `flex-row`), and
+ * the list scrolls while an item is dragged near its edges.
+ */
+export function sortableList<T>(props: SortableListProps<T>): Mountable<GpuiHost> {
+  return (host: GpuiHost) => {
+    const items = unrefValue(props.items) || []
+    const mountDiv = div({
+      class: props.class,
+      testId: props.testId,
+      children: items.map((item, index) => props.render(item, index)),
+    })
+    // Tag the div's descriptor as it's appended
+    const listHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.handlers = { ...element.handlers, reorder: props.onReorder as () => void }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(listHost)
+  }
+}
+
+/**
+ * selectable - Container whose items are selected by dragging a marquee
+ *
+ * Items are hit-tested natively against where they were last drawn.
+ * Shift, Ctrl or Cmd add to the selection; a click selects a single item.
+ */
+export function selectable<T>(props: SelectableProps<T>): Mountable<GpuiHost> {
+  return (host: GpuiHost) => {
+    const items = unrefValue(props.items) || []
+    const selected = unrefValue(props.selected) || []
+    const keys = items.map(props.key)
+    const mountDiv = div({
+      class: props.class,
+      testId: props.testId,
+      children: items.map((item, index) => props.render(item, selected.includes(keys[index]))),
+    })
+    const selectableHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.selectable = { keys, selected }
+        element.handlers = {
+          ...element.handlers,
+          selectionchange: props.onSelectionChange as unknown as () => void,
+        }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(selectableHost)
+  }
+}
+
+/**
+ * dragHandle - Part of a `sortableList` item that starts dragging it
+ */
+export const dragHandle: SyncComponent<GpuiHost, [DivProps]> = (props) => {
+  const mountDiv = div(props)
+  return (host: GpuiHost) => {
+    const handleHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.dragHandle = true
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(handleHost)
+  }
+}
+
+/**
+ * img - Image from `rasen.image` or a file, scaled to the element's size
+ */
+export const img: SyncComponent<GpuiHost, [ImgProps]> = (props) => {
+  const mountDiv = div({ class: props.class, testId: props.testId })
+  return (host: GpuiHost) => {
+    const imageHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        const src = unrefValue(props.src)
+        element.image = typeof src === 'string' ? { path: src, fit: props.fit } : { id: src.id, fit: props.fit }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(imageHost)
+  }
+}
+
+/**
+ * camera - Live webcam frames, scaled to the element's size
+ */
+export const camera: SyncComponent<GpuiHost, [CameraProps]> & {
+  /** PNG bytes of the next frame; see `rasen.camera.snapshot` */
+  snapshot(deviceId?: string): Promise<Uint8Array>
+} = Object.assign(
+  (props: CameraProps) => {
+    const mountDiv = div({ class: props.class, testId: props.testId })
+    return (host: GpuiHost) => {
+      const cameraHost: GpuiHost = {
+        ...host,
+        appendChild(element: ElementDescriptor) {
+          element.camera = { deviceId: unrefValue(props.deviceId), fit: props.fit }
+          host.appendChild(element)
+        },
+      }
+      return mountDiv(cameraHost)
+    }
+  },
+  { snapshot: (deviceId?: string) => rasen.camera.snapshot(deviceId) },
+)
+
+/**
+ * shader - A WGSL fragment shader drawn over the element's bounds; check
+ * `rasen.platform.supports('shaders')` first
+ */
+export const shader: SyncComponent<GpuiHost, [ShaderProps]> = (props) => {
+  const mountDiv = div({ class: props.class, testId: props.testId })
+  return (host: GpuiHost) => {
+    const shaderHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        const uniforms: Record<string, number | number[]> = {}
+        for (const [name, uniform] of Object.entries(unrefValue(props.uniforms) ?? {})) {
+          const value = unrefValue(uniform)
+          uniforms[name] = typeof value === 'number' ? value : Array.from(value)
+        }
+        element.shader = { fragment: unrefValue(props.fragment), uniforms, animate: !!props.animate }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(shaderHost)
+  }
+}
+
+/**
+ * colorPicker - Saturation/brightness square, hue bar and hex field
+ */
+export const colorPicker: SyncComponent<GpuiHost, [ColorPickerProps]> = (props) => {
+  const mountDiv = div({ class: props.class, testId: props.testId })
+  return (host: GpuiHost) => {
+    const pickerHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.colorPicker = { value: unrefValue(props.value) || '' }
+        element.handlers = { ...element.handlers, change: props.onChange as () => void }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(pickerHost)
+  }
+}
+
+/**
+ * datePicker - Date field with a calendar popup
+ */
+export const datePicker: SyncComponent<GpuiHost, [DatePickerProps]> = (props) => {
+  const mountDiv = div({ class: props.class, testId: props.testId })
+  return (host: GpuiHost) => {
+    const pickerHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.datePicker = {
+          value: unrefValue(props.value) || undefined,
+          min: unrefValue(props.min),
+          max: unrefValue(props.max),
+          locale: props.locale,
+          // Native code has no time zone database, so JS supplies today
+          today: localDate(new Date()),
+          placeholder: props.placeholder,
+        }
+        element.handlers = { ...element.handlers, change: props.onChange as () => void }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(pickerHost)
+  }
+}
+
+/**
+ * tree - Expandable tree of nodes, with lazily loaded children
+ *
+ * Expansion, loaded children and the selection are kept across renders.
+ * Arrow keys navigate the tree after it's clicked.
+ */
+export function tree(props: TreeProps): Mountable<GpuiHost> {
+  const expanded = new Set<string>()
+  const loaded = new Map<string, TreeNode[]>()
+  const loading = new Set<string>()
+  const nodes = new Map<string, TreeNode>()
+  let selected: string | null = null
+  
+  const children = (node: TreeNode) => node.children ?? loaded.get(node.id)
+  
+  const toggle = async (id: string) => {
+    const node = nodes.get(id)
+    if (!node) return
+    if (expanded.delete(id)) return
+    expanded.add(id)
+    if (children(node) || !node.hasChildren || !props.loadChildren || loading.has(id)) return
+    loading.add(id)
+    try {
+      // The native side re-renders once the promise settles
+      loaded.set(id, await props.loadChildren(node))
+    } catch (error) {
+      expanded.delete(id)
+      throw error
+    } finally {
+      loading.delete(id)
+    }
+  }
+  
+  const select = (id: string) => {
+    const node = nodes.get(id)
+    if (!node) return
+    selected = id
+    props.onSelect?.(node)
+  }
+  
+  return (host: GpuiHost) => {
+    // Flatten the expanded part of the tree
+    const rows: TreeRow[] = []
+    nodes.clear()
+    const visit = (list: TreeNode[], depth: number) => {
+      for (const node of list) {
+        const known = children(node)
+        const isExpanded = expanded.has(node.id)
+        nodes.set(node.id, node)
+        rows.push({
+          id: node.id,
+          label: node.label,
+          depth,
+          expandable: known ? known.length > 0 : !!node.hasChildren,
+          expanded: isExpanded,
+          loading: loading.has(node.id),
+        })
+        if (isExpanded && known) visit(known, depth + 1)
+      }
+    }
+    visit(unrefValue(props.roots) || [], 0)
+    
+    const mountDiv = div({ class: props.class, testId: props.testId })
+    const treeHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        element.tree = { rows, selected }
+        element.handlers = {
+          ...element.handlers,
+          toggle: toggle as unknown as () => void,
+          select: select as unknown as () => void,
+        }
+        host.appendChild(element)
+      },
+    }
+    return mountDiv(treeHost)
+  }
+}
+
+/**
+ * filePicker - In-window file browser with breadcrumbs
+ *
+ * Lists `root` as a tree whose directories load as they're expanded. The
+ * breadcrumbs lead from `root` to the selection; clicking one lists that
+ * directory on its own.
+ */
+export function filePicker(props: FilePickerProps): Mountable<GpuiHost> {
+  const collator = rasen.collator(undefined, { sensitivity: 'base', numeric: true })
+  const rootName = props.root.split(/[\\/]/).filter(Boolean).pop() || props.root
+  // Every path listed so far, with its parent directory for the breadcrumbs
+  const entries = new Map<string, { name: string; parent?: string; isDirectory: boolean }>([
+    [props.root, { name: rootName, isDirectory: true }],
+  ])
+  // Listing of `dir`, updated in place so the tree sees changes
+  const roots: TreeNode[] = []
+  let dir = props.root
+  let selected = props.root
+  let error: string | null = null
+  
+  const matches = (name: string) => {
+    if (!props.filters?.length) return true
+    const extension = name.includes('.') ? name.slice(name.lastIndexOf('.') + 1).toLowerCase() : ''
+    return props.filters.some(filter => filter.extensions.some(ext => ext.toLowerCase() === extension))
+  }
+  
+  const list = async (path: string): Promise<TreeNode[]> => {
+    const listed = await rasen.fs.readdir(path)
+    return listed
+      .filter(entry => (props.showHidden || !entry.name.startsWith('.')) && (entry.isDirectory || matches(entry.name)))
+      .sort((a, b) => Number(b.isDirectory) - Number(a.isDirectory) || collator.compare(a.name, b.name))
+      .map(entry => {
+        entries.set(entry.path, { name: entry.name, parent: path, isDirectory: entry.isDirectory })
+        return { id: entry.path, label: entry.name, hasChildren: entry.isDirectory }
+      })
+  }
+  
+  const open = async (path: string) => {
+    try {
+      roots.splice(0, roots.length, ...(await list(path)))
+      dir = selected = path
+      error = null
+    } catch (e) {
+      error = e instanceof Error ? e.message : String(e)
+    }
+  }
+  
+  const listing = tree({
+    class: 'flex-1',
+    roots,
+    loadChildren: node => list(node.id),
+    onSelect: node => {
+      selected = node.id
+      if (!node.hasChildren) props.onSelect?.(node.id)
+    },
+  })
+  open(props.root)
+  
+  return (host: GpuiHost) => {
+    const trail: string[] = []
+    for (let path: string | undefined = selected; path !== undefined; path = entries.get(path)?.parent) {
+      trail.unshift(path)
+    }
+    const crumbs = trail.flatMap((path, index) => {
+      const entry = entries.get(path)!
+      const separator = index > 0 ? [text({ class: 'text-gray-400', children: '›' })] : []
+      const crumb = entry.isDirectory
+        ? button({
+            class: path === dir ? 'font-semibold' : 'text-blue-600',
+            onClick: () => open(path),
+            children: [text({ children: entry.name })],
+          })
+        : text({ children: entry.name })
+      return [...separator, crumb]
+    })
+    
+    return div({
+      class: `flex flex-col ${unrefValue(props.class) || ''}`,
+      testId: props.testId,
+      children: [
+        div({ class: 'flex flex-row items-center gap-1 px-2 py-1 text-sm', children: crumbs }),
+        error ? text({ class: 'p-2 text-red-600 text-sm', children: error }) : listing,
+      ],
+    })(host)
+  }
+}
+
+/**
+ * `YYYY-MM-DD` of a date in local time
+ */
+function localDate(date: Date): string {
+  const pad = (n: number) => String(n).padStart(2, '0')
+  return `${date.getFullYear()}-${pad(date.getMonth() + 1)}-${pad(date.getDate())}`
+}
+
+/**
+ * Copy of `items` with the item at `from` moved to index `to`, for `onReorder`
+ */
+export function moveItem<T>(items: readonly T[], from: number, to: number): T[] {
+  const moved = items.slice()
+  moved.splice(to, 0, ...moved.splice(from, 1))
+  return moved
+}
+
+export interface Point {
+  x: number
+  y: number
+}
+
+export interface Bounds {
+  x: number
+  y: number
+  width: number
+  height: number
+}
+
+/**
+ * Handle on a laid-out div, for converting points between its coordinates
+ * (relative to its top-left corner), the window's and the screen's
+ *
+ * Conversions return `null` until the element has been laid out, and again
+ * once it's no longer rendered.
+ */
+export interface ElementRef {
+  readonly id: string
+  /** Screen point to element coordinates */
+  toLocal(screenX: number, screenY: number): Point | null
+  /** Element point to screen coordinates */
+  toScreen(x: number, y: number): Point | null
+  /** Window point, as in mouse events, to element coordinates */
+  fromWindow(x: number, y: number): Point | null
+  /** Element point to window coordinates */
+  toWindow(x: number, y: number): Point | null
+  /** Position and size in window coordinates */
+  bounds(): Bounds | null
+  /** Scroll the sortable list the element is in to show it, after the next layout */
+  scrollIntoView(): void
+}
+
+let elementRefCounter = 0
+
+/**
+ * Create a ref to pass as a div's `ref` prop; create it in the setup phase
+ * so it stays the same across renders
+ */
+export function elementRef(): ElementRef {
+  const id = `ref${++elementRefCounter}`
+  const convert = (name: 'toLocal' | 'toScreen' | 'fromWindow' | 'toWindow') => (x: number, y: number) => rasen.layout[name](id, x, y)
+  return {
+    id,
+    toLocal: convert('toLocal'),
+    toScreen: convert('toScreen'),
+    fromWindow: convert('fromWindow'),
+    toWindow: convert('toWindow'),
+    bounds: () => rasen.layout.bounds(id),
+    scrollIntoView() {
+      rasen.layout.scrollIntoView(id)
+      // Lays the element out again even if nothing else changed
+      requestRender()
+    },
+  }
+}
+
+// ============ JSX ============
+
+/* eslint-disable @typescript-eslint/no-explicit-any */
+type JsxChild = Mountable<GpuiHost> | PropValue<string | number> | boolean | null | undefined | JsxChild[]
+
+/** Components lowercase JSX tags stand for */
+const intrinsics: Record<string, (props: any) => Mountable<GpuiHost>> = {
+  div,
+  text,
+  button,
+  img,
+  camera,
+  shader,
+  keymap,
+  sortableList,
+  selectable,
+  dragHandle,
+  colorPicker,
+  datePicker,
+  tree,
+  filePicker,
+}
+
+function flattenChildren(children: JsxChild[], out: JsxChild[] = []): JsxChild[] {
+  for (const child of children) {
+    if (Array.isArray(child)) flattenChildren(child, out)
+    // Like React, `{cond && <div />}` renders nothing when false
+    else if (child !== null && child !== undefined && typeof child !== 'boolean') out.push(child)
+  }
+  return out
+}
+
+/** Strings, numbers and refs as text elements */
+function toMountable(child: JsxChild): Mountable<GpuiHost> {
+  return typeof child === 'function' ? (child as Mountable<GpuiHost>) : text({ children: child as PropValue<string> })
+}
+
+/**
+ * h - JSX factory: `<div class="p-4">{count}</div>` compiles to
+ * `h('div', { class: 'p-4' }, count)`
+ *
+ * Lowercase tags are this package's components. Text inside `<text>` is
+ * joined into its content, and refs in it are read when it renders. A
+ * component with an async setup renders nothing, or its `<Suspense>`'s
+ * fallback, until it resolves.
+ */
+export function h(type: string | ((props: any) => Mountable<GpuiHost> | Promise<Mountable<GpuiHost>>), props: Record<string, any> | null, ...children: JsxChild[]): Mountable<GpuiHost> {
+  const component = typeof type === 'string' ? intrinsics[type] : type
+  if (!component) throw new Error(`Unknown JSX element <${type as string}>`)
+  const flat = flattenChildren(children)
+  if (component === text) {
+    const parts = flat as PropValue<string | number>[]
+    const content = parts.length === 1 ? parts[0] : { get value() { return parts.map(part => String(unrefValue(part))).join('') } }
+    return text({ ...props, children: content } as TextProps)
+  }
+  const result = flat.length === 0 ? component({ ...props }) : component({ ...props, children: flat.map(toMountable) })
+  return isThenable(result) ? deferred(result) : result
+}
+
+/**
+ * Fragment - `<>...</>`: mounts its children in place, without a wrapper
+ */
+export function Fragment(props: { children?: Mountable<GpuiHost>[] }): Mountable<GpuiHost> {
+  return (host: GpuiHost) => {
+    const unmounts = (props.children ?? []).map(child => child(host))
+    return () => unmounts.forEach(unmount => typeof unmount === 'function' && unmount())
+  }
+}
+
+/**
+ * show - Mounts `render()` while `cond` is truthy, else `fallback()` or an
+ * empty placeholder
+ *
+ * The placeholder holds the child's position, so the siblings after it keep
+ * their element IDs, and with them their state, as `cond` toggles. Unlike
+ * `{cond && <div />}`, which shifts them.
+ */
+export function show(cond: PropValue<unknown>, render: () => Mountable<GpuiHost>, fallback?: () => Mountable<GpuiHost>): Mountable<GpuiHost> {
+  return (host: GpuiHost) => {
+    if (unrefValue(cond)) return render()(host)
+    if (fallback) return fallback()(host)
+    host.appendChild({ type: 'empty', class: '' })
+    return () => {}
+  }
+}
+
+/** Alias of `show` */
+export const when = show
+
+/**
+ * each - Mounts `render(item, index)` for every item, keyed by `key(item)`
+ *
+ * A row's `render` runs again only when a new item takes its key, so rows
+ * keep their setup-phase state across renders. Each row's element is given
+ * its key, so a re-render moves rows that moved and adds or removes only the
+ * ones whose keys came or went.
+ */
+export function each<T>(
+  items: PropValue<readonly T[]>,
+  render: (item: T, index: number) => Mountable<GpuiHost>,
+  key: (item: T, index: number) => string | number,
+): Mountable<GpuiHost> {
+  // Row mount functions by key, from the last render
+  let rows = new Map<string, { item: T; mount: Mountable<GpuiHost> }>()
+  return (host: GpuiHost) => {
+    const next = new Map<string, { item: T; mount: Mountable<GpuiHost> }>()
+    const unmounts = (unrefValue(items) || []).map((item, index) => {
+      const rowKey = String(key(item, index))
+      let row = rows.get(rowKey)
+      if (!row || row.item !== item) row = { item, mount: render(item, index) }
+      next.set(rowKey, row)
+      const rowHost: GpuiHost = {
+        ...host,
+        appendChild(element: ElementDescriptor) {
+          if (element.key === undefined) element.key = rowKey
+          host.appendChild(element)
+        },
+      }
+      return row.mount(rowHost)
+    })
+    rows = next
+    return () => unmounts.forEach(unmount => typeof unmount === 'function' && unmount())
+  }
+}
+
+/**
+ * Whether `value` is a promise, or any thenable: a promise from another
+ * realm or library isn't an `instanceof Promise`
+ */
+function isThenable<T>(value: T | PromiseLike<T>): value is PromiseLike<T> {
+  return typeof (value as PromiseLike<T> | null)?.then === 'function'
+}
+
+/** Mount function of a component whose setup is still running */
+type PendingMountable = Mountable<GpuiHost> & { pending(): boolean }
+
+/**
+ * Mount function for an async setup: mounts `fallback` (or an empty
+ * placeholder) until `setup` resolves, then what it resolved to
+ *
+ * A rejection is thrown when mounting, so it shows like a render error.
+ */
+function deferred(setup: PromiseLike<Mountable<GpuiHost>>, fallback?: Mountable<GpuiHost>): PendingMountable {
+  let resolved: Mountable<GpuiHost> | null = null
+  let failed: { error: unknown } | null = null
+  setup.then(
+    mount => {
+      resolved = mount
+      requestRender()
+    },
+    error => {
+      failed = { error }
+      requestRender()
+    },
+  )
+  const mountable = ((host: GpuiHost) => {
+    if (failed) throw failed.error
+    if (resolved) return resolved(host)
+    if (fallback) return fallback(host)
+    host.appendChild({ type: 'empty', class: '' })
+    return () => {}
+  }) as PendingMountable
+  mountable.pending = () => !resolved && !failed
+  return mountable
+}
+
+/**
+ * suspense - Runs a component's setup, which may be async, showing
+ * `fallback` while it's pending
+ *
+ * Call it in the setup phase, so the setup runs once:
+ * `suspense(() => UserCard({ id }), text({ children: 'Loading…' }))`.
+ */
+export function suspense(
+  setup: () => Mountable<GpuiHost> | Promise<Mountable<GpuiHost>>,
+  fallback?: Mountable<GpuiHost>,
+): Mountable<GpuiHost> {
+  const result = setup()
+  return isThenable(result) ? deferred(result, fallback) : result
+}
+
+/**
+ * Suspense - `<Suspense fallback={...}>`: shows `fallback` until every async
+ * component among its children has resolved
+ */
+export function Suspense(props: { fallback?: Mountable<GpuiHost>; children?: Mountable<GpuiHost>[] }): Mountable<GpuiHost> {
+  const children = props.children ?? []
+  const mountChildren = Fragment({ children })
+  return (host: GpuiHost) => {
+    const pending = children.some(child => 'pending' in child && (child as PendingMountable).pending())
+    if (!pending) return mountChildren(host)
+    if (props.fallback) return props.fallback(host)
+    host.appendChild({ type: 'empty', class: '' })
+    return () => {}
+  }
+}
+
+// Renders so far, and whether the current one mounts memos in full
+let renderCount = 0
+let fullRender = false
+let memoCounter = 0
+
+/**
+ * memo - Mounts `mountable` again only when one of `deps` changed since the
+ * last render, and otherwise has the native side keep what it rendered
+ *
+ * `deps` are refs or values, or a function returning them, compared with
+ * `Object.is`. Refs read inside `mountable` but not listed don't update it.
+ * Its elements are keyed by the memo unless they have a key of their own.
+ */
+export function memo(mountable: Mountable<GpuiHost>, deps: PropValue<unknown>[] | (() => unknown[])): Mountable<GpuiHost> {
+  const id = `memo${++memoCounter}`
+  let last: { values: unknown[]; keys: string[]; render: number } | null = null
+  return (host: GpuiHost) => {
+    const values = typeof deps === 'function' ? deps() : deps.map(dep => unrefValue(dep))
+    // Skipping relies on the last render being on screen
+    if (
+      last && !fullRender && last.render === renderCount - 1 &&
+      values.length === last.values.length && values.every((value, i) => Object.is(value, last!.values[i]))
+    ) {
+      last.render = renderCount
+      for (const key of last.keys) host.appendChild({ type: 'skip', class: '', key })
+      return () => {}
+    }
+    const keys: string[] = []
+    const memoHost: GpuiHost = {
+      ...host,
+      appendChild(element: ElementDescriptor) {
+        if (element.key === undefined) element.key = keys.length === 0 ? id : `${id}:${keys.length}`
+        keys.push(element.key)
+        host.appendChild(element)
+      },
+    }
+    const unmount = mountable(memoHost)
+    last = { values, keys, render: renderCount }
+    return unmount
+  }
+}
+
+declare global {
+  // eslint-disable-next-line @typescript-eslint/no-namespace
+  namespace JSX {
+    type Element = Mountable<GpuiHost>
+    interface ElementChildrenAttribute {
+      children: object
+    }
+    interface IntrinsicElements {
+      div: Omit<DivProps, 'children'> & { children?: JsxChild }
+      text: Omit<TextProps, 'children'> & { children?: JsxChild }
+      button: Omit<DivProps, 'children'> & { children?: JsxChild }
+      img: ImgProps
+      camera: CameraProps
+      shader: ShaderProps
+      keymap: Omit<KeymapProps, 'children'> & { children?: JsxChild }
+      sortableList: SortableListProps<any>
+      selectable: SelectableProps<any>
+      dragHandle: Omit<DivProps, 'children'> & { children?: JsxChild }
+      colorPicker: ColorPickerProps
+      datePicker: DatePickerProps
+      tree: TreeProps
+      filePicker: FilePickerProps
+    }
+  }
+}
+/* eslint-enable @typescript-eslint/no-explicit-any */
+
 // ============ App Runner ============
 
 export type GpuiApp = Mountable<GpuiHost>
@@ -204,6 +1171,57 @@ export type GpuiApp = Mountable<GpuiHost>
 let __mountFn: GpuiApp | null = null
 let __unmountFn: (() => void) | null = null
 
+/**
+ * Watches what a window's last render read (see `track`)
+ */
+interface Tracking {
+  stop: (() => void) | null
+}
+const __appTracking: Tracking = { stop: null }
+
+/**
+ * Run `render`, watching what it reads through the reactive runtime: the
+ * first change to it tells the native side state changed, so handlers that
+ * change nothing don't re-render, and requests a re-render, wherever the
+ * write came from. Stops watching the previous render of `tracking`.
+ *
+ * Without a reactive runtime (see `setReactiveRuntime`) writes can't be
+ * seen, and every handler re-renders.
+ */
+function track(tracking: Tracking, render: () => void): void {
+  const g = globalThis as unknown as Record<string, () => void>
+  tracking.stop?.()
+  tracking.stop = null
+  let runtime: ReactiveRuntime
+  try {
+    runtime = getReactiveRuntime()
+  } catch {
+    g.__stateUntracked()
+    render()
+    return
+  }
+  // The watch runs `render` once; on a change it runs again reading
+  // nothing, so only the first change after a render is reported
+  let rendering = true
+  let failed = false
+  let error: unknown
+  tracking.stop = runtime.watch(() => {
+    if (!rendering) return {}
+    rendering = false
+    try {
+      render()
+    } catch (e) {
+      failed = true
+      error = e
+    }
+    return null
+  }, () => {
+    g.__stateChanged()
+    requestRender()
+  })
+  if (failed) throw error
+}
+
 /**
  * Create a minimal host for collecting element descriptors
  */
@@ -227,10 +1245,19 @@ function createHost(): GpuiHost & { getElements(): ElementDescriptor[] } {
 }
 
 /**
- * Re-render function - called by native runtime on state changes
+ * Re-render function - called by native runtime on state changes, with
+ * `full` to mount memos whatever their deps
  */
-function __rerender(): ElementDescriptor | null {
-  if (!__mountFn) return null
+function __rerender(full = false): ElementDescriptor | null {
+  // eslint-disable-next-line @typescript-eslint/no-explicit-any
+  const g = globalThis as unknown as Record<string, unknown>
+  
+  // Still waiting for an async App: keep its placeholder
+  if (!__mountFn) return (g.__rootElement as ElementDescriptor | null) ?? null
+  
+  // `full` when the native side has no tree for memos to skip against
+  renderCount++
+  fullRender = full
   
   // Call previous unmount if exists
   if (__unmountFn) {
@@ -239,31 +1266,725 @@ function __rerender(): ElementDescriptor | null {
   
   // Create fresh host and mount
   const rootHost = createHost()
-  const result = __mountFn(rootHost)
-  __unmountFn = typeof result === 'function' ? result : null
+  const mount = __mountFn
+  track(__appTracking, () => {
+    const result = mount(rootHost)
+    __unmountFn = typeof result === 'function' ? result : null
+  })
   
   const elements = rootHost.getElements()
   const rootElement = elements[0] || null
   
   // Store globally for native runtime
-  // eslint-disable-next-line @typescript-eslint/no-explicit-any
-  const g = globalThis as unknown as Record<string, unknown>
   g.__rootElement = rootElement
   
   return rootElement
 }
 
+/**
+ * requestRender - Render again on the next frame
+ *
+ * Event handlers, timers and native callbacks already re-render after they
+ * run. Call this when state changes elsewhere, e.g. from an effect of your
+ * reactivity library.
+ */
+export function requestRender(): void {
+  const request = (globalThis as unknown as Record<string, unknown>).__requestRender
+  if (typeof request === 'function') request()
+}
+
+/**
+ * nextTick - Run `callback` after the next re-render, which it requests
+ *
+ * The promise resolves after the callback, so `await nextTick()` waits for
+ * the view to show earlier changes.
+ */
+export function nextTick(callback?: () => void): Promise<void> {
+  const tick = (globalThis as unknown as Record<string, unknown>).__nextTick as (callback?: () => void) => Promise<void>
+  return tick(callback)
+}
+
+/**
+ * onError - Handle errors thrown by event handlers and renders
+ *
+ * `source` says where the error came from: `'handler 12'`, `'async handler
+ * 12'` or `'render'`. Handlers added later run first, until one returns
+ * `true`; an error none takes shows on the error overlay (async handlers
+ * are only logged). Returns a function removing the handler.
+ */
+export function onError(handler: (error: unknown, source: string) => boolean | void): () => void {
+  const register = (globalThis as unknown as Record<string, unknown>).__onError as (
+    handler: (error: unknown, source: string) => boolean | void,
+  ) => () => void
+  return register(handler)
+}
+
+// ============ Stores ============
+
+/**
+ * Shared state with subscriptions, created with `createStore`
+ */
+export interface Store<T> {
+  /** The current state */
+  get(): T
+  /** Replace the state, or derive the next one from it */
+  set(update: T | ((state: T) => T)): void
+  /**
+   * Call `listener(state, previous)` after every change, or with a
+   * selector, `listener(selected, previousSelected)` when the selected value
+   * changes; returns a function that unsubscribes
+   */
+  subscribe(listener: (state: T, previous: T) => void): () => void
+  subscribe<S>(selector: (state: T) => S, listener: (selected: S, previous: S) => void): () => void
+  /** A ref reading `selector(state)`, to pass as a prop */
+  select<S>(selector: (state: T) => S): ReadonlyRef<S>
+}
+
+/**
+ * createStore - State shared by components, handlers and native callbacks
+ *
+ * Changes re-render the view on the next frame, whatever reactivity library
+ * the app uses. Treat the state as immutable: `set` a new object rather than
+ * mutating the current one.
+ */
+export function createStore<T>(initial: T): Store<T> {
+  let state = initial
+  const listeners = new Set<(state: T, previous: T) => void>()
+  
+  const subscribe = (selectorOrListener: (state: T, previous?: T) => unknown, listener?: (selected: unknown, previous: unknown) => void) => {
+    let wrapped: (state: T, previous: T) => void
+    if (listener) {
+      const selector = selectorOrListener as (state: T) => unknown
+      wrapped = (next, previous) => {
+        const selected = selector(next)
+        const before = selector(previous)
+        if (!Object.is(selected, before)) listener(selected, before)
+      }
+    } else {
+      wrapped = selectorOrListener as (state: T, previous: T) => void
+    }
+    listeners.add(wrapped)
+    return () => { listeners.delete(wrapped) }
+  }
+  
+  return {
+    get: () => state,
+    set(update) {
+      const previous = state
+      state = typeof update === 'function' ? (update as (state: T) => T)(state) : update
+      if (Object.is(state, previous)) return
+      for (const listener of [...listeners]) {
+        try {
+          listener(state, previous)
+        } catch (e) {
+          console.error('Error in store listener:', e)
+        }
+      }
+      requestRender()
+    },
+    subscribe: subscribe as Store<T>['subscribe'],
+    select: <S>(selector: (state: T) => S) => ({
+      get value() { return selector(state) },
+    }),
+  }
+}
+
 // Make __rerender globally accessible
 // eslint-disable-next-line @typescript-eslint/no-explicit-any
 ;(globalThis as unknown as Record<string, unknown>).__rerender = __rerender
 
+/**
+ * Options of the app window, passed to `run`
+ */
+export interface WindowOptions {
+  /** Title bar text; the app name from rasen.config.js by default */
+  title?: string
+  /** Content size in logical pixels, 800×600 by default */
+  width?: number
+  height?: number
+  /** Smallest size the user can resize the window to */
+  minSize?: { width: number; height: number }
+  /** Whether the user can resize the window (default true) */
+  resizable?: boolean
+  /** Open centered on its display (default true), or at its top-left corner */
+  centered?: boolean
+  /** ID of the display to open on, from `rasen.screen.getDisplays()`; the main display by default */
+  display?: number
+  /** `rasen.window.open` only: save the window in the session under this name, for `rasen.session.restore` */
+  name?: string
+  /** `rasen.window.open` only: route the window starts at, passed to its component */
+  route?: string | null
+}
+
+/**
+ * The window a `rasen.window.open` component renders into
+ */
+export interface WindowInfo {
+  id: number
+  /** The `route` option it opened with */
+  route: string | null
+}
+
+/** A component `rasen.window.open` renders in a window of its own */
+export type WindowComponent = (window: WindowInfo) => GpuiApp | Promise<GpuiApp>
+
 /**
  * run - Start a GPUI application
+ *
+ * `App` may be async; the window shows an empty root until it resolves.
+ * `options` set up the window, which opens once the script has run.
  */
-export function run(App: () => GpuiApp): void {
+export function run(App: () => GpuiApp | Promise<GpuiApp>, options?: WindowOptions): void {
+  ;(globalThis as unknown as Record<string, unknown>).__windowOptions = options ?? null
   // Get the mount function from App
-  __mountFn = App()
+  const app = App()
+  
+  if (isThenable(app)) {
+    // The native side drains promise jobs and reports a rejection
+    // eslint-disable-next-line @typescript-eslint/no-explicit-any
+    const g = globalThis as unknown as Record<string, unknown>
+    const placeholder: ElementDescriptor = { type: 'div', class: 'size-full' }
+    g.__rootElement = placeholder
+    g.__pendingApp = app.then(mount => {
+      __mountFn = mount
+      g.__pendingApp = null
+    })
+    return
+  }
+  __mountFn = app
   
   // Initial render
   __rerender()
 }
+
+// Windows opened with `rasen.window.open`, by ID. Render counts are per
+// window, so memos only compare against their own window's renders.
+interface MountedWindow {
+  mount: GpuiApp | null
+  unmount: (() => void) | null
+  root: ElementDescriptor | null
+  renders: number
+  tracking: Tracking
+}
+const __windows = new Map<number, MountedWindow>()
+
+/**
+ * Run a window component's setup, when `rasen.window.open` opens it
+ */
+function __mountWindow(id: number, Component: WindowComponent, route: string | null): void {
+  const app = Component({ id, route })
+  const win: MountedWindow = { mount: null, unmount: null, root: null, renders: 0, tracking: { stop: null } }
+  __windows.set(id, win)
+  if (isThenable(app)) {
+    win.root = { type: 'div', class: 'size-full' }
+    const report = (globalThis as unknown as Record<string, unknown>).__reportError as (error: unknown, source: string) => void
+    app.then(mount => {
+      win.mount = mount
+      requestRender()
+    }, error => report(error, `window ${id}`))
+    return
+  }
+  win.mount = app
+}
+
+/**
+ * Re-render a window's component, like `__rerender` does the app's
+ */
+function __rerenderWindow(id: number, full = false): ElementDescriptor | null {
+  const win = __windows.get(id)
+  if (!win?.mount) return win?.root ?? null
+  const renders = renderCount
+  renderCount = ++win.renders
+  fullRender = full
+  try {
+    if (win.unmount) win.unmount()
+    const rootHost = createHost()
+    const mount = win.mount
+    track(win.tracking, () => {
+      const result = mount(rootHost)
+      win.unmount = typeof result === 'function' ? result : null
+    })
+    win.root = rootHost.getElements()[0] || null
+    return win.root
+  } finally {
+    renderCount = renders
+  }
+}
+
+/**
+ * Unmount the component of a window that closed
+ */
+function __windowClosed(id: number): void {
+  const win = __windows.get(id)
+  if (!win) return
+  __windows.delete(id)
+  if (win.unmount) win.unmount()
+  win.tracking.stop?.()
+  win.tracking.stop = null
+}
+
+;(globalThis as unknown as Record<string, unknown>).__mountWindow = __mountWindow
+;(globalThis as unknown as Record<string, unknown>).__rerenderWindow = __rerenderWindow
+;(globalThis as unknown as Record<string, unknown>).__windowClosed = __windowClosed
+
+// ============ Native APIs ============
+
+/**
+ * Optional platform features that can be feature-detected; `file-dialogs`
+ * is whether `rasen.dialog` can show dialogs
+ */
+export type PlatformCapability =
+  | 'file-dialogs'
+  | 'tray'
+  | 'menu-bar'
+  | 'global-shortcuts'
+  | 'window-vibrancy'
+  | 'camera'
+  | 'eyedropper'
+  | 'shaders'
+
+export interface RasenPlatform {
+  /** Current platform: 'macos', 'windows', 'linux' or 'unknown' */
+  readonly name: string
+  /** Whether the current platform supports a capability */
+  supports(capability: PlatformCapability): boolean
+  /** Whether the user asked the OS for more contrast */
+  prefersHighContrast(): boolean
+  /** Listen for the contrast setting changing; returns a function removing the listener */
+  on(event: 'high-contrast-change', listener: (highContrast: boolean) => void): () => void
+}
+
+/**
+ * Hooks for test harnesses driving the app with `rasen-gpui run --automation`
+ */
+export interface RasenAutomation {
+  /**
+   * Make a ref assignable by `setRef { name, value }` requests when the app
+   * runs with `rasen-gpui run --automation`
+   */
+  expose<T>(name: string, target: Ref<T>): void
+}
+
+export interface SessionWindow {
+  /** Name the window was opened under; `null` for the main window */
+  name: string | null
+  /** Route last reported with `setRoute`, if any */
+  route: string | null
+}
+
+export interface RasenSession {
+  /**
+   * Windows of the previous run, the main window first; reopens the others,
+   * once, with the component of `components` under their name, at their
+   * bounds and route
+   */
+  restore(components?: Record<string, WindowComponent>): SessionWindow[]
+  /** Record the current route of a window, the main window by default */
+  setRoute(route: string | null, windowId?: number): void
+  /** Route recorded for a window, the main window by default */
+  getRoute(windowId?: number): string | null
+}
+
+/**
+ * History for a router that starts at a window's saved route and records
+ * its changes in the session, so they come back with `rasen.session.restore`
+ *
+ * Pass the `WindowInfo` a `rasen.window.open` component gets; the main
+ * window's by default.
+ */
+export function createSessionHistory(window?: WindowInfo, initialPath = '/') {
+  const windowId = window?.id ?? 0
+  const listeners = new Set<(path: string) => void>()
+  const stack = [(window ? window.route : rasen.session.getRoute()) ?? initialPath]
+  let index = 0
+  const moved = () => {
+    const path = stack[index]
+    rasen.session.setRoute(path, windowId)
+    listeners.forEach(listener => listener(path))
+  }
+  rasen.session.setRoute(stack[0], windowId)
+  return {
+    getPath: () => stack[index],
+    push(path: string) {
+      stack.splice(index + 1)
+      stack.push(path)
+      index++
+      moved()
+    },
+    replace(path: string) {
+      stack[index] = path
+      moved()
+    },
+    go(n: number) {
+      if (index + n < 0 || index + n >= stack.length) return
+      index += n
+      moved()
+    },
+    subscribe(listener: (path: string) => void) {
+      listeners.add(listener)
+      return () => {
+        listeners.delete(listener)
+      }
+    },
+  }
+}
+
+/**
+ * Key-value storage persisted between launches, like `localStorage`
+ */
+export interface RasenStorage {
+  readonly length: number
+  getItem(key: string): string | null
+  /** Throws a `RangeError` beyond 5 MB of keys and values */
+  setItem(key: string, value: string): void
+  removeItem(key: string): void
+  clear(): void
+  /** The `index`th key, in key order */
+  key(index: number): string | null
+}
+
+export interface RasenActivity {
+  /** Call `callback` once the user has been inactive for `ms`; returns an unsubscribe function */
+  onIdle(ms: number, callback: () => void): () => void
+  /** Call `callback` on the first input after a minute without any, or the shortest `onIdle` timeout; returns an unsubscribe function */
+  onActive(callback: () => void): () => void
+}
+
+export type PageSize = 'A3' | 'A4' | 'A5' | 'letter' | 'legal'
+
+export interface PdfOptions {
+  /** Page size, `A4` by default */
+  pageSize?: PageSize
+  /** Tree to lay out instead of the current root */
+  tree?: ElementDescriptor
+}
+
+/**
+ * A window opened with `rasen.window.open`
+ */
+export interface WindowHandle {
+  id: number
+  /** Close the window, unmounting its component */
+  close(): void
+  /** Enter or leave fullscreen */
+  setFullscreen(on: boolean): void
+}
+
+export interface RasenWindow {
+  /**
+   * Open another window rendering `Component`, which shares the app's state;
+   * its setup runs right away, and the window opens after the current handler
+   */
+  open(Component: WindowComponent, options?: WindowOptions): WindowHandle
+  /** Enter or leave fullscreen in the main window */
+  setFullscreen(on: boolean): void
+  /** Lay out the tree for paged output and write it to `path` as a PDF */
+  exportPdf(options: PdfOptions & { path: string }): void
+  /** Lay out the tree for paged output and send it to the default printer */
+  print(options?: PdfOptions): void
+  /** Scale the rendered content like browser zoom (1 = 100%) */
+  setZoom(factor: number): void
+  /** Current zoom factor */
+  getZoom(): number
+}
+
+export interface UndoAction {
+  undo(): void
+  redo(): void
+}
+
+export interface RasenUndoStack {
+  /** Record an action that was just performed; clears the redo history */
+  push(action: UndoAction): void
+  /** Undo the most recent action; returns whether there was one */
+  undo(): boolean
+  /** Redo the most recently undone action; returns whether there was one */
+  redo(): boolean
+  canUndo(): boolean
+  canRedo(): boolean
+  clear(): void
+}
+
+export interface CollatorOptions {
+  /** Which differences count, as in `Intl.Collator`; `variant` by default */
+  sensitivity?: 'base' | 'accent' | 'case' | 'variant'
+  /** Compare digit runs by numeric value */
+  numeric?: boolean
+}
+
+export interface RasenCollator {
+  /** Resolved BCP 47 locale */
+  readonly locale: string
+  /** Negative, zero or positive, for `Array.prototype.sort` */
+  compare(a: string, b: string): number
+}
+
+export interface FormatDateOptions {
+  /** BCP 47 locale; the user's by default */
+  locale?: string
+  /** Length, as `Intl.DateTimeFormat`'s `dateStyle`; `medium` by default */
+  style?: 'short' | 'medium' | 'long' | 'full'
+}
+
+export interface DirEntry {
+  name: string
+  /** The listed directory joined with `name` */
+  path: string
+  /** True for directories and links to them */
+  isDirectory: boolean
+}
+
+export interface FileStat {
+  size: number
+  isFile: boolean
+  isDirectory: boolean
+  /** Last modification, in milliseconds since the Unix epoch */
+  mtimeMs: number
+}
+
+/**
+ * Asynchronous file access, limited to the `fs.allow` directories when set;
+ * failures reject with an `Error` with a Node-style `code` (`ENOENT`, ...)
+ */
+export interface RasenFs {
+  /** Contents of a UTF-8 text file */
+  readFile(path: string): Promise<string>
+  /** Create or replace a file with UTF-8 text */
+  writeFile(path: string, data: string): Promise<void>
+  /** Entries of a directory, in no particular order */
+  readdir(path: string): Promise<DirEntry[]>
+  /** Follows symlinks */
+  stat(path: string): Promise<FileStat>
+  /** With `recursive`, also create missing parents and accept existing directories */
+  mkdir(path: string, options?: { recursive?: boolean }): Promise<void>
+  /** Delete a file or empty directory; with `recursive`, a directory and its contents */
+  remove(path: string, options?: { recursive?: boolean }): Promise<void>
+}
+
+export interface BusMessageInfo {
+  topic: string
+  /** Process-wide sequence number; later messages have larger numbers */
+  seq: number
+}
+
+export interface RasenBus {
+  /**
+   * Send `payload` (copied as JSON) to every listener for `topic` in the app,
+   * including this runtime's; returns the message's sequence number
+   */
+  emit(topic: string, payload?: unknown): number
+  /** Listen for messages on `topic`; returns an unsubscribe function */
+  on<T = unknown>(topic: string, callback: (payload: T, info: BusMessageInfo) => void): () => void
+}
+
+/**
+ * Point conversions for the element measured by ref `id` (see `elementRef`)
+ */
+/**
+ * An image kept natively; pass it to `img({ src })` and `release` it when
+ * it's no longer needed
+ */
+export interface ImageHandle {
+  readonly id: number
+  readonly width: number
+  readonly height: number
+}
+
+/**
+ * Image processing on background threads; failures reject like `rasen.fs`
+ */
+export interface RasenImage {
+  /** Decode PNG, JPEG, GIF (first frame), WebP or BMP data */
+  decode(bytes: ArrayBuffer | Uint8Array): Promise<ImageHandle>
+  /** Read and decode an image file */
+  open(path: string): Promise<ImageHandle>
+  /** Scale to `width` x `height`, or to `width` keeping the aspect ratio */
+  resize(image: ImageHandle, width: number, height?: number): Promise<ImageHandle>
+  /** Shrink an image or image file to fit in a `size` square */
+  thumbnail(source: ImageHandle | string, size: number): Promise<ImageHandle>
+  /** Free an image; returns whether it was still kept */
+  release(image: ImageHandle): boolean
+}
+
+export interface CameraDevice {
+  /** What `camera({ deviceId })` and `snapshot` take */
+  deviceId: string
+  label: string
+}
+
+/**
+ * Webcam capture; check `rasen.platform.supports('camera')` first
+ */
+export interface RasenCamera {
+  /** PNG bytes of the next frame of a device, the default one if left out */
+  snapshot(deviceId?: string): Promise<Uint8Array>
+  /** The capture devices connected */
+  list(): CameraDevice[]
+}
+
+export interface SpawnOptions {
+  /** Working directory of the process; the app's by default */
+  cwd?: string
+  /** Variables added to the app's environment */
+  env?: Record<string, string>
+  /** Called with output text as it arrives */
+  onStdout?: (text: string) => void
+  onStderr?: (text: string) => void
+  /** Called after all output, with the exit code, or the signal that ended the process */
+  onExit?: (code: number | null, signal: string | null) => void
+}
+
+export interface ChildProcess {
+  readonly pid: number
+  /** Stop the process; returns whether it was still running */
+  kill(): boolean
+}
+
+/**
+ * Child processes with streamed output; disabled in hardened mode
+ */
+export interface RasenProcess {
+  /** Start `command`; throws with a `code` such as `ENOENT` if it can't */
+  spawn(command: string, args?: string[], options?: SpawnOptions): ChildProcess
+}
+
+export interface NotifyOptions {
+  title: string
+  body?: string
+  /** Icon file path, or an icon theme name on Linux */
+  icon?: string
+  /** Called when the notification is clicked; Linux only */
+  onClick?: () => void | Promise<void>
+}
+
+export interface FileDialogOptions {
+  title?: string
+  /** Only show files with these extensions */
+  filters?: FileFilter[]
+  /** Directory to start in; for `save`, a path or file name to suggest */
+  defaultPath?: string
+}
+
+export interface OpenDialogOptions extends FileDialogOptions {
+  /** Pick several; resolves with an array */
+  multiple?: boolean
+  /** Pick directories instead of files */
+  directory?: boolean
+}
+
+export interface MessageDialogOptions {
+  title?: string
+  message: string
+  level?: 'info' | 'warning' | 'error'
+  /** Up to 3 labels, the first being the default; just `OK` by default */
+  buttons?: string[]
+}
+
+/**
+ * Native file and message dialogs; promises resolve with `null` when cancelled
+ */
+export interface RasenDialog {
+  open(options?: OpenDialogOptions & { multiple?: false }): Promise<string | null>
+  open(options: OpenDialogOptions & { multiple: true }): Promise<string[] | null>
+  save(options?: FileDialogOptions): Promise<string | null>
+  /** Resolves with the label of the clicked button */
+  message(options: MessageDialogOptions): Promise<string>
+}
+
+/**
+ * Hand URLs and files to other apps; both throw with a `code` such as
+ * `ENOENT` or `EPERM`
+ */
+export interface RasenShell {
+  /** Open a URL in the browser, or a file in its default app */
+  open(target: string): void
+  /** Open the file manager with `path` selected */
+  showInFolder(path: string): void
+}
+
+/**
+ * A connected display, with bounds on the desktop in logical pixels
+ */
+export interface Display {
+  /** What the `display` window option takes */
+  id: number
+  x: number
+  y: number
+  width: number
+  height: number
+  /** Device pixels per logical pixel; `null` until one of the app's windows has been on it */
+  scaleFactor: number | null
+  primary: boolean
+}
+
+export interface RasenScreen {
+  getDisplays(): Display[]
+}
+
+/**
+ * What a `before-quit` listener gets
+ */
+export interface BeforeQuitEvent {
+  /** Keep the app running */
+  preventDefault(): void
+}
+
+export interface RasenApp {
+  /**
+   * Run before the app quits; a listener can cancel, or return a promise to
+   * defer quitting until it settles (10 seconds at most)
+   */
+  on(event: 'before-quit', listener: (event: BeforeQuitEvent) => void | Promise<void>): () => void
+  /** Run as the app exits */
+  on(event: 'quit', listener: () => void): () => void
+  /** Run when a later launch of a single-instance app hands over its arguments */
+  on(event: 'second-instance', listener: (args: string[], cwd: string) => void): () => void
+  /** Quit, once the `before-quit` listeners agree */
+  quit(): void
+}
+
+export interface RasenLayout {
+  toLocal(id: string, x: number, y: number): Point | null
+  toScreen(id: string, x: number, y: number): Point | null
+  fromWindow(id: string, x: number, y: number): Point | null
+  toWindow(id: string, x: number, y: number): Point | null
+  bounds(id: string): Bounds | null
+  scrollIntoView(id: string): void
+}
+
+/**
+ * Native APIs provided by the rasen-gpui runtime on `globalThis.rasen`
+ */
+export interface RasenNative {
+  platform: RasenPlatform
+  app: RasenApp
+  automation: RasenAutomation
+  session: RasenSession
+  /** Also available as `localStorage` */
+  storage: RasenStorage
+  activity: RasenActivity
+  window: RasenWindow
+  screen: RasenScreen
+  undoStack: RasenUndoStack
+  /** Locale-aware string comparison; `locale` defaults to the system locale */
+  collator(locale?: string, options?: CollatorOptions): RasenCollator
+  /** Format a `YYYY-MM-DD` date for display */
+  formatDate(value: string, options?: FormatDateOptions): string
+  fs: RasenFs
+  image: RasenImage
+  camera: RasenCamera
+  layout: RasenLayout
+  process: RasenProcess
+  bus: RasenBus
+  /** Post a native OS notification */
+  notify(options: NotifyOptions): void
+  dialog: RasenDialog
+  shell: RasenShell
+  /** Collect garbage now; returns the bytes the JS heap uses afterwards */
+  gc(): number
+}
+
+declare global {
+  // eslint-disable-next-line no-var
+  var rasen: RasenNative
+}